@@ -0,0 +1,57 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use klock_core::audit::{AuditLog, AuditSink, DecisionRecord};
+use klock_core::types::{EventEnvelope, EventPayload};
+
+/// Keeps decision records for GET /admin/audit and, optionally, appends
+/// them to a file as JSON lines, one event envelope each, so they outlive
+/// the process.
+pub struct ServerAuditSink {
+    log: Arc<AuditLog>,
+    file: Option<Mutex<File>>,
+}
+
+impl ServerAuditSink {
+    pub fn new(log: Arc<AuditLog>, path: Option<&str>) -> Result<Self, String> {
+        let file = match path {
+            Some(path) => Some(Mutex::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("Cannot open audit log '{}': {}", path, e))?,
+            )),
+            None => None,
+        };
+        Ok(Self { log, file })
+    }
+}
+
+impl AuditSink for ServerAuditSink {
+    fn record(&self, record: &DecisionRecord) {
+        self.log.record(record);
+        let Some(file) = &self.file else {
+            return;
+        };
+        let recorded_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let envelope =
+            EventEnvelope::new(EventPayload::SchedulerDecision(record.clone()), recorded_at);
+        let mut line = match serde_json::to_vec(&envelope) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("Cannot encode decision record: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+        let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_all(&line) {
+            tracing::warn!("Cannot write to the audit log: {}", e);
+        }
+    }
+}
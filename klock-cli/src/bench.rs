@@ -0,0 +1,236 @@
+//! Synthetic storage benchmark behind `klock bench-storage`.
+//!
+//! Runs the same seeded workload of acquires, releases and heartbeats
+//! against each backend in-process, on a simulated clock that advances one
+//! millisecond per operation so leases expire and get evicted as they would
+//! under load.
+
+use std::time::{Duration, Instant};
+
+use klock_core::client::{open_store, LeaseStoreExt};
+use klock_core::types::{LeaseResult, LeaseState, Predicate, ResourceRef, ResourceType};
+
+/// Simulated start time of every run
+const START: u64 = 1_000_000;
+
+/// Operations between eviction passes
+const EVICT_EVERY: u64 = 1000;
+
+/// Shape of the synthetic workload
+pub struct Workload {
+    pub agents: usize,
+    pub ops: u64,
+    /// Resources the agents contend for
+    pub resources: usize,
+    /// TTL of acquired leases, in simulated ms
+    pub ttl: u64,
+    pub seed: u64,
+}
+
+/// How one backend fared
+pub struct BenchResult {
+    pub backend: String,
+    pub ops_per_sec: f64,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub granted: u64,
+    pub refused: u64,
+    pub eviction_passes: u64,
+    pub evicted: u64,
+    /// Mean time of an eviction pass, including granting the waiters it
+    /// unblocked
+    pub eviction_mean: Duration,
+}
+
+/// Deterministic xorshift generator, so every backend sees the same
+/// sequence of operations
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+/// Parse an operation count such as "5000", "100k" or "2m"
+pub fn parse_count(value: &str) -> Result<u64, String> {
+    let lower = value.trim().to_ascii_lowercase();
+    let (digits, scale) = match lower.strip_suffix('k') {
+        Some(digits) => (digits, 1_000),
+        None => match lower.strip_suffix('m') {
+            Some(digits) => (digits, 1_000_000),
+            None => (lower.as_str(), 1),
+        },
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|count| count.checked_mul(scale))
+        .filter(|count| *count > 0)
+        .ok_or_else(|| format!("'{}' is not a count like 5000, 100k or 2m", value))
+}
+
+/// Run `workload` against the backend named by `spec`. A SQLite database
+/// must not exist yet; it is created for the run and removed afterwards.
+pub fn run(spec: &str, workload: &Workload) -> Result<BenchResult, String> {
+    let path = spec.strip_prefix("sqlite:");
+    if let Some(path) = path {
+        if std::path::Path::new(path).exists() {
+            return Err(format!(
+                "'{}' already exists; benchmark against a fresh database path",
+                path
+            ));
+        }
+    }
+    let store = open_store(spec)?;
+    let result = measure(spec, store, workload);
+    if let Some(path) = path {
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+    Ok(result)
+}
+
+fn measure(
+    spec: &str,
+    mut store: Box<dyn LeaseStoreExt + Send>,
+    workload: &Workload,
+) -> BenchResult {
+    let agents: Vec<String> = (0..workload.agents)
+        .map(|i| format!("bench-agent-{}", i))
+        .collect();
+    let resources: Vec<ResourceRef> = (0..workload.resources)
+        .map(|i| ResourceRef::new(ResourceType::File, format!("/bench/file_{}.rs", i)))
+        .collect();
+    for (i, agent) in agents.iter().enumerate() {
+        store.register_agent_priority(agent.clone(), (START + i as u64).into());
+    }
+
+    let mut rng = Rng(workload.seed.max(1));
+    let mut held: Vec<String> = Vec::new();
+    let mut latencies: Vec<Duration> = Vec::with_capacity(workload.ops as usize);
+    let mut eviction_time = Duration::ZERO;
+    let mut result = BenchResult {
+        backend: spec.to_string(),
+        ops_per_sec: 0.0,
+        p50: Duration::ZERO,
+        p99: Duration::ZERO,
+        granted: 0,
+        refused: 0,
+        eviction_passes: 0,
+        evicted: 0,
+        eviction_mean: Duration::ZERO,
+    };
+
+    let started = Instant::now();
+    for op in 0..workload.ops {
+        let now = START + op;
+        let roll = rng.below(100);
+        let agent = &agents[rng.below(agents.len())];
+        let resource = &resources[rng.below(resources.len())];
+        let target = (!held.is_empty()).then(|| rng.below(held.len()));
+
+        let timer = Instant::now();
+        match target {
+            Some(index) if roll >= 80 => {
+                store.heartbeat(&held[index], now);
+            }
+            Some(index) if roll >= 50 => {
+                let lease_id = held.swap_remove(index);
+                if store.release(&lease_id) {
+                    held.extend(store.grant_waiters(now).into_iter().map(|l| l.id));
+                }
+            }
+            _ => {
+                let predicate = if roll.is_multiple_of(4) {
+                    Predicate::Consumes
+                } else {
+                    Predicate::Mutates
+                };
+                let session = format!("{}-session", agent);
+                match store.acquire(
+                    agent,
+                    &session,
+                    resource.clone(),
+                    predicate,
+                    workload.ttl,
+                    now,
+                ) {
+                    LeaseResult::Success { lease } => {
+                        result.granted += 1;
+                        held.push(lease.id);
+                    }
+                    LeaseResult::Failure { .. } => result.refused += 1,
+                }
+            }
+        }
+        latencies.push(timer.elapsed());
+
+        if (op + 1) % EVICT_EVERY == 0 {
+            let timer = Instant::now();
+            result.evicted += store.evict_expired(now) as u64;
+            let granted = store.grant_waiters(now);
+            eviction_time += timer.elapsed();
+            result.eviction_passes += 1;
+            held.extend(granted.into_iter().map(|l| l.id));
+            held.retain(|id| {
+                store
+                    .get_lease(id)
+                    .is_some_and(|lease| lease.state == LeaseState::Active)
+            });
+        }
+    }
+    let elapsed = started.elapsed() - eviction_time;
+
+    latencies.sort_unstable();
+    let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+    result.ops_per_sec = workload.ops as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+    result.p50 = percentile(50);
+    result.p99 = percentile(99);
+    if result.eviction_passes > 0 {
+        result.eviction_mean = eviction_time / result.eviction_passes as u32;
+    }
+    result
+}
+
+/// Print one row per backend
+pub fn print_table(results: &[BenchResult]) {
+    let width = results
+        .iter()
+        .map(|r| r.backend.len())
+        .max()
+        .unwrap_or(0)
+        .max("backend".len());
+    println!(
+        "{:<width$}  {:>12}  {:>10}  {:>10}  {:>9}  {:>9}  {:>8}  {:>15}",
+        "backend",
+        "ops/sec",
+        "p50 (µs)",
+        "p99 (µs)",
+        "granted",
+        "refused",
+        "evicted",
+        "evict pass (µs)",
+    );
+    for r in results {
+        println!(
+            "{:<width$}  {:>12.0}  {:>10.1}  {:>10.1}  {:>9}  {:>9}  {:>8}  {:>15.1}",
+            r.backend,
+            r.ops_per_sec,
+            micros(r.p50),
+            micros(r.p99),
+            r.granted,
+            r.refused,
+            r.evicted,
+            micros(r.eviction_mean),
+        );
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
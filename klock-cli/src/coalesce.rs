@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+/// Runs identical concurrent requests once: while a request for a key is in
+/// flight, duplicates await its result instead of repeating the work.
+pub struct Coalescer<K, V> {
+    in_flight: std::sync::Mutex<HashMap<K, watch::Receiver<Option<V>>>>,
+    executed: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+/// How many requests a `Coalescer` ran, and how many shared another's result
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CoalesceStats {
+    pub executed: u64,
+    pub coalesced: u64,
+}
+
+/// Forgets the leader's request when it finishes or is cancelled, so later
+/// requests start afresh
+struct InFlight<'a, K: Eq + Hash, V> {
+    coalescer: &'a Coalescer<K, V>,
+    key: K,
+    receiver: watch::Receiver<Option<V>>,
+}
+
+impl<K: Eq + Hash, V> Drop for InFlight<'_, K, V> {
+    fn drop(&mut self) {
+        let mut in_flight = self
+            .coalescer
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if in_flight
+            .get(&self.key)
+            .is_some_and(|pending| pending.same_channel(&self.receiver))
+        {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Coalescer<K, V> {
+    pub fn new() -> Self {
+        Self {
+            in_flight: std::sync::Mutex::new(HashMap::new()),
+            executed: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    /// Run `request`, or wait for the identical one already in flight and
+    /// return a copy of its result. If that one is cancelled (its client
+    /// went away), the duplicate runs `request` itself.
+    pub async fn run<F, Fut>(&self, key: K, request: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        let pending = self.lock().get(&key).cloned();
+        if let Some(mut pending) = pending {
+            let shared = match pending.wait_for(Option::is_some).await {
+                Ok(result) => result.clone(),
+                Err(_) => None,
+            };
+            if let Some(result) = shared {
+                self.coalesced.fetch_add(1, Ordering::Relaxed);
+                return result;
+            }
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        // Another duplicate may have taken the lead meanwhile; it is
+        // replaced, and the two simply run separately
+        self.lock().insert(key.clone(), receiver.clone());
+        let guard = InFlight {
+            coalescer: self,
+            key,
+            receiver,
+        };
+        self.executed.fetch_add(1, Ordering::Relaxed);
+        let result = request().await;
+        drop(guard);
+        sender.send_replace(Some(result.clone()));
+        result
+    }
+
+    /// Requests run and requests that shared a result, since startup
+    pub fn stats(&self) -> CoalesceStats {
+        CoalesceStats {
+            executed: self.executed.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<K, watch::Receiver<Option<V>>>> {
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Default for Coalescer<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
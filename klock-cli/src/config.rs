@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use klock_core::ceiling::PriorityCeiling;
+use klock_core::conflict::{CompatibilityMatrix, ConflictEngine};
+use klock_core::cosign::{CosignRegistry, CosignRule};
+use klock_core::fallback::FallbackGroups;
+use klock_core::implication::ImplicationRule;
+use klock_core::maintenance::MaintenanceWindow;
+use klock_core::namespace::NamespaceBridges;
+use klock_core::refinement::SymbolRefinement;
+use klock_core::template::ManifestTemplate;
+use klock_core::types::{Confidence, ResourceType};
+
+/// The config files `klock serve` reads at startup and again on every
+/// reload (`POST /admin/reload` or SIGHUP).
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFiles {
+    /// Custom predicate compatibility matrix (see `CompatibilityMatrix`)
+    pub conflict_matrix: Option<String>,
+    /// Compatibility matrices for individual resource types
+    pub type_matrices: Option<String>,
+    /// Implication rules, or "standard" for the built-in set
+    pub implication_rules: Option<String>,
+    /// Symbol containment map (see `ContainmentRegistry`)
+    pub containment: Option<String>,
+    /// Resource equivalence map (see `EquivalenceMap`)
+    pub equivalences: Option<String>,
+    /// Resources shared across namespaces (see `NamespaceBridges`); isolates
+    /// namespaces
+    pub namespace_bridges: Option<String>,
+    /// Not a file; isolates namespaces even without bridges
+    pub isolate_namespaces: bool,
+    /// Not a file; applied to the conflict engine built from the files above
+    pub tentative_confidence: Option<Confidence>,
+    /// Not a file either; also applied to the conflict engine
+    pub symbol_refinement: Option<SymbolRefinement>,
+    /// Manifest templates to register
+    pub templates: Option<String>,
+    /// Maintenance windows to enforce
+    pub maintenance_windows: Option<String>,
+    /// Priority ceilings of critical resources
+    pub priority_ceilings: Option<String>,
+    /// Groups of interchangeable resources to suggest with Die verdicts
+    pub fallback_groups: Option<String>,
+    /// Operations that need a second agent's approval
+    pub cosign_rules: Option<String>,
+}
+
+/// Everything built from the config files, plus the contents of each file
+/// so a reload can tell what changed.
+#[derive(Debug, Clone, Default)]
+pub struct LoadedConfig {
+    pub conflict_engine: ConflictEngine,
+    pub templates: Vec<ManifestTemplate>,
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    pub priority_ceilings: Vec<PriorityCeiling>,
+    pub fallback_groups: FallbackGroups,
+    pub cosign_rules: Vec<CosignRule>,
+    contents: BTreeMap<&'static str, String>,
+}
+
+/// What a reload changed
+#[derive(Debug, Default, Serialize)]
+pub struct ConfigDiff {
+    /// Config files whose contents changed, by option name (e.g.
+    /// "conflict_matrix"); a file that was added or removed counts too
+    pub changed: Vec<String>,
+    pub templates: NameDiff,
+    pub maintenance_windows: NameDiff,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct NameDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub updated: Vec<String>,
+}
+
+impl ConfigFiles {
+    /// Read and validate every configured file. Nothing is applied here, so
+    /// a bad file leaves the running configuration untouched.
+    pub fn load(&self) -> Result<LoadedConfig, String> {
+        let mut contents = BTreeMap::new();
+
+        let matrix: Option<CompatibilityMatrix> = load_json(
+            &mut contents,
+            "conflict_matrix",
+            "conflict matrix",
+            self.conflict_matrix.as_deref(),
+        )?;
+        let mut engine = match matrix {
+            Some(matrix) => ConflictEngine::with_matrix(matrix)?,
+            None => ConflictEngine::default(),
+        };
+
+        let type_matrices: Option<HashMap<ResourceType, CompatibilityMatrix>> = load_json(
+            &mut contents,
+            "type_matrices",
+            "type matrices",
+            self.type_matrices.as_deref(),
+        )?;
+        for (resource_type, matrix) in type_matrices.unwrap_or_default() {
+            engine = engine.with_type_matrix(resource_type, matrix)?;
+        }
+
+        let rules: Vec<ImplicationRule> = match self.implication_rules.as_deref() {
+            Some("standard") => {
+                contents.insert("implication_rules", "standard".to_string());
+                ImplicationRule::standard()
+            }
+            path => load_json(
+                &mut contents,
+                "implication_rules",
+                "implication rules",
+                path,
+            )?
+            .unwrap_or_default(),
+        };
+        for rule in rules {
+            engine = engine.with_implication(rule);
+        }
+
+        if let Some(containment) = load_json(
+            &mut contents,
+            "containment",
+            "containment map",
+            self.containment.as_deref(),
+        )? {
+            engine = engine.with_containment(containment);
+        }
+
+        if let Some(equivalences) = load_json(
+            &mut contents,
+            "equivalences",
+            "equivalence map",
+            self.equivalences.as_deref(),
+        )? {
+            engine = engine.with_equivalences(equivalences);
+        }
+
+        let bridges: Option<NamespaceBridges> = load_json(
+            &mut contents,
+            "namespace_bridges",
+            "namespace bridges",
+            self.namespace_bridges.as_deref(),
+        )?;
+        if bridges.is_some() || self.isolate_namespaces {
+            engine = engine.with_isolated_namespaces(bridges.unwrap_or_default());
+        }
+
+        let templates: Vec<ManifestTemplate> = load_json(
+            &mut contents,
+            "templates",
+            "templates",
+            self.templates.as_deref(),
+        )?
+        .unwrap_or_default();
+        for template in &templates {
+            template.validate()?;
+        }
+
+        let maintenance_windows: Vec<MaintenanceWindow> = load_json(
+            &mut contents,
+            "maintenance_windows",
+            "maintenance windows",
+            self.maintenance_windows.as_deref(),
+        )?
+        .unwrap_or_default();
+        for window in &maintenance_windows {
+            window.validate()?;
+        }
+
+        let priority_ceilings: Vec<PriorityCeiling> = load_json(
+            &mut contents,
+            "priority_ceilings",
+            "priority ceilings",
+            self.priority_ceilings.as_deref(),
+        )?
+        .unwrap_or_default();
+        for ceiling in &priority_ceilings {
+            ceiling.validate()?;
+        }
+
+        let fallback_groups: FallbackGroups = load_json(
+            &mut contents,
+            "fallback_groups",
+            "fallback groups",
+            self.fallback_groups.as_deref(),
+        )?
+        .unwrap_or_default();
+
+        let cosign_rules: Vec<CosignRule> = load_json(
+            &mut contents,
+            "cosign_rules",
+            "co-signing rules",
+            self.cosign_rules.as_deref(),
+        )?
+        .unwrap_or_default();
+        CosignRegistry::default().set_rules(cosign_rules.clone())?;
+
+        Ok(LoadedConfig {
+            conflict_engine: engine
+                .with_tentative_confidence(self.tentative_confidence)
+                .with_symbol_refinement(self.symbol_refinement.clone()),
+            templates,
+            maintenance_windows,
+            priority_ceilings,
+            fallback_groups,
+            cosign_rules,
+            contents,
+        })
+    }
+}
+
+impl LoadedConfig {
+    /// What changes when `next` replaces this configuration
+    pub fn diff(&self, next: &LoadedConfig) -> ConfigDiff {
+        let mut changed: Vec<String> = self
+            .contents
+            .keys()
+            .chain(next.contents.keys())
+            .filter(|key| self.contents.get(*key) != next.contents.get(*key))
+            .map(|key| key.to_string())
+            .collect();
+        changed.sort();
+        changed.dedup();
+
+        ConfigDiff {
+            changed,
+            templates: diff_by_name(&self.templates, &next.templates, |t| &t.name),
+            maintenance_windows: diff_by_name(
+                &self.maintenance_windows,
+                &next.maintenance_windows,
+                |w| &w.name,
+            ),
+        }
+    }
+}
+
+/// Read `path` (if configured) as JSON, remembering its contents under `key`.
+fn load_json<T: DeserializeOwned>(
+    contents: &mut BTreeMap<&'static str, String>,
+    key: &'static str,
+    what: &str,
+    path: Option<&str>,
+) -> Result<Option<T>, String> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {} '{}': {}", what, path, e))?;
+    let value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid {} '{}': {}", what, path, e))?;
+    contents.insert(key, text);
+    Ok(Some(value))
+}
+
+fn diff_by_name<T: PartialEq>(old: &[T], new: &[T], name: impl Fn(&T) -> &String) -> NameDiff {
+    let old: BTreeMap<&String, &T> = old.iter().map(|item| (name(item), item)).collect();
+    let new: BTreeMap<&String, &T> = new.iter().map(|item| (name(item), item)).collect();
+    let mut diff = NameDiff::default();
+    for (key, item) in &new {
+        match old.get(key) {
+            None => diff.added.push(key.to_string()),
+            Some(previous) if previous != item => diff.updated.push(key.to_string()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .keys()
+        .filter(|key| !new.contains_key(*key))
+        .map(|key| key.to_string())
+        .collect();
+    diff
+}
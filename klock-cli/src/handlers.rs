@@ -1,5 +1,30 @@
 use serde::{Deserialize, Serialize};
 
+use crate::coalesce::CoalesceStats;
+
+use klock_core::activity::{ActivityKind, TimelineQuery};
+use klock_core::alias::RenameAlias;
+use klock_core::audit::AuditQuery;
+use klock_core::backoff::BackoffPolicy;
+use klock_core::capacity::CapacityReport;
+use klock_core::ceiling::{CeilingAssignment, PriorityCeiling};
+use klock_core::client::{parse_confidence, parse_resource_type};
+use klock_core::codes::ReasonCode;
+use klock_core::deadlock::WaitForEdge;
+use klock_core::fair_share::FairSharePolicy;
+use klock_core::infrastructure_mirror::MirrorReport;
+use klock_core::maintenance::ScheduledWindow;
+use klock_core::preemption::PreemptionPolicy;
+use klock_core::registry::{PriorityClass, UnregisteredAgentPolicy};
+use klock_core::release::ReleaseCondition;
+use klock_core::scheduler::VerdictStatus;
+use klock_core::skew::ClockReport;
+use klock_core::template::ManifestTemplate;
+use klock_core::types::normalize::PathNormalization;
+use klock_core::types::{ResourceRef, ResourceType};
+use klock_core::usage::AgentLabels;
+use std::collections::BTreeMap;
+
 // ─── Validation Constants ───────────────────────────────────────────────────
 
 const VALID_PREDICATES: &[&str] = &[
@@ -9,6 +34,7 @@ const VALID_PREDICATES: &[&str] = &[
     "DELETES",
     "DEPENDS_ON",
     "RENAMES",
+    "EXCLUDES",
 ];
 
 const VALID_RESOURCE_TYPES: &[&str] = &[
@@ -45,37 +71,64 @@ pub fn validate_resource_type(resource_type: &str) -> Result<(), String> {
     }
 }
 
+/// Rejects paths that escape the workspace root of `paths`
+pub fn validate_resource_path(
+    paths: &PathNormalization,
+    resource_type: &str,
+    path: &str,
+) -> Result<(), String> {
+    paths
+        .try_apply(&parse_resource_type(resource_type), path)
+        .map(|_| ())
+}
+
 // ─── Request Types ──────────────────────────────────────────────────────────
 
 #[derive(Deserialize)]
 pub struct RegisterAgentRequest {
     pub agent_id: String,
     pub priority: u64,
+    /// `critical`, `normal` (the default) or `background`; the class
+    /// dominates, `priority` orders agents within it
+    #[serde(default)]
+    pub class: PriorityClass,
+    /// Labels usage reports group by, e.g. `{"team": "payments"}`;
+    /// registering again replaces them
+    #[serde(default)]
+    pub labels: AgentLabels,
+}
+
+#[derive(Deserialize)]
+pub struct UpdatePriorityRequest {
+    pub priority: u64,
 }
 
 #[derive(Deserialize)]
 pub struct AcquireLeaseRequest {
     pub agent_id: String,
+    /// Omit to use the agent's default session
+    #[serde(default)]
     pub session_id: String,
     pub resource_type: String,
     pub resource_path: String,
     pub predicate: String,
     pub ttl: u64,
+    /// When the work must be done by (ms since epoch); breaks priority ties
+    #[serde(default)]
+    pub deadline: Option<u64>,
 }
 
 impl AcquireLeaseRequest {
-    pub fn validate(&self) -> Result<(), String> {
+    pub fn validate(&self, paths: &PathNormalization) -> Result<(), String> {
         if self.agent_id.is_empty() {
             return Err("agent_id is required".to_string());
         }
-        if self.session_id.is_empty() {
-            return Err("session_id is required".to_string());
-        }
         if self.resource_path.is_empty() {
             return Err("resource_path is required".to_string());
         }
         validate_predicate(&self.predicate)?;
         validate_resource_type(&self.resource_type)?;
+        validate_resource_path(paths, &self.resource_type, &self.resource_path)?;
         if self.ttl == 0 {
             return Err("ttl must be greater than 0".to_string());
         }
@@ -84,32 +137,143 @@ impl AcquireLeaseRequest {
 }
 
 #[derive(Deserialize)]
-pub struct ReleaseLeaseRequest {
-    pub lease_id: String,
+pub struct ReserveLeasesRequest {
+    pub agent_id: String,
+    /// Omit to use the agent's default session
+    #[serde(default)]
+    pub session_id: String,
+    pub resources: Vec<IntentItem>,
+    pub ttl: u64,
+    pub activate_by: u64,
+}
+
+impl ReserveLeasesRequest {
+    pub fn validate(&self, paths: &PathNormalization) -> Result<(), String> {
+        if self.agent_id.is_empty() {
+            return Err("agent_id is required".to_string());
+        }
+        if self.resources.is_empty() {
+            return Err("resources must not be empty".to_string());
+        }
+        for (i, item) in self.resources.iter().enumerate() {
+            validate_predicate(&item.predicate).map_err(|e| format!("resources[{}]: {}", i, e))?;
+            validate_resource_type(&item.resource_type)
+                .and_then(|_| {
+                    validate_resource_path(paths, &item.resource_type, &item.resource_path)
+                })
+                .map_err(|e| format!("resources[{}]: {}", i, e))?;
+        }
+        if self.ttl == 0 {
+            return Err("ttl must be greater than 0".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize)]
 pub struct DeclareIntentRequest {
+    /// Omit to use the agent's default session
+    #[serde(default)]
     pub session_id: String,
     pub agent_id: String,
     pub intents: Vec<IntentItem>,
 }
 
 impl DeclareIntentRequest {
-    pub fn validate(&self) -> Result<(), String> {
+    pub fn validate(&self, paths: &PathNormalization) -> Result<(), String> {
         if self.agent_id.is_empty() {
             return Err("agent_id is required".to_string());
         }
-        if self.session_id.is_empty() {
-            return Err("session_id is required".to_string());
-        }
         if self.intents.is_empty() {
             return Err("intents must not be empty".to_string());
         }
         for (i, intent) in self.intents.iter().enumerate() {
             validate_predicate(&intent.predicate).map_err(|e| format!("intents[{}]: {}", i, e))?;
             validate_resource_type(&intent.resource_type)
+                .and_then(|_| {
+                    validate_resource_path(paths, &intent.resource_type, &intent.resource_path)
+                })
                 .map_err(|e| format!("intents[{}]: {}", i, e))?;
+            if let Some(confidence) = &intent.confidence {
+                if parse_confidence(confidence).is_none() {
+                    return Err(format!(
+                        "intents[{}]: Invalid confidence '{}'. Must be one of: HIGH, MEDIUM, LOW",
+                        i, confidence
+                    ));
+                }
+            }
+            if let Some(renamed_to) = &intent.renamed_to {
+                if !intent.predicate.eq_ignore_ascii_case("RENAMES") {
+                    return Err(format!(
+                        "intents[{}]: renamed_to is only valid for RENAMES",
+                        i
+                    ));
+                }
+                let resource_type = parse_resource_type(&intent.resource_type);
+                paths
+                    .try_apply(&resource_type, renamed_to)
+                    .and_then(|renamed_to| {
+                        RenameAlias::validate(
+                            &ResourceRef::new(resource_type.clone(), &intent.resource_path)
+                                .normalized(paths),
+                            &ResourceRef::new(resource_type, renamed_to),
+                        )
+                    })
+                    .map_err(|e| format!("intents[{}]: {}", i, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Leases to release all or nothing, if the conditions hold
+#[derive(Deserialize)]
+pub struct ReleaseManyRequest {
+    pub lease_ids: Vec<String>,
+    #[serde(default)]
+    pub conditions: Vec<ReleaseCondition>,
+}
+
+/// A manifest to declare and lease in one step
+#[derive(Deserialize)]
+pub struct AcquireManifestRequest {
+    #[serde(flatten)]
+    pub manifest: DeclareIntentRequest,
+    /// TTL of every lease, in ms
+    pub ttl: u64,
+}
+
+impl AcquireManifestRequest {
+    pub fn validate(&self, paths: &PathNormalization) -> Result<(), String> {
+        self.manifest.validate(paths)?;
+        if self.ttl == 0 {
+            return Err("ttl must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Manifests of one agent to declare all or nothing
+#[derive(Deserialize)]
+pub struct AtomicIntentRequest {
+    pub manifests: Vec<DeclareIntentRequest>,
+}
+
+impl AtomicIntentRequest {
+    pub fn validate(&self, paths: &PathNormalization) -> Result<(), String> {
+        let Some(first) = self.manifests.first() else {
+            return Err("manifests must not be empty".to_string());
+        };
+        for (i, manifest) in self.manifests.iter().enumerate() {
+            manifest
+                .validate(paths)
+                .map_err(|e| format!("manifests[{}]: {}", i, e))?;
+            if manifest.agent_id != first.agent_id {
+                return Err(format!(
+                    "manifests[{}]: every manifest must have agent_id '{}'",
+                    i, first.agent_id
+                ));
+            }
         }
         Ok(())
     }
@@ -120,6 +284,358 @@ pub struct IntentItem {
     pub predicate: String,
     pub resource_type: String,
     pub resource_path: String,
+    /// New path, for RENAMES intents
+    #[serde(default)]
+    pub renamed_to: Option<String>,
+    /// HIGH (default), MEDIUM or LOW
+    #[serde(default)]
+    pub confidence: Option<String>,
+    /// Keep the intent after its session releases the leases covering it
+    #[serde(default)]
+    pub long_lived: bool,
+}
+
+#[derive(Deserialize)]
+pub struct TemplateIntentRequest {
+    /// Omit to use the agent's default session
+    #[serde(default)]
+    pub session_id: String,
+    pub agent_id: String,
+    /// Name of a registered template, or a template given inline
+    pub template: TemplateSpec,
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum TemplateSpec {
+    Name(String),
+    Inline(ManifestTemplate),
+}
+
+impl TemplateIntentRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.agent_id.is_empty() {
+            return Err("agent_id is required".to_string());
+        }
+        match &self.template {
+            TemplateSpec::Name(name) if name.is_empty() => {
+                Err("template must not be empty".to_string())
+            }
+            TemplateSpec::Name(_) => Ok(()),
+            TemplateSpec::Inline(template) => template.validate(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProjectionQuery {
+    /// Timestamp (ms since epoch) to project to
+    pub at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct HoldersQuery {
+    /// Past instant (ms since epoch) to look up the holders at
+    pub at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateBarrierRequest {
+    pub name: String,
+    pub parties: usize,
+}
+
+#[derive(Deserialize)]
+pub struct BeginSessionRequest {
+    pub agent_id: String,
+    /// Omitted, the server picks one
+    #[serde(default)]
+    pub session_id: String,
+    /// End the session after this many ms without use
+    pub ttl_ms: u64,
+}
+
+#[derive(Deserialize)]
+pub struct DeclareNamespaceRequest {
+    pub name: String,
+    /// Reap the namespace this many ms from now; omitted, only on delete
+    pub expires_in: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct ArriveRequest {
+    pub agent_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct BarrierWaitQuery {
+    /// How long to block waiting for the barrier to trip (ms)
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct AcquireSemaphoreRequest {
+    pub agent_id: String,
+    /// Total permits of the semaphore; fixed by whoever declares it first
+    pub permits: usize,
+    pub ttl: u64,
+}
+
+#[derive(Deserialize)]
+pub struct QuarantineRequest {
+    pub resource_type: String,
+    /// Path, directory or glob pattern to freeze
+    pub resource_path: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Lift the quarantine automatically after this many ms
+    #[serde(default)]
+    pub ttl: Option<u64>,
+}
+
+impl QuarantineRequest {
+    pub fn validate(&self, paths: &PathNormalization) -> Result<(), String> {
+        validate_resource_type(&self.resource_type)?;
+        if self.resource_path.is_empty() {
+            return Err("resource_path is required".to_string());
+        }
+        validate_resource_path(paths, &self.resource_type, &self.resource_path)?;
+        if self.ttl == Some(0) {
+            return Err("ttl must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct QuarantineQuery {
+    pub resource_type: String,
+    pub resource_path: String,
+}
+
+/// A verdict for the scheduler to force (debug builds with the `testing`
+/// feature only)
+#[cfg(all(feature = "testing", debug_assertions))]
+#[derive(Deserialize)]
+pub struct InjectFaultRequest {
+    /// The requesting agent; any agent when omitted
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    #[serde(default)]
+    pub resource_type: Option<String>,
+    /// Path, directory or glob pattern; any resource when omitted
+    #[serde(default)]
+    pub resource_path: Option<String>,
+    /// `WAIT` or `DIE`
+    pub verdict: String,
+    /// How many matching decisions to force
+    #[serde(default = "default_fault_times")]
+    pub times: usize,
+}
+
+#[cfg(all(feature = "testing", debug_assertions))]
+fn default_fault_times() -> usize {
+    1
+}
+
+#[cfg(all(feature = "testing", debug_assertions))]
+impl InjectFaultRequest {
+    pub fn into_fault(self) -> Result<klock_core::fault::InjectedFault, String> {
+        let verdict = match self.verdict.to_uppercase().as_str() {
+            "WAIT" => VerdictStatus::Wait,
+            "DIE" => VerdictStatus::Die,
+            _ => {
+                return Err(format!(
+                    "Invalid verdict '{}'. Must be one of: WAIT, DIE",
+                    self.verdict
+                ))
+            }
+        };
+        let resource = match (self.resource_type, self.resource_path) {
+            (Some(resource_type), Some(path)) if !path.is_empty() => {
+                validate_resource_type(&resource_type)?;
+                Some(ResourceRef::new(parse_resource_type(&resource_type), &path))
+            }
+            (None, None) => None,
+            _ => return Err("resource_type and resource_path must be given together".to_string()),
+        };
+        Ok(klock_core::fault::InjectedFault {
+            agent_id: self.agent_id,
+            resource,
+            verdict,
+            times: self.times,
+        })
+    }
+}
+
+/// Selects the session whose intents are withdrawn
+#[derive(Deserialize)]
+pub struct WithdrawIntentsQuery {
+    pub agent_id: String,
+    pub session_id: String,
+}
+
+/// Selects an agent's queued requests for a resource
+#[derive(Deserialize)]
+pub struct CancelWaitersQuery {
+    pub agent_id: String,
+    pub resource_type: String,
+    pub resource_path: String,
+}
+
+#[derive(Deserialize)]
+pub struct FirehoseQuery {
+    /// Last sequence already processed; 0 reads from the oldest kept
+    #[serde(default)]
+    pub cursor: u64,
+    /// Most entries to return (default 500, at most 5000)
+    pub limit: Option<usize>,
+    /// How long to block waiting for new entries (ms)
+    pub wait_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct UsageReportQuery {
+    /// Label to group by, or `agent` (the default)
+    pub group_by: Option<String>,
+    /// How far back to report, e.g. `24h` (the default) or `7d`
+    pub period: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    /// Session whose feed to drain; the agent's default session if omitted
+    #[serde(default)]
+    pub session_id: String,
+}
+
+/// Query parameters of lease release
+#[derive(Deserialize)]
+pub struct RevisionParams {
+    /// Refuse the change with 409 unless the lease is still at this
+    /// revision
+    pub expected_revision: Option<u64>,
+}
+
+/// Query parameters of a heartbeat
+#[derive(Deserialize)]
+pub struct HeartbeatParams {
+    /// Refuse the heartbeat with 409 unless the lease is still at this
+    /// revision
+    pub expected_revision: Option<u64>,
+    /// The client's clock (ms since the epoch), to report drift against
+    pub client_time: Option<u64>,
+}
+
+/// Query parameters of an operator's revocation
+#[derive(Deserialize)]
+pub struct RevokeParams {
+    /// Refuse the revocation with 409 unless the lease is still at this
+    /// revision
+    pub expected_revision: Option<u64>,
+    /// Recorded on the lease and sent to its holder
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CosignLeaseRequest {
+    /// The co-signer approving the request
+    pub agent_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct PreemptRequest {
+    /// The senior agent taking the lease
+    pub agent_id: String,
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct TimelineParams {
+    /// Only entries at or after this time (ms since epoch)
+    pub since: Option<u64>,
+    /// Only entries before this time (ms since epoch)
+    pub until: Option<u64>,
+    pub kind: Option<ActivityKind>,
+    /// Only entries touching resources overlapping this one; needs
+    /// `resource_path`
+    pub resource_type: Option<String>,
+    pub resource_path: Option<String>,
+    /// Return at most the latest `limit` entries
+    pub limit: Option<usize>,
+}
+
+impl TimelineParams {
+    pub fn to_query(&self) -> Result<TimelineQuery, String> {
+        let resource = match (&self.resource_type, &self.resource_path) {
+            (None, None) => None,
+            (Some(resource_type), Some(path)) => {
+                validate_resource_type(resource_type)?;
+                Some(ResourceRef::new(parse_resource_type(resource_type), path))
+            }
+            _ => return Err("resource_type and resource_path must be given together".to_string()),
+        };
+        Ok(TimelineQuery {
+            since: self.since,
+            until: self.until,
+            kind: self.kind,
+            resource,
+            limit: self.limit,
+        })
+    }
+}
+
+/// Filters for GET /admin/audit
+#[derive(Deserialize)]
+pub struct AuditParams {
+    pub agent_id: Option<String>,
+    /// Only decisions at or after this time (ms since epoch)
+    pub since: Option<u64>,
+    /// Only decisions before this time (ms since epoch)
+    pub until: Option<u64>,
+    /// `GRANTED`, `WAIT` or `DIE`
+    pub verdict: Option<String>,
+    /// Only decisions on resources overlapping this one; needs
+    /// `resource_path`
+    pub resource_type: Option<String>,
+    pub resource_path: Option<String>,
+    /// Return at most the latest `limit` decisions (default 100)
+    pub limit: Option<usize>,
+}
+
+impl AuditParams {
+    pub fn to_query(&self) -> Result<AuditQuery, String> {
+        let verdict = match self.verdict.as_deref().map(str::to_uppercase).as_deref() {
+            None => None,
+            Some("GRANTED") => Some(VerdictStatus::Granted),
+            Some("WAIT") => Some(VerdictStatus::Wait),
+            Some("DIE") => Some(VerdictStatus::Die),
+            Some(_) => {
+                return Err(format!(
+                    "Invalid verdict '{}'. Must be one of: GRANTED, WAIT, DIE",
+                    self.verdict.as_deref().unwrap_or_default()
+                ))
+            }
+        };
+        let resource = match (&self.resource_type, &self.resource_path) {
+            (None, None) => None,
+            (Some(resource_type), Some(path)) => {
+                validate_resource_type(resource_type)?;
+                Some(ResourceRef::new(parse_resource_type(resource_type), path))
+            }
+            _ => return Err("resource_type and resource_path must be given together".to_string()),
+        };
+        Ok(AuditQuery {
+            agent_id: self.agent_id.clone(),
+            since: self.since,
+            until: self.until,
+            verdict,
+            resource,
+            limit: Some(self.limit.unwrap_or(100)),
+        })
+    }
 }
 
 // ─── Response Types ─────────────────────────────────────────────────────────
@@ -131,6 +647,9 @@ pub struct ApiResponse<T: Serialize> {
     pub data: Option<T>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Catalog code of the error (see `klock explain`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ReasonCode>,
 }
 
 impl<T: Serialize> ApiResponse<T> {
@@ -139,39 +658,39 @@ impl<T: Serialize> ApiResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            code: None,
         }
     }
 
-    pub fn err(msg: impl Into<String>) -> Self {
+    pub fn err(code: ReasonCode, msg: impl Into<String>) -> Self {
         Self {
             success: false,
             data: None,
             error: Some(msg.into()),
+            code: Some(code),
         }
     }
 }
 
 #[derive(Serialize)]
-pub struct LeaseResponse {
-    pub lease_id: String,
+pub struct ActiveLeaseInfo {
+    pub id: String,
     pub agent_id: String,
     pub resource: String,
+    pub predicate: String,
     pub expires_at: u64,
+    pub revision: u64,
+    /// Less than the warning threshold of the TTL is left
+    pub expiring_soon: bool,
 }
 
 #[derive(Serialize)]
-pub struct LeaseFailureResponse {
-    pub reason: String,
-    pub wait_time: Option<u64>,
-}
-
-#[derive(Serialize)]
-pub struct ActiveLeaseInfo {
-    pub id: String,
-    pub agent_id: String,
+pub struct ReservationInfo {
+    pub lease_id: String,
+    pub session_id: String,
     pub resource: String,
     pub predicate: String,
-    pub expires_at: u64,
+    pub activate_by: u64,
 }
 
 #[derive(Serialize)]
@@ -179,15 +698,110 @@ pub struct EvictResponse {
     pub evicted: usize,
 }
 
+/// Counters for `GET /admin/metrics`
+#[derive(Serialize)]
+pub struct MetricsResponse {
+    /// Lease acquisitions run, and duplicates answered with an identical
+    /// in-flight request's result
+    pub acquire_coalescing: CoalesceStats,
+}
+
+/// `GET /state/wait-for`
+#[derive(Serialize)]
+pub struct WaitForResponse {
+    pub edges: Vec<WaitForEdge>,
+    /// A cycle of agents each waiting on the next, if there is one
+    pub deadlock: Option<Vec<WaitForEdge>>,
+}
+
+#[derive(Serialize)]
+pub struct CeilingsResponse {
+    pub ceilings: Vec<PriorityCeiling>,
+    /// Agents currently running at a ceiling
+    pub assignments: Vec<CeilingAssignment>,
+}
+
+#[derive(Serialize)]
+pub struct AgentInfo {
+    pub agent_id: String,
+    /// The agent's priority timestamp within its class
+    pub priority: Option<u64>,
+    pub class: Option<PriorityClass>,
+    /// How far priority aging currently improves `priority`
+    pub aging_boost: u64,
+    pub health: klock_core::health::AgentHealth,
+    #[serde(skip_serializing_if = "AgentLabels::is_empty")]
+    pub labels: AgentLabels,
+}
+
 #[derive(Serialize)]
 pub struct HealthResponse {
     pub status: String,
     pub active_leases: usize,
     pub version: String,
+    pub storage_ready: bool,
+    /// Dual-write progress, when mirroring to a second backend
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<MirrorReport>,
+    /// Load shedding state, when the server runs with capacity caps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<CapacityReport>,
+    /// Monotonic time and clock skew, when either is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock: Option<ClockReport>,
 }
 
+/// What this server accepts, for clients that adapt to it
 #[derive(Serialize)]
-pub struct HeartbeatResponse {
-    pub renewed: bool,
-    pub lease_id: String,
+pub struct CapabilitiesResponse {
+    pub version: String,
+    pub scheduler: String,
+    /// Resource types resolved by another policy than `scheduler`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub schedulers_by_type: BTreeMap<String, String>,
+    /// What happens to agents without a registered priority
+    pub unregistered_agents: UnregisteredAgentPolicy,
+    pub predicates: Vec<String>,
+    pub resource_types: Vec<String>,
+    /// The current or next opening of each maintenance window, soonest first
+    pub maintenance_windows: Vec<ScheduledWindow>,
+    /// Whether, and by whom, leases can be preempted
+    pub preemption: PreemptionPolicy,
+    /// How much each held lease costs an agent's priority
+    pub fair_share: FairSharePolicy,
+    /// How long Die verdicts ask agents to back off
+    pub backoff: BackoffPolicy,
+}
+
+impl CapabilitiesResponse {
+    pub fn new(
+        scheduler: &str,
+        schedulers_by_type: Vec<(ResourceType, &str)>,
+        unregistered_agents: UnregisteredAgentPolicy,
+        maintenance_windows: Vec<ScheduledWindow>,
+        preemption: PreemptionPolicy,
+        fair_share: FairSharePolicy,
+        backoff: BackoffPolicy,
+    ) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            scheduler: scheduler.to_string(),
+            schedulers_by_type: schedulers_by_type
+                .into_iter()
+                .map(|(resource_type, name)| (resource_type.to_string(), name.to_string()))
+                .collect(),
+            unregistered_agents,
+            predicates: VALID_PREDICATES.iter().map(|p| p.to_string()).collect(),
+            resource_types: VALID_RESOURCE_TYPES.iter().map(|t| t.to_string()).collect(),
+            maintenance_windows,
+            preemption,
+            fair_share,
+            backoff,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
 }
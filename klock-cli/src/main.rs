@@ -1,7 +1,28 @@
+mod audit;
+mod bench;
+mod coalesce;
+mod config;
 mod handlers;
+mod remote;
 mod server;
 
 use clap::{Parser, Subcommand};
+use config::ConfigFiles;
+use klock_core::backoff::BackoffPolicy;
+use klock_core::client::{open_store, parse_confidence, parse_predicate};
+use klock_core::codes::{codes, ReasonCode};
+use klock_core::conflict::{MatrixExplanation, MatrixTable};
+use klock_core::conformance::{standard_vectors, ConformanceVector};
+use klock_core::infrastructure_mirror::{copy_state, parity};
+use klock_core::policy_registry::parse_override;
+use klock_core::refinement::SymbolRefinement;
+use klock_core::registry::UnregisteredAgentPolicy;
+use klock_core::scheduler::{scheduler_by_name, Scheduler};
+use klock_core::summary::ConflictSummary;
+use klock_core::types::normalize::PathNormalization;
+use klock_core::types::{Confidence, Predicate, ResourceRef, ResourceType};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 #[derive(Parser)]
 #[command(
@@ -14,6 +35,8 @@ struct Cli {
     command: Commands,
 }
 
+// Parsed once at startup, so the size of `Serve` doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Start the Klock HTTP coordination server
@@ -29,15 +52,454 @@ enum Commands {
         /// Storage backend: "memory" or "sqlite:<path>"
         #[arg(long, default_value = "memory", env = "KLOCK_STORAGE")]
         storage: String,
+
+        /// Refuse to start if the storage backend is unavailable instead of
+        /// falling back to in-memory storage
+        #[arg(long, env = "KLOCK_STRICT_STORAGE")]
+        strict_storage: bool,
+
+        /// Seconds --strict-storage waits for the database to become available
+        #[arg(long, default_value = "30", env = "KLOCK_STORAGE_TIMEOUT_SECS")]
+        storage_timeout_secs: u64,
+
+        /// Deprioritize agents whose health score drops below this (0-100)
+        #[arg(long, env = "KLOCK_DEPRIORITIZE_BELOW")]
+        deprioritize_below: Option<u32>,
+
+        /// Quarantine agents whose health score drops below this (0-100)
+        #[arg(long, env = "KLOCK_QUARANTINE_BELOW")]
+        quarantine_below: Option<u32>,
+
+        /// Improve the priority of an agent by one step (a minute of
+        /// seniority) after every this many consecutive Die verdicts
+        #[arg(long, env = "KLOCK_AGE_AFTER_DENIALS")]
+        age_after_denials: Option<u32>,
+
+        /// Improve the priority of an agent by one step for every this many
+        /// milliseconds it keeps getting Die verdicts
+        #[arg(long, env = "KLOCK_AGE_AFTER_MS")]
+        age_after_ms: Option<u64>,
+
+        /// Let an agent revoke the leases of agents at least this much
+        /// younger (in priority units) through POST /leases/:id/preempt;
+        /// preemption is disabled when unset
+        #[arg(long, env = "KLOCK_PREEMPT_MIN_GAP")]
+        preempt_min_gap: Option<u64>,
+
+        /// Push an agent's priority back by this many milliseconds for each
+        /// active lease it holds when scheduling, so agents holding many
+        /// leases yield to those holding few; disabled when unset
+        #[arg(long, env = "KLOCK_FAIR_SHARE_PENALTY")]
+        fair_share_penalty: Option<u64>,
+
+        /// Warn a lease's session with a lease_expiring event once this
+        /// percentage of its TTL, or less, remains without a heartbeat
+        /// (0 disables)
+        #[arg(long, default_value = "20", env = "KLOCK_EXPIRY_WARNING_PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+        expiry_warning_percent: u8,
+
+        /// Refuse repeated acquires of a resource by the same agent within
+        /// this many milliseconds of a failed attempt (0 disables)
+        #[arg(long, default_value = "0", env = "KLOCK_MIN_ACQUIRE_INTERVAL_MS")]
+        min_acquire_interval_ms: u64,
+
+        /// How long an agent's default session (used when a request names
+        /// no session) outlives its last use
+        #[arg(long, default_value = "1800000", env = "KLOCK_DEFAULT_SESSION_TTL_MS")]
+        default_session_ttl_ms: u64,
+
+        /// Conflict resolution policy: wait-die, wound-wait, fifo or no-wait
+        #[arg(long, default_value = "wait-die", env = "KLOCK_SCHEDULER", value_parser = scheduler_by_name)]
+        scheduler: Arc<dyn Scheduler>,
+
+        /// Policy for one resource type instead of --scheduler, as
+        /// TYPE=policy, e.g. DATABASE_TABLE=no-wait (repeatable; policies:
+        /// wait-die, wound-wait, fifo or no-wait)
+        #[arg(long, env = "KLOCK_SCHEDULER_FOR", value_delimiter = ',', value_parser = parse_override)]
+        scheduler_for: Vec<(ResourceType, Arc<dyn Scheduler>)>,
+
+        /// What to do with agents that request or hold leases without a
+        /// registered priority: treat-as-lowest, auto-register (on their
+        /// first request) or reject
+        #[arg(
+            long,
+            default_value = "treat-as-lowest",
+            env = "KLOCK_UNREGISTERED_AGENTS"
+        )]
+        unregistered_agents: UnregisteredAgentPolicy,
+
+        /// Most leases held at once, active and reserved; acquires are shed
+        /// near it, lowest priority first (unlimited by default)
+        #[arg(long, env = "KLOCK_MAX_LEASES")]
+        max_leases: Option<usize>,
+
+        /// Most memory, in MiB, the lease state may take by the server's
+        /// estimate; acquires are shed near it (unlimited by default)
+        #[arg(long, env = "KLOCK_MAX_MEMORY_MB")]
+        max_memory_mb: Option<usize>,
+
+        /// Share of a cap from which background agents' acquires are refused;
+        /// normal agents' are from halfway to the cap, everyone's at it
+        #[arg(long, default_value = "80", env = "KLOCK_SHED_FROM_PERCENT", value_parser = clap::value_parser!(u8).range(1..=100))]
+        shed_from_percent: u8,
+
+        /// At the lease cap, revoke advisory leases (CONSUMES, DEPENDS_ON,
+        /// PROVIDES), lowest priority first, to make room
+        #[arg(long, env = "KLOCK_REVOKE_ADVISORY_LEASES")]
+        revoke_advisory_leases: bool,
+
+        /// Backoff suggested with Die verdicts: holder-expiry (until the
+        /// conflicting leases run out, at least 100 ms, or 1000 ms when
+        /// none is in the way), holder-expiry:<base_ms>:<min_ms>, fixed:<ms>,
+        /// exponential:<base_ms>:<max_ms> or
+        /// exponential-jitter:<base_ms>:<max_ms>
+        #[arg(long, default_value = "holder-expiry", env = "KLOCK_BACKOFF", value_parser = BackoffPolicy::parse)]
+        backoff: BackoffPolicy,
+
+        /// JSON file with a custom predicate compatibility matrix
+        #[arg(long, env = "KLOCK_CONFLICT_MATRIX")]
+        conflict_matrix: Option<String>,
+
+        /// JSON file mapping resource types to the compatibility matrix used
+        /// for them instead, e.g. {"ConfigKey": {...}}
+        #[arg(long, env = "KLOCK_TYPE_MATRICES")]
+        type_matrices: Option<String>,
+
+        /// Implication rules: "standard" or a JSON file with a list of rules
+        #[arg(long, env = "KLOCK_IMPLICATION_RULES")]
+        implication_rules: Option<String>,
+
+        /// Report conflicts of intents at or below this confidence (LOW,
+        /// MEDIUM or HIGH) as warnings instead of refusing them
+        #[arg(long, env = "KLOCK_TENTATIVE_CONFIDENCE", value_parser = parse_confidence_arg)]
+        tentative_confidence: Option<Confidence>,
+
+        /// Report file-level conflicts between these predicates (e.g.
+        /// PROVIDES) as warnings when both sides declared symbols in the
+        /// file and none overlap, comma-separated
+        #[arg(long, env = "KLOCK_REFINE_SYMBOL_CONFLICTS", value_delimiter = ',', value_parser = parse_predicate_arg)]
+        refine_symbol_conflicts: Vec<Predicate>,
+
+        /// JSON file mapping bare symbols to the files that define them
+        #[arg(long, env = "KLOCK_CONTAINMENT")]
+        containment: Option<String>,
+
+        /// JSON file declaring resources of different types that conflict
+        /// as one, e.g. a config key and the file holding it
+        #[arg(long, env = "KLOCK_EQUIVALENCES")]
+        equivalences: Option<String>,
+
+        /// Only let agents of one namespace (the part of their ID before
+        /// '/') conflict with each other
+        #[arg(long, env = "KLOCK_ISOLATE_NAMESPACES")]
+        isolate_namespaces: bool,
+
+        /// JSON file declaring resources shared across namespaces, e.g. a
+        /// schema two projects migrate; isolates namespaces
+        #[arg(long, env = "KLOCK_NAMESPACE_BRIDGES")]
+        namespace_bridges: Option<String>,
+
+        /// Seconds between background compaction passes (0 disables)
+        #[arg(long, default_value = "300", env = "KLOCK_COMPACT_INTERVAL_SECS")]
+        compact_interval_secs: u64,
+
+        /// Milliseconds granted intents live unless a heartbeat on one of
+        /// their session's leases renews them (never expire when unset)
+        #[arg(long, env = "KLOCK_INTENT_TTL_MS", value_parser = clap::value_parser!(u64).range(1..))]
+        intent_ttl_ms: Option<u64>,
+
+        /// Unregister agents with no lease, waiter or intent once they have
+        /// not been seen for this many milliseconds (kept when unset)
+        #[arg(long, env = "KLOCK_IDLE_AGENT_MS", value_parser = clap::value_parser!(u64).range(1..))]
+        idle_agent_ms: Option<u64>,
+
+        /// Also write every lease change to this storage backend
+        /// ("memory" or "sqlite:<path>") while migrating to it
+        #[arg(long, env = "KLOCK_MIRROR_TO")]
+        mirror_to: Option<String>,
+
+        /// Lease changes kept for GET /firehose readers (0 disables)
+        #[arg(long, default_value = "10000", env = "KLOCK_FIREHOSE_CAPACITY")]
+        firehose_capacity: usize,
+
+        /// Time leases with a monotonic clock started at wall time, so
+        /// wall-clock jumps neither expire leases early nor keep them alive
+        #[arg(long, env = "KLOCK_MONOTONIC_CLOCK")]
+        monotonic_clock: bool,
+
+        /// Hold eviction after the wall clock jumps back by more than this
+        /// many milliseconds, until it is past where it was (0 disables)
+        #[arg(long, default_value = "5000", env = "KLOCK_CLOCK_SKEW_TOLERANCE_MS")]
+        clock_skew_tolerance_ms: u64,
+
+        /// Scheduler decisions kept for GET /admin/audit (0 disables)
+        #[arg(long, default_value = "10000", env = "KLOCK_AUDIT_CAPACITY")]
+        audit_capacity: usize,
+
+        /// Also append every scheduler decision to this file, as JSON lines
+        #[arg(long, env = "KLOCK_AUDIT_LOG")]
+        audit_log: Option<String>,
+
+        /// Seconds between usage rollups for GET /reports/usage (0 disables
+        /// usage metering)
+        #[arg(long, default_value = "300", env = "KLOCK_USAGE_ROLLUP_SECS")]
+        usage_rollup_secs: u64,
+
+        /// WebAssembly module asked to allow, deny or modify every acquire
+        /// and intent before the scheduler runs (needs the wasm-policy
+        /// feature)
+        #[arg(long, env = "KLOCK_POLICY_SCRIPT")]
+        policy_script: Option<String>,
+
+        /// Fuel each policy evaluation may burn, roughly one unit per
+        /// instruction
+        #[arg(long, default_value = "10000000", env = "KLOCK_POLICY_FUEL")]
+        policy_fuel: u64,
+
+        /// Memory the policy module may use, in MiB
+        #[arg(long, default_value = "16", env = "KLOCK_POLICY_MEMORY_MB")]
+        policy_memory_mb: usize,
+
+        /// Treat FILE paths as case-insensitive, so "/src/App.ts" and
+        /// "/src/app.ts" are the same resource
+        #[arg(long, env = "KLOCK_CASE_INSENSITIVE_PATHS")]
+        case_insensitive_paths: bool,
+
+        /// Absolute workspace locations agents may prefix FILE paths with,
+        /// comma-separated; "/repo/src/app.ts" is "/src/app.ts" under a
+        /// root of "/repo", and paths escaping the root are rejected
+        #[arg(long, env = "KLOCK_WORKSPACE_ROOT", value_delimiter = ',')]
+        workspace_root: Vec<String>,
+
+        /// Workspace root of the agents of one namespace instead of
+        /// --workspace-root, as NAMESPACE=PATH, e.g. ci=/home/ci/checkout
+        /// (repeatable)
+        #[arg(long, env = "KLOCK_NAMESPACE_WORKSPACE_ROOT", value_delimiter = ',', value_parser = parse_var_arg)]
+        namespace_workspace_root: Vec<(String, String)>,
+
+        /// JSON file with a list of manifest templates to register
+        #[arg(long, env = "KLOCK_TEMPLATES")]
+        templates: Option<String>,
+
+        /// JSON file with a list of maintenance windows: periods when only
+        /// the listed agents may acquire or declare on a resource
+        #[arg(long, env = "KLOCK_MAINTENANCE_WINDOWS")]
+        maintenance_windows: Option<String>,
+
+        /// JSON file with a list of priority ceilings: critical resources
+        /// whose holders run at the ceiling priority until they release
+        #[arg(long, env = "KLOCK_PRIORITY_CEILINGS")]
+        priority_ceilings: Option<String>,
+
+        /// JSON file mapping group names to interchangeable resources
+        /// (e.g. shards); Die verdicts suggest the group's free members
+        #[arg(long, env = "KLOCK_FALLBACK_GROUPS")]
+        fallback_groups: Option<String>,
+
+        /// JSON file with a list of co-signing rules: deletes and renames
+        /// of protected resources wait for another agent's approval
+        #[arg(long, env = "KLOCK_COSIGN_RULES")]
+        cosign_rules: Option<String>,
+    },
+
+    /// Check for conflicts from a JSON intent manifest (stdin), or from a
+    /// manifest template expanded with --var
+    Check {
+        /// Conformance vector to replay first, so the manifest is checked
+        /// against the agents, leases and intents it leaves
+        #[arg(long)]
+        setup: Option<String>,
+
+        /// Print conflicts grouped by resource and by blocking agent,
+        /// instead of the full verdict
+        #[arg(long)]
+        summary: bool,
+
+        /// With --summary, show only the N worst groups of each kind
+        #[arg(long, value_name = "N", requires = "summary")]
+        top: Option<usize>,
+
+        /// Print how Wait-Die decides each intent (holders considered,
+        /// priorities compared, the rule that fired) instead of the verdict
+        #[arg(long, conflicts_with = "summary")]
+        explain: bool,
+
+        /// Name of the template to expand instead of reading stdin
+        #[arg(long, requires = "templates")]
+        template: Option<String>,
+
+        /// JSON file with a list of manifest templates
+        #[arg(long, env = "KLOCK_TEMPLATES")]
+        templates: Option<String>,
+
+        /// Template variable as name=value (repeatable)
+        #[arg(long = "var", value_parser = parse_var_arg)]
+        vars: Vec<(String, String)>,
+
+        /// Agent declaring the expanded template
+        #[arg(long, default_value = "cli")]
+        agent: String,
+
+        /// Session of the expanded template
+        #[arg(long, default_value = "cli")]
+        session: String,
+    },
+
+    /// Copy live leases, agent priorities and rename aliases from one
+    /// storage backend to another, then verify both hold the same state
+    MigrateStorage {
+        /// Backend to copy from: "memory" or "sqlite:<path>"
+        #[arg(long)]
+        from: String,
+
+        /// Backend to copy into: "memory" or "sqlite:<path>"
+        #[arg(long)]
+        to: String,
+    },
+
+    /// Run the same synthetic lease workload against each storage backend
+    /// and compare throughput, latency and eviction cost
+    BenchStorage {
+        /// Backends to compare, e.g. "memory,sqlite:/tmp/klock-bench.db";
+        /// SQLite paths must not exist yet and are removed afterwards
+        #[arg(long, value_delimiter = ',', default_value = "memory")]
+        backends: Vec<String>,
+
+        /// Number of agents contending for resources
+        #[arg(long, default_value = "100")]
+        agents: usize,
+
+        /// Operations per backend, e.g. 5000, 100k or 2m
+        #[arg(long, default_value = "100k", value_parser = bench::parse_count)]
+        ops: u64,
+
+        /// Number of distinct resources (default: 10 per agent)
+        #[arg(long)]
+        resources: Option<usize>,
+
+        /// Lease TTL in simulated milliseconds; the clock advances 1ms per
+        /// operation
+        #[arg(long, default_value = "2000")]
+        ttl: u64,
+
+        /// Seed of the workload; the same seed replays the same operations
+        #[arg(long, default_value = "42")]
+        seed: u64,
+    },
+
+    /// Freeze resources on a running server while an incident is handled
+    Quarantine {
+        /// Server URL
+        #[arg(long, default_value = "http://localhost:3100", env = "KLOCK_URL")]
+        server: String,
+
+        /// API key, if the server requires one
+        #[arg(long, env = "KLOCK_API_KEY")]
+        api_key: Option<String>,
+
+        #[command(subcommand)]
+        action: QuarantineAction,
+    },
+
+    /// Query reports from a running server
+    Report {
+        /// Server URL
+        #[arg(long, default_value = "http://localhost:3100", env = "KLOCK_URL")]
+        server: String,
+
+        /// API key, if the server requires one
+        #[arg(long, env = "KLOCK_API_KEY")]
+        api_key: Option<String>,
+
+        #[command(subcommand)]
+        action: ReportAction,
+    },
+
+    /// Print the compatibility matrices a running server decides conflicts
+    /// with, including per-type overrides
+    Matrix {
+        /// Server URL
+        #[arg(long, default_value = "http://localhost:3100", env = "KLOCK_URL")]
+        server: String,
+
+        /// API key, if the server requires one
+        #[arg(long, env = "KLOCK_API_KEY")]
+        api_key: Option<String>,
+
+        /// Print the JSON the server returned instead of tables
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Describe a reason code from responses, e.g. "klock explain K1001"
+    Explain {
+        /// Code (K1001) or name (WAIT_SENIOR); omit to list every code
+        code: Option<String>,
     },
 
-    /// Check for conflicts from a JSON intent manifest (stdin)
-    Check,
+    /// Generate or replay the conformance vectors SDKs are tested against
+    Conformance {
+        #[command(subcommand)]
+        action: ConformanceAction,
+    },
 
     /// Print version information
     Version,
 }
 
+#[derive(Subcommand)]
+enum ConformanceAction {
+    /// Write the standard vectors, with outcomes from this kernel, as JSON
+    Generate {
+        /// Directory to write one <name>.json file per vector into
+        #[arg(long, default_value = "conformance/vectors")]
+        out: String,
+    },
+    /// Replay every vector in a directory and report mismatches
+    Check {
+        #[arg(default_value = "conformance/vectors")]
+        dir: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportAction {
+    /// Lease time and acquires per agent or agent label, for chargeback
+    Usage {
+        /// "agent", or an agent label such as "team"
+        #[arg(long, default_value = "agent")]
+        group_by: String,
+
+        /// How far back to report, e.g. "90m", "24h" or "30d"
+        #[arg(long, default_value = "24h")]
+        period: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum QuarantineAction {
+    /// Quarantine a resource, e.g. "FILE:/src/payments/**"
+    Add {
+        /// Resource key: TYPE:path, where path may be a directory or glob
+        resource: String,
+
+        /// Why the resource is frozen, e.g. an incident ID
+        #[arg(long)]
+        reason: Option<String>,
+
+        /// Lift the quarantine automatically after this many milliseconds
+        #[arg(long)]
+        ttl: Option<u64>,
+    },
+    /// Lift the quarantine on a resource
+    Remove {
+        /// Resource key exactly as quarantined
+        resource: String,
+    },
+    /// List quarantines in force
+    List,
+}
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
@@ -49,22 +511,329 @@ async fn main() {
             port,
             host,
             storage,
+            strict_storage,
+            storage_timeout_secs,
+            deprioritize_below,
+            quarantine_below,
+            age_after_denials,
+            age_after_ms,
+            preempt_min_gap,
+            fair_share_penalty,
+            expiry_warning_percent,
+            min_acquire_interval_ms,
+            default_session_ttl_ms,
+            scheduler,
+            scheduler_for,
+            unregistered_agents,
+            max_leases,
+            max_memory_mb,
+            shed_from_percent,
+            revoke_advisory_leases,
+            backoff,
+            conflict_matrix,
+            type_matrices,
+            implication_rules,
+            containment,
+            equivalences,
+            isolate_namespaces,
+            namespace_bridges,
+            tentative_confidence,
+            refine_symbol_conflicts,
+            compact_interval_secs,
+            intent_ttl_ms,
+            idle_agent_ms,
+            mirror_to,
+            firehose_capacity,
+            monotonic_clock,
+            clock_skew_tolerance_ms,
+            audit_capacity,
+            audit_log,
+            usage_rollup_secs,
+            policy_script,
+            policy_fuel,
+            policy_memory_mb,
+            case_insensitive_paths,
+            workspace_root,
+            namespace_workspace_root,
+            templates,
+            maintenance_windows,
+            priority_ceilings,
+            fallback_groups,
+            cosign_rules,
         } => {
-            server::run(&host, port, &storage).await;
-        }
-        Commands::Check => {
-            eprintln!("Reading intent manifest from stdin...");
-            let mut input = String::new();
-            std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)
-                .expect("Failed to read stdin");
+            let mut namespace_paths: BTreeMap<String, PathNormalization> = BTreeMap::new();
+            for (namespace, root) in namespace_workspace_root {
+                namespace_paths
+                    .entry(namespace)
+                    .or_insert_with(|| PathNormalization {
+                        case_fold: case_insensitive_paths,
+                        roots: Vec::new(),
+                    })
+                    .roots
+                    .push(root);
+            }
 
-            let manifest: klock_core::state::IntentManifest =
-                serde_json::from_str(&input).expect("Invalid JSON manifest");
+            let config_files = ConfigFiles {
+                conflict_matrix,
+                type_matrices,
+                implication_rules,
+                containment,
+                equivalences,
+                namespace_bridges,
+                isolate_namespaces,
+                tentative_confidence,
+                symbol_refinement: (!refine_symbol_conflicts.is_empty()).then_some(
+                    SymbolRefinement {
+                        predicates: refine_symbol_conflicts,
+                    },
+                ),
+                templates,
+                maintenance_windows,
+                priority_ceilings,
+                fallback_groups,
+                cosign_rules,
+            };
+            let config = match config_files.load() {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
 
-            let mut client = klock_core::client::KlockClient::new();
-            let verdict = client.declare_intent(&manifest);
+            server::run(server::ServeOptions {
+                host,
+                port,
+                storage,
+                strict_storage,
+                storage_timeout_secs,
+                health_policy: klock_core::health::HealthPolicy {
+                    deprioritize_below,
+                    quarantine_below,
+                    ..Default::default()
+                },
+                aging_policy: klock_core::aging::AgingPolicy {
+                    after_denials: age_after_denials,
+                    after_ms: age_after_ms,
+                    ..Default::default()
+                },
+                preemption_policy: klock_core::preemption::PreemptionPolicy {
+                    min_priority_gap: preempt_min_gap,
+                },
+                fair_share_policy: klock_core::fair_share::FairSharePolicy {
+                    penalty_per_lease: fair_share_penalty,
+                },
+                expiry_warning_policy: klock_core::expiry::ExpiryWarningPolicy {
+                    threshold_percent: Some(expiry_warning_percent),
+                },
+                debounce_policy: klock_core::debounce::DebouncePolicy {
+                    min_interval_ms: min_acquire_interval_ms,
+                },
+                scheduler,
+                scheduler_for,
+                unregistered_agents,
+                capacity_policy: klock_core::capacity::CapacityPolicy {
+                    max_leases,
+                    max_memory_bytes: max_memory_mb.map(|mb| mb.saturating_mul(1024 * 1024)),
+                    shed_from_percent,
+                    revoke_advisory: revoke_advisory_leases,
+                },
+                backoff_policy: backoff,
+                compact_interval_secs,
+                intent_ttl_ms,
+                idle_agent_ms,
+                session_policy: klock_core::session::SessionPolicy {
+                    idle_ttl_ms: default_session_ttl_ms,
+                    ..Default::default()
+                },
+                mirror_to,
+                firehose_capacity,
+                monotonic_clock,
+                skew_policy: klock_core::skew::SkewPolicy::new(clock_skew_tolerance_ms),
+                audit_capacity,
+                audit_log,
+                usage_rollup_secs,
+                policy_script,
+                policy_fuel,
+                policy_memory_mb,
+                path_normalization: PathNormalization {
+                    case_fold: case_insensitive_paths,
+                    roots: workspace_root,
+                },
+                namespace_paths,
+                config_files,
+                config,
+            })
+            .await;
+        }
+        Commands::Check {
+            setup,
+            summary,
+            top,
+            explain,
+            template,
+            templates,
+            vars,
+            agent,
+            session,
+        } => {
+            let mut client = match setup.as_deref().map(replay_setup).transpose() {
+                Ok(client) => client.unwrap_or_default(),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let manifest = match template {
+                Some(name) => ConfigFiles {
+                    templates,
+                    ..Default::default()
+                }
+                .load()
+                .and_then(|config| {
+                    let template = config
+                        .templates
+                        .into_iter()
+                        .find(|t| t.name == name)
+                        .ok_or_else(|| format!("Template '{}' not found", name))?;
+                    let now = client.now();
+                    template.expand(&agent, &session, &vars.into_iter().collect(), now, || {
+                        client.next_id()
+                    })
+                }),
+                None => {
+                    eprintln!("Reading intent manifest from stdin...");
+                    let mut input = String::new();
+                    std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)
+                        .expect("Failed to read stdin");
+                    serde_json::from_str(&input)
+                        .map_err(|e| format!("Invalid JSON manifest: {}", e))
+                }
+            };
+            let manifest = match manifest {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
 
-            println!("{}", serde_json::to_string_pretty(&verdict).unwrap());
+            if summary {
+                let mut report = client.summarize_intent(&manifest);
+                if let Some(n) = top {
+                    report.truncate(n);
+                }
+                print_summary(&report);
+            } else if explain {
+                let explanations = client.explain_intent(&manifest);
+                println!("{}", serde_json::to_string_pretty(&explanations).unwrap());
+            } else {
+                let verdict = client.declare_intent(&manifest);
+                println!("{}", serde_json::to_string_pretty(&verdict).unwrap());
+            }
+        }
+        Commands::MigrateStorage { from, to } => {
+            if let Err(e) = migrate_storage(&from, &to) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::BenchStorage {
+            backends,
+            agents,
+            ops,
+            resources,
+            ttl,
+            seed,
+        } => {
+            if let Err(e) = bench_storage(
+                &backends,
+                &bench::Workload {
+                    agents: agents.max(1),
+                    ops,
+                    resources: resources.unwrap_or(agents * 10).max(1),
+                    ttl,
+                    seed,
+                },
+            ) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Quarantine {
+            server,
+            api_key,
+            action,
+        } => {
+            let remote = remote::Remote::new(&server, api_key);
+            // ureq blocks; keep it off the async runtime's worker threads
+            let result = tokio::task::spawn_blocking(move || run_quarantine(&remote, action))
+                .await
+                .expect("quarantine command panicked");
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Report {
+            server,
+            api_key,
+            action,
+        } => {
+            let remote = remote::Remote::new(&server, api_key);
+            let result = tokio::task::spawn_blocking(move || run_report(&remote, action))
+                .await
+                .expect("report command panicked");
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Matrix {
+            server,
+            api_key,
+            json,
+        } => {
+            let remote = remote::Remote::new(&server, api_key);
+            let result = tokio::task::spawn_blocking(move || {
+                remote.call("GET", "/capabilities/matrix", &[], None)
+            })
+            .await
+            .expect("matrix command panicked")
+            .and_then(|data| {
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&data).unwrap());
+                    return Ok(());
+                }
+                let explanation: MatrixExplanation = serde_json::from_value(data)
+                    .map_err(|e| format!("Unexpected matrix response: {}", e))?;
+                print_matrix("Default", &explanation.default);
+                for (resource_type, table) in &explanation.by_type {
+                    println!();
+                    print_matrix(resource_type, table);
+                }
+                Ok(())
+            });
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Explain { code } => {
+            if let Err(e) = explain(code.as_deref()) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Conformance { action } => {
+            let result = match action {
+                ConformanceAction::Generate { out } => generate_vectors(&out),
+                ConformanceAction::Check { dir } => check_vectors(&dir),
+            };
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
         }
         Commands::Version => {
             println!("klock {}", env!("CARGO_PKG_VERSION"));
@@ -72,3 +841,296 @@ async fn main() {
         }
     }
 }
+
+fn migrate_storage(from: &str, to: &str) -> Result<(), String> {
+    if from == to {
+        return Err("--from and --to name the same backend".to_string());
+    }
+    let source = open_store(from)?;
+    let mut target = open_store(to)?;
+
+    let copied = copy_state(source.as_ref(), target.as_mut())?;
+    println!("Copied {} live leases from {} to {}", copied, from, to);
+
+    let differences = parity(source.as_ref(), target.as_ref());
+    if differences.is_empty() {
+        println!("Parity verified");
+        return Ok(());
+    }
+    for difference in &differences {
+        eprintln!("  {}", difference);
+    }
+    Err(format!(
+        "{} differences remain after copying",
+        differences.len()
+    ))
+}
+
+fn bench_storage(backends: &[String], workload: &bench::Workload) -> Result<(), String> {
+    let mut results = Vec::new();
+    for backend in backends {
+        eprintln!("Benchmarking {} ({} ops)...", backend, workload.ops);
+        results.push(bench::run(backend, workload)?);
+    }
+    bench::print_table(&results);
+    Ok(())
+}
+
+fn explain(code: Option<&str>) -> Result<(), String> {
+    let Some(code) = code else {
+        for info in codes() {
+            println!("{}  {:<22} {}", info.code, info.name, info.summary);
+        }
+        return Ok(());
+    };
+    let info = ReasonCode::parse(code)
+        .ok_or_else(|| {
+            format!(
+                "Unknown code '{}'; run 'klock explain' to list every code",
+                code
+            )
+        })?
+        .info();
+    println!("{} {}", info.code, info.name);
+    println!("  {}", info.summary);
+    println!("  What to do: {}", info.remedy);
+    Ok(())
+}
+
+fn generate_vectors(out: &str) -> Result<(), String> {
+    std::fs::create_dir_all(out).map_err(|e| format!("Failed to create '{}': {}", out, e))?;
+    let vectors = standard_vectors();
+    for vector in &vectors {
+        let path = std::path::Path::new(out).join(format!("{}.json", vector.name));
+        let json = serde_json::to_string_pretty(vector).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json + "\n")
+            .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))?;
+    }
+    println!("Wrote {} vectors to {}", vectors.len(), out);
+    Ok(())
+}
+
+/// The client in the state the conformance vector at `path` leaves
+fn replay_setup(path: &str) -> Result<klock_core::client::KlockClient, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    let vector: ConformanceVector =
+        serde_json::from_str(&contents).map_err(|e| format!("Invalid vector '{}': {}", path, e))?;
+    vector.replay().map(|(client, _)| client)
+}
+
+/// Print a `klock check --summary` report: totals, then a table per
+/// grouping, worst first
+fn print_summary(report: &ConflictSummary) {
+    let code = report
+        .code
+        .map(|code| format!(" ({})", code))
+        .unwrap_or_default();
+    println!(
+        "{:?}{}: {} of {} intents conflict, {} conflicts, {} warnings",
+        report.status,
+        code,
+        report.conflicting_intents,
+        report.intents,
+        report.conflicts,
+        report.warnings
+    );
+    for (title, column, groups) in [
+        ("By resource", "resource", &report.by_resource),
+        ("By blocking agent", "agent", &report.by_holder),
+    ] {
+        if groups.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{}:", title);
+        println!(
+            "  {:<8}  {:<8}  {:>9}  {:>7}  {}",
+            "verdict", "code", "conflicts", "intents", column
+        );
+        for group in groups {
+            println!(
+                "  {:<8}  {:<8}  {:>9}  {:>7}  {}",
+                format!("{:?}", group.status),
+                group.code.map(|code| code.code()).unwrap_or("-"),
+                group.conflicts,
+                group.intents,
+                group.key
+            );
+        }
+    }
+}
+
+/// Print a compatibility matrix with held predicates as rows and requesting
+/// ones as columns
+fn print_matrix(title: &str, table: &MatrixTable) {
+    println!(
+        "{} (rows: held, columns: requesting; ok = may coexist):",
+        title
+    );
+    let names: Vec<String> = table
+        .predicates
+        .iter()
+        .map(|p| format!("{:?}", p))
+        .collect();
+    let header: Vec<String> = names.iter().map(|name| format!("{:>9}", name)).collect();
+    println!("  {:<9}  {}", "", header.join(" "));
+    for (name, row) in names.iter().zip(&table.compatible) {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|&compatible| format!("{:>9}", if compatible { "ok" } else { "-" }))
+            .collect();
+        println!("  {:<9}  {}", name, cells.join(" "));
+    }
+}
+
+fn check_vectors(dir: &str) -> Result<(), String> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read '{}': {}", dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let mut failures = 0;
+    for path in &paths {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        let vector: ConformanceVector = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid vector '{}': {}", path.display(), e))?;
+        match vector.check() {
+            Ok(()) => println!("ok    {}", vector.name),
+            Err(e) => {
+                failures += 1;
+                println!("FAIL  {}", e);
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(format!("{} of {} vectors failed", failures, paths.len()));
+    }
+    println!("{} vectors passed", paths.len());
+    Ok(())
+}
+
+fn parse_predicate_arg(value: &str) -> Result<Predicate, String> {
+    handlers::validate_predicate(value)?;
+    Ok(parse_predicate(value))
+}
+
+fn parse_confidence_arg(value: &str) -> Result<Confidence, String> {
+    parse_confidence(value)
+        .ok_or_else(|| format!("Invalid confidence '{}': use LOW, MEDIUM or HIGH", value))
+}
+
+fn parse_var_arg(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .ok_or_else(|| format!("Invalid variable '{}': use name=value", value))
+}
+
+fn parse_resource_key(key: &str) -> Result<ResourceRef, String> {
+    ResourceRef::from_key(key)
+        .filter(|resource| !resource.path.is_empty())
+        .ok_or_else(|| {
+            format!(
+                "Invalid resource '{}': expected TYPE:path, e.g. FILE:/src/payments/**",
+                key
+            )
+        })
+}
+
+fn run_report(remote: &remote::Remote, action: ReportAction) -> Result<(), String> {
+    match action {
+        ReportAction::Usage { group_by, period } => {
+            let report = remote.call(
+                "GET",
+                "/reports/usage",
+                &[("group_by", group_by.as_str()), ("period", period.as_str())],
+                None,
+            )?;
+            let groups = report["groups"].as_array().cloned().unwrap_or_default();
+            if groups.is_empty() {
+                println!("No leases held in the last {}", period);
+            }
+            for group in groups {
+                let agents: Vec<&str> = group["agents"]
+                    .as_array()
+                    .map(|agents| agents.iter().filter_map(|a| a.as_str()).collect())
+                    .unwrap_or_default();
+                println!(
+                    "{}  acquires: {}  lease_secs: {:.1}  agents: {}",
+                    group["group"].as_str().unwrap_or("(unlabeled)"),
+                    group["acquires"].as_u64().unwrap_or(0),
+                    group["lease_ms"].as_u64().unwrap_or(0) as f64 / 1000.0,
+                    agents.join(",")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_quarantine(remote: &remote::Remote, action: QuarantineAction) -> Result<(), String> {
+    match action {
+        QuarantineAction::Add {
+            resource,
+            reason,
+            ttl,
+        } => {
+            let resource = parse_resource_key(&resource)?;
+            let quarantine = remote.call(
+                "POST",
+                "/resources/quarantine",
+                &[],
+                Some(serde_json::json!({
+                    "resource_type": resource.resource_type.to_string(),
+                    "resource_path": resource.path,
+                    "reason": reason,
+                    "ttl": ttl,
+                })),
+            )?;
+            println!("Quarantined {}", resource.key());
+            if let Some(expires_at) = quarantine["expires_at"].as_u64() {
+                println!("  expires_at: {}", expires_at);
+            }
+        }
+        QuarantineAction::Remove { resource } => {
+            let resource = parse_resource_key(&resource)?;
+            let resource_type = resource.resource_type.to_string();
+            remote.call(
+                "DELETE",
+                "/resources/quarantine",
+                &[
+                    ("resource_type", resource_type.as_str()),
+                    ("resource_path", resource.path.as_str()),
+                ],
+                None,
+            )?;
+            println!("Lifted quarantine on {}", resource.key());
+        }
+        QuarantineAction::List => {
+            let quarantines = remote.call("GET", "/resources/quarantine", &[], None)?;
+            let quarantines = quarantines.as_array().cloned().unwrap_or_default();
+            if quarantines.is_empty() {
+                println!("No resources quarantined");
+            }
+            for quarantine in quarantines {
+                let key = serde_json::from_value::<ResourceRef>(quarantine["resource"].clone())
+                    .map(|resource| resource.key())
+                    .unwrap_or_default();
+                let mut line = key;
+                if let Some(reason) = quarantine["reason"].as_str() {
+                    line.push_str(&format!("  reason: {}", reason));
+                }
+                if let Some(expires_at) = quarantine["expires_at"].as_u64() {
+                    line.push_str(&format!("  expires_at: {}", expires_at));
+                }
+                println!("{}", line);
+            }
+        }
+    }
+    Ok(())
+}
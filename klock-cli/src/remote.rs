@@ -0,0 +1,73 @@
+//! Minimal HTTP client for CLI commands that operate on a running server.
+
+use serde_json::Value;
+
+/// A running `klock serve` instance
+pub struct Remote {
+    base_url: String,
+    api_key: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl Remote {
+    pub fn new(base_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|key| !key.is_empty()),
+            agent: ureq::AgentBuilder::new()
+                .timeout(std::time::Duration::from_secs(10))
+                .build(),
+        }
+    }
+
+    /// Send a request and return the `data` of a successful `ApiResponse`,
+    /// or the server's error message.
+    pub fn call(
+        &self,
+        method: &str,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut request = self.agent.request(method, &url);
+        for (name, value) in query {
+            request = request.query(name, value);
+        }
+        if let Some(key) = &self.api_key {
+            request = request.set("Authorization", &format!("Bearer {}", key));
+        }
+
+        let response = match body {
+            Some(body) => request
+                .set("Content-Type", "application/json")
+                .send_string(&body.to_string()),
+            None => request.call(),
+        };
+        let response = match response {
+            Ok(response) => response,
+            // Error statuses still carry an `ApiResponse` body
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(format!("Request to {} failed: {}", url, e)),
+        };
+
+        let status = response.status();
+        let text = response
+            .into_string()
+            .map_err(|e| format!("Failed to read response from {}: {}", url, e))?;
+        let json: Value = serde_json::from_str(&text)
+            .map_err(|_| format!("HTTP {} from {}: {}", status, url, text))?;
+        if json["success"].as_bool() == Some(true) {
+            Ok(json["data"].clone())
+        } else {
+            let error = json["error"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("HTTP {} from {}", status, url));
+            Err(match json["code"].as_str() {
+                Some(code) => format!("{} [{}]", error, code),
+                None => error,
+            })
+        }
+    }
+}
@@ -1,46 +1,351 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use axum::{
-    extract::{Path, Request, State},
+    extract::{Extension, Path, Query, Request, State},
     http::{HeaderMap, StatusCode},
     middleware::{self, Next},
-    response::Response,
-    routing::{delete, get, post},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use tower_http::cors::CorsLayer;
 
-use klock_core::client::KlockClient;
-use klock_core::types::{LeaseFailureReason, LeaseResult};
+use klock_core::activity::ActivityEntry;
+use klock_core::aging::AgingPolicy;
+use klock_core::audit::{AuditLog, DecisionRecord};
+use klock_core::backoff::BackoffPolicy;
+use klock_core::barrier::BarrierStatus;
+use klock_core::capacity::CapacityPolicy;
+use klock_core::client::{
+    open_store, parse_confidence, parse_predicate, parse_resource_type, KlockClient,
+};
+use klock_core::clock::MonotonicClock;
+use klock_core::codes::{codes, CodeInfo, ReasonCode};
+use klock_core::compaction::{CompactionPolicy, CompactionReport, MemoryReport};
+use klock_core::conflict::MatrixExplanation;
+use klock_core::cosign::{CosignError, CosignRequest};
+use klock_core::debounce::DebouncePolicy;
+use klock_core::expiry::ExpiryWarningPolicy;
+use klock_core::fair_share::FairSharePolicy;
+#[cfg(all(feature = "testing", debug_assertions))]
+use klock_core::fault::InjectedFault;
+use klock_core::feed::FeedBatch;
+use klock_core::firehose::FirehosePage;
+use klock_core::health::HealthPolicy;
+use klock_core::infrastructure::LeaseMutationError;
+use klock_core::namespace::{Namespace, NamespaceReaping};
+use klock_core::policy_hook::PolicyHook;
+use klock_core::preemption::{PreemptionError, PreemptionPolicy};
+use klock_core::quarantine::ResourceQuarantine;
+use klock_core::registry::{Priority, UnregisteredAgentPolicy};
+use klock_core::scheduler::Scheduler;
+use klock_core::semaphore::{SemaphoreResult, SemaphoreStatus};
+use klock_core::session::{Session, SessionEnd, SessionPolicy};
+use klock_core::skew::SkewPolicy;
+use klock_core::state::{StateProjection, StateSnapshot};
+use klock_core::template::ManifestTemplate;
+use klock_core::types::normalize::PathNormalization;
+use klock_core::types::{
+    Lease, LeaseFailureReason, LeaseResult, ResourceRef, ResourceType, SPOTriple,
+};
+use klock_core::usage::{
+    parse_period, InMemoryUsageStore, UsageReport, UsageStore, GROUP_BY_AGENT,
+};
+use klock_core::wait_queue::{Waiter, WaiterFilter};
 
+use crate::audit::ServerAuditSink;
+use crate::coalesce::Coalescer;
+use crate::config::{ConfigDiff, ConfigFiles, LoadedConfig};
 use crate::handlers::*;
 
 pub type AppState = Arc<Mutex<KlockClient>>;
 
-pub async fn run(host: &str, port: u16, storage: &str) {
-    let client = create_client(storage);
+/// Identity of a lease acquisition: agent, session, resource type and path,
+/// predicate
+type AcquireKey = (String, String, String, String, String);
+
+/// Shares one decision among identical concurrent acquisitions
+type AcquireCoalescer = Coalescer<AcquireKey, (StatusCode, Json<serde_json::Value>)>;
+
+/// Configuration for `klock serve`
+pub struct ServeOptions {
+    pub host: String,
+    pub port: u16,
+    pub storage: String,
+    /// Refuse to start instead of falling back to in-memory storage
+    pub strict_storage: bool,
+    /// How long strict mode waits for the storage backend to become available
+    pub storage_timeout_secs: u64,
+    pub health_policy: HealthPolicy,
+    pub aging_policy: AgingPolicy,
+    pub preemption_policy: PreemptionPolicy,
+    pub fair_share_policy: FairSharePolicy,
+    pub expiry_warning_policy: ExpiryWarningPolicy,
+    pub debounce_policy: DebouncePolicy,
+    /// Conflict resolution policy (Wait-Die by default)
+    pub scheduler: Arc<dyn Scheduler>,
+    /// Policies that replace `scheduler` on one resource type
+    pub scheduler_for: Vec<(ResourceType, Arc<dyn Scheduler>)>,
+    /// What happens to agents without a registered priority
+    pub unregistered_agents: UnregisteredAgentPolicy,
+    /// Lease and memory caps, and load shedding near them
+    pub capacity_policy: CapacityPolicy,
+    pub backoff_policy: BackoffPolicy,
+    /// Seconds between background compaction passes (0 disables)
+    pub compact_interval_secs: u64,
+    /// How long granted intents live without renewal, if they expire
+    pub intent_ttl_ms: Option<u64>,
+    /// How long an agent with nothing held may go unseen before compaction
+    /// unregisters it, if ever
+    pub idle_agent_ms: Option<u64>,
+    pub session_policy: SessionPolicy,
+    /// Secondary backend to dual-write to while migrating storage
+    pub mirror_to: Option<String>,
+    /// Lease changes kept for firehose readers (0 disables)
+    pub firehose_capacity: usize,
+    /// Time leases with a monotonic clock instead of the wall clock
+    pub monotonic_clock: bool,
+    /// How far back the wall clock may jump before eviction is held
+    pub skew_policy: SkewPolicy,
+    /// Scheduler decisions kept for GET /admin/audit (0 disables)
+    pub audit_capacity: usize,
+    /// File every scheduler decision is appended to, as JSON lines
+    pub audit_log: Option<String>,
+    /// Seconds between usage rollups (0 disables usage metering)
+    pub usage_rollup_secs: u64,
+    /// WebAssembly policy module consulted before the scheduler
+    pub policy_script: Option<String>,
+    /// Fuel each policy evaluation may burn
+    pub policy_fuel: u64,
+    /// Memory cap of the policy module, in MiB
+    pub policy_memory_mb: usize,
+    /// How agents' resource paths are canonicalized
+    pub path_normalization: PathNormalization,
+    /// Rules replacing `path_normalization` for the agents of a namespace
+    pub namespace_paths: BTreeMap<String, PathNormalization>,
+    /// Config files re-read on reload
+    pub config_files: ConfigFiles,
+    /// What was loaded from them at startup
+    pub config: LoadedConfig,
+}
+
+/// The config files and what was last loaded from them, for reloads
+pub struct ConfigReload {
+    files: ConfigFiles,
+    current: Mutex<LoadedConfig>,
+}
+
+pub async fn run(options: ServeOptions) {
+    let mut client = if options.strict_storage {
+        match wait_for_storage(&options.storage, options.storage_timeout_secs).await {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!("❌ Storage backend unavailable: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        create_client(&options.storage)
+    };
+    client.set_health_policy(options.health_policy);
+    client.set_aging_policy(options.aging_policy);
+    client.set_preemption_policy(options.preemption_policy);
+    client.set_fair_share_policy(options.fair_share_policy);
+    client.set_expiry_warning_policy(options.expiry_warning_policy);
+    client.set_debounce_policy(options.debounce_policy);
+    client.set_session_policy(options.session_policy);
+    client.set_intent_ttl(options.intent_ttl_ms);
+    client.set_compaction_policy(CompactionPolicy {
+        idle_agent_ms: options.idle_agent_ms,
+        ..CompactionPolicy::default()
+    });
+    client.set_scheduler(options.scheduler);
+    for (resource_type, scheduler) in options.scheduler_for {
+        tracing::info!(
+            "⚖️  {} conflicts resolved by {}",
+            resource_type,
+            scheduler.name()
+        );
+        client.set_resource_type_scheduler(resource_type, Some(scheduler));
+    }
+    client.set_unregistered_agent_policy(options.unregistered_agents);
+    client.set_capacity_policy(options.capacity_policy);
+    client.set_backoff_policy(options.backoff_policy);
+    client.set_path_normalization(options.path_normalization);
+    for (namespace, rules) in options.namespace_paths {
+        tracing::info!(
+            "📁 Namespace {} rooted at {}",
+            namespace,
+            rules.roots.join(", ")
+        );
+        client.set_namespace_path_normalization(&namespace, Some(rules));
+    }
+    client.set_firehose_capacity(options.firehose_capacity);
+    if options.monotonic_clock {
+        client.set_clock(Arc::new(MonotonicClock::new()));
+        tracing::info!("⏱️  Lease expiry timed by a monotonic clock");
+    }
+    client.set_skew_policy(options.skew_policy);
+    if options.usage_rollup_secs > 0 {
+        client.set_usage_store(Some(open_usage_store(&options.storage)));
+    }
+    #[cfg(all(feature = "testing", debug_assertions))]
+    {
+        client.enable_fault_injection();
+        tracing::warn!("🧪 Scheduler fault injection enabled at /debug/faults");
+    }
+    if let Err(e) = apply_config(&mut client, &LoadedConfig::default(), &options.config) {
+        tracing::error!("❌ {}", e);
+        std::process::exit(1);
+    }
+    if let Some(spec) = &options.mirror_to {
+        match open_store(spec) {
+            Ok(secondary) => {
+                client.mirror_to(secondary);
+                tracing::info!("🪞 Mirroring lease changes to {}", spec);
+            }
+            Err(e) => {
+                tracing::error!("❌ Mirror backend unavailable: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let audit = if options.audit_capacity > 0 || options.audit_log.is_some() {
+        let log = Arc::new(AuditLog::new(options.audit_capacity));
+        match ServerAuditSink::new(log.clone(), options.audit_log.as_deref()) {
+            Ok(sink) => client.set_audit_sink(Some(Arc::new(sink))),
+            Err(e) => {
+                tracing::error!("❌ {}", e);
+                std::process::exit(1);
+            }
+        }
+        if let Some(path) = &options.audit_log {
+            tracing::info!("📝 Writing scheduler decisions to {}", path);
+        }
+        (options.audit_capacity > 0).then_some(log)
+    } else {
+        None
+    };
+    if let Some(path) = &options.policy_script {
+        match load_policy_script(path, options.policy_fuel, options.policy_memory_mb) {
+            Ok(hook) => {
+                client.set_policy_hook(Some(hook));
+                tracing::info!("📜 Admission policy script: {}", path);
+            }
+            Err(e) => {
+                tracing::error!("❌ {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
     let state: AppState = Arc::new(Mutex::new(client));
+    let acquires = Arc::new(AcquireCoalescer::new());
+    let reload = Arc::new(ConfigReload {
+        files: options.config_files,
+        current: Mutex::new(options.config),
+    });
+
+    #[cfg(unix)]
+    tokio::spawn(reload_on_sighup(state.clone(), reload.clone()));
+
+    if options.compact_interval_secs > 0 {
+        tokio::spawn(compaction_loop(
+            state.clone(),
+            options.compact_interval_secs,
+        ));
+    }
+    if options.usage_rollup_secs > 0 {
+        tokio::spawn(usage_rollup_loop(state.clone(), options.usage_rollup_secs));
+    }
 
     // NOTE: Rate limiting should be handled at the infrastructure level
     // (nginx, envoy, cloud load balancer) for production deployments.
 
-    let app = Router::new()
+    let router = Router::new()
         // Health is always open (no auth)
         .route("/health", get(health))
+        .route("/health/ready", get(readiness))
         // Protected routes
         .route("/agents", post(register_agent))
+        .route("/agents/{id}", get(get_agent))
+        .route("/agents/{id}/priority", put(update_agent_priority))
+        .route("/agents/{id}/events", get(poll_events))
+        .route("/agents/{id}/timeline", get(agent_timeline))
         .route("/leases", post(acquire_lease))
         .route("/leases", get(list_leases))
+        .route("/leases/reserve", post(reserve_leases))
+        .route("/leases/waiting", get(list_waiters))
+        .route("/leases/cosign", get(list_cosign_requests))
+        .route("/leases/release", post(release_many_checked))
+        .route("/queue", delete(cancel_agent_waiters))
+        .route("/queue/{id}", delete(cancel_waiter))
+        .route("/firehose", get(firehose))
+        .route("/leases/{id}/activate", post(activate_lease))
+        .route("/leases/{id}/cosign", post(cosign_lease))
+        .route("/leases/{id}", get(get_lease))
         .route("/leases/{id}", delete(release_lease))
+        .route("/leases/{id}/preempt", post(preempt_lease))
         .route("/leases/{id}/heartbeat", post(heartbeat_lease))
+        .route("/admin/leases/{id}/revoke", post(revoke_lease))
         .route("/intents", post(declare_intent))
+        .route("/intents", delete(withdraw_session_intents))
+        .route("/intents/{id}", delete(withdraw_intent))
+        .route("/intents/template", post(declare_intent_from_template))
+        .route("/intents/atomic", post(declare_intents_atomic))
+        .route("/intents/acquire", post(acquire_manifest))
+        .route("/templates", post(register_template))
+        .route("/templates", get(list_templates))
+        .route("/templates/{name}", delete(remove_template))
         .route("/evict", post(evict_expired))
+        .route("/barriers", post(create_barrier))
+        .route("/barriers/{name}", get(barrier_status))
+        .route("/barriers/{name}", delete(remove_barrier))
+        .route("/barriers/{name}/arrive", post(arrive_barrier))
+        .route("/barriers/{name}/wait", get(wait_barrier))
+        .route("/sessions", post(begin_session))
+        .route("/sessions/{id}", get(get_session))
+        .route("/sessions/{id}", delete(end_session))
+        .route("/namespaces", post(declare_namespace))
+        .route("/namespaces", get(list_namespaces))
+        .route("/namespaces/{name}", get(get_namespace))
+        .route("/namespaces/{name}", delete(delete_namespace))
+        .route("/semaphores/{name}", get(semaphore_status))
+        .route("/semaphores/{name}/acquire", post(acquire_semaphore))
+        .route("/permits/{id}", delete(release_semaphore))
+        .route("/resources/quarantine", post(quarantine_resource))
+        .route("/resources/quarantine", get(list_quarantines))
+        .route("/resources/quarantine", delete(lift_quarantine))
+        .route("/resources/ceilings", get(list_ceilings))
+        .route("/resources/{key}/holders", get(resource_holders))
+        .route("/state/projection", get(state_projection))
+        .route("/state/snapshot", get(state_snapshot))
+        .route("/state/wait-for", get(wait_for_graph))
+        .route("/codes", get(list_codes))
+        .route("/capabilities", get(capabilities))
+        .route("/capabilities/matrix", get(compatibility_matrix))
+        .route("/admin/memory", get(memory_report))
+        .route("/admin/compact", post(compact))
+        .route("/admin/restore", post(restore_snapshot))
+        .route("/admin/reload", post(reload_config))
+        .route("/admin/metrics", get(metrics))
+        .route("/admin/audit", get(audit_records))
+        .route("/reports/usage", get(usage_report));
+    #[cfg(all(feature = "testing", debug_assertions))]
+    let router = router.route(
+        "/debug/faults",
+        post(inject_fault).get(list_faults).delete(clear_faults),
+    );
+    let app = router
+        .layer(Extension(reload))
+        .layer(Extension(acquires))
+        .layer(Extension(audit))
         .layer(middleware::from_fn(auth_middleware))
         .layer(CorsLayer::permissive())
         .with_state(state);
 
-    let addr = format!("{}:{}", host, port);
+    let addr = format!("{}:{}", options.host, options.port);
 
     if std::env::var("KLOCK_API_KEY").is_ok() {
         tracing::info!("🔐 API key authentication enabled");
@@ -59,20 +364,16 @@ pub async fn run(host: &str, port: u16, storage: &str) {
 
 // ─── Auth Middleware ────────────────────────────────────────────────────────
 
-async fn auth_middleware(
-    headers: HeaderMap,
-    request: Request,
-    next: Next,
-) -> Result<Response, StatusCode> {
+async fn auth_middleware(headers: HeaderMap, request: Request, next: Next) -> Response {
     // If no API key is configured, allow all requests (dev mode)
     let expected_key = match std::env::var("KLOCK_API_KEY") {
         Ok(key) if !key.is_empty() => key,
-        _ => return Ok(next.run(request).await),
+        _ => return next.run(request).await,
     };
 
-    // Always allow health check without auth
-    if request.uri().path() == "/health" {
-        return Ok(next.run(request).await);
+    // Always allow health checks without auth
+    if request.uri().path().starts_with("/health") {
+        return next.run(request).await;
     }
 
     // Check the Authorization header
@@ -84,10 +385,17 @@ async fn auth_middleware(
     let token = auth_header.strip_prefix("Bearer ").unwrap_or("");
 
     if token == expected_key {
-        Ok(next.run(request).await)
+        next.run(request).await
     } else {
         tracing::warn!("🚫 Unauthorized request to {}", request.uri().path());
-        Err(StatusCode::UNAUTHORIZED)
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ApiResponse::<()>::err(
+                ReasonCode::Unauthorized,
+                "Missing or invalid API key",
+            )),
+        )
+            .into_response()
     }
 }
 
@@ -95,13 +403,71 @@ async fn auth_middleware(
 
 async fn health(State(state): State<AppState>) -> Json<ApiResponse<HealthResponse>> {
     let client = state.lock().await;
+    let clock = client.clock_report();
+    let status = match &clock {
+        Some(clock) if clock.suspect => "clock_skew",
+        _ => "ok",
+    };
     Json(ApiResponse::ok(HealthResponse {
-        status: "ok".to_string(),
+        status: status.to_string(),
         active_leases: client.get_active_leases().len(),
         version: env!("CARGO_PKG_VERSION").to_string(),
+        storage_ready: client.storage_ready().is_ok(),
+        mirror: client.mirror_report(),
+        capacity: client.capacity_report(),
+        clock,
     }))
 }
 
+/// Readiness probe: 503 while the storage backend cannot serve writes.
+async fn readiness(
+    State(state): State<AppState>,
+) -> (StatusCode, Json<ApiResponse<ReadinessResponse>>) {
+    let client = state.lock().await;
+    match client.storage_ready() {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ApiResponse::ok(ReadinessResponse { ready: true })),
+        ),
+        Err(e) => {
+            tracing::warn!("Readiness check failed: {}", e);
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(ApiResponse::err(
+                    ReasonCode::StorageUnavailable,
+                    format!("Storage not ready: {}", e),
+                )),
+            )
+        }
+    }
+}
+
+/// The reason-code catalog, for SDKs and dashboards
+async fn list_codes() -> Json<ApiResponse<Vec<CodeInfo>>> {
+    Json(ApiResponse::ok(codes()))
+}
+
+async fn capabilities(State(state): State<AppState>) -> Json<ApiResponse<CapabilitiesResponse>> {
+    let client = state.lock().await;
+    Json(ApiResponse::ok(CapabilitiesResponse::new(
+        client.scheduler_name(),
+        client.resource_type_schedulers(),
+        client.unregistered_agent_policy(),
+        client.upcoming_maintenance(),
+        client.preemption_policy().clone(),
+        client.fair_share_policy().clone(),
+        client.backoff_policy(),
+    )))
+}
+
+/// The compatibility matrices conflicts are decided with, after overrides
+async fn compatibility_matrix(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<MatrixExplanation>> {
+    let client = state.lock().await;
+    Json(ApiResponse::ok(client.conflict_engine().explain_matrix()))
+}
+
 async fn register_agent(
     State(state): State<AppState>,
     Json(req): Json<RegisterAgentRequest>,
@@ -109,45 +475,163 @@ async fn register_agent(
     if req.agent_id.is_empty() {
         return (
             StatusCode::BAD_REQUEST,
-            Json(ApiResponse::err("agent_id is required")),
+            Json(ApiResponse::err(
+                ReasonCode::InvalidRequest,
+                "agent_id is required",
+            )),
         );
     }
 
     let mut client = state.lock().await;
-    client.register_agent(&req.agent_id, req.priority);
-    tracing::info!(agent_id = %req.agent_id, priority = req.priority, "Agent registered");
+    client.register_agent_in_class(&req.agent_id, req.class, req.priority);
+    client.set_agent_labels(&req.agent_id, req.labels);
+    let priority = Priority::new(req.class, req.priority);
+    tracing::info!(agent_id = %req.agent_id, %priority, "Agent registered");
     (
         StatusCode::CREATED,
         Json(ApiResponse::ok(format!(
             "Agent '{}' registered with priority {}",
-            req.agent_id, req.priority
+            req.agent_id, priority
         ))),
     )
 }
 
+async fn get_agent(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<AgentInfo>>) {
+    let client = state.lock().await;
+    if !client.is_known_agent(&id) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Agent '{}' not found", id),
+            )),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::ok(AgentInfo {
+            priority: client.agent_priority(&id).map(|p| p.timestamp),
+            class: client.agent_priority(&id).map(|p| p.class),
+            aging_boost: client.aging_boost(&id),
+            health: client.agent_health(&id),
+            labels: client.agent_labels(&id),
+            agent_id: id,
+        })),
+    )
+}
+
+/// An orchestrator promotes or demotes a registered agent mid-run
+async fn update_agent_priority(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdatePriorityRequest>,
+) -> (StatusCode, Json<ApiResponse<AgentInfo>>) {
+    let mut client = state.lock().await;
+    let Some(previous) = client.update_agent_priority(&id, req.priority) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Agent '{}' is not registered", id),
+            )),
+        );
+    };
+    tracing::info!(
+        agent_id = %id,
+        previous,
+        priority = req.priority,
+        "Agent priority updated"
+    );
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::ok(AgentInfo {
+            priority: client.agent_priority(&id).map(|p| p.timestamp),
+            class: client.agent_priority(&id).map(|p| p.class),
+            aging_boost: client.aging_boost(&id),
+            health: client.agent_health(&id),
+            labels: client.agent_labels(&id),
+            agent_id: id,
+        })),
+    )
+}
+
+async fn poll_events(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<EventsQuery>,
+) -> Json<ApiResponse<FeedBatch>> {
+    let mut client = state.lock().await;
+    Json(ApiResponse::ok(client.poll_events(&id, &query.session_id)))
+}
+
+async fn agent_timeline(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<TimelineParams>,
+) -> (StatusCode, Json<ApiResponse<Vec<ActivityEntry>>>) {
+    let query = match params.to_query() {
+        Ok(query) => query,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+            )
+        }
+    };
+    let client = state.lock().await;
+    (
+        StatusCode::OK,
+        Json(ApiResponse::ok(client.agent_timeline(&id, &query))),
+    )
+}
+
 async fn acquire_lease(
     State(state): State<AppState>,
+    Extension(acquires): Extension<Arc<AcquireCoalescer>>,
     Json(req): Json<AcquireLeaseRequest>,
 ) -> (StatusCode, Json<serde_json::Value>) {
     // Validate request
-    if let Err(e) = req.validate() {
+    let paths = state.lock().await.path_normalization(&req.agent_id).clone();
+    if let Err(e) = req.validate(&paths) {
         return (
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({
                 "success": false,
                 "error": e,
+                "code": ReasonCode::InvalidRequest,
             })),
         );
     }
 
+    // Concurrent retries of the same acquisition share its decision
+    let key = (
+        req.agent_id.clone(),
+        req.session_id.clone(),
+        req.resource_type.to_uppercase(),
+        req.resource_path.clone(),
+        req.predicate.to_uppercase(),
+    );
+    acquires.run(key, || acquire_lease_once(&state, &req)).await
+}
+
+async fn acquire_lease_once(
+    state: &AppState,
+    req: &AcquireLeaseRequest,
+) -> (StatusCode, Json<serde_json::Value>) {
     let mut client = state.lock().await;
-    let result = client.acquire_lease(
+    let result = client.acquire_lease_with_deadline(
         &req.agent_id,
         &req.session_id,
         &req.resource_type,
         &req.resource_path,
         &req.predicate,
         req.ttl,
+        req.deadline,
     );
 
     match result {
@@ -155,7 +639,7 @@ async fn acquire_lease(
             tracing::info!(
                 agent_id = %req.agent_id,
                 lease_id = %lease.id,
-                resource = %format!("{}:{}", req.resource_type, req.resource_path),
+                resource = %lease.resource.key(),
                 "Lease acquired"
             );
             (
@@ -165,34 +649,228 @@ async fn acquire_lease(
                     "data": {
                         "lease_id": lease.id,
                         "agent_id": lease.agent_id,
-                        "resource": format!("{}:{}", req.resource_type, req.resource_path),
+                        "session_id": lease.session_id,
+                        "resource": lease.resource.key(),
                         "predicate": req.predicate.to_uppercase(),
                         "expires_at": lease.expires_at,
+                        "revision": lease.revision,
                     }
                 })),
             )
         }
         LeaseResult::Failure {
-            reason, wait_time, ..
+            reason,
+            wait_time,
+            queue_position,
+            request_id,
+            holders,
+            ..
         } => {
-            let reason_str = match reason {
-                LeaseFailureReason::Wait => "WAIT",
-                LeaseFailureReason::Die => "DIE",
-                LeaseFailureReason::Conflict => "CONFLICT",
-                LeaseFailureReason::ResourceLocked => "RESOURCE_LOCKED",
-                LeaseFailureReason::SessionExpired => "SESSION_EXPIRED",
-            };
+            let reason_str = reason.as_str();
             tracing::info!(
                 agent_id = %req.agent_id,
                 reason = reason_str,
                 "Lease denied"
             );
+            let status = failure_status(reason);
+            let resource =
+                ResourceRef::new(parse_resource_type(&req.resource_type), &req.resource_path);
+            let predicate = parse_predicate(&req.predicate);
+            // Name the leases in the way, so the agent can narrow its request
+            let blockers = match reason {
+                LeaseFailureReason::Conflict
+                | LeaseFailureReason::Wait
+                | LeaseFailureReason::Die => {
+                    client.lease_blockers(&req.agent_id, &req.session_id, &resource, predicate)
+                }
+                _ => Vec::new(),
+            };
+            // ...and where it could go instead
+            let fallbacks = match reason {
+                LeaseFailureReason::Die => {
+                    client.fallbacks(&req.agent_id, &req.session_id, &resource, predicate)
+                }
+                _ => Vec::new(),
+            };
+            let mut body = serde_json::json!({
+                "success": false,
+                "reason": reason_str,
+                "code": reason.code(),
+                "wait_time": wait_time,
+                "queue_position": queue_position,
+                "request_id": request_id,
+                "holders": holders,
+                "blockers": blockers,
+                "fallbacks": fallbacks,
+            });
+            // The policy script's reason
+            if let Some(message) = client.last_policy_denial() {
+                body["message"] = message.into();
+            }
+            (status, Json(body))
+        }
+    }
+}
+
+async fn reserve_leases(
+    State(state): State<AppState>,
+    Json(req): Json<ReserveLeasesRequest>,
+) -> (StatusCode, Json<ApiResponse<Vec<ReservationInfo>>>) {
+    let paths = state.lock().await.path_normalization(&req.agent_id).clone();
+    if let Err(e) = req.validate(&paths) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        );
+    }
+
+    let resources: Vec<_> = req
+        .resources
+        .iter()
+        .map(|item| {
             (
-                StatusCode::CONFLICT,
+                klock_core::types::ResourceRef::new(
+                    parse_resource_type(&item.resource_type),
+                    &item.resource_path,
+                ),
+                parse_predicate(&item.predicate),
+            )
+        })
+        .collect();
+
+    let mut client = state.lock().await;
+    match client.pre_acquire(
+        &req.agent_id,
+        &req.session_id,
+        &resources,
+        req.ttl,
+        req.activate_by,
+    ) {
+        Ok(reserved) => {
+            tracing::info!(
+                agent_id = %req.agent_id,
+                count = reserved.len(),
+                activate_by = req.activate_by,
+                "Leases reserved"
+            );
+            (
+                StatusCode::CREATED,
+                Json(ApiResponse::ok(
+                    reserved
+                        .iter()
+                        .map(|l| ReservationInfo {
+                            lease_id: l.id.clone(),
+                            session_id: l.session_id.clone(),
+                            resource: l.resource.key(),
+                            predicate: format!("{:?}", l.predicate),
+                            activate_by: l.expires_at,
+                        })
+                        .collect(),
+                )),
+            )
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+async fn activate_lease(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let result = state.lock().await.activate_lease(&id);
+    activation_response(&id, result)
+}
+
+/// Approve a co-signing request, then activate its lease
+async fn cosign_lease(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<CosignLeaseRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let result = state.lock().await.cosign(&id, &req.agent_id);
+    match result {
+        Ok(result) => {
+            tracing::warn!(lease_id = %id, cosigner = %req.agent_id, "Lease co-signed");
+            activation_response(&id, result)
+        }
+        Err(e) => {
+            let status = match e {
+                CosignError::NotPending => StatusCode::NOT_FOUND,
+                _ => StatusCode::FORBIDDEN,
+            };
+            (
+                status,
+                Json(serde_json::json!(ApiResponse::<String>::err(
+                    e.code(),
+                    format!("Cannot co-sign lease '{}': {}", id, e),
+                ))),
+            )
+        }
+    }
+}
+
+/// Co-signing requests awaiting approval or activation, oldest first
+async fn list_cosign_requests(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<CosignRequest>>> {
+    Json(ApiResponse::ok(state.lock().await.pending_cosigns()))
+}
+
+/// The status of a refused acquire or activation
+fn failure_status(reason: LeaseFailureReason) -> StatusCode {
+    match reason {
+        LeaseFailureReason::TooManyRetries => StatusCode::TOO_MANY_REQUESTS,
+        LeaseFailureReason::Quarantined | LeaseFailureReason::MaintenanceWindow => {
+            StatusCode::LOCKED
+        }
+        LeaseFailureReason::PolicyDenied | LeaseFailureReason::UnregisteredAgent => {
+            StatusCode::FORBIDDEN
+        }
+        LeaseFailureReason::CapacityExceeded => StatusCode::SERVICE_UNAVAILABLE,
+        LeaseFailureReason::SessionExpired => StatusCode::GONE,
+        // Not refused: a pending lease awaits a co-signer
+        LeaseFailureReason::CosignRequired => StatusCode::ACCEPTED,
+        _ => StatusCode::CONFLICT,
+    }
+}
+
+fn activation_response(id: &str, result: LeaseResult) -> (StatusCode, Json<serde_json::Value>) {
+    match result {
+        LeaseResult::Success { lease } => {
+            tracing::info!(lease_id = %lease.id, agent_id = %lease.agent_id, "Reservation activated");
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "success": true,
+                    "data": {
+                        "lease_id": lease.id,
+                        "agent_id": lease.agent_id,
+                        "resource": lease.resource.key(),
+                        "predicate": format!("{:?}", lease.predicate),
+                        "expires_at": lease.expires_at,
+                        "revision": lease.revision,
+                    }
+                })),
+            )
+        }
+        LeaseResult::Failure {
+            reason,
+            wait_time,
+            holders,
+            ..
+        } => {
+            tracing::info!(lease_id = %id, reason = reason.as_str(), "Reservation activation denied");
+            (
+                failure_status(reason),
                 Json(serde_json::json!({
                     "success": false,
-                    "reason": reason_str,
+                    "reason": reason.as_str(),
+                    "code": reason.code(),
                     "wait_time": wait_time,
+                    "holders": holders,
                 })),
             )
         }
@@ -202,89 +880,476 @@ async fn acquire_lease(
 async fn release_lease(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Json<ApiResponse<String>> {
+    Query(params): Query<RevisionParams>,
+) -> (StatusCode, Json<serde_json::Value>) {
     let mut client = state.lock().await;
-    if client.release_lease(&id) {
+    let released = match params.expected_revision {
+        Some(revision) => match client.release_lease_if(&id, revision) {
+            Ok(_) => true,
+            Err(e) => return mutation_refused(&id, &e),
+        },
+        None => client.release_lease(&id),
+    };
+    if released {
         tracing::info!(lease_id = %id, "Lease released");
-        Json(ApiResponse::ok(format!("Lease '{}' released", id)))
-    } else {
-        Json(ApiResponse::<String>::err(format!(
-            "Lease '{}' not found",
-            id
-        )))
-    }
-}
-
-async fn heartbeat_lease(
-    State(state): State<AppState>,
-    Path(id): Path<String>,
-) -> (StatusCode, Json<ApiResponse<HeartbeatResponse>>) {
-    let mut client = state.lock().await;
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64;
-
-    if client.heartbeat_lease(&id, now) {
-        tracing::info!(lease_id = %id, "Lease heartbeat renewed");
         (
             StatusCode::OK,
-            Json(ApiResponse::ok(HeartbeatResponse {
-                renewed: true,
-                lease_id: id,
-            })),
+            Json(serde_json::json!(ApiResponse::ok(format!(
+                "Lease '{}' released",
+                id
+            )))),
         )
     } else {
         (
-            StatusCode::NOT_FOUND,
-            Json(ApiResponse::err(format!(
-                "Lease '{}' not found or expired",
-                id
+            StatusCode::OK,
+            Json(serde_json::json!(ApiResponse::<String>::err(
+                ReasonCode::LeaseNotFound,
+                format!("Lease '{}' not found", id),
             ))),
         )
     }
 }
 
-async fn list_leases(State(state): State<AppState>) -> Json<ApiResponse<Vec<ActiveLeaseInfo>>> {
-    let client = state.lock().await;
-    let leases: Vec<ActiveLeaseInfo> = client
-        .get_active_leases()
+async fn release_many_checked(
+    State(state): State<AppState>,
+    Json(req): Json<ReleaseManyRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let mut client = state.lock().await;
+    let batch = match client.release_many_checked(&req.lease_ids, &req.conditions) {
+        Ok(batch) => batch,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!(ApiResponse::<()>::err(
+                    ReasonCode::InvalidRequest,
+                    e
+                ))),
+            )
+        }
+    };
+    if batch.released {
+        tracing::info!(leases = batch.results.len(), "Leases released");
+        return (
+            StatusCode::OK,
+            Json(serde_json::json!(ApiResponse::ok(batch))),
+        );
+    }
+    // The first lease that held the batch back names the refusal
+    let blocker = batch
+        .results
         .iter()
-        .map(|l| ActiveLeaseInfo {
-            id: l.id.clone(),
-            agent_id: l.agent_id.clone(),
-            resource: l.resource.key(),
-            predicate: format!("{:?}", l.predicate),
-            expires_at: l.expires_at,
-        })
-        .collect();
-    Json(ApiResponse::ok(leases))
+        .find_map(|r| Some((r.lease_id.clone(), r.status.code()?)));
+    (
+        StatusCode::CONFLICT,
+        Json(serde_json::json!({
+            "success": false,
+            "error": match &blocker {
+                Some((lease_id, _)) => format!("Nothing released: lease '{}' held the batch back", lease_id),
+                None => "Nothing released".to_string(),
+            },
+            "code": blocker.map(|(_, code)| code),
+            "data": batch,
+        })),
+    )
 }
 
-async fn declare_intent(
+async fn heartbeat_lease(
     State(state): State<AppState>,
-    Json(req): Json<DeclareIntentRequest>,
+    Path(id): Path<String>,
+    Query(params): Query<HeartbeatParams>,
 ) -> (StatusCode, Json<serde_json::Value>) {
-    // Validate request
-    if let Err(e) = req.validate() {
+    let mut client = state.lock().await;
+    let report =
+        match client.heartbeat_lease_report(&id, params.expected_revision, params.client_time) {
+            Ok(report) => report,
+            Err(e) => return mutation_refused(&id, &e),
+        };
+    if let Some(warning) = &report.drift_warning {
+        tracing::warn!(lease_id = %id, "{}", warning);
+    }
+    if report.renewed {
+        tracing::info!(lease_id = %id, "Lease heartbeat renewed");
         return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({
-                "success": false,
-                "error": e,
-            })),
+            StatusCode::OK,
+            Json(serde_json::json!(ApiResponse::ok(report))),
         );
     }
 
-    let mut client = state.lock().await;
+    // Not renewed: say what became of the lease
+    let error = match report.state {
+        Some(state) => format!("Lease '{}' is {:?}, not renewed", id, state),
+        None => format!("Lease '{}' not found", id),
+    };
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!(ApiResponse {
+            success: false,
+            data: report.state.map(|_| report),
+            error: Some(error),
+            code: Some(ReasonCode::LeaseNotFound),
+        })),
+    )
+}
 
-    // Build SPOTriples from the request
-    let intents: Vec<klock_core::types::SPOTriple> = req
-        .intents
-        .iter()
-        .map(|item| {
-            let id = client.next_id();
-            klock_core::types::SPOTriple {
+async fn revoke_lease(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<RevokeParams>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let mut client = state.lock().await;
+    match client.revoke_lease(&id, params.expected_revision, params.reason.as_deref()) {
+        Ok(lease) => {
+            tracing::warn!(lease_id = %id, agent_id = %lease.agent_id, "Lease revoked");
+            (
+                StatusCode::OK,
+                Json(serde_json::json!(ApiResponse::ok(ActiveLeaseInfo {
+                    id: lease.id,
+                    agent_id: lease.agent_id,
+                    resource: lease.resource.key(),
+                    predicate: format!("{:?}", lease.predicate),
+                    expires_at: lease.expires_at,
+                    revision: lease.revision,
+                    // Revoked, not expiring
+                    expiring_soon: false,
+                }))),
+            )
+        }
+        Err(e) => mutation_refused(&id, &e),
+    }
+}
+
+/// A lease in any state, so a holder can find out whether and why it lost it
+async fn get_lease(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<Lease>>) {
+    match state.lock().await.get_lease(&id) {
+        Some(lease) => (StatusCode::OK, Json(ApiResponse::ok(lease))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::LeaseNotFound,
+                format!("Lease '{}' not found", id),
+            )),
+        ),
+    }
+}
+
+/// A senior agent revokes a junior's lease, as the preemption policy allows
+async fn preempt_lease(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<PreemptRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let mut client = state.lock().await;
+    match client.preempt_lease(&req.agent_id, &id, &req.reason) {
+        Ok(lease) => {
+            tracing::warn!(
+                lease_id = %id,
+                agent_id = %lease.agent_id,
+                preempted_by = %req.agent_id,
+                "Lease preempted"
+            );
+            (
+                StatusCode::OK,
+                Json(serde_json::json!(ApiResponse::ok(lease))),
+            )
+        }
+        Err(PreemptionError::Lease(e)) => mutation_refused(&id, &e),
+        Err(e) => {
+            let status = match e {
+                PreemptionError::UnknownAgent => StatusCode::NOT_FOUND,
+                _ => StatusCode::FORBIDDEN,
+            };
+            (
+                status,
+                Json(serde_json::json!(ApiResponse::<String>::err(
+                    e.code(),
+                    format!("Cannot preempt lease '{}': {}", id, e),
+                ))),
+            )
+        }
+    }
+}
+
+/// The response to a refused revision-checked lease mutation: 409 with the
+/// lease's current revision if it moved on, 404 otherwise
+fn mutation_refused(
+    lease_id: &str,
+    error: &LeaseMutationError,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let (status, current_revision) = match error {
+        LeaseMutationError::Conflict { current_revision } => {
+            (StatusCode::CONFLICT, Some(*current_revision))
+        }
+        _ => (StatusCode::NOT_FOUND, None),
+    };
+    (
+        status,
+        Json(serde_json::json!({
+            "success": false,
+            "error": format!("Lease '{}': {}", lease_id, error),
+            "code": error.code(),
+            "current_revision": current_revision,
+        })),
+    )
+}
+
+async fn list_waiters(State(state): State<AppState>) -> Json<ApiResponse<Vec<Waiter>>> {
+    let client = state.lock().await;
+    Json(ApiResponse::ok(client.lease_waiters()))
+}
+
+/// Withdraw one queued acquire by the `request_id` it was queued under
+async fn cancel_waiter(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<Vec<Waiter>>>) {
+    let cancelled = state
+        .lock()
+        .await
+        .cancel_pending(&WaiterFilter::Id(id.clone()));
+    if cancelled.is_empty() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("No queued request '{}'", id),
+            )),
+        );
+    }
+    tracing::info!(request_id = %id, "Queued request cancelled");
+    (StatusCode::OK, Json(ApiResponse::ok(cancelled)))
+}
+
+/// Withdraw an agent's queued acquires for one resource
+async fn cancel_agent_waiters(
+    State(state): State<AppState>,
+    Query(query): Query<CancelWaitersQuery>,
+) -> (StatusCode, Json<ApiResponse<Vec<Waiter>>>) {
+    if let Err(e) = validate_resource_type(&query.resource_type) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        );
+    }
+    let resource = ResourceRef::new(
+        parse_resource_type(&query.resource_type),
+        &query.resource_path,
+    );
+    let cancelled = state.lock().await.cancel_pending(&WaiterFilter::Resource {
+        agent_id: query.agent_id.clone(),
+        resource: resource.clone(),
+    });
+    tracing::info!(
+        agent_id = %query.agent_id,
+        resource = %resource.key(),
+        cancelled = cancelled.len(),
+        "Queued requests cancelled"
+    );
+    (StatusCode::OK, Json(ApiResponse::ok(cancelled)))
+}
+
+/// Lease changes after `cursor`; with `wait_ms`, blocks until there are some
+/// or the wait runs out.
+async fn firehose(
+    State(state): State<AppState>,
+    Query(query): Query<FirehoseQuery>,
+) -> (StatusCode, Json<ApiResponse<FirehosePage>>) {
+    const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+    let limit = query.limit.unwrap_or(500).clamp(1, 5000);
+    let timeout = std::time::Duration::from_millis(query.wait_ms.unwrap_or(0).min(300_000));
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let page = state.lock().await.firehose(query.cursor, limit);
+        let Some(page) = page else {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::err(
+                    ReasonCode::NotFound,
+                    "The firehose is disabled (--firehose-capacity 0)",
+                )),
+            );
+        };
+        if !page.entries.is_empty() || page.missed > 0 || tokio::time::Instant::now() >= deadline {
+            return (StatusCode::OK, Json(ApiResponse::ok(page)));
+        }
+        tokio::time::sleep(POLL_INTERVAL.min(deadline - tokio::time::Instant::now())).await;
+    }
+}
+
+async fn list_leases(State(state): State<AppState>) -> Json<ApiResponse<Vec<ActiveLeaseInfo>>> {
+    let client = state.lock().await;
+    let leases: Vec<ActiveLeaseInfo> = client
+        .get_active_leases()
+        .iter()
+        .map(|l| ActiveLeaseInfo {
+            id: l.id.clone(),
+            agent_id: l.agent_id.clone(),
+            resource: l.resource.key(),
+            predicate: format!("{:?}", l.predicate),
+            expires_at: l.expires_at,
+            revision: l.revision,
+            expiring_soon: client.is_expiring_soon(l),
+        })
+        .collect();
+    Json(ApiResponse::ok(leases))
+}
+
+async fn declare_intent(
+    State(state): State<AppState>,
+    Json(req): Json<DeclareIntentRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    // Validate request
+    let paths = state.lock().await.path_normalization(&req.agent_id).clone();
+    if let Err(e) = req.validate(&paths) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e,
+                "code": ReasonCode::InvalidRequest,
+            })),
+        );
+    }
+
+    let mut client = state.lock().await;
+    let manifest = build_manifest(&mut client, req);
+    let verdict = client.declare_intent(&manifest);
+    (StatusCode::OK, Json(serde_json::json!(verdict)))
+}
+
+/// Withdraw one granted intent, leaving any lease acquired for it
+async fn withdraw_intent(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<SPOTriple>>) {
+    let Some(intent) = state.lock().await.withdraw_intent(&id) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("No active intent '{}'", id),
+            )),
+        );
+    };
+    tracing::info!(intent_id = %id, agent_id = %intent.subject, "Intent withdrawn");
+    (StatusCode::OK, Json(ApiResponse::ok(intent)))
+}
+
+/// Withdraw every granted intent of a session
+async fn withdraw_session_intents(
+    State(state): State<AppState>,
+    Query(query): Query<WithdrawIntentsQuery>,
+) -> (StatusCode, Json<ApiResponse<Vec<SPOTriple>>>) {
+    if query.agent_id.is_empty() || query.session_id.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(
+                ReasonCode::InvalidRequest,
+                "agent_id and session_id are required",
+            )),
+        );
+    }
+    let withdrawn = state
+        .lock()
+        .await
+        .withdraw_session_intents(&query.agent_id, &query.session_id);
+    tracing::info!(
+        agent_id = %query.agent_id,
+        session_id = %query.session_id,
+        withdrawn = withdrawn.len(),
+        "Session intents withdrawn"
+    );
+    (StatusCode::OK, Json(ApiResponse::ok(withdrawn)))
+}
+
+/// Declare several manifests of one agent, all or nothing
+async fn declare_intents_atomic(
+    State(state): State<AppState>,
+    Json(req): Json<AtomicIntentRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let paths = state
+        .lock()
+        .await
+        .path_normalization(req.manifests.first().map_or("", |m| m.agent_id.as_str()))
+        .clone();
+    if let Err(e) = req.validate(&paths) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e,
+                "code": ReasonCode::InvalidRequest,
+            })),
+        );
+    }
+
+    let mut client = state.lock().await;
+    let manifests: Vec<_> = req
+        .manifests
+        .into_iter()
+        .map(|manifest| build_manifest(&mut client, manifest))
+        .collect();
+    match client.declare_intents_atomic(&manifests) {
+        Ok(verdict) => (StatusCode::OK, Json(serde_json::json!(verdict))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e,
+                "code": ReasonCode::InvalidRequest,
+            })),
+        ),
+    }
+}
+
+/// Declare a manifest and, if granted, lease every intent, all or nothing
+async fn acquire_manifest(
+    State(state): State<AppState>,
+    Json(req): Json<AcquireManifestRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let paths = state
+        .lock()
+        .await
+        .path_normalization(&req.manifest.agent_id)
+        .clone();
+    if let Err(e) = req.validate(&paths) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e,
+                "code": ReasonCode::InvalidRequest,
+            })),
+        );
+    }
+
+    let mut client = state.lock().await;
+    let manifest = build_manifest(&mut client, req.manifest);
+    let acquisition = client.acquire_manifest(&manifest, req.ttl);
+    if acquisition.acquired() {
+        tracing::info!(
+            agent_id = %manifest.agent_id,
+            leases = acquisition.leases.len(),
+            "Manifest acquired"
+        );
+    }
+    (StatusCode::OK, Json(serde_json::json!(acquisition)))
+}
+
+/// Build the manifest a validated request declares
+fn build_manifest(
+    client: &mut KlockClient,
+    req: DeclareIntentRequest,
+) -> klock_core::state::IntentManifest {
+    // Build SPOTriples from the request
+    let intents: Vec<klock_core::types::SPOTriple> = req
+        .intents
+        .iter()
+        .map(|item| {
+            let id = client.next_id();
+            klock_core::types::SPOTriple {
                 id,
                 subject: req.agent_id.clone(),
                 predicate: match item.predicate.to_uppercase().as_str() {
@@ -294,6 +1359,7 @@ async fn declare_intent(
                     "DELETES" => klock_core::types::Predicate::Deletes,
                     "DEPENDS_ON" => klock_core::types::Predicate::DependsOn,
                     "RENAMES" => klock_core::types::Predicate::Renames,
+                    "EXCLUDES" => klock_core::types::Predicate::Excludes,
                     _ => klock_core::types::Predicate::Consumes, // validated above
                 },
                 object: klock_core::types::ResourceRef::new(
@@ -310,20 +1376,114 @@ async fn declare_intent(
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
                     .as_millis() as u64,
-                confidence: klock_core::types::Confidence::High,
+                confidence: item
+                    .confidence
+                    .as_deref()
+                    .and_then(parse_confidence)
+                    .unwrap_or(klock_core::types::Confidence::High),
                 session_id: req.session_id.clone(),
+                renamed_to: item.renamed_to.clone(),
+                expires_at: None,
+                long_lived: item.long_lived,
             }
         })
         .collect();
 
-    let manifest = klock_core::state::IntentManifest {
+    klock_core::state::IntentManifest {
         session_id: req.session_id,
         agent_id: req.agent_id,
         intents,
+    }
+}
+
+async fn declare_intent_from_template(
+    State(state): State<AppState>,
+    Json(req): Json<TemplateIntentRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    if let Err(e) = req.validate() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e,
+                "code": ReasonCode::InvalidRequest,
+            })),
+        );
+    }
+
+    let mut client = state.lock().await;
+    let result = match &req.template {
+        TemplateSpec::Name(name) => {
+            if client.template(name).is_none() {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(serde_json::json!({
+                        "success": false,
+                        "error": format!("Template '{}' not found", name),
+                        "code": ReasonCode::NotFound,
+                    })),
+                );
+            }
+            client.declare_intent_from_template(&req.agent_id, &req.session_id, name, &req.vars)
+        }
+        TemplateSpec::Inline(template) => {
+            client.declare_intent_with_template(&req.agent_id, &req.session_id, template, &req.vars)
+        }
     };
+    match result {
+        Ok(verdict) => (StatusCode::OK, Json(serde_json::json!(verdict))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e,
+                "code": ReasonCode::InvalidRequest,
+            })),
+        ),
+    }
+}
 
-    let verdict = client.declare_intent(&manifest);
-    (StatusCode::OK, Json(serde_json::json!(verdict)))
+async fn register_template(
+    State(state): State<AppState>,
+    Json(template): Json<ManifestTemplate>,
+) -> (StatusCode, Json<ApiResponse<ManifestTemplate>>) {
+    let mut client = state.lock().await;
+    match client.register_template(template.clone()) {
+        Ok(()) => {
+            tracing::info!(template = %template.name, "Template registered");
+            (StatusCode::CREATED, Json(ApiResponse::ok(template)))
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+async fn list_templates(State(state): State<AppState>) -> Json<ApiResponse<Vec<ManifestTemplate>>> {
+    let client = state.lock().await;
+    Json(ApiResponse::ok(client.templates()))
+}
+
+async fn remove_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<ApiResponse<String>>) {
+    let mut client = state.lock().await;
+    if client.remove_template(&name) {
+        (
+            StatusCode::OK,
+            Json(ApiResponse::ok(format!("Template '{}' removed", name))),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Template '{}' not found", name),
+            )),
+        )
+    }
 }
 
 async fn evict_expired(State(state): State<AppState>) -> Json<ApiResponse<EvictResponse>> {
@@ -333,40 +1493,905 @@ async fn evict_expired(State(state): State<AppState>) -> Json<ApiResponse<EvictR
     Json(ApiResponse::ok(EvictResponse { evicted }))
 }
 
-// ─── Storage Backend Selection ──────────────────────────────────────────────
-
-fn create_client(storage: &str) -> KlockClient {
-    if storage == "memory" {
-        tracing::info!("💾 Storage backend: in-memory (leases will not persist)");
-        KlockClient::new()
-    } else if let Some(path) = storage.strip_prefix("sqlite:") {
-        #[cfg(feature = "sqlite")]
-        {
-            tracing::info!("💾 Storage backend: SQLite ({})", path);
-            match KlockClient::with_sqlite(path) {
-                Ok(client) => client,
-                Err(e) => {
-                    tracing::error!("Failed to open SQLite: {}. Falling back to in-memory.", e);
-                    KlockClient::new()
-                }
-            }
-        }
-        #[cfg(not(feature = "sqlite"))]
-        {
-            tracing::error!(
-                "SQLite storage requested but `sqlite` feature is not enabled. \
-                 Rebuild with: cargo build --features sqlite"
-            );
-            tracing::warn!("Falling back to in-memory storage.");
-            let _ = path;
-            KlockClient::new()
-        }
-    } else {
-        tracing::error!(
-            "Unknown storage backend: '{}'. Use 'memory' or 'sqlite:<path>'",
-            storage
+async fn create_barrier(
+    State(state): State<AppState>,
+    Json(req): Json<CreateBarrierRequest>,
+) -> (StatusCode, Json<ApiResponse<BarrierStatus>>) {
+    let mut client = state.lock().await;
+    if client.barrier_status(&req.name).is_some() {
+        return (
+            StatusCode::CONFLICT,
+            Json(ApiResponse::err(
+                ReasonCode::AlreadyExists,
+                format!("Barrier '{}' already exists", req.name),
+            )),
         );
-        tracing::warn!("Falling back to in-memory storage.");
-        KlockClient::new()
     }
+    match client.create_barrier(&req.name, req.parties) {
+        Ok(status) => {
+            tracing::info!(barrier = %req.name, parties = req.parties, "Barrier created");
+            (StatusCode::CREATED, Json(ApiResponse::ok(status)))
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+async fn barrier_status(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<ApiResponse<BarrierStatus>>) {
+    let client = state.lock().await;
+    match client.barrier_status(&name) {
+        Some(status) => (StatusCode::OK, Json(ApiResponse::ok(status))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Barrier '{}' not found", name),
+            )),
+        ),
+    }
+}
+
+async fn remove_barrier(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<ApiResponse<String>>) {
+    let mut client = state.lock().await;
+    if client.remove_barrier(&name) {
+        tracing::info!(barrier = %name, "Barrier removed");
+        (
+            StatusCode::OK,
+            Json(ApiResponse::ok(format!("Barrier '{}' removed", name))),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Barrier '{}' not found", name),
+            )),
+        )
+    }
+}
+
+async fn begin_session(
+    State(state): State<AppState>,
+    Json(req): Json<BeginSessionRequest>,
+) -> (StatusCode, Json<ApiResponse<Session>>) {
+    let mut client = state.lock().await;
+    match client.begin_session(&req.agent_id, &req.session_id, req.ttl_ms) {
+        Ok(session) => {
+            tracing::info!(agent_id = %req.agent_id, session_id = %session.session_id, ttl_ms = req.ttl_ms, "Session begun");
+            (StatusCode::OK, Json(ApiResponse::ok(session)))
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+async fn get_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<Session>>) {
+    let client = state.lock().await;
+    match client.session(&id) {
+        Some(session) => (StatusCode::OK, Json(ApiResponse::ok(session))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Session '{}' not found", id),
+            )),
+        ),
+    }
+}
+
+async fn end_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<SessionEnd>>) {
+    let mut client = state.lock().await;
+    match client.end_session(&id) {
+        Some(end) => {
+            tracing::info!(
+                session_id = %id,
+                leases = end.leases_released.len(),
+                intents = end.intents_withdrawn,
+                "Session ended"
+            );
+            (StatusCode::OK, Json(ApiResponse::ok(end)))
+        }
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Session '{}' not found", id),
+            )),
+        ),
+    }
+}
+
+async fn declare_namespace(
+    State(state): State<AppState>,
+    Json(req): Json<DeclareNamespaceRequest>,
+) -> (StatusCode, Json<ApiResponse<Namespace>>) {
+    let mut client = state.lock().await;
+    match client.declare_namespace(&req.name, req.expires_in) {
+        Ok(namespace) => {
+            tracing::info!(namespace = %req.name, expires_at = ?namespace.expires_at, "Namespace declared");
+            (StatusCode::OK, Json(ApiResponse::ok(namespace)))
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+async fn list_namespaces(State(state): State<AppState>) -> Json<ApiResponse<Vec<Namespace>>> {
+    let client = state.lock().await;
+    Json(ApiResponse::ok(client.namespaces()))
+}
+
+async fn get_namespace(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<ApiResponse<Namespace>>) {
+    let client = state.lock().await;
+    match client.namespace(&name) {
+        Some(namespace) => (StatusCode::OK, Json(ApiResponse::ok(namespace.clone()))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Namespace '{}' not found", name),
+            )),
+        ),
+    }
+}
+
+async fn delete_namespace(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<ApiResponse<NamespaceReaping>>) {
+    let mut client = state.lock().await;
+    match client.delete_namespace(&name) {
+        Some(reaping) => {
+            tracing::info!(
+                namespace = %name,
+                agents = reaping.agents.len(),
+                leases = reaping.leases_released.len(),
+                "Namespace deleted"
+            );
+            (StatusCode::OK, Json(ApiResponse::ok(reaping)))
+        }
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Namespace '{}' not found", name),
+            )),
+        ),
+    }
+}
+
+async fn arrive_barrier(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<ArriveRequest>,
+) -> (StatusCode, Json<ApiResponse<BarrierStatus>>) {
+    if req.agent_id.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(
+                ReasonCode::InvalidRequest,
+                "agent_id is required",
+            )),
+        );
+    }
+    let mut client = state.lock().await;
+    match client.arrive(&name, &req.agent_id) {
+        Ok(status) => {
+            if status.tripped_at.is_some() && status.arrived.last() == Some(&req.agent_id) {
+                tracing::info!(barrier = %name, agent_id = %req.agent_id, "Barrier tripped");
+            }
+            (StatusCode::OK, Json(ApiResponse::ok(status)))
+        }
+        Err(e) => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(ReasonCode::NotFound, e)),
+        ),
+    }
+}
+
+/// Long-poll until the barrier trips or `timeout_ms` elapses, then return
+/// its status. Callers check `tripped` to tell the two apart.
+async fn wait_barrier(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<BarrierWaitQuery>,
+) -> (StatusCode, Json<ApiResponse<BarrierStatus>>) {
+    const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+    let timeout = std::time::Duration::from_millis(query.timeout_ms.unwrap_or(30_000).min(300_000));
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let status = state.lock().await.barrier_status(&name);
+        let Some(status) = status else {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::err(
+                    ReasonCode::NotFound,
+                    format!("Barrier '{}' not found", name),
+                )),
+            );
+        };
+        if status.tripped || tokio::time::Instant::now() >= deadline {
+            return (StatusCode::OK, Json(ApiResponse::ok(status)));
+        }
+        tokio::time::sleep(POLL_INTERVAL.min(deadline - tokio::time::Instant::now())).await;
+    }
+}
+
+async fn acquire_semaphore(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<AcquireSemaphoreRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let mut client = state.lock().await;
+    match client.acquire_semaphore(&req.agent_id, &name, req.permits, req.ttl) {
+        Ok(SemaphoreResult::Success { permit }) => {
+            tracing::info!(
+                agent_id = %req.agent_id,
+                semaphore = %name,
+                permit_id = %permit.id,
+                "Semaphore permit acquired"
+            );
+            (
+                StatusCode::CREATED,
+                Json(serde_json::json!({ "success": true, "data": permit })),
+            )
+        }
+        Ok(SemaphoreResult::Failure { reason, wait_time }) => {
+            tracing::info!(
+                agent_id = %req.agent_id,
+                semaphore = %name,
+                reason = reason.as_str(),
+                "Semaphore permit denied"
+            );
+            (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({
+                    "success": false,
+                    "reason": reason.as_str(),
+                    "code": reason.code(),
+                    "wait_time": wait_time,
+                })),
+            )
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e,
+                "code": ReasonCode::InvalidRequest,
+            })),
+        ),
+    }
+}
+
+async fn semaphore_status(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<ApiResponse<SemaphoreStatus>>) {
+    let client = state.lock().await;
+    match client.semaphore_status(&name) {
+        Some(status) => (StatusCode::OK, Json(ApiResponse::ok(status))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Semaphore '{}' has no holders", name),
+            )),
+        ),
+    }
+}
+
+async fn release_semaphore(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<String>>) {
+    let mut client = state.lock().await;
+    if client.release_semaphore(&id) {
+        tracing::info!(permit_id = %id, "Semaphore permit released");
+        (
+            StatusCode::OK,
+            Json(ApiResponse::ok(format!("Permit '{}' released", id))),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("Permit '{}' not found or expired", id),
+            )),
+        )
+    }
+}
+
+async fn quarantine_resource(
+    State(state): State<AppState>,
+    Json(req): Json<QuarantineRequest>,
+) -> (StatusCode, Json<ApiResponse<ResourceQuarantine>>) {
+    // Quarantines belong to no agent, so the client-wide rules apply
+    let paths = state.lock().await.path_normalization("").clone();
+    if let Err(e) = req.validate(&paths) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        );
+    }
+    let resource = ResourceRef::new(parse_resource_type(&req.resource_type), &req.resource_path);
+    let mut client = state.lock().await;
+    match client.quarantine_resource(resource, req.reason, req.ttl) {
+        Ok(quarantine) => {
+            tracing::warn!(
+                resource = %quarantine.resource.key(),
+                reason = quarantine.reason.as_deref().unwrap_or(""),
+                "Resource quarantined"
+            );
+            (StatusCode::CREATED, Json(ApiResponse::ok(quarantine)))
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+async fn list_quarantines(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<ResourceQuarantine>>> {
+    let client = state.lock().await;
+    Json(ApiResponse::ok(client.quarantines()))
+}
+
+async fn lift_quarantine(
+    State(state): State<AppState>,
+    Query(query): Query<QuarantineQuery>,
+) -> (StatusCode, Json<ApiResponse<String>>) {
+    if let Err(e) = validate_resource_type(&query.resource_type) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        );
+    }
+    let resource = ResourceRef::new(
+        parse_resource_type(&query.resource_type),
+        &query.resource_path,
+    );
+    let mut client = state.lock().await;
+    if client.lift_quarantine(&resource) {
+        tracing::info!(resource = %resource.key(), "Resource quarantine lifted");
+        (
+            StatusCode::OK,
+            Json(ApiResponse::ok(format!(
+                "Quarantine on {} lifted",
+                resource.key()
+            ))),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                format!("{} is not quarantined", resource.key()),
+            )),
+        )
+    }
+}
+
+async fn list_ceilings(State(state): State<AppState>) -> Json<ApiResponse<CeilingsResponse>> {
+    let client = state.lock().await;
+    Json(ApiResponse::ok(CeilingsResponse {
+        ceilings: client.priority_ceilings().to_vec(),
+        assignments: client.ceiling_assignments(),
+    }))
+}
+
+async fn state_projection(
+    State(state): State<AppState>,
+    Query(query): Query<ProjectionQuery>,
+) -> (StatusCode, Json<ApiResponse<StateProjection>>) {
+    let Some(at) = query.at else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(
+                ReasonCode::InvalidRequest,
+                "at is required (ms since epoch)",
+            )),
+        );
+    };
+    let client = state.lock().await;
+    (StatusCode::OK, Json(ApiResponse::ok(client.project(at))))
+}
+
+async fn state_snapshot(State(state): State<AppState>) -> Json<ApiResponse<StateSnapshot>> {
+    Json(ApiResponse::ok(state.lock().await.snapshot()))
+}
+
+async fn restore_snapshot(
+    State(state): State<AppState>,
+    Json(snapshot): Json<StateSnapshot>,
+) -> (StatusCode, Json<ApiResponse<usize>>) {
+    match state.lock().await.restore(snapshot) {
+        Ok(leases) => {
+            tracing::info!(leases, "State restored from a snapshot");
+            (StatusCode::OK, Json(ApiResponse::ok(leases)))
+        }
+        Err(e) => (
+            StatusCode::CONFLICT,
+            Json(ApiResponse::err(ReasonCode::AlreadyExists, e)),
+        ),
+    }
+}
+
+async fn resource_holders(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(query): Query<HoldersQuery>,
+) -> (StatusCode, Json<ApiResponse<Vec<Lease>>>) {
+    let Some(at) = query.at else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(
+                ReasonCode::InvalidRequest,
+                "at is required (ms since epoch)",
+            )),
+        );
+    };
+    let client = state.lock().await;
+    match client.holders_at(&key, at) {
+        Ok(holders) => (StatusCode::OK, Json(ApiResponse::ok(holders))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+async fn wait_for_graph(State(state): State<AppState>) -> Json<ApiResponse<WaitForResponse>> {
+    let client = state.lock().await;
+    let graph = client.wait_for_graph();
+    Json(ApiResponse::ok(WaitForResponse {
+        deadlock: graph.any_cycle(),
+        edges: graph.edges().to_vec(),
+    }))
+}
+
+async fn metrics(
+    Extension(acquires): Extension<Arc<AcquireCoalescer>>,
+) -> Json<ApiResponse<MetricsResponse>> {
+    Json(ApiResponse::ok(MetricsResponse {
+        acquire_coalescing: acquires.stats(),
+    }))
+}
+
+/// Recent scheduler decisions, oldest first, for explaining past verdicts
+async fn audit_records(
+    Extension(audit): Extension<Option<Arc<AuditLog>>>,
+    Query(params): Query<AuditParams>,
+) -> (StatusCode, Json<ApiResponse<Vec<DecisionRecord>>>) {
+    let Some(audit) = audit else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                "Decision auditing is disabled (--audit-capacity 0)",
+            )),
+        );
+    };
+    match params.to_query() {
+        Ok(query) => (StatusCode::OK, Json(ApiResponse::ok(audit.records(&query)))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+async fn memory_report(State(state): State<AppState>) -> Json<ApiResponse<MemoryReport>> {
+    let client = state.lock().await;
+    Json(ApiResponse::ok(client.memory_report()))
+}
+
+async fn compact(State(state): State<AppState>) -> Json<ApiResponse<CompactionReport>> {
+    let mut client = state.lock().await;
+    let report = client.compact();
+    tracing::info!(
+        leases = report.leases_removed,
+        intents = report.intents_removed,
+        health_records = report.health_records_removed,
+        namespaces = report.namespaces_reaped,
+        "State compacted"
+    );
+    Json(ApiResponse::ok(report))
+}
+
+// ─── Background Compaction ──────────────────────────────────────────────────
+
+async fn compaction_loop(state: AppState, interval_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+    // The first tick completes immediately; nothing to compact at startup
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let mut client = state.lock().await;
+        let report = client.compact();
+        if let Some(clock) = client.clock_report().filter(|clock| clock.suspect) {
+            tracing::warn!(
+                high_water = clock.high_water,
+                backward_jumps = clock.backward_jumps,
+                evictions_held = clock.evictions_held,
+                "⚠️  Wall clock jumped back; eviction held until it is past the high water mark"
+            );
+        }
+        drop(client);
+        if report.total() > 0 {
+            tracing::debug!(
+                leases = report.leases_removed,
+                intents = report.intents_removed,
+                health_records = report.health_records_removed,
+                namespaces = report.namespaces_reaped,
+                "Background compaction"
+            );
+        }
+    }
+}
+
+async fn usage_rollup_loop(state: AppState, interval_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+    // The first tick completes immediately; the period has only just begun
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match state.lock().await.roll_up_usage() {
+            Ok(agents) => tracing::debug!(agents, "Usage rolled up"),
+            Err(e) => tracing::warn!("Cannot store usage rollup, retrying next period: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "wasm-policy")]
+fn load_policy_script(
+    path: &str,
+    fuel: u64,
+    memory_mb: usize,
+) -> Result<Arc<dyn PolicyHook>, String> {
+    let limits = klock_core::policy_hook::WasmLimits {
+        fuel,
+        memory_bytes: memory_mb << 20,
+    };
+    Ok(Arc::new(klock_core::policy_hook::WasmPolicy::open(
+        path, limits,
+    )?))
+}
+
+#[cfg(not(feature = "wasm-policy"))]
+fn load_policy_script(
+    _path: &str,
+    _fuel: u64,
+    _memory_mb: usize,
+) -> Result<Arc<dyn PolicyHook>, String> {
+    Err("--policy-script needs a klock built with the wasm-policy feature".to_string())
+}
+
+/// Usage rollups go to the same SQLite database as the leases, or stay in
+/// memory
+fn open_usage_store(storage: &str) -> Arc<dyn UsageStore> {
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = storage.strip_prefix("sqlite:") {
+        match klock_core::usage::SqliteUsageStore::open(path) {
+            Ok(store) => return Arc::new(store),
+            Err(e) => tracing::error!(
+                "Failed to open usage rollups in SQLite: {}. Keeping them in memory.",
+                e
+            ),
+        }
+    }
+    #[cfg(not(feature = "sqlite"))]
+    let _ = storage;
+    Arc::new(InMemoryUsageStore::new())
+}
+
+/// Lease usage per team (or any other agent label), for chargeback
+async fn usage_report(
+    State(state): State<AppState>,
+    Query(query): Query<UsageReportQuery>,
+) -> (StatusCode, Json<ApiResponse<UsageReport>>) {
+    let period = match parse_period(query.period.as_deref().unwrap_or("24h")) {
+        Ok(period) => period,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+            )
+        }
+    };
+    let group_by = query.group_by.as_deref().unwrap_or(GROUP_BY_AGENT);
+    let client = state.lock().await;
+    let now = client.now();
+    match client.usage_report(group_by, now.saturating_sub(period), now) {
+        Some(Ok(report)) => (StatusCode::OK, Json(ApiResponse::ok(report))),
+        Some(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::err(ReasonCode::StorageUnavailable, e)),
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                "Usage metering is disabled (--usage-rollup-secs 0)",
+            )),
+        ),
+    }
+}
+
+// ─── Config Reload ──────────────────────────────────────────────────────────
+
+/// Swap `next` in for `previous` on a running client. Leases, intents and
+/// sessions are kept; templates registered through the API survive unless
+/// the file now defines one with the same name.
+fn apply_config(
+    client: &mut KlockClient,
+    previous: &LoadedConfig,
+    next: &LoadedConfig,
+) -> Result<(), String> {
+    // The only step that can fail goes first, so a refused reload changes nothing
+    client.set_maintenance_windows(next.maintenance_windows.clone())?;
+    // Validated when the file was loaded
+    client.set_priority_ceilings(next.priority_ceilings.clone())?;
+    client.set_fallback_groups(next.fallback_groups.clone());
+    // Validated when the file was loaded
+    client.set_cosign_rules(next.cosign_rules.clone())?;
+    for template in &previous.templates {
+        if !next.templates.iter().any(|t| t.name == template.name) {
+            client.remove_template(&template.name);
+        }
+    }
+    for template in &next.templates {
+        // Validated when the file was loaded
+        client.register_template(template.clone())?;
+    }
+    client.set_conflict_engine(next.conflict_engine.clone());
+    Ok(())
+}
+
+/// Re-read every config file and apply them all, or none if any is invalid.
+async fn reload(state: &AppState, reload: &ConfigReload) -> Result<ConfigDiff, String> {
+    // Held throughout, so concurrent reloads apply in order
+    let mut current = reload.current.lock().await;
+    let next = reload.files.load()?;
+    let diff = current.diff(&next);
+    apply_config(&mut *state.lock().await, &current, &next)?;
+    *current = next;
+    Ok(diff)
+}
+
+/// Reload and record the outcome in the server log
+async fn reload_and_log(
+    state: &AppState,
+    config: &ConfigReload,
+    trigger: &str,
+) -> Result<ConfigDiff, String> {
+    let result = reload(state, config).await;
+    match &result {
+        Ok(diff) => tracing::warn!(
+            trigger,
+            changed = ?diff.changed,
+            templates = ?diff.templates,
+            maintenance_windows = ?diff.maintenance_windows,
+            "🔄 Configuration reloaded"
+        ),
+        Err(e) => tracing::error!(trigger, "❌ Configuration reload failed: {}", e),
+    }
+    result
+}
+
+async fn reload_config(
+    State(state): State<AppState>,
+    Extension(config): Extension<Arc<ConfigReload>>,
+) -> (StatusCode, Json<ApiResponse<ConfigDiff>>) {
+    match reload_and_log(&state, &config, "api").await {
+        Ok(diff) => (StatusCode::OK, Json(ApiResponse::ok(diff))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+#[cfg(unix)]
+async fn reload_on_sighup(state: AppState, config: Arc<ConfigReload>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::warn!("⚠️  SIGHUP reload unavailable: {}", e);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        // Failures are logged; the previous configuration stays in force
+        let _ = reload_and_log(&state, &config, "sighup").await;
+    }
+}
+
+// ─── Storage Backend Selection ──────────────────────────────────────────────
+
+/// Strict mode: open the configured backend, retrying until it becomes
+/// available (e.g. a volume that is mounted late) or `timeout_secs` elapses.
+/// Configuration errors fail immediately.
+async fn wait_for_storage(storage: &str, timeout_secs: u64) -> Result<KlockClient, String> {
+    if storage == "memory" {
+        tracing::info!("💾 Storage backend: in-memory (leases will not persist)");
+        return Ok(KlockClient::new());
+    }
+
+    let Some(path) = storage.strip_prefix("sqlite:") else {
+        return Err(format!(
+            "Unknown storage backend: '{}'. Use 'memory' or 'sqlite:<path>'",
+            storage
+        ));
+    };
+
+    #[cfg(feature = "sqlite")]
+    {
+        tracing::info!(
+            "💾 Storage backend: SQLite ({}, strict, timeout {}s)",
+            path,
+            timeout_secs
+        );
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(timeout_secs);
+        let mut delay = std::time::Duration::from_millis(250);
+        loop {
+            match open_sqlite(path) {
+                Ok(client) => return Ok(client),
+                Err(e) if std::time::Instant::now() >= deadline => return Err(e),
+                Err(e) => {
+                    tracing::warn!("Storage not ready ({}); retrying in {:?}", e, delay);
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(std::time::Duration::from_secs(5));
+                }
+            }
+        }
+    }
+    #[cfg(not(feature = "sqlite"))]
+    {
+        let _ = (path, timeout_secs);
+        Err(
+            "SQLite storage requested but `sqlite` feature is not enabled. \
+             Rebuild with: cargo build --features sqlite"
+                .to_string(),
+        )
+    }
+}
+
+/// Open a SQLite-backed client and prove the database is writable.
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &str) -> Result<KlockClient, String> {
+    // SQLite's own error for a missing or read-only directory is just
+    // "unable to open database file"; name the likely cause instead
+    let dir = std::path::Path::new(path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    match std::fs::metadata(dir) {
+        Err(_) => {
+            return Err(format!(
+                "directory '{}' does not exist (is the volume mounted?)",
+                dir.display()
+            ));
+        }
+        Ok(meta) if meta.permissions().readonly() => {
+            return Err(format!("directory '{}' is read-only", dir.display()));
+        }
+        Ok(_) => {}
+    }
+
+    let client = KlockClient::with_sqlite(path)?;
+    client.storage_ready()?;
+    Ok(client)
+}
+
+fn create_client(storage: &str) -> KlockClient {
+    if storage == "memory" {
+        tracing::info!("💾 Storage backend: in-memory (leases will not persist)");
+        KlockClient::new()
+    } else if let Some(path) = storage.strip_prefix("sqlite:") {
+        #[cfg(feature = "sqlite")]
+        {
+            tracing::info!("💾 Storage backend: SQLite ({})", path);
+            match open_sqlite(path) {
+                Ok(client) => client,
+                Err(e) => {
+                    tracing::error!("Failed to open SQLite: {}. Falling back to in-memory.", e);
+                    KlockClient::new()
+                }
+            }
+        }
+        #[cfg(not(feature = "sqlite"))]
+        {
+            tracing::error!(
+                "SQLite storage requested but `sqlite` feature is not enabled. \
+                 Rebuild with: cargo build --features sqlite"
+            );
+            tracing::warn!("Falling back to in-memory storage.");
+            let _ = path;
+            KlockClient::new()
+        }
+    } else {
+        tracing::error!(
+            "Unknown storage backend: '{}'. Use 'memory' or 'sqlite:<path>'",
+            storage
+        );
+        tracing::warn!("Falling back to in-memory storage.");
+        KlockClient::new()
+    }
+}
+
+/// Program the scheduler to force a verdict (debug builds with the
+/// `testing` feature only)
+#[cfg(all(feature = "testing", debug_assertions))]
+async fn inject_fault(
+    State(state): State<AppState>,
+    Json(req): Json<crate::handlers::InjectFaultRequest>,
+) -> (StatusCode, Json<ApiResponse<Vec<InjectedFault>>>) {
+    let client = state.lock().await;
+    let Some(faults) = client.fault_injector() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::err(
+                ReasonCode::NotFound,
+                "Fault injection is not enabled".to_string(),
+            )),
+        );
+    };
+    match req.into_fault().and_then(|fault| faults.inject(fault)) {
+        Ok(()) => {
+            tracing::warn!("Scheduler fault injected");
+            (StatusCode::CREATED, Json(ApiResponse::ok(faults.faults())))
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::err(ReasonCode::InvalidRequest, e)),
+        ),
+    }
+}
+
+#[cfg(all(feature = "testing", debug_assertions))]
+async fn list_faults(State(state): State<AppState>) -> Json<ApiResponse<Vec<InjectedFault>>> {
+    let client = state.lock().await;
+    Json(ApiResponse::ok(
+        client
+            .fault_injector()
+            .map(|faults| faults.faults())
+            .unwrap_or_default(),
+    ))
+}
+
+#[cfg(all(feature = "testing", debug_assertions))]
+async fn clear_faults(State(state): State<AppState>) -> Json<ApiResponse<String>> {
+    let client = state.lock().await;
+    let cleared = client.fault_injector().map_or(0, |faults| faults.clear());
+    Json(ApiResponse::ok(format!(
+        "Cleared {} injected faults",
+        cleared
+    )))
 }
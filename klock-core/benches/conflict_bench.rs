@@ -1,6 +1,10 @@
 use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
 
+use klock_core::client::KlockClient;
 use klock_core::conflict::ConflictEngine;
+use klock_core::deadlock::WaitForGraph;
+use klock_core::dependency::DependencyGraph;
+use klock_core::registry::Priority;
 use klock_core::scheduler::WaitDieScheduler;
 use klock_core::state::{IntentManifest, KlockKernel, StateSnapshot};
 use klock_core::types::*;
@@ -18,6 +22,9 @@ fn make_triple(agent: &str, pred: Predicate, path: &str, session: &str) -> SPOTr
         timestamp: 1000,
         confidence: Confidence::High,
         session_id: session.to_string(),
+        renamed_to: None,
+        expires_at: None,
+        long_lived: false,
     }
 }
 
@@ -70,8 +77,8 @@ fn bench_check_with_varying_triples(c: &mut Criterion) {
 
 fn bench_scheduler_decide(c: &mut Criterion) {
     let mut priorities = HashMap::new();
-    priorities.insert("older".to_string(), 100_u64);
-    priorities.insert("younger".to_string(), 200_u64);
+    priorities.insert("older".to_string(), Priority::from(100));
+    priorities.insert("younger".to_string(), Priority::from(200));
 
     let active = vec![make_lease("older", Predicate::Mutates, "/app.ts")];
     let resource = ResourceRef::new(ResourceType::File, "/app.ts");
@@ -80,6 +87,7 @@ fn bench_scheduler_decide(c: &mut Criterion) {
         b.iter(|| {
             WaitDieScheduler::decide(
                 black_box("younger"),
+                black_box("s2"),
                 black_box(Predicate::Mutates),
                 black_box(&resource),
                 black_box(&active),
@@ -91,13 +99,16 @@ fn bench_scheduler_decide(c: &mut Criterion) {
 
 fn bench_kernel_execute(c: &mut Criterion) {
     let mut priorities = HashMap::new();
-    priorities.insert("older".to_string(), 100_u64);
-    priorities.insert("younger".to_string(), 200_u64);
+    priorities.insert("older".to_string(), Priority::from(100));
+    priorities.insert("younger".to_string(), Priority::from(200));
 
     let state = StateSnapshot {
         active_leases: vec![make_lease("older", Predicate::Mutates, "/app.ts")],
+        pending_leases: vec![],
         active_intents: vec![make_triple("older", Predicate::Mutates, "/app.ts", "s1")],
         priorities,
+        dependencies: DependencyGraph::new(),
+        waits_for: WaitForGraph::new(),
     };
 
     let manifest = IntentManifest {
@@ -111,11 +122,53 @@ fn bench_kernel_execute(c: &mut Criterion) {
     });
 }
 
+/// Declaring an intent while `count` other agents each hold an intent and a
+/// lease on their own file. The resource indexes keep the cost flat.
+fn bench_declare_intent_with_varying_holders(c: &mut Criterion) {
+    let mut group = c.benchmark_group("declare_intent_holders");
+
+    for count in [10, 100, 1000] {
+        let mut client = KlockClient::new();
+        // The requester is older than every holder, so it waits rather than
+        // dies, and the state is the same on every iteration
+        client.register_agent("requester", 1);
+        for i in 0..count {
+            let agent = format!("agent_{}", i);
+            let path = format!("/file_{}.ts", i);
+            client.register_agent(&agent, 100 + i as u64);
+            client.acquire_lease(&agent, "s1", "FILE", &path, "MUTATES", 600_000);
+            client.declare_intent(&IntentManifest {
+                session_id: "s1".to_string(),
+                agent_id: agent.clone(),
+                intents: vec![make_triple(&agent, Predicate::Mutates, &path, "s1")],
+            });
+        }
+
+        let manifest = IntentManifest {
+            session_id: "s2".to_string(),
+            agent_id: "requester".to_string(),
+            intents: vec![make_triple(
+                "requester",
+                Predicate::Mutates,
+                "/file_0.ts",
+                "s2",
+            )],
+        };
+
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| client.declare_intent(black_box(&manifest)))
+        });
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_check_pair,
     bench_check_with_varying_triples,
     bench_scheduler_decide,
     bench_kernel_execute,
+    bench_declare_intent_with_varying_holders,
 );
 criterion_main!(benches);
@@ -39,7 +39,7 @@ fn bench_throughput(c: &mut Criterion) {
                     // Each agent acquires a lease on a different file
                     for i in 0..count {
                         let resource =
-                            ResourceRef::new(ResourceType::File, &format!("/file_{}.ts", i));
+                            ResourceRef::new(ResourceType::File, format!("/file_{}.ts", i));
                         store.acquire(
                             &format!("agent-{}", i),
                             "s1",
@@ -66,7 +66,7 @@ fn bench_eviction(c: &mut Criterion) {
 
             for i in 0..1000 {
                 store.register_agent_priority(format!("a{}", i), i as u64);
-                let resource = ResourceRef::new(ResourceType::File, &format!("/f{}.ts", i));
+                let resource = ResourceRef::new(ResourceType::File, format!("/f{}.ts", i));
                 store.acquire(
                     &format!("a{}", i),
                     "s1",
@@ -0,0 +1,221 @@
+//! Per-agent activity timelines.
+//!
+//! Operators ask "what has this agent been doing for the last hour?". The
+//! client answers from an activity log: every acquisition, refusal,
+//! release, revocation, heartbeat, eviction, intent verdict and co-signing
+//! step is recorded against the agent it concerns, in the order it
+//! happened, and queried by time range, kind and resource.
+//!
+//! Each agent keeps at most `ACTIVITY_CAPACITY` entries; older ones are
+//! dropped first. Compaction drops entries older than
+//! `CompactionPolicy::activity_retention_ms`.
+
+use crate::types::{Predicate, ResourceRef};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Entries kept per agent
+pub const ACTIVITY_CAPACITY: usize = 1024;
+
+/// What an agent did, or what happened to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    /// A lease was granted, or a reservation activated
+    Acquired,
+    /// An acquire or activation was refused
+    Denied,
+    /// The agent released a lease
+    Released,
+    /// An operator revoked one of the agent's leases
+    Revoked,
+    /// The agent heartbeated a lease, successfully or not
+    Heartbeat,
+    /// A lease outlived its TTL and was evicted
+    Evicted,
+    /// An intent manifest was judged
+    Verdict,
+    /// An acquire needs a co-signer; a pending lease awaits approval
+    CosignRequested,
+    /// A co-signing request was approved, by or for the agent
+    Cosigned,
+    /// A co-signing request expired without approval
+    CosignExpired,
+    /// The agent withdrew a granted intent
+    IntentWithdrawn,
+}
+
+/// One timeline entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    /// When it happened, in ms since the epoch
+    pub at: u64,
+    pub agent_id: String,
+    pub session_id: String,
+    pub kind: ActivityKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease_id: Option<String>,
+    /// The resources involved: a lease's resource, or a manifest's intents
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<ResourceRef>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub predicate: Option<Predicate>,
+    /// How it ended: a refusal reason (`DIE`), verdict status (`Granted`),
+    /// `renewed`/`missed` for heartbeats, or the co-signing rule and
+    /// co-signer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<String>,
+}
+
+impl ActivityEntry {
+    fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<ActivityEntry>()
+            + self.agent_id.capacity()
+            + self.session_id.capacity()
+            + self.lease_id.as_ref().map_or(0, String::capacity)
+            + self
+                .resources
+                .iter()
+                .map(|r| std::mem::size_of::<ResourceRef>() + r.path.capacity())
+                .sum::<usize>()
+            + self.outcome.as_ref().map_or(0, String::capacity)
+    }
+}
+
+/// Filters for `ActivityLog::timeline`; the default matches everything
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimelineQuery {
+    /// Only entries at or after this time
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Only entries before this time
+    #[serde(default)]
+    pub until: Option<u64>,
+    #[serde(default)]
+    pub kind: Option<ActivityKind>,
+    /// Only entries touching a resource that overlaps this one
+    #[serde(default)]
+    pub resource: Option<ResourceRef>,
+    /// Return at most the latest `limit` matching entries
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl TimelineQuery {
+    fn matches(&self, entry: &ActivityEntry) -> bool {
+        self.since.is_none_or(|since| entry.at >= since)
+            && self.until.is_none_or(|until| entry.at < until)
+            && self.kind.is_none_or(|kind| entry.kind == kind)
+            && self
+                .resource
+                .as_ref()
+                .is_none_or(|resource| entry.resources.iter().any(|r| r.overlaps(resource)))
+    }
+}
+
+/// Activity by agent, oldest first
+#[derive(Debug, Default)]
+pub struct ActivityLog {
+    agents: HashMap<String, VecDeque<ActivityEntry>>,
+    /// When each agent was last seen; outlives compaction of its entries
+    last_seen: HashMap<String, u64>,
+}
+
+impl ActivityLog {
+    pub fn record(&mut self, entry: ActivityEntry) {
+        self.touch(&entry.agent_id, entry.at);
+        let entries = self.agents.entry(entry.agent_id.clone()).or_default();
+        if entries.len() == ACTIVITY_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Note that the agent was seen at `at`, without an entry
+    pub fn touch(&mut self, agent_id: &str, at: u64) {
+        let seen = self.last_seen.entry(agent_id.to_string()).or_default();
+        *seen = (*seen).max(at);
+    }
+
+    /// When the agent was last seen, if it ever was
+    pub fn last_seen(&self, agent_id: &str) -> Option<u64> {
+        self.last_seen.get(agent_id).copied()
+    }
+
+    /// The agent's entries matching `query`, oldest first
+    pub fn timeline(&self, agent_id: &str, query: &TimelineQuery) -> Vec<ActivityEntry> {
+        let Some(entries) = self.agents.get(agent_id) else {
+            return Vec::new();
+        };
+        let mut matching: Vec<ActivityEntry> = entries
+            .iter()
+            .filter(|entry| query.matches(entry))
+            .cloned()
+            .collect();
+        if let Some(limit) = query.limit {
+            matching.drain(..matching.len().saturating_sub(limit));
+        }
+        matching
+    }
+
+    /// IDs of the leases any agent acquired at or after `since` on a
+    /// resource overlapping `resource`, as far back as the log reaches
+    pub fn acquired_since(&self, resource: &ResourceRef, since: u64) -> Vec<String> {
+        let query = TimelineQuery {
+            since: Some(since),
+            kind: Some(ActivityKind::Acquired),
+            resource: Some(resource.clone()),
+            ..TimelineQuery::default()
+        };
+        self.agents
+            .values()
+            .flatten()
+            .filter(|entry| query.matches(entry))
+            .filter_map(|entry| entry.lease_id.clone())
+            .collect()
+    }
+
+    /// Drop the agent's timeline. Returns the number of entries dropped.
+    pub fn forget(&mut self, agent_id: &str) -> usize {
+        self.last_seen.remove(agent_id);
+        self.agents
+            .remove(agent_id)
+            .map_or(0, |entries| entries.len())
+    }
+
+    /// Drop entries recorded before `before`. Returns the number dropped.
+    pub fn compact(&mut self, before: u64) -> usize {
+        let mut removed = 0;
+        self.agents.retain(|_, entries| {
+            let initial = entries.len();
+            entries.retain(|entry| entry.at >= before);
+            removed += initial - entries.len();
+            !entries.is_empty()
+        });
+        removed
+    }
+
+    /// Total entries across all agents
+    pub fn len(&self) -> usize {
+        self.agents.values().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    /// Approximate footprint of every agent's entries
+    pub fn estimated_bytes(&self) -> usize {
+        self.agents
+            .iter()
+            .map(|(agent_id, entries)| {
+                agent_id.capacity()
+                    + std::mem::size_of::<(String, VecDeque<ActivityEntry>)>()
+                    + entries
+                        .iter()
+                        .map(ActivityEntry::estimated_bytes)
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+}
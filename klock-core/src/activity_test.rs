@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use crate::activity::{ActivityKind, TimelineQuery};
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::compaction::CompactionPolicy;
+    use crate::state::IntentManifest;
+    use crate::types::{Confidence, LeaseResult, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::sync::Arc;
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn lease_id(result: LeaseResult) -> String {
+        match result {
+            LeaseResult::Success { lease } => lease.id,
+            other => panic!("Expected success, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_timeline_records_agent_activity_in_order() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("alice", 100);
+        client.register_agent("bob", 200);
+
+        let held = lease_id(client.acquire_lease("alice", "s1", "FILE", "/a.ts", "MUTATES", 500));
+        clock.advance(10);
+        // Bob is younger and dies against Alice's lease
+        client.acquire_lease("bob", "s2", "FILE", "/a.ts", "MUTATES", 500);
+        clock.advance(10);
+        assert!(client.heartbeat_lease(&held, client.now()));
+        clock.advance(10);
+        let id = client.next_id();
+        client.declare_intent(&IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "alice".to_string(),
+            intents: vec![SPOTriple {
+                id,
+                subject: "alice".to_string(),
+                predicate: Predicate::Consumes,
+                object: file("/b.ts"),
+                timestamp: client.now(),
+                confidence: Confidence::High,
+                session_id: "s1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        clock.advance(10);
+        let other = lease_id(client.acquire_lease("alice", "s1", "FILE", "/c.ts", "MUTATES", 500));
+        assert!(client.release_lease(&other));
+        clock.advance(1_000);
+        client.evict_expired();
+
+        let timeline = client.agent_timeline("alice", &TimelineQuery::default());
+        let kinds: Vec<ActivityKind> = timeline.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ActivityKind::Acquired,
+                ActivityKind::Heartbeat,
+                ActivityKind::Verdict,
+                ActivityKind::Acquired,
+                ActivityKind::Released,
+                ActivityKind::Evicted,
+            ]
+        );
+        assert!(timeline.windows(2).all(|w| w[0].at <= w[1].at));
+        assert_eq!(timeline[1].outcome.as_deref(), Some("renewed"));
+        assert_eq!(timeline[2].outcome.as_deref(), Some("Granted"));
+        assert_eq!(timeline[5].lease_id.as_deref(), Some(held.as_str()));
+
+        let bob = client.agent_timeline("bob", &TimelineQuery::default());
+        assert_eq!(bob.len(), 1);
+        assert_eq!(bob[0].kind, ActivityKind::Denied);
+        assert_eq!(bob[0].outcome.as_deref(), Some("DIE"));
+    }
+
+    #[test]
+    fn test_timeline_query_filters() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        for path in ["/src/a.ts", "/src/b.ts", "/docs/c.md"] {
+            client.acquire_lease("alice", "s1", "FILE", path, "MUTATES", 60_000);
+            clock.advance(100);
+        }
+
+        let since = TimelineQuery {
+            since: Some(1_100),
+            ..TimelineQuery::default()
+        };
+        assert_eq!(client.agent_timeline("alice", &since).len(), 2);
+        let in_src = TimelineQuery {
+            resource: Some(file("/src/")),
+            ..TimelineQuery::default()
+        };
+        assert_eq!(client.agent_timeline("alice", &in_src).len(), 2);
+        let latest = TimelineQuery {
+            limit: Some(1),
+            ..TimelineQuery::default()
+        };
+        let latest = client.agent_timeline("alice", &latest);
+        assert_eq!(latest[0].resources, [file("/docs/c.md")]);
+        let releases = TimelineQuery {
+            kind: Some(ActivityKind::Released),
+            ..TimelineQuery::default()
+        };
+        assert!(client.agent_timeline("alice", &releases).is_empty());
+        assert!(
+            client
+                .agent_timeline("nobody", &TimelineQuery::default())
+                .is_empty()
+        );
+
+        // Compaction drops entries past the retention window
+        client.set_compaction_policy(CompactionPolicy {
+            activity_retention_ms: 1_000,
+            ..CompactionPolicy::default()
+        });
+        clock.set(2_150);
+        assert_eq!(client.compact().activity_entries_removed, 2);
+        assert_eq!(client.memory_report().activity.count, 1);
+    }
+}
@@ -0,0 +1,348 @@
+//! Admission checks.
+//!
+//! Before the scheduler sees a request, the client turns away what it may
+//! not grant whoever holds what: requests of unregistered agents it
+//! rejects, of ended sessions and of quarantined agents, for quarantined
+//! resources or resources another agent's maintenance window reserves,
+//! retries too soon after failures, and new leases shed near the capacity
+//! caps. The policy hook, if one is set, is asked last (see
+//! `policy_hook`). New checks belong here rather than in the callers.
+
+use super::KlockClient;
+use crate::codes::ReasonCode;
+use crate::policy_hook::{PolicyDecision, PolicyRequest, PolicyRequestKind};
+use crate::registry::{PriorityClass, UnregisteredAgentPolicy};
+use crate::state::{IntentManifest, KernelVerdict, KernelVerdictStatus};
+use crate::types::{LeaseFailureReason, LeaseResult, Predicate, ResourceRef};
+
+/// A request turned away by an admission check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Refusal {
+    pub reason: LeaseFailureReason,
+    pub message: String,
+    /// How long until the check may pass, if it is known
+    pub wait_time: Option<u64>,
+}
+
+impl Refusal {
+    fn new(reason: LeaseFailureReason, message: String, wait_time: Option<u64>) -> Self {
+        Self {
+            reason,
+            message,
+            wait_time,
+        }
+    }
+
+    pub fn into_result(self) -> LeaseResult {
+        LeaseResult::rejected(self.reason, self.wait_time)
+    }
+
+    /// The verdict refusing `manifest`: `Rejected` when asking again cannot
+    /// help, `Die` when the check may pass later
+    pub fn into_verdict(self, manifest: &IntentManifest) -> KernelVerdict {
+        let status = match self.reason {
+            LeaseFailureReason::UnregisteredAgent
+            | LeaseFailureReason::SessionExpired
+            | LeaseFailureReason::PolicyDenied => KernelVerdictStatus::Rejected,
+            _ => KernelVerdictStatus::Die,
+        };
+        KernelVerdict {
+            agent_id: manifest.agent_id.clone(),
+            session_id: manifest.session_id.clone(),
+            status,
+            reason: Some(self.message),
+            code: Some(ReasonCode::from(self.reason)),
+            held_by: None,
+            holders: Vec::new(),
+            conflicts: Vec::new(),
+            blockers: Vec::new(),
+            retry_after_ms: self.wait_time,
+            implied: Vec::new(),
+            warnings: Vec::new(),
+            fallbacks: Vec::new(),
+            suggestions: Vec::new(),
+            per_intent: Vec::new(),
+        }
+    }
+}
+
+impl KlockClient {
+    /// The built-in checks for `agent_id` taking `resources` in
+    /// `session_id` at `now`. Expired leases are evicted on the way, so the
+    /// capacity caps count only live ones.
+    pub(super) fn admit(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resources: &[(ResourceRef, Predicate)],
+        now: u64,
+    ) -> Result<(), Refusal> {
+        self.skew.observe(now);
+        self.enroll(agent_id, now);
+        self.expire_sessions_at(now);
+        if let Some(refusal) = self.refusal(
+            agent_id,
+            session_id,
+            resources.iter().map(|(resource, _)| resource),
+            now,
+        ) {
+            return Err(refusal);
+        }
+
+        for (resource, _) in resources {
+            let resource_key = resource.key();
+            if let Some(remaining) = self
+                .debounce
+                .backoff_remaining(agent_id, &resource_key, now)
+            {
+                return Err(Refusal::new(
+                    LeaseFailureReason::TooManyRetries,
+                    format!(
+                        "Too many failed acquires of {}; retry in {} ms",
+                        resource_key, remaining
+                    ),
+                    Some(remaining),
+                ));
+            }
+        }
+
+        self.evict_and_track(now);
+        if self.sheds(agent_id) {
+            return Err(Refusal::new(
+                LeaseFailureReason::CapacityExceeded,
+                format!("Capacity exceeded: new leases of '{}' are shed", agent_id),
+                None,
+            ));
+        }
+        self.refresh_health(agent_id, now);
+        self.refresh_aging(agent_id, now);
+        Ok(())
+    }
+
+    /// Ask the policy hook about `agent_id` taking `resources` for `ttl`
+    /// ms, by `deadline`. Returns the TTL and deadline to grant with, as
+    /// the hook changed them; changed differently for several resources,
+    /// the shortest TTL and earliest deadline asked for.
+    pub(super) fn admit_by_policy(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resources: &[(ResourceRef, Predicate)],
+        ttl: u64,
+        deadline: Option<u64>,
+        now: u64,
+    ) -> Result<(u64, Option<u64>), Refusal> {
+        self.policy_denial = None;
+        if self.policy_hook.is_none() {
+            return Ok((ttl, deadline));
+        }
+        let priority = self.agent_priority(agent_id);
+        let (mut modified_ttl, mut modified_deadline) = (None, None);
+        for (resource, predicate) in resources {
+            match self.consult_policy(PolicyRequest {
+                kind: PolicyRequestKind::Acquire,
+                agent_id: agent_id.to_string(),
+                session_id: session_id.to_string(),
+                resource: resource.clone(),
+                predicate: *predicate,
+                ttl: Some(ttl),
+                deadline,
+                priority,
+                now,
+            }) {
+                PolicyDecision::Allow => {}
+                PolicyDecision::Modify {
+                    ttl: new_ttl,
+                    deadline: new_deadline,
+                } => {
+                    if let Some(new_ttl) = new_ttl.filter(|ttl| *ttl > 0) {
+                        modified_ttl = Some(modified_ttl.map_or(new_ttl, |t: u64| t.min(new_ttl)));
+                    }
+                    if let Some(new_deadline) = new_deadline {
+                        modified_deadline = Some(
+                            modified_deadline.map_or(new_deadline, |at: u64| at.min(new_deadline)),
+                        );
+                    }
+                }
+                PolicyDecision::Deny { reason } => {
+                    self.policy_denial = Some(reason.clone());
+                    return Err(Refusal::new(LeaseFailureReason::PolicyDenied, reason, None));
+                }
+            }
+        }
+        Ok((modified_ttl.unwrap_or(ttl), modified_deadline.or(deadline)))
+    }
+
+    /// The verdict for a manifest turned away before any conflict is
+    /// checked: by a built-in check other than the capacity caps and retry
+    /// spacing, which only apply to leases, or by the policy hook
+    pub(super) fn admit_manifest(
+        &self,
+        manifest: &IntentManifest,
+        now: u64,
+    ) -> Option<KernelVerdict> {
+        if let Some(refusal) = self.refusal(
+            &manifest.agent_id,
+            &manifest.session_id,
+            manifest.intents.iter().map(|intent| &intent.object),
+            now,
+        ) {
+            return Some(refusal.into_verdict(manifest));
+        }
+
+        self.policy_hook.as_ref()?;
+        let priority = self.agent_priority(&manifest.agent_id);
+        manifest.intents.iter().find_map(|intent| {
+            let decision = self.consult_policy(PolicyRequest {
+                kind: PolicyRequestKind::Intent,
+                agent_id: manifest.agent_id.clone(),
+                session_id: manifest.session_id.clone(),
+                resource: intent.object.clone(),
+                predicate: intent.predicate,
+                ttl: None,
+                deadline: None,
+                priority,
+                now,
+            });
+            let PolicyDecision::Deny { reason } = decision else {
+                return None;
+            };
+            Some(
+                Refusal::new(LeaseFailureReason::PolicyDenied, reason, None).into_verdict(manifest),
+            )
+        })
+    }
+
+    /// The first check that turns away `agent_id` in `session_id` from
+    /// `resources`, whatever it asks for them
+    fn refusal<'a>(
+        &self,
+        agent_id: &str,
+        session_id: &str,
+        mut resources: impl Iterator<Item = &'a ResourceRef>,
+        now: u64,
+    ) -> Option<Refusal> {
+        if self.rejects(agent_id) {
+            return Some(Refusal::new(
+                LeaseFailureReason::UnregisteredAgent,
+                format!("Agent '{}' is not registered", agent_id),
+                None,
+            ));
+        }
+        if self.sessions.has_ended(agent_id, session_id) {
+            return Some(Refusal::new(
+                LeaseFailureReason::SessionExpired,
+                format!("Session '{}' has expired", session_id),
+                None,
+            ));
+        }
+        if let Some(until) = self.health.quarantined_until(agent_id, now) {
+            return Some(Refusal::new(
+                LeaseFailureReason::AgentQuarantined,
+                format!("Agent quarantined until {}", until),
+                Some(until - now),
+            ));
+        }
+        resources.find_map(|resource| {
+            if let Some((message, wait_time)) = self.quarantined(resource, now) {
+                return Some(Refusal::new(
+                    LeaseFailureReason::Quarantined,
+                    message,
+                    wait_time,
+                ));
+            }
+            let (message, wait_time) = self.in_maintenance(agent_id, resource, now)?;
+            Some(Refusal::new(
+                LeaseFailureReason::MaintenanceWindow,
+                message,
+                Some(wait_time),
+            ))
+        })
+    }
+
+    /// Register an unknown requester under `AutoRegister`, as a normal
+    /// agent as young as `now`
+    pub(super) fn enroll(&mut self, agent_id: &str, now: u64) {
+        if self.unregistered_agent_policy() == UnregisteredAgentPolicy::AutoRegister
+            && self.agent_priority(agent_id).is_none()
+        {
+            self.register_agent_in_class(agent_id, PriorityClass::Normal, now);
+        }
+    }
+
+    /// Whether the agent's requests are refused for want of a priority
+    pub(super) fn rejects(&self, agent_id: &str) -> bool {
+        self.unregistered_agent_policy() == UnregisteredAgentPolicy::Reject
+            && self.agent_priority(agent_id).is_none()
+    }
+
+    /// The policy hook's decision on `request`; `Allow` without a hook
+    fn consult_policy(&self, request: PolicyRequest) -> PolicyDecision {
+        match &self.policy_hook {
+            Some(hook) => hook.evaluate(&request),
+            None => PolicyDecision::Allow,
+        }
+    }
+
+    /// Whether to refuse `agent_id` a new lease to shed load. At the lease
+    /// cap, advisory leases are revoked first if the policy says so.
+    fn sheds(&mut self, agent_id: &str) -> bool {
+        let policy = *self.shedder.policy();
+        if !policy.is_enabled() {
+            return false;
+        }
+        let (mut leases, memory_bytes) = self.capacity_usage();
+        if policy.revoke_advisory && policy.at_lease_cap(leases) {
+            leases -= self.revoke_advisory_leases(leases);
+        }
+        let class = self
+            .agent_priority(agent_id)
+            .map_or(PriorityClass::Background, |priority| priority.class);
+        let refused = policy.level(leases, memory_bytes).refuses(class);
+        if refused {
+            self.shedder.record_refusal();
+        }
+        refused
+    }
+
+    /// Refusal message and time until expiry, if `resource` is quarantined
+    pub(super) fn quarantined(
+        &self,
+        resource: &ResourceRef,
+        now: u64,
+    ) -> Option<(String, Option<u64>)> {
+        let quarantine = self.quarantines.blocking(resource, now)?;
+        let message = match &quarantine.reason {
+            Some(reason) => format!(
+                "{} is quarantined under {} ({})",
+                resource.key(),
+                quarantine.resource.key(),
+                reason
+            ),
+            None => format!(
+                "{} is quarantined under {}",
+                resource.key(),
+                quarantine.resource.key()
+            ),
+        };
+        Some((message, quarantine.expires_at.map(|at| at - now)))
+    }
+
+    /// Refusal message and time until the window closes, if an open
+    /// maintenance window keeps `agent_id` off `resource`
+    fn in_maintenance(
+        &self,
+        agent_id: &str,
+        resource: &ResourceRef,
+        now: u64,
+    ) -> Option<(String, u64)> {
+        let (window, end) = self.maintenance.blocking(agent_id, resource, now)?;
+        let message = format!(
+            "{} is reserved by maintenance window '{}' until {}",
+            resource.key(),
+            window.name,
+            end
+        );
+        Some((message, end - now))
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use crate::capacity::CapacityPolicy;
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::health::HealthPolicy;
+    use crate::registry::PriorityClass;
+    use crate::types::{LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType};
+    use std::sync::Arc;
+
+    fn client() -> KlockClient {
+        let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+        client
+    }
+
+    /// Reserve `path` for `agent_id`, returning the pending lease's ID
+    fn reserve(client: &mut KlockClient, agent_id: &str, path: &str) -> Result<String, String> {
+        let resources = [(
+            ResourceRef::new(ResourceType::File, path),
+            Predicate::Mutates,
+        )];
+        let reserved = client.pre_acquire(agent_id, "s2", &resources, 60_000, 30_000)?;
+        Ok(reserved[0].id.clone())
+    }
+
+    fn refusal(result: &LeaseResult) -> Option<LeaseFailureReason> {
+        match result {
+            LeaseResult::Success { .. } => None,
+            LeaseResult::Failure { reason, .. } => Some(*reason),
+        }
+    }
+
+    #[test]
+    fn test_quarantined_agent_cannot_reserve_or_activate() {
+        let mut client = client();
+        client.set_health_policy(HealthPolicy {
+            quarantine_below: Some(99),
+            ..HealthPolicy::default()
+        });
+        let spare = reserve(&mut client, "junior", "/spare.ts").unwrap();
+
+        // A Die pushes the junior below the threshold
+        client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        let result = client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(refusal(&result), Some(LeaseFailureReason::Die));
+
+        let error = reserve(&mut client, "junior", "/b.ts").unwrap_err();
+        assert!(error.contains("quarantined"), "{}", error);
+        let result = client.activate_lease(&spare);
+        let LeaseResult::Failure {
+            reason, wait_time, ..
+        } = result
+        else {
+            panic!("Expected the activation to be refused");
+        };
+        assert_eq!(reason, LeaseFailureReason::AgentQuarantined);
+        assert!(wait_time.is_some());
+        assert_eq!(client.get_active_leases().len(), 1);
+    }
+
+    #[test]
+    fn test_activation_is_shed_like_an_acquire() {
+        let mut client = client();
+        client.register_agent_in_class("bg", PriorityClass::Background, 300);
+        client.set_capacity_policy(CapacityPolicy {
+            max_leases: Some(3),
+            max_memory_bytes: None,
+            shed_from_percent: 60,
+            revoke_advisory: false,
+        });
+        let spare = reserve(&mut client, "bg", "/spare.ts").unwrap();
+
+        // 2 of 3 leases, the reservation included: background agents are shed
+        client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        let result = client.activate_lease(&spare);
+        assert_eq!(refusal(&result), Some(LeaseFailureReason::CapacityExceeded));
+        let result = client.acquire_lease("junior", "s2", "FILE", "/b.ts", "MUTATES", 60_000);
+        assert_eq!(refusal(&result), None);
+        assert_eq!(client.capacity_report().unwrap().refused, 1);
+    }
+}
@@ -0,0 +1,155 @@
+//! Priority aging.
+//!
+//! Under Wait-Die a junior dies every time it meets a senior's lease, so a
+//! senior that keeps re-acquiring a resource can starve it indefinitely.
+//! With aging enabled, an agent whose requests keep dying grows "older": its
+//! effective priority improves by one step after every N consecutive
+//! denials, or every T milliseconds of futile retrying, until it is granted
+//! something and returns to its registered priority.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// When and how far starving agents age. Disabled by default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgingPolicy {
+    /// Age one step per this many consecutive denials (`None` = never)
+    pub after_denials: Option<u32>,
+    /// Age one step per this many milliseconds since the first denial of
+    /// the streak (`None` = never)
+    pub after_ms: Option<u64>,
+    /// Subtracted from the agent's priority timestamp per step
+    pub step_by: u64,
+    /// Upper bound on the total improvement
+    pub max_boost: u64,
+    /// A streak with no denial for this long is forgotten
+    pub idle_reset_ms: u64,
+}
+
+impl Default for AgingPolicy {
+    fn default() -> Self {
+        Self {
+            after_denials: None,
+            after_ms: None,
+            step_by: 60 * 1000,
+            max_boost: 60 * 60 * 1000,
+            idle_reset_ms: 60 * 1000,
+        }
+    }
+}
+
+impl AgingPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.after_denials.is_some_and(|n| n > 0) || self.after_ms.is_some_and(|ms| ms > 0)
+    }
+}
+
+/// Consecutive denials of one agent
+#[derive(Debug, Clone, Copy)]
+struct Streak {
+    denials: u32,
+    first_at: u64,
+    last_at: u64,
+}
+
+/// Per-agent denial streaks, and the boost currently applied to each
+/// agent's registered priority
+#[derive(Debug, Default)]
+pub struct AgingTracker {
+    policy: AgingPolicy,
+    streaks: HashMap<String, Streak>,
+    applied: HashMap<String, u64>,
+}
+
+impl AgingTracker {
+    pub fn new(policy: AgingPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn policy(&self) -> &AgingPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: AgingPolicy) {
+        self.policy = policy;
+    }
+
+    /// Record a Die verdict for the agent, extending its streak or starting
+    /// a new one if it had none or the last went idle
+    pub fn record_denial(&mut self, agent_id: &str, now: u64) {
+        if !self.policy.is_enabled() {
+            return;
+        }
+        let idle_reset_ms = self.policy.idle_reset_ms;
+        let streak = self
+            .streaks
+            .entry(agent_id.to_string())
+            .and_modify(|s| {
+                if now.saturating_sub(s.last_at) > idle_reset_ms {
+                    *s = Streak {
+                        denials: 0,
+                        first_at: now,
+                        last_at: now,
+                    };
+                }
+            })
+            .or_insert(Streak {
+                denials: 0,
+                first_at: now,
+                last_at: now,
+            });
+        streak.denials += 1;
+        streak.last_at = now;
+    }
+
+    /// The agent was granted something: its streak ends
+    pub fn record_grant(&mut self, agent_id: &str) {
+        self.streaks.remove(agent_id);
+    }
+
+    /// How far the agent's current streak improves its priority at `now`
+    pub fn boost(&self, agent_id: &str, now: u64) -> u64 {
+        let Some(streak) = self.streaks.get(agent_id) else {
+            return 0;
+        };
+        if now.saturating_sub(streak.last_at) > self.policy.idle_reset_ms {
+            return 0;
+        }
+        let by_denials = self
+            .policy
+            .after_denials
+            .filter(|n| *n > 0)
+            .map_or(0, |n| u64::from(streak.denials / n));
+        let by_time = self
+            .policy
+            .after_ms
+            .filter(|ms| *ms > 0)
+            .map_or(0, |ms| now.saturating_sub(streak.first_at) / ms);
+        by_denials
+            .max(by_time)
+            .saturating_mul(self.policy.step_by)
+            .min(self.policy.max_boost)
+    }
+
+    /// Drop the agent's streak and applied boost
+    pub fn forget(&mut self, agent_id: &str) {
+        self.streaks.remove(agent_id);
+        self.applied.remove(agent_id);
+    }
+
+    /// The boost currently subtracted from the agent's registered priority
+    pub fn applied(&self, agent_id: &str) -> u64 {
+        self.applied.get(agent_id).copied().unwrap_or(0)
+    }
+
+    pub fn set_applied(&mut self, agent_id: &str, boost: u64) {
+        if boost == 0 {
+            self.applied.remove(agent_id);
+        } else {
+            self.applied.insert(agent_id.to_string(), boost);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+#[cfg(test)]
+mod tests {
+    use crate::aging::{AgingPolicy, AgingTracker};
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::types::{LeaseFailureReason, LeaseResult};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_disabled_by_default() {
+        let mut tracker = AgingTracker::default();
+        for at in 0..10 {
+            tracker.record_denial("junior", 1000 + at);
+        }
+        assert_eq!(tracker.boost("junior", 1010), 0);
+    }
+
+    #[test]
+    fn test_boost_grows_with_denials_and_time() {
+        let mut tracker = AgingTracker::new(AgingPolicy {
+            after_denials: Some(3),
+            after_ms: Some(1000),
+            step_by: 10,
+            max_boost: 50,
+            idle_reset_ms: 5000,
+        });
+        tracker.record_denial("junior", 1000);
+        tracker.record_denial("junior", 1100);
+        assert_eq!(tracker.boost("junior", 1100), 0);
+        tracker.record_denial("junior", 1200);
+        assert_eq!(tracker.boost("junior", 1200), 10);
+
+        // Two seconds of futile retrying outweigh three denials
+        tracker.record_denial("junior", 3000);
+        assert_eq!(tracker.boost("junior", 3000), 20);
+        // Capped
+        assert_eq!(tracker.boost("junior", 7900), 50);
+        // Idle for longer than the reset: the streak no longer counts
+        assert_eq!(tracker.boost("junior", 8100), 0);
+
+        tracker.record_denial("junior", 8100);
+        assert_eq!(tracker.boost("junior", 8100), 0);
+        tracker.record_grant("junior");
+        assert_eq!(tracker.boost("junior", 8100), 0);
+    }
+
+    #[test]
+    fn test_starving_junior_ages_past_senior() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.set_aging_policy(AgingPolicy {
+            after_denials: Some(2),
+            step_by: 150,
+            ..AgingPolicy::default()
+        });
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        for _ in 0..2 {
+            clock.advance(1);
+            assert!(matches!(
+                client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000),
+                LeaseResult::Failure {
+                    reason: LeaseFailureReason::Die,
+                    ..
+                }
+            ));
+        }
+        assert_eq!(client.aging_boost("junior"), 150);
+        assert_eq!(client.agent_priority("junior"), Some(50.into()));
+
+        // Now the senior's junior, it waits instead of dying
+        clock.advance(1);
+        assert!(matches!(
+            client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Wait,
+                ..
+            }
+        ));
+        // ... and the senior can no longer jump back in ahead of it
+        assert!(client.release_lease(&lease.id));
+        clock.advance(1);
+        assert!(matches!(
+            client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        clock.advance(1);
+        assert!(matches!(
+            client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Wait,
+                ..
+            }
+        ));
+
+        // Granted: back to its registered priority
+        assert_eq!(client.aging_boost("junior"), 0);
+        assert_eq!(client.agent_priority("junior"), Some(200.into()));
+    }
+
+    #[test]
+    fn test_time_based_aging_applies_on_retry() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.set_aging_policy(AgingPolicy {
+            after_ms: Some(1000),
+            step_by: 10,
+            idle_reset_ms: 10_000,
+            ..AgingPolicy::default()
+        });
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+        client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+
+        clock.advance(1);
+        client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(client.aging_boost("junior"), 0);
+
+        clock.advance(3000);
+        client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(client.aging_boost("junior"), 30);
+
+        // Re-registering keeps the boost
+        client.register_agent("junior", 200);
+        assert_eq!(client.agent_priority("junior"), Some(170.into()));
+    }
+}
@@ -0,0 +1,181 @@
+//! Rename aliases.
+//!
+//! Once a `RENAMES` intent with a target (`renamed_to`) is granted, the old
+//! and new names refer to the same resource until the renaming session ends.
+//! The `ConflictEngine` expands every resource to all of its names before
+//! matching, so a lease on `/src/old.ts` still blocks a mutation of
+//! `/src/new.ts` and vice versa. Directory renames carry their contents, and
+//! file renames carry the symbols qualified with the file.
+
+use crate::types::{ResourceRef, ResourceType};
+use serde::{Deserialize, Serialize};
+
+/// One granted rename
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenameAlias {
+    pub from: ResourceRef,
+    pub to: ResourceRef,
+    pub agent_id: String,
+    /// The alias lives as long as this session holds leases or intents
+    pub session_id: String,
+    pub created_at: u64,
+}
+
+impl RenameAlias {
+    pub fn new(
+        from: ResourceRef,
+        to: ResourceRef,
+        agent_id: &str,
+        session_id: &str,
+        now: u64,
+    ) -> Result<Self, String> {
+        Self::validate(&from, &to)?;
+        Ok(Self {
+            from,
+            to,
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            created_at: now,
+        })
+    }
+
+    /// Check that `to` is a valid new name for `from`.
+    pub fn validate(from: &ResourceRef, to: &ResourceRef) -> Result<(), String> {
+        if from.resource_type != to.resource_type {
+            return Err(format!(
+                "Cannot rename {} to a different resource type ({})",
+                from.key(),
+                to.key()
+            ));
+        }
+        if to.path.is_empty() || from == to {
+            return Err(format!(
+                "Invalid rename target '{}' for {}",
+                to.path,
+                from.key()
+            ));
+        }
+        if from.is_directory() != to.is_directory() {
+            return Err(format!(
+                "Cannot rename {} to {}: both must be directories or neither",
+                from.key(),
+                to.key()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Active rename aliases
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AliasTable {
+    aliases: Vec<RenameAlias>,
+}
+
+impl AliasTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an alias unless the same rename is already recorded.
+    pub fn insert(&mut self, alias: RenameAlias) {
+        if !self
+            .aliases
+            .iter()
+            .any(|a| a.from == alias.from && a.to == alias.to)
+        {
+            self.aliases.push(alias);
+        }
+    }
+
+    pub fn aliases(&self) -> &[RenameAlias] {
+        &self.aliases
+    }
+
+    pub fn len(&self) -> usize {
+        self.aliases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Keep only the aliases for which `keep` returns true. Returns the
+    /// number removed.
+    pub fn retain(&mut self, keep: impl FnMut(&RenameAlias) -> bool) -> usize {
+        let initial = self.aliases.len();
+        self.aliases.retain(keep);
+        initial - self.aliases.len()
+    }
+
+    /// Every other name `resource` is known by, following chains of renames
+    /// in both directions (`a → b → c` makes `a`, `b` and `c` equivalent).
+    pub fn names(&self, resource: &ResourceRef) -> Vec<ResourceRef> {
+        if self.aliases.is_empty() {
+            return Vec::new();
+        }
+        let mut names = vec![resource.clone()];
+        let mut next = 0;
+        while next < names.len() {
+            let current = names[next].clone();
+            next += 1;
+            for alias in &self.aliases {
+                for (from, to) in [(&alias.from, &alias.to), (&alias.to, &alias.from)] {
+                    if let Some(renamed) = rename(&current, from, to)
+                        && !names.contains(&renamed)
+                    {
+                        names.push(renamed);
+                    }
+                }
+            }
+        }
+        names.remove(0);
+        names
+    }
+
+    /// Approximate footprint of all aliases
+    pub fn estimated_bytes(&self) -> usize {
+        self.aliases
+            .iter()
+            .map(|a| {
+                std::mem::size_of::<RenameAlias>()
+                    + a.from.path.capacity()
+                    + a.to.path.capacity()
+                    + a.agent_id.capacity()
+                    + a.session_id.capacity()
+            })
+            .sum()
+    }
+}
+
+/// `resource` as it is named after renaming `from` to `to`, if the rename
+/// affects it.
+fn rename(resource: &ResourceRef, from: &ResourceRef, to: &ResourceRef) -> Option<ResourceRef> {
+    if resource == from {
+        return Some(to.clone());
+    }
+    let rest = if from.is_directory() && resource.resource_type != ResourceType::Symbol {
+        // A directory carries its files and subdirectories
+        (resource.resource_type == from.resource_type)
+            .then(|| resource.path.strip_prefix(from.path.as_str()))
+            .flatten()?
+    } else if resource.resource_type == ResourceType::Symbol
+        && from.resource_type == ResourceType::File
+    {
+        // A file (or directory) carries the symbols qualified with it
+        let (file, _) = resource.path.split_once('#')?;
+        if from.is_directory() {
+            resource.path.strip_prefix(from.path.as_str())?
+        } else if file == from.path {
+            &resource.path[file.len()..]
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    };
+    Some(ResourceRef::new(
+        resource.resource_type.clone(),
+        format!("{}{}", to.path, rest),
+    ))
+}
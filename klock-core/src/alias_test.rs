@@ -0,0 +1,231 @@
+#[cfg(test)]
+mod tests {
+    use crate::alias::{AliasTable, RenameAlias};
+    use crate::client::KlockClient;
+    use crate::compaction::CompactionPolicy;
+    use crate::conflict::{ConflictEngine, ConflictResult};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, Lease, LeaseResult, Predicate, ResourceRef, ResourceType, SPOTriple,
+    };
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn symbol(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::Symbol, path)
+    }
+
+    fn table(renames: &[(&str, &str)]) -> AliasTable {
+        let mut table = AliasTable::new();
+        for (from, to) in renames {
+            table.insert(
+                RenameAlias::new(file(from), file(to), "renamer", "s_rename", 1000).unwrap(),
+            );
+        }
+        table
+    }
+
+    fn manifest(
+        agent_id: &str,
+        session_id: &str,
+        predicate: Predicate,
+        path: &str,
+        renamed_to: Option<&str>,
+    ) -> IntentManifest {
+        IntentManifest {
+            session_id: session_id.to_string(),
+            agent_id: agent_id.to_string(),
+            intents: vec![SPOTriple {
+                id: format!("t_{}", agent_id),
+                subject: agent_id.to_string(),
+                predicate,
+                object: file(path),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: session_id.to_string(),
+                renamed_to: renamed_to.map(str::to_string),
+                expires_at: None,
+                long_lived: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_names_follow_renames() {
+        let aliases = table(&[
+            ("/src/old.ts", "/src/new.ts"),
+            ("/src/new.ts", "/lib/final.ts"),
+        ]);
+        assert_eq!(
+            aliases.names(&file("/src/old.ts")),
+            vec![file("/src/new.ts"), file("/lib/final.ts")]
+        );
+        assert_eq!(
+            aliases.names(&file("/lib/final.ts")),
+            vec![file("/src/new.ts"), file("/src/old.ts")]
+        );
+        // Symbols qualified with the file move with it
+        assert_eq!(
+            aliases.names(&symbol("/src/old.ts#User"))[0],
+            symbol("/src/new.ts#User")
+        );
+        assert!(aliases.names(&file("/src/other.ts")).is_empty());
+
+        let aliases = table(&[("/src/auth/", "/src/identity/")]);
+        assert_eq!(
+            aliases.names(&file("/src/auth/login.ts")),
+            vec![file("/src/identity/login.ts")]
+        );
+        assert_eq!(
+            aliases.names(&symbol("/src/identity/login.ts#login")),
+            vec![symbol("/src/auth/login.ts#login")]
+        );
+    }
+
+    #[test]
+    fn test_invalid_renames_rejected() {
+        assert!(RenameAlias::validate(&file("/a.ts"), &file("/a.ts")).is_err());
+        assert!(RenameAlias::validate(&file("/a.ts"), &file("")).is_err());
+        assert!(RenameAlias::validate(&file("/src/"), &file("/lib.ts")).is_err());
+        assert!(RenameAlias::validate(&file("/a.ts"), &symbol("/b.ts")).is_err());
+        assert!(RenameAlias::validate(&file("/a.ts"), &file("/b.ts")).is_ok());
+    }
+
+    #[test]
+    fn test_engine_matches_both_names() {
+        let held = [Lease::new(
+            "l_holder".to_string(),
+            "holder".to_string(),
+            "s_holder".to_string(),
+            file("/src/old.ts"),
+            Predicate::Mutates,
+            5000,
+            1000,
+        )];
+
+        assert_eq!(
+            ConflictEngine::default().check_leases(
+                "agent",
+                "s1",
+                Predicate::Mutates,
+                &file("/src/new.ts"),
+                &held
+            ),
+            ConflictResult::Ok
+        );
+
+        let engine =
+            ConflictEngine::default().with_aliases(table(&[("/src/old.ts", "/src/new.ts")]));
+        match engine.check_leases(
+            "agent",
+            "s1",
+            Predicate::Mutates,
+            &file("/src/new.ts"),
+            &held,
+        ) {
+            ConflictResult::Conflict(detail) => {
+                assert!(
+                    detail.reason.contains("rename alias FILE:/src/"),
+                    "{}",
+                    detail.reason
+                )
+            }
+            other => panic!("Expected conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_granted_rename_protects_both_names_for_session() {
+        let mut client = KlockClient::new();
+        client.set_compaction_policy(CompactionPolicy {
+            stale_intent_ms: 0,
+            ..CompactionPolicy::default()
+        });
+        client.register_agent("renamer", 100);
+        client.register_agent("other", 200);
+
+        let held = client.acquire_lease(
+            "renamer",
+            "s_rename",
+            "FILE",
+            "/src/old.ts",
+            "RENAMES",
+            60_000,
+        );
+        let LeaseResult::Success { lease } = held else {
+            panic!("Expected lease, got {:?}", held);
+        };
+        let verdict = client.declare_intent(&manifest(
+            "renamer",
+            "s_rename",
+            Predicate::Renames,
+            "/src/old.ts",
+            Some("/src/new.ts"),
+        ));
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+
+        // The old name's lease and intent now also cover the new name
+        assert!(matches!(
+            client.acquire_lease("other", "s_other", "FILE", "/src/new.ts", "MUTATES", 60_000),
+            LeaseResult::Failure { .. }
+        ));
+        let verdict = client.declare_intent(&manifest(
+            "other",
+            "s_other",
+            Predicate::Mutates,
+            "/src/new.ts",
+            None,
+        ));
+        assert_ne!(verdict.status, KernelVerdictStatus::Granted);
+        assert_eq!(client.memory_report().aliases.count, 1);
+
+        // The alias ends with the renaming session
+        assert!(client.release_lease(&lease.id));
+        assert_eq!(client.compact().aliases_removed, 1);
+        assert!(client.conflict_engine().aliases().is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_aliases_persist_in_sqlite() {
+        let path = std::env::temp_dir().join(format!("klock_alias_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        {
+            let mut client = KlockClient::with_sqlite(path).unwrap();
+            client.register_agent("renamer", 100);
+            let verdict = client.declare_intent(&manifest(
+                "renamer",
+                "s_rename",
+                Predicate::Renames,
+                "/src/old.ts",
+                Some("/src/new.ts"),
+            ));
+            assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+            assert!(matches!(
+                client.acquire_lease(
+                    "renamer",
+                    "s_rename",
+                    "FILE",
+                    "/src/old.ts",
+                    "MUTATES",
+                    60_000
+                ),
+                LeaseResult::Success { .. }
+            ));
+        }
+
+        let mut client = KlockClient::with_sqlite(path).unwrap();
+        assert_eq!(client.conflict_engine().aliases().len(), 1);
+        client.register_agent("other", 200);
+        assert!(matches!(
+            client.acquire_lease("other", "s_other", "FILE", "/src/new.ts", "MUTATES", 60_000),
+            LeaseResult::Failure { .. }
+        ));
+
+        drop(client);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,259 @@
+//! Scheduler decision audit records.
+//!
+//! A Die verdict is gone once the agent has read it, so "why did agent X
+//! die at 14:02" cannot be answered from the leases left behind. With an
+//! audit sink installed, the client wraps its scheduler in an
+//! `AuditingScheduler` that hands the sink a `DecisionRecord` for every
+//! decision: who asked for what, the verdict, the conflicting holders and
+//! the priorities it was decided on (after aging, ceilings and fair share).
+//! Decisions for acquires, activations, queued requests and manifests are
+//! all recorded; a manifest the kernel re-evaluates after a concurrent
+//! change is recorded once per evaluation. `AuditLog` keeps the latest
+//! records in memory; other sinks can ship them elsewhere.
+
+use crate::clock::Clock;
+use crate::conflict::ConflictEngine;
+use crate::registry::Priority;
+use crate::scheduler::{Scheduler, SchedulerVerdict, VerdictStatus};
+use crate::types::{Holder, Lease, Predicate, ResourceRef};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// One scheduler decision
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    /// When the decision was made, in ms since the epoch
+    pub at: u64,
+    /// The policy that made it
+    pub scheduler: String,
+    pub agent_id: String,
+    pub session_id: String,
+    pub predicate: Predicate,
+    pub resource: ResourceRef,
+    pub verdict: VerdictStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Every conflicting holder, the one the verdict was decided on first
+    #[serde(default)]
+    pub holders: Vec<Holder>,
+    /// The requester's and holders' priorities as the scheduler saw them;
+    /// unregistered agents are missing
+    #[serde(default)]
+    pub priorities: BTreeMap<String, Priority>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preempted: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+}
+
+/// Where decision records go
+pub trait AuditSink: Send + Sync {
+    fn record(&self, record: &DecisionRecord);
+}
+
+/// Filters for `AuditLog::records`; the default matches everything
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditQuery {
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    /// Only records at or after this time
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Only records before this time
+    #[serde(default)]
+    pub until: Option<u64>,
+    #[serde(default)]
+    pub verdict: Option<VerdictStatus>,
+    /// Only decisions on a resource that overlaps this one
+    #[serde(default)]
+    pub resource: Option<ResourceRef>,
+    /// Return at most the latest `limit` matching records
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, record: &DecisionRecord) -> bool {
+        self.agent_id.as_ref().is_none_or(|a| *a == record.agent_id)
+            && self.since.is_none_or(|since| record.at >= since)
+            && self.until.is_none_or(|until| record.at < until)
+            && self.verdict.is_none_or(|verdict| record.verdict == verdict)
+            && self
+                .resource
+                .as_ref()
+                .is_none_or(|resource| record.resource.overlaps(resource))
+    }
+}
+
+/// The latest `capacity` decisions, in memory; older ones are dropped
+#[derive(Debug)]
+pub struct AuditLog {
+    capacity: usize,
+    records: Mutex<VecDeque<DecisionRecord>>,
+}
+
+impl AuditLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Matching records, oldest first
+    pub fn records(&self, query: &AuditQuery) -> Vec<DecisionRecord> {
+        let records = self.records.lock().unwrap();
+        let matching: Vec<&DecisionRecord> = records.iter().filter(|r| query.matches(r)).collect();
+        let skip = query
+            .limit
+            .map_or(0, |limit| matching.len().saturating_sub(limit));
+        matching.into_iter().skip(skip).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl AuditSink for AuditLog {
+    fn record(&self, record: &DecisionRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.records.lock().unwrap();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record.clone());
+    }
+}
+
+/// A scheduler that reports each of `inner`'s decisions to a sink
+pub struct AuditingScheduler {
+    inner: Arc<dyn Scheduler>,
+    sink: Arc<dyn AuditSink>,
+    clock: Arc<dyn Clock>,
+}
+
+impl AuditingScheduler {
+    pub fn new(inner: Arc<dyn Scheduler>, sink: Arc<dyn AuditSink>, clock: Arc<dyn Clock>) -> Self {
+        Self { inner, sink, clock }
+    }
+
+    fn audit(
+        &self,
+        agent_id: &str,
+        session_id: &str,
+        predicate: Predicate,
+        resource: &ResourceRef,
+        priorities: &HashMap<String, Priority>,
+        verdict: &SchedulerVerdict,
+    ) {
+        let priorities = std::iter::once(agent_id)
+            .chain(verdict.holders.iter().map(|h| h.agent_id.as_str()))
+            .filter_map(|agent_id| Some((agent_id.to_string(), *priorities.get(agent_id)?)))
+            .collect();
+        self.sink.record(&DecisionRecord {
+            at: self.clock.now_ms(),
+            scheduler: self.inner.name_for(resource).to_string(),
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            predicate,
+            resource: resource.clone(),
+            verdict: verdict.status,
+            reason: verdict.reason.clone(),
+            holders: verdict.holders.clone(),
+            priorities,
+            preempted: verdict.preempted.clone(),
+            retry_after_ms: verdict.retry_after_ms,
+        });
+    }
+}
+
+impl Scheduler for AuditingScheduler {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn name_for(&self, resource: &ResourceRef) -> &'static str {
+        self.inner.name_for(resource)
+    }
+
+    fn resolve(
+        &self,
+        requesting_agent_id: &str,
+        conflicting_holders: &[&Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        self.inner
+            .resolve(requesting_agent_id, conflicting_holders, priorities)
+    }
+
+    fn decide(
+        &self,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        let verdict = self.inner.decide(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+        );
+        self.audit(
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            priorities,
+            &verdict,
+        );
+        verdict
+    }
+
+    fn decide_at(
+        &self,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+        now: u64,
+    ) -> SchedulerVerdict {
+        // Recorded with the backoff the stores hand out
+        let verdict = self.inner.decide_at(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+            now,
+        );
+        self.audit(
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            priorities,
+            &verdict,
+        );
+        verdict
+    }
+}
@@ -0,0 +1,144 @@
+#[cfg(test)]
+mod tests {
+    use crate::audit::{AuditLog, AuditQuery, AuditSink};
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::registry::Priority;
+    use crate::scheduler::VerdictStatus;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, LeaseResult, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::sync::Arc;
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn audited_client(capacity: usize) -> (KlockClient, Arc<ManualClock>, Arc<AuditLog>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        let log = Arc::new(AuditLog::new(capacity));
+        client.set_audit_sink(Some(log.clone() as Arc<dyn AuditSink>));
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+        (client, clock, log)
+    }
+
+    #[test]
+    fn test_die_is_recorded_with_holders_and_priorities() {
+        let (mut client, clock, log) = audited_client(100);
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        clock.advance(500);
+        client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000);
+
+        let records = log.records(&AuditQuery::default());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].verdict, VerdictStatus::Granted);
+        assert_eq!(records[0].priorities.len(), 1);
+
+        let died = &records[1];
+        assert_eq!(died.at, 1500);
+        assert_eq!(died.scheduler, "wait-die");
+        assert_eq!(died.agent_id, "junior");
+        assert_eq!(died.session_id, "s2");
+        assert_eq!(died.predicate, Predicate::Mutates);
+        assert_eq!(died.resource, file("/a.ts"));
+        assert_eq!(died.verdict, VerdictStatus::Die);
+        assert_eq!(died.holders[0].lease_id, lease.id);
+        assert_eq!(died.priorities["senior"], Priority::from(100));
+        assert_eq!(died.priorities["junior"], Priority::from(200));
+        // The backoff the store handed out: until the senior's lease expires
+        assert_eq!(died.retry_after_ms, Some(59_500));
+
+        // Unaudited again once the sink is removed
+        client.set_audit_sink(None);
+        client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(log.len(), 2);
+    }
+
+    #[test]
+    fn test_manifests_are_recorded_and_projections_are_not() {
+        let (mut client, clock, log) = audited_client(100);
+        client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        clock.advance(1);
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s2".to_string(),
+            agent_id: "junior".to_string(),
+            intents: vec![SPOTriple {
+                id: "i1".to_string(),
+                subject: "junior".to_string(),
+                predicate: Predicate::Mutates,
+                object: file("/a.ts"),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s2".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        let recorded = log.len();
+        assert!(
+            log.records(&AuditQuery {
+                agent_id: Some("junior".to_string()),
+                verdict: Some(VerdictStatus::Die),
+                ..Default::default()
+            })
+            .iter()
+            .any(|r| r.session_id == "s2")
+        );
+
+        client.project(120_000);
+        assert_eq!(log.len(), recorded);
+    }
+
+    #[test]
+    fn test_log_keeps_latest_records_and_filters() {
+        let (mut client, clock, log) = audited_client(3);
+        for path in ["/src/a.ts", "/src/b.ts", "/docs/c.md", "/src/d.ts"] {
+            client.acquire_lease("senior", "s1", "FILE", path, "MUTATES", 60_000);
+            clock.advance(10);
+        }
+        let paths = |query: &AuditQuery| -> Vec<String> {
+            log.records(query)
+                .into_iter()
+                .map(|r| r.resource.path)
+                .collect()
+        };
+        assert_eq!(
+            paths(&AuditQuery::default()),
+            vec!["/src/b.ts", "/docs/c.md", "/src/d.ts"]
+        );
+        let in_src = AuditQuery {
+            resource: Some(file("/src/**")),
+            ..Default::default()
+        };
+        assert_eq!(paths(&in_src), vec!["/src/b.ts", "/src/d.ts"]);
+        assert_eq!(
+            paths(&AuditQuery {
+                limit: Some(1),
+                ..in_src
+            }),
+            vec!["/src/d.ts"]
+        );
+        assert_eq!(
+            paths(&AuditQuery {
+                since: Some(1020),
+                until: Some(1030),
+                ..Default::default()
+            }),
+            vec!["/docs/c.md"]
+        );
+        assert!(
+            paths(&AuditQuery {
+                agent_id: Some("junior".to_string()),
+                ..Default::default()
+            })
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! How long a refused agent is told to back off.
+//!
+//! Every Die verdict carries a `retry_after_ms`. By default it asks the
+//! agent to come back once the leases in its way run out, which suits
+//! short leases but sends agents blocked by long ones away for minutes. A
+//! `BackoffPolicy` chooses instead: a fixed delay, or one that doubles
+//! with each consecutive Die of the same agent on the same resource, with
+//! optional jitter so agents refused together do not all retry together.
+//! The client wraps its scheduler in a `BackoffScheduler`, so the policy
+//! applies to acquires, activations, queued requests and manifests alike.
+
+use crate::clock::{Clock, splitmix64};
+use crate::conflict::ConflictEngine;
+use crate::registry::Priority;
+use crate::scheduler::{Scheduler, SchedulerVerdict, VerdictStatus};
+use crate::types::{Lease, Predicate, ResourceRef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// The backoff suggested with a Die verdict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum BackoffPolicy {
+    /// Until the last conflicting lease runs out at `now`, unless renewed,
+    /// but at least `min_ms` so a lease about to run out does not invite an
+    /// immediate retry; `base_ms` when no lease is in the way (e.g. an
+    /// intent, or a missing priority). A lease's expiry is its last
+    /// heartbeat plus its TTL, so a holder that stopped heartbeating is
+    /// waited out, and one that keeps heartbeating is checked on again at
+    /// its next renewal deadline.
+    HolderExpiry {
+        #[serde(default = "default_base_ms")]
+        base_ms: u64,
+        #[serde(default = "default_min_ms")]
+        min_ms: u64,
+    },
+    /// Always the same delay
+    Fixed { delay_ms: u64 },
+    /// `base_ms`, doubled for each further consecutive Die of the agent on
+    /// the resource, up to `max_ms`. With `jitter`, a random amount of up to
+    /// half the delay is taken off.
+    Exponential {
+        base_ms: u64,
+        max_ms: u64,
+        #[serde(default)]
+        jitter: bool,
+    },
+}
+
+fn default_base_ms() -> u64 {
+    1000
+}
+
+fn default_min_ms() -> u64 {
+    100
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        Self::HolderExpiry {
+            base_ms: default_base_ms(),
+            min_ms: default_min_ms(),
+        }
+    }
+}
+
+impl BackoffPolicy {
+    /// Parse a policy as written on the command line: `holder-expiry`,
+    /// `holder-expiry:<base_ms>:<min_ms>`, `fixed:<ms>`,
+    /// `exponential:<base_ms>:<max_ms>` or
+    /// `exponential-jitter:<base_ms>:<max_ms>`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let number = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid backoff '{}': '{}' is not a number", spec, value))
+        };
+        let parts: Vec<&str> = spec.split(':').collect();
+        let policy = match parts.as_slice() {
+            ["holder-expiry"] => Self::default(),
+            ["holder-expiry", base, min] => Self::HolderExpiry {
+                base_ms: number(base)?,
+                min_ms: number(min)?,
+            },
+            ["fixed", delay] => Self::Fixed {
+                delay_ms: number(delay)?,
+            },
+            [strategy @ ("exponential" | "exponential-jitter"), base, max] => Self::Exponential {
+                base_ms: number(base)?,
+                max_ms: number(max)?,
+                jitter: *strategy == "exponential-jitter",
+            },
+            _ => {
+                return Err(format!(
+                    "Invalid backoff '{}': use holder-expiry, \
+                     holder-expiry:<base_ms>:<min_ms>, fixed:<ms>, exponential:<base_ms>:<max_ms> or exponential-jitter:<base_ms>:<max_ms>",
+                    spec
+                ));
+            }
+        };
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Self::HolderExpiry { base_ms: 0, .. } => {
+                Err("Backoff base must be at least 1 ms".to_string())
+            }
+            Self::HolderExpiry { .. } => Ok(()),
+            Self::Fixed { delay_ms: 0 } => Err("Backoff delay must be at least 1 ms".to_string()),
+            Self::Fixed { .. } => Ok(()),
+            Self::Exponential { base_ms: 0, .. } => {
+                Err("Backoff base must be at least 1 ms".to_string())
+            }
+            Self::Exponential {
+                base_ms, max_ms, ..
+            } if max_ms < base_ms => Err(format!(
+                "Backoff maximum ({} ms) is below its base ({} ms)",
+                max_ms, base_ms
+            )),
+            Self::Exponential { .. } => Ok(()),
+        }
+    }
+
+    /// The backoff for the `attempt`th consecutive Die (counting from 1) of
+    /// `agent_id`, refused at `now` because of `holders`
+    pub fn retry_after_ms(
+        &self,
+        agent_id: &str,
+        holders: &[&Lease],
+        attempt: u32,
+        now: u64,
+    ) -> u64 {
+        match *self {
+            Self::HolderExpiry { base_ms, min_ms } => holders
+                .iter()
+                .map(|lease| lease.expires_at.saturating_sub(now))
+                .max()
+                .unwrap_or(base_ms)
+                .max(min_ms),
+            Self::Fixed { delay_ms } => delay_ms,
+            Self::Exponential {
+                base_ms,
+                max_ms,
+                jitter,
+            } => {
+                let doublings = attempt.saturating_sub(1).min(63);
+                let delay = base_ms.saturating_mul(1 << doublings).min(max_ms);
+                if !jitter {
+                    return delay;
+                }
+                // Deterministic under a manual clock, but different for
+                // agents refused at the same moment
+                let mut hasher = DefaultHasher::new();
+                agent_id.hash(&mut hasher);
+                attempt.hash(&mut hasher);
+                let half = delay / 2;
+                delay - splitmix64(now, hasher.finish()) % (half + 1)
+            }
+        }
+    }
+
+    /// Whether the backoff depends on how many times in a row the agent
+    /// was refused
+    fn counts_attempts(&self) -> bool {
+        matches!(self, Self::Exponential { .. })
+    }
+}
+
+impl fmt::Display for BackoffPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HolderExpiry { .. } if *self == Self::default() => write!(f, "holder-expiry"),
+            Self::HolderExpiry { base_ms, min_ms } => {
+                write!(f, "holder-expiry:{}:{}", base_ms, min_ms)
+            }
+            Self::Fixed { delay_ms } => write!(f, "fixed:{}", delay_ms),
+            Self::Exponential {
+                base_ms,
+                max_ms,
+                jitter: false,
+            } => write!(f, "exponential:{}:{}", base_ms, max_ms),
+            Self::Exponential {
+                base_ms,
+                max_ms,
+                jitter: true,
+            } => write!(f, "exponential-jitter:{}:{}", base_ms, max_ms),
+        }
+    }
+}
+
+/// A scheduler whose Die verdicts carry `policy`'s backoff
+pub struct BackoffScheduler {
+    inner: Arc<dyn Scheduler>,
+    policy: BackoffPolicy,
+    clock: Arc<dyn Clock>,
+    /// Consecutive Die verdicts per agent and resource key, for
+    /// exponential backoff; a grant clears them
+    attempts: Mutex<HashMap<(String, String), u32>>,
+}
+
+impl BackoffScheduler {
+    pub fn new(inner: Arc<dyn Scheduler>, policy: BackoffPolicy, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inner,
+            policy,
+            clock,
+            attempts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn back_off(
+        &self,
+        engine: &ConflictEngine,
+        agent_id: &str,
+        session_id: &str,
+        predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        now: u64,
+        verdict: &mut SchedulerVerdict,
+    ) {
+        let attempt = if self.policy.counts_attempts() {
+            let key = (agent_id.to_string(), resource.key());
+            let mut attempts = self.attempts.lock().unwrap();
+            match verdict.status {
+                VerdictStatus::Granted => {
+                    attempts.remove(&key);
+                    return;
+                }
+                VerdictStatus::Wait => return,
+                VerdictStatus::Die => {
+                    let attempt = attempts.entry(key).or_default();
+                    *attempt += 1;
+                    *attempt
+                }
+            }
+        } else if verdict.status == VerdictStatus::Die {
+            1
+        } else {
+            return;
+        };
+        let holders =
+            engine.conflicting_leases(agent_id, session_id, predicate, resource, active_leases);
+        verdict.retry_after_ms = Some(self.policy.retry_after_ms(agent_id, &holders, attempt, now));
+    }
+}
+
+impl Scheduler for BackoffScheduler {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn name_for(&self, resource: &ResourceRef) -> &'static str {
+        self.inner.name_for(resource)
+    }
+
+    fn resolve(
+        &self,
+        requesting_agent_id: &str,
+        conflicting_holders: &[&Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        self.inner
+            .resolve(requesting_agent_id, conflicting_holders, priorities)
+    }
+
+    fn decide(
+        &self,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        self.decide_at(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+            self.clock.now_ms(),
+        )
+    }
+
+    fn decide_at(
+        &self,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+        now: u64,
+    ) -> SchedulerVerdict {
+        let mut verdict = self.inner.decide(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+        );
+        self.back_off(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            now,
+            &mut verdict,
+        );
+        verdict
+    }
+}
@@ -0,0 +1,192 @@
+#[cfg(test)]
+mod tests {
+    use crate::backoff::BackoffPolicy;
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::scheduler::NoWaitScheduler;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use std::sync::Arc;
+
+    fn client_with(policy: BackoffPolicy) -> (KlockClient, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.set_backoff_policy(policy);
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+        (client, clock)
+    }
+
+    /// The backoff of a refused acquire
+    fn retry_after(result: LeaseResult) -> Option<u64> {
+        match result {
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                wait_time,
+                ..
+            } => wait_time,
+            other => panic!("Expected a Die, got {:?}", other),
+        }
+    }
+
+    fn lease_id(result: LeaseResult) -> String {
+        match result {
+            LeaseResult::Success { lease } => lease.id,
+            other => panic!("Expected success, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_and_display_round_trip() {
+        for spec in [
+            "holder-expiry",
+            "holder-expiry:500:50",
+            "fixed:250",
+            "exponential:100:30000",
+            "exponential-jitter:100:30000",
+        ] {
+            assert_eq!(BackoffPolicy::parse(spec).unwrap().to_string(), spec);
+        }
+        assert_eq!(
+            BackoffPolicy::parse("exponential-jitter:100:800").unwrap(),
+            BackoffPolicy::Exponential {
+                base_ms: 100,
+                max_ms: 800,
+                jitter: true
+            }
+        );
+        for spec in [
+            "linear",
+            "holder-expiry:0:100",
+            "fixed",
+            "fixed:0",
+            "fixed:soon",
+            "exponential:100",
+            "exponential:500:100",
+        ] {
+            assert!(BackoffPolicy::parse(spec).is_err(), "{}", spec);
+        }
+        assert_eq!(
+            BackoffPolicy::default(),
+            BackoffPolicy::HolderExpiry {
+                base_ms: 1000,
+                min_ms: 100
+            }
+        );
+    }
+
+    #[test]
+    fn test_exponential_backoff_doubles_per_resource_until_granted() {
+        let (mut client, clock) = client_with(BackoffPolicy::Exponential {
+            base_ms: 100,
+            max_ms: 300,
+            jitter: false,
+        });
+        let held =
+            lease_id(client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000));
+        clock.advance(1);
+        client.acquire_lease("senior", "s1", "FILE", "/b.ts", "MUTATES", 60_000);
+
+        let mut backoffs = Vec::new();
+        for _ in 0..4 {
+            clock.advance(1);
+            backoffs.push(retry_after(
+                client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000),
+            ));
+        }
+        assert_eq!(backoffs, vec![Some(100), Some(200), Some(300), Some(300)]);
+
+        // Counted per resource
+        assert_eq!(
+            retry_after(client.acquire_lease("junior", "s2", "FILE", "/b.ts", "MUTATES", 60_000)),
+            Some(100)
+        );
+
+        // A grant starts the count over
+        assert!(client.release_lease(&held));
+        clock.advance(1);
+        let junior =
+            lease_id(client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000));
+        assert!(client.release_lease(&junior));
+        clock.advance(1);
+        client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        clock.advance(1);
+        assert_eq!(
+            retry_after(client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000)),
+            Some(100)
+        );
+    }
+
+    #[test]
+    fn test_holder_expiry_base_and_floor_are_configurable() {
+        let policy = BackoffPolicy::HolderExpiry {
+            base_ms: 250,
+            min_ms: 50,
+        };
+        // Nothing in the way: the base
+        assert_eq!(policy.retry_after_ms("junior", &[], 1, 1000), 250);
+
+        // No-wait verdicts back off under the policy too
+        let (mut client, clock) = client_with(policy);
+        client.set_scheduler(Arc::new(NoWaitScheduler));
+        client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 1000);
+        clock.set(1990);
+        // 10 ms left on the holder's lease, but never below the floor
+        assert_eq!(
+            retry_after(client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 1000)),
+            Some(50)
+        );
+    }
+
+    #[test]
+    fn test_jitter_stays_within_the_upper_half() {
+        let policy = BackoffPolicy::Exponential {
+            base_ms: 1000,
+            max_ms: 1000,
+            jitter: true,
+        };
+        let mut spread = std::collections::BTreeSet::new();
+        for now in 0..50 {
+            let backoff = policy.retry_after_ms("junior", &[], 1, now);
+            assert!((500..=1000).contains(&backoff), "{}", backoff);
+            assert_eq!(policy.retry_after_ms("junior", &[], 1, now), backoff);
+            spread.insert(backoff);
+        }
+        assert!(spread.len() > 1);
+    }
+
+    #[test]
+    fn test_fixed_backoff_applies_to_manifests() {
+        let (mut client, clock) = client_with(BackoffPolicy::Fixed { delay_ms: 250 });
+        client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        clock.advance(1);
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s2".to_string(),
+            agent_id: "junior".to_string(),
+            intents: vec![SPOTriple {
+                id: "i1".to_string(),
+                subject: "junior".to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, "/a.ts"),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s2".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(verdict.retry_after_ms, Some(250));
+
+        // Back to waiting out the holder
+        client.set_backoff_policy(BackoffPolicy::default());
+        assert_eq!(
+            retry_after(client.acquire_lease("junior", "s2", "FILE", "/a.ts", "MUTATES", 60_000)),
+            Some(59_999)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Coordination barriers.
+//!
+//! A barrier is a named rendezvous point: it is created for a fixed number
+//! of parties and trips once that many distinct agents have arrived. Agents
+//! that arrive early learn from the returned status that they must wait;
+//! the arrival that completes the set sees `tripped: true`, as does every
+//! later status check. Barriers are single-use — delete and recreate one to
+//! start another phase.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Point-in-time view of a barrier
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BarrierStatus {
+    pub name: String,
+    /// Number of distinct agents required to trip the barrier
+    pub parties: usize,
+    /// Agents that have arrived, in arrival order
+    pub arrived: Vec<String>,
+    /// Arrivals still missing (`0` once tripped)
+    pub waiting_for: usize,
+    pub tripped: bool,
+    pub created_at: u64,
+    /// When the last required agent arrived
+    pub tripped_at: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+struct Barrier {
+    parties: usize,
+    arrived: Vec<String>,
+    created_at: u64,
+    tripped_at: Option<u64>,
+}
+
+impl Barrier {
+    fn status(&self, name: &str) -> BarrierStatus {
+        BarrierStatus {
+            name: name.to_string(),
+            parties: self.parties,
+            arrived: self.arrived.clone(),
+            waiting_for: self.parties.saturating_sub(self.arrived.len()),
+            tripped: self.tripped_at.is_some(),
+            created_at: self.created_at,
+            tripped_at: self.tripped_at,
+        }
+    }
+}
+
+/// All barriers known to a client, keyed by name
+#[derive(Debug, Default)]
+pub struct BarrierRegistry {
+    barriers: HashMap<String, Barrier>,
+}
+
+impl BarrierRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a barrier that trips once `parties` distinct agents arrive.
+    pub fn create(
+        &mut self,
+        name: &str,
+        parties: usize,
+        now: u64,
+    ) -> Result<BarrierStatus, String> {
+        if name.is_empty() {
+            return Err("Barrier name must not be empty".to_string());
+        }
+        if parties == 0 {
+            return Err("Barrier parties must be greater than 0".to_string());
+        }
+        if self.barriers.contains_key(name) {
+            return Err(format!("Barrier '{}' already exists", name));
+        }
+        let barrier = Barrier {
+            parties,
+            arrived: Vec::new(),
+            created_at: now,
+            tripped_at: None,
+        };
+        let status = barrier.status(name);
+        self.barriers.insert(name.to_string(), barrier);
+        Ok(status)
+    }
+
+    /// Record that `agent_id` reached the barrier. Arriving twice, or after
+    /// the barrier tripped, is a no-op that returns the current status.
+    pub fn arrive(
+        &mut self,
+        name: &str,
+        agent_id: &str,
+        now: u64,
+    ) -> Result<BarrierStatus, String> {
+        if agent_id.is_empty() {
+            return Err("agent_id must not be empty".to_string());
+        }
+        let barrier = self
+            .barriers
+            .get_mut(name)
+            .ok_or_else(|| format!("Barrier '{}' not found", name))?;
+        if barrier.tripped_at.is_none() && !barrier.arrived.iter().any(|a| a == agent_id) {
+            barrier.arrived.push(agent_id.to_string());
+            if barrier.arrived.len() >= barrier.parties {
+                barrier.tripped_at = Some(now);
+            }
+        }
+        Ok(barrier.status(name))
+    }
+
+    pub fn status(&self, name: &str) -> Option<BarrierStatus> {
+        self.barriers.get(name).map(|barrier| barrier.status(name))
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.barriers.remove(name).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.barriers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.barriers.is_empty()
+    }
+
+    /// Approximate footprint of all barriers
+    pub fn estimated_bytes(&self) -> usize {
+        self.barriers
+            .iter()
+            .map(|(name, barrier)| {
+                name.capacity()
+                    + std::mem::size_of::<(String, Barrier)>()
+                    + barrier
+                        .arrived
+                        .iter()
+                        .map(|a| a.capacity() + std::mem::size_of::<String>())
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use crate::barrier::BarrierRegistry;
+    use crate::client::KlockClient;
+
+    #[test]
+    fn test_barrier_trips_when_all_parties_arrive() {
+        let mut barriers = BarrierRegistry::new();
+        let status = barriers.create("phase-1", 3, 1000).unwrap();
+        assert_eq!(status.waiting_for, 3);
+        assert!(!status.tripped);
+
+        let status = barriers.arrive("phase-1", "agent-a", 1100).unwrap();
+        assert_eq!(status.waiting_for, 2);
+        // Arriving twice does not count twice
+        let status = barriers.arrive("phase-1", "agent-a", 1150).unwrap();
+        assert_eq!(status.arrived, vec!["agent-a".to_string()]);
+
+        barriers.arrive("phase-1", "agent-b", 1200).unwrap();
+        let status = barriers.arrive("phase-1", "agent-c", 1300).unwrap();
+        assert!(status.tripped);
+        assert_eq!(status.waiting_for, 0);
+        assert_eq!(status.tripped_at, Some(1300));
+
+        // Late arrivals see the tripped barrier unchanged
+        let status = barriers.arrive("phase-1", "agent-d", 1400).unwrap();
+        assert_eq!(status.arrived.len(), 3);
+        assert_eq!(status.tripped_at, Some(1300));
+    }
+
+    #[test]
+    fn test_barrier_errors() {
+        let mut barriers = BarrierRegistry::new();
+        assert!(barriers.create("", 2, 1000).is_err());
+        assert!(barriers.create("b", 0, 1000).is_err());
+        barriers.create("b", 2, 1000).unwrap();
+        assert!(barriers.create("b", 2, 1000).is_err());
+        assert!(barriers.arrive("missing", "agent", 1000).is_err());
+        assert!(barriers.arrive("b", "", 1000).is_err());
+
+        assert!(barriers.remove("b"));
+        assert!(barriers.status("b").is_none());
+        assert!(barriers.create("b", 2, 2000).is_ok());
+    }
+
+    #[test]
+    fn test_client_barrier_round_trip() {
+        let mut client = KlockClient::new();
+        client.create_barrier("refactor", 2).unwrap();
+        assert!(!client.arrive("refactor", "agent-1").unwrap().tripped);
+        assert!(client.arrive("refactor", "agent-2").unwrap().tripped);
+        assert!(client.barrier_status("refactor").unwrap().tripped);
+        assert_eq!(client.memory_report().barriers.count, 1);
+
+        assert!(client.remove_barrier("refactor"));
+        assert!(client.barrier_status("refactor").is_none());
+    }
+}
@@ -0,0 +1,169 @@
+//! Load shedding near capacity.
+//!
+//! A server keeping its state in memory grows with every lease agents take,
+//! and running out of memory loses every lease at once. A `CapacityPolicy`
+//! caps the leases held (active and reserved) and the client's estimated
+//! memory (see `MemoryReport`), and sheds load before either cap is
+//! reached, lowest priority first: from `shed_from_percent` of a cap the
+//! client refuses acquires and reservations by background agents (and by
+//! agents without a priority), from halfway between that and the cap by
+//! normal agents too, and at the cap by everyone, with `CapacityExceeded`.
+//! Leases already held are kept, unless `revoke_advisory` is set: then, at
+//! the lease cap, the client revokes advisory leases — those that change
+//! nothing (`CONSUMES`, `DEPENDS_ON`, `PROVIDES`) — lowest priority first,
+//! until it is back under the cap.
+
+use crate::registry::PriorityClass;
+use crate::types::Predicate;
+use serde::{Deserialize, Serialize};
+
+/// Default share of a cap from which acquires are shed
+pub const DEFAULT_SHED_FROM_PERCENT: u8 = 80;
+
+/// Caps on what the client holds, and how to shed load near them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapacityPolicy {
+    /// Most leases held at once, active and reserved; `None` for no cap
+    pub max_leases: Option<usize>,
+    /// Most memory the client's structures may take, as estimated by
+    /// `memory_report`; `None` for no cap
+    pub max_memory_bytes: Option<usize>,
+    /// Share of a cap (1-100) from which background agents are refused
+    pub shed_from_percent: u8,
+    /// At the lease cap, revoke advisory leases to get back under it
+    pub revoke_advisory: bool,
+}
+
+impl Default for CapacityPolicy {
+    /// No caps
+    fn default() -> Self {
+        Self {
+            max_leases: None,
+            max_memory_bytes: None,
+            shed_from_percent: DEFAULT_SHED_FROM_PERCENT,
+            revoke_advisory: false,
+        }
+    }
+}
+
+impl CapacityPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_leases.is_some() || self.max_memory_bytes.is_some()
+    }
+
+    /// How much to shed with `leases` held and `memory_bytes` in use: by
+    /// the fuller of the two caps
+    pub fn level(&self, leases: usize, memory_bytes: usize) -> ShedLevel {
+        let percent = |used: usize, cap: usize| used as u128 * 100 / cap.max(1) as u128;
+        let used = self.max_leases.map_or(0, |cap| percent(leases, cap)).max(
+            self.max_memory_bytes
+                .map_or(0, |cap| percent(memory_bytes, cap)),
+        );
+        let shed_from = u128::from(self.shed_from_percent.clamp(1, 100));
+        if used >= 100 {
+            ShedLevel::All
+        } else if used >= (shed_from + 100) / 2 {
+            ShedLevel::Normal
+        } else if used >= shed_from {
+            ShedLevel::Background
+        } else {
+            ShedLevel::None
+        }
+    }
+
+    /// Whether `leases` reaches the lease cap
+    pub fn at_lease_cap(&self, leases: usize) -> bool {
+        self.max_leases.is_some_and(|cap| leases >= cap)
+    }
+}
+
+/// Whose acquires are refused
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShedLevel {
+    /// Nobody's
+    #[default]
+    None,
+    /// Background agents'
+    Background,
+    /// Normal and background agents'
+    Normal,
+    /// Everyone's: a cap is reached
+    All,
+}
+
+impl ShedLevel {
+    /// Whether an acquire by an agent of `class` is refused
+    pub fn refuses(&self, class: PriorityClass) -> bool {
+        match self {
+            ShedLevel::None => false,
+            ShedLevel::Background => class == PriorityClass::Background,
+            ShedLevel::Normal => class != PriorityClass::Critical,
+            ShedLevel::All => true,
+        }
+    }
+}
+
+/// Whether a lease on `predicate` is advisory: it changes nothing, so
+/// revoking it under capacity pressure loses no work in progress
+pub fn is_advisory(predicate: Predicate) -> bool {
+    matches!(
+        predicate,
+        Predicate::Consumes | Predicate::DependsOn | Predicate::Provides
+    )
+}
+
+/// Shedding state, for health checks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapacityReport {
+    pub level: ShedLevel,
+    /// Leases held, active and reserved
+    pub leases: usize,
+    pub max_leases: Option<usize>,
+    /// Estimated memory in use; only measured with a memory cap
+    pub memory_bytes: usize,
+    pub max_memory_bytes: Option<usize>,
+    /// Acquires and reservations refused for capacity
+    pub refused: u64,
+    /// Advisory leases revoked for capacity
+    pub revoked: u64,
+}
+
+/// The policy and what it has shed so far
+#[derive(Debug, Clone, Default)]
+pub struct LoadShedder {
+    policy: CapacityPolicy,
+    refused: u64,
+    revoked: u64,
+}
+
+impl LoadShedder {
+    pub fn policy(&self) -> &CapacityPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: CapacityPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn record_refusal(&mut self) {
+        self.refused += 1;
+    }
+
+    pub fn record_revocation(&mut self) {
+        self.revoked += 1;
+    }
+
+    /// The state with `leases` held and `memory_bytes` in use
+    pub fn report(&self, leases: usize, memory_bytes: usize) -> CapacityReport {
+        CapacityReport {
+            level: self.policy.level(leases, memory_bytes),
+            leases,
+            max_leases: self.policy.max_leases,
+            memory_bytes,
+            max_memory_bytes: self.policy.max_memory_bytes,
+            refused: self.refused,
+            revoked: self.revoked,
+        }
+    }
+}
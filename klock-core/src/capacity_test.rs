@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod tests {
+    use crate::capacity::{CapacityPolicy, ShedLevel, is_advisory};
+    use crate::client::KlockClient;
+    use crate::clock::{Clock, ManualClock};
+    use crate::feed::AgentEvent;
+    use crate::registry::PriorityClass;
+    use crate::types::{LeaseFailureReason, LeaseResult, LeaseState, Predicate};
+    use std::sync::Arc;
+
+    fn policy(max_leases: usize, revoke_advisory: bool) -> CapacityPolicy {
+        CapacityPolicy {
+            max_leases: Some(max_leases),
+            max_memory_bytes: None,
+            shed_from_percent: 60,
+            revoke_advisory,
+        }
+    }
+
+    fn client() -> (KlockClient, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent_in_class("crit", PriorityClass::Critical, 100);
+        client.register_agent("norm", 100);
+        client.register_agent_in_class("bg", PriorityClass::Background, 100);
+        (client, clock)
+    }
+
+    fn acquire(
+        client: &mut KlockClient,
+        clock: &ManualClock,
+        agent_id: &str,
+        predicate: &str,
+    ) -> LeaseResult {
+        clock.advance(1);
+        let path = format!("/{}.ts", clock.now_ms());
+        client.acquire_lease(agent_id, "s1", "FILE", &path, predicate, 60_000)
+    }
+
+    fn shed(result: &LeaseResult) -> bool {
+        matches!(
+            result,
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::CapacityExceeded,
+                ..
+            }
+        )
+    }
+
+    #[test]
+    fn test_levels_shed_lowest_classes_first() {
+        let policy = policy(10, false);
+        let levels: Vec<ShedLevel> = [0, 5, 6, 7, 8, 9, 10, 12]
+            .into_iter()
+            .map(|leases| policy.level(leases, 0))
+            .collect();
+        assert_eq!(
+            levels,
+            [
+                ShedLevel::None,
+                ShedLevel::None,
+                ShedLevel::Background,
+                ShedLevel::Background,
+                ShedLevel::Normal,
+                ShedLevel::Normal,
+                ShedLevel::All,
+                ShedLevel::All,
+            ]
+        );
+        // The fuller cap decides
+        let both = CapacityPolicy {
+            max_memory_bytes: Some(1000),
+            ..policy
+        };
+        assert_eq!(both.level(1, 1000), ShedLevel::All);
+        assert!(!CapacityPolicy::default().is_enabled());
+
+        assert!(ShedLevel::Background.refuses(PriorityClass::Background));
+        assert!(!ShedLevel::Background.refuses(PriorityClass::Normal));
+        assert!(!ShedLevel::Normal.refuses(PriorityClass::Critical));
+        assert!(ShedLevel::All.refuses(PriorityClass::Critical));
+        assert!(is_advisory(Predicate::Consumes));
+        assert!(!is_advisory(Predicate::Mutates));
+    }
+
+    #[test]
+    fn test_client_refuses_acquires_near_the_cap() {
+        let (mut client, clock) = client();
+        client.set_capacity_policy(policy(5, false));
+        assert_eq!(client.capacity_report().unwrap().level, ShedLevel::None);
+
+        for _ in 0..3 {
+            assert!(!shed(&acquire(&mut client, &clock, "norm", "MUTATES")));
+        }
+        // 3 of 5: background agents and agents without a priority are shed
+        assert!(shed(&acquire(&mut client, &clock, "bg", "MUTATES")));
+        assert!(shed(&acquire(&mut client, &clock, "stranger", "MUTATES")));
+        assert!(!shed(&acquire(&mut client, &clock, "norm", "MUTATES")));
+        // 4 of 5: normal agents too
+        assert!(shed(&acquire(&mut client, &clock, "norm", "MUTATES")));
+        assert!(!shed(&acquire(&mut client, &clock, "crit", "MUTATES")));
+        // At the cap: everyone, reservations included
+        assert!(shed(&acquire(&mut client, &clock, "crit", "MUTATES")));
+        assert!(
+            client
+                .pre_acquire("crit", "s1", &[], 60_000, 120_000)
+                .is_err()
+        );
+
+        let report = client.capacity_report().unwrap();
+        assert_eq!(report.level, ShedLevel::All);
+        assert_eq!((report.leases, report.max_leases), (5, Some(5)));
+        assert_eq!((report.refused, report.revoked), (5, 0));
+
+        // Releasing brings the level down again
+        let lease_id = client.get_active_leases()[0].id.clone();
+        assert!(client.release_lease(&lease_id));
+        assert!(!shed(&acquire(&mut client, &clock, "crit", "MUTATES")));
+        assert!(client.capacity_report().is_some());
+        client.set_capacity_policy(CapacityPolicy::default());
+        assert!(client.capacity_report().is_none());
+    }
+
+    #[test]
+    fn test_advisory_leases_are_revoked_at_the_cap() {
+        let (mut client, clock) = client();
+        client.set_capacity_policy(policy(3, true));
+        let lease_id = |result: LeaseResult| match result {
+            LeaseResult::Success { lease } => lease.id,
+            other => panic!("Expected success, got {:?}", other),
+        };
+        let reader = lease_id(acquire(&mut client, &clock, "norm", "CONSUMES"));
+        let writer = lease_id(acquire(&mut client, &clock, "norm", "MUTATES"));
+        let senior_reader = lease_id(acquire(&mut client, &clock, "crit", "CONSUMES"));
+
+        // The normal agent's read goes, the critical one's and the write stay
+        assert!(!shed(&acquire(&mut client, &clock, "crit", "MUTATES")));
+        let state = |client: &KlockClient, id: &str| client.get_lease(id).unwrap().state;
+        assert_eq!(state(&client, &reader), LeaseState::Revoked);
+        assert_eq!(state(&client, &writer), LeaseState::Active);
+        assert_eq!(state(&client, &senior_reader), LeaseState::Active);
+        assert!(matches!(
+            client.poll_events("norm", "s1").events.as_slice(),
+            [AgentEvent::LeaseRevoked {
+                revoked_by: None,
+                ..
+            }]
+        ));
+        assert_eq!(client.capacity_report().unwrap().revoked, 1);
+
+        // Then the critical agent's; once only writes are left, nothing goes
+        assert!(!shed(&acquire(&mut client, &clock, "crit", "MUTATES")));
+        assert_eq!(state(&client, &senior_reader), LeaseState::Revoked);
+        assert!(shed(&acquire(&mut client, &clock, "crit", "MUTATES")));
+        assert_eq!(state(&client, &writer), LeaseState::Active);
+        assert_eq!(client.capacity_report().unwrap().revoked, 2);
+    }
+}
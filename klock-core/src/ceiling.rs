@@ -0,0 +1,118 @@
+//! Priority ceilings for critical resources.
+//!
+//! A critical resource carries a ceiling priority, typically older than any
+//! agent. While an agent holds an active lease overlapping it, the agent
+//! runs at the ceiling (or its own priority, if older): under Wound-Wait no
+//! one can wound it mid-critical-section, and under Wait-Die it waits for
+//! seniors in its other requests instead of dying and abandoning the
+//! section. Its priority reverts when its last such lease is released,
+//! revoked or expires.
+
+use crate::types::ResourceRef;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The ceiling priority of a critical resource
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriorityCeiling {
+    /// The critical resource; directories and glob patterns make everything
+    /// they cover critical
+    pub resource: ResourceRef,
+    /// Priority timestamp assumed by holders (lower = older)
+    pub ceiling: u64,
+}
+
+/// An agent currently running at a ceiling
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CeilingAssignment {
+    pub agent_id: String,
+    /// The lowest ceiling among the agent's critical leases
+    pub ceiling: u64,
+    /// How far the ceiling improves the agent's priority
+    pub boost: u64,
+}
+
+impl PriorityCeiling {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.resource.path.is_empty() {
+            return Err("Priority ceiling has an empty resource path".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The configured ceilings, and the boost each holder currently has applied
+/// to its registered priority
+#[derive(Debug, Clone, Default)]
+pub struct CeilingTable {
+    ceilings: Vec<PriorityCeiling>,
+    applied: HashMap<String, (u64, u64)>,
+}
+
+impl CeilingTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the configured ceilings. Boosts already applied are kept
+    /// until the holders are next refreshed.
+    pub fn set_ceilings(&mut self, ceilings: Vec<PriorityCeiling>) -> Result<(), String> {
+        for ceiling in &ceilings {
+            ceiling.validate()?;
+        }
+        self.ceilings = ceilings;
+        Ok(())
+    }
+
+    pub fn ceilings(&self) -> &[PriorityCeiling] {
+        &self.ceilings
+    }
+
+    /// The ceiling a lease on `resource` confers: the lowest of the
+    /// ceilings overlapping it
+    pub fn ceiling_for(&self, resource: &ResourceRef) -> Option<u64> {
+        self.ceilings
+            .iter()
+            .filter(|c| c.resource.overlaps(resource))
+            .map(|c| c.ceiling)
+            .min()
+    }
+
+    /// The boost currently subtracted from the agent's registered priority
+    pub fn applied(&self, agent_id: &str) -> u64 {
+        self.applied.get(agent_id).map_or(0, |(_, boost)| *boost)
+    }
+
+    /// Record that the agent runs at `ceiling`, `boost` below its own
+    /// priority; `None` once it holds no critical lease
+    pub fn set_applied(&mut self, agent_id: &str, ceiling: Option<u64>, boost: u64) {
+        match ceiling {
+            Some(ceiling) if boost > 0 => {
+                self.applied.insert(agent_id.to_string(), (ceiling, boost));
+            }
+            _ => {
+                self.applied.remove(agent_id);
+            }
+        }
+    }
+
+    /// Agents currently running at a ceiling, by agent ID
+    pub fn assignments(&self) -> Vec<CeilingAssignment> {
+        let mut assignments: Vec<CeilingAssignment> = self
+            .applied
+            .iter()
+            .map(|(agent_id, (ceiling, boost))| CeilingAssignment {
+                agent_id: agent_id.clone(),
+                ceiling: *ceiling,
+                boost: *boost,
+            })
+            .collect();
+        assignments.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        assignments
+    }
+
+    /// Agents with a boost applied
+    pub fn boosted_agents(&self) -> Vec<String> {
+        self.applied.keys().cloned().collect()
+    }
+}
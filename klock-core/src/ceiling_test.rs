@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod tests {
+    use crate::ceiling::{CeilingAssignment, CeilingTable, PriorityCeiling};
+    use crate::client::KlockClient;
+    use crate::scheduler::scheduler_by_name;
+    use crate::types::{LeaseFailureReason, LeaseResult, LeaseState, ResourceRef, ResourceType};
+
+    fn ceiling(path: &str, ceiling: u64) -> PriorityCeiling {
+        PriorityCeiling {
+            resource: ResourceRef::new(ResourceType::File, path),
+            ceiling,
+        }
+    }
+
+    #[test]
+    fn test_ceiling_for_takes_lowest_overlapping() {
+        let mut table = CeilingTable::new();
+        table
+            .set_ceilings(vec![ceiling("/db/", 50), ceiling("/db/schema.sql", 10)])
+            .unwrap();
+        let file = |path: &str| ResourceRef::new(ResourceType::File, path);
+        assert_eq!(table.ceiling_for(&file("/db/schema.sql")), Some(10));
+        assert_eq!(table.ceiling_for(&file("/db/seed.sql")), Some(50));
+        assert_eq!(table.ceiling_for(&file("/src/main.rs")), None);
+        // A lease on the whole directory covers both
+        assert_eq!(table.ceiling_for(&file("/db/")), Some(10));
+
+        assert!(table.set_ceilings(vec![ceiling("", 0)]).is_err());
+    }
+
+    #[test]
+    fn test_holder_of_critical_resource_is_not_wounded() {
+        let mut client =
+            KlockClient::new().with_scheduler(scheduler_by_name("wound-wait").unwrap());
+        client
+            .set_priority_ceilings(vec![ceiling("/db/", 0)])
+            .unwrap();
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("junior", "s2", "FILE", "/db/schema.sql", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        assert_eq!(client.agent_priority("junior"), Some(0.into()));
+        assert_eq!(
+            client.ceiling_assignments(),
+            vec![CeilingAssignment {
+                agent_id: "junior".to_string(),
+                ceiling: 0,
+                boost: 200,
+            }]
+        );
+
+        // The senior waits instead of wounding the junior mid-migration
+        assert!(matches!(
+            client.acquire_lease("senior", "s1", "FILE", "/db/schema.sql", "MUTATES", 60_000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Wait,
+                ..
+            }
+        ));
+        assert_eq!(
+            client.get_lease(&lease.id).unwrap().state,
+            LeaseState::Active
+        );
+
+        // Released: back to its own priority, and wounded as usual again.
+        // The queued senior is granted the resource and takes the ceiling.
+        assert!(client.release_lease(&lease.id));
+        assert_eq!(client.agent_priority("junior"), Some(200.into()));
+        assert_eq!(
+            client.ceiling_assignments(),
+            vec![CeilingAssignment {
+                agent_id: "senior".to_string(),
+                ceiling: 0,
+                boost: 100,
+            }]
+        );
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("junior", "s2", "FILE", "/src/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        assert!(matches!(
+            client.acquire_lease("senior", "s1", "FILE", "/src/a.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        assert_eq!(
+            client.get_lease(&lease.id).unwrap().state,
+            LeaseState::Revoked
+        );
+    }
+
+    #[test]
+    fn test_changing_ceilings_moves_current_holders() {
+        let mut client = KlockClient::new();
+        client.register_agent("junior", 200);
+        client.acquire_lease("junior", "s1", "FILE", "/db/schema.sql", "MUTATES", 60_000);
+        assert_eq!(client.agent_priority("junior"), Some(200.into()));
+
+        client
+            .set_priority_ceilings(vec![ceiling("/db/", 20)])
+            .unwrap();
+        assert_eq!(client.agent_priority("junior"), Some(20.into()));
+
+        // Re-registering keeps the ceiling on top of the new priority
+        client.register_agent("junior", 300);
+        assert_eq!(client.agent_priority("junior"), Some(20.into()));
+
+        client.set_priority_ceilings(Vec::new()).unwrap();
+        assert_eq!(client.agent_priority("junior"), Some(300.into()));
+    }
+}
@@ -1,47 +1,168 @@
 //! High-level ergonomic client that wraps the pure kernel + pluggable storage.
 //! Both the napi-rs (JS) and PyO3 (Python) FFI layers delegate to this.
 
-use crate::infrastructure::LeaseStore;
+#[path = "admission.rs"]
+mod admission;
+
+use admission::Refusal;
+
+use crate::activity::{ActivityEntry, ActivityKind, ActivityLog, TimelineQuery};
+use crate::aging::{AgingPolicy, AgingTracker};
+use crate::alias::{AliasTable, RenameAlias};
+use crate::audit::{AuditSink, AuditingScheduler};
+use crate::backoff::{BackoffPolicy, BackoffScheduler};
+use crate::barrier::{BarrierRegistry, BarrierStatus};
+use crate::capacity::{CapacityPolicy, CapacityReport, LoadShedder, is_advisory};
+use crate::ceiling::{CeilingAssignment, CeilingTable, PriorityCeiling};
+use crate::clock::{Clock, IdSource, SystemClock};
+use crate::codes::ReasonCode;
+use crate::compaction::{CompactionPolicy, CompactionReport, MemoryReport, StructureUsage};
+use crate::conflict::{ConflictDetail, ConflictEngine};
+use crate::cosign::{CosignError, CosignRegistry, CosignRequest, CosignRule};
+use crate::deadlock::{WaitForEdge, WaitForGraph};
+use crate::debounce::{AcquireDebouncer, DebouncePolicy};
+use crate::dependency::{DependencyEdge, DependencyGraph};
+use crate::event::KlockEvent;
+use crate::expiry::{ExpiryWarningPolicy, ExpiryWarnings};
+use crate::fair_share::FairSharePolicy;
+use crate::fallback::{Fallback, FallbackGroups};
+#[cfg(feature = "testing")]
+use crate::fault::{FaultInjectingScheduler, FaultInjector};
+use crate::feed::{AgentEvent, FeedBatch, FeedRegistry};
+use crate::firehose::{Firehose, FirehosePage};
+use crate::health::{AgentHealth, HealthPolicy, HealthSignal, HealthTracker};
+use crate::index::IntentSet;
+use crate::infrastructure::{LeaseMutationError, LeaseSnapshot, LeaseStore, sort_leases};
 use crate::infrastructure_in_memory::InMemoryLeaseStore;
+use crate::infrastructure_mirror::{MirrorReport, MirroredStore};
+use crate::maintenance::{MaintenanceSchedule, MaintenanceWindow, ScheduledWindow};
+use crate::namespace::{Namespace, NamespaceReaping, NamespaceRegistry, namespace_of};
+use crate::policy_hook::PolicyHook;
+use crate::policy_registry::PolicyRegistry;
+use crate::preemption::{PreemptionError, PreemptionPolicy};
+use crate::quarantine::{QuarantineRegistry, ResourceQuarantine};
+use crate::refinement;
+use crate::registry::{AgentRegistry, Priority, PriorityClass, UnregisteredAgentPolicy};
+use crate::release::{BatchRelease, LeaseRelease, ReleaseCondition, ReleaseStatus};
+use crate::scheduler::{Scheduler, SchedulerExplanation, WaitDieScheduler};
+use crate::semaphore::{SemaphoreRegistry, SemaphoreResult, SemaphoreStatus};
+use crate::session::{DefaultSession, Session, SessionEnd, SessionPolicy, SessionRegistry};
+use crate::skew::{ClockReport, SkewDetector, SkewPolicy};
 use crate::state::{
-    IntentManifest, KernelVerdict, KernelVerdictStatus, KlockKernel, StateSnapshot,
+    AtomicVerdict, IntentManifest, KernelVerdict, KernelVerdictStatus, KlockKernel,
+    ManifestAcquisition, ManifestLeaseFailure, StateProjection, StateSnapshot,
 };
+use crate::summary::ConflictSummary;
+use crate::template::{ManifestTemplate, TemplateRegistry};
+use crate::types::normalize::PathNormalization;
 use crate::types::*;
-use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
-
-fn now_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
-}
+use crate::usage::{AgentLabels, UsageMeter, UsageReport, UsageStore};
+use crate::wait_queue::{Waiter, WaiterFilter};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+
+/// How often `declare_intent` re-evaluates a grant whose leases changed
+/// underneath it before giving up with `ReasonCode::StateChanged`
+const MAX_VALIDATION_ATTEMPTS: usize = 3;
 
-/// Trait combining LeaseStore with agent priority management.
+/// Trait combining LeaseStore with the pluggable policies it consults.
 /// Allows KlockClient to be generic over storage backends.
+///
+/// Priorities live in an `AgentRegistry`; `register_agent_priority` and
+/// `get_priorities` are kept as adapters over it.
 pub trait LeaseStoreExt: LeaseStore {
-    fn register_agent_priority(&mut self, agent_id: String, priority: u64);
-    fn get_priorities(&self) -> HashMap<String, u64>;
+    fn registry(&self) -> Arc<dyn AgentRegistry>;
+    fn set_registry(&mut self, registry: Arc<dyn AgentRegistry>);
+    fn set_scheduler(&mut self, scheduler: Arc<dyn Scheduler>);
+    fn set_fair_share(&mut self, policy: FairSharePolicy);
+    fn set_conflict_engine(&mut self, engine: Arc<ConflictEngine>);
+
+    fn register_agent_priority(&mut self, agent_id: String, priority: Priority) {
+        self.registry().register(agent_id, priority);
+    }
+    /// Change a registered agent's priority; schedulers see it on their
+    /// next decision. Returns the previous priority, or `None` (changing
+    /// nothing) if the agent is not registered.
+    fn update_agent_priority(&mut self, agent_id: &str, priority: Priority) -> Option<Priority> {
+        let registry = self.registry();
+        let previous = registry.priority(agent_id)?;
+        registry.register(agent_id.to_string(), priority);
+        Some(previous)
+    }
+    fn get_priorities(&self) -> HashMap<String, Priority> {
+        self.registry().priorities()
+    }
+    /// How a dual-write secondary is keeping up; `None` unless mirrored
+    fn mirror_report(&self) -> Option<MirrorReport> {
+        None
+    }
+}
+
+/// Open a storage backend from its configuration string: `memory` or
+/// `sqlite:<path>`.
+pub fn open_store(spec: &str) -> Result<Box<dyn LeaseStoreExt + Send>, String> {
+    if spec == "memory" {
+        return Ok(Box::new(InMemoryLeaseStore::new()));
+    }
+    let Some(path) = spec.strip_prefix("sqlite:") else {
+        return Err(format!(
+            "Unknown storage backend: '{}'. Use 'memory' or 'sqlite:<path>'",
+            spec
+        ));
+    };
+    #[cfg(feature = "sqlite")]
+    {
+        let store = crate::infrastructure_sqlite::SqliteLeaseStore::open(path)
+            .map_err(|e| format!("Failed to open SQLite database at '{}': {}", path, e))?;
+        store.check_ready()?;
+        Ok(Box::new(store))
+    }
+    #[cfg(not(feature = "sqlite"))]
+    {
+        let _ = path;
+        Err(
+            "SQLite storage requested but `sqlite` feature is not enabled. \
+             Rebuild with: cargo build --features sqlite"
+                .to_string(),
+        )
+    }
 }
 
 impl LeaseStoreExt for InMemoryLeaseStore {
-    fn register_agent_priority(&mut self, agent_id: String, priority: u64) {
-        InMemoryLeaseStore::register_agent_priority(self, agent_id, priority);
+    fn registry(&self) -> Arc<dyn AgentRegistry> {
+        InMemoryLeaseStore::registry(self)
+    }
+    fn set_registry(&mut self, registry: Arc<dyn AgentRegistry>) {
+        InMemoryLeaseStore::set_registry(self, registry);
+    }
+    fn set_scheduler(&mut self, scheduler: Arc<dyn Scheduler>) {
+        InMemoryLeaseStore::set_scheduler(self, scheduler);
+    }
+    fn set_fair_share(&mut self, policy: FairSharePolicy) {
+        InMemoryLeaseStore::set_fair_share(self, policy);
     }
-    fn get_priorities(&self) -> HashMap<String, u64> {
-        InMemoryLeaseStore::get_priorities(self)
+    fn set_conflict_engine(&mut self, engine: Arc<ConflictEngine>) {
+        InMemoryLeaseStore::set_conflict_engine(self, engine);
     }
 }
 
 #[cfg(feature = "sqlite")]
 impl LeaseStoreExt for crate::infrastructure_sqlite::SqliteLeaseStore {
-    fn register_agent_priority(&mut self, agent_id: String, priority: u64) {
-        crate::infrastructure_sqlite::SqliteLeaseStore::register_agent_priority(
-            self, agent_id, priority,
-        );
+    fn registry(&self) -> Arc<dyn AgentRegistry> {
+        crate::infrastructure_sqlite::SqliteLeaseStore::registry(self)
+    }
+    fn set_registry(&mut self, registry: Arc<dyn AgentRegistry>) {
+        crate::infrastructure_sqlite::SqliteLeaseStore::set_registry(self, registry);
+    }
+    fn set_scheduler(&mut self, scheduler: Arc<dyn Scheduler>) {
+        crate::infrastructure_sqlite::SqliteLeaseStore::set_scheduler(self, scheduler);
+    }
+    fn set_fair_share(&mut self, policy: FairSharePolicy) {
+        crate::infrastructure_sqlite::SqliteLeaseStore::set_fair_share(self, policy);
     }
-    fn get_priorities(&self) -> HashMap<String, u64> {
-        crate::infrastructure_sqlite::SqliteLeaseStore::get_priorities(self)
+    fn set_conflict_engine(&mut self, engine: Arc<ConflictEngine>) {
+        crate::infrastructure_sqlite::SqliteLeaseStore::set_conflict_engine(self, engine);
     }
 }
 
@@ -49,20 +170,168 @@ impl LeaseStoreExt for crate::infrastructure_sqlite::SqliteLeaseStore {
 /// conflict resolution through a single ergonomic API.
 pub struct KlockClient {
     store: Box<dyn LeaseStoreExt + Send>,
+    /// Source of agent priorities, shared with the store
+    registry: Arc<dyn AgentRegistry>,
     /// Tracks active intents per session for conflict checking
-    active_intents: Vec<SPOTriple>,
+    active_intents: IntentSet,
+    /// `DEPENDS_ON` edges between the holders of `active_intents`
+    dependencies: DependencyGraph,
+    /// What each session refused with Wait is waiting on, until its next
+    /// verdict, keyed by agent and session
+    intent_waits: BTreeMap<(String, String), Vec<WaitForEdge>>,
+    /// How long intents live unless their session renews them; forever
+    /// when unset
+    intent_ttl_ms: Option<u64>,
     /// Counter for generating unique IDs
     id_counter: u64,
+    /// How `next_id` turns the counter into an ID
+    ids: IdSource,
+    /// Source of the current time
+    clock: Arc<dyn Clock>,
+    /// Conflict detection rules shared by the kernel and the store
+    engine: Arc<ConflictEngine>,
+    /// Conflict resolution policy, by resource type, as configured
+    policy: PolicyRegistry,
+    /// `policy` wrapped for backoff, auditing (and fault injection), shared
+    /// by the kernel and the store
+    scheduler: Arc<dyn Scheduler>,
+    /// Backoff suggested with Die verdicts
+    backoff: BackoffPolicy,
+    /// Where every scheduler decision is reported, if anywhere
+    audit: Option<Arc<dyn AuditSink>>,
+    /// Per-agent health scoring and demotion/quarantine policy
+    health: HealthTracker,
+    /// Denial streaks of starving agents, for priority aging
+    aging: AgingTracker,
+    /// Leases whose holders were warned they are about to expire
+    expiry: ExpiryWarnings,
+    /// Interchangeable resources suggested to agents refused with Die
+    fallback_groups: FallbackGroups,
+    /// Ceiling priorities of critical resources, and who runs at one
+    ceilings: CeilingTable,
+    /// Which seniors may revoke which juniors' leases
+    preemption: PreemptionPolicy,
+    /// Penalty for the leases each agent holds, shared with the store
+    fair_share: FairSharePolicy,
+    /// Retention rules for `compact`
+    compaction: CompactionPolicy,
+    /// Lease and memory caps, and the load shed near them
+    shedder: LoadShedder,
+    /// Backward clock jumps seen, which hold eviction
+    skew: SkewDetector,
+    /// Minimum spacing between failed acquires of the same resource
+    debounce: AcquireDebouncer,
+    /// Operations that need a second agent's approval, and the requests
+    /// awaiting it
+    cosign: CosignRegistry,
+    /// Named rendezvous points for multi-agent workflows
+    barriers: BarrierRegistry,
+    /// Named counting semaphores
+    semaphores: SemaphoreRegistry,
+    /// Resources frozen by operators
+    quarantines: QuarantineRegistry,
+    /// Scheduled windows of exclusive access
+    maintenance: MaintenanceSchedule,
+    /// Sessions used for requests that name none
+    sessions: SessionRegistry,
+    /// Scopes of throwaway agents, reaped when they expire
+    namespaces: NamespaceRegistry,
+    /// How agents' resource paths are canonicalized
+    paths: PathNormalization,
+    /// Path rules of namespaces whose agents work elsewhere, by namespace
+    namespace_paths: HashMap<String, PathNormalization>,
+    /// Events waiting to be polled, per session
+    feeds: FeedRegistry,
+    /// Manifest templates registered by name
+    templates: TemplateRegistry,
+    /// What each agent has been doing, for timelines
+    activity: ActivityLog,
+    /// Every lease state change, for external enforcers and mirrors
+    firehose: Firehose,
+    /// Agent labels, and each agent's acquires and lease time
+    usage: UsageMeter,
+    /// Scripted admission policy, consulted before the scheduler
+    policy_hook: Option<Arc<dyn PolicyHook>>,
+    /// Why the policy hook denied the last acquire, if it did
+    policy_denial: Option<String>,
+    /// Why the last read or write of intents in the store failed, until
+    /// a later write succeeds
+    storage_error: Option<String>,
+    /// Verdicts tests have programmed the scheduler to force
+    #[cfg(feature = "testing")]
+    faults: Option<FaultInjector>,
 }
 
 impl KlockClient {
     /// Create a new KlockClient with an empty in-memory store.
     pub fn new() -> Self {
-        Self {
-            store: Box::new(InMemoryLeaseStore::new()),
-            active_intents: Vec::new(),
+        Self::with_store(Box::new(InMemoryLeaseStore::new()))
+    }
+
+    pub(crate) fn with_store(mut store: Box<dyn LeaseStoreExt + Send>) -> Self {
+        // Renames granted before a restart still alias their paths
+        let engine = Arc::new(ConflictEngine::default().with_aliases(store.load_aliases()));
+        if !engine.aliases().is_empty() {
+            store.set_conflict_engine(engine.clone());
+        }
+        // ... and intents granted before it still conflict
+        let (intents, storage_error) = match store.load_intents() {
+            Ok(intents) => (intents, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        // Lease end times are stamped from the events the store records
+        store.record_events(true);
+        let mut client = Self {
+            registry: store.registry(),
+            store,
+            active_intents: IntentSet::new(),
+            dependencies: DependencyGraph::new(),
+            intent_waits: BTreeMap::new(),
+            intent_ttl_ms: None,
             id_counter: 0,
+            ids: IdSource::Sequential,
+            clock: Arc::new(SystemClock),
+            engine,
+            policy: PolicyRegistry::default(),
+            scheduler: Arc::new(WaitDieScheduler),
+            backoff: BackoffPolicy::default(),
+            audit: None,
+            health: HealthTracker::default(),
+            aging: AgingTracker::default(),
+            expiry: ExpiryWarnings::default(),
+            fallback_groups: FallbackGroups::default(),
+            ceilings: CeilingTable::default(),
+            preemption: PreemptionPolicy::default(),
+            fair_share: FairSharePolicy::default(),
+            compaction: CompactionPolicy::default(),
+            shedder: LoadShedder::default(),
+            skew: SkewDetector::default(),
+            debounce: AcquireDebouncer::default(),
+            cosign: CosignRegistry::default(),
+            barriers: BarrierRegistry::default(),
+            semaphores: SemaphoreRegistry::default(),
+            quarantines: QuarantineRegistry::default(),
+            maintenance: MaintenanceSchedule::default(),
+            sessions: SessionRegistry::default(),
+            namespaces: NamespaceRegistry::default(),
+            paths: PathNormalization::default(),
+            namespace_paths: HashMap::new(),
+            feeds: FeedRegistry::default(),
+            templates: TemplateRegistry::default(),
+            activity: ActivityLog::default(),
+            firehose: Firehose::default(),
+            usage: UsageMeter::default(),
+            policy_hook: None,
+            policy_denial: None,
+            storage_error,
+            #[cfg(feature = "testing")]
+            faults: None,
+        };
+        client.install_scheduler();
+        for (sequence, intent) in intents {
+            client.index_intent(sequence, intent);
         }
+        client
     }
 
     /// Create a new KlockClient backed by SQLite at the given path.
@@ -71,106 +340,3472 @@ impl KlockClient {
     pub fn with_sqlite(path: &str) -> Result<Self, String> {
         let store = crate::infrastructure_sqlite::SqliteLeaseStore::open(path)
             .map_err(|e| format!("Failed to open SQLite database at '{}': {}", path, e))?;
-        Ok(Self {
-            store: Box::new(store),
-            active_intents: Vec::new(),
-            id_counter: 0,
-        })
+        Ok(Self::with_store(Box::new(store)))
+    }
+
+    /// Create an in-memory KlockClient whose output is reproducible: time
+    /// comes from `clock` and intent IDs from a stream seeded with `seed`.
+    /// Running the same calls against two such clients yields identical
+    /// verdicts and lease listings, for golden-file tests and examples.
+    pub fn deterministic(seed: u64, clock: Arc<dyn Clock>) -> Self {
+        let mut client = Self::new();
+        client.set_clock(clock);
+        client.set_id_source(IdSource::Seeded(seed));
+        client
+    }
+
+    /// Resolve conflicts with `scheduler` instead of Wait-Die, e.g.
+    /// `KlockClient::new().with_scheduler(Arc::new(WoundWaitScheduler))`.
+    /// The client's store uses the same policy.
+    pub fn with_scheduler(mut self, scheduler: Arc<dyn Scheduler>) -> Self {
+        self.set_scheduler(scheduler);
+        self
     }
 
-    /// Register an agent with a priority timestamp.
+    /// Register an agent with a priority timestamp, in the normal class.
     /// Lower timestamps = higher priority (older = senior).
     pub fn register_agent(&mut self, agent_id: &str, priority: u64) {
-        self.store
-            .register_agent_priority(agent_id.to_string(), priority);
+        self.register_agent_in_class(agent_id, PriorityClass::Normal, priority);
+    }
+
+    /// Register an agent in a priority class. The class always dominates:
+    /// the timestamp only orders agents within the same class.
+    pub fn register_agent_in_class(&mut self, agent_id: &str, class: PriorityClass, priority: u64) {
+        let priority = self.adjusted_priority(agent_id, Priority::new(class, priority));
+        self.registry.register(agent_id.to_string(), priority);
+        let now = self.now();
+        self.activity.touch(agent_id, now);
+        // Re-apply any ceiling on top of the new priority
+        self.ceilings.set_applied(agent_id, None, 0);
+        self.refresh_ceiling(agent_id);
+    }
+
+    /// Promote (or demote) a registered agent mid-run, within its class.
+    /// Its next acquires and manifests are judged at the new priority
+    /// timestamp, with any health penalty, aging boost and ceiling still
+    /// applied on top. Returns the previous effective timestamp, or `None`
+    /// if the agent is not registered.
+    pub fn update_agent_priority(&mut self, agent_id: &str, priority: u64) -> Option<u64> {
+        let class = self.agent_priority(agent_id)?.class;
+        let priority = self.adjusted_priority(agent_id, Priority::new(class, priority));
+        let previous = self.store.update_agent_priority(agent_id, priority)?;
+        self.ceilings.set_applied(agent_id, None, 0);
+        self.refresh_ceiling(agent_id);
+        Some(previous.timestamp)
+    }
+
+    /// `priority` with the agent's standing adjustments: a demoted agent
+    /// keeps its penalty, and an aged one its boost
+    fn adjusted_priority(&self, agent_id: &str, priority: Priority) -> Priority {
+        let timestamp = if self.health.is_demoted(agent_id) {
+            priority
+                .timestamp
+                .saturating_add(self.health.policy().deprioritize_by)
+        } else {
+            priority.timestamp
+        };
+        priority.with_timestamp(timestamp.saturating_sub(self.aging.applied(agent_id)))
+    }
+
+    /// Get the effective priority of a registered agent.
+    pub fn agent_priority(&self, agent_id: &str) -> Option<Priority> {
+        self.registry.priority(agent_id)
+    }
+
+    /// Replace the agent registry (in-memory by default, or the store's own
+    /// SQLite table). The store is switched over to the same registry.
+    pub fn set_registry(&mut self, registry: Arc<dyn AgentRegistry>) {
+        self.store.set_registry(registry.clone());
+        self.registry = registry;
+    }
+
+    /// Whether the agent is registered or has any recorded activity.
+    pub fn is_known_agent(&self, agent_id: &str) -> bool {
+        self.agent_priority(agent_id).is_some() || self.health.knows(agent_id)
+    }
+
+    /// Replace the health policy used to demote or quarantine flapping agents.
+    pub fn set_health_policy(&mut self, policy: HealthPolicy) {
+        self.health.set_policy(policy);
+    }
+
+    /// Replace the priority aging policy (disabled by default). Agents
+    /// whose requests keep dying then grow older until they are granted.
+    pub fn set_aging_policy(&mut self, policy: AgingPolicy) {
+        self.aging.set_policy(policy);
+    }
+
+    /// Replace when holders are warned that a lease is about to expire (at
+    /// 20% of its TTL left by default; see `expiry`).
+    pub fn set_expiry_warning_policy(&mut self, policy: ExpiryWarningPolicy) {
+        self.expiry.set_policy(policy);
+    }
+
+    pub fn expiry_warning_policy(&self) -> &ExpiryWarningPolicy {
+        self.expiry.policy()
+    }
+
+    /// Whether `lease` will expire soon unless its holder heartbeats
+    pub fn is_expiring_soon(&self, lease: &Lease) -> bool {
+        self.expiry.policy().is_expiring_soon(lease, self.now())
+    }
+
+    /// Queue a `lease_expiring` event on the session of each lease that
+    /// became due for a warning since the last check. Returns the number
+    /// of warnings. Polling a feed checks first.
+    pub fn warn_expiring_leases(&mut self) -> usize {
+        let now = self.now();
+        let active = self.store.get_active_leases();
+        let due = self.expiry.due(&active, now);
+        for lease in &due {
+            self.feeds.push(
+                &lease.agent_id,
+                &lease.session_id,
+                AgentEvent::LeaseExpiring {
+                    lease_id: lease.id.clone(),
+                    resource: lease.resource.clone(),
+                    predicate: lease.predicate,
+                    expires_at: lease.expires_at,
+                    remaining_ms: lease.expires_at - now,
+                    at: now,
+                },
+            );
+        }
+        due.len()
+    }
+
+    /// How far aging currently improves the agent's priority (`0` if it is
+    /// not starving or aging is disabled).
+    pub fn aging_boost(&self, agent_id: &str) -> u64 {
+        self.aging.applied(agent_id)
+    }
+
+    /// Replace the priority ceilings of critical resources (none by
+    /// default). Holders of active leases on them run at the ceiling until
+    /// those leases end; current holders are moved to the new ceilings.
+    pub fn set_priority_ceilings(&mut self, ceilings: Vec<PriorityCeiling>) -> Result<(), String> {
+        self.ceilings.set_ceilings(ceilings)?;
+        let mut agents: Vec<String> = self
+            .store
+            .get_active_leases()
+            .into_iter()
+            .map(|l| l.agent_id)
+            .chain(self.ceilings.boosted_agents())
+            .collect();
+        agents.sort();
+        agents.dedup();
+        for agent_id in agents {
+            self.refresh_ceiling(&agent_id);
+        }
+        Ok(())
+    }
+
+    /// The configured priority ceilings.
+    pub fn priority_ceilings(&self) -> &[PriorityCeiling] {
+        self.ceilings.ceilings()
+    }
+
+    /// Agents currently running at a ceiling, by agent ID.
+    pub fn ceiling_assignments(&self) -> Vec<CeilingAssignment> {
+        self.ceilings.assignments()
+    }
+
+    /// Replace the preemption policy (disabled by default), which decides
+    /// when `preempt_lease` lets a senior revoke a junior's lease.
+    pub fn set_preemption_policy(&mut self, policy: PreemptionPolicy) {
+        self.preemption = policy;
+    }
+
+    pub fn preemption_policy(&self) -> &PreemptionPolicy {
+        &self.preemption
+    }
+
+    /// Replace the fair-share policy (disabled by default), which pushes
+    /// back an agent's priority for each active lease it holds whenever the
+    /// kernel or the store schedules it (see `fair_share`).
+    pub fn set_fair_share_policy(&mut self, policy: FairSharePolicy) {
+        self.store.set_fair_share(policy.clone());
+        self.fair_share = policy;
+    }
+
+    pub fn fair_share_policy(&self) -> &FairSharePolicy {
+        &self.fair_share
+    }
+
+    /// Registered priorities, as the scheduler sees them under fair share
+    fn scheduling_priorities(
+        &self,
+        mut priorities: HashMap<String, Priority>,
+    ) -> HashMap<String, Priority> {
+        if self.fair_share.is_enabled() {
+            self.fair_share
+                .apply(&mut priorities, &self.store.lease_counts());
+        }
+        priorities
+    }
+
+    /// Replace the retry debounce policy (disabled by default).
+    pub fn set_debounce_policy(&mut self, policy: DebouncePolicy) {
+        self.debounce.set_policy(policy);
+    }
+
+    /// Current health report for an agent.
+    pub fn agent_health(&self, agent_id: &str) -> AgentHealth {
+        self.health.report(agent_id, self.now())
+    }
+
+    /// Replace the conflict detection rules (built-in matrix by default).
+    /// Applies to both `declare_intent` and `acquire_lease`. Rename aliases
+    /// are client state and carry over to the new engine.
+    pub fn set_conflict_engine(&mut self, engine: ConflictEngine) {
+        let engine = Arc::new(engine.with_aliases(self.engine.aliases().clone()));
+        self.store.set_conflict_engine(engine.clone());
+        self.engine = engine;
+        // Aliases and implication rules change what each intent touches
+        self.reindex_intents();
+    }
+
+    pub fn conflict_engine(&self) -> &ConflictEngine {
+        &self.engine
+    }
+
+    /// Replace the scheduling policy (Wait-Die by default).
+    /// Applies to both `declare_intent` and `acquire_lease`, on every
+    /// resource type without a policy of its own.
+    pub fn set_scheduler(&mut self, scheduler: Arc<dyn Scheduler>) {
+        self.policy.set_default(scheduler);
+        self.install_scheduler();
+    }
+
+    /// Resolve conflicts on `resource_type` with `scheduler` instead of the
+    /// default policy (see `policy_registry`), or with the default again
+    /// for `None`.
+    pub fn set_resource_type_scheduler(
+        &mut self,
+        resource_type: ResourceType,
+        scheduler: Option<Arc<dyn Scheduler>>,
+    ) {
+        self.policy.set(resource_type, scheduler);
+        self.install_scheduler();
+    }
+
+    /// The resource types with a policy of their own, and its name
+    pub fn resource_type_schedulers(&self) -> Vec<(ResourceType, &'static str)> {
+        self.policy.overrides()
+    }
+
+    /// Choose what happens to agents without a registered priority
+    /// (treated as lowest by default). Under `AutoRegister` a requester is
+    /// registered on its first request; under `Reject` its requests fail
+    /// with `UnregisteredAgent`. Schedulers apply the policy to holders.
+    pub fn set_unregistered_agent_policy(&mut self, policy: UnregisteredAgentPolicy) {
+        self.policy.set_unregistered_agents(policy);
+        self.install_scheduler();
+    }
+
+    pub fn unregistered_agent_policy(&self) -> UnregisteredAgentPolicy {
+        self.policy.unregistered_agents()
+    }
+
+    /// Choose the backoff Die verdicts suggest (see `backoff`). Resets the
+    /// counts of consecutive Die verdicts exponential backoff grows with.
+    pub fn set_backoff_policy(&mut self, policy: BackoffPolicy) {
+        self.backoff = policy;
+        self.install_scheduler();
+    }
+
+    pub fn backoff_policy(&self) -> BackoffPolicy {
+        self.backoff
+    }
+
+    /// How `FILE` paths of agents outside namespaces with their own rules
+    /// are canonicalized: case folding and workspace roots (see
+    /// `types::normalize`). Resources already held keep their spelling, so
+    /// set the rules before agents connect.
+    pub fn set_path_normalization(&mut self, rules: PathNormalization) {
+        self.paths = rules;
+    }
+
+    /// Give the agents of `namespace` their own path rules, e.g. the roots
+    /// of a CI checkout, or with `None` fall back to the client's.
+    pub fn set_namespace_path_normalization(
+        &mut self,
+        namespace: &str,
+        rules: Option<PathNormalization>,
+    ) {
+        match rules {
+            Some(rules) => self.namespace_paths.insert(namespace.to_string(), rules),
+            None => self.namespace_paths.remove(namespace),
+        };
+    }
+
+    /// The rules `agent_id`'s resource paths are canonicalized under
+    pub fn path_normalization(&self, agent_id: &str) -> &PathNormalization {
+        namespace_of(agent_id)
+            .and_then(|namespace| self.namespace_paths.get(namespace))
+            .unwrap_or(&self.paths)
+    }
+
+    /// `manifest` with its paths, and rename targets, canonicalized under
+    /// its agent's rules
+    fn localize_manifest<'a>(&self, manifest: &'a IntentManifest) -> Cow<'a, IntentManifest> {
+        let rules = self.path_normalization(&manifest.agent_id);
+        if *rules == PathNormalization::default() {
+            return Cow::Borrowed(manifest);
+        }
+        let mut localized = manifest.clone();
+        for intent in &mut localized.intents {
+            intent.object = intent.object.normalized(rules);
+            if let Some(renamed_to) = &mut intent.renamed_to {
+                *renamed_to = rules.apply(&intent.object.resource_type, renamed_to);
+            }
+        }
+        Cow::Owned(localized)
+    }
+
+    /// Report every scheduler decision to `sink` (see `audit`), or stop
+    /// reporting with `None`.
+    pub fn set_audit_sink(&mut self, sink: Option<Arc<dyn AuditSink>>) {
+        self.audit = sink;
+        self.install_scheduler();
+    }
+
+    /// Consult `hook` on every acquire and intent once the built-in
+    /// admission checks pass (see `policy_hook`), or stop with `None`.
+    pub fn set_policy_hook(&mut self, hook: Option<Arc<dyn PolicyHook>>) {
+        self.policy_hook = hook;
+    }
+
+    pub fn has_policy_hook(&self) -> bool {
+        self.policy_hook.is_some()
+    }
+
+    /// Why the policy hook refused the last acquire with `PolicyDenied`;
+    /// `None` if the last acquire got past it.
+    pub fn last_policy_denial(&self) -> Option<&str> {
+        self.policy_denial.as_deref()
+    }
+
+    /// Hand the kernel and the store `policy`, wrapped as configured
+    fn install_scheduler(&mut self) {
+        let mut scheduler: Arc<dyn Scheduler> = Arc::new(self.policy.clone());
+        #[cfg(feature = "testing")]
+        if let Some(faults) = &self.faults {
+            scheduler = Arc::new(FaultInjectingScheduler::new(scheduler, faults.clone()));
+        }
+        scheduler = Arc::new(BackoffScheduler::new(
+            scheduler,
+            self.backoff,
+            self.clock.clone(),
+        ));
+        // Outermost, so forced verdicts are recorded too
+        if let Some(sink) = &self.audit {
+            scheduler = Arc::new(AuditingScheduler::new(
+                scheduler,
+                sink.clone(),
+                self.clock.clone(),
+            ));
+        }
+        self.store.set_scheduler(scheduler.clone());
+        self.scheduler = scheduler;
+    }
+
+    /// Wrap the scheduler so tests can force Wait and Die verdicts (see
+    /// `fault`), now and after any later `set_scheduler`. Returns the
+    /// injector that programs them; calling it again returns the same one.
+    #[cfg(feature = "testing")]
+    pub fn enable_fault_injection(&mut self) -> FaultInjector {
+        if let Some(faults) = &self.faults {
+            return faults.clone();
+        }
+        let faults = FaultInjector::new();
+        self.faults = Some(faults.clone());
+        self.install_scheduler();
+        faults
+    }
+
+    /// The fault injector, if `enable_fault_injection` was called
+    #[cfg(feature = "testing")]
+    pub fn fault_injector(&self) -> Option<FaultInjector> {
+        self.faults.clone()
+    }
+
+    /// Replace the source of time (the system clock by default).
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+        // Backoffs and decision records are timed by the new clock
+        self.install_scheduler();
+    }
+
+    /// Replace how intent IDs are generated (sequential by default).
+    pub fn set_id_source(&mut self, ids: IdSource) {
+        self.ids = ids;
+    }
+
+    /// Name of the active scheduling policy.
+    pub fn scheduler_name(&self) -> &'static str {
+        self.scheduler.name()
     }
 
     /// Declare an intent manifest and get a kernel verdict.
     /// This checks for conflicts and applies Wait-Die scheduling.
+    ///
+    /// A manifest with an empty `session_id` runs in the agent's default
+    /// session, which the verdict names.
     pub fn declare_intent(&mut self, manifest: &IntentManifest) -> KernelVerdict {
-        let snapshot = StateSnapshot {
-            active_leases: self.store.get_active_leases(),
-            active_intents: self.active_intents.clone(),
-            priorities: self.store.get_priorities(),
+        let manifest = self.localize_manifest(manifest);
+        self.declare_localized(&manifest)
+    }
+
+    /// `declare_intent` of a manifest whose paths are already normalized
+    fn declare_localized(&mut self, manifest: &IntentManifest) -> KernelVerdict {
+        let mut verdict = self.judge(manifest);
+        self.attach_fallbacks(manifest, &mut verdict);
+        self.record_verdict(manifest, &verdict);
+        verdict
+    }
+
+    /// Group the conflicts `manifest` would meet now by resource and by
+    /// blocking agent, without registering anything. Only the kernel judges
+    /// it: quarantines, maintenance windows and retry limits are not
+    /// checked.
+    pub fn summarize_intent(&self, manifest: &IntentManifest) -> ConflictSummary {
+        KlockKernel::summarize_with(
+            &self.scheduling_snapshot(),
+            &self.localize_manifest(manifest),
+            &self.engine,
+            self.scheduler.as_ref(),
+        )
+    }
+
+    /// How Wait-Die would decide each intent of `manifest` against the held
+    /// leases now (see `WaitDieScheduler::explain`), without registering
+    /// anything. Agents without a priority get the lowest one, as the
+    /// scheduler sees them, unless unregistered agents are rejected.
+    /// Tentative intents, which are never refused, are left out; other
+    /// policies, intent conflicts and admission checks are not explained.
+    pub fn explain_intent(&self, manifest: &IntentManifest) -> Vec<SchedulerExplanation> {
+        let manifest = self.localize_manifest(manifest);
+        let active_leases = self.store.get_active_leases();
+        let mut priorities = self.scheduling_priorities(self.registry.priorities());
+        if self.unregistered_agent_policy() != UnregisteredAgentPolicy::Reject {
+            for agent_id in active_leases
+                .iter()
+                .map(|lease| lease.agent_id.as_str())
+                .chain([manifest.agent_id.as_str()])
+            {
+                if !priorities.contains_key(agent_id) {
+                    priorities.insert(agent_id.to_string(), Priority::lowest());
+                }
+            }
+        }
+        let now = self.now();
+        manifest
+            .intents
+            .iter()
+            .filter(|intent| !self.engine.is_tentative(intent.confidence))
+            .map(|intent| {
+                WaitDieScheduler::explain(
+                    &self.backoff,
+                    &self.engine,
+                    &manifest.agent_id,
+                    &manifest.session_id,
+                    intent.predicate,
+                    &intent.object,
+                    &active_leases,
+                    &priorities,
+                    now,
+                )
+            })
+            .collect()
+    }
+
+    /// Declare several manifests of one agent, typically one per session,
+    /// all or nothing: either every manifest's intents are registered or
+    /// none are. Each manifest is judged as `declare_intent` would, against
+    /// the current state plus the manifests before it, and gets its own
+    /// verdict (see `AtomicVerdict`).
+    ///
+    /// Fails if `manifests` is empty or belongs to more than one agent.
+    pub fn declare_intents_atomic(
+        &mut self,
+        manifests: &[IntentManifest],
+    ) -> Result<AtomicVerdict, String> {
+        let Some(first) = manifests.first() else {
+            return Err("manifests must not be empty".to_string());
+        };
+        if let Some(other) = manifests.iter().find(|m| m.agent_id != first.agent_id) {
+            return Err(format!(
+                "All manifests must belong to one agent; got '{}' and '{}'",
+                first.agent_id, other.agent_id
+            ));
+        }
+        let agent_id = first.agent_id.clone();
+        let now = self.now();
+        let manifests: Vec<IntentManifest> = manifests
+            .iter()
+            .map(|manifest| {
+                let manifest = self.localize_manifest(manifest).into_owned();
+                self.with_session(&manifest, now)
+            })
+            .collect();
+
+        self.enroll(&agent_id, now);
+        // Manifests turned away before the kernel runs keep that verdict;
+        // the kernel judges the rest together
+        let admissions: Vec<Option<KernelVerdict>> = manifests
+            .iter()
+            .map(|m| self.admit_manifest(m, now))
+            .collect();
+        let admitted: Vec<IntentManifest> = manifests
+            .iter()
+            .zip(&admissions)
+            .filter(|(_, admission)| admission.is_none())
+            .map(|(manifest, _)| manifest.clone())
+            .collect();
+        let mut judged = self.evaluate_atomic(&admitted).verdicts.into_iter();
+        let mut verdict = AtomicVerdict::new(
+            admissions
+                .into_iter()
+                .map(|admission| admission.unwrap_or_else(|| judged.next().unwrap()))
+                .collect(),
+        );
+        for (manifest, manifest_verdict) in manifests.iter().zip(&mut verdict.verdicts) {
+            self.attach_fallbacks(manifest, manifest_verdict);
+        }
+
+        match verdict.status {
+            KernelVerdictStatus::Granted => {
+                for manifest in &manifests {
+                    self.register_intents(manifest, now);
+                }
+                self.granted(&agent_id, now);
+            }
+            KernelVerdictStatus::Die => self.denied(&agent_id, now),
+            KernelVerdictStatus::Wait | KernelVerdictStatus::Rejected => {}
+        }
+        for (manifest, manifest_verdict) in manifests.iter().zip(&verdict.verdicts) {
+            self.record_verdict(manifest, manifest_verdict);
+        }
+        Ok(verdict)
+    }
+
+    /// Declare `manifest` and, if it is granted, lease every intent for
+    /// `ttl` ms in its session, all or nothing. Declaring and locking in
+    /// two calls leaves a window in which another agent can take a resource
+    /// the verdict promised. If any acquire is refused (an admission check,
+    /// or a tentative intent's conflict), the leases taken before it are
+    /// released without announcing a change, a request it queued is
+    /// cancelled, and the manifest's intents are withdrawn.
+    pub fn acquire_manifest(&mut self, manifest: &IntentManifest, ttl: u64) -> ManifestAcquisition {
+        let manifest = self.localize_manifest(manifest);
+        let manifest = manifest.as_ref();
+        let verdict = self.declare_localized(manifest);
+        let mut acquisition = ManifestAcquisition {
+            verdict,
+            leases: Vec::new(),
+            failure: None,
+        };
+        if acquisition.verdict.status != KernelVerdictStatus::Granted {
+            return acquisition;
+        }
+        let session_id = acquisition.verdict.session_id.clone();
+        for intent in &manifest.intents {
+            let session_id = match intent.session_id.as_str() {
+                "" => session_id.as_str(),
+                session_id => session_id,
+            };
+            let result = self.acquire(
+                &manifest.agent_id,
+                session_id,
+                intent.object.clone(),
+                intent.predicate,
+                ttl,
+                None,
+            );
+            self.record_lease_outcome(
+                &manifest.agent_id,
+                session_id,
+                intent.object.clone(),
+                intent.predicate,
+                &result,
+            );
+            match result {
+                LeaseResult::Success { lease } => acquisition.leases.push(lease),
+                LeaseResult::Failure {
+                    reason,
+                    wait_time,
+                    request_id,
+                    holders,
+                    ..
+                } => {
+                    if let Some(request_id) = request_id {
+                        if reason == LeaseFailureReason::CosignRequired {
+                            self.withdraw_cosign(&request_id);
+                        } else {
+                            self.cancel_pending(&WaiterFilter::Id(request_id));
+                        }
+                    }
+                    acquisition.failure = Some(ManifestLeaseFailure {
+                        intent_id: intent.id.clone(),
+                        reason: reason.as_str().to_string(),
+                        code: reason.code(),
+                        wait_time,
+                        holders,
+                    });
+                    break;
+                }
+            }
+        }
+        if acquisition.failure.is_some() {
+            let leases = std::mem::take(&mut acquisition.leases);
+            self.roll_back_manifest(manifest, &leases);
+        }
+        acquisition
+    }
+
+    /// Drop a co-signing request and its pending lease
+    fn withdraw_cosign(&mut self, lease_id: &str) {
+        let now = self.now();
+        let lease = self.store.get_lease(lease_id);
+        self.cosign.remove(lease_id);
+        if self.store.release(lease_id)
+            && let Some(lease) = lease
+        {
+            self.record_lease_activity(&lease, ActivityKind::Released, Some("Withdrawn"), now);
+        }
+        self.drain_events(now);
+    }
+
+    /// Undo a partly leased manifest: release `leases`, which changed
+    /// nothing, so dependents are not told, and withdraw its intents
+    fn roll_back_manifest(&mut self, manifest: &IntentManifest, leases: &[Lease]) {
+        let now = self.now();
+        for lease in leases {
+            if self.store.release(&lease.id) {
+                self.record_lease_activity(lease, ActivityKind::Released, Some("Rolled back"), now);
+            }
+        }
+        self.remove_intents(|active| {
+            manifest
+                .intents
+                .iter()
+                .any(|intent| intent.id == active.id && intent.subject == active.subject)
+        });
+        self.refresh_ceiling(&manifest.agent_id);
+        self.grant_queued(now);
+        self.drain_events(now);
+    }
+
+    fn record_verdict(&mut self, manifest: &IntentManifest, verdict: &KernelVerdict) {
+        self.activity.record(ActivityEntry {
+            at: self.now(),
+            agent_id: verdict.agent_id.clone(),
+            session_id: verdict.session_id.clone(),
+            kind: ActivityKind::Verdict,
+            lease_id: None,
+            resources: manifest.intents.iter().map(|i| i.object.clone()).collect(),
+            predicate: None,
+            outcome: Some(format!("{:?}", verdict.status)),
+        });
+
+        let key = (verdict.agent_id.clone(), verdict.session_id.clone());
+        if verdict.status == KernelVerdictStatus::Wait && !verdict.blockers.is_empty() {
+            let waits = verdict
+                .blockers
+                .iter()
+                .map(|b| WaitForEdge::new(&verdict.agent_id, &b.holder, b.resource.clone()))
+                .collect();
+            self.intent_waits.insert(key, waits);
+        } else {
+            self.intent_waits.remove(&key);
+        }
+    }
+
+    /// Judge a manifest and register its intents if granted
+    fn judge(&mut self, manifest: &IntentManifest) -> KernelVerdict {
+        let now = self.now();
+        let session_id = self.session_for(&manifest.agent_id, &manifest.session_id, now);
+        let filled;
+        let manifest = if session_id == manifest.session_id {
+            manifest
+        } else {
+            filled = Self::in_session(manifest, session_id);
+            &filled
         };
+        self.enroll(&manifest.agent_id, now);
+        self.expire_sessions_at(now);
+        if let Some(verdict) = self.admit_manifest(manifest, now) {
+            return verdict;
+        }
+        self.evict_expired_intents(now);
 
-        let verdict = KlockKernel::execute(&snapshot, manifest);
+        let verdict = self.evaluate(manifest);
 
         // If granted, register the intents as active
-        if verdict.status == KernelVerdictStatus::Granted {
-            for intent in &manifest.intents {
-                self.active_intents.push(intent.clone());
+        match verdict.status {
+            KernelVerdictStatus::Granted => {
+                self.register_intents(manifest, now);
+                self.granted(&manifest.agent_id, now);
             }
+            KernelVerdictStatus::Die => self.denied(&manifest.agent_id, now),
+            KernelVerdictStatus::Wait | KernelVerdictStatus::Rejected => {}
         }
 
         verdict
     }
 
-    /// Acquire a lease on a resource.
-    pub fn acquire_lease(
-        &mut self,
-        agent_id: &str,
-        session_id: &str,
-        resource_type: &str,
-        resource_path: &str,
-        predicate: &str,
-        ttl: u64,
-    ) -> LeaseResult {
-        let resource = ResourceRef::new(parse_resource_type(resource_type), resource_path);
-        let pred = parse_predicate(predicate);
-        let now = now_ms();
+    /// The manifest with its session resolved (see `session_for`)
+    fn with_session(&mut self, manifest: &IntentManifest, now: u64) -> IntentManifest {
+        let session_id = self.session_for(&manifest.agent_id, &manifest.session_id, now);
+        Self::in_session(manifest, session_id)
+    }
 
-        self.store
-            .acquire(agent_id, session_id, resource, pred, ttl, now)
+    /// The manifest moved to `session_id`, along with its intents that name
+    /// no session
+    fn in_session(manifest: &IntentManifest, session_id: String) -> IntentManifest {
+        let mut with_session = manifest.clone();
+        for intent in &mut with_session.intents {
+            if intent.session_id.is_empty() {
+                intent.session_id = session_id.clone();
+            }
+        }
+        with_session.session_id = session_id;
+        with_session
     }
 
-    /// Release a held lease by its ID.
-    pub fn release_lease(&mut self, lease_id: &str) -> bool {
-        // Also remove from active intents
-        self.active_intents.retain(|i| i.id != lease_id);
-        self.store.release(lease_id)
+    /// Register a granted manifest's intents as active, expiring after the
+    /// intent TTL unless they carry their own expiry
+    fn register_intents(&mut self, manifest: &IntentManifest, now: u64) {
+        let mut written = Ok(());
+        for intent in &manifest.intents {
+            let mut intent = intent.clone();
+            if let Some(ttl_ms) = self.intent_ttl_ms {
+                intent.expires_at.get_or_insert(now.saturating_add(ttl_ms));
+            }
+            let sequence = self.active_intents.next_sequence();
+            written = written.and(self.store.save_intent(sequence, &intent));
+            self.index_intent(sequence, intent);
+        }
+        self.note_storage(written);
+        self.record_renames(manifest, now);
     }
 
-    /// Get all currently active leases.
-    pub fn get_active_leases(&self) -> Vec<Lease> {
-        self.store.get_active_leases()
+    /// Alias the old and new names of every granted rename with a target.
+    /// Invalid targets (see `RenameAlias::validate`) are ignored.
+    fn record_renames(&mut self, manifest: &IntentManifest, now: u64) {
+        let mut aliases = self.engine.aliases().clone();
+        let initial = aliases.len();
+        for intent in &manifest.intents {
+            let Some(renamed_to) = &intent.renamed_to else {
+                continue;
+            };
+            if intent.predicate != Predicate::Renames {
+                continue;
+            }
+            let to = ResourceRef::new(intent.object.resource_type.clone(), renamed_to);
+            if let Ok(alias) = RenameAlias::new(
+                intent.object.clone(),
+                to,
+                &manifest.agent_id,
+                &manifest.session_id,
+                now,
+            ) {
+                aliases.insert(alias);
+            }
+        }
+        if aliases.len() != initial {
+            self.set_aliases(aliases);
+        }
     }
 
-    /// Evict expired leases. Returns the number of leases evicted.
-    pub fn evict_expired(&mut self) -> usize {
-        let now = now_ms();
-        self.store.evict_expired(now)
+    /// Install a new alias table in the engine and persist it.
+    fn set_aliases(&mut self, aliases: AliasTable) {
+        self.store.save_aliases(&aliases);
+        let engine = Arc::new((*self.engine).clone().with_aliases(aliases));
+        self.store.set_conflict_engine(engine.clone());
+        self.engine = engine;
+        // Aliases and implication rules change what each intent touches
+        self.reindex_intents();
     }
 
-    /// Heartbeat a lease to renew its TTL. Returns true if successful.
-    pub fn heartbeat_lease(&mut self, lease_id: &str, now: u64) -> bool {
-        self.store.heartbeat(lease_id, now)
+    /// Project the lease state at a future timestamp: which leases will have
+    /// expired and which pending reservations could then be activated,
+    /// assuming no heartbeats or releases in between.
+    pub fn project(&self, at: u64) -> StateProjection {
+        // Hypothetical decisions are neither audited nor forced
+        KlockKernel::project_with(&self.scheduling_snapshot(), at, &self.engine, &self.policy)
     }
 
-    /// Generate a unique ID for intents/triples.
-    pub fn next_id(&mut self) -> String {
-        self.id_counter += 1;
-        format!("klock_{}", self.id_counter)
+    /// Run the kernel against a point-in-time view of the leases on the
+    /// manifest's resources. A grant is only returned if those leases are
+    /// still held by the same holders afterwards (optimistic validation);
+    /// otherwise the evaluation is repeated, up to
+    /// `MAX_VALIDATION_ATTEMPTS` times, before the requester is told to
+    /// wait.
+    fn evaluate(&self, manifest: &IntentManifest) -> KernelVerdict {
+        let resources = self.footprint(manifest);
+        let mut leases = self.store.read_snapshot(&resources);
+        for _ in 0..MAX_VALIDATION_ATTEMPTS {
+            let mut snapshot = self.snapshot_for(manifest, &resources, &leases);
+            let mut verdict = KlockKernel::execute_with(
+                &snapshot,
+                manifest,
+                &self.engine,
+                self.scheduler.as_ref(),
+            );
+            // Only a Wait can close a wait-for cycle, so the graph is built
+            // for those alone
+            if verdict.status == KernelVerdictStatus::Wait {
+                snapshot.waits_for = self.wait_for_graph();
+                if !snapshot.waits_for.is_empty() {
+                    verdict = KlockKernel::execute_with(
+                        &snapshot,
+                        manifest,
+                        &self.engine,
+                        self.scheduler.as_ref(),
+                    );
+                }
+            }
+            self.back_off(&mut verdict, &leases);
+            if verdict.status != KernelVerdictStatus::Granted
+                || self.store.generation() == leases.generation
+            {
+                return verdict;
+            }
+            let current = self.store.read_snapshot(&resources);
+            if current.same_holders(&leases) {
+                return verdict;
+            }
+            leases = current;
+        }
+        KernelVerdict {
+            agent_id: manifest.agent_id.clone(),
+            session_id: manifest.session_id.clone(),
+            status: KernelVerdictStatus::Wait,
+            reason: Some(
+                "Leases on the manifest's resources changed during evaluation".to_string(),
+            ),
+            code: Some(ReasonCode::StateChanged),
+            held_by: None,
+            holders: Vec::new(),
+            conflicts: Vec::new(),
+            blockers: Vec::new(),
+            retry_after_ms: None,
+            implied: Vec::new(),
+            warnings: Vec::new(),
+            fallbacks: Vec::new(),
+            suggestions: Vec::new(),
+            per_intent: Vec::new(),
+        }
     }
-}
 
-impl Default for KlockClient {
-    fn default() -> Self {
-        Self::new()
+    /// `evaluate` for manifests declared together: the kernel judges them
+    /// all or nothing against one view of the leases on all their
+    /// resources, validated the same way.
+    fn evaluate_atomic(&self, manifests: &[IntentManifest]) -> AtomicVerdict {
+        let Some(first) = manifests.first() else {
+            return AtomicVerdict::new(Vec::new());
+        };
+        // One manifest standing for the whole group when building snapshots
+        let combined = IntentManifest {
+            session_id: first.session_id.clone(),
+            agent_id: first.agent_id.clone(),
+            intents: manifests
+                .iter()
+                .flat_map(|m| m.intents.iter().cloned())
+                .collect(),
+        };
+        let resources = self.footprint(&combined);
+        let mut leases = self.store.read_snapshot(&resources);
+        for _ in 0..MAX_VALIDATION_ATTEMPTS {
+            let mut snapshot = self.snapshot_for(&combined, &resources, &leases);
+            let mut verdict = KlockKernel::execute_atomic_with(
+                &snapshot,
+                manifests,
+                &self.engine,
+                self.scheduler.as_ref(),
+            );
+            if verdict.status == KernelVerdictStatus::Wait {
+                snapshot.waits_for = self.wait_for_graph();
+                if !snapshot.waits_for.is_empty() {
+                    verdict = KlockKernel::execute_atomic_with(
+                        &snapshot,
+                        manifests,
+                        &self.engine,
+                        self.scheduler.as_ref(),
+                    );
+                }
+            }
+            for manifest_verdict in &mut verdict.verdicts {
+                self.back_off(manifest_verdict, &leases);
+            }
+            // Refused siblings share the culprit's backoff
+            verdict = AtomicVerdict::new(verdict.verdicts);
+            if verdict.status != KernelVerdictStatus::Granted
+                || self.store.generation() == leases.generation
+            {
+                return verdict;
+            }
+            let current = self.store.read_snapshot(&resources);
+            if current.same_holders(&leases) {
+                return verdict;
+            }
+            leases = current;
+        }
+        AtomicVerdict::new(
+            manifests
+                .iter()
+                .map(|manifest| KernelVerdict {
+                    agent_id: manifest.agent_id.clone(),
+                    session_id: manifest.session_id.clone(),
+                    status: KernelVerdictStatus::Wait,
+                    reason: Some(
+                        "Leases on the manifests' resources changed during evaluation".to_string(),
+                    ),
+                    code: Some(ReasonCode::StateChanged),
+                    held_by: None,
+                    holders: Vec::new(),
+                    conflicts: Vec::new(),
+                    blockers: Vec::new(),
+                    retry_after_ms: None,
+                    implied: Vec::new(),
+                    warnings: Vec::new(),
+                    fallbacks: Vec::new(),
+                    suggestions: Vec::new(),
+                    per_intent: Vec::new(),
+                })
+                .collect(),
+        )
     }
-}
 
-// ─── Parsing Helpers ────────────────────────────────────────────────────────
+    /// Have a Die verdict back off until the leases blocking it run out
+    /// (other backoff policies were applied by the scheduler), and tell a
+    /// Wait verdict when they are expected to.
+    fn back_off(&self, verdict: &mut KernelVerdict, leases: &LeaseSnapshot) {
+        let until_holders_expire = matches!(self.backoff, BackoffPolicy::HolderExpiry { .. });
+        let policy = match verdict.status {
+            KernelVerdictStatus::Die if until_holders_expire => self.backoff,
+            // Waiters learn when the holders should be done, whatever the
+            // backoff of Die verdicts
+            KernelVerdictStatus::Wait if until_holders_expire => self.backoff,
+            KernelVerdictStatus::Wait => BackoffPolicy::default(),
+            _ => return,
+        };
+        let holders: Vec<&Lease> = leases
+            .leases
+            .iter()
+            .filter(|lease| {
+                verdict
+                    .blockers
+                    .iter()
+                    .any(|b| b.lease_id.as_deref() == Some(lease.id.as_str()))
+            })
+            .collect();
+        if !holders.is_empty() {
+            verdict.retry_after_ms =
+                Some(policy.retry_after_ms(&verdict.agent_id, &holders, 1, self.now()));
+        }
+    }
 
-pub fn parse_predicate(s: &str) -> Predicate {
-    match s.to_uppercase().as_str() {
-        "PROVIDES" => Predicate::Provides,
-        "CONSUMES" => Predicate::Consumes,
-        "MUTATES" => Predicate::Mutates,
-        "DELETES" => Predicate::Deletes,
-        "DEPENDS_ON" => Predicate::DependsOn,
-        "RENAMES" => Predicate::Renames,
-        _ => Predicate::Consumes, // Safe default
+    /// Every resource the manifest's intents can conflict on
+    fn footprint(&self, manifest: &IntentManifest) -> Vec<ResourceRef> {
+        let mut resources: Vec<ResourceRef> = manifest
+            .intents
+            .iter()
+            .flat_map(|intent| {
+                self.engine
+                    .footprint_resources(intent.predicate, &intent.object)
+            })
+            .collect();
+        // Refinement compares the symbols both sides declared in each file
+        if self.engine.symbol_refinement().is_some() {
+            let files: Vec<ResourceRef> = resources
+                .iter()
+                .filter_map(|resource| refinement::file_of(&self.engine.qualify(resource)))
+                .collect();
+            resources.extend(files);
+        }
+        resources
+    }
+
+    /// The state that can bear on `manifest`: only the leases and intents
+    /// that may overlap what its intents touch, found through the resource
+    /// indexes instead of by scanning everything held.
+    fn snapshot_for(
+        &self,
+        manifest: &IntentManifest,
+        resources: &[ResourceRef],
+        leases: &LeaseSnapshot,
+    ) -> StateSnapshot {
+        let now = self.now();
+        let active_leases = leases.leases.clone();
+        // The scheduler only compares the requester with the holders
+        let priorities = self.scheduling_priorities(
+            active_leases
+                .iter()
+                .map(|lease| lease.agent_id.as_str())
+                .chain([manifest.agent_id.as_str()])
+                .filter_map(|agent_id| {
+                    Some((agent_id.to_string(), self.registry.priority(agent_id)?))
+                })
+                .collect(),
+        );
+        StateSnapshot {
+            active_leases,
+            pending_leases: Vec::new(),
+            // In declaration order, so conflicts are reported as before
+            active_intents: self
+                .active_intents
+                .candidates(resources)
+                .into_iter()
+                .filter(|intent| !intent.is_expired(now))
+                .cloned()
+                .collect(),
+            priorities,
+            // Only manifests adding dependency edges can close a cycle
+            dependencies: if manifest
+                .intents
+                .iter()
+                .any(|i| matches!(i.predicate, Predicate::DependsOn | Predicate::Provides))
+            {
+                self.dependencies.clone()
+            } else {
+                DependencyGraph::new()
+            },
+            waits_for: WaitForGraph::new(),
+        }
+    }
+
+    /// Make `intent` active as declared `sequence`th, indexed under what
+    /// it touches, along with the dependency edges it makes with the
+    /// intents before it
+    fn index_intent(&mut self, sequence: u64, intent: SPOTriple) {
+        let resources = self
+            .engine
+            .footprint_resources(intent.predicate, &intent.object);
+        if matches!(intent.predicate, Predicate::DependsOn | Predicate::Provides) {
+            let earlier: Vec<SPOTriple> = self
+                .active_intents
+                .candidates(&resources)
+                .into_iter()
+                .cloned()
+                .collect();
+            for edge in DependencyGraph::edges_between(&self.engine, &intent, &earlier) {
+                self.dependencies.insert(edge);
+            }
+        }
+        self.active_intents.insert(sequence, intent, resources);
+    }
+
+    /// Withdraw the active intents `remove` selects, along with the
+    /// dependency edges no other intent makes, and delete them from the
+    /// store. Returns the intents withdrawn, in declaration order.
+    fn remove_intents(&mut self, remove: impl FnMut(&SPOTriple) -> bool) -> Vec<SPOTriple> {
+        let removed = self.active_intents.extract_if(remove);
+        self.settle_removed_intents(&removed);
+        removed.into_iter().map(|(_, intent)| intent).collect()
+    }
+
+    /// Follow-up to `removed` leaving the active intents
+    fn settle_removed_intents(&mut self, removed: &[(u64, SPOTriple)]) {
+        if removed.is_empty() {
+            return;
+        }
+        self.prune_dependencies(removed);
+        let written = removed
+            .iter()
+            .map(|(sequence, _)| self.store.delete_intent(*sequence))
+            .fold(Ok(()), Result::and);
+        self.note_storage(written);
+    }
+
+    /// Keep the outcome of writing intents to the store for
+    /// `storage_ready`
+    fn note_storage(&mut self, written: Result<(), String>) {
+        self.storage_error = written.err();
+    }
+
+    /// Drop the dependency edges of the agents of `removed` that no active
+    /// intent makes any more
+    fn prune_dependencies(&mut self, removed: &[(u64, SPOTriple)]) {
+        let agents: HashSet<&str> = removed
+            .iter()
+            .map(|(_, intent)| intent)
+            .filter(|i| matches!(i.predicate, Predicate::DependsOn | Predicate::Provides))
+            .map(|i| i.subject.as_str())
+            .collect();
+        if agents.is_empty() {
+            return;
+        }
+        let stale: Vec<DependencyEdge> = self
+            .dependencies
+            .edges()
+            .iter()
+            .filter(|edge| {
+                (agents.contains(edge.dependent.as_str())
+                    || agents.contains(edge.provider.as_str()))
+                    && !self.makes_edge(edge)
+            })
+            .cloned()
+            .collect();
+        for edge in &stale {
+            self.dependencies.remove(edge);
+        }
+    }
+
+    /// Whether some pair of active intents makes `edge`
+    fn makes_edge(&self, edge: &DependencyEdge) -> bool {
+        let resources = self
+            .engine
+            .footprint_resources(Predicate::DependsOn, &edge.resource);
+        let nearby: Vec<SPOTriple> = self
+            .active_intents
+            .candidates(&resources)
+            .into_iter()
+            .cloned()
+            .collect();
+        nearby
+            .iter()
+            .filter(|i| {
+                i.predicate == Predicate::DependsOn
+                    && i.subject == edge.dependent
+                    && i.object == edge.resource
+            })
+            .any(|dependent| {
+                DependencyGraph::edges_between(&self.engine, dependent, &nearby).contains(edge)
+            })
+    }
+
+    /// Rebuild the intent index and dependency graph after the engine
+    /// changed what each intent touches
+    fn reindex_intents(&mut self) {
+        let intents: Vec<(u64, SPOTriple)> = self
+            .active_intents
+            .entries()
+            .map(|(sequence, intent)| (sequence, intent.clone()))
+            .collect();
+        self.active_intents.clear();
+        self.dependencies.clear();
+        for (sequence, intent) in intents {
+            self.index_intent(sequence, intent);
+        }
+    }
+
+    /// `DEPENDS_ON` edges between the agents holding active intents.
+    pub fn dependency_graph(&self) -> &DependencyGraph {
+        &self.dependencies
+    }
+
+    /// The full coordination state (live leases, active intents, registered
+    /// priorities and the graphs between them), to checkpoint or to
+    /// `restore` into another client
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            priorities: self.registry.priorities(),
+            ..self.scheduling_snapshot()
+        }
+    }
+
+    /// Load a `snapshot` taken from another client: its priorities are
+    /// registered and its live leases and intents taken over as they were.
+    /// The dependency graph is rebuilt from the intents; wait-for edges are
+    /// not restored and form again as refused agents retry. Refused unless
+    /// this client holds no live leases or intents. Returns the number of
+    /// leases restored.
+    pub fn restore(&mut self, snapshot: StateSnapshot) -> Result<usize, String> {
+        if !self.active_intents.is_empty()
+            || !self.store.get_active_leases().is_empty()
+            || !self.store.get_pending_leases().is_empty()
+        {
+            return Err("Cannot restore into a client holding live leases or intents".to_string());
+        }
+        let now = self.now();
+        for (agent_id, priority) in snapshot.priorities {
+            self.registry.register(agent_id, priority);
+        }
+        let restored = snapshot
+            .active_leases
+            .into_iter()
+            .chain(snapshot.pending_leases)
+            .filter(|lease| {
+                self.store.apply(&KlockEvent::LeaseCreated {
+                    lease: lease.clone(),
+                })
+            })
+            .count();
+        for intent in snapshot.active_intents {
+            let sequence = self.active_intents.next_sequence();
+            self.index_intent(sequence, intent);
+        }
+        self.drain_events(now);
+        for (sequence, intent) in self.active_intents.entries() {
+            self.store.save_intent(sequence, intent)?;
+        }
+        Ok(restored)
+    }
+
+    /// `snapshot` with priorities as the scheduler sees them
+    fn scheduling_snapshot(&self) -> StateSnapshot {
+        let now = self.now();
+        StateSnapshot {
+            active_leases: self.store.get_active_leases(),
+            pending_leases: self.store.get_pending_leases(),
+            active_intents: self
+                .active_intents
+                .iter()
+                .filter(|intent| !intent.is_expired(now))
+                .cloned()
+                .collect(),
+            priorities: self.scheduling_priorities(self.registry.priorities()),
+            dependencies: self.dependencies.clone(),
+            waits_for: self.wait_for_graph(),
+        }
+    }
+
+    /// Who waits on whom: sessions whose manifests were refused with Wait,
+    /// on the holders still holding what was in their way, and queued lease
+    /// requests, on the holders of conflicting leases and the requests
+    /// queued ahead of them.
+    pub fn wait_for_graph(&self) -> WaitForGraph {
+        let mut graph = WaitForGraph::new();
+        let waiters = self.store.waiters();
+        if self.intent_waits.is_empty() && waiters.is_empty() {
+            return graph;
+        }
+        let leases = self.store.get_active_leases();
+        for edge in self.intent_waits.values().flatten() {
+            if holds(&self.active_intents, &leases, edge) {
+                graph.insert(edge.clone());
+            }
+        }
+        for (position, waiter) in waiters.iter().enumerate() {
+            for blocker in self.engine.check_leases_all(
+                &waiter.agent_id,
+                &waiter.session_id,
+                waiter.predicate,
+                &waiter.resource,
+                &leases,
+            ) {
+                graph.insert(WaitForEdge::new(
+                    &waiter.agent_id,
+                    &blocker.holder,
+                    blocker.resource,
+                ));
+            }
+            // `waiters` lists each queue in order
+            for ahead in waiters[..position]
+                .iter()
+                .filter(|w| w.resource == waiter.resource)
+            {
+                graph.insert(WaitForEdge::new(
+                    &waiter.agent_id,
+                    &ahead.agent_id,
+                    ahead.resource.clone(),
+                ));
+            }
+        }
+        graph
+    }
+
+    /// A cycle of agents each waiting on the next, if the wait-for graph
+    /// has one (see `KlockKernel::find_deadlock`)
+    pub fn find_deadlock(&self) -> Option<Vec<WaitForEdge>> {
+        KlockKernel::find_deadlock(&self.scheduling_snapshot())
+    }
+
+    /// Acquire a lease on a resource. An empty `session_id` uses the agent's
+    /// default session.
+    pub fn acquire_lease(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource_type: &str,
+        resource_path: &str,
+        predicate: &str,
+        ttl: u64,
+    ) -> LeaseResult {
+        self.acquire_lease_with_deadline(
+            agent_id,
+            session_id,
+            resource_type,
+            resource_path,
+            predicate,
+            ttl,
+            None,
+        )
+    }
+
+    /// `acquire_lease` for work due by `deadline` (ms since epoch). Against
+    /// an agent of equal priority, after aging and the other adjustments,
+    /// the earlier deadline is senior (EDF); no deadline comes last. The
+    /// lease keeps the deadline, so it also counts when others request what
+    /// it holds.
+    #[allow(clippy::too_many_arguments)]
+    pub fn acquire_lease_with_deadline(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource_type: &str,
+        resource_path: &str,
+        predicate: &str,
+        ttl: u64,
+        deadline: Option<u64>,
+    ) -> LeaseResult {
+        let resource = ResourceRef::new(parse_resource_type(resource_type), resource_path)
+            .normalized(self.path_normalization(agent_id));
+        let pred = parse_predicate(predicate);
+        let result = self.acquire(agent_id, session_id, resource.clone(), pred, ttl, deadline);
+        let session_id = match &result {
+            LeaseResult::Success { lease } => lease.session_id.as_str(),
+            LeaseResult::Failure { .. } => session_id,
+        };
+        self.record_lease_outcome(agent_id, session_id, resource, pred, &result);
+        result
+    }
+
+    /// Admission checks, then the store's acquire
+    fn acquire(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        pred: Predicate,
+        ttl: u64,
+        deadline: Option<u64>,
+    ) -> LeaseResult {
+        let now = self.now();
+        let request = [(resource.clone(), pred)];
+        if let Err(refusal) = self.admit(agent_id, session_id, &request, now) {
+            return refusal.into_result();
+        }
+        let session_id = self.session_for(agent_id, session_id, now);
+        let (ttl, deadline) =
+            match self.admit_by_policy(agent_id, &session_id, &request, ttl, deadline, now) {
+                Ok(admitted) => admitted,
+                Err(refusal) => return refusal.into_result(),
+            };
+        let resource_key = resource.key();
+        if let Some(rule) = self.cosign.rule_for(pred, &resource).cloned() {
+            return self.request_cosign(&rule, agent_id, &session_id, resource, pred, ttl, now);
+        }
+        let result = self.store.acquire_with_deadline(
+            agent_id,
+            &session_id,
+            resource,
+            pred,
+            ttl,
+            deadline,
+            now,
+        );
+
+        match &result {
+            LeaseResult::Success { .. } => {
+                self.debounce.clear(agent_id, &resource_key);
+                self.granted(agent_id, now);
+                self.refresh_ceiling(agent_id);
+                // A wounded holder may have lost its critical lease
+                for boosted in self.ceilings.boosted_agents() {
+                    self.refresh_ceiling(&boosted);
+                }
+            }
+            LeaseResult::Failure { reason, .. } => {
+                self.debounce.record_failure(agent_id, &resource_key, now);
+                if *reason == LeaseFailureReason::Die {
+                    self.denied(agent_id, now);
+                }
+            }
+        }
+
+        self.drain_events(now);
+        result
+    }
+
+    /// Acquires queued after a Wait verdict, by resource and then in queue
+    /// order. Each is granted automatically when the leases in its way end.
+    pub fn lease_waiters(&self) -> Vec<Waiter> {
+        self.store.waiters()
+    }
+
+    /// Abandon queued acquires, e.g. when an agent re-plans, by request ID
+    /// (`request_id` of the Wait result) or by agent and resource. Returns
+    /// the cancelled requests. The sessions holding their resources get a
+    /// `waiter_cancelled` event, and requests queued behind them may be
+    /// granted. A request already granted is not affected; release its
+    /// lease instead.
+    pub fn cancel_pending(&mut self, filter: &WaiterFilter) -> Vec<Waiter> {
+        let cancelled = match filter {
+            WaiterFilter::Resource { agent_id, resource } => {
+                self.store.cancel_waiters(&WaiterFilter::Resource {
+                    agent_id: agent_id.clone(),
+                    resource: resource.normalized(self.path_normalization(agent_id)),
+                })
+            }
+            filter => self.store.cancel_waiters(filter),
+        };
+        if cancelled.is_empty() {
+            return cancelled;
+        }
+        let now = self.now();
+        let waiters = self.store.waiters();
+        for waiter in &cancelled {
+            let waiting = waiters
+                .iter()
+                .filter(|w| w.resource == waiter.resource)
+                .count();
+            let mut holders: Vec<(String, String)> = self
+                .store
+                .read_snapshot(std::slice::from_ref(&waiter.resource))
+                .leases
+                .into_iter()
+                .filter(|lease| lease.resource.overlaps(&waiter.resource))
+                .map(|lease| (lease.agent_id, lease.session_id))
+                .collect();
+            holders.sort();
+            holders.dedup();
+            for (agent_id, session_id) in holders {
+                self.feeds.push(
+                    &agent_id,
+                    &session_id,
+                    AgentEvent::WaiterCancelled {
+                        request_id: waiter.id.clone(),
+                        agent_id: waiter.agent_id.clone(),
+                        resource: waiter.resource.clone(),
+                        predicate: waiter.predicate,
+                        waiting,
+                        at: now,
+                    },
+                );
+            }
+        }
+        // A cancelled head may have held up compatible requests behind it
+        self.grant_queued(now);
+        self.drain_events(now);
+        cancelled
+    }
+
+    /// The active leases that block `predicate` on `resource` for the agent
+    /// in `session_id` (its default session if empty), each with how its
+    /// resource relates to the requested one. Explains a refused
+    /// `acquire_lease`: a refusal on a directory may be caused by a few
+    /// leases inside it, which the agent can work around.
+    pub fn lease_blockers(
+        &self,
+        agent_id: &str,
+        session_id: &str,
+        resource: &ResourceRef,
+        predicate: Predicate,
+    ) -> Vec<ConflictDetail> {
+        let session_id = match session_id {
+            "" => self
+                .sessions
+                .get(agent_id, self.now())
+                .map_or("", |session| session.session_id.as_str()),
+            session_id => session_id,
+        };
+        let resource = &resource.normalized(self.path_normalization(agent_id));
+        let leases = self
+            .store
+            .read_snapshot(&self.engine.footprint_resources(predicate, resource))
+            .leases;
+        self.engine
+            .check_leases_all(agent_id, session_id, predicate, resource, &leases)
+    }
+
+    /// Reserve resources as warm spares for a later pipeline stage.
+    ///
+    /// Reservations stay `Pending` (and never block other agents) until
+    /// `activate_lease` is called or `activate_by` passes. At activation the
+    /// reserver wins against any lease granted after the reservation. An
+    /// empty `session_id` uses the agent's default session. Reservations
+    /// and their activation pass the same admission checks as an acquire.
+    pub fn pre_acquire(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resources: &[(ResourceRef, Predicate)],
+        ttl: u64,
+        activate_by: u64,
+    ) -> Result<Vec<Lease>, String> {
+        let now = self.now();
+        if activate_by <= now {
+            return Err(format!(
+                "activate_by ({}) must be in the future (now = {})",
+                activate_by, now
+            ));
+        }
+        let rules = self.path_normalization(agent_id);
+        let resources: &[(ResourceRef, Predicate)] = &resources
+            .iter()
+            .map(|(resource, predicate)| (resource.normalized(rules), *predicate))
+            .collect::<Vec<_>>();
+        if let Err(refusal) = self.admit(agent_id, session_id, resources, now) {
+            return Err(refusal.message);
+        }
+        if let Some((rule, _)) = resources
+            .iter()
+            .find_map(|(resource, pred)| Some((self.cosign.rule_for(*pred, resource)?, resource)))
+        {
+            return Err(format!(
+                "Co-signing rule '{}' protects the request; acquire it to ask for approval",
+                rule.name
+            ));
+        }
+        let session_id = self.session_for(agent_id, session_id, now);
+        let (ttl, _) = self
+            .admit_by_policy(agent_id, &session_id, resources, ttl, None, now)
+            .map_err(|refusal| refusal.message)?;
+        let leases =
+            self.store
+                .pre_acquire(agent_id, &session_id, resources, ttl, activate_by, now);
+        self.drain_events(now);
+        Ok(leases)
+    }
+
+    /// Activate a pending reservation created by `pre_acquire`.
+    pub fn activate_lease(&mut self, lease_id: &str) -> LeaseResult {
+        let now = self.now();
+        let Some(lease) = self.store.get_lease(lease_id) else {
+            return self.store.activate(lease_id, now);
+        };
+        let result = if self.cosign.awaits_approval(lease_id) {
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::CosignRequired,
+                existing_lease: Some(lease.clone()),
+                wait_time: Some(lease.expires_at.saturating_sub(now)),
+                queue_position: None,
+                request_id: Some(lease_id.to_string()),
+                holders: Vec::new(),
+            }
+        } else if let Err(refusal) = self.admit_activation(&lease, now) {
+            refusal.into_result()
+        } else {
+            self.store.activate(lease_id, now)
+        };
+        if matches!(result, LeaseResult::Success { .. }) {
+            self.cosign.remove(lease_id);
+            self.refresh_ceiling(&lease.agent_id);
+        }
+        self.drain_events(now);
+        self.record_lease_outcome(
+            &lease.agent_id,
+            &lease.session_id,
+            lease.resource,
+            lease.predicate,
+            &result,
+        );
+        result
+    }
+
+    /// The admission checks and the policy hook for activating `lease`. The
+    /// TTL was settled when it was reserved, so only a denial counts.
+    fn admit_activation(&mut self, lease: &Lease, now: u64) -> Result<(), Refusal> {
+        let request = [(lease.resource.clone(), lease.predicate)];
+        self.admit(&lease.agent_id, &lease.session_id, &request, now)?;
+        self.admit_by_policy(
+            &lease.agent_id,
+            &lease.session_id,
+            &request,
+            lease.ttl,
+            lease.deadline,
+            now,
+        )?;
+        Ok(())
+    }
+
+    /// Require another agent's approval for the operations `rules` protect
+    /// (see `cosign`), rejecting invalid rules. None by default.
+    pub fn set_cosign_rules(&mut self, rules: Vec<CosignRule>) -> Result<(), String> {
+        self.cosign.set_rules(rules)
+    }
+
+    pub fn cosign_rules(&self) -> &[CosignRule] {
+        self.cosign.rules()
+    }
+
+    /// Co-signing requests not yet activated, oldest first
+    pub fn pending_cosigns(&mut self) -> Vec<CosignRequest> {
+        let now = self.now();
+        self.evict_and_track(now);
+        self.cosign.pending()
+    }
+
+    /// Approve the co-signing request for `lease_id` as `cosigner`, then
+    /// activate the lease. An activation refused by the scheduler leaves
+    /// the request approved: the requester activates it later with
+    /// `activate_lease`.
+    pub fn cosign(&mut self, lease_id: &str, cosigner: &str) -> Result<LeaseResult, CosignError> {
+        let now = self.now();
+        self.evict_and_track(now);
+        let request = self.cosign.approve(lease_id, cosigner, now)?;
+        self.record_cosign_activity(
+            &request,
+            &request.agent_id,
+            &request.session_id,
+            ActivityKind::Cosigned,
+            format!("by {}", cosigner),
+            now,
+        );
+        let session_id = self.session_for(cosigner, "", now);
+        self.record_cosign_activity(
+            &request,
+            cosigner,
+            &session_id,
+            ActivityKind::Cosigned,
+            format!("for {}", request.agent_id),
+            now,
+        );
+        Ok(self.activate_lease(lease_id))
+    }
+
+    /// Park a protected acquire as a pending lease until a co-signer
+    /// approves it
+    #[allow(clippy::too_many_arguments)]
+    fn request_cosign(
+        &mut self,
+        rule: &CosignRule,
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        pred: Predicate,
+        ttl: u64,
+        now: u64,
+    ) -> LeaseResult {
+        let expires_at = now + rule.timeout_ms;
+        let lease = self
+            .store
+            .pre_acquire(
+                agent_id,
+                session_id,
+                &[(resource, pred)],
+                ttl,
+                expires_at,
+                now,
+            )
+            .remove(0);
+        let request = self.cosign.request(rule, &lease, now);
+        self.record_cosign_activity(
+            &request,
+            agent_id,
+            session_id,
+            ActivityKind::CosignRequested,
+            format!("rule {}", rule.name),
+            now,
+        );
+        self.drain_events(now);
+        LeaseResult::Failure {
+            reason: LeaseFailureReason::CosignRequired,
+            wait_time: Some(rule.timeout_ms),
+            queue_position: None,
+            request_id: Some(lease.id.clone()),
+            existing_lease: Some(lease),
+            holders: Vec::new(),
+        }
+    }
+
+    /// Record a co-signing step for `request` on `agent_id`'s timeline
+    fn record_cosign_activity(
+        &mut self,
+        request: &CosignRequest,
+        agent_id: &str,
+        session_id: &str,
+        kind: ActivityKind,
+        outcome: String,
+        now: u64,
+    ) {
+        self.activity.record(ActivityEntry {
+            at: now,
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            kind,
+            lease_id: Some(request.lease_id.clone()),
+            resources: vec![request.resource.clone()],
+            predicate: Some(request.predicate),
+            outcome: Some(outcome),
+        });
+    }
+
+    /// Release a held lease by its ID.
+    pub fn release_lease(&mut self, lease_id: &str) -> bool {
+        // Also remove from active intents
+        self.forget_intent(lease_id);
+        let lease = self.store.get_lease(lease_id);
+        let released = self.store.release(lease_id);
+        self.cosign.remove(lease_id);
+        if released && let Some(lease) = lease {
+            self.lease_ended(&lease, ActivityKind::Released);
+        }
+        released
+    }
+
+    /// `release_lease`, refused with `LeaseMutationError::Conflict` unless
+    /// the lease is still at `expected_revision`. Returns the released
+    /// lease.
+    pub fn release_lease_if(
+        &mut self,
+        lease_id: &str,
+        expected_revision: u64,
+    ) -> Result<Lease, LeaseMutationError> {
+        let event = KlockEvent::LeaseReleased {
+            lease_id: lease_id.to_string(),
+        };
+        let lease = self.store.apply_at(&event, expected_revision)?;
+        self.forget_intent(lease_id);
+        self.lease_ended(&lease, ActivityKind::Released);
+        Ok(lease)
+    }
+
+    /// Release `lease_ids` together, if every one of them is held and every
+    /// condition holds (see `release`); otherwise release none of them.
+    /// Conditions must be on leases of the batch, and a lease may appear
+    /// in it once: a malformed batch is refused with `Err`.
+    pub fn release_many_checked(
+        &mut self,
+        lease_ids: &[String],
+        conditions: &[ReleaseCondition],
+    ) -> Result<BatchRelease, String> {
+        for (i, lease_id) in lease_ids.iter().enumerate() {
+            if lease_ids[..i].contains(lease_id) {
+                return Err(format!("Lease '{}' is in the batch twice", lease_id));
+            }
+        }
+        if let Some(condition) = conditions
+            .iter()
+            .find(|c| !lease_ids.iter().any(|id| id == c.lease_id()))
+        {
+            return Err(format!(
+                "Condition on lease '{}', which is not in the batch",
+                condition.lease_id()
+            ));
+        }
+
+        // Check everything before touching anything
+        let mut leases = Vec::with_capacity(lease_ids.len());
+        let mut results: Vec<LeaseRelease> = lease_ids
+            .iter()
+            .map(|lease_id| {
+                let lease = self.store.get_lease(lease_id);
+                let status = match &lease {
+                    None => ReleaseStatus::NotFound,
+                    Some(lease) if lease.is_terminal() => {
+                        ReleaseStatus::NotHeld { state: lease.state }
+                    }
+                    Some(lease) => conditions
+                        .iter()
+                        .filter(|c| c.lease_id() == lease_id)
+                        .find_map(|c| self.failed_condition(lease, c))
+                        .map_or(ReleaseStatus::Released, |reason| {
+                            ReleaseStatus::ConditionFailed { reason }
+                        }),
+                };
+                leases.extend(lease);
+                LeaseRelease {
+                    lease_id: lease_id.clone(),
+                    status,
+                }
+            })
+            .collect();
+        if results.iter().any(|r| r.status != ReleaseStatus::Released) {
+            for result in &mut results {
+                if result.status == ReleaseStatus::Released {
+                    result.status = ReleaseStatus::Aborted;
+                }
+            }
+            return Ok(BatchRelease {
+                released: false,
+                results,
+            });
+        }
+
+        for lease in leases {
+            let _ = self.release_lease_if(&lease.id, lease.revision);
+        }
+        Ok(BatchRelease {
+            released: true,
+            results,
+        })
+    }
+
+    /// Why `condition` does not hold for `lease`, if it does not
+    fn failed_condition(&self, lease: &Lease, condition: &ReleaseCondition) -> Option<String> {
+        match condition {
+            ReleaseCondition::Revision { revision, .. } => (lease.revision != *revision)
+                .then(|| format!("Lease is at revision {}, not {}", lease.revision, revision)),
+            ReleaseCondition::NoConflictingLease { .. } => {
+                let mut candidates = self
+                    .store
+                    .leases_touching(std::slice::from_ref(&lease.resource));
+                for lease_id in self
+                    .activity
+                    .acquired_since(&lease.resource, lease.acquired_at)
+                {
+                    if !candidates.iter().any(|c| c.id == lease_id)
+                        && let Some(candidate) = self.store.get_lease(&lease_id)
+                    {
+                        candidates.push(candidate);
+                    }
+                }
+                candidates.retain(|c| c.id != lease.id && c.acquired_at >= lease.acquired_at);
+                sort_leases(&mut candidates);
+                let conflicting = self.engine.conflicting_leases(
+                    &lease.agent_id,
+                    &lease.session_id,
+                    lease.predicate,
+                    &lease.resource,
+                    &candidates,
+                );
+                conflicting.first().map(|other| {
+                    format!(
+                        "'{}' acquired a conflicting lease on {} ({:?}, lease {}) at {}",
+                        other.agent_id,
+                        other.resource.key(),
+                        other.predicate,
+                        other.id,
+                        other.acquired_at
+                    )
+                })
+            }
+        }
+    }
+
+    /// Forcibly end another agent's active lease, e.g. one held by a stuck
+    /// agent. With `expected_revision`, the revocation is refused if the
+    /// lease changed since the caller read it. `reason` is recorded on the
+    /// lease and sent to the holder's session. Returns the revoked lease.
+    pub fn revoke_lease(
+        &mut self,
+        lease_id: &str,
+        expected_revision: Option<u64>,
+        reason: Option<&str>,
+    ) -> Result<Lease, LeaseMutationError> {
+        let expected_revision = match expected_revision {
+            Some(revision) => revision,
+            None => {
+                self.store
+                    .get_lease(lease_id)
+                    .ok_or(LeaseMutationError::NotFound)?
+                    .revision
+            }
+        };
+        let reason = reason.unwrap_or("Revoked by an operator").to_string();
+        self.revoke(lease_id, expected_revision, None, reason)
+    }
+
+    /// Let a senior agent revoke a junior's active lease, as the preemption
+    /// policy allows (see `preemption`). The lease records `reason`, and
+    /// the holder's session is told who took it. Returns the revoked lease.
+    pub fn preempt_lease(
+        &mut self,
+        agent_id: &str,
+        lease_id: &str,
+        reason: &str,
+    ) -> Result<Lease, PreemptionError> {
+        if !self.preemption.is_enabled() {
+            return Err(PreemptionError::Disabled);
+        }
+        let requester = self
+            .agent_priority(agent_id)
+            .ok_or(PreemptionError::UnknownAgent)?;
+        let lease = self
+            .store
+            .get_lease(lease_id)
+            .ok_or(PreemptionError::Lease(LeaseMutationError::NotFound))?;
+        if lease.state != LeaseState::Active {
+            return Err(PreemptionError::Lease(LeaseMutationError::InvalidState {
+                state: lease.state,
+            }));
+        }
+        // An unregistered holder is as junior as can be
+        let holder = self
+            .agent_priority(&lease.agent_id)
+            .unwrap_or(Priority::lowest());
+        if lease.agent_id == agent_id || !self.preemption.allows(requester, holder) {
+            return Err(PreemptionError::NotSenior { requester, holder });
+        }
+        self.revoke(
+            lease_id,
+            lease.revision,
+            Some(agent_id),
+            format!("Preempted by senior agent '{}': {}", agent_id, reason),
+        )
+        .map_err(PreemptionError::Lease)
+    }
+
+    /// Revoke a lease at `expected_revision` and tell its holder's session
+    fn revoke(
+        &mut self,
+        lease_id: &str,
+        expected_revision: u64,
+        revoked_by: Option<&str>,
+        reason: String,
+    ) -> Result<Lease, LeaseMutationError> {
+        let event = KlockEvent::LeaseRevoked {
+            lease_id: lease_id.to_string(),
+            reason: reason.clone(),
+        };
+        let lease = self.store.apply_at(&event, expected_revision)?;
+        self.feeds.push(
+            &lease.agent_id,
+            &lease.session_id,
+            AgentEvent::LeaseRevoked {
+                lease_id: lease.id.clone(),
+                resource: lease.resource.clone(),
+                predicate: lease.predicate,
+                revoked_by: revoked_by.map(str::to_string),
+                reason,
+                at: self.now(),
+            },
+        );
+        self.forget_intent(lease_id);
+        self.lease_ended(&lease, ActivityKind::Revoked);
+        Ok(lease)
+    }
+
+    /// Drop the intents that expired before `now`, so they block nobody.
+    /// Returns the number of intents evicted.
+    pub fn evict_expired_intents(&mut self, now: u64) -> usize {
+        self.remove_intents(|intent| intent.is_expired(now)).len()
+    }
+
+    /// Withdraw the active intent `intent_id`, so it blocks nobody. A lease
+    /// acquired for it is left alone. Returns the intent withdrawn, or
+    /// `None` if no active intent has that ID.
+    pub fn withdraw_intent(&mut self, intent_id: &str) -> Option<SPOTriple> {
+        let removed = [self.active_intents.remove(intent_id)?];
+        self.settle_removed_intents(&removed);
+        let [(_, intent)] = removed;
+        self.record_withdrawal(&intent, None);
+        Some(intent)
+    }
+
+    /// Withdraw every active intent `agent_id` declared in `session_id`,
+    /// and what the session was refused with Wait over. Session IDs are
+    /// only unique per agent, so other agents' sessions of the same ID are
+    /// left alone, as are leases. Returns the intents withdrawn, in
+    /// declaration order.
+    pub fn withdraw_session_intents(&mut self, agent_id: &str, session_id: &str) -> Vec<SPOTriple> {
+        let withdrawn = self
+            .remove_intents(|intent| intent.subject == agent_id && intent.session_id == session_id);
+        self.intent_waits
+            .remove(&(agent_id.to_string(), session_id.to_string()));
+        for intent in &withdrawn {
+            self.record_withdrawal(intent, None);
+        }
+        withdrawn
+    }
+
+    fn record_withdrawal(&mut self, intent: &SPOTriple, outcome: Option<&str>) {
+        self.activity.record(ActivityEntry {
+            at: self.now(),
+            agent_id: intent.subject.clone(),
+            session_id: intent.session_id.clone(),
+            kind: ActivityKind::IntentWithdrawn,
+            lease_id: None,
+            resources: vec![intent.object.clone()],
+            predicate: Some(intent.predicate),
+            outcome: outcome.map(str::to_string),
+        });
+    }
+
+    /// Drop the active intent recorded under `id`, if any
+    fn forget_intent(&mut self, id: &str) {
+        let removed = self.active_intents.remove_all(id);
+        self.settle_removed_intents(&removed);
+    }
+
+    /// Follow-up to a lease released or revoked by request
+    fn lease_ended(&mut self, lease: &Lease, kind: ActivityKind) {
+        let now = self.now();
+        self.notify_dependents(lease, now);
+        if kind == ActivityKind::Released {
+            self.release_covered_intents(lease);
+        }
+        self.record_lease_activity(lease, kind, None, now);
+        self.refresh_ceiling(&lease.agent_id);
+        self.grant_queued(now);
+        self.drain_events(now);
+    }
+
+    /// Withdraw the intents the released `lease` covered that no other
+    /// active lease of its session still covers, so a session that released
+    /// everything it leased for an intent stops blocking others with it.
+    /// Long-lived intents are kept.
+    fn release_covered_intents(&mut self, lease: &Lease) {
+        let covered: Vec<ResourceRef> = self
+            .active_intents
+            .iter()
+            .filter(|intent| !intent.long_lived && lease.covers(intent))
+            .map(|intent| intent.object.clone())
+            .collect();
+        if covered.is_empty() {
+            return;
+        }
+        let remaining: Vec<Lease> = self
+            .store
+            .leases_touching(&covered)
+            .into_iter()
+            .filter(|held| held.id != lease.id)
+            .collect();
+        let released = self.remove_intents(|intent| {
+            !intent.long_lived
+                && lease.covers(intent)
+                && !remaining.iter().any(|held| held.covers(intent))
+        });
+        for intent in &released {
+            self.record_withdrawal(intent, Some("Released"));
+        }
+    }
+
+    /// Tell the sessions that depend on a resource the released `lease`
+    /// changed
+    fn notify_dependents(&mut self, lease: &Lease, now: u64) {
+        if !matches!(
+            lease.predicate,
+            Predicate::Mutates | Predicate::Deletes | Predicate::Renames
+        ) {
+            return;
+        }
+        let mut notified: Vec<(&str, &str, &ResourceRef)> = Vec::new();
+        for intent in self
+            .active_intents
+            .candidates(std::slice::from_ref(&lease.resource))
+        {
+            let watch = (
+                intent.subject.as_str(),
+                intent.session_id.as_str(),
+                &intent.object,
+            );
+            if intent.predicate == Predicate::DependsOn
+                && intent.subject != lease.agent_id
+                && intent.object.overlaps(&lease.resource)
+                && !notified.contains(&watch)
+            {
+                notified.push(watch);
+            }
+        }
+        for (agent_id, session_id, watched) in notified {
+            self.feeds.push(
+                agent_id,
+                session_id,
+                AgentEvent::DependencyChanged {
+                    watched: watched.clone(),
+                    resource: lease.resource.clone(),
+                    predicate: lease.predicate,
+                    changed_by: lease.agent_id.clone(),
+                    lease_id: lease.id.clone(),
+                    at: now,
+                },
+            );
+        }
+    }
+
+    /// The session's dependency watch list: the resources of its active
+    /// `DEPENDS_ON` intents.
+    pub fn watched_resources(&self, agent_id: &str, session_id: &str) -> Vec<ResourceRef> {
+        let mut watched: Vec<ResourceRef> = Vec::new();
+        for intent in self.active_intents.iter() {
+            if intent.predicate == Predicate::DependsOn
+                && intent.subject == agent_id
+                && intent.session_id == session_id
+                && !watched.contains(&intent.object)
+            {
+                watched.push(intent.object.clone());
+            }
+        }
+        watched
+    }
+
+    /// Take the events queued for a session (an empty session ID means the
+    /// agent's default session), oldest first.
+    pub fn poll_events(&mut self, agent_id: &str, session_id: &str) -> FeedBatch {
+        self.warn_expiring_leases();
+        let session_id = if session_id.is_empty() {
+            match self.sessions.get(agent_id, self.now()) {
+                Some(session) => session.session_id.clone(),
+                None => return FeedBatch::default(),
+            }
+        } else {
+            session_id.to_string()
+        };
+        self.feeds.poll(agent_id, &session_id)
+    }
+
+    /// Look up a lease by ID, whatever its state
+    pub fn get_lease(&self, lease_id: &str) -> Option<Lease> {
+        self.store.get_lease(lease_id)
+    }
+
+    /// Get all currently active leases, oldest first (ties broken by ID).
+    pub fn get_active_leases(&self) -> Vec<Lease> {
+        self.store.get_active_leases()
+    }
+
+    /// Leases on resources overlapping `resource_key` (`TYPE:path`) that
+    /// were held at `at`, oldest first, as far back as the store keeps
+    /// lease history: the SQLite store keeps all of it, the in-memory store
+    /// only terminal leases not yet compacted.
+    pub fn holders_at(&self, resource_key: &str, at: u64) -> Result<Vec<Lease>, String> {
+        let resource = ResourceRef::from_key(resource_key)
+            .filter(|resource| !resource.path.is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "Invalid resource key '{}': expected TYPE:path, e.g. FILE:/src/a.ts",
+                    resource_key
+                )
+            })?
+            .normalized(&self.paths);
+        Ok(self.store.leases_at(&resource, at))
+    }
+
+    /// Evict expired leases. Returns the number of leases evicted.
+    pub fn evict_expired(&mut self) -> usize {
+        let now = self.now();
+        self.evict_and_track(now)
+    }
+
+    /// Heartbeat a lease to renew its TTL. Returns true if successful.
+    pub fn heartbeat_lease(&mut self, lease_id: &str, now: u64) -> bool {
+        let renewed = self.store.heartbeat(lease_id, now);
+        if let Some(lease) = self.store.get_lease(lease_id) {
+            self.heartbeat_recorded(&lease, renewed, now);
+        }
+        renewed
+    }
+
+    /// `heartbeat_lease`, refused with `LeaseMutationError::Conflict`
+    /// unless the lease is still at `expected_revision`. Returns the renewed
+    /// lease. A refused heartbeat on a stale revision is not charged to the
+    /// agent's health; one on a lease that is no longer active is.
+    pub fn heartbeat_lease_if(
+        &mut self,
+        lease_id: &str,
+        expected_revision: u64,
+        now: u64,
+    ) -> Result<Lease, LeaseMutationError> {
+        let event = KlockEvent::LeaseRenewed {
+            lease_id: lease_id.to_string(),
+            at: now,
+        };
+        let result = self.store.apply_at(&event, expected_revision);
+        match &result {
+            Ok(lease) => self.heartbeat_recorded(lease, true, now),
+            Err(LeaseMutationError::InvalidState { .. }) => {
+                if let Some(lease) = self.store.get_lease(lease_id) {
+                    self.heartbeat_recorded(&lease, false, now);
+                }
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// Heartbeat a lease at the client's clock and report the lease as it
+    /// stands afterwards, renewed or not: its state (a revoked or expired
+    /// lease says so), remaining TTL and renewal count, and the drift
+    /// between `client_time` and the client's clock if the caller sent it.
+    /// With `expected_revision`, a stale revision is refused as in
+    /// `heartbeat_lease_if`.
+    pub fn heartbeat_lease_report(
+        &mut self,
+        lease_id: &str,
+        expected_revision: Option<u64>,
+        client_time: Option<u64>,
+    ) -> Result<HeartbeatReport, LeaseMutationError> {
+        let now = self.now();
+        let renewed = match expected_revision {
+            Some(revision) => match self.heartbeat_lease_if(lease_id, revision, now) {
+                Ok(_) => true,
+                Err(e @ LeaseMutationError::Conflict { .. }) => return Err(e),
+                Err(_) => false,
+            },
+            None => self.heartbeat_lease(lease_id, now),
+        };
+        let lease = self.store.get_lease(lease_id);
+        Ok(HeartbeatReport::new(
+            lease_id,
+            lease.as_ref(),
+            renewed,
+            now,
+            client_time,
+        ))
+    }
+
+    /// Account for a heartbeat on `lease`, renewed or missed
+    fn heartbeat_recorded(&mut self, lease: &Lease, renewed: bool, now: u64) {
+        if renewed {
+            // Work in a default session keeps it alive
+            if !self.sessions.is_empty() {
+                self.sessions.touch(&lease.agent_id, &lease.session_id, now);
+            }
+            self.renew_intents(&lease.agent_id, &lease.session_id, now);
+        } else {
+            self.health
+                .record(&lease.agent_id, HealthSignal::MissedHeartbeat, now);
+            self.refresh_health(&lease.agent_id, now);
+        }
+        let outcome = if renewed { "renewed" } else { "missed" };
+        self.record_lease_activity(lease, ActivityKind::Heartbeat, Some(outcome), now);
+        self.drain_events(now);
+    }
+
+    /// Push back the expiry of a session's unexpired intents by the intent
+    /// TTL, as a heartbeat on one of its leases shows it is alive
+    fn renew_intents(&mut self, agent_id: &str, session_id: &str, now: u64) {
+        let Some(ttl_ms) = self.intent_ttl_ms else {
+            return;
+        };
+        let mut renewed = Vec::new();
+        for (sequence, intent) in self.active_intents.iter_mut() {
+            if intent.subject == agent_id
+                && intent.session_id == session_id
+                && let Some(expires_at) = intent.expires_at.as_mut()
+                && *expires_at >= now
+            {
+                *expires_at = (*expires_at).max(now.saturating_add(ttl_ms));
+                renewed.push((sequence, intent.clone()));
+            }
+        }
+        if !renewed.is_empty() {
+            let written = renewed
+                .iter()
+                .map(|(sequence, intent)| self.store.save_intent(*sequence, intent))
+                .fold(Ok(()), Result::and);
+            self.note_storage(written);
+        }
+    }
+
+    /// The session a request runs in: `session_id`, or the agent's default
+    /// session when it is empty.
+    fn session_for(&mut self, agent_id: &str, session_id: &str, now: u64) -> String {
+        if session_id.is_empty() {
+            self.sessions.resolve(agent_id, now)
+        } else {
+            self.sessions.touch(agent_id, session_id, now);
+            session_id.to_string()
+        }
+    }
+
+    /// Evict expired leases, charging each holder an expiration signal.
+    fn evict_and_track(&mut self, now: u64) -> usize {
+        // Leases are not expired on a clock that just jumped back
+        if !self.skew.observe(now) {
+            self.skew.record_held();
+            return 0;
+        }
+        let expired: Vec<Lease> = self
+            .store
+            .get_active_leases()
+            .into_iter()
+            .filter(|l| l.expires_at < now)
+            .collect();
+        for lease in &expired {
+            self.health
+                .record(&lease.agent_id, HealthSignal::Expiration, now);
+            self.refresh_health(&lease.agent_id, now);
+            self.record_lease_activity(lease, ActivityKind::Evicted, None, now);
+        }
+        for request in self.cosign.take_expired(now) {
+            let outcome = format!("rule {}", request.rule);
+            self.record_cosign_activity(
+                &request,
+                &request.agent_id,
+                &request.session_id,
+                ActivityKind::CosignExpired,
+                outcome,
+                now,
+            );
+        }
+        let evicted = self.store.evict_expired(now);
+        for lease in &expired {
+            self.refresh_ceiling(&lease.agent_id);
+        }
+        if evicted > 0 {
+            self.grant_queued(now);
+        }
+        self.drain_events(now);
+        evicted
+    }
+
+    /// Hand resources freed by ended leases to the agents queued for them,
+    /// telling each waiter's session through its feed
+    fn grant_queued(&mut self, now: u64) {
+        for lease in self.store.grant_waiters(now) {
+            self.granted(&lease.agent_id, now);
+            self.refresh_ceiling(&lease.agent_id);
+            self.record_lease_activity(&lease, ActivityKind::Acquired, Some("QUEUED"), now);
+            self.feeds.push(
+                &lease.agent_id,
+                &lease.session_id,
+                AgentEvent::LeaseGranted {
+                    lease_id: lease.id.clone(),
+                    resource: lease.resource.clone(),
+                    predicate: lease.predicate,
+                    expires_at: lease.expires_at,
+                    at: now,
+                },
+            );
+        }
+    }
+
+    /// Stamp leases the store ended since the last call with their end
+    /// time, then copy the events it applied into the firehose and the
+    /// usage meter, each with the lease it left behind
+    fn drain_events(&mut self, now: u64) {
+        for event in self.store.take_events() {
+            if let KlockEvent::LeaseReleased { lease_id }
+            | KlockEvent::LeaseRevoked { lease_id, .. }
+            | KlockEvent::LeaseExpired { lease_id } = &event
+            {
+                self.store.record_lease_end(lease_id, now);
+            }
+            if !self.records_events() {
+                continue;
+            }
+            let lease = match &event {
+                KlockEvent::LeaseCreated { lease } => self
+                    .store
+                    .get_lease(&lease.id)
+                    .or_else(|| Some(lease.clone())),
+                event => event.lease_id().and_then(|id| self.store.get_lease(id)),
+            };
+            self.usage.observe(now, &event, lease.as_ref());
+            if self.firehose.is_enabled() {
+                self.firehose.record(now, &event, lease);
+            }
+        }
+    }
+
+    /// Whether anything consumes the events the store applies
+    fn records_events(&self) -> bool {
+        self.firehose.is_enabled() || self.usage.is_enabled()
+    }
+
+    /// Keep the last `capacity` lease state changes for `firehose`, or stop
+    /// recording them with 0. Starts a new epoch: sequences restart at 1.
+    pub fn set_firehose_capacity(&mut self, capacity: usize) {
+        let now = self.now();
+        self.drain_events(now);
+        self.firehose = Firehose::new(capacity, now);
+    }
+
+    /// Up to `limit` lease state changes after `cursor`, oldest first, or
+    /// `None` if the firehose is disabled.
+    pub fn firehose(&self, cursor: u64, limit: usize) -> Option<FirehosePage> {
+        self.firehose
+            .is_enabled()
+            .then(|| self.firehose.read(cursor, limit))
+    }
+
+    /// Label an agent (e.g. `team=payments`) for usage reports, replacing
+    /// its previous labels
+    pub fn set_agent_labels(&mut self, agent_id: &str, labels: AgentLabels) {
+        self.usage.set_labels(agent_id, labels);
+    }
+
+    pub fn agent_labels(&self, agent_id: &str) -> AgentLabels {
+        self.usage.labels(agent_id).cloned().unwrap_or_default()
+    }
+
+    /// Meter each agent's acquires and lease time from now on, keeping
+    /// closed periods in `store`; `None` stops metering. Leases already
+    /// held are not counted.
+    pub fn set_usage_store(&mut self, store: Option<Arc<dyn UsageStore>>) {
+        let now = self.now();
+        self.drain_events(now);
+        self.usage.set_store(store, now);
+    }
+
+    /// Close the current usage period and store one rollup per agent that
+    /// used leases in it. Returns how many were stored.
+    pub fn roll_up_usage(&mut self) -> Result<usize, String> {
+        let now = self.now();
+        self.drain_events(now);
+        self.usage.roll_up(now)
+    }
+
+    /// Usage between `from` and `to` grouped by the `group_by` label, or
+    /// by agent with `usage::GROUP_BY_AGENT`, including the current
+    /// period. `None` when usage is not metered.
+    pub fn usage_report(
+        &self,
+        group_by: &str,
+        from: u64,
+        to: u64,
+    ) -> Option<Result<UsageReport, String>> {
+        self.usage.report(group_by, from, to, self.now())
+    }
+
+    /// Record an acquire or activation on the agent's timeline
+    fn record_lease_outcome(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        predicate: Predicate,
+        result: &LeaseResult,
+    ) {
+        let (kind, lease_id, outcome) = match result {
+            LeaseResult::Success { lease } => {
+                (ActivityKind::Acquired, Some(lease.id.clone()), None)
+            }
+            LeaseResult::Failure { reason, .. } => (
+                ActivityKind::Denied,
+                None,
+                Some(reason.as_str().to_string()),
+            ),
+        };
+        self.activity.record(ActivityEntry {
+            at: self.now(),
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            kind,
+            lease_id,
+            resources: vec![resource],
+            predicate: Some(predicate),
+            outcome,
+        });
+    }
+
+    /// Record something that happened to `lease` on its holder's timeline
+    fn record_lease_activity(
+        &mut self,
+        lease: &Lease,
+        kind: ActivityKind,
+        outcome: Option<&str>,
+        now: u64,
+    ) {
+        self.activity.record(ActivityEntry {
+            at: now,
+            agent_id: lease.agent_id.clone(),
+            session_id: lease.session_id.clone(),
+            kind,
+            lease_id: Some(lease.id.clone()),
+            resources: vec![lease.resource.clone()],
+            predicate: Some(lease.predicate),
+            outcome: outcome.map(str::to_string),
+        });
+    }
+
+    /// The agent's recorded activity matching `query`, oldest first:
+    /// acquisitions and refusals, releases, heartbeats, evictions and
+    /// intent verdicts.
+    pub fn agent_timeline(&self, agent_id: &str, query: &TimelineQuery) -> Vec<ActivityEntry> {
+        self.activity.timeline(agent_id, query)
+    }
+
+    /// The agent got a Die verdict: it counts against its health and
+    /// extends its aging streak.
+    fn denied(&mut self, agent_id: &str, now: u64) {
+        self.health.record(agent_id, HealthSignal::Die, now);
+        self.refresh_health(agent_id, now);
+        self.aging.record_denial(agent_id, now);
+        self.refresh_aging(agent_id, now);
+    }
+
+    /// The agent was granted a lease, permit or manifest: it has stopped
+    /// starving and returns to its own priority.
+    fn granted(&mut self, agent_id: &str, now: u64) {
+        self.aging.record_grant(agent_id);
+        self.refresh_aging(agent_id, now);
+    }
+
+    /// Bring the aging boost applied to an agent's registered priority in
+    /// line with its current denial streak.
+    fn refresh_aging(&mut self, agent_id: &str, now: u64) {
+        let applied = self.aging.applied(agent_id);
+        let Some(priority) = self.agent_priority(agent_id) else {
+            return;
+        };
+        let base = priority.timestamp.saturating_add(applied);
+        // Never past the oldest possible priority, so the boost can be undone
+        let boost = self.aging.boost(agent_id, now).min(base);
+        if boost == applied {
+            return;
+        }
+        self.aging.set_applied(agent_id, boost);
+        self.registry
+            .register(agent_id.to_string(), priority.with_timestamp(base - boost));
+    }
+
+    /// Run the agent at the lowest ceiling among its active leases on
+    /// critical resources, or at its own priority once it holds none.
+    fn refresh_ceiling(&mut self, agent_id: &str) {
+        let applied = self.ceilings.applied(agent_id);
+        if self.ceilings.ceilings().is_empty() && applied == 0 {
+            return;
+        }
+        let Some(priority) = self.agent_priority(agent_id) else {
+            return;
+        };
+        let ceiling = self
+            .store
+            .get_active_leases()
+            .iter()
+            .filter(|l| l.agent_id == agent_id)
+            .filter_map(|l| self.ceilings.ceiling_for(&l.resource))
+            .min();
+        let base = priority.timestamp.saturating_add(applied);
+        let boost = ceiling.map_or(0, |c| base.saturating_sub(c));
+        self.ceilings.set_applied(agent_id, ceiling, boost);
+        if boost != applied {
+            self.registry
+                .register(agent_id.to_string(), priority.with_timestamp(base - boost));
+        }
+    }
+
+    /// Re-evaluate an agent's health and apply or lift its priority penalty.
+    fn refresh_health(&mut self, agent_id: &str, now: u64) {
+        let was_demoted = self.health.is_demoted(agent_id);
+        self.health.evaluate(agent_id, now);
+        let demoted = self.health.is_demoted(agent_id);
+        if demoted == was_demoted {
+            return;
+        }
+
+        let penalty = self.health.policy().deprioritize_by;
+        if let Some(priority) = self.agent_priority(agent_id) {
+            let adjusted = if demoted {
+                priority.timestamp.saturating_add(penalty)
+            } else {
+                priority.timestamp.saturating_sub(penalty)
+            };
+            self.registry
+                .register(agent_id.to_string(), priority.with_timestamp(adjusted));
+        }
+    }
+
+    /// Start dual-writing to `secondary`: its live state is replaced by a
+    /// copy of the current store's, and every later change is mirrored onto
+    /// it while reads keep coming from the current store.
+    pub fn mirror_to(&mut self, secondary: Box<dyn LeaseStoreExt + Send>) {
+        let primary = std::mem::replace(&mut self.store, Box::new(InMemoryLeaseStore::new()));
+        let mut store = MirroredStore::new(primary, secondary);
+        store.set_conflict_engine(self.engine.clone());
+        store.set_scheduler(self.scheduler.clone());
+        store.set_fair_share(self.fair_share.clone());
+        store.record_events(true);
+        self.registry = store.registry();
+        self.store = Box::new(store);
+        // The primary may not persist intents
+        let written = self
+            .active_intents
+            .entries()
+            .map(|(sequence, intent)| self.store.save_intent(sequence, intent))
+            .fold(Ok(()), Result::and);
+        self.note_storage(written);
+    }
+
+    /// Divergence between the backends while dual-writing, or `None` if
+    /// this client is not mirroring.
+    pub fn mirror_report(&self) -> Option<MirrorReport> {
+        self.store.mirror_report()
+    }
+
+    /// Verify the storage backend can currently serve reads and writes,
+    /// and that the last write of intents to it went through.
+    pub fn storage_ready(&self) -> Result<(), String> {
+        self.store.check_ready()?;
+        match &self.storage_error {
+            Some(e) => Err(format!("Intents not persisted: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Replace the retention rules used by `compact`.
+    pub fn set_compaction_policy(&mut self, policy: CompactionPolicy) {
+        self.compaction = policy;
+    }
+
+    /// Expire intents `ttl_ms` after they are granted unless a heartbeat on
+    /// one of their session's leases renews them, or never with `None` (the
+    /// default). Intents granted earlier keep their expiry.
+    pub fn set_intent_ttl(&mut self, ttl_ms: Option<u64>) {
+        self.intent_ttl_ms = ttl_ms;
+    }
+
+    pub fn intent_ttl(&self) -> Option<u64> {
+        self.intent_ttl_ms
+    }
+
+    /// Cap the leases held and the memory used, shedding acquires and
+    /// reservations near the caps (see `capacity`). No caps by default.
+    pub fn set_capacity_policy(&mut self, policy: CapacityPolicy) {
+        self.shedder.set_policy(policy);
+    }
+
+    pub fn capacity_policy(&self) -> &CapacityPolicy {
+        self.shedder.policy()
+    }
+
+    /// Hold eviction after the clock jumps back by more than the policy's
+    /// tolerance (see `skew`). Disabled by default.
+    pub fn set_skew_policy(&mut self, policy: SkewPolicy) {
+        self.skew.set_policy(policy);
+    }
+
+    pub fn skew_policy(&self) -> &SkewPolicy {
+        self.skew.policy()
+    }
+
+    /// Clock state for health checks; `None` on a wall clock without skew
+    /// detection
+    pub fn clock_report(&self) -> Option<ClockReport> {
+        let wall_offset_ms = self.clock.wall_offset_ms();
+        if wall_offset_ms.is_none() && !self.skew.policy().is_enabled() {
+            return None;
+        }
+        Some(self.skew.report(wall_offset_ms))
+    }
+
+    /// How close the client is to its caps and what it has shed; `None`
+    /// without caps
+    pub fn capacity_report(&self) -> Option<CapacityReport> {
+        if !self.shedder.policy().is_enabled() {
+            return None;
+        }
+        let (leases, memory_bytes) = self.capacity_usage();
+        Some(self.shedder.report(leases, memory_bytes))
+    }
+
+    /// Leases held and, with a memory cap, the estimated memory in use
+    fn capacity_usage(&self) -> (usize, usize) {
+        let usage = self.store.usage();
+        let memory_bytes = match self.shedder.policy().max_memory_bytes {
+            Some(_) => self.memory_report().total_estimated_bytes,
+            None => 0,
+        };
+        (usage.active_leases + usage.pending_leases, memory_bytes)
+    }
+
+    /// Revoke advisory leases, lowest priority first, until fewer than the
+    /// lease cap are held. Returns how many were revoked.
+    fn revoke_advisory_leases(&mut self, held: usize) -> usize {
+        let Some(cap) = self.shedder.policy().max_leases else {
+            return 0;
+        };
+        let priorities = self.registry.priorities();
+        let mut advisory: Vec<Lease> = self
+            .store
+            .get_active_leases()
+            .into_iter()
+            .filter(|lease| is_advisory(lease.predicate))
+            .collect();
+        // The most junior holder's newest lease first
+        advisory.sort_by_key(|lease| {
+            std::cmp::Reverse((
+                priorities
+                    .get(&lease.agent_id)
+                    .copied()
+                    .unwrap_or(Priority::lowest()),
+                lease.acquired_at,
+            ))
+        });
+        let mut revoked = 0;
+        for lease in advisory {
+            if held - revoked < cap {
+                break;
+            }
+            let reason = "Revoked to shed load at the lease cap".to_string();
+            if self.revoke(&lease.id, lease.revision, None, reason).is_ok() {
+                revoked += 1;
+                self.shedder.record_revocation();
+            }
+        }
+        revoked
+    }
+
+    /// The agent's default session, started now if it has none or the last
+    /// one went idle.
+    pub fn default_session(&mut self, agent_id: &str) -> String {
+        self.sessions.resolve(agent_id, self.now())
+    }
+
+    /// End the agent's default session; its next request without a session
+    /// starts a new one. Leases already held in it are kept; requests queued
+    /// in it are cancelled.
+    pub fn end_default_session(&mut self, agent_id: &str) -> bool {
+        let Some(session) = self.sessions.take(agent_id) else {
+            return false;
+        };
+        self.cancel_session_waiters(session);
+        true
+    }
+
+    /// Cancel the requests queued in a default session that ended
+    fn cancel_session_waiters(&mut self, session: DefaultSession) {
+        self.intent_waits
+            .remove(&(session.agent_id.clone(), session.session_id.clone()));
+        self.cancel_pending(&WaiterFilter::Session {
+            agent_id: session.agent_id,
+            session_id: session.session_id,
+        });
+    }
+
+    /// Replace how long default sessions outlive their last use, and ended
+    /// sessions are remembered.
+    pub fn set_session_policy(&mut self, policy: SessionPolicy) {
+        self.sessions.set_policy(policy);
+    }
+
+    /// Begin an explicit session for the agent, expiring after `ttl_ms`
+    /// without use; an empty `session_id` gets a new one. Beginning a
+    /// session the agent already began restarts its TTL.
+    pub fn begin_session(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        ttl_ms: u64,
+    ) -> Result<Session, String> {
+        let now = self.now();
+        self.expire_sessions_at(now);
+        let session_id = if session_id.is_empty() {
+            format!("{}-{}", agent_id, self.next_id())
+        } else {
+            session_id.to_string()
+        };
+        self.sessions.begin(agent_id, &session_id, ttl_ms, now)
+    }
+
+    /// The explicit session `session_id`, if it is live
+    pub fn session(&self, session_id: &str) -> Option<Session> {
+        let now = self.now();
+        self.sessions
+            .session(session_id)
+            .filter(|session| session.is_live(now))
+            .cloned()
+    }
+
+    /// End an explicit session: release its leases, cancel its queued
+    /// requests and withdraw its intents. Later requests in it are refused
+    /// with `SessionExpired`. `None` if no such session is live.
+    pub fn end_session(&mut self, session_id: &str) -> Option<SessionEnd> {
+        let now = self.now();
+        self.expire_sessions_at(now);
+        let session = self.sessions.end_session(session_id, now)?;
+        Some(self.close_session(session, false))
+    }
+
+    /// End every explicit session whose TTL ran out, as `end_session`
+    /// does; acquires, declarations and `compact` do so too.
+    pub fn expire_sessions(&mut self) -> Vec<SessionEnd> {
+        let now = self.now();
+        self.expire_sessions_at(now)
+    }
+
+    fn expire_sessions_at(&mut self, now: u64) -> Vec<SessionEnd> {
+        self.sessions
+            .take_expired_sessions(now)
+            .into_iter()
+            .map(|session| self.close_session(session, true))
+            .collect()
+    }
+
+    /// Release what an explicit session that ended still held
+    fn close_session(&mut self, session: Session, expired: bool) -> SessionEnd {
+        let leases: Vec<Lease> = self
+            .store
+            .get_active_leases()
+            .into_iter()
+            .chain(self.store.get_pending_leases())
+            .filter(|lease| {
+                lease.agent_id == session.agent_id && lease.session_id == session.session_id
+            })
+            .collect();
+        let leases_released = leases
+            .into_iter()
+            .filter(|lease| self.release_lease(&lease.id))
+            .map(|lease| lease.id)
+            .collect();
+        let waiters_cancelled = self
+            .cancel_pending(&WaiterFilter::Session {
+                agent_id: session.agent_id.clone(),
+                session_id: session.session_id.clone(),
+            })
+            .len();
+        let intents_withdrawn = self
+            .withdraw_session_intents(&session.agent_id, &session.session_id)
+            .len();
+        SessionEnd {
+            session,
+            expired,
+            leases_released,
+            waiters_cancelled,
+            intents_withdrawn,
+        }
+    }
+
+    /// Declare a namespace for the agents whose IDs start with `<name>/`,
+    /// reaped `expires_in` ms from now, or only when deleted. Declaring it
+    /// again resets its expiry.
+    pub fn declare_namespace(
+        &mut self,
+        name: &str,
+        expires_in: Option<u64>,
+    ) -> Result<Namespace, String> {
+        let now = self.now();
+        self.namespaces.declare(name, expires_in, now)
+    }
+
+    pub fn namespace(&self, name: &str) -> Option<&Namespace> {
+        self.namespaces.get(name)
+    }
+
+    /// Every declared namespace, by name
+    pub fn namespaces(&self) -> Vec<Namespace> {
+        self.namespaces.list()
+    }
+
+    /// Delete a namespace and reap it at once. `None` if it is not declared.
+    pub fn delete_namespace(&mut self, name: &str) -> Option<NamespaceReaping> {
+        let namespace = self.namespaces.remove(name)?;
+        Some(self.reap_namespace(&namespace, false))
+    }
+
+    /// Reap every namespace whose TTL has run out; `compact` does so too.
+    pub fn reap_expired_namespaces(&mut self) -> Vec<NamespaceReaping> {
+        let now = self.now();
+        self.namespaces
+            .take_expired(now)
+            .iter()
+            .map(|namespace| self.reap_namespace(namespace, true))
+            .collect()
+    }
+
+    /// Release the leases of the namespace's agents, cancel their queued
+    /// requests, withdraw their intents and forget them, then tell their
+    /// sessions
+    fn reap_namespace(&mut self, namespace: &Namespace, expired: bool) -> NamespaceReaping {
+        let now = self.now();
+        let mut agents: BTreeSet<String> = self
+            .registry
+            .priorities()
+            .into_keys()
+            .filter(|agent_id| namespace.contains(agent_id))
+            .collect();
+        let mut sessions: BTreeSet<(String, String)> = BTreeSet::new();
+
+        let leases: Vec<Lease> = self
+            .store
+            .get_active_leases()
+            .into_iter()
+            .chain(self.store.get_pending_leases())
+            .filter(|lease| namespace.contains(&lease.agent_id))
+            .collect();
+        let mut leases_released = Vec::new();
+        for lease in leases {
+            if self.release_lease(&lease.id) {
+                leases_released.push(lease.id);
+            }
+            agents.insert(lease.agent_id.clone());
+            sessions.insert((lease.agent_id, lease.session_id));
+        }
+
+        let waiters: Vec<Waiter> = self
+            .store
+            .waiters()
+            .into_iter()
+            .filter(|waiter| namespace.contains(&waiter.agent_id))
+            .collect();
+        let mut waiters_cancelled = 0;
+        for waiter in waiters {
+            waiters_cancelled += self.cancel_pending(&WaiterFilter::Id(waiter.id)).len();
+            agents.insert(waiter.agent_id);
+        }
+
+        let removed = self.remove_intents(|intent| namespace.contains(&intent.subject));
+        for intent in &removed {
+            agents.insert(intent.subject.clone());
+            sessions.insert((intent.subject.clone(), intent.session_id.clone()));
+        }
+        let intents_removed = removed.len();
+        self.intent_waits
+            .retain(|(agent_id, _), _| !namespace.contains(agent_id));
+
+        for agent_id in &agents {
+            self.registry.unregister(agent_id);
+            self.health.forget(agent_id);
+            self.debounce.forget(agent_id);
+            self.aging.forget(agent_id);
+            self.activity.forget(agent_id);
+            self.sessions.forget(agent_id);
+        }
+        for (agent_id, session_id) in &sessions {
+            self.feeds.push(
+                agent_id,
+                session_id,
+                AgentEvent::NamespaceReaped {
+                    namespace: namespace.name.clone(),
+                    expired,
+                    at: now,
+                },
+            );
+        }
+        self.firehose.record_namespace_reaped(now, &namespace.name);
+
+        NamespaceReaping {
+            namespace: namespace.name.clone(),
+            expired,
+            agents: agents.into_iter().collect(),
+            leases_released,
+            waiters_cancelled,
+            intents_removed,
+        }
+    }
+
+    /// Drop terminal leases, stale and expired intents, idle health
+    /// records, retry records whose backoff has elapsed, expired semaphore
+    /// permits, rename aliases and event feeds of finished sessions, lapsed
+    /// resource quarantines, and idle default sessions (cancelling the
+    /// requests queued in them). Expired namespaces are reaped last, so the events
+    /// telling their sessions survive until the next compaction.
+    pub fn compact(&mut self) -> CompactionReport {
+        let now = self.now();
+        self.evict_and_track(now);
+
+        let expired_sessions = self.sessions.take_expired(now);
+        let sessions_expired = expired_sessions.len() + self.expire_sessions_at(now).len();
+        for session in expired_sessions {
+            self.cancel_session_waiters(session);
+        }
+        self.sessions.purge_ended(now);
+
+        let active = self.store.get_active_leases();
+        let stale_before = now.saturating_sub(self.compaction.stale_intent_ms);
+        let intents_removed = self
+            .remove_intents(|intent| {
+                intent.is_expired(now)
+                    || (intent.timestamp < stale_before
+                        && !active.iter().any(|l| {
+                            l.agent_id == intent.subject && l.session_id == intent.session_id
+                        }))
+            })
+            .len();
+
+        // A wait is over once its holders have let go
+        let intents = &self.active_intents;
+        self.intent_waits.retain(|_, waits| {
+            waits.retain(|edge| holds(intents, &active, edge));
+            !waits.is_empty()
+        });
+
+        // A rename stays aliased while its session holds leases or intents
+        let mut aliases = self.engine.aliases().clone();
+        let aliases_removed = aliases.retain(|alias| {
+            active.iter().any(|l| l.session_id == alias.session_id)
+                || self
+                    .active_intents
+                    .iter()
+                    .any(|i| i.session_id == alias.session_id)
+        });
+        if aliases_removed > 0 {
+            self.set_aliases(aliases);
+        }
+
+        // A feed lives as long as its session holds leases or intents
+        let intents = &self.active_intents;
+        let feeds_removed = self.feeds.retain(|agent_id, session_id| {
+            active
+                .iter()
+                .any(|l| l.agent_id == agent_id && l.session_id == session_id)
+                || intents
+                    .iter()
+                    .any(|i| i.subject == agent_id && i.session_id == session_id)
+        });
+
+        let leases_removed = self
+            .store
+            .compact(now.saturating_sub(self.compaction.terminal_lease_retention_ms));
+        self.drain_events(now);
+        let namespaces_reaped = self.reap_expired_namespaces().len();
+        let agents_unregistered = self.unregister_idle_agents(now);
+
+        CompactionReport {
+            leases_removed,
+            intents_removed,
+            health_records_removed: self.health.compact(now),
+            retry_records_removed: self.debounce.compact(now),
+            permits_reclaimed: self.semaphores.reclaim_expired(now),
+            aliases_removed,
+            quarantines_expired: self.quarantines.purge_expired(now),
+            sessions_expired,
+            feeds_removed,
+            activity_entries_removed: self
+                .activity
+                .compact(now.saturating_sub(self.compaction.activity_retention_ms)),
+            namespaces_reaped,
+            agents_unregistered,
+        }
+    }
+
+    /// Unregister agents that hold no lease, waiter or intent and were last
+    /// seen longer ago than `CompactionPolicy::idle_agent_ms`. Agents
+    /// registered before this client saw them count as seen now.
+    fn unregister_idle_agents(&mut self, now: u64) -> usize {
+        let Some(idle_ms) = self.compaction.idle_agent_ms else {
+            return 0;
+        };
+        let idle_before = now.saturating_sub(idle_ms);
+        let mut busy: BTreeSet<String> = self
+            .store
+            .get_active_leases()
+            .into_iter()
+            .chain(self.store.get_pending_leases())
+            .map(|lease| lease.agent_id)
+            .collect();
+        busy.extend(self.store.waiters().into_iter().map(|w| w.agent_id));
+        busy.extend(self.active_intents.iter().map(|i| i.subject.clone()));
+
+        let mut unregistered = 0;
+        for agent_id in self.registry.priorities().into_keys() {
+            if busy.contains(&agent_id) {
+                continue;
+            }
+            match self.activity.last_seen(&agent_id) {
+                None => self.activity.touch(&agent_id, now),
+                Some(seen) if seen < idle_before && self.registry.unregister(&agent_id) => {
+                    self.health.forget(&agent_id);
+                    self.debounce.forget(&agent_id);
+                    self.aging.forget(&agent_id);
+                    self.activity.forget(&agent_id);
+                    unregistered += 1;
+                }
+                Some(_) => {}
+            }
+        }
+        unregistered
+    }
+
+    /// Entry counts and approximate memory held by each structure.
+    pub fn memory_report(&self) -> MemoryReport {
+        let leases = self.store.usage();
+        let intents = StructureUsage {
+            count: self.active_intents.len(),
+            estimated_bytes: self
+                .active_intents
+                .iter()
+                .map(SPOTriple::estimated_bytes)
+                .sum(),
+        };
+        let priorities = self.registry.priorities();
+        let agents = StructureUsage {
+            count: priorities.len(),
+            estimated_bytes: priorities
+                .keys()
+                .map(|id| id.capacity() + std::mem::size_of::<(String, Priority)>())
+                .sum(),
+        };
+        let health_records = StructureUsage {
+            count: self.health.len(),
+            estimated_bytes: self.health.estimated_bytes(),
+        };
+        let retry_records = StructureUsage {
+            count: self.debounce.len(),
+            estimated_bytes: self.debounce.estimated_bytes(),
+        };
+        let barriers = StructureUsage {
+            count: self.barriers.len(),
+            estimated_bytes: self.barriers.estimated_bytes(),
+        };
+        let semaphores = StructureUsage {
+            count: self.semaphores.len(),
+            estimated_bytes: self.semaphores.estimated_bytes(),
+        };
+        let aliases = StructureUsage {
+            count: self.engine.aliases().len(),
+            estimated_bytes: self.engine.aliases().estimated_bytes(),
+        };
+        let quarantines = StructureUsage {
+            count: self.quarantines.len(),
+            estimated_bytes: self.quarantines.estimated_bytes(),
+        };
+        let sessions = StructureUsage {
+            count: self.sessions.len(),
+            estimated_bytes: self.sessions.estimated_bytes(),
+        };
+        let feeds = StructureUsage {
+            count: self.feeds.len(),
+            estimated_bytes: self.feeds.estimated_bytes(),
+        };
+        let templates = StructureUsage {
+            count: self.templates.len(),
+            estimated_bytes: self.templates.estimated_bytes(),
+        };
+        let activity = StructureUsage {
+            count: self.activity.len(),
+            estimated_bytes: self.activity.estimated_bytes(),
+        };
+        let firehose = StructureUsage {
+            count: self.firehose.len(),
+            estimated_bytes: self.firehose.estimated_bytes(),
+        };
+
+        MemoryReport {
+            total_estimated_bytes: leases.estimated_bytes
+                + intents.estimated_bytes
+                + agents.estimated_bytes
+                + health_records.estimated_bytes
+                + retry_records.estimated_bytes
+                + barriers.estimated_bytes
+                + semaphores.estimated_bytes
+                + aliases.estimated_bytes
+                + quarantines.estimated_bytes
+                + sessions.estimated_bytes
+                + feeds.estimated_bytes
+                + templates.estimated_bytes
+                + activity.estimated_bytes
+                + firehose.estimated_bytes,
+            leases,
+            intents,
+            agents,
+            health_records,
+            retry_records,
+            barriers,
+            semaphores,
+            aliases,
+            quarantines,
+            sessions,
+            feeds,
+            templates,
+            activity,
+            firehose,
+        }
+    }
+
+    /// Create a barrier that trips once `parties` distinct agents arrive.
+    pub fn create_barrier(&mut self, name: &str, parties: usize) -> Result<BarrierStatus, String> {
+        self.barriers.create(name, parties, self.now())
+    }
+
+    /// Mark `agent_id` as having reached the barrier. The returned status
+    /// reports `tripped` once every party has arrived.
+    pub fn arrive(&mut self, name: &str, agent_id: &str) -> Result<BarrierStatus, String> {
+        self.barriers.arrive(name, agent_id, self.now())
+    }
+
+    pub fn barrier_status(&self, name: &str) -> Option<BarrierStatus> {
+        self.barriers.status(name)
+    }
+
+    /// Delete a barrier so its name can be reused.
+    pub fn remove_barrier(&mut self, name: &str) -> bool {
+        self.barriers.remove(name)
+    }
+
+    /// Take one permit of the semaphore `name`, declaring it with `permits`
+    /// total permits on first use. When every permit is held, Wait-Die
+    /// decides whether the agent should wait or abort.
+    pub fn acquire_semaphore(
+        &mut self,
+        agent_id: &str,
+        name: &str,
+        permits: usize,
+        ttl: u64,
+    ) -> Result<SemaphoreResult, String> {
+        let now = self.now();
+        self.enroll(agent_id, now);
+        if self.rejects(agent_id) {
+            return Ok(SemaphoreResult::Failure {
+                reason: LeaseFailureReason::UnregisteredAgent,
+                wait_time: None,
+            });
+        }
+        if let Some(until) = self.health.quarantined_until(agent_id, now) {
+            return Ok(SemaphoreResult::Failure {
+                reason: LeaseFailureReason::AgentQuarantined,
+                wait_time: Some(until - now),
+            });
+        }
+
+        let result = self.semaphores.acquire(
+            name,
+            agent_id,
+            permits,
+            ttl,
+            &self.registry.priorities(),
+            now,
+        )?;
+        match &result {
+            SemaphoreResult::Success { .. } => self.granted(agent_id, now),
+            SemaphoreResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            } => self.denied(agent_id, now),
+            SemaphoreResult::Failure { .. } => {}
+        }
+        Ok(result)
+    }
+
+    /// Return a semaphore permit before its TTL elapses.
+    pub fn release_semaphore(&mut self, permit_id: &str) -> bool {
+        self.semaphores.release(permit_id)
+    }
+
+    pub fn semaphore_status(&self, name: &str) -> Option<SemaphoreStatus> {
+        self.semaphores.status(name, self.now())
+    }
+
+    /// Freeze `resource` (a path, directory or glob pattern): until the
+    /// quarantine is lifted or `ttl` milliseconds pass, acquires,
+    /// activations and intents touching it are refused with `Quarantined`.
+    /// Leases already held are unaffected.
+    pub fn quarantine_resource(
+        &mut self,
+        resource: ResourceRef,
+        reason: Option<String>,
+        ttl: Option<u64>,
+    ) -> Result<ResourceQuarantine, String> {
+        if ttl == Some(0) {
+            return Err("ttl must be greater than 0".to_string());
+        }
+        let now = self.now();
+        let resource = resource.normalized(&self.paths);
+        self.quarantines
+            .add(resource, reason, ttl.map(|ttl| now + ttl), now)
+    }
+
+    /// Lift the quarantine on exactly `resource`.
+    pub fn lift_quarantine(&mut self, resource: &ResourceRef) -> bool {
+        self.quarantines.remove(&resource.normalized(&self.paths))
+    }
+
+    /// Quarantines currently in force.
+    pub fn quarantines(&self) -> Vec<ResourceQuarantine> {
+        self.quarantines.list(self.now())
+    }
+
+    /// Replace the maintenance windows. While a window is open, acquires,
+    /// activations and intents overlapping its resource are refused with
+    /// `MaintenanceWindow` unless the agent is one of its allowed agents.
+    /// Leases already held are unaffected.
+    pub fn set_maintenance_windows(
+        &mut self,
+        windows: Vec<MaintenanceWindow>,
+    ) -> Result<(), String> {
+        self.maintenance = MaintenanceSchedule::with_windows(windows)?;
+        Ok(())
+    }
+
+    pub fn maintenance_windows(&self) -> &[MaintenanceWindow] {
+        self.maintenance.windows()
+    }
+
+    /// The current or next opening of each maintenance window, soonest first
+    pub fn upcoming_maintenance(&self) -> Vec<ScheduledWindow> {
+        self.maintenance.upcoming(self.now())
+    }
+
+    /// Register a manifest template, replacing any with the same name.
+    pub fn register_template(&mut self, template: ManifestTemplate) -> Result<(), String> {
+        self.templates.insert(template)
+    }
+
+    /// Remove the template called `name`.
+    pub fn remove_template(&mut self, name: &str) -> bool {
+        self.templates.remove(name)
+    }
+
+    /// The template called `name`, if registered.
+    pub fn template(&self, name: &str) -> Option<&ManifestTemplate> {
+        self.templates.get(name)
+    }
+
+    /// Registered templates, by name.
+    pub fn templates(&self) -> Vec<ManifestTemplate> {
+        self.templates.list()
+    }
+
+    /// Expand the registered template `name` with `variables` and declare
+    /// the resulting manifest. Fails if the template is unknown or a
+    /// variable it uses is missing.
+    pub fn declare_intent_from_template(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        name: &str,
+        variables: &BTreeMap<String, String>,
+    ) -> Result<KernelVerdict, String> {
+        let template = self
+            .templates
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Template '{}' not found", name))?;
+        self.declare_intent_with_template(agent_id, session_id, &template, variables)
+    }
+
+    /// Expand `template`, which need not be registered, with `variables`
+    /// and declare the resulting manifest.
+    pub fn declare_intent_with_template(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        template: &ManifestTemplate,
+        variables: &BTreeMap<String, String>,
+    ) -> Result<KernelVerdict, String> {
+        let manifest = template.expand(agent_id, session_id, variables, self.now(), || {
+            self.next_id()
+        })?;
+        Ok(self.declare_intent(&manifest))
+    }
+
+    /// Replace the groups of interchangeable resources that Die verdicts
+    /// suggest alternatives from (none by default; see `fallback`).
+    pub fn set_fallback_groups(&mut self, groups: FallbackGroups) {
+        self.fallback_groups = groups;
+    }
+
+    pub fn fallback_groups(&self) -> &FallbackGroups {
+        &self.fallback_groups
+    }
+
+    /// The members of `resource`'s fallback groups that the agent, in
+    /// `session_id` (its default session if empty), could take for
+    /// `predicate` now: not quarantined, and in conflict with no lease and
+    /// no other session's intent. Groups with no such member are left out.
+    pub fn fallbacks(
+        &self,
+        agent_id: &str,
+        session_id: &str,
+        resource: &ResourceRef,
+        predicate: Predicate,
+    ) -> Vec<Fallback> {
+        let now = self.now();
+        let session_id = match session_id {
+            "" => self
+                .sessions
+                .get(agent_id, now)
+                .map_or("", |session| session.session_id.as_str()),
+            session_id => session_id,
+        };
+        let resource = &resource.normalized(self.path_normalization(agent_id));
+        self.fallback_groups
+            .candidates(resource)
+            .into_iter()
+            .filter_map(|(group, members)| {
+                let alternatives: Vec<ResourceRef> = members
+                    .into_iter()
+                    .filter(|member| self.is_free_for(agent_id, session_id, member, predicate, now))
+                    .cloned()
+                    .collect();
+                (!alternatives.is_empty()).then(|| Fallback {
+                    resource: resource.clone(),
+                    group: group.to_string(),
+                    alternatives,
+                })
+            })
+            .collect()
+    }
+
+    /// Whether nothing would stand in the way of `predicate` on `resource`
+    fn is_free_for(
+        &self,
+        agent_id: &str,
+        session_id: &str,
+        resource: &ResourceRef,
+        predicate: Predicate,
+        now: u64,
+    ) -> bool {
+        let probe = SPOTriple {
+            id: String::new(),
+            subject: agent_id.to_string(),
+            predicate,
+            object: resource.clone(),
+            timestamp: now,
+            confidence: Confidence::High,
+            session_id: session_id.to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        };
+        let nearby: Vec<SPOTriple> = self
+            .active_intents
+            .candidates(&self.engine.footprint_resources(predicate, resource))
+            .into_iter()
+            .cloned()
+            .collect();
+        self.quarantined(resource, now).is_none()
+            && self
+                .lease_blockers(agent_id, session_id, resource, predicate)
+                .is_empty()
+            && self.engine.check_intent_all(&probe, &nearby).is_empty()
+    }
+
+    /// Suggest fallbacks for the refused resources of a manifest that died
+    fn attach_fallbacks(&self, manifest: &IntentManifest, verdict: &mut KernelVerdict) {
+        if verdict.status != KernelVerdictStatus::Die || self.fallback_groups.is_empty() {
+            return;
+        }
+        for intent in &manifest.intents {
+            if !verdict
+                .blockers
+                .iter()
+                .any(|blocker| blocker.resource.overlaps(&intent.object))
+            {
+                continue;
+            }
+            for fallback in self.fallbacks(
+                &verdict.agent_id,
+                &verdict.session_id,
+                &intent.object,
+                intent.predicate,
+            ) {
+                if !verdict.fallbacks.contains(&fallback) {
+                    verdict.fallbacks.push(fallback);
+                }
+            }
+        }
+    }
+
+    /// Generate a unique ID for intents/triples.
+    pub fn next_id(&mut self) -> String {
+        self.id_counter += 1;
+        self.ids.id(self.id_counter)
+    }
+
+    /// The current time according to the client's clock.
+    pub fn now(&self) -> u64 {
+        self.clock.now_ms()
+    }
+}
+
+/// Whether the holder of a wait-for edge still holds an intent or an
+/// active lease on the resource in the way
+fn holds(intents: &IntentSet, leases: &[Lease], edge: &WaitForEdge) -> bool {
+    intents
+        .iter()
+        .any(|i| i.subject == edge.holder && i.object == edge.resource)
+        || leases
+            .iter()
+            .any(|l| l.agent_id == edge.holder && l.resource == edge.resource)
+}
+
+impl Default for KlockClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ─── Parsing Helpers ────────────────────────────────────────────────────────
+
+pub fn parse_predicate(s: &str) -> Predicate {
+    match s.to_uppercase().as_str() {
+        "PROVIDES" => Predicate::Provides,
+        "CONSUMES" => Predicate::Consumes,
+        "MUTATES" => Predicate::Mutates,
+        "DELETES" => Predicate::Deletes,
+        "DEPENDS_ON" => Predicate::DependsOn,
+        "RENAMES" => Predicate::Renames,
+        "EXCLUDES" => Predicate::Excludes,
+        _ => Predicate::Consumes, // Safe default
+    }
+}
+
+/// Parse `HIGH`, `MEDIUM` or `LOW` (any case)
+pub fn parse_confidence(s: &str) -> Option<Confidence> {
+    match s.to_uppercase().as_str() {
+        "HIGH" => Some(Confidence::High),
+        "MEDIUM" => Some(Confidence::Medium),
+        "LOW" => Some(Confidence::Low),
+        _ => None,
     }
 }
 
@@ -0,0 +1,129 @@
+//! Time and ID sources.
+//!
+//! A `KlockClient` reads the wall clock for lease timestamps and expiry and
+//! numbers intents sequentially. Golden-file tests and documentation
+//! examples need byte-identical output across runs, so both sources are
+//! pluggable: a `ManualClock` only moves when told to, and a seeded
+//! `IdSource` yields the same opaque IDs for the same seed. A
+//! `MonotonicClock` keeps lease expiry steady when the wall clock jumps.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Source of the current time, in ms since the epoch
+pub trait Clock: Send + Sync {
+    fn now_ms(&self) -> u64;
+
+    /// For clocks that do not follow the wall clock, how far the wall clock
+    /// is ahead of them in ms; `None` for wall clocks
+    fn wall_offset_ms(&self) -> Option<i64> {
+        None
+    }
+}
+
+/// The system wall clock (the default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+}
+
+/// Wall time at creation, advanced by a monotonic clock: NTP steps and VM
+/// pauses that move the wall clock do not move it, so they neither expire
+/// leases early nor keep them alive. `wall_offset_ms` maps its times to
+/// wall time for display.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    start_ms: u64,
+    start: Instant,
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        Self {
+            start_ms: SystemClock.now_ms(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now_ms(&self) -> u64 {
+        self.start_ms + self.start.elapsed().as_millis() as u64
+    }
+
+    fn wall_offset_ms(&self) -> Option<i64> {
+        Some(SystemClock.now_ms() as i64 - self.now_ms() as i64)
+    }
+}
+
+/// A clock that stands still until set or advanced. Share it through an
+/// `Arc` to move time for a client from the outside.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new(start_ms: u64) -> Self {
+        Self {
+            now: AtomicU64::new(start_ms),
+        }
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now.store(now_ms, Ordering::SeqCst);
+    }
+
+    /// Move the clock forward by `ms`, returning the new time.
+    pub fn advance(&self, ms: u64) -> u64 {
+        self.now.fetch_add(ms, Ordering::SeqCst) + ms
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+/// How `KlockClient::next_id` numbers intents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdSource {
+    /// `klock_1`, `klock_2`, ... (the default)
+    #[default]
+    Sequential,
+    /// Opaque `klock_<16 hex digits>` IDs drawn from a SplitMix64 stream.
+    /// Equal seeds give equal streams on every platform.
+    Seeded(u64),
+}
+
+impl IdSource {
+    /// The ID for the `n`th call (from 1)
+    pub fn id(&self, n: u64) -> String {
+        match self {
+            IdSource::Sequential => format!("klock_{}", n),
+            IdSource::Seeded(seed) => format!("klock_{:016x}", splitmix64(*seed, n)),
+        }
+    }
+}
+
+/// The `n`th output of SplitMix64 started at `seed`
+pub(crate) fn splitmix64(seed: u64, n: u64) -> u64 {
+    let mut z = seed.wrapping_add(n.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
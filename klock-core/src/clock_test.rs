@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::{Clock, IdSource, ManualClock};
+    use crate::state::IntentManifest;
+    use crate::types::{Confidence, LeaseResult, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::sync::Arc;
+
+    /// Drive a client through a fixed script and record everything it says
+    fn transcript(seed: u64) -> String {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut client = KlockClient::deterministic(seed, clock.clone());
+        let mut out = Vec::new();
+        for (agent, priority) in [("carol", 300), ("alice", 100), ("bob", 200)] {
+            client.register_agent(agent, priority);
+        }
+        for (agent, path) in [("carol", "/c.ts"), ("alice", "/a.ts"), ("bob", "/b.ts")] {
+            let LeaseResult::Success { lease } =
+                client.acquire_lease(agent, "s", "FILE", path, "MUTATES", 5_000)
+            else {
+                panic!("Expected Success");
+            };
+            out.push(serde_json::to_string(&lease));
+            clock.advance(10);
+        }
+
+        for agent in ["bob", "alice"] {
+            let id = client.next_id();
+            let manifest = IntentManifest {
+                session_id: "s".to_string(),
+                agent_id: agent.to_string(),
+                intents: vec![SPOTriple {
+                    id,
+                    subject: agent.to_string(),
+                    predicate: Predicate::Mutates,
+                    object: ResourceRef::new(ResourceType::File, "/"),
+                    timestamp: client.now(),
+                    confidence: Confidence::High,
+                    session_id: "s".to_string(),
+                    renamed_to: None,
+                    expires_at: None,
+                    long_lived: false,
+                }],
+            };
+            out.push(serde_json::to_string(&client.declare_intent(&manifest)));
+        }
+
+        out.push(serde_json::to_string(&client.get_active_leases()));
+        clock.advance(5_000);
+        client.evict_expired();
+        out.push(serde_json::to_string(&client.get_active_leases()));
+        out.into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_deterministic_clients_produce_identical_output() {
+        let first = transcript(42);
+        assert_eq!(first, transcript(42));
+
+        // Leases list oldest first, stamped by the manual clock
+        assert!(first.contains(r#""id":"lease_carol_1000""#));
+        let leases = first.lines().nth(5).unwrap();
+        let carol = leases.find("lease_carol_1000").unwrap();
+        let alice = leases.find("lease_alice_1010").unwrap();
+        let bob = leases.find("lease_bob_1020").unwrap();
+        assert!(carol < alice && alice < bob);
+        // All three expire once the clock passes their TTL
+        assert_eq!(first.lines().last(), Some("[]"));
+    }
+
+    #[test]
+    fn test_manual_clock_and_id_sources() {
+        let clock = ManualClock::new(5);
+        assert_eq!(clock.advance(10), 15);
+        clock.set(100);
+        assert_eq!(clock.now_ms(), 100);
+
+        assert_eq!(IdSource::Sequential.id(3), "klock_3");
+        let seeded = IdSource::Seeded(7);
+        assert_eq!(seeded.id(1), IdSource::Seeded(7).id(1));
+        assert_ne!(seeded.id(1), seeded.id(2));
+        assert_ne!(seeded.id(1), IdSource::Seeded(8).id(1));
+        assert_eq!(seeded.id(1).len(), "klock_".len() + 16);
+
+        let mut client = KlockClient::new();
+        assert_eq!(client.next_id(), "klock_1");
+    }
+}
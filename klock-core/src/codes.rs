@@ -0,0 +1,373 @@
+//! Stable reason codes for refusals and errors.
+//!
+//! Every refused request and every error carries a code such as
+//! `K1001` alongside its human-readable message, so SDKs and dashboards can
+//! branch on the code instead of parsing text. Codes are grouped by the
+//! first digit:
+//!
+//! - `K1xxx`: conflicts resolved by the scheduler
+//! - `K2xxx`: admission policy (quarantines, retry limits, maintenance windows)
+//! - `K3xxx`: leases, reservations and sessions that are gone
+//! - `K4xxx`: malformed requests and unknown names
+//! - `K5xxx`: the server or its storage
+//!
+//! A code, once published, keeps its meaning; new outcomes get new codes.
+
+use crate::types::LeaseFailureReason;
+use serde::{Deserialize, Serialize};
+
+/// A code from the catalog. Serializes as the bare code (`"K1001"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ReasonCode {
+    #[serde(rename = "K1001")]
+    WaitSenior,
+    #[serde(rename = "K1002")]
+    DieJunior,
+    #[serde(rename = "K1003")]
+    Conflict,
+    #[serde(rename = "K1004")]
+    ResourceLocked,
+    #[serde(rename = "K1005")]
+    StateChanged,
+    #[serde(rename = "K1006")]
+    DependencyCycle,
+    #[serde(rename = "K1007")]
+    SiblingRefused,
+    #[serde(rename = "K1008")]
+    Deadlock,
+    #[serde(rename = "K2001")]
+    AgentQuarantined,
+    #[serde(rename = "K2002")]
+    ResourceQuarantined,
+    #[serde(rename = "K2003")]
+    TooManyRetries,
+    #[serde(rename = "K2004")]
+    MaintenanceWindow,
+    #[serde(rename = "K2005")]
+    PreemptionRefused,
+    #[serde(rename = "K2006")]
+    PolicyDenied,
+    #[serde(rename = "K2007")]
+    UnregisteredAgent,
+    #[serde(rename = "K2008")]
+    CapacityExceeded,
+    #[serde(rename = "K2009")]
+    CosignRequired,
+    #[serde(rename = "K2010")]
+    CosignRefused,
+    #[serde(rename = "K3001")]
+    SessionExpired,
+    #[serde(rename = "K3002")]
+    ReservationExpired,
+    #[serde(rename = "K3003")]
+    LeaseNotFound,
+    #[serde(rename = "K3004")]
+    RevisionMismatch,
+    #[serde(rename = "K3005")]
+    PostconditionFailed,
+    #[serde(rename = "K4001")]
+    InvalidRequest,
+    #[serde(rename = "K4002")]
+    NotFound,
+    #[serde(rename = "K4003")]
+    AlreadyExists,
+    #[serde(rename = "K5001")]
+    StorageUnavailable,
+    #[serde(rename = "K5002")]
+    Unauthorized,
+}
+
+/// One entry of the catalog, as served to SDKs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CodeInfo {
+    pub code: &'static str,
+    pub name: &'static str,
+    /// What happened
+    pub summary: &'static str,
+    /// What the caller should do about it
+    pub remedy: &'static str,
+}
+
+impl ReasonCode {
+    pub const ALL: [ReasonCode; 28] = [
+        ReasonCode::WaitSenior,
+        ReasonCode::DieJunior,
+        ReasonCode::Conflict,
+        ReasonCode::ResourceLocked,
+        ReasonCode::StateChanged,
+        ReasonCode::DependencyCycle,
+        ReasonCode::SiblingRefused,
+        ReasonCode::Deadlock,
+        ReasonCode::AgentQuarantined,
+        ReasonCode::ResourceQuarantined,
+        ReasonCode::TooManyRetries,
+        ReasonCode::MaintenanceWindow,
+        ReasonCode::PreemptionRefused,
+        ReasonCode::PolicyDenied,
+        ReasonCode::UnregisteredAgent,
+        ReasonCode::CapacityExceeded,
+        ReasonCode::CosignRequired,
+        ReasonCode::CosignRefused,
+        ReasonCode::SessionExpired,
+        ReasonCode::ReservationExpired,
+        ReasonCode::LeaseNotFound,
+        ReasonCode::RevisionMismatch,
+        ReasonCode::PostconditionFailed,
+        ReasonCode::InvalidRequest,
+        ReasonCode::NotFound,
+        ReasonCode::AlreadyExists,
+        ReasonCode::StorageUnavailable,
+        ReasonCode::Unauthorized,
+    ];
+
+    /// The stable code, e.g. `K1001`
+    pub fn code(&self) -> &'static str {
+        self.info().code
+    }
+
+    /// The symbolic name, e.g. `WAIT_SENIOR`
+    pub fn name(&self) -> &'static str {
+        self.info().name
+    }
+
+    pub fn info(&self) -> CodeInfo {
+        let (code, name, summary, remedy) = match self {
+            ReasonCode::WaitSenior => (
+                "K1001",
+                "WAIT_SENIOR",
+                "A conflicting holder is in the way, and the requester outranks it.",
+                "Wait for the holder to finish, then retry; the request is not lost.",
+            ),
+            ReasonCode::DieJunior => (
+                "K1002",
+                "DIE_JUNIOR",
+                "A conflicting holder outranks the requester, which must abort.",
+                "Drop the work in progress, keep the agent's priority, and retry after \
+                 retry_after_ms (or wait_time). If every blocker's relation is \
+                 \"descendant\", only parts of the requested resource are taken: request \
+                 the rest of it instead.",
+            ),
+            ReasonCode::Conflict => (
+                "K1003",
+                "CONFLICT",
+                "Another agent holds a conflicting lease.",
+                "Retry once the holder releases it, or work on something else.",
+            ),
+            ReasonCode::ResourceLocked => (
+                "K1004",
+                "RESOURCE_LOCKED",
+                "The resource is locked for another operation.",
+                "Retry after the lock is released.",
+            ),
+            ReasonCode::StateChanged => (
+                "K1005",
+                "STATE_CHANGED",
+                "Leases on the requested resources kept changing while the request was \
+                 evaluated.",
+                "Retry; the resources are contended, so back off briefly first.",
+            ),
+            ReasonCode::DependencyCycle => (
+                "K1006",
+                "DEPENDENCY_CYCLE",
+                "The manifest's DEPENDS_ON or PROVIDES intents would close a cycle of agents \
+                 each waiting on the next.",
+                "Break the cycle shown in conflicts: drop a dependency, or provide the \
+                 resource yourself. Retrying unchanged is refused again.",
+            ),
+            ReasonCode::SiblingRefused => (
+                "K1007",
+                "SIBLING_REFUSED",
+                "Another manifest declared in the same atomic declaration was refused, so \
+                 none of them were registered.",
+                "Deal with the refused manifest named in reason, then declare the whole \
+                 group again; this manifest was not at fault.",
+            ),
+            ReasonCode::Deadlock => (
+                "K1008",
+                "DEADLOCK",
+                "Waiting would close a cycle of agents each waiting on the next, across \
+                 several resources, so none of them could ever proceed.",
+                "Release what the agent holds, or drop the intents named in conflicts, \
+                 and retry after retry_after_ms; the cycle is shown in conflicts.",
+            ),
+            ReasonCode::AgentQuarantined => (
+                "K2001",
+                "AGENT_QUARANTINED",
+                "The agent is quarantined after repeated failures (expired leases, \
+                 aborts).",
+                "Wait out the quarantine (retry_after_ms), and check the agent's health \
+                 with GET /agents/{id}.",
+            ),
+            ReasonCode::ResourceQuarantined => (
+                "K2002",
+                "RESOURCE_QUARANTINED",
+                "An operator froze the resource.",
+                "Wait until the quarantine lapses or an operator lifts it.",
+            ),
+            ReasonCode::TooManyRetries => (
+                "K2003",
+                "TOO_MANY_RETRIES",
+                "The agent retried the same resource before its minimum interval elapsed.",
+                "Back off for wait_time before retrying.",
+            ),
+            ReasonCode::MaintenanceWindow => (
+                "K2004",
+                "MAINTENANCE_WINDOW",
+                "A scheduled maintenance window reserves the resource for other agents.",
+                "Retry once the window closes (retry_after_ms or wait_time); upcoming \
+                 windows are listed by GET /capabilities.",
+            ),
+            ReasonCode::PreemptionRefused => (
+                "K2005",
+                "PREEMPTION_REFUSED",
+                "The preemption policy does not let the agent revoke that lease: preemption \
+                 is disabled, or the agent is not senior enough to the holder.",
+                "Acquire the resource normally and wait for the holder instead.",
+            ),
+            ReasonCode::PolicyDenied => (
+                "K2006",
+                "POLICY_DENIED",
+                "The server's policy script refused the request.",
+                "Do not retry as is; the reason says which rule refused it.",
+            ),
+            ReasonCode::UnregisteredAgent => (
+                "K2007",
+                "UNREGISTERED_AGENT",
+                "The agent has no registered priority, because it never registered or was \
+                 unregistered after going idle, and the server rejects unregistered agents.",
+                "Register the agent (POST /agents), then request again.",
+            ),
+            ReasonCode::CapacityExceeded => (
+                "K2008",
+                "CAPACITY_EXCEEDED",
+                "The server is near its lease or memory cap and sheds requests from \
+                 lower-priority agents first.",
+                "Back off and retry later; release leases that are no longer needed.",
+            ),
+            ReasonCode::CosignRequired => (
+                "K2009",
+                "COSIGN_REQUIRED",
+                "The operation is protected by a co-signing rule; a pending lease was created \
+                 and awaits another agent's approval.",
+                "Ask one of the rule's co-signers to co-sign the lease, then activate it \
+                 before the request expires.",
+            ),
+            ReasonCode::CosignRefused => (
+                "K2010",
+                "COSIGN_REFUSED",
+                "The agent may not co-sign the request: it made the request, or is not one \
+                 of the rule's co-signers.",
+                "Have one of the co-signers listed in the request co-sign it.",
+            ),
+            ReasonCode::SessionExpired => (
+                "K3001",
+                "SESSION_EXPIRED",
+                "The session has expired.",
+                "Start a new session and acquire again.",
+            ),
+            ReasonCode::ReservationExpired => (
+                "K3002",
+                "RESERVATION_EXPIRED",
+                "The reservation is unknown, already activated, or past its deadline.",
+                "Reserve again, or acquire the lease directly.",
+            ),
+            ReasonCode::LeaseNotFound => (
+                "K3003",
+                "LEASE_NOT_FOUND",
+                "The lease is unknown, released, or expired.",
+                "Acquire a new lease; work done under the old one is unprotected.",
+            ),
+            ReasonCode::RevisionMismatch => (
+                "K3004",
+                "REVISION_MISMATCH",
+                "The lease changed since the caller read its revision.",
+                "Read the lease again (the current revision is in the response) and decide \
+                 whether the change still applies.",
+            ),
+            ReasonCode::PostconditionFailed => (
+                "K3005",
+                "POSTCONDITION_FAILED",
+                "A post-condition of a checked release failed, so none of its leases were released.",
+                "The leases are still held: check the work the failed condition protects, then \
+                 release again.",
+            ),
+            ReasonCode::InvalidRequest => (
+                "K4001",
+                "INVALID_REQUEST",
+                "The request is malformed or has invalid fields.",
+                "Fix the request as described in the error message.",
+            ),
+            ReasonCode::NotFound => (
+                "K4002",
+                "NOT_FOUND",
+                "The named agent, barrier, semaphore or quarantine does not exist.",
+                "Check the name, or create it first.",
+            ),
+            ReasonCode::AlreadyExists => (
+                "K4003",
+                "ALREADY_EXISTS",
+                "A barrier with that name already exists.",
+                "Pick another name, or remove the old barrier once it is done.",
+            ),
+            ReasonCode::StorageUnavailable => (
+                "K5001",
+                "STORAGE_UNAVAILABLE",
+                "The storage backend cannot currently serve reads and writes.",
+                "Retry later; operators should check the storage backend.",
+            ),
+            ReasonCode::Unauthorized => (
+                "K5002",
+                "UNAUTHORIZED",
+                "The request lacks a valid API key.",
+                "Send the server's key as a Bearer token.",
+            ),
+        };
+        CodeInfo {
+            code,
+            name,
+            summary,
+            remedy,
+        }
+    }
+
+    /// Look a code up by code (`K1001`) or name (`WAIT_SENIOR`), ignoring
+    /// case
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_uppercase();
+        Self::ALL
+            .into_iter()
+            .find(|code| code.code() == s || code.name() == s)
+    }
+}
+
+impl std::fmt::Display for ReasonCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.code(), self.name())
+    }
+}
+
+impl From<LeaseFailureReason> for ReasonCode {
+    fn from(reason: LeaseFailureReason) -> Self {
+        match reason {
+            LeaseFailureReason::Conflict => ReasonCode::Conflict,
+            LeaseFailureReason::Wait => ReasonCode::WaitSenior,
+            LeaseFailureReason::Die => ReasonCode::DieJunior,
+            LeaseFailureReason::ResourceLocked => ReasonCode::ResourceLocked,
+            LeaseFailureReason::SessionExpired => ReasonCode::SessionExpired,
+            LeaseFailureReason::ReservationExpired => ReasonCode::ReservationExpired,
+            LeaseFailureReason::AgentQuarantined => ReasonCode::AgentQuarantined,
+            LeaseFailureReason::TooManyRetries => ReasonCode::TooManyRetries,
+            LeaseFailureReason::Quarantined => ReasonCode::ResourceQuarantined,
+            LeaseFailureReason::MaintenanceWindow => ReasonCode::MaintenanceWindow,
+            LeaseFailureReason::PolicyDenied => ReasonCode::PolicyDenied,
+            LeaseFailureReason::UnregisteredAgent => ReasonCode::UnregisteredAgent,
+            LeaseFailureReason::CapacityExceeded => ReasonCode::CapacityExceeded,
+            LeaseFailureReason::CosignRequired => ReasonCode::CosignRequired,
+        }
+    }
+}
+
+/// The whole catalog, in code order
+pub fn codes() -> Vec<CodeInfo> {
+    ReasonCode::ALL.iter().map(ReasonCode::info).collect()
+}
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::codes::{ReasonCode, codes};
+    use crate::types::{LeaseFailureReason, LeaseResult};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_catalog_is_unique_and_parses() {
+        let catalog = codes();
+        assert_eq!(catalog.len(), ReasonCode::ALL.len());
+        assert_eq!(
+            catalog.iter().map(|c| c.code).collect::<HashSet<_>>().len(),
+            catalog.len()
+        );
+        assert_eq!(
+            catalog.iter().map(|c| c.name).collect::<HashSet<_>>().len(),
+            catalog.len()
+        );
+
+        for code in ReasonCode::ALL {
+            assert_eq!(ReasonCode::parse(code.code()), Some(code));
+            assert_eq!(ReasonCode::parse(code.name()), Some(code));
+            // The wire form is the bare code
+            assert_eq!(
+                serde_json::to_string(&code).unwrap(),
+                format!("\"{}\"", code.code())
+            );
+        }
+        assert_eq!(ReasonCode::parse(" k1001 "), Some(ReasonCode::WaitSenior));
+        assert_eq!(ReasonCode::parse("die_junior"), Some(ReasonCode::DieJunior));
+        assert_eq!(ReasonCode::parse("K9999"), None);
+        assert_eq!(ReasonCode::WaitSenior.to_string(), "K1001 WAIT_SENIOR");
+    }
+
+    #[test]
+    fn test_refusals_carry_codes() {
+        assert_eq!(LeaseFailureReason::Die.code(), ReasonCode::DieJunior);
+        assert_eq!(
+            LeaseFailureReason::Quarantined.code(),
+            ReasonCode::ResourceQuarantined
+        );
+
+        let mut client = KlockClient::new();
+        client.register_agent("older", 100);
+        client.register_agent("younger", 200);
+        assert!(matches!(
+            client.acquire_lease("older", "s1", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        let LeaseResult::Failure { reason, .. } =
+            client.acquire_lease("younger", "s2", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected refusal");
+        };
+        assert_eq!(reason.code(), ReasonCode::DieJunior);
+    }
+}
@@ -0,0 +1,115 @@
+//! Compaction of long-lived in-process state and memory reporting.
+//!
+//! Memory-backed servers accumulate terminal leases, intents from sessions
+//! that are long gone, health records for agents that went quiet, and retry
+//! records whose backoff has elapsed, and priorities of agents that
+//! stopped coming back. The
+//! client can drop all of these on demand or on a timer, and report how much
+//! each structure currently holds.
+
+use serde::{Deserialize, Serialize};
+
+use crate::infrastructure::StoreUsage;
+
+/// How old state must be before compaction removes it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionPolicy {
+    /// Terminal leases last touched longer ago than this are dropped
+    pub terminal_lease_retention_ms: u64,
+    /// Intents older than this whose session holds no active lease are dropped
+    pub stale_intent_ms: u64,
+    /// Activity timeline entries older than this are dropped
+    #[serde(default = "default_activity_retention_ms")]
+    pub activity_retention_ms: u64,
+    /// Agents with no lease, waiter or intent that were last seen longer
+    /// ago than this are unregistered; `None` keeps every agent
+    #[serde(default)]
+    pub idle_agent_ms: Option<u64>,
+}
+
+fn default_activity_retention_ms() -> u64 {
+    24 * 60 * 60 * 1000
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self {
+            terminal_lease_retention_ms: 5 * 60 * 1000,
+            stale_intent_ms: 60 * 60 * 1000,
+            activity_retention_ms: default_activity_retention_ms(),
+            idle_agent_ms: None,
+        }
+    }
+}
+
+/// What a compaction pass removed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactionReport {
+    pub leases_removed: usize,
+    pub intents_removed: usize,
+    pub health_records_removed: usize,
+    #[serde(default)]
+    pub retry_records_removed: usize,
+    #[serde(default)]
+    pub permits_reclaimed: usize,
+    #[serde(default)]
+    pub aliases_removed: usize,
+    #[serde(default)]
+    pub quarantines_expired: usize,
+    #[serde(default)]
+    pub sessions_expired: usize,
+    #[serde(default)]
+    pub feeds_removed: usize,
+    #[serde(default)]
+    pub activity_entries_removed: usize,
+    /// Namespaces whose TTL ran out (see `namespace`)
+    #[serde(default)]
+    pub namespaces_reaped: usize,
+    /// Idle agents whose priorities were dropped
+    #[serde(default)]
+    pub agents_unregistered: usize,
+}
+
+impl CompactionReport {
+    pub fn total(&self) -> usize {
+        self.leases_removed
+            + self.intents_removed
+            + self.health_records_removed
+            + self.retry_records_removed
+            + self.permits_reclaimed
+            + self.aliases_removed
+            + self.quarantines_expired
+            + self.sessions_expired
+            + self.feeds_removed
+            + self.activity_entries_removed
+            + self.namespaces_reaped
+            + self.agents_unregistered
+    }
+}
+
+/// Entry count and approximate footprint of one structure
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StructureUsage {
+    pub count: usize,
+    pub estimated_bytes: usize,
+}
+
+/// Memory held by each structure of a client
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryReport {
+    pub leases: StoreUsage,
+    pub intents: StructureUsage,
+    pub agents: StructureUsage,
+    pub health_records: StructureUsage,
+    pub retry_records: StructureUsage,
+    pub barriers: StructureUsage,
+    pub semaphores: StructureUsage,
+    pub aliases: StructureUsage,
+    pub quarantines: StructureUsage,
+    pub sessions: StructureUsage,
+    pub feeds: StructureUsage,
+    pub templates: StructureUsage,
+    pub activity: StructureUsage,
+    pub firehose: StructureUsage,
+    pub total_estimated_bytes: usize,
+}
@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::compaction::CompactionPolicy;
+    use crate::health::{HealthSignal, HealthTracker};
+    use crate::infrastructure::LeaseStore;
+    use crate::infrastructure_in_memory::InMemoryLeaseStore;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, LeaseResult, Predicate, ResourceRef, ResourceType, SPOTriple};
+
+    fn create_triple(agent_id: &str, session_id: &str, res_path: &str) -> SPOTriple {
+        SPOTriple {
+            id: format!("t_{}_{}", agent_id, res_path),
+            subject: agent_id.to_string(),
+            predicate: Predicate::Mutates,
+            object: ResourceRef::new(ResourceType::File, res_path),
+            timestamp: 1000,
+            confidence: Confidence::High,
+            session_id: session_id.to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        }
+    }
+
+    #[test]
+    fn test_store_compact_drops_only_old_terminal_leases() {
+        let mut store = InMemoryLeaseStore::new();
+        store.register_agent_priority("agent_1".to_string(), 100);
+
+        let mut acquire = |path: &str, now: u64| match store.acquire(
+            "agent_1",
+            "s1",
+            ResourceRef::new(ResourceType::File, path),
+            Predicate::Mutates,
+            5000,
+            now,
+        ) {
+            LeaseResult::Success { lease } => lease,
+            _ => panic!("Expected Success"),
+        };
+        let old = acquire("/old", 1000);
+        let recent = acquire("/recent", 8000);
+        let held = acquire("/held", 2000);
+
+        store.release(&old.id);
+        store.release(&recent.id);
+        assert_eq!(store.usage().terminal_leases, 2);
+
+        assert_eq!(store.compact(5000), 1);
+        assert!(store.get_lease(&old.id).is_none());
+        assert!(store.get_lease(&recent.id).is_some());
+        assert!(store.get_lease(&held.id).is_some());
+
+        let usage = store.usage();
+        assert_eq!(usage.active_leases, 1);
+        assert_eq!(usage.terminal_leases, 1);
+        assert!(usage.estimated_bytes > 0);
+    }
+
+    #[test]
+    fn test_health_compact_keeps_live_records() {
+        let mut tracker = HealthTracker::default();
+        tracker.record("stale", HealthSignal::Die, 1000);
+        tracker.record("fresh", HealthSignal::Die, 10_000_000);
+
+        assert_eq!(tracker.compact(10_000_000), 1);
+        assert!(!tracker.knows("stale"));
+        assert!(tracker.knows("fresh"));
+    }
+
+    #[test]
+    fn test_client_compact_drops_stale_intents_without_leases() {
+        let mut client = KlockClient::new();
+        client.set_compaction_policy(CompactionPolicy {
+            stale_intent_ms: 0,
+            ..CompactionPolicy::default()
+        });
+        client.register_agent("agent_a", 100);
+        client.register_agent("agent_b", 200);
+
+        for (agent, session, path) in [("agent_a", "s1", "/a.ts"), ("agent_b", "s2", "/b.ts")] {
+            let verdict = client.declare_intent(&IntentManifest {
+                session_id: session.to_string(),
+                agent_id: agent.to_string(),
+                intents: vec![create_triple(agent, session, path)],
+            });
+            assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        }
+
+        // agent_a's session still holds a lease, so its intent survives
+        assert!(matches!(
+            client.acquire_lease("agent_a", "s1", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        assert_eq!(client.memory_report().intents.count, 2);
+
+        let report = client.compact();
+        assert_eq!(report.intents_removed, 1);
+        assert_eq!(report.leases_removed, 0);
+
+        let memory = client.memory_report();
+        assert_eq!(memory.intents.count, 1);
+        assert_eq!(memory.agents.count, 2);
+        assert_eq!(memory.leases.active_leases, 1);
+        assert_eq!(
+            memory.total_estimated_bytes,
+            memory.leases.estimated_bytes
+                + memory.intents.estimated_bytes
+                + memory.agents.estimated_bytes
+                + memory.health_records.estimated_bytes
+                + memory.activity.estimated_bytes
+        );
+    }
+}
@@ -1,75 +1,1066 @@
-use crate::types::{Lease, Predicate, SPOTriple};
+use crate::alias::AliasTable;
+use crate::containment::ContainmentRegistry;
+use crate::equivalence::EquivalenceMap;
+use crate::implication::{ImplicationRule, ImpliedIntent, ImpliedTarget};
+use crate::namespace::{NamespaceBridges, namespace_of};
+use crate::refinement::{self, SymbolRefinement};
+use crate::types::{Confidence, Lease, Predicate, ResourceRef, ResourceType, SPOTriple};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 
 /// Represents the outcome of a conflict check
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ConflictResult {
     /// No conflict found
     Ok,
-    /// A conflict was detected
-    Conflict { reason: String },
+    /// A conflict was detected with the first blocking holder
+    Conflict(ConflictDetail),
 }
 
-/// A pure engine for O(1) conflict detection using precomputed compatibility matrices.
-pub struct ConflictEngine;
+impl ConflictResult {
+    /// The blocking holder, if there was a conflict
+    pub fn detail(&self) -> Option<&ConflictDetail> {
+        match self {
+            ConflictResult::Ok => None,
+            ConflictResult::Conflict(detail) => Some(detail),
+        }
+    }
 
-impl ConflictEngine {
-    /// Central 6x6 Compatibility Matrix based on Wait-Die semantics.
-    /// Rows: Existing Predicate (Held)
-    /// Cols: New Predicate (Requesting)
-    /// True = Compatible (No Conflict)
-    /// False = Incompatible (Conflict)
+    /// Human-readable explanation of the conflict, if there was one
+    pub fn reason(&self) -> Option<&str> {
+        self.detail().map(|detail| detail.reason.as_str())
+    }
+}
+
+impl From<Option<ConflictDetail>> for ConflictResult {
+    fn from(conflict: Option<ConflictDetail>) -> Self {
+        match conflict {
+            Some(detail) => ConflictResult::Conflict(detail),
+            None => ConflictResult::Ok,
+        }
+    }
+}
+
+/// One existing intent or lease that blocks a requested operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictDetail {
+    /// Agent holding the blocking intent or lease
+    pub holder: String,
+    /// The holder's session
+    pub session_id: String,
+    /// The holder's predicate
+    pub predicate: Predicate,
+    /// The predicate that was refused
+    pub requesting_predicate: Predicate,
+    /// The resource the holder declared, which may contain or be contained
+    /// by the requested one
+    pub resource: ResourceRef,
+    /// How `resource` relates to the requested resource
+    #[serde(default)]
+    pub relation: ResourceRelation,
+    /// Set when the blocker is a lease rather than an intent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease_id: Option<String>,
+    /// Human-readable explanation
+    pub reason: String,
+}
+
+/// How a blocking holder's resource relates to the requested one, so an
+/// agent refused on a directory can tell which part of it is taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceRelation {
+    /// The same resource
+    #[default]
+    Exact,
+    /// The holder's resource contains the requested one (e.g. its directory)
+    Ancestor,
+    /// The requested resource contains the holder's (e.g. a file inside the
+    /// requested directory); requesting the rest of it may succeed
+    Descendant,
+    /// One side is a glob pattern matching the other
+    Pattern,
+    /// Matched through a rename alias, an equivalence or an implication
+    Indirect,
+}
+
+impl ResourceRelation {
+    /// How `held` relates to `requesting`
+    pub fn between(held: &ResourceRef, requesting: &ResourceRef) -> Self {
+        if held == requesting {
+            ResourceRelation::Exact
+        } else if held.is_pattern() || requesting.is_pattern() {
+            if held.overlaps(requesting) {
+                ResourceRelation::Pattern
+            } else {
+                ResourceRelation::Indirect
+            }
+        } else if held.contains(requesting) {
+            ResourceRelation::Ancestor
+        } else if requesting.contains(held) {
+            ResourceRelation::Descendant
+        } else {
+            ResourceRelation::Indirect
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResourceRelation::Exact => "exact",
+            ResourceRelation::Ancestor => "ancestor",
+            ResourceRelation::Descendant => "descendant",
+            ResourceRelation::Pattern => "pattern",
+            ResourceRelation::Indirect => "indirect",
+        }
+    }
+}
+
+impl ConflictDetail {
+    /// Key of the holder's resource, e.g. `FILE:/src/auth.ts`
+    pub fn resource_key(&self) -> String {
+        self.resource.key()
+    }
+}
+
+const PREDICATES: [Predicate; 7] = [
+    Predicate::Provides,
+    Predicate::Consumes,
+    Predicate::Mutates,
+    Predicate::Deletes,
+    Predicate::DependsOn,
+    Predicate::Renames,
+    Predicate::Excludes,
+];
+
+/// A 7x7 predicate compatibility matrix.
+///
+/// Rows are the held predicate, columns the requesting one; `true` means the
+/// two may coexist on the same resource. Compatibility must be symmetric:
+/// whether two leases can coexist cannot depend on which one was granted
+/// first.
+///
+/// Serialized as a map from each predicate to the predicates it is
+/// compatible with; predicates left out are compatible with nothing:
+///
+/// ```json
+/// { "Consumes": ["Consumes", "DependsOn"], "DependsOn": ["Consumes", "DependsOn"] }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<Predicate, Vec<Predicate>>",
+    into = "BTreeMap<Predicate, Vec<Predicate>>"
+)]
+pub struct CompatibilityMatrix {
+    cells: [[bool; 7]; 7],
+}
+
+impl CompatibilityMatrix {
+    /// Central 7x7 Compatibility Matrix based on Wait-Die semantics.
     ///
-    /// Order: Provides(0), Consumes(1), Mutates(2), Deletes(3), DependsOn(4), Renames(5)
+    /// Order: Provides(0), Consumes(1), Mutates(2), Deletes(3), DependsOn(4),
+    /// Renames(5), Excludes(6)
     #[rustfmt::skip]
-    const MATRIX: [[bool; 6]; 6] = [
-        //          Prov   Cons   Mut    Del    Dep    Ren
-        /* Prov */ [false, true,  false, false, true,  false],
-        /* Cons */ [true,  true,  false, false, true,  false],
-        /* Mut  */ [false, false, false, false, false, false],
-        /* Del  */ [false, false, false, false, false, false],
-        /* Dep  */ [true,  true,  false, false, true,  false],
-        /* Ren  */ [false, false, false, false, false, false],
-    ];
+    pub const DEFAULT: Self = Self { cells: [
+        //          Prov   Cons   Mut    Del    Dep    Ren    Excl
+        /* Prov */ [false, true,  false, false, true,  false, false],
+        /* Cons */ [true,  true,  false, false, true,  false, false],
+        /* Mut  */ [false, false, false, false, false, false, false],
+        /* Del  */ [false, false, false, false, false, false, false],
+        /* Dep  */ [true,  true,  false, false, true,  false, false],
+        /* Ren  */ [false, false, false, false, false, false, false],
+        /* Excl */ [false, false, false, false, false, false, false],
+    ]};
 
-    /// O(1) check if two predicates conflict
-    pub fn check_pair(held: Predicate, requesting: Predicate) -> bool {
-        // We look up the matrix. It returns true if COMPATIBLE.
-        // Therefore, it CONFLICTS if the matrix returns FALSE.
-        !Self::MATRIX[held.to_index()][requesting.to_index()]
+    /// Matrix where every pair of predicates conflicts
+    pub const EXCLUSIVE: Self = Self {
+        cells: [[false; 7]; 7],
+    };
+
+    /// Build a matrix from raw cells, rejecting asymmetric ones.
+    pub fn new(cells: [[bool; 7]; 7]) -> Result<Self, String> {
+        let matrix = Self { cells };
+        matrix.validate()?;
+        Ok(matrix)
     }
 
-    /// Checks if a new intent conflicts with any existing intents.
-    pub fn check(new_triple: &SPOTriple, existing_triples: &[SPOTriple]) -> ConflictResult {
-        let key = new_triple.object.key();
+    /// Whether a `requesting` predicate may coexist with a `held` one
+    pub fn compatible(&self, held: Predicate, requesting: Predicate) -> bool {
+        self.cells[held.to_index()][requesting.to_index()]
+    }
+
+    /// Mark two predicates as compatible in both directions
+    pub fn allow(mut self, a: Predicate, b: Predicate) -> Self {
+        self.cells[a.to_index()][b.to_index()] = true;
+        self.cells[b.to_index()][a.to_index()] = true;
+        self
+    }
+
+    /// Mark two predicates as conflicting in both directions
+    pub fn forbid(mut self, a: Predicate, b: Predicate) -> Self {
+        self.cells[a.to_index()][b.to_index()] = false;
+        self.cells[b.to_index()][a.to_index()] = false;
+        self
+    }
+
+    pub fn cells(&self) -> &[[bool; 7]; 7] {
+        &self.cells
+    }
+
+    /// Checks that compatibility is symmetric, listing every offending pair.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut asymmetric = Vec::new();
+        for (i, held) in PREDICATES.iter().enumerate() {
+            for (j, requesting) in PREDICATES.iter().enumerate().skip(i + 1) {
+                if self.cells[i][j] != self.cells[j][i] {
+                    asymmetric.push(format!("{:?}/{:?}", held, requesting));
+                }
+            }
+        }
+
+        if asymmetric.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Compatibility matrix must be symmetric; asymmetric pairs: {}",
+                asymmetric.join(", ")
+            ))
+        }
+    }
+}
+
+impl Default for CompatibilityMatrix {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Predicates that change or claim a resource outright, and so must be
+/// compatible with nothing
+const EXCLUSIVE_PREDICATES: [Predicate; 3] =
+    [Predicate::Deletes, Predicate::Renames, Predicate::Excludes];
+
+/// A property a compatibility matrix breaks (see
+/// `CompatibilityMatrix::violations`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MatrixViolation {
+    /// The pair's compatibility depends on which predicate was held first
+    Asymmetric {
+        held: Predicate,
+        requesting: Predicate,
+    },
+    /// A predicate that must reject everything is compatible with `other`
+    ExclusiveCompatible {
+        exclusive: Predicate,
+        other: Predicate,
+    },
+}
+
+impl std::fmt::Display for MatrixViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatrixViolation::Asymmetric { held, requesting } => write!(
+                f,
+                "{:?}/{:?} is compatible in one direction only",
+                held, requesting
+            ),
+            MatrixViolation::ExclusiveCompatible { exclusive, other } => write!(
+                f,
+                "{:?} must conflict with everything but is compatible with {:?}",
+                exclusive, other
+            ),
+        }
+    }
+}
+
+impl CompatibilityMatrix {
+    /// Every required property the matrix breaks: compatibility must be
+    /// symmetric, and `Deletes`, `Renames` and `Excludes` must conflict with
+    /// every predicate. Empty for a consistent matrix.
+    ///
+    /// `new` only enforces symmetry, so custom matrices can still relax the
+    /// exclusive predicates; loaders and tests that want the full contract
+    /// check this list.
+    pub fn violations(&self) -> Vec<MatrixViolation> {
+        let mut violations = Vec::new();
+        for (i, &held) in PREDICATES.iter().enumerate() {
+            for (j, &requesting) in PREDICATES.iter().enumerate().skip(i + 1) {
+                if self.cells[i][j] != self.cells[j][i] {
+                    violations.push(MatrixViolation::Asymmetric { held, requesting });
+                }
+            }
+        }
+        for exclusive in EXCLUSIVE_PREDICATES {
+            for other in PREDICATES {
+                if self.compatible(exclusive, other) || self.compatible(other, exclusive) {
+                    violations.push(MatrixViolation::ExclusiveCompatible { exclusive, other });
+                }
+            }
+        }
+        violations
+    }
+}
+
+impl TryFrom<BTreeMap<Predicate, Vec<Predicate>>> for CompatibilityMatrix {
+    type Error = String;
+
+    fn try_from(rows: BTreeMap<Predicate, Vec<Predicate>>) -> Result<Self, Self::Error> {
+        let mut cells = [[false; 7]; 7];
+        for (held, compatible) in rows {
+            for requesting in compatible {
+                cells[held.to_index()][requesting.to_index()] = true;
+            }
+        }
+        Self::new(cells)
+    }
+}
+
+impl From<CompatibilityMatrix> for BTreeMap<Predicate, Vec<Predicate>> {
+    fn from(matrix: CompatibilityMatrix) -> Self {
+        PREDICATES
+            .iter()
+            .map(|&held| {
+                let compatible = PREDICATES
+                    .iter()
+                    .copied()
+                    .filter(|&requesting| matrix.compatible(held, requesting))
+                    .collect();
+                (held, compatible)
+            })
+            .collect()
+    }
+}
+
+/// A compatibility matrix laid out as a table, for SDKs and UIs to render
+/// without knowing the cell order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatrixTable {
+    /// Row (held) and column (requesting) order
+    pub predicates: Vec<Predicate>,
+    /// One row per held predicate: whether each requesting predicate may
+    /// coexist with it
+    pub compatible: Vec<Vec<bool>>,
+}
+
+impl CompatibilityMatrix {
+    pub fn as_table(&self) -> MatrixTable {
+        MatrixTable {
+            predicates: PREDICATES.to_vec(),
+            compatible: self.cells.iter().map(|row| row.to_vec()).collect(),
+        }
+    }
+}
+
+/// The matrices an engine decides with, as tables
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatrixExplanation {
+    /// The matrix for every resource type without an override
+    pub default: MatrixTable,
+    /// Overrides by resource type (`with_type_matrix`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub by_type: BTreeMap<String, MatrixTable>,
+}
+
+/// A pure engine for O(1) conflict detection using precomputed compatibility matrices.
+///
+/// Resources are matched hierarchically rather than by exact key: a lease on
+/// the directory `FILE:/src/` conflicts with one on `FILE:/src/auth.ts`, and
+/// a file covers the symbols qualified with it (see `ResourceRef::contains`).
+///
+/// The associated functions (`check_pair`, `check`, `check_against_leases`)
+/// use the built-in matrix. Construct an instance with `with_matrix` to use a
+/// custom one, e.g. to let CRDT-backed resources accept concurrent mutations,
+/// add implication rules with `with_implication`, map bare symbols to
+/// their files with `with_containment`, treat renamed resources as one
+/// with `with_aliases`, and resources of different types as one with
+/// `with_equivalences`. `with_type_matrix` replaces the matrix for one
+/// resource type, e.g. to let config keys accept concurrent `Provides`.
+/// `with_isolated_namespaces` keeps the agents of different namespaces
+/// apart, except on bridged resources.
+#[derive(Debug, Clone, Default)]
+pub struct ConflictEngine {
+    matrix: CompatibilityMatrix,
+    /// Matrices used instead of `matrix` for resources of one type
+    type_matrices: HashMap<ResourceType, CompatibilityMatrix>,
+    implications: Vec<ImplicationRule>,
+    containment: ContainmentRegistry,
+    aliases: AliasTable,
+    equivalences: EquivalenceMap,
+    /// Set when namespaces are isolated: the resources shared across them
+    namespace_bridges: Option<NamespaceBridges>,
+    /// Intents at or below this confidence only warn about conflicts
+    tentative_confidence: Option<Confidence>,
+    /// File-level conflicts the kernel refines by the symbols declared
+    symbol_refinement: Option<SymbolRefinement>,
+}
+
+/// One (predicate, resource) pair touched by an operation
+struct Footprint<'a> {
+    predicate: Predicate,
+    resource: Cow<'a, ResourceRef>,
+    /// Rule that implied it, `None` for the operation itself
+    rule: Option<String>,
+    /// Only matches the exact resource, not its contents (directory listings)
+    exact: bool,
+}
+
+impl Footprint<'_> {
+    fn overlaps(&self, other: &Footprint) -> bool {
+        if self.exact || other.exact {
+            self.resource == other.resource
+        } else {
+            self.resource.overlaps(&other.resource)
+        }
+    }
+}
+
+impl ConflictEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build an engine around a custom matrix, rejecting asymmetric ones.
+    pub fn with_matrix(matrix: CompatibilityMatrix) -> Result<Self, String> {
+        matrix.validate()?;
+        Ok(Self {
+            matrix,
+            ..Self::default()
+        })
+    }
+
+    /// Use `matrix` instead of the engine's matrix for resources of
+    /// `resource_type`, rejecting asymmetric matrices. When resources of two
+    /// types overlap (a symbol inside a file, equivalent resources), the
+    /// predicates must be compatible under both types' matrices.
+    pub fn with_type_matrix(
+        mut self,
+        resource_type: ResourceType,
+        matrix: CompatibilityMatrix,
+    ) -> Result<Self, String> {
+        matrix
+            .validate()
+            .map_err(|e| format!("{} ({} override)", e, resource_type))?;
+        self.type_matrices.insert(resource_type, matrix);
+        Ok(self)
+    }
+
+    /// Add an implication rule applied before every conflict check.
+    pub fn with_implication(mut self, rule: ImplicationRule) -> Self {
+        self.implications.push(rule);
+        self
+    }
+
+    /// Qualify bare symbols with the files they live in before matching.
+    pub fn with_containment(mut self, containment: ContainmentRegistry) -> Self {
+        self.containment = containment;
+        self
+    }
+
+    /// Match resources under every name granted renames have given them,
+    /// replacing any previous alias table.
+    pub fn with_aliases(mut self, aliases: AliasTable) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Match resources also as every resource declared equivalent to them
+    /// (e.g. a config key and the file holding it), replacing any previous
+    /// equivalence map.
+    pub fn with_equivalences(mut self, equivalences: EquivalenceMap) -> Self {
+        self.equivalences = equivalences;
+        self
+    }
+
+    /// Only let agents of one namespace conflict with each other (agents
+    /// outside every namespace form one too), except on the resources
+    /// `bridges` shares, replacing any previous bridges.
+    pub fn with_isolated_namespaces(mut self, bridges: NamespaceBridges) -> Self {
+        self.namespace_bridges = Some(bridges);
+        self
+    }
+
+    /// Treat intents at or below `confidence` as tentative: the kernel
+    /// reports their conflicts as warnings instead of refusing them. `None`
+    /// (the default) makes every conflict hard.
+    pub fn with_tentative_confidence(mut self, confidence: Option<Confidence>) -> Self {
+        self.tentative_confidence = confidence;
+        self
+    }
+
+    /// Whether an intent of this confidence only warns about conflicts
+    pub fn is_tentative(&self, confidence: Confidence) -> bool {
+        self.tentative_confidence
+            .is_some_and(|threshold| confidence.at_most(threshold))
+    }
+
+    /// Report file-level conflicts between sessions that declared disjoint
+    /// symbols in the file as warnings (see `refinement`). `None` (the
+    /// default) refines nothing.
+    pub fn with_symbol_refinement(mut self, refinement: Option<SymbolRefinement>) -> Self {
+        self.symbol_refinement = refinement;
+        self
+    }
+
+    pub fn symbol_refinement(&self) -> Option<&SymbolRefinement> {
+        self.symbol_refinement.as_ref()
+    }
+
+    /// Whether a conflict between `held` on `held_resource` and
+    /// `requesting` on `requesting_resource` is refined away: both lie in
+    /// one file, and the symbols each side's session declared there
+    /// (`held_declared` and `requesting_declared` are everything it
+    /// declared) do not overlap.
+    pub fn refines<'a>(
+        &self,
+        held: Predicate,
+        held_resource: &ResourceRef,
+        held_declared: impl IntoIterator<Item = &'a ResourceRef>,
+        requesting: Predicate,
+        requesting_resource: &ResourceRef,
+        requesting_declared: impl IntoIterator<Item = &'a ResourceRef>,
+    ) -> bool {
+        let Some(symbol_refinement) = &self.symbol_refinement else {
+            return false;
+        };
+        if !symbol_refinement.relaxes(held, requesting) {
+            return false;
+        }
+        let Some(file) = refinement::file_of(&self.qualify(held_resource)) else {
+            return false;
+        };
+        if refinement::file_of(&self.qualify(requesting_resource)).as_ref() != Some(&file) {
+            return false;
+        }
+        let symbols_in_file = |own: &ResourceRef, declared: Vec<&ResourceRef>| {
+            std::iter::once(own)
+                .chain(declared)
+                .map(|resource| self.qualify(resource).into_owned())
+                .filter(|resource| resource.containing_file().as_ref() == Some(&file))
+                .collect::<Vec<_>>()
+        };
+        refinement::disjoint(
+            &symbols_in_file(held_resource, held_declared.into_iter().collect()),
+            &symbols_in_file(
+                requesting_resource,
+                requesting_declared.into_iter().collect(),
+            ),
+        )
+    }
+
+    pub fn matrix(&self) -> &CompatibilityMatrix {
+        &self.matrix
+    }
+
+    /// The matrix that governs resources of `resource_type`: its override,
+    /// or else the engine's matrix
+    pub fn matrix_for(&self, resource_type: &ResourceType) -> &CompatibilityMatrix {
+        self.type_matrices
+            .get(resource_type)
+            .unwrap_or(&self.matrix)
+    }
+
+    /// The per-type overrides set with `with_type_matrix`
+    pub fn type_matrices(&self) -> &HashMap<ResourceType, CompatibilityMatrix> {
+        &self.type_matrices
+    }
+
+    /// The effective matrix and its per-type overrides, as tables
+    pub fn explain_matrix(&self) -> MatrixExplanation {
+        MatrixExplanation {
+            default: self.matrix.as_table(),
+            by_type: self
+                .type_matrices
+                .iter()
+                .map(|(resource_type, matrix)| (resource_type.to_string(), matrix.as_table()))
+                .collect(),
+        }
+    }
+
+    /// Check the engine's matrix for required properties, returning every
+    /// violation (see `CompatibilityMatrix::violations`).
+    pub fn validate_matrix(&self) -> Vec<MatrixViolation> {
+        self.matrix.violations()
+    }
+
+    pub fn containment(&self) -> &ContainmentRegistry {
+        &self.containment
+    }
+
+    pub fn aliases(&self) -> &AliasTable {
+        &self.aliases
+    }
+
+    pub fn equivalences(&self) -> &EquivalenceMap {
+        &self.equivalences
+    }
+
+    /// The bridges between namespaces, `None` unless they are isolated
+    pub fn namespace_bridges(&self) -> Option<&NamespaceBridges> {
+        self.namespace_bridges.as_ref()
+    }
+
+    pub fn implications(&self) -> &[ImplicationRule] {
+        &self.implications
+    }
+
+    /// The operations implied by performing `predicate` on `resource`.
+    pub fn implied(&self, predicate: Predicate, resource: &ResourceRef) -> Vec<ImpliedIntent> {
+        let resource = self.qualify(resource);
+        self.implications
+            .iter()
+            .filter_map(|rule| rule.apply(predicate, &resource))
+            .collect()
+    }
+
+    /// The resource as matched by this engine: bare symbols with a known
+    /// file are qualified with it.
+    pub fn qualify<'a>(&self, resource: &'a ResourceRef) -> Cow<'a, ResourceRef> {
+        match self.containment.qualify(resource) {
+            Some(qualified) => Cow::Owned(qualified),
+            None => Cow::Borrowed(resource),
+        }
+    }
+
+    /// Every resource touched by performing `predicate` on `resource`: the
+    /// resource itself, its rename aliases and equivalents, then the targets
+    /// of its implications, and finally what it is bridged to in other
+    /// namespaces.
+    pub fn footprint_resources(
+        &self,
+        predicate: Predicate,
+        resource: &ResourceRef,
+    ) -> Vec<ResourceRef> {
+        let mut resources: Vec<ResourceRef> = self
+            .footprint(predicate, resource)
+            .into_iter()
+            .map(|f| f.resource.into_owned())
+            .collect();
+        if let Some(bridges) = &self.namespace_bridges {
+            resources.extend(bridges.counterparts(&self.qualify(resource)));
+        }
+        resources
+    }
+
+    /// The operation itself, its aliases and equivalents, and everything it
+    /// implies
+    fn footprint<'a>(&self, predicate: Predicate, resource: &'a ResourceRef) -> Vec<Footprint<'a>> {
+        let resource = self.qualify(resource);
+        let implied: Vec<(&ImplicationRule, ImpliedIntent)> = self
+            .implications
+            .iter()
+            .filter_map(|rule| Some((rule, rule.apply(predicate, &resource)?)))
+            .collect();
 
-        for existing in existing_triples {
-            // Skip if they are for a different resource
-            if existing.object.key() != key {
-                continue;
+        let aliases = self.aliases.names(&resource);
+
+        let mut footprint = vec![Footprint {
+            predicate,
+            resource,
+            rule: None,
+            exact: false,
+        }];
+        for alias in aliases {
+            footprint.push(Footprint {
+                predicate,
+                rule: Some(format!("rename alias {}", alias.key())),
+                resource: Cow::Owned(alias),
+                exact: false,
+            });
+        }
+        for equivalent in self.equivalences.equivalents(&footprint[0].resource) {
+            footprint.push(Footprint {
+                predicate,
+                rule: Some(format!("equivalent {}", equivalent.key())),
+                resource: Cow::Owned(equivalent),
+                exact: false,
+            });
+        }
+        for (rule, implied) in implied {
+            footprint.push(Footprint {
+                predicate: implied.predicate,
+                resource: Cow::Owned(implied.resource),
+                rule: Some(implied.rule),
+                exact: rule.target == ImpliedTarget::ParentDirectory,
+            });
+        }
+        footprint
+    }
+
+    /// Finds the first conflicting pair between two footprints, returning
+    /// the rules (if any) that implied each side.
+    fn footprints_conflict(
+        &self,
+        held_predicate: Predicate,
+        held_resource: &ResourceRef,
+        requesting_predicate: Predicate,
+        requesting_resource: &ResourceRef,
+    ) -> Option<(Option<String>, Option<String>)> {
+        if self.implications.is_empty() && self.aliases.is_empty() && self.equivalences.is_empty() {
+            return (self.is_conflict_between(
+                held_predicate,
+                &held_resource.resource_type,
+                requesting_predicate,
+                &requesting_resource.resource_type,
+            ) && self
+                .qualify(held_resource)
+                .overlaps(&self.qualify(requesting_resource)))
+            .then_some((None, None));
+        }
+
+        let held = self.footprint(held_predicate, held_resource);
+        for requesting in self.footprint(requesting_predicate, requesting_resource) {
+            for h in &held {
+                if h.overlaps(&requesting)
+                    && self.is_conflict_between(
+                        h.predicate,
+                        &h.resource.resource_type,
+                        requesting.predicate,
+                        &requesting.resource.resource_type,
+                    )
+                {
+                    return Some((h.rule.clone(), requesting.rule));
+                }
             }
+        }
+        None
+    }
+
+    /// `footprints_conflict` between two agents' operations. With isolated
+    /// namespaces, an agent's operation only meets another namespace's
+    /// through a bridge, as an operation on the bridged resource.
+    fn agents_conflict(
+        &self,
+        held_agent: &str,
+        held_predicate: Predicate,
+        held_resource: &ResourceRef,
+        requesting_agent: &str,
+        requesting_predicate: Predicate,
+        requesting_resource: &ResourceRef,
+    ) -> Option<(Option<String>, Option<String>)> {
+        let held_namespace = namespace_of(held_agent);
+        let requesting_namespace = namespace_of(requesting_agent);
+        let Some(bridges) = self
+            .namespace_bridges
+            .as_ref()
+            .filter(|_| held_namespace != requesting_namespace)
+        else {
+            return self.footprints_conflict(
+                held_predicate,
+                held_resource,
+                requesting_predicate,
+                requesting_resource,
+            );
+        };
+        bridges
+            .bridged(
+                requesting_namespace,
+                &self.qualify(requesting_resource),
+                held_namespace,
+            )
+            .into_iter()
+            .find_map(|bridged| {
+                let (held_rule, requesting_rule) = self.footprints_conflict(
+                    held_predicate,
+                    held_resource,
+                    requesting_predicate,
+                    &bridged,
+                )?;
+                let bridge = format!("bridge to {}", bridged.key());
+                Some((
+                    held_rule,
+                    Some(match requesting_rule {
+                        Some(rule) => format!("{}, {}", bridge, rule),
+                        None => bridge,
+                    }),
+                ))
+            })
+    }
+
+    /// How a held resource relates to a requested one, with bare symbols
+    /// qualified by their files
+    pub fn relation(&self, held: &ResourceRef, requesting: &ResourceRef) -> ResourceRelation {
+        if held == requesting {
+            return ResourceRelation::Exact;
+        }
+        ResourceRelation::between(&self.qualify(held), &self.qualify(requesting))
+    }
+
+    /// O(1) check if two predicates conflict under this engine's matrix
+    pub fn is_conflict(&self, held: Predicate, requesting: Predicate) -> bool {
+        !self.matrix.compatible(held, requesting)
+    }
+
+    /// O(1) check if two predicates conflict on a resource of
+    /// `resource_type`, under its override if it has one
+    pub fn is_conflict_on(
+        &self,
+        held: Predicate,
+        requesting: Predicate,
+        resource_type: &ResourceType,
+    ) -> bool {
+        !self.matrix_for(resource_type).compatible(held, requesting)
+    }
+
+    /// Whether predicates on two overlapping resources conflict: they must
+    /// be compatible under the matrices of both resource types
+    fn is_conflict_between(
+        &self,
+        held: Predicate,
+        held_type: &ResourceType,
+        requesting: Predicate,
+        requesting_type: &ResourceType,
+    ) -> bool {
+        if self.type_matrices.is_empty() {
+            return self.is_conflict(held, requesting);
+        }
+        self.is_conflict_on(held, requesting, held_type)
+            || (held_type != requesting_type
+                && self.is_conflict_on(held, requesting, requesting_type))
+    }
+
+    /// Checks if a new intent conflicts with any existing intents.
+    pub fn check_intent(
+        &self,
+        new_triple: &SPOTriple,
+        existing_triples: &[SPOTriple],
+    ) -> ConflictResult {
+        self.intent_conflicts(new_triple, existing_triples)
+            .next()
+            .into()
+    }
 
+    /// Every existing intent that conflicts with a new one, in order.
+    pub fn check_intent_all(
+        &self,
+        new_triple: &SPOTriple,
+        existing_triples: &[SPOTriple],
+    ) -> Vec<ConflictDetail> {
+        self.intent_conflicts(new_triple, existing_triples)
+            .collect()
+    }
+
+    /// Check a whole manifest in one pass: every existing intent that
+    /// conflicts with each of `new_triples`, one list per new triple, in
+    /// order. An operation the manifest repeats (same subject, session,
+    /// predicate and resource) is checked once.
+    pub fn check_batch(
+        &self,
+        new_triples: &[SPOTriple],
+        existing_triples: &[SPOTriple],
+    ) -> Vec<Vec<ConflictDetail>> {
+        let mut first_seen: HashMap<(&str, &str, Predicate, &ResourceRef), usize> = HashMap::new();
+        let mut results: Vec<Vec<ConflictDetail>> = Vec::with_capacity(new_triples.len());
+        for (position, triple) in new_triples.iter().enumerate() {
+            let key = (
+                triple.subject.as_str(),
+                triple.session_id.as_str(),
+                triple.predicate,
+                &triple.object,
+            );
+            let conflicts = match first_seen.get(&key) {
+                Some(&first) => results[first].clone(),
+                None => {
+                    first_seen.insert(key, position);
+                    self.check_intent_all(triple, existing_triples)
+                }
+            };
+            results.push(conflicts);
+        }
+        results
+    }
+
+    fn intent_conflicts<'a>(
+        &'a self,
+        new_triple: &'a SPOTriple,
+        existing_triples: &'a [SPOTriple],
+    ) -> impl Iterator<Item = ConflictDetail> + 'a {
+        existing_triples.iter().filter_map(move |existing| {
             // Skip if it is the same agent in the same session (reentrant lock logic)
             if existing.subject == new_triple.subject
                 && existing.session_id == new_triple.session_id
             {
-                continue;
+                return None;
             }
 
-            if Self::check_pair(existing.predicate, new_triple.predicate) {
-                return ConflictResult::Conflict {
-                    reason: format!(
-                        "Agent {}'s {:?} operation conflicts with Agent {}'s held {:?} operation on {:?}",
-                        new_triple.subject,
-                        new_triple.predicate,
-                        existing.subject,
-                        existing.predicate,
-                        new_triple.object
-                    ),
-                };
-            }
+            let rules = self.agents_conflict(
+                &existing.subject,
+                existing.predicate,
+                &existing.object,
+                &new_triple.subject,
+                new_triple.predicate,
+                &new_triple.object,
+            )?;
+            let relation = self.relation(&existing.object, &new_triple.object);
+            Some(ConflictDetail {
+                holder: existing.subject.clone(),
+                session_id: existing.session_id.clone(),
+                predicate: existing.predicate,
+                requesting_predicate: new_triple.predicate,
+                resource: existing.object.clone(),
+                relation,
+                lease_id: None,
+                reason: format!(
+                    "Agent {}'s {:?} operation conflicts with Agent {}'s held {:?} operation on {:?}{}{}",
+                    new_triple.subject,
+                    new_triple.predicate,
+                    existing.subject,
+                    existing.predicate,
+                    new_triple.object,
+                    containment_note(&existing.object, relation),
+                    implication_note(rules)
+                ),
+            })
+        })
+    }
+
+    /// Checks if a requested predicate conflicts with any active leases
+    pub fn check_leases(
+        &self,
+        requesting_agent: &str,
+        requesting_session: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+    ) -> ConflictResult {
+        self.lease_conflicts(
+            requesting_agent,
+            requesting_session,
+            requesting_predicate,
+            resource,
+            active_leases,
+        )
+        .next()
+        .into()
+    }
+
+    /// Every active lease that conflicts with the requested predicate, in
+    /// order.
+    pub fn check_leases_all(
+        &self,
+        requesting_agent: &str,
+        requesting_session: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+    ) -> Vec<ConflictDetail> {
+        self.lease_conflicts(
+            requesting_agent,
+            requesting_session,
+            requesting_predicate,
+            resource,
+            active_leases,
+        )
+        .collect()
+    }
+
+    fn lease_conflicts<'a>(
+        &'a self,
+        requesting_agent: &'a str,
+        requesting_session: &'a str,
+        requesting_predicate: Predicate,
+        resource: &'a ResourceRef,
+        active_leases: &'a [Lease],
+    ) -> impl Iterator<Item = ConflictDetail> + 'a {
+        active_leases.iter().filter_map(move |lease| {
+            let rules = self.lease_blocks(
+                lease,
+                requesting_agent,
+                requesting_session,
+                requesting_predicate,
+                resource,
+            )?;
+            let relation = self.relation(&lease.resource, resource);
+            Some(ConflictDetail {
+                holder: lease.agent_id.clone(),
+                session_id: lease.session_id.clone(),
+                predicate: lease.predicate,
+                requesting_predicate,
+                resource: lease.resource.clone(),
+                relation,
+                lease_id: Some(lease.id.clone()),
+                reason: format!(
+                    "Conflict: {:?} vs held {:?}{}{}",
+                    requesting_predicate,
+                    lease.predicate,
+                    containment_note(&lease.resource, relation),
+                    implication_note(rules)
+                ),
+            })
+        })
+    }
+
+    /// Returns the active leases on `resource` whose predicate conflicts
+    /// with `requesting_predicate`: the leases `check_leases_all` reports,
+    /// without building their details.
+    pub fn conflicting_leases<'a>(
+        &self,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &'a [Lease],
+    ) -> Vec<&'a Lease> {
+        active_leases
+            .iter()
+            .filter(|lease| {
+                self.lease_blocks(
+                    lease,
+                    requesting_agent_id,
+                    requesting_session_id,
+                    requesting_predicate,
+                    resource,
+                )
+                .is_some()
+            })
+            .collect()
+    }
+
+    /// The one lease conflict rule every lease check goes through. A lease
+    /// held by the requesting agent in the requesting session is reentrant
+    /// and never blocks (KLIS-7); any other lease blocks when the footprints
+    /// conflict, across namespaces only through a bridge. Returns the
+    /// implication rules behind the conflict.
+    fn lease_blocks(
+        &self,
+        lease: &Lease,
+        requesting_agent: &str,
+        requesting_session: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+    ) -> Option<(Option<String>, Option<String>)> {
+        if lease.agent_id == requesting_agent && lease.session_id == requesting_session {
+            return None;
         }
+        self.agents_conflict(
+            &lease.agent_id,
+            lease.predicate,
+            &lease.resource,
+            requesting_agent,
+            requesting_predicate,
+            resource,
+        )
+    }
 
-        ConflictResult::Ok
+    /// O(1) check if two predicates conflict
+    pub fn check_pair(held: Predicate, requesting: Predicate) -> bool {
+        // We look up the matrix. It returns true if COMPATIBLE.
+        // Therefore, it CONFLICTS if the matrix returns FALSE.
+        !CompatibilityMatrix::DEFAULT.compatible(held, requesting)
+    }
+
+    /// Checks if a new intent conflicts with any existing intents.
+    pub fn check(new_triple: &SPOTriple, existing_triples: &[SPOTriple]) -> ConflictResult {
+        Self::default().check_intent(new_triple, existing_triples)
+    }
+
+    /// Every existing intent that conflicts with a new one, rather than
+    /// just the first.
+    pub fn check_all(
+        new_triple: &SPOTriple,
+        existing_triples: &[SPOTriple],
+    ) -> Vec<ConflictDetail> {
+        Self::default().check_intent_all(new_triple, existing_triples)
     }
 
     /// Checks if a requested predicate conflicts with any active leases
@@ -80,25 +1071,38 @@ impl ConflictEngine {
         resource_key: &str,
         active_leases: &[Lease],
     ) -> ConflictResult {
-        for lease in active_leases {
-            if lease.resource.key() != resource_key {
-                continue;
-            }
+        // Keys always come from `ResourceRef::key`, so an unparsable one
+        // cannot match any lease
+        let Some(resource) = ResourceRef::from_key(resource_key) else {
+            return ConflictResult::Ok;
+        };
 
-            if lease.agent_id == requesting_agent && lease.session_id == requesting_session {
-                continue;
-            }
+        Self::default().check_leases(
+            requesting_agent,
+            requesting_session,
+            requesting_predicate,
+            &resource,
+            active_leases,
+        )
+    }
+}
 
-            if Self::check_pair(lease.predicate, requesting_predicate) {
-                return ConflictResult::Conflict {
-                    reason: format!(
-                        "Conflict: {:?} vs held {:?}",
-                        requesting_predicate, lease.predicate
-                    ),
-                };
-            }
-        }
+/// Suffix naming the held resource, and how it relates to the requested
+/// one, when they differ
+fn containment_note(held: &ResourceRef, relation: ResourceRelation) -> String {
+    match relation {
+        ResourceRelation::Exact => String::new(),
+        ResourceRelation::Indirect => format!(" (held on {})", held.key()),
+        _ => format!(" (held on {}, {})", held.key(), relation.as_str()),
+    }
+}
 
-        ConflictResult::Ok
+/// Suffix naming the implication rules behind a conflict, if any
+fn implication_note((held, requesting): (Option<String>, Option<String>)) -> String {
+    match (held, requesting) {
+        (None, None) => String::new(),
+        (Some(h), None) => format!(" (held operation implied via {})", h),
+        (None, Some(r)) => format!(" (implied via {})", r),
+        (Some(h), Some(r)) => format!(" (implied via {}; held operation implied via {})", r, h),
     }
 }
@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests {
-    use crate::conflict::{ConflictEngine, ConflictResult};
+    use crate::conflict::{
+        CompatibilityMatrix, ConflictEngine, ConflictResult, MatrixViolation, ResourceRelation,
+    };
     use crate::types::{Confidence, Predicate, ResourceRef, ResourceType, SPOTriple};
 
     // =========================================================================
@@ -15,6 +17,9 @@ mod tests {
             timestamp: 1000,
             confidence: Confidence::High,
             session_id: session.to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
         }
     }
 
@@ -61,6 +66,7 @@ mod tests {
             Predicate::Deletes,
             Predicate::DependsOn,
             Predicate::Renames,
+            Predicate::Excludes,
         ] {
             assert!(
                 ConflictEngine::check_pair(Predicate::Deletes, pred),
@@ -75,6 +81,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn excludes_conflicts_even_with_readers() {
+        for pred in [
+            Predicate::Provides,
+            Predicate::Consumes,
+            Predicate::DependsOn,
+            Predicate::Excludes,
+        ] {
+            assert!(ConflictEngine::check_pair(Predicate::Excludes, pred));
+            assert!(ConflictEngine::check_pair(pred, Predicate::Excludes));
+        }
+
+        // Custom matrices written before Excludes existed keep it exclusive
+        let matrix: CompatibilityMatrix =
+            serde_json::from_str(r#"{ "Consumes": ["Consumes"] }"#).unwrap();
+        assert!(!matrix.compatible(Predicate::Excludes, Predicate::Consumes));
+        assert!(!matrix.compatible(Predicate::Excludes, Predicate::Excludes));
+    }
+
     #[test]
     fn provides_consumes_compatible() {
         // Creating a resource while another reads it is safe
@@ -166,4 +191,469 @@ mod tests {
             ConflictResult::Conflict { .. }
         ));
     }
+
+    #[test]
+    fn check_conflict_describes_the_holder_and_both_predicates() {
+        let existing = make_triple("agent_a", Predicate::Mutates, "/src/app.ts", "s1");
+        let new = make_triple("agent_b", Predicate::Consumes, "/src/app.ts", "s2");
+        let result = ConflictEngine::check(&new, &[existing]);
+        let detail = result.detail().expect("Expected conflict");
+        assert_eq!(detail.holder, "agent_a");
+        assert_eq!(detail.session_id, "s1");
+        assert_eq!(detail.predicate, Predicate::Mutates);
+        assert_eq!(detail.requesting_predicate, Predicate::Consumes);
+        assert_eq!(detail.resource_key(), "FILE:/src/app.ts");
+        assert_eq!(result.reason(), Some(detail.reason.as_str()));
+    }
+
+    #[test]
+    fn check_all_returns_every_blocker() {
+        let existing = [
+            make_triple("agent_a", Predicate::Mutates, "/src/app.ts", "s1"),
+            make_triple("agent_b", Predicate::Consumes, "/src/other.ts", "s2"),
+            make_triple("agent_c", Predicate::Deletes, "/src/", "s3"),
+            make_triple("agent_d", Predicate::Mutates, "/src/app.ts", "s4"),
+        ];
+        let new = make_triple("agent_d", Predicate::Mutates, "/src/app.ts", "s4");
+
+        let conflicts = ConflictEngine::check_all(&new, &existing);
+        let holders: Vec<&str> = conflicts.iter().map(|c| c.holder.as_str()).collect();
+        assert_eq!(holders, vec!["agent_a", "agent_c"]);
+        assert_eq!(conflicts[1].predicate, Predicate::Deletes);
+        assert_eq!(conflicts[1].resource.path, "/src/");
+        assert_eq!(conflicts[1].session_id, "s3");
+        assert!(conflicts[1].lease_id.is_none());
+
+        // check still reports the first of them
+        assert_eq!(
+            ConflictEngine::check(&new, &existing),
+            ConflictResult::Conflict(conflicts[0].clone())
+        );
+    }
+
+    // =========================================================================
+    // Custom compatibility matrices
+    // =========================================================================
+
+    #[test]
+    fn custom_matrix_allows_concurrent_mutations() {
+        let engine = ConflictEngine::with_matrix(
+            CompatibilityMatrix::default().allow(Predicate::Mutates, Predicate::Mutates),
+        )
+        .unwrap();
+
+        let existing = make_triple("agent_a", Predicate::Mutates, "/crdt/doc.json", "s1");
+        let new = make_triple("agent_b", Predicate::Mutates, "/crdt/doc.json", "s2");
+        assert_eq!(
+            engine.check_intent(&new, std::slice::from_ref(&existing)),
+            ConflictResult::Ok
+        );
+
+        // The built-in matrix is untouched
+        assert!(matches!(
+            ConflictEngine::check(&new, &[existing]),
+            ConflictResult::Conflict { .. }
+        ));
+        assert!(engine.is_conflict(Predicate::Mutates, Predicate::Deletes));
+    }
+
+    #[test]
+    fn asymmetric_matrix_is_rejected() {
+        let mut cells = *CompatibilityMatrix::DEFAULT.cells();
+        cells[Predicate::Consumes.to_index()][Predicate::Mutates.to_index()] = true;
+
+        let err = CompatibilityMatrix::new(cells).unwrap_err();
+        assert!(err.contains("Consumes/Mutates"));
+    }
+
+    #[test]
+    fn validate_matrix_lists_violations() {
+        assert!(ConflictEngine::default().validate_matrix().is_empty());
+        assert!(CompatibilityMatrix::EXCLUSIVE.violations().is_empty());
+
+        // Concurrent mutations are a legitimate relaxation; deletions are not
+        let crdt = CompatibilityMatrix::DEFAULT.allow(Predicate::Mutates, Predicate::Mutates);
+        assert!(crdt.violations().is_empty());
+        let engine = ConflictEngine::with_matrix(
+            CompatibilityMatrix::DEFAULT.allow(Predicate::Deletes, Predicate::Consumes),
+        )
+        .unwrap();
+        assert_eq!(
+            engine.validate_matrix(),
+            vec![MatrixViolation::ExclusiveCompatible {
+                exclusive: Predicate::Deletes,
+                other: Predicate::Consumes,
+            }]
+        );
+    }
+
+    #[test]
+    fn type_matrix_overrides_one_resource_type() {
+        let engine = ConflictEngine::default()
+            .with_type_matrix(
+                ResourceType::ConfigKey,
+                CompatibilityMatrix::DEFAULT.allow(Predicate::Provides, Predicate::Provides),
+            )
+            .unwrap();
+        assert!(!engine.is_conflict_on(
+            Predicate::Provides,
+            Predicate::Provides,
+            &ResourceType::ConfigKey
+        ));
+        assert!(engine.is_conflict_on(
+            Predicate::Provides,
+            Predicate::Provides,
+            &ResourceType::File
+        ));
+
+        let mut existing = make_triple("agent_a", Predicate::Provides, "feature.flags", "s1");
+        existing.object = ResourceRef::new(ResourceType::ConfigKey, "feature.flags");
+        let mut new = make_triple("agent_b", Predicate::Provides, "feature.flags", "s2");
+        new.object = ResourceRef::new(ResourceType::ConfigKey, "feature.flags");
+        assert_eq!(
+            engine.check_intent(&new, std::slice::from_ref(&existing)),
+            ConflictResult::Ok
+        );
+
+        // Files keep the engine-wide matrix
+        let existing = make_triple("agent_a", Predicate::Provides, "/src/a.ts", "s1");
+        let new = make_triple("agent_b", Predicate::Provides, "/src/a.ts", "s2");
+        assert!(matches!(
+            engine.check_intent(&new, &[existing]),
+            ConflictResult::Conflict { .. }
+        ));
+    }
+
+    #[test]
+    fn matrix_round_trips_through_config() {
+        let matrix = CompatibilityMatrix::default().allow(Predicate::Mutates, Predicate::Mutates);
+        let json = serde_json::to_string(&matrix).unwrap();
+        let parsed: CompatibilityMatrix = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, matrix);
+
+        let asymmetric = r#"{ "Consumes": ["Mutates"] }"#;
+        assert!(serde_json::from_str::<CompatibilityMatrix>(asymmetric).is_err());
+    }
+
+    #[test]
+    fn explain_matrix_lays_out_every_matrix_as_a_table() {
+        let table = CompatibilityMatrix::DEFAULT.as_table();
+        assert_eq!(table.predicates.len(), 7);
+        for (i, &held) in table.predicates.iter().enumerate() {
+            for (j, &requesting) in table.predicates.iter().enumerate() {
+                assert_eq!(
+                    table.compatible[i][j],
+                    CompatibilityMatrix::DEFAULT.compatible(held, requesting)
+                );
+            }
+        }
+
+        assert!(
+            ConflictEngine::default()
+                .explain_matrix()
+                .by_type
+                .is_empty()
+        );
+        let crdt = CompatibilityMatrix::DEFAULT.allow(Predicate::Mutates, Predicate::Mutates);
+        let engine = ConflictEngine::with_matrix(crdt)
+            .unwrap()
+            .with_type_matrix(ResourceType::ConfigKey, CompatibilityMatrix::EXCLUSIVE)
+            .unwrap();
+        let explained = engine.explain_matrix();
+        assert_eq!(explained.default, crdt.as_table());
+        assert_eq!(explained.by_type.len(), 1);
+        assert_eq!(
+            explained.by_type[&ResourceType::ConfigKey.to_string()],
+            CompatibilityMatrix::EXCLUSIVE.as_table()
+        );
+    }
+
+    // =========================================================================
+    // Hierarchical resources
+    // =========================================================================
+
+    #[test]
+    fn directory_contains_nested_files() {
+        let dir = ResourceRef::new(ResourceType::File, "/src/");
+        assert!(dir.contains(&ResourceRef::new(ResourceType::File, "/src/auth.ts")));
+        assert!(dir.contains(&ResourceRef::new(ResourceType::File, "/src/api/users.ts")));
+        assert!(!dir.contains(&ResourceRef::new(ResourceType::File, "/srcs/auth.ts")));
+
+        // A plain file covers nothing but itself
+        let file = ResourceRef::new(ResourceType::File, "/src/auth");
+        assert!(!file.contains(&ResourceRef::new(ResourceType::File, "/src/auth/x.ts")));
+    }
+
+    #[test]
+    fn files_and_symbols_contain_members() {
+        let file = ResourceRef::new(ResourceType::File, "/src/user.ts");
+        let class = ResourceRef::new(ResourceType::Symbol, "/src/user.ts#User");
+        let method = ResourceRef::new(ResourceType::Symbol, "/src/user.ts#User.login");
+
+        assert!(file.contains(&method));
+        assert!(class.contains(&method));
+        assert!(method.overlaps(&class));
+        assert!(!class.contains(&ResourceRef::new(
+            ResourceType::Symbol,
+            "/src/user.ts#UserId"
+        )));
+        assert!(!ResourceRef::new(ResourceType::Symbol, "User").contains(&file));
+    }
+
+    #[test]
+    fn check_detects_directory_conflict() {
+        let existing = make_triple("agent_a", Predicate::Mutates, "/src/", "s1");
+        let nested = make_triple("agent_b", Predicate::Consumes, "/src/auth.ts", "s2");
+        let sibling = make_triple("agent_b", Predicate::Consumes, "/lib/auth.ts", "s2");
+
+        match ConflictEngine::check(&nested, std::slice::from_ref(&existing)) {
+            ConflictResult::Conflict(detail) => {
+                assert!(detail.reason.contains("held on FILE:/src/"))
+            }
+            ConflictResult::Ok => panic!("Expected directory conflict"),
+        }
+        assert_eq!(
+            ConflictEngine::check(&sibling, &[existing]),
+            ConflictResult::Ok
+        );
+    }
+
+    #[test]
+    fn conflict_details_name_the_relation() {
+        let file = |path: &str| ResourceRef::new(ResourceType::File, path);
+        let lease = |id: &str, path: &str| {
+            crate::types::Lease::new(
+                id.to_string(),
+                "agent_a".to_string(),
+                "s1".to_string(),
+                file(path),
+                Predicate::Mutates,
+                5000,
+                1000,
+            )
+        };
+        let leases = [
+            lease("l1", "/src/auth.ts"),
+            lease("l2", "/src/db/pool.ts"),
+            lease("l3", "/lib/util.ts"),
+        ];
+
+        // A refusal on the directory names the files inside it that are taken
+        let blockers = ConflictEngine::default().check_leases_all(
+            "agent_b",
+            "s2",
+            Predicate::Mutates,
+            &file("/src/"),
+            &leases,
+        );
+        assert_eq!(blockers.len(), 2);
+        assert!(
+            blockers
+                .iter()
+                .all(|b| b.relation == ResourceRelation::Descendant)
+        );
+        assert!(
+            blockers[0]
+                .reason
+                .ends_with("(held on FILE:/src/auth.ts, descendant)")
+        );
+
+        let relation = |held: &str, requesting: &str| {
+            ResourceRelation::between(&file(held), &file(requesting))
+        };
+        assert_eq!(relation("/src/a.ts", "/src/a.ts"), ResourceRelation::Exact);
+        assert_eq!(relation("/src/", "/src/a.ts"), ResourceRelation::Ancestor);
+        assert_eq!(relation("/src/a.ts", "/src/"), ResourceRelation::Descendant);
+        assert_eq!(
+            relation("/src/**/*.ts", "/src/a.ts"),
+            ResourceRelation::Pattern
+        );
+        assert_eq!(
+            serde_json::to_string(&ResourceRelation::Descendant).unwrap(),
+            r#""descendant""#
+        );
+    }
+
+    #[test]
+    fn check_against_leases_is_hierarchical() {
+        let lease = crate::types::Lease::new(
+            "l1".to_string(),
+            "agent_a".to_string(),
+            "s1".to_string(),
+            ResourceRef::new(ResourceType::File, "/src/user.ts"),
+            Predicate::Deletes,
+            5000,
+            1000,
+        );
+
+        assert!(matches!(
+            ConflictEngine::check_against_leases(
+                "agent_b",
+                "s2",
+                Predicate::Mutates,
+                "SYMBOL:/src/user.ts#User.authenticate",
+                &[lease],
+            ),
+            ConflictResult::Conflict { .. }
+        ));
+    }
+
+    // =========================================================================
+    // HTTP methods on API endpoints
+    // =========================================================================
+
+    #[test]
+    fn endpoint_methods_are_part_of_the_key() {
+        let get = ResourceRef::api_endpoint(Some("get"), "/users");
+        let post = ResourceRef::api_endpoint(Some("POST"), "/users");
+        let any = ResourceRef::api_endpoint(None, "/users");
+
+        assert_eq!(get.key(), "API_ENDPOINT:GET /users");
+        assert_eq!(get.endpoint(), Some((Some("GET"), "/users")));
+        assert_eq!(any.endpoint(), Some((None, "/users")));
+
+        assert!(!get.overlaps(&post));
+        // A route without a method covers every method on it
+        assert!(any.contains(&get));
+        assert!(post.overlaps(&any));
+        assert!(!any.overlaps(&ResourceRef::api_endpoint(Some("GET"), "/orders")));
+
+        // Patterns keep the method
+        let pattern = ResourceRef::api_endpoint(Some("GET"), "/users/*");
+        assert!(pattern.overlaps(&ResourceRef::api_endpoint(Some("GET"), "/users/1")));
+        assert!(!pattern.overlaps(&ResourceRef::api_endpoint(Some("PUT"), "/users/1")));
+        assert!(pattern.overlaps(&ResourceRef::api_endpoint(None, "/users/1")));
+    }
+
+    #[test]
+    fn different_methods_do_not_conflict() {
+        let engine = ConflictEngine::default();
+        let lease = crate::types::Lease::new(
+            "l1".to_string(),
+            "agent_a".to_string(),
+            "s1".to_string(),
+            ResourceRef::api_endpoint(Some("GET"), "/users"),
+            Predicate::Mutates,
+            5000,
+            1000,
+        );
+        let held = std::slice::from_ref(&lease);
+
+        let check = |method: Option<&str>| {
+            engine.check_leases(
+                "agent_b",
+                "s2",
+                Predicate::Mutates,
+                &ResourceRef::api_endpoint(method, "/users"),
+                held,
+            )
+        };
+        assert_eq!(check(Some("POST")), ConflictResult::Ok);
+        assert!(matches!(
+            check(Some("GET")),
+            ConflictResult::Conflict { .. }
+        ));
+        assert!(matches!(check(None), ConflictResult::Conflict { .. }));
+    }
+
+    // =========================================================================
+    // Column-level database resources
+    // =========================================================================
+
+    #[test]
+    fn columns_conflict_only_with_their_table() {
+        let email = ResourceRef::database_table("users", Some("email"));
+        let name = ResourceRef::database_table("users", Some("name"));
+        let table = ResourceRef::database_table("users", None);
+
+        assert_eq!(email.key(), "DATABASE_TABLE:users#email");
+        assert!(!email.overlaps(&name));
+        assert!(table.contains(&email));
+        assert!(!ResourceRef::database_table("user", None).contains(&email));
+        assert!(
+            ResourceRef::database_table("users", Some("*"))
+                .overlaps(&ResourceRef::database_table("users", Some("email")))
+        );
+
+        let engine = ConflictEngine::default();
+        let column_lease = crate::types::Lease::new(
+            "l1".to_string(),
+            "agent_a".to_string(),
+            "s1".to_string(),
+            email,
+            Predicate::Mutates,
+            5000,
+            1000,
+        );
+        let held = std::slice::from_ref(&column_lease);
+
+        assert_eq!(
+            engine.check_leases("agent_b", "s2", Predicate::Mutates, &name, held),
+            ConflictResult::Ok
+        );
+        assert!(matches!(
+            engine.check_leases("agent_b", "s2", Predicate::Deletes, &table, held),
+            ConflictResult::Conflict { .. }
+        ));
+    }
+
+    #[test]
+    fn schema_qualified_tables_are_not_columns() {
+        let schema = ResourceRef::database_table("public", None);
+        let users = ResourceRef::database_table("public.users", None);
+        let email = ResourceRef::database_table("public.users", Some("email"));
+
+        assert!(!schema.contains(&users));
+        assert!(!schema.overlaps(&users));
+        assert!(!schema.overlaps(&email));
+        assert!(users.contains(&email));
+        assert!(!email.contains(&users));
+        assert!(ResourceRef::database_table("public.*", None).overlaps(&email));
+
+        let engine = ConflictEngine::default();
+        let held = [crate::types::Lease::new(
+            "l1".to_string(),
+            "agent_a".to_string(),
+            "s1".to_string(),
+            schema,
+            Predicate::Deletes,
+            5000,
+            1000,
+        )];
+        assert_eq!(
+            engine.check_leases("agent_b", "s2", Predicate::Mutates, &users, &held),
+            ConflictResult::Ok
+        );
+    }
+
+    // =========================================================================
+    // Batch checks
+    // =========================================================================
+
+    #[test]
+    fn check_batch_reports_per_intent() {
+        let existing = vec![
+            make_triple("agent_a", Predicate::Consumes, "/src/a.ts", "s1"),
+            make_triple("agent_b", Predicate::Mutates, "/src/", "s2"),
+        ];
+        let manifest = vec![
+            make_triple("agent_c", Predicate::Mutates, "/src/a.ts", "s3"),
+            make_triple("agent_c", Predicate::Consumes, "/lib/b.ts", "s3"),
+            make_triple("agent_c", Predicate::Mutates, "/src/a.ts", "s3"),
+        ];
+
+        let results = ConflictEngine::default().check_batch(&manifest, &existing);
+        assert_eq!(results.len(), 3);
+        let holders: Vec<&str> = results[0].iter().map(|c| c.holder.as_str()).collect();
+        assert_eq!(holders, vec!["agent_a", "agent_b"]);
+        assert!(results[1].is_empty());
+        // The repeated operation gets the same answer
+        assert_eq!(results[2], results[0]);
+        for (triple, result) in manifest.iter().zip(&results) {
+            assert_eq!(
+                &ConflictEngine::default().check_intent_all(triple, &existing),
+                result
+            );
+        }
+    }
 }
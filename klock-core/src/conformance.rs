@@ -0,0 +1,445 @@
+//! Language-agnostic conformance vectors.
+//!
+//! A vector is a scripted scenario: agents and their priorities, then a
+//! sequence of acquires, releases and intent manifests, each paired with the
+//! outcome this kernel gave it. Vectors are generated from the standard
+//! scenarios below (`klock conformance generate`) and checked into
+//! `conformance/vectors/` as JSON. Every SDK replays them through its own
+//! bindings and must reach the same outcomes, so drift between a binding and
+//! the core matrix or scheduler shows up as a failing vector.
+//!
+//! Only decisions are compared: whether each step succeeded, the refusal
+//! reason, and the verdict status. Lease IDs, timestamps and wait times
+//! differ between runs and are not part of a vector.
+
+use crate::client::{KlockClient, parse_predicate, parse_resource_type};
+use crate::clock::ManualClock;
+use crate::registry::{Priority, PriorityClass};
+use crate::state::{IntentManifest, KernelVerdictStatus};
+use crate::types::{Confidence, LeaseResult, ResourceRef, SPOTriple};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Clock reading at the first step of every run
+const START: u64 = 1_000_000;
+
+/// TTL of the leases in the standard scenarios; long enough that nothing
+/// expires while an SDK replays a vector on its wall clock
+const TTL: u64 = 60_000;
+
+/// A scripted scenario and the outcome of each step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConformanceVector {
+    pub name: String,
+    pub description: String,
+    /// Agent priorities, registered before the first step: timestamps, or
+    /// `{"class": ..., "timestamp": ...}` for classes other than normal
+    #[serde(default)]
+    pub agents: BTreeMap<String, Priority>,
+    pub steps: Vec<ConformanceStep>,
+}
+
+/// One request, and the outcome the kernel gave it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConformanceStep {
+    #[serde(flatten)]
+    pub action: StepAction,
+    /// Absent until the vector is generated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect: Option<StepOutcome>,
+}
+
+/// A request to replay, with the same arguments the SDKs take
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum StepAction {
+    Acquire {
+        agent_id: String,
+        session_id: String,
+        resource_type: String,
+        resource_path: String,
+        predicate: String,
+        ttl: u64,
+    },
+    /// Release the lease granted by the acquire at index `step`
+    Release { step: usize },
+    /// Declare an intent manifest. SDKs without intent declaration skip
+    /// vectors containing these steps.
+    Declare {
+        agent_id: String,
+        session_id: String,
+        intents: Vec<IntentSpec>,
+    },
+}
+
+/// One intent of a `Declare` step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntentSpec {
+    pub predicate: String,
+    pub resource_type: String,
+    pub resource_path: String,
+}
+
+/// What a step produced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepOutcome {
+    pub success: bool,
+    /// The refusal reason of an acquire (`DIE`, `WAIT`, ...), or the status
+    /// of a refused manifest (`WAIT`, `DIE`, `REJECTED`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl StepOutcome {
+    fn granted() -> Self {
+        Self {
+            success: true,
+            reason: None,
+        }
+    }
+
+    fn refused(reason: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            reason: Some(reason.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for StepOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.reason, self.success) {
+            (Some(reason), _) => write!(f, "{}", reason),
+            (None, true) => write!(f, "success"),
+            (None, false) => write!(f, "failure"),
+        }
+    }
+}
+
+impl ConformanceVector {
+    /// Replay the steps against a fresh deterministic kernel, returning each
+    /// step's outcome. Fails if a release names a step that granted nothing.
+    pub fn run(&self) -> Result<Vec<StepOutcome>, String> {
+        self.replay().map(|(_, outcomes)| outcomes)
+    }
+
+    /// `run`, also returning the client in the state the steps left it, e.g.
+    /// to check a manifest against that state
+    pub fn replay(&self) -> Result<(KlockClient, Vec<StepOutcome>), String> {
+        let clock = Arc::new(ManualClock::new(START));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        for (agent_id, priority) in &self.agents {
+            client.register_agent_in_class(agent_id, priority.class, priority.timestamp);
+        }
+
+        let mut leases: HashMap<usize, String> = HashMap::new();
+        let mut outcomes = Vec::with_capacity(self.steps.len());
+        for (index, step) in self.steps.iter().enumerate() {
+            let outcome = match &step.action {
+                StepAction::Acquire {
+                    agent_id,
+                    session_id,
+                    resource_type,
+                    resource_path,
+                    predicate,
+                    ttl,
+                } => match client.acquire_lease(
+                    agent_id,
+                    session_id,
+                    resource_type,
+                    resource_path,
+                    predicate,
+                    *ttl,
+                ) {
+                    LeaseResult::Success { lease } => {
+                        leases.insert(index, lease.id);
+                        StepOutcome::granted()
+                    }
+                    LeaseResult::Failure { reason, .. } => StepOutcome::refused(reason.as_str()),
+                },
+                StepAction::Release { step } => {
+                    let lease_id = leases.get(step).ok_or_else(|| {
+                        format!(
+                            "{}: step {} releases step {}, which granted no lease",
+                            self.name, index, step
+                        )
+                    })?;
+                    if client.release_lease(lease_id) {
+                        StepOutcome::granted()
+                    } else {
+                        StepOutcome::refused("LEASE_NOT_FOUND")
+                    }
+                }
+                StepAction::Declare {
+                    agent_id,
+                    session_id,
+                    intents,
+                } => {
+                    let now = client.now();
+                    let intents = intents
+                        .iter()
+                        .enumerate()
+                        .map(|(position, intent)| SPOTriple {
+                            id: format!("{}_{}_{}", self.name, index, position),
+                            subject: agent_id.clone(),
+                            predicate: parse_predicate(&intent.predicate),
+                            object: ResourceRef::new(
+                                parse_resource_type(&intent.resource_type),
+                                &intent.resource_path,
+                            ),
+                            timestamp: now,
+                            confidence: Confidence::High,
+                            session_id: session_id.clone(),
+                            renamed_to: None,
+                            expires_at: None,
+                            long_lived: false,
+                        })
+                        .collect();
+                    let verdict = client.declare_intent(&IntentManifest {
+                        session_id: session_id.clone(),
+                        agent_id: agent_id.clone(),
+                        intents,
+                    });
+                    match verdict.status {
+                        KernelVerdictStatus::Granted => StepOutcome::granted(),
+                        KernelVerdictStatus::Wait => StepOutcome::refused("WAIT"),
+                        KernelVerdictStatus::Die => StepOutcome::refused("DIE"),
+                        KernelVerdictStatus::Rejected => StepOutcome::refused("REJECTED"),
+                    }
+                }
+            };
+            outcomes.push(outcome);
+            clock.advance(1);
+        }
+        Ok((client, outcomes))
+    }
+
+    /// The vector with every step's `expect` set to what this kernel does
+    pub fn generated(mut self) -> Result<Self, String> {
+        let outcomes = self.run()?;
+        for (step, outcome) in self.steps.iter_mut().zip(outcomes) {
+            step.expect = Some(outcome);
+        }
+        Ok(self)
+    }
+
+    /// Replay the vector and compare every step with its `expect`
+    pub fn check(&self) -> Result<(), String> {
+        let outcomes = self.run()?;
+        for (index, (step, actual)) in self.steps.iter().zip(&outcomes).enumerate() {
+            let Some(expected) = &step.expect else {
+                return Err(format!("{}: step {} has no expectation", self.name, index));
+            };
+            if expected != actual {
+                return Err(format!(
+                    "{}: step {} expected {}, got {}",
+                    self.name, index, expected, actual
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether SDKs that can only acquire and release can replay the vector
+    pub fn leases_only(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| !matches!(step.action, StepAction::Declare { .. }))
+    }
+}
+
+fn acquire(agent_id: &str, session_id: &str, resource: &str, predicate: &str) -> ConformanceStep {
+    let (resource_type, resource_path) = resource.split_once(':').unwrap_or(("FILE", resource));
+    ConformanceStep {
+        action: StepAction::Acquire {
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            resource_type: resource_type.to_string(),
+            resource_path: resource_path.to_string(),
+            predicate: predicate.to_string(),
+            ttl: TTL,
+        },
+        expect: None,
+    }
+}
+
+fn release(step: usize) -> ConformanceStep {
+    ConformanceStep {
+        action: StepAction::Release { step },
+        expect: None,
+    }
+}
+
+fn declare(agent_id: &str, session_id: &str, intents: &[(&str, &str)]) -> ConformanceStep {
+    ConformanceStep {
+        action: StepAction::Declare {
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            intents: intents
+                .iter()
+                .map(|(predicate, path)| IntentSpec {
+                    predicate: predicate.to_string(),
+                    resource_type: "FILE".to_string(),
+                    resource_path: path.to_string(),
+                })
+                .collect(),
+        },
+        expect: None,
+    }
+}
+
+fn scenario(name: &str, description: &str, steps: Vec<ConformanceStep>) -> ConformanceVector {
+    ConformanceVector {
+        name: name.to_string(),
+        description: description.to_string(),
+        agents: BTreeMap::from([
+            ("older".to_string(), 100.into()),
+            ("younger".to_string(), 200.into()),
+        ]),
+        steps,
+    }
+}
+
+const PREDICATES: [&str; 7] = [
+    "PROVIDES",
+    "CONSUMES",
+    "MUTATES",
+    "DELETES",
+    "DEPENDS_ON",
+    "RENAMES",
+    "EXCLUDES",
+];
+
+/// The published scenarios, with expectations generated by this kernel
+pub fn standard_vectors() -> Vec<ConformanceVector> {
+    // Every ordered predicate pair, each on its own file, in both seniority
+    // orders: the matrix decides whether they coexist, the scheduler how
+    // the loser is refused
+    let mut matrix = Vec::new();
+    for held in PREDICATES {
+        for requested in PREDICATES {
+            let path = format!("/matrix/{}/{}", held, requested);
+            matrix.push(acquire("older", "s1", &path, held));
+            matrix.push(acquire("younger", "s2", &path, requested));
+            let path = format!("/matrix-reversed/{}/{}", held, requested);
+            matrix.push(acquire("younger", "s2", &path, held));
+            matrix.push(acquire("older", "s1", &path, requested));
+        }
+    }
+
+    vec![
+        scenario(
+            "predicate_matrix",
+            "Every pair of predicates on one file, requested by a junior against a senior \
+             holder and by a senior against a junior holder",
+            matrix,
+        ),
+        scenario(
+            "release_frees_resource",
+            "A junior dies against a held lease and is granted once it is released; releasing \
+             twice fails",
+            vec![
+                acquire("older", "s1", "/src/a.ts", "MUTATES"),
+                acquire("younger", "s2", "/src/a.ts", "MUTATES"),
+                release(0),
+                acquire("younger", "s2", "/src/a.ts", "MUTATES"),
+                release(3),
+                release(0),
+            ],
+        ),
+        scenario(
+            "session_reentrancy",
+            "An agent's leases never block it within a session, and do across its sessions",
+            vec![
+                acquire("older", "s1", "/src/a.ts", "MUTATES"),
+                acquire("older", "s1", "/src/a.ts", "DELETES"),
+                acquire("older", "s3", "/src/a.ts", "CONSUMES"),
+            ],
+        ),
+        scenario(
+            "hierarchical_resources",
+            "Directories cover the files below them, in both directions",
+            vec![
+                acquire("older", "s1", "/src/", "MUTATES"),
+                acquire("younger", "s2", "/src/auth/login.ts", "CONSUMES"),
+                acquire("younger", "s2", "/srcx/main.ts", "MUTATES"),
+                acquire("younger", "s2", "/docs/guide.md", "MUTATES"),
+                acquire("older", "s1", "/docs/", "CONSUMES"),
+            ],
+        ),
+        scenario(
+            "glob_patterns",
+            "Glob patterns conflict with the paths they match, and only those",
+            vec![
+                acquire("older", "s1", "/src/**/*.ts", "MUTATES"),
+                acquire("younger", "s2", "/src/auth/login.ts", "CONSUMES"),
+                acquire("younger", "s2", "/src/auth/login.rs", "MUTATES"),
+                acquire("younger", "s2", "/lib/util.ts", "MUTATES"),
+            ],
+        ),
+        scenario(
+            "resource_types",
+            "Resources of different types never overlap; table columns are independent of \
+             each other but not of their table",
+            vec![
+                acquire("older", "s1", "FILE:users", "MUTATES"),
+                acquire("younger", "s2", "DATABASE_TABLE:users", "MUTATES"),
+                acquire("older", "s1", "DATABASE_TABLE:orders#total", "MUTATES"),
+                acquire("younger", "s2", "DATABASE_TABLE:orders#status", "MUTATES"),
+                acquire("younger", "s2", "DATABASE_TABLE:orders", "CONSUMES"),
+                acquire("older", "s1", "CONFIG_KEY:feature.flags", "PROVIDES"),
+                acquire("younger", "s2", "CONFIG_KEY:feature.flags", "CONSUMES"),
+            ],
+        ),
+        ConformanceVector {
+            agents: BTreeMap::from([
+                ("older".to_string(), 100.into()),
+                (
+                    "pager".to_string(),
+                    Priority::new(PriorityClass::Critical, 900),
+                ),
+                (
+                    "indexer".to_string(),
+                    Priority::new(PriorityClass::Background, 50),
+                ),
+            ]),
+            ..scenario(
+                "priority_classes",
+                "The priority class dominates the timestamp: a normal agent waits for a \
+                 background holder with an older timestamp, and dies against a critical \
+                 holder with a younger one",
+                vec![
+                    acquire("indexer", "s3", "/jobs/reindex", "MUTATES"),
+                    acquire("older", "s1", "/jobs/reindex", "MUTATES"),
+                    acquire("pager", "s4", "/incident.md", "MUTATES"),
+                    acquire("older", "s1", "/incident.md", "MUTATES"),
+                ],
+            )
+        },
+        scenario(
+            "manifest_verdicts",
+            "Intent manifests are refused with Wait-Die by conflicting leases; conflicts \
+             with declared intents alone are reported but granted",
+            vec![
+                acquire("older", "s1", "/src/a.ts", "MUTATES"),
+                acquire("younger", "s2", "/src/b.ts", "MUTATES"),
+                declare("younger", "s2", &[("CONSUMES", "/src/a.ts")]),
+                declare("older", "s1", &[("CONSUMES", "/src/b.ts")]),
+                declare(
+                    "older",
+                    "s1",
+                    &[("MUTATES", "/src/c.ts"), ("CONSUMES", "/src/a.ts")],
+                ),
+                declare("younger", "s2", &[("CONSUMES", "/src/c.ts")]),
+            ],
+        ),
+    ]
+    .into_iter()
+    .map(|vector| {
+        vector
+            .generated()
+            .expect("standard scenarios only release granted leases")
+    })
+    .collect()
+}
@@ -0,0 +1,47 @@
+#[cfg(test)]
+mod tests {
+    use crate::conformance::{ConformanceVector, StepOutcome, standard_vectors};
+    use std::path::Path;
+
+    #[test]
+    fn test_published_vectors_match_kernel() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../conformance/vectors");
+        for vector in standard_vectors() {
+            let path = dir.join(format!("{}.json", vector.name));
+            let contents = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            let published: ConformanceVector = serde_json::from_str(&contents).unwrap();
+            assert_eq!(
+                published, vector,
+                "{} is stale; regenerate with `klock conformance generate`",
+                vector.name
+            );
+            published.check().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_check_reports_drift() {
+        let mut vector = standard_vectors()
+            .into_iter()
+            .find(|v| v.name == "release_frees_resource")
+            .unwrap();
+        assert!(vector.leases_only());
+        assert_eq!(
+            vector.steps[1].expect,
+            Some(StepOutcome {
+                success: false,
+                reason: Some("DIE".to_string()),
+            })
+        );
+
+        vector.steps[1].expect = Some(StepOutcome {
+            success: true,
+            reason: None,
+        });
+        assert_eq!(
+            vector.check().unwrap_err(),
+            "release_frees_resource: step 1 expected success, got DIE"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Symbol-to-file containment.
+//!
+//! A bare `SYMBOL` resource like `User.authenticate` does not say which file
+//! defines it, so on its own it never overlaps a `FILE` lease. A
+//! `ContainmentRegistry` records where symbols live; the `ConflictEngine`
+//! qualifies bare symbols through it (`User.authenticate` →
+//! `/src/user.ts#User.authenticate`) before matching, so deleting the file
+//! conflicts with mutating the symbol.
+
+use crate::types::{ResourceRef, ResourceType};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Maps symbols to the files that define them. Members inherit the file of
+/// their closest registered parent: registering `User` also places
+/// `User.authenticate` in the same file.
+///
+/// Serialized as a plain map:
+///
+/// ```json
+/// { "User": "/src/user.ts", "Billing.charge": "/src/billing.ts" }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<String, String>",
+    into = "BTreeMap<String, String>"
+)]
+pub struct ContainmentRegistry {
+    files: BTreeMap<String, String>,
+}
+
+impl ContainmentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `symbol` is defined in `file`, replacing any previous entry.
+    pub fn insert(&mut self, symbol: &str, file: &str) -> Result<(), String> {
+        validate_entry(symbol, file)?;
+        self.files.insert(symbol.to_string(), file.to_string());
+        Ok(())
+    }
+
+    /// Builder form of `insert`.
+    pub fn with(mut self, symbol: &str, file: &str) -> Result<Self, String> {
+        self.insert(symbol, file)?;
+        Ok(self)
+    }
+
+    pub fn remove(&mut self, symbol: &str) -> bool {
+        self.files.remove(symbol).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The file defining `symbol`, from its own entry or its closest
+    /// registered parent.
+    pub fn file_of(&self, symbol: &str) -> Option<&str> {
+        let mut name = symbol;
+        loop {
+            if let Some(file) = self.files.get(name) {
+                return Some(file);
+            }
+            name = &name[..name.rfind('.')?];
+        }
+    }
+
+    /// The file-qualified form of a bare `SYMBOL` resource, if its file is
+    /// known. Other resources are never rewritten.
+    pub fn qualify(&self, resource: &ResourceRef) -> Option<ResourceRef> {
+        if resource.resource_type != ResourceType::Symbol || resource.path.contains('#') {
+            return None;
+        }
+        let file = self.file_of(&resource.path)?;
+        Some(ResourceRef::new(
+            ResourceType::Symbol,
+            format!("{}#{}", file, resource.path),
+        ))
+    }
+}
+
+fn validate_entry(symbol: &str, file: &str) -> Result<(), String> {
+    if symbol.is_empty() || symbol.contains('#') {
+        return Err(format!(
+            "Invalid symbol '{}': must be a bare (unqualified) name",
+            symbol
+        ));
+    }
+    if file.is_empty() || file.ends_with('/') || file.contains('#') {
+        return Err(format!(
+            "Invalid file '{}' for symbol '{}': must be a file path",
+            file, symbol
+        ));
+    }
+    Ok(())
+}
+
+impl TryFrom<BTreeMap<String, String>> for ContainmentRegistry {
+    type Error = String;
+
+    fn try_from(files: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        for (symbol, file) in &files {
+            validate_entry(symbol, file)?;
+        }
+        Ok(Self { files })
+    }
+}
+
+impl From<ContainmentRegistry> for BTreeMap<String, String> {
+    fn from(registry: ContainmentRegistry) -> Self {
+        registry.files
+    }
+}
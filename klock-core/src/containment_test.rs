@@ -0,0 +1,189 @@
+#[cfg(test)]
+mod tests {
+    use crate::conflict::{ConflictEngine, ConflictResult};
+    use crate::containment::ContainmentRegistry;
+    use crate::implication::ImplicationRule;
+    use crate::infrastructure::LeaseStore;
+    use crate::infrastructure_in_memory::InMemoryLeaseStore;
+    use crate::types::{Lease, LeaseResult, Predicate, ResourceRef, ResourceType};
+    use std::sync::Arc;
+
+    fn registry() -> ContainmentRegistry {
+        ContainmentRegistry::new()
+            .with("User", "/src/user.ts")
+            .unwrap()
+            .with("Billing.charge", "/src/billing.ts")
+            .unwrap()
+    }
+
+    fn symbol(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::Symbol, path)
+    }
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn lease(agent_id: &str, resource: ResourceRef, predicate: Predicate) -> Lease {
+        Lease::new(
+            format!("l_{}", agent_id),
+            agent_id.to_string(),
+            "s_holder".to_string(),
+            resource,
+            predicate,
+            5000,
+            1000,
+        )
+    }
+
+    #[test]
+    fn test_file_of_inherits_from_parent() {
+        let registry = registry();
+        assert_eq!(registry.file_of("User"), Some("/src/user.ts"));
+        assert_eq!(registry.file_of("User.authenticate"), Some("/src/user.ts"));
+        assert_eq!(registry.file_of("Billing.charge"), Some("/src/billing.ts"));
+        assert_eq!(registry.file_of("Billing"), None);
+        assert_eq!(registry.file_of("Username"), None);
+
+        assert_eq!(
+            registry.qualify(&symbol("User.authenticate")),
+            Some(symbol("/src/user.ts#User.authenticate"))
+        );
+        // Already-qualified symbols and other resource types are left alone
+        assert_eq!(registry.qualify(&symbol("/lib/user.ts#User")), None);
+        assert_eq!(registry.qualify(&file("User")), None);
+    }
+
+    #[test]
+    fn test_invalid_entries_rejected() {
+        let mut registry = ContainmentRegistry::new();
+        assert!(registry.insert("", "/src/a.ts").is_err());
+        assert!(registry.insert("/src/a.ts#A", "/src/a.ts").is_err());
+        assert!(registry.insert("A", "/src/").is_err());
+        assert!(registry.is_empty());
+
+        let parsed: Result<ContainmentRegistry, _> =
+            serde_json::from_str(r#"{ "User": "/src/user.ts", "Bad": "" }"#);
+        assert!(parsed.is_err());
+        let parsed: ContainmentRegistry =
+            serde_json::from_str(r#"{ "User": "/src/user.ts" }"#).unwrap();
+        assert_eq!(parsed.len(), 1);
+    }
+
+    #[test]
+    fn test_symbol_conflicts_with_containing_file() {
+        let held = [lease("holder", file("/src/user.ts"), Predicate::Deletes)];
+
+        // Without a registry the bare symbol is unrelated to the file
+        assert_eq!(
+            ConflictEngine::default().check_leases(
+                "agent",
+                "s1",
+                Predicate::Mutates,
+                &symbol("User.authenticate"),
+                &held
+            ),
+            ConflictResult::Ok
+        );
+
+        let engine = ConflictEngine::default().with_containment(registry());
+        assert!(matches!(
+            engine.check_leases(
+                "agent",
+                "s1",
+                Predicate::Mutates,
+                &symbol("User.authenticate"),
+                &held
+            ),
+            ConflictResult::Conflict { .. }
+        ));
+        assert_eq!(
+            engine.check_leases(
+                "agent",
+                "s1",
+                Predicate::Mutates,
+                &symbol("Billing.refund"),
+                &held
+            ),
+            ConflictResult::Ok
+        );
+
+        // Bare and qualified names for the same symbol match
+        let held = [lease(
+            "holder",
+            symbol("/src/user.ts#User.login"),
+            Predicate::Mutates,
+        )];
+        assert!(matches!(
+            engine.check_leases("agent", "s1", Predicate::Mutates, &symbol("User"), &held),
+            ConflictResult::Conflict { .. }
+        ));
+    }
+
+    #[test]
+    fn test_implications_follow_containment() {
+        let engine = ConflictEngine::default()
+            .with_implication(ImplicationRule::provides_mutate_containing_file())
+            .with_containment(registry());
+
+        let implied = engine.implied(Predicate::Provides, &symbol("User.logout"));
+        assert_eq!(implied.len(), 1);
+        assert_eq!(implied[0].resource, file("/src/user.ts"));
+
+        let held = [lease("holder", file("/src/user.ts"), Predicate::Consumes)];
+        assert!(matches!(
+            engine.check_leases(
+                "agent",
+                "s1",
+                Predicate::Provides,
+                &symbol("User.logout"),
+                &held
+            ),
+            ConflictResult::Conflict { .. }
+        ));
+    }
+
+    #[test]
+    fn test_store_detects_cross_type_conflict() {
+        let mut store = InMemoryLeaseStore::new();
+        store.set_conflict_engine(Arc::new(
+            ConflictEngine::default().with_containment(registry()),
+        ));
+        store.register_agent_priority("older".to_string(), 100);
+        store.register_agent_priority("younger".to_string(), 200);
+
+        assert!(matches!(
+            store.acquire(
+                "older",
+                "s1",
+                symbol("User.authenticate"),
+                Predicate::Mutates,
+                5000,
+                1000
+            ),
+            LeaseResult::Success { .. }
+        ));
+        assert!(matches!(
+            store.acquire(
+                "younger",
+                "s2",
+                file("/src/user.ts"),
+                Predicate::Deletes,
+                5000,
+                1000
+            ),
+            LeaseResult::Failure { .. }
+        ));
+        assert!(matches!(
+            store.acquire(
+                "younger",
+                "s2",
+                file("/src/billing.ts"),
+                Predicate::Deletes,
+                5000,
+                1000
+            ),
+            LeaseResult::Success { .. }
+        ));
+    }
+}
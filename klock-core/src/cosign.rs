@@ -0,0 +1,262 @@
+//! Co-signed leases: four-eyes approval for destructive operations.
+//!
+//! Some operations are too costly to get wrong on one agent's say-so:
+//! deleting infrastructure definitions, renaming a public API. A
+//! `CosignRule` protects a resource pattern: an acquire of one of its
+//! predicates (`DELETES` and `RENAMES` by default) does not grant a lease
+//! but creates a pending one, refused with `CosignRequired`, until another
+//! agent from the rule's co-signers approves it with `KlockClient::cosign`.
+//! Approval activates the lease as a reservation would be activated: the
+//! pending lease blocks nobody, and wins against leases granted after it
+//! was requested. A request not co-signed within the rule's timeout
+//! expires with its pending lease. Requests, approvals and expiries are
+//! recorded on the agents' timelines.
+
+use crate::codes::ReasonCode;
+use crate::types::{Lease, Predicate, ResourceRef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How long a request waits for a co-signer unless its rule says otherwise
+pub const DEFAULT_COSIGN_TIMEOUT_MS: u64 = 300_000;
+
+fn protected_predicates() -> Vec<Predicate> {
+    vec![Predicate::Deletes, Predicate::Renames]
+}
+
+fn default_timeout_ms() -> u64 {
+    DEFAULT_COSIGN_TIMEOUT_MS
+}
+
+/// Operations on a resource pattern that need a second agent's approval
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CosignRule {
+    pub name: String,
+    /// The protected resource; directories and glob patterns protect
+    /// everything they cover
+    pub resource: ResourceRef,
+    /// The predicates that need approval
+    #[serde(default = "protected_predicates")]
+    pub predicates: Vec<Predicate>,
+    /// Agents that may approve; never the requester itself
+    pub cosigners: Vec<String>,
+    /// How long a request waits for approval, in ms
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl CosignRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("Co-signing rule name is required".to_string());
+        }
+        if self.resource.path.is_empty() {
+            return Err(format!(
+                "Co-signing rule '{}' has an empty resource path",
+                self.name
+            ));
+        }
+        if self.predicates.is_empty() {
+            return Err(format!(
+                "Co-signing rule '{}' protects no predicate",
+                self.name
+            ));
+        }
+        if self.cosigners.is_empty() {
+            return Err(format!("Co-signing rule '{}' has no co-signers", self.name));
+        }
+        if self.timeout_ms == 0 {
+            return Err(format!(
+                "Co-signing rule '{}' must have a positive timeout_ms",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether an acquire of `predicate` on `resource` needs approval
+    pub fn covers(&self, predicate: Predicate, resource: &ResourceRef) -> bool {
+        self.predicates.contains(&predicate) && self.resource.overlaps(resource)
+    }
+}
+
+/// A lease waiting for, or granted, a co-signer's approval
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CosignRequest {
+    /// The pending lease
+    pub lease_id: String,
+    /// The rule that asked for approval
+    pub rule: String,
+    pub agent_id: String,
+    pub session_id: String,
+    pub resource: ResourceRef,
+    pub predicate: Predicate,
+    pub requested_at: u64,
+    /// When the request expires unless activated
+    pub expires_at: u64,
+    /// Who may approve, as the rule said when the request was made
+    pub cosigners: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cosigned_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cosigned_at: Option<u64>,
+}
+
+/// Why a co-signature was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CosignError {
+    /// No request awaits approval for the lease: it was never protected,
+    /// was already activated, or expired
+    NotPending,
+    /// Agents cannot approve their own requests
+    OwnRequest,
+    /// The agent is not among the request's co-signers
+    NotAuthorized { rule: String },
+}
+
+impl CosignError {
+    /// The catalog code reported for the refusal
+    pub fn code(&self) -> ReasonCode {
+        match self {
+            CosignError::NotPending => ReasonCode::LeaseNotFound,
+            CosignError::OwnRequest | CosignError::NotAuthorized { .. } => {
+                ReasonCode::CosignRefused
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CosignError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CosignError::NotPending => write!(f, "no co-signing request is pending for the lease"),
+            CosignError::OwnRequest => write!(f, "agents cannot co-sign their own requests"),
+            CosignError::NotAuthorized { rule } => {
+                write!(f, "not a co-signer of rule '{}'", rule)
+            }
+        }
+    }
+}
+
+/// Co-signing rules and the requests made under them
+#[derive(Debug, Clone, Default)]
+pub struct CosignRegistry {
+    rules: Vec<CosignRule>,
+    requests: HashMap<String, CosignRequest>,
+}
+
+impl CosignRegistry {
+    /// Replace the rules, rejecting invalid rules and duplicate names.
+    /// Pending requests keep the co-signers they were made with.
+    pub fn set_rules(&mut self, rules: Vec<CosignRule>) -> Result<(), String> {
+        for (i, rule) in rules.iter().enumerate() {
+            rule.validate()?;
+            if rules[..i].iter().any(|r| r.name == rule.name) {
+                return Err(format!("Co-signing rule '{}' is defined twice", rule.name));
+            }
+        }
+        self.rules = rules;
+        Ok(())
+    }
+
+    pub fn rules(&self) -> &[CosignRule] {
+        &self.rules
+    }
+
+    /// The first rule that protects `predicate` on `resource`
+    pub fn rule_for(&self, predicate: Predicate, resource: &ResourceRef) -> Option<&CosignRule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.covers(predicate, resource))
+    }
+
+    /// Record that the pending `lease` awaits approval under `rule`
+    pub fn request(&mut self, rule: &CosignRule, lease: &Lease, now: u64) -> CosignRequest {
+        let request = CosignRequest {
+            lease_id: lease.id.clone(),
+            rule: rule.name.clone(),
+            agent_id: lease.agent_id.clone(),
+            session_id: lease.session_id.clone(),
+            resource: lease.resource.clone(),
+            predicate: lease.predicate,
+            requested_at: now,
+            expires_at: lease.expires_at,
+            cosigners: rule.cosigners.clone(),
+            cosigned_by: None,
+            cosigned_at: None,
+        };
+        self.requests.insert(lease.id.clone(), request.clone());
+        request
+    }
+
+    pub fn get(&self, lease_id: &str) -> Option<&CosignRequest> {
+        self.requests.get(lease_id)
+    }
+
+    /// Whether the lease still needs a co-signer before it can activate
+    pub fn awaits_approval(&self, lease_id: &str) -> bool {
+        self.requests
+            .get(lease_id)
+            .is_some_and(|request| request.cosigned_by.is_none())
+    }
+
+    /// Approve the request for `lease_id` as `cosigner` at `now`
+    pub fn approve(
+        &mut self,
+        lease_id: &str,
+        cosigner: &str,
+        now: u64,
+    ) -> Result<CosignRequest, CosignError> {
+        let request = self
+            .requests
+            .get_mut(lease_id)
+            .filter(|request| request.expires_at >= now)
+            .ok_or(CosignError::NotPending)?;
+        if request.agent_id == cosigner {
+            return Err(CosignError::OwnRequest);
+        }
+        if !request.cosigners.iter().any(|c| c == cosigner) {
+            return Err(CosignError::NotAuthorized {
+                rule: request.rule.clone(),
+            });
+        }
+        request.cosigned_by = Some(cosigner.to_string());
+        request.cosigned_at = Some(now);
+        Ok(request.clone())
+    }
+
+    /// Forget the request for `lease_id`, once activated or withdrawn
+    pub fn remove(&mut self, lease_id: &str) -> Option<CosignRequest> {
+        self.requests.remove(lease_id)
+    }
+
+    /// Remove and return the requests expired at `now`, oldest first
+    pub fn take_expired(&mut self, now: u64) -> Vec<CosignRequest> {
+        let mut expired: Vec<CosignRequest> = self
+            .requests
+            .values()
+            .filter(|request| request.expires_at < now)
+            .cloned()
+            .collect();
+        expired.sort_by(|a, b| (a.requested_at, &a.lease_id).cmp(&(b.requested_at, &b.lease_id)));
+        for request in &expired {
+            self.requests.remove(&request.lease_id);
+        }
+        expired
+    }
+
+    /// Every request not yet activated, oldest first
+    pub fn pending(&self) -> Vec<CosignRequest> {
+        let mut pending: Vec<CosignRequest> = self.requests.values().cloned().collect();
+        pending.sort_by(|a, b| (a.requested_at, &a.lease_id).cmp(&(b.requested_at, &b.lease_id)));
+        pending
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+}
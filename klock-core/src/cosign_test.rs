@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod tests {
+    use crate::activity::{ActivityKind, TimelineQuery};
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::codes::ReasonCode;
+    use crate::cosign::{CosignError, CosignRegistry, CosignRule};
+    use crate::types::{
+        LeaseFailureReason, LeaseResult, LeaseState, Predicate, ResourceRef, ResourceType,
+    };
+    use std::sync::Arc;
+
+    fn rule(name: &str, path: &str) -> CosignRule {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "resource": {"resource_type": "File", "path": path},
+            "cosigners": ["lead", "ops"],
+            "timeout_ms": 10_000,
+        }))
+        .unwrap()
+    }
+
+    fn client() -> (KlockClient, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("dev", 100);
+        client.register_agent("lead", 200);
+        client.register_agent("ops", 300);
+        client
+            .set_cosign_rules(vec![rule("infra", "/infra/**")])
+            .unwrap();
+        (client, clock)
+    }
+
+    fn pending_id(result: &LeaseResult) -> String {
+        match result {
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::CosignRequired,
+                request_id: Some(id),
+                existing_lease: Some(lease),
+                ..
+            } => {
+                assert_eq!(lease.state, LeaseState::Pending);
+                id.clone()
+            }
+            other => panic!("Expected a co-signing request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rules_protect_deletes_and_renames_under_their_pattern() {
+        let rule = rule("infra", "/infra/**");
+        assert_eq!(rule.predicates, [Predicate::Deletes, Predicate::Renames]);
+        let main = ResourceRef::new(ResourceType::File, "/infra/prod/main.tf");
+        assert!(rule.covers(Predicate::Deletes, &main));
+        assert!(rule.covers(Predicate::Renames, &main));
+        assert!(!rule.covers(Predicate::Mutates, &main));
+        let readme = ResourceRef::new(ResourceType::File, "/README.md");
+        assert!(!rule.covers(Predicate::Deletes, &readme));
+
+        let mut registry = CosignRegistry::default();
+        let invalid = CosignRule {
+            cosigners: Vec::new(),
+            ..rule.clone()
+        };
+        assert!(registry.set_rules(vec![invalid]).is_err());
+        assert!(
+            registry
+                .set_rules(vec![rule.clone(), rule.clone()])
+                .is_err()
+        );
+        assert!(registry.rules().is_empty());
+        registry.set_rules(vec![rule]).unwrap();
+        assert!(registry.rule_for(Predicate::Deletes, &main).is_some());
+
+        assert_eq!(CosignError::NotPending.code(), ReasonCode::LeaseNotFound);
+        assert_eq!(CosignError::OwnRequest.code(), ReasonCode::CosignRefused);
+    }
+
+    #[test]
+    fn test_cosigned_leases_activate_with_an_audit_trail() {
+        let (mut client, _clock) = client();
+        // Unprotected operations are granted as usual
+        let edit = client.acquire_lease("dev", "s1", "FILE", "/infra/main.tf", "MUTATES", 60_000);
+        assert!(matches!(edit, LeaseResult::Success { .. }));
+        client.release_lease(&client.get_active_leases()[0].id.clone());
+
+        let result = client.acquire_lease("dev", "s1", "FILE", "/infra/main.tf", "DELETES", 60_000);
+        let lease_id = pending_id(&result);
+        assert!(client.get_active_leases().is_empty());
+        assert!(
+            client
+                .pre_acquire(
+                    "dev",
+                    "s1",
+                    &[(
+                        ResourceRef::new(ResourceType::File, "/infra/vpc.tf"),
+                        Predicate::Deletes
+                    )],
+                    60_000,
+                    5_000,
+                )
+                .is_err()
+        );
+
+        // Nobody activates it without a co-signer's approval
+        assert!(matches!(
+            client.activate_lease(&lease_id),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::CosignRequired,
+                ..
+            }
+        ));
+        assert_eq!(
+            client.cosign(&lease_id, "dev").unwrap_err(),
+            CosignError::OwnRequest
+        );
+        assert_eq!(
+            client.cosign(&lease_id, "stranger").unwrap_err(),
+            CosignError::NotAuthorized {
+                rule: "infra".to_string()
+            }
+        );
+        assert_eq!(client.pending_cosigns().len(), 1);
+
+        let result = client.cosign(&lease_id, "lead").unwrap();
+        assert!(matches!(result, LeaseResult::Success { lease } if lease.id == lease_id));
+        assert!(client.pending_cosigns().is_empty());
+        assert_eq!(
+            client.cosign(&lease_id, "ops").unwrap_err(),
+            CosignError::NotPending
+        );
+
+        let kinds = |client: &KlockClient, agent: &str| -> Vec<(ActivityKind, Option<String>)> {
+            client
+                .agent_timeline(agent, &TimelineQuery::default())
+                .into_iter()
+                .filter(|entry| entry.lease_id.as_deref() == Some(lease_id.as_str()))
+                .map(|entry| (entry.kind, entry.outcome))
+                .collect()
+        };
+        assert_eq!(
+            kinds(&client, "dev"),
+            [
+                (
+                    ActivityKind::CosignRequested,
+                    Some("rule infra".to_string())
+                ),
+                (ActivityKind::Cosigned, Some("by lead".to_string())),
+                (ActivityKind::Acquired, None),
+            ]
+        );
+        assert_eq!(
+            kinds(&client, "lead"),
+            [(ActivityKind::Cosigned, Some("for dev".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_requests_expire_without_a_cosigner() {
+        let (mut client, clock) = client();
+        let result = client.acquire_lease("dev", "s1", "FILE", "/infra/main.tf", "RENAMES", 60_000);
+        let lease_id = pending_id(&result);
+
+        clock.advance(10_001);
+        assert!(client.pending_cosigns().is_empty());
+        assert_eq!(
+            client.cosign(&lease_id, "lead").unwrap_err(),
+            CosignError::NotPending
+        );
+        assert!(!matches!(
+            client.activate_lease(&lease_id),
+            LeaseResult::Success { .. }
+        ));
+        let expired = client.agent_timeline(
+            "dev",
+            &TimelineQuery {
+                kind: Some(ActivityKind::CosignExpired),
+                ..TimelineQuery::default()
+            },
+        );
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].lease_id.as_deref(), Some(lease_id.as_str()));
+    }
+}
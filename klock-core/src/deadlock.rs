@@ -0,0 +1,131 @@
+//! The wait-for graph, for multi-resource deadlock detection.
+//!
+//! Wait-Die keeps agents contending for one resource from deadlocking: a
+//! request waits only on younger holders. Agents waiting on several
+//! resources at once can still end up in a cycle, though, when priorities
+//! tie or shift with aging, or when a waiter is held up by the queued
+//! requests ahead of it. The wait-for graph has an edge from each waiting
+//! agent to each agent it waits on, built from the manifests refused with
+//! Wait and from the queued lease requests (to the holders of conflicting
+//! leases and to the waiters ahead of them). The kernel turns a Wait
+//! verdict whose edges would close a cycle into `Die` with code
+//! `K1008 DEADLOCK`, naming the cycle in the verdict's conflicts.
+//!
+//! Edges are between agents; an agent waiting on itself adds none.
+
+use crate::types::ResourceRef;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// `waiter` waits on `holder` for `resource`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaitForEdge {
+    pub waiter: String,
+    pub holder: String,
+    /// What the holder holds, or has queued for, in the waiter's way
+    pub resource: ResourceRef,
+}
+
+impl WaitForEdge {
+    pub fn new(waiter: &str, holder: &str, resource: ResourceRef) -> Self {
+        Self {
+            waiter: waiter.to_string(),
+            holder: holder.to_string(),
+            resource,
+        }
+    }
+}
+
+/// Who waits on whom
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaitForGraph {
+    edges: Vec<WaitForEdge>,
+}
+
+impl WaitForGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an edge unless it is a self-wait or already recorded.
+    pub fn insert(&mut self, edge: WaitForEdge) {
+        if edge.waiter != edge.holder && !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    pub fn edges(&self) -> &[WaitForEdge] {
+        &self.edges
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// The cycle `added` would close, starting with the added edge that
+    /// closes it, or `None` if the graph stays acyclic.
+    pub fn find_cycle(&self, added: &[WaitForEdge]) -> Option<Vec<WaitForEdge>> {
+        let added: Vec<WaitForEdge> = added
+            .iter()
+            .filter(|e| e.waiter != e.holder)
+            .cloned()
+            .collect();
+        cycle_through(self.edges.iter().chain(&added), &added)
+    }
+
+    /// A cycle already in the graph, if any
+    pub fn any_cycle(&self) -> Option<Vec<WaitForEdge>> {
+        cycle_through(self.edges.iter(), &self.edges)
+    }
+
+    /// A cycle as `a -[FILE:/x]-> b -[FILE:/y]-> a`
+    pub fn describe(cycle: &[WaitForEdge]) -> String {
+        let mut description = cycle.first().map_or_else(String::new, |e| e.waiter.clone());
+        for edge in cycle {
+            description.push_str(&format!(" -[{}]-> {}", edge.resource.key(), edge.holder));
+        }
+        description
+    }
+}
+
+/// The first cycle among `edges` closed by one of `closing`
+fn cycle_through<'a>(
+    edges: impl Iterator<Item = &'a WaitForEdge>,
+    closing: &[WaitForEdge],
+) -> Option<Vec<WaitForEdge>> {
+    let mut outgoing: HashMap<&str, Vec<&WaitForEdge>> = HashMap::new();
+    for edge in edges {
+        outgoing.entry(&edge.waiter).or_default().push(edge);
+    }
+
+    for closer in closing {
+        // Breadth-first from the holder back to the waiter
+        let mut reached_by: HashMap<&str, &WaitForEdge> = HashMap::new();
+        let mut queue = VecDeque::from([closer.holder.as_str()]);
+        while let Some(agent) = queue.pop_front() {
+            if agent == closer.waiter {
+                let mut path = Vec::new();
+                let mut current = agent;
+                while current != closer.holder {
+                    let edge = reached_by[current];
+                    path.push(edge.clone());
+                    current = &edge.waiter;
+                }
+                path.push(closer.clone());
+                path.reverse();
+                return Some(path);
+            }
+            for edge in outgoing.get(agent).into_iter().flatten() {
+                if edge.holder != closer.holder && !reached_by.contains_key(edge.holder.as_str()) {
+                    reached_by.insert(&edge.holder, edge);
+                    queue.push_back(&edge.holder);
+                }
+            }
+        }
+    }
+    None
+}
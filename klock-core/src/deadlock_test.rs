@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::codes::ReasonCode;
+    use crate::deadlock::{WaitForEdge, WaitForGraph};
+    use crate::scheduler::FifoScheduler;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use std::sync::Arc;
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn edge(waiter: &str, holder: &str, path: &str) -> WaitForEdge {
+        WaitForEdge::new(waiter, holder, file(path))
+    }
+
+    fn mutates(agent: &str, path: &str) -> IntentManifest {
+        IntentManifest {
+            session_id: format!("{}_s", agent),
+            agent_id: agent.to_string(),
+            intents: vec![SPOTriple {
+                id: format!("{}_{}", agent, path),
+                subject: agent.to_string(),
+                predicate: Predicate::Mutates,
+                object: file(path),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: format!("{}_s", agent),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_find_cycle_reports_path() {
+        let mut graph = WaitForGraph::new();
+        graph.insert(edge("a", "b", "/b.ts"));
+        graph.insert(edge("b", "c", "/c.ts"));
+        graph.insert(edge("a", "b", "/b.ts"));
+        graph.insert(edge("a", "a", "/a.ts"));
+        assert_eq!(graph.len(), 2);
+        assert!(graph.any_cycle().is_none());
+
+        assert!(graph.find_cycle(&[edge("a", "c", "/c.ts")]).is_none());
+        let cycle = graph.find_cycle(&[edge("c", "a", "/a.ts")]).unwrap();
+        assert_eq!(
+            cycle,
+            vec![
+                edge("c", "a", "/a.ts"),
+                edge("a", "b", "/b.ts"),
+                edge("b", "c", "/c.ts")
+            ]
+        );
+        assert_eq!(
+            WaitForGraph::describe(&cycle),
+            "c -[FILE:/a.ts]-> a -[FILE:/b.ts]-> b -[FILE:/c.ts]-> c"
+        );
+
+        graph.insert(edge("c", "a", "/a.ts"));
+        assert_eq!(graph.any_cycle().map(|c| c.len()), Some(3));
+    }
+
+    #[test]
+    fn test_wait_closing_a_cycle_dies_with_deadlock() {
+        let mut client = KlockClient::new().with_scheduler(Arc::new(FifoScheduler));
+        client.register_agent("a", 100);
+        client.register_agent("b", 200);
+        client.acquire_lease("a", "a_s", "FILE", "/x.ts", "MUTATES", 60_000);
+        client.acquire_lease("b", "b_s", "FILE", "/y.ts", "MUTATES", 60_000);
+
+        let verdict = client.declare_intent(&mutates("a", "/y.ts"));
+        assert_eq!(verdict.status, KernelVerdictStatus::Wait);
+        assert_eq!(client.wait_for_graph().edges(), &[edge("a", "b", "/y.ts")]);
+
+        let verdict = client.declare_intent(&mutates("b", "/x.ts"));
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(verdict.code, Some(ReasonCode::Deadlock));
+        assert_eq!(verdict.held_by.as_deref(), Some("a"));
+        assert!(
+            verdict
+                .conflicts
+                .contains(&"Deadlock: b -[FILE:/x.ts]-> a -[FILE:/y.ts]-> b".to_string()),
+            "{:?}",
+            verdict.conflicts
+        );
+        // The refused agent is not recorded as waiting
+        assert_eq!(client.wait_for_graph().len(), 1);
+        assert!(client.find_deadlock().is_none());
+    }
+
+    #[test]
+    fn test_queued_lease_requests_form_a_deadlock() {
+        let mut client = KlockClient::new().with_scheduler(Arc::new(FifoScheduler));
+        client.register_agent("a", 100);
+        client.register_agent("b", 200);
+        client.acquire_lease("a", "a_s", "FILE", "/x.ts", "MUTATES", 60_000);
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("b", "b_s", "FILE", "/y.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        for (agent, path) in [("a", "/y.ts"), ("b", "/x.ts")] {
+            let result = client.acquire_lease(
+                agent,
+                &format!("{}_s", agent),
+                "FILE",
+                path,
+                "MUTATES",
+                60_000,
+            );
+            assert!(matches!(
+                result,
+                LeaseResult::Failure {
+                    reason: LeaseFailureReason::Wait,
+                    ..
+                }
+            ));
+        }
+
+        let cycle = client.find_deadlock().unwrap();
+        assert_eq!(cycle.len(), 2);
+        assert_eq!(
+            WaitForGraph::describe(&cycle),
+            "b -[FILE:/x.ts]-> a -[FILE:/y.ts]-> b"
+        );
+
+        // Releasing one side grants a's request and breaks the cycle
+        assert!(client.release_lease(&lease.id));
+        assert!(client.find_deadlock().is_none());
+    }
+}
@@ -0,0 +1,106 @@
+//! Retry debouncing for lease acquisition.
+//!
+//! Agents that get Die often retry in a tight loop, and every retry costs a
+//! store round-trip. When a minimum interval is configured, an agent whose
+//! acquire for a resource failed must wait that long before trying the same
+//! resource again; earlier attempts are refused with `TooManyRetries`
+//! without touching the store.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Minimum spacing between retries. Disabled by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DebouncePolicy {
+    /// How long after a failed acquire the same (agent, resource) pair is
+    /// refused (`0` = never)
+    pub min_interval_ms: u64,
+}
+
+/// Last failed acquire per (agent, resource key)
+#[derive(Debug, Default)]
+pub struct AcquireDebouncer {
+    policy: DebouncePolicy,
+    failures: HashMap<(String, String), u64>,
+}
+
+impl AcquireDebouncer {
+    pub fn new(policy: DebouncePolicy) -> Self {
+        Self {
+            policy,
+            failures: HashMap::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &DebouncePolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: DebouncePolicy) {
+        self.policy = policy;
+    }
+
+    /// Returns how long the agent must still back off before retrying the
+    /// resource, or `None` if it may try now
+    pub fn backoff_remaining(&self, agent_id: &str, resource_key: &str, now: u64) -> Option<u64> {
+        if self.policy.min_interval_ms == 0 {
+            return None;
+        }
+        let failed_at = self
+            .failures
+            .get(&(agent_id.to_string(), resource_key.to_string()))?;
+        let retry_at = failed_at.saturating_add(self.policy.min_interval_ms);
+        (retry_at > now).then(|| retry_at - now)
+    }
+
+    /// Record an acquire that the store refused
+    pub fn record_failure(&mut self, agent_id: &str, resource_key: &str, now: u64) {
+        if self.policy.min_interval_ms == 0 {
+            return;
+        }
+        self.failures
+            .insert((agent_id.to_string(), resource_key.to_string()), now);
+    }
+
+    /// Forget the pair after a successful acquire
+    pub fn clear(&mut self, agent_id: &str, resource_key: &str) {
+        self.failures
+            .remove(&(agent_id.to_string(), resource_key.to_string()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.failures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Approximate footprint of all retry records
+    pub fn estimated_bytes(&self) -> usize {
+        self.failures
+            .keys()
+            .map(|(agent_id, resource_key)| {
+                agent_id.capacity()
+                    + resource_key.capacity()
+                    + std::mem::size_of::<((String, String), u64)>()
+            })
+            .sum()
+    }
+
+    /// Drop the agent's records. Returns the number removed.
+    pub fn forget(&mut self, agent_id: &str) -> usize {
+        let initial = self.failures.len();
+        self.failures.retain(|(agent, _), _| agent != agent_id);
+        initial - self.failures.len()
+    }
+
+    /// Drop records whose backoff has elapsed. Returns the number removed.
+    pub fn compact(&mut self, now: u64) -> usize {
+        let min_interval_ms = self.policy.min_interval_ms;
+        let initial = self.failures.len();
+        self.failures
+            .retain(|_, failed_at| failed_at.saturating_add(min_interval_ms) > now);
+        initial - self.failures.len()
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::debounce::{AcquireDebouncer, DebouncePolicy};
+    use crate::types::{LeaseFailureReason, LeaseResult};
+
+    fn debouncer(min_interval_ms: u64) -> AcquireDebouncer {
+        AcquireDebouncer::new(DebouncePolicy { min_interval_ms })
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let mut debounce = AcquireDebouncer::default();
+        debounce.record_failure("agent", "FILE:/a", 1000);
+        assert_eq!(debounce.backoff_remaining("agent", "FILE:/a", 1000), None);
+        assert!(debounce.is_empty());
+    }
+
+    #[test]
+    fn test_backoff_after_failure() {
+        let mut debounce = debouncer(500);
+        debounce.record_failure("agent", "FILE:/a", 1000);
+
+        assert_eq!(
+            debounce.backoff_remaining("agent", "FILE:/a", 1200),
+            Some(300)
+        );
+        assert_eq!(debounce.backoff_remaining("agent", "FILE:/a", 1500), None);
+        // Other agents and other resources are unaffected
+        assert_eq!(debounce.backoff_remaining("other", "FILE:/a", 1200), None);
+        assert_eq!(debounce.backoff_remaining("agent", "FILE:/b", 1200), None);
+
+        debounce.clear("agent", "FILE:/a");
+        assert_eq!(debounce.backoff_remaining("agent", "FILE:/a", 1200), None);
+    }
+
+    #[test]
+    fn test_compact_drops_elapsed_records() {
+        let mut debounce = debouncer(500);
+        debounce.record_failure("agent", "FILE:/a", 1000);
+        debounce.record_failure("agent", "FILE:/b", 1400);
+
+        assert_eq!(debounce.compact(1600), 1);
+        assert_eq!(debounce.len(), 1);
+        assert!(
+            debounce
+                .backoff_remaining("agent", "FILE:/b", 1600)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_client_rejects_fast_retry_after_die() {
+        let mut client = KlockClient::new();
+        client.set_debounce_policy(DebouncePolicy {
+            min_interval_ms: 60_000,
+        });
+        client.register_agent("older", 100);
+        client.register_agent("younger", 200);
+
+        let held = client.acquire_lease("older", "s1", "FILE", "/src/a.ts", "MUTATES", 60_000);
+        assert!(matches!(held, LeaseResult::Success { .. }));
+
+        assert!(matches!(
+            client.acquire_lease("younger", "s2", "FILE", "/src/a.ts", "MUTATES", 60_000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            }
+        ));
+        match client.acquire_lease("younger", "s2", "FILE", "/src/a.ts", "MUTATES", 60_000) {
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::TooManyRetries,
+                wait_time: Some(wait),
+                ..
+            } => assert!(wait > 0 && wait <= 60_000),
+            other => panic!("Expected TooManyRetries, got {:?}", other),
+        }
+
+        // A different resource is not debounced
+        assert!(matches!(
+            client.acquire_lease("younger", "s2", "FILE", "/src/b.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        assert_eq!(client.memory_report().retry_records.count, 1);
+    }
+}
@@ -0,0 +1,148 @@
+//! The `DEPENDS_ON` dependency graph.
+//!
+//! An agent that declares `DEPENDS_ON` on a resource another agent
+//! `PROVIDES` waits on that agent's output, which makes an edge from the
+//! dependent to the provider. When the edges form a cycle, every agent on
+//! it waits on the next and none can finish. The kernel refuses manifests
+//! that would close such a cycle with `KernelVerdictStatus::Rejected`,
+//! naming the path in the verdict's conflicts.
+//!
+//! Edges are between agents; an agent depending on what it provides itself
+//! adds none.
+
+use crate::conflict::ConflictEngine;
+use crate::types::{Predicate, ResourceRef, SPOTriple};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// `dependent` waits on `provider` for `resource`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyEdge {
+    pub dependent: String,
+    pub provider: String,
+    /// The resource the dependent declared
+    pub resource: ResourceRef,
+}
+
+/// Dependency edges between the agents holding intents
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    edges: Vec<DependencyEdge>,
+}
+
+impl DependencyGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an edge unless an identical one is already recorded.
+    pub fn insert(&mut self, edge: DependencyEdge) {
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    /// Drop `edge`, if it is recorded.
+    pub fn remove(&mut self, edge: &DependencyEdge) {
+        self.edges.retain(|recorded| recorded != edge);
+    }
+
+    pub fn edges(&self) -> &[DependencyEdge] {
+        &self.edges
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.edges.clear();
+    }
+
+    /// The edges between `intent` and each of `others`, in either direction
+    pub fn edges_between(
+        engine: &ConflictEngine,
+        intent: &SPOTriple,
+        others: &[SPOTriple],
+    ) -> Vec<DependencyEdge> {
+        others
+            .iter()
+            .filter_map(|other| match (intent.predicate, other.predicate) {
+                (Predicate::DependsOn, Predicate::Provides) => edge(engine, intent, other),
+                (Predicate::Provides, Predicate::DependsOn) => edge(engine, other, intent),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The cycle `added` would close, starting with the added edge that
+    /// closes it, or `None` if the graph stays acyclic.
+    pub fn find_cycle(&self, added: &[DependencyEdge]) -> Option<Vec<DependencyEdge>> {
+        let mut outgoing: HashMap<&str, Vec<&DependencyEdge>> = HashMap::new();
+        for edge in self.edges.iter().chain(added) {
+            outgoing.entry(&edge.dependent).or_default().push(edge);
+        }
+
+        for closing in added {
+            // Breadth-first from the provider back to the dependent
+            let mut reached_by: HashMap<&str, &DependencyEdge> = HashMap::new();
+            let mut queue = VecDeque::from([closing.provider.as_str()]);
+            while let Some(agent) = queue.pop_front() {
+                if agent == closing.dependent {
+                    let mut path = Vec::new();
+                    let mut current = agent;
+                    while current != closing.provider {
+                        let edge = reached_by[current];
+                        path.push(edge.clone());
+                        current = &edge.dependent;
+                    }
+                    path.push(closing.clone());
+                    path.reverse();
+                    return Some(path);
+                }
+                for edge in outgoing.get(agent).into_iter().flatten() {
+                    if edge.provider != closing.provider
+                        && !reached_by.contains_key(edge.provider.as_str())
+                    {
+                        reached_by.insert(&edge.provider, edge);
+                        queue.push_back(&edge.provider);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// A cycle as `a -[FILE:/x]-> b -[FILE:/y]-> a`
+    pub fn describe(cycle: &[DependencyEdge]) -> String {
+        let mut description = cycle
+            .first()
+            .map_or_else(String::new, |e| e.dependent.clone());
+        for edge in cycle {
+            description.push_str(&format!(" -[{}]-> {}", edge.resource.key(), edge.provider));
+        }
+        description
+    }
+}
+
+/// The edge from `dependent`'s `DEPENDS_ON` intent to `provider`'s
+/// `PROVIDES` intent, if they are different agents on overlapping resources
+fn edge(
+    engine: &ConflictEngine,
+    dependent: &SPOTriple,
+    provider: &SPOTriple,
+) -> Option<DependencyEdge> {
+    (dependent.subject != provider.subject
+        && engine
+            .qualify(&dependent.object)
+            .overlaps(&engine.qualify(&provider.object)))
+    .then(|| DependencyEdge {
+        dependent: dependent.subject.clone(),
+        provider: provider.subject.clone(),
+        resource: dependent.object.clone(),
+    })
+}
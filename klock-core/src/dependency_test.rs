@@ -0,0 +1,171 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::codes::ReasonCode;
+    use crate::dependency::{DependencyEdge, DependencyGraph};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, Predicate, ResourceRef, ResourceType, SPOTriple};
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn edge(dependent: &str, provider: &str, path: &str) -> DependencyEdge {
+        DependencyEdge {
+            dependent: dependent.to_string(),
+            provider: provider.to_string(),
+            resource: file(path),
+        }
+    }
+
+    fn manifest(agent: &str, intents: &[(Predicate, &str)]) -> IntentManifest {
+        IntentManifest {
+            session_id: format!("{}_s", agent),
+            agent_id: agent.to_string(),
+            intents: intents
+                .iter()
+                .enumerate()
+                .map(|(i, (predicate, path))| SPOTriple {
+                    id: format!("{}_{}", agent, i),
+                    subject: agent.to_string(),
+                    predicate: *predicate,
+                    object: file(path),
+                    timestamp: 1000,
+                    confidence: Confidence::High,
+                    session_id: format!("{}_s", agent),
+                    renamed_to: None,
+                    expires_at: None,
+                    long_lived: false,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_find_cycle_reports_path() {
+        let mut graph = DependencyGraph::new();
+        graph.insert(edge("a", "b", "/b.ts"));
+        graph.insert(edge("b", "c", "/c.ts"));
+        graph.insert(edge("a", "b", "/b.ts"));
+        assert_eq!(graph.len(), 2);
+
+        assert!(graph.find_cycle(&[edge("a", "c", "/c.ts")]).is_none());
+        let cycle = graph.find_cycle(&[edge("c", "a", "/a.ts")]).unwrap();
+        assert_eq!(
+            cycle,
+            vec![
+                edge("c", "a", "/a.ts"),
+                edge("a", "b", "/b.ts"),
+                edge("b", "c", "/c.ts")
+            ]
+        );
+        assert_eq!(
+            DependencyGraph::describe(&cycle),
+            "c -[FILE:/a.ts]-> a -[FILE:/b.ts]-> b -[FILE:/c.ts]-> c"
+        );
+    }
+
+    #[test]
+    fn test_client_rejects_manifest_closing_a_cycle() {
+        let mut client = KlockClient::new();
+        for (agent, priority) in [("a", 100), ("b", 200), ("c", 300)] {
+            client.register_agent(agent, priority);
+        }
+
+        let granted = |client: &mut KlockClient, m: IntentManifest| {
+            let verdict = client.declare_intent(&m);
+            assert_eq!(
+                verdict.status,
+                KernelVerdictStatus::Granted,
+                "{:?}",
+                verdict
+            );
+        };
+        granted(
+            &mut client,
+            manifest("a", &[(Predicate::Provides, "/a.ts")]),
+        );
+        granted(
+            &mut client,
+            manifest(
+                "b",
+                &[
+                    (Predicate::DependsOn, "/a.ts"),
+                    (Predicate::Provides, "/b.ts"),
+                ],
+            ),
+        );
+        // Depending on a dependent is fine as long as nothing loops back
+        granted(
+            &mut client,
+            manifest("c", &[(Predicate::DependsOn, "/b.ts")]),
+        );
+        assert_eq!(client.dependency_graph().len(), 2);
+
+        let verdict = client.declare_intent(&manifest(
+            "a",
+            &[
+                (Predicate::Mutates, "/z.ts"),
+                (Predicate::DependsOn, "/b.ts"),
+            ],
+        ));
+        assert_eq!(verdict.status, KernelVerdictStatus::Rejected);
+        // Only the dependency closing the cycle is to blame
+        let per_intent: Vec<_> = verdict
+            .per_intent
+            .iter()
+            .map(|v| (v.intent_id.as_str(), v.status.clone()))
+            .collect();
+        assert_eq!(
+            per_intent,
+            [
+                ("a_0", KernelVerdictStatus::Granted),
+                ("a_1", KernelVerdictStatus::Rejected),
+            ]
+        );
+        assert_eq!(verdict.per_intent[1].conflicts, verdict.conflicts);
+        assert_eq!(verdict.code, Some(ReasonCode::DependencyCycle));
+        assert_eq!(
+            verdict.conflicts,
+            vec!["Dependency cycle: a -[FILE:/b.ts]-> b -[FILE:/a.ts]-> a".to_string()]
+        );
+        assert_eq!(verdict.held_by.as_deref(), Some("b"));
+        assert_eq!(client.dependency_graph().len(), 2);
+
+        // Once b drops its dependency, the same manifest goes through
+        assert!(!client.release_lease("b_0"));
+        assert_eq!(client.dependency_graph().len(), 1);
+        granted(
+            &mut client,
+            manifest("a", &[(Predicate::DependsOn, "/b.ts")]),
+        );
+    }
+
+    #[test]
+    fn test_withdrawing_an_intent_keeps_edges_other_intents_make() {
+        let mut client = KlockClient::new();
+        client.register_agent("a", 100);
+        client.register_agent("b", 200);
+        for m in [
+            manifest(
+                "a",
+                &[
+                    (Predicate::Provides, "/a.ts"),
+                    (Predicate::Provides, "/a.ts"),
+                ],
+            ),
+            manifest("b", &[(Predicate::DependsOn, "/a.ts")]),
+        ] {
+            assert_eq!(
+                client.declare_intent(&m).status,
+                KernelVerdictStatus::Granted
+            );
+        }
+        assert_eq!(client.dependency_graph().edges(), [edge("b", "a", "/a.ts")]);
+
+        assert!(client.withdraw_intent("a_0").is_some());
+        assert_eq!(client.dependency_graph().edges(), [edge("b", "a", "/a.ts")]);
+        assert!(client.withdraw_intent("a_1").is_some());
+        assert!(client.dependency_graph().is_empty());
+    }
+}
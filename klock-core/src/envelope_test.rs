@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use crate::audit::DecisionRecord;
+    use crate::event::KlockEvent;
+    use crate::scheduler::VerdictStatus;
+    use crate::types::{
+        EVENT_SCHEMA_VERSION, EventEnvelope, EventPayload, Predicate, ResourceRef, ResourceType,
+    };
+    use serde_json::json;
+
+    fn decision() -> DecisionRecord {
+        DecisionRecord {
+            at: 900,
+            scheduler: "wait-die".to_string(),
+            agent_id: "junior".to_string(),
+            session_id: "s1".to_string(),
+            predicate: Predicate::Mutates,
+            resource: ResourceRef::new(ResourceType::File, "/src/a.ts"),
+            verdict: VerdictStatus::Die,
+            reason: None,
+            holders: Vec::new(),
+            priorities: Default::default(),
+            preempted: Vec::new(),
+            retry_after_ms: None,
+        }
+    }
+
+    #[test]
+    fn test_envelopes_share_one_schema() {
+        let renewed = EventEnvelope::lease_change(
+            &KlockEvent::LeaseRenewed {
+                lease_id: "l1".to_string(),
+                at: 1500,
+            },
+            None,
+            2000,
+        );
+        assert_eq!(
+            serde_json::to_value(&renewed).unwrap(),
+            json!({
+                "schema_version": EVENT_SCHEMA_VERSION,
+                "occurred_at": 1500,
+                "recorded_at": 2000,
+                "type": "lease_change",
+                "payload": {"change": {"type": "lease_renewed", "lease_id": "l1", "at": 1500}},
+            })
+        );
+
+        let decided = EventEnvelope::new(EventPayload::SchedulerDecision(decision()), 1000);
+        assert_eq!(decided.event_type(), "scheduler_decision");
+        assert_eq!(decided.actor.as_deref(), Some("junior"));
+        assert_eq!((decided.occurred_at, decided.recorded_at), (900, 1000));
+        let json = serde_json::to_value(&decided).unwrap();
+        assert_eq!(json["type"], "scheduler_decision");
+        assert_eq!(json["payload"]["verdict"], "Die");
+        let decoded: EventEnvelope = serde_json::from_value(json).unwrap();
+        assert!(
+            matches!(decoded.event, EventPayload::SchedulerDecision(record) if record == decision())
+        );
+    }
+
+    #[test]
+    fn test_older_builds_read_newer_events_of_the_same_version() {
+        // An event type and fields this build does not know
+        let envelope: EventEnvelope = serde_json::from_value(json!({
+            "schema_version": EVENT_SCHEMA_VERSION,
+            "occurred_at": 1000,
+            "recorded_at": 1000,
+            "type": "webhook_delivered",
+            "payload": {"url": "https://example.com/hook"},
+            "trace_id": "abc",
+        }))
+        .unwrap();
+        assert!(matches!(envelope.event, EventPayload::Unknown {}));
+        assert!(envelope.actor.is_none());
+        assert!(envelope.check_version().is_ok());
+
+        let mut newer = EventEnvelope::new(
+            EventPayload::NamespaceReaped {
+                namespace: "ci-1".to_string(),
+            },
+            1000,
+        );
+        newer.schema_version = EVENT_SCHEMA_VERSION + 1;
+        assert!(newer.check_version().is_err());
+    }
+}
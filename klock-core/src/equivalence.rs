@@ -0,0 +1,116 @@
+//! Cross-resource-type equivalences.
+//!
+//! Some resources are different views of the same thing: the `CONFIG_KEY`
+//! `app.port` lives in the `FILE` `/config/app.toml`, and an agent editing
+//! the file races an agent mutating the key. An `EquivalenceMap` declares
+//! such pairs; the `ConflictEngine` expands every resource to its
+//! equivalents before matching, so a lease on either side blocks
+//! conflicting operations on the other.
+//!
+//! A resource inherits the equivalences of everything containing it:
+//! declaring the directory `FILE:/config/` equivalent to a key also covers
+//! every file beneath it. Equivalences are not transitive.
+
+use crate::types::ResourceRef;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Pairs of resources that conflict as if they were one.
+///
+/// Serialized as a map from resource keys to the keys they are equivalent
+/// to; each pair needs to be listed on one side only:
+///
+/// ```json
+/// { "CONFIG_KEY:app.port": ["FILE:/config/app.toml"] }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<String, Vec<String>>",
+    into = "BTreeMap<String, Vec<String>>"
+)]
+pub struct EquivalenceMap {
+    pairs: Vec<(ResourceRef, ResourceRef)>,
+}
+
+impl EquivalenceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare `a` and `b` equivalent (in both directions).
+    pub fn insert(&mut self, a: ResourceRef, b: ResourceRef) -> Result<(), String> {
+        if a == b {
+            return Err(format!("Cannot declare {} equivalent to itself", a.key()));
+        }
+        let known = self
+            .pairs
+            .iter()
+            .any(|(x, y)| (x == &a && y == &b) || (x == &b && y == &a));
+        if !known {
+            self.pairs.push((a, b));
+        }
+        Ok(())
+    }
+
+    /// Builder form of `insert`.
+    pub fn with(mut self, a: ResourceRef, b: ResourceRef) -> Result<Self, String> {
+        self.insert(a, b)?;
+        Ok(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// The resources declared equivalent to `resource` or to anything
+    /// containing it.
+    pub fn equivalents(&self, resource: &ResourceRef) -> Vec<ResourceRef> {
+        let mut equivalents: Vec<ResourceRef> = Vec::new();
+        for (a, b) in &self.pairs {
+            for (side, other) in [(a, b), (b, a)] {
+                if side.contains(resource) && other != resource && !equivalents.contains(other) {
+                    equivalents.push(other.clone());
+                }
+            }
+        }
+        equivalents
+    }
+}
+
+fn parse_key(key: &str) -> Result<ResourceRef, String> {
+    ResourceRef::from_key(key).ok_or_else(|| {
+        format!(
+            "Invalid resource key '{}': expected TYPE:path, e.g. CONFIG_KEY:app.port",
+            key
+        )
+    })
+}
+
+impl TryFrom<BTreeMap<String, Vec<String>>> for EquivalenceMap {
+    type Error = String;
+
+    fn try_from(entries: BTreeMap<String, Vec<String>>) -> Result<Self, Self::Error> {
+        let mut map = Self::new();
+        for (key, equivalents) in entries {
+            let resource = parse_key(&key)?;
+            for equivalent in equivalents {
+                map.insert(resource.clone(), parse_key(&equivalent)?)?;
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl From<EquivalenceMap> for BTreeMap<String, Vec<String>> {
+    fn from(map: EquivalenceMap) -> Self {
+        let mut entries: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (a, b) in map.pairs {
+            entries.entry(a.key()).or_default().push(b.key());
+        }
+        entries
+    }
+}
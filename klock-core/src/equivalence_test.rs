@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+    use crate::conflict::ConflictEngine;
+    use crate::equivalence::EquivalenceMap;
+    use crate::infrastructure::LeaseStore;
+    use crate::infrastructure_in_memory::InMemoryLeaseStore;
+    use crate::types::{LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType};
+    use std::sync::Arc;
+
+    fn config(key: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::ConfigKey, key)
+    }
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    #[test]
+    fn test_equivalents_cover_contained_resources() {
+        let map = EquivalenceMap::new()
+            .with(config("app.port"), file("config/"))
+            .unwrap();
+        assert_eq!(map.equivalents(&config("app.port")), vec![file("/config/")]);
+        assert_eq!(
+            map.equivalents(&file("/config/app.toml")),
+            vec![config("app.port")]
+        );
+        assert_eq!(map.equivalents(&file("/config/")), vec![config("app.port")]);
+        assert!(map.equivalents(&file("/src/app.toml")).is_empty());
+        assert!(map.equivalents(&config("db.port")).is_empty());
+
+        assert!(
+            EquivalenceMap::new()
+                .insert(config("app"), config("app"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_equivalence_map_json_roundtrip() {
+        let map: EquivalenceMap =
+            serde_json::from_str(r#"{ "CONFIG_KEY:app.port": ["FILE:config/app.toml"] }"#).unwrap();
+        assert_eq!(map.len(), 1);
+        assert_eq!(
+            map.equivalents(&config("app.port")),
+            vec![file("/config/app.toml")]
+        );
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(json, r#"{"CONFIG_KEY:app.port":["FILE:/config/app.toml"]}"#);
+
+        assert!(serde_json::from_str::<EquivalenceMap>(r#"{ "app.port": ["FILE:/a"] }"#).is_err());
+    }
+
+    #[test]
+    fn test_config_key_and_file_conflict() {
+        let engine = ConflictEngine::default().with_equivalences(
+            EquivalenceMap::new()
+                .with(config("app.port"), file("/config/app.toml"))
+                .unwrap(),
+        );
+        let mut store = InMemoryLeaseStore::new();
+        store.set_conflict_engine(Arc::new(engine));
+        store.register_agent_priority("editor".to_string(), 100);
+        store.register_agent_priority("tuner".to_string(), 200);
+
+        let LeaseResult::Success { lease } = store.acquire(
+            "editor",
+            "s1",
+            file("/config/app.toml"),
+            Predicate::Mutates,
+            5000,
+            1000,
+        ) else {
+            panic!("Expected Success");
+        };
+
+        // The junior agent mutating the key dies against the file's editor
+        assert!(matches!(
+            store.acquire(
+                "tuner",
+                "s2",
+                config("app.port"),
+                Predicate::Mutates,
+                5000,
+                1001
+            ),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            }
+        ));
+        // Other keys are unaffected
+        assert!(matches!(
+            store.acquire(
+                "tuner",
+                "s2",
+                config("db.port"),
+                Predicate::Mutates,
+                5000,
+                1002
+            ),
+            LeaseResult::Success { .. }
+        ));
+
+        assert!(store.release(&lease.id));
+        assert!(matches!(
+            store.acquire(
+                "tuner",
+                "s2",
+                config("app.port"),
+                Predicate::Mutates,
+                5000,
+                1003
+            ),
+            LeaseResult::Success { .. }
+        ));
+    }
+}
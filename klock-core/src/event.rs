@@ -0,0 +1,96 @@
+//! Typed lease-state transitions.
+//!
+//! Stores decide *what* should happen (scheduling, conflict checks) and then
+//! express every change to lease state as a `KlockEvent` applied through
+//! `LeaseStore::apply`. Because the transition rules live in one place per
+//! store, replaying the same events yields the same leases on every backend,
+//! and the event sequence is a complete record of how state evolved.
+//! Published outside the process, events travel in an `EventEnvelope`.
+
+use crate::types::{Lease, LeaseState};
+use serde::{Deserialize, Serialize};
+
+/// One change to lease state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KlockEvent {
+    /// A lease was created: `Active` from an acquire, `Pending` from a
+    /// reservation
+    LeaseCreated { lease: Lease },
+    /// A pending reservation became active at `at`
+    LeaseActivated { lease_id: String, at: u64 },
+    /// An active lease was heartbeated at `at`, extending it by its TTL
+    LeaseRenewed { lease_id: String, at: u64 },
+    /// The holder released an active or pending lease
+    LeaseReleased { lease_id: String },
+    /// An active lease was taken away from its holder: preempted by a
+    /// scheduler or a senior agent, or revoked by an operator
+    LeaseRevoked { lease_id: String, reason: String },
+    /// An active or pending lease outlived its TTL
+    LeaseExpired { lease_id: String },
+    /// Terminal leases last touched before `before` were discarded
+    LeasesCompacted { before: u64 },
+}
+
+impl KlockEvent {
+    /// The lease this event changes, if it targets a single lease
+    pub fn lease_id(&self) -> Option<&str> {
+        match self {
+            KlockEvent::LeaseCreated { lease } => Some(&lease.id),
+            KlockEvent::LeaseActivated { lease_id, .. }
+            | KlockEvent::LeaseRenewed { lease_id, .. }
+            | KlockEvent::LeaseReleased { lease_id }
+            | KlockEvent::LeaseRevoked { lease_id, .. }
+            | KlockEvent::LeaseExpired { lease_id } => Some(lease_id),
+            KlockEvent::LeasesCompacted { .. } => None,
+        }
+    }
+
+    /// The variant name, for logs and divergence reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            KlockEvent::LeaseCreated { .. } => "LeaseCreated",
+            KlockEvent::LeaseActivated { .. } => "LeaseActivated",
+            KlockEvent::LeaseRenewed { .. } => "LeaseRenewed",
+            KlockEvent::LeaseReleased { .. } => "LeaseReleased",
+            KlockEvent::LeaseRevoked { .. } => "LeaseRevoked",
+            KlockEvent::LeaseExpired { .. } => "LeaseExpired",
+            KlockEvent::LeasesCompacted { .. } => "LeasesCompacted",
+        }
+    }
+}
+
+/// Apply `event` to a single lease, bumping its revision. Returns false,
+/// leaving the lease untouched, if the event does not apply to a lease in
+/// its current state.
+///
+/// These are the transition rules every store must follow; stores that keep
+/// leases in memory call this directly, others mirror it in their own
+/// storage.
+pub fn apply_to_lease(lease: &mut Lease, event: &KlockEvent) -> bool {
+    match event {
+        KlockEvent::LeaseActivated { at, .. } if lease.state == LeaseState::Pending => {
+            lease.state = LeaseState::Active;
+            lease.last_heartbeat = *at;
+            lease.expires_at = at + lease.ttl;
+        }
+        KlockEvent::LeaseRenewed { at, .. } if lease.state == LeaseState::Active => {
+            lease.last_heartbeat = *at;
+            lease.expires_at = at + lease.ttl;
+            lease.renewals += 1;
+        }
+        KlockEvent::LeaseReleased { .. } if !lease.is_terminal() => {
+            lease.state = LeaseState::Released;
+        }
+        KlockEvent::LeaseRevoked { reason, .. } if lease.state == LeaseState::Active => {
+            lease.state = LeaseState::Revoked;
+            lease.revoked_reason = Some(reason.clone());
+        }
+        KlockEvent::LeaseExpired { .. } if !lease.is_terminal() => {
+            lease.state = LeaseState::Expired;
+        }
+        _ => return false,
+    }
+    lease.revision += 1;
+    true
+}
@@ -0,0 +1,157 @@
+#[cfg(test)]
+mod tests {
+    use crate::event::{KlockEvent, apply_to_lease};
+    use crate::infrastructure::LeaseStore;
+    use crate::infrastructure_in_memory::InMemoryLeaseStore;
+    use crate::types::{Lease, LeaseResult, LeaseState, Predicate, ResourceRef, ResourceType};
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    /// Every lease in the store, as comparable tuples sorted by ID
+    fn snapshot(store: &dyn LeaseStore, ids: &[String]) -> Vec<(String, LeaseState, u64, u64)> {
+        let mut leases: Vec<_> = ids
+            .iter()
+            .filter_map(|id| store.get_lease(id))
+            .map(|l| (l.id, l.state, l.last_heartbeat, l.expires_at))
+            .collect();
+        leases.sort_by(|a, b| a.0.cmp(&b.0));
+        leases
+    }
+
+    /// Acquire, reserve, activate over a latecomer, renew, release and
+    /// expire, returning the IDs of every lease created.
+    fn workload(store: &mut dyn LeaseStore) -> Vec<String> {
+        let mut ids = Vec::new();
+        let LeaseResult::Success { lease } =
+            store.acquire("a", "s_a", file("/a.ts"), Predicate::Mutates, 5000, 1000)
+        else {
+            panic!("Expected lease");
+        };
+        ids.push(lease.id.clone());
+
+        let reserved = store.pre_acquire(
+            "b",
+            "s_b",
+            &[(file("/b.ts"), Predicate::Mutates)],
+            5000,
+            3000,
+            1000,
+        );
+        ids.push(reserved[0].id.clone());
+        let LeaseResult::Success { lease: latecomer } =
+            store.acquire("c", "s_c", file("/b.ts"), Predicate::Mutates, 5000, 1500)
+        else {
+            panic!("Expected lease");
+        };
+        ids.push(latecomer.id.clone());
+        assert!(matches!(
+            store.activate(&reserved[0].id, 2000),
+            LeaseResult::Success { .. }
+        ));
+
+        assert!(store.heartbeat(&lease.id, 2500));
+        assert!(store.release(&reserved[0].id));
+        assert_eq!(store.evict_expired(7600), 1);
+        ids
+    }
+
+    #[test]
+    fn test_transitions_follow_lease_state() {
+        let mut lease = Lease::new(
+            "l1".to_string(),
+            "a".to_string(),
+            "s".to_string(),
+            file("/a.ts"),
+            Predicate::Mutates,
+            5000,
+            1000,
+        )
+        .into_pending(3000);
+        let renew = KlockEvent::LeaseRenewed {
+            lease_id: "l1".to_string(),
+            at: 2000,
+        };
+
+        // Pending leases cannot be renewed or revoked, only activated
+        assert!(!apply_to_lease(&mut lease, &renew));
+        assert!(!apply_to_lease(
+            &mut lease,
+            &KlockEvent::LeaseRevoked {
+                lease_id: "l1".to_string(),
+                reason: "test".to_string(),
+            }
+        ));
+        assert!(apply_to_lease(
+            &mut lease,
+            &KlockEvent::LeaseActivated {
+                lease_id: "l1".to_string(),
+                at: 1500,
+            }
+        ));
+        assert_eq!(lease.expires_at, 6500);
+        assert!(apply_to_lease(&mut lease, &renew));
+        assert_eq!(lease.expires_at, 7000);
+
+        // Terminal leases never change again
+        let release = KlockEvent::LeaseReleased {
+            lease_id: "l1".to_string(),
+        };
+        assert!(apply_to_lease(&mut lease, &release));
+        assert!(!apply_to_lease(&mut lease, &release));
+        assert_eq!(lease.state, LeaseState::Released);
+    }
+
+    #[test]
+    fn test_replay_rebuilds_state() {
+        let mut store = InMemoryLeaseStore::new();
+        store.record_events(true);
+        let ids = workload(&mut store);
+        let events = store.take_events();
+        assert!(store.take_events().is_empty());
+
+        // 3 created, 1 revoked latecomer, 1 activated, 1 renewed,
+        // 1 released, 1 expired
+        assert_eq!(events.len(), 8);
+        assert!(events.iter().any(
+            |e| matches!(e, KlockEvent::LeaseRevoked { lease_id, .. } if *lease_id == ids[2])
+        ));
+
+        let mut replayed = InMemoryLeaseStore::new();
+        assert_eq!(replayed.replay(&events), events.len());
+        assert_eq!(snapshot(&replayed, &ids), snapshot(&store, &ids));
+        assert!(replayed.get_active_leases().is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_backends_evolve_identically() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let path = std::env::temp_dir().join(format!("klock_event_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut memory = InMemoryLeaseStore::new();
+        memory.record_events(true);
+        let ids = workload(&mut memory);
+        let events = memory.take_events();
+
+        {
+            let mut sqlite = SqliteLeaseStore::open(path).unwrap();
+            sqlite.record_events(true);
+            assert_eq!(workload(&mut sqlite), ids);
+            assert_eq!(sqlite.take_events().len(), events.len());
+            assert_eq!(snapshot(&sqlite, &ids), snapshot(&memory, &ids));
+        }
+
+        // Replaying the in-memory history into SQLite reaches the same state
+        let _ = std::fs::remove_file(path);
+        let mut sqlite = SqliteLeaseStore::open(path).unwrap();
+        assert_eq!(sqlite.replay(&events), events.len());
+        assert_eq!(snapshot(&sqlite, &ids), snapshot(&memory, &ids));
+
+        drop(sqlite);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,93 @@
+//! Pre-expiry warnings.
+//!
+//! A lease whose holder stops heartbeating (a stalled agent, a long
+//! compile) simply runs out, and the holder finds out only when its next
+//! heartbeat fails. With a warning threshold set, a lease counts as
+//! expiring soon once less than that share of its TTL remains since the
+//! last heartbeat; its session then gets one `lease_expiring` event, and
+//! lease listings flag it for dashboards. A heartbeat moves the expiry, so
+//! a lease that falls behind again is warned about again.
+
+use crate::types::{Lease, LeaseState};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// When holders are warned that a lease is about to expire
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpiryWarningPolicy {
+    /// Warn once this percentage of the TTL, or less, remains (`None` =
+    /// never)
+    pub threshold_percent: Option<u8>,
+}
+
+impl Default for ExpiryWarningPolicy {
+    fn default() -> Self {
+        Self {
+            threshold_percent: Some(20),
+        }
+    }
+}
+
+impl ExpiryWarningPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.threshold_percent.is_some_and(|percent| percent > 0)
+    }
+
+    /// Whether `lease` has the threshold of its TTL or less left at `now`,
+    /// but has not run out yet
+    pub fn is_expiring_soon(&self, lease: &Lease, now: u64) -> bool {
+        let Some(percent) = self.threshold_percent.filter(|p| *p > 0) else {
+            return false;
+        };
+        lease.state == LeaseState::Active
+            && lease.expires_at > now
+            && u128::from(lease.expires_at.saturating_sub(now)) * 100
+                <= u128::from(lease.ttl) * u128::from(percent)
+    }
+}
+
+/// The leases whose holders have been warned, by the expiry they were
+/// warned about
+#[derive(Debug, Default)]
+pub struct ExpiryWarnings {
+    policy: ExpiryWarningPolicy,
+    warned: HashMap<String, u64>,
+}
+
+impl ExpiryWarnings {
+    pub fn set_policy(&mut self, policy: ExpiryWarningPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> &ExpiryWarningPolicy {
+        &self.policy
+    }
+
+    /// The leases among `active` that are expiring soon at `now` and whose
+    /// holders were not yet warned about this expiry. Forgets leases that
+    /// are no longer active.
+    pub fn due<'a>(&mut self, active: &'a [Lease], now: u64) -> Vec<&'a Lease> {
+        let ids: HashSet<&str> = active.iter().map(|lease| lease.id.as_str()).collect();
+        self.warned
+            .retain(|lease_id, _| ids.contains(lease_id.as_str()));
+        if !self.policy.is_enabled() {
+            return Vec::new();
+        }
+        active
+            .iter()
+            .filter(|lease| self.policy.is_expiring_soon(lease, now))
+            .filter(|lease| {
+                self.warned.insert(lease.id.clone(), lease.expires_at) != Some(lease.expires_at)
+            })
+            .collect()
+    }
+
+    /// Leases currently remembered as warned
+    pub fn len(&self) -> usize {
+        self.warned.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warned.is_empty()
+    }
+}
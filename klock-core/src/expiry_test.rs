@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::{Clock, ManualClock};
+    use crate::expiry::ExpiryWarningPolicy;
+    use crate::feed::AgentEvent;
+    use crate::types::{Lease, LeaseResult};
+    use std::sync::Arc;
+
+    fn acquire(client: &mut KlockClient, path: &str) -> Lease {
+        match client.acquire_lease("bot", "s1", "FILE", path, "MUTATES", 10_000) {
+            LeaseResult::Success { lease } => lease,
+            other => panic!("Expected success, got {:?}", other),
+        }
+    }
+
+    fn expiring(client: &mut KlockClient) -> Vec<(String, u64, u64)> {
+        client
+            .poll_events("bot", "s1")
+            .events
+            .into_iter()
+            .filter_map(|event| match event {
+                AgentEvent::LeaseExpiring {
+                    lease_id,
+                    expires_at,
+                    remaining_ms,
+                    ..
+                } => Some((lease_id, expires_at, remaining_ms)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_holder_is_warned_once_per_expiry() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("bot", 100);
+        let lease = acquire(&mut client, "/a.ts");
+
+        clock.advance(7_999);
+        assert!(!client.is_expiring_soon(&client.get_lease(&lease.id).unwrap()));
+        assert!(expiring(&mut client).is_empty());
+
+        // 20% of the TTL left
+        clock.advance(1);
+        assert!(client.is_expiring_soon(&client.get_lease(&lease.id).unwrap()));
+        assert_eq!(
+            expiring(&mut client),
+            vec![(lease.id.clone(), 11_000, 2_000)]
+        );
+        clock.advance(500);
+        assert!(expiring(&mut client).is_empty());
+
+        // A heartbeat moves the expiry; falling behind again warns again
+        assert!(client.heartbeat_lease(&lease.id, clock.now_ms()));
+        assert!(!client.is_expiring_soon(&client.get_lease(&lease.id).unwrap()));
+        clock.advance(9_000);
+        assert_eq!(
+            expiring(&mut client),
+            vec![(lease.id.clone(), 19_500, 1_000)]
+        );
+
+        // Released leases are forgotten
+        assert!(client.release_lease(&lease.id));
+        assert_eq!(client.warn_expiring_leases(), 0);
+    }
+
+    #[test]
+    fn test_threshold_is_configurable() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("bot", 100);
+        client.set_expiry_warning_policy(ExpiryWarningPolicy {
+            threshold_percent: Some(50),
+        });
+        let lease = acquire(&mut client, "/a.ts");
+        clock.advance(5_000);
+        assert_eq!(client.warn_expiring_leases(), 1);
+
+        client.set_expiry_warning_policy(ExpiryWarningPolicy {
+            threshold_percent: None,
+        });
+        clock.advance(1);
+        acquire(&mut client, "/b.ts");
+        clock.advance(9_000);
+        assert_eq!(client.warn_expiring_leases(), 0);
+        assert!(!client.is_expiring_soon(&client.get_lease(&lease.id).unwrap()));
+    }
+}
@@ -0,0 +1,60 @@
+//! Fair-share scheduling.
+//!
+//! Priorities are fixed at registration, so an old agent that grabs lease
+//! after lease keeps winning every conflict and can monopolize a repo. With
+//! a fair-share policy enabled, each active lease an agent holds pushes its
+//! priority timestamp back by the policy's penalty when the scheduler
+//! decides, so a greedy agent yields to one holding nothing. The penalty is
+//! applied at decision time from the store's per-agent lease counts; the
+//! registered priorities are left alone, and a class is never crossed.
+
+use crate::registry::Priority;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How much holding leases costs an agent. Disabled by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FairSharePolicy {
+    /// Milliseconds added to an agent's priority timestamp per active lease
+    /// it holds (`None` = fair share disabled)
+    pub penalty_per_lease: Option<u64>,
+}
+
+impl FairSharePolicy {
+    pub fn new(penalty_per_lease: u64) -> Self {
+        Self {
+            penalty_per_lease: Some(penalty_per_lease),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.penalty_per_lease.is_some()
+    }
+
+    /// The priority an agent holding `leases` active leases is scheduled at
+    pub fn adjust(&self, priority: Priority, leases: usize) -> Priority {
+        match self.penalty_per_lease {
+            Some(penalty) => priority.with_timestamp(
+                priority
+                    .timestamp
+                    .saturating_add(penalty.saturating_mul(leases as u64)),
+            ),
+            None => priority,
+        }
+    }
+
+    /// Adjust every priority in `priorities` by its agent's lease count
+    pub fn apply(
+        &self,
+        priorities: &mut HashMap<String, Priority>,
+        lease_counts: &HashMap<String, usize>,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        for (agent_id, priority) in priorities.iter_mut() {
+            let leases = lease_counts.get(agent_id).copied().unwrap_or(0);
+            *priority = self.adjust(*priority, leases);
+        }
+    }
+}
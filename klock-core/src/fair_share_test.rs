@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::fair_share::FairSharePolicy;
+    use crate::infrastructure::LeaseStore;
+    use crate::infrastructure_in_memory::InMemoryLeaseStore;
+    use crate::registry::{Priority, PriorityClass};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use crate::wait_queue::WaiterFilter;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn mutates(agent: &str, path: &str) -> IntentManifest {
+        IntentManifest {
+            session_id: format!("{}_s", agent),
+            agent_id: agent.to_string(),
+            intents: vec![SPOTriple {
+                id: format!("{}_{}", agent, path),
+                subject: agent.to_string(),
+                predicate: Predicate::Mutates,
+                object: file(path),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: format!("{}_s", agent),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        }
+    }
+
+    fn reason(result: &LeaseResult) -> Option<LeaseFailureReason> {
+        match result {
+            LeaseResult::Success { .. } => None,
+            LeaseResult::Failure { reason, .. } => Some(*reason),
+        }
+    }
+
+    #[test]
+    fn test_penalty_stays_within_class() {
+        let policy = FairSharePolicy::new(1000);
+        assert_eq!(policy.adjust(Priority::from(100), 3), Priority::from(3100));
+        assert_eq!(
+            policy.adjust(Priority::from(u64::MAX - 1), 2),
+            Priority::from(u64::MAX)
+        );
+
+        let mut priorities = HashMap::from([
+            (
+                "pager".to_string(),
+                Priority::new(PriorityClass::Critical, 900),
+            ),
+            ("idle".to_string(), Priority::from(100)),
+        ]);
+        let counts = HashMap::from([("pager".to_string(), 50)]);
+        FairSharePolicy::default().apply(&mut priorities, &counts);
+        assert_eq!(priorities["pager"].timestamp, 900);
+
+        policy.apply(&mut priorities, &counts);
+        assert_eq!(
+            priorities["pager"],
+            Priority::new(PriorityClass::Critical, 50_900)
+        );
+        assert_eq!(priorities["idle"], Priority::from(100));
+        // However many leases it holds, a critical agent outranks a normal one
+        assert!(priorities["pager"] < priorities["idle"]);
+    }
+
+    fn assert_counts_active_leases(store: &mut dyn LeaseStore) {
+        // Lease IDs are made from the agent and the time
+        for (now, (agent, path)) in [("a", "/1.ts"), ("a", "/2.ts"), ("b", "/3.ts")]
+            .into_iter()
+            .enumerate()
+        {
+            let result =
+                store.acquire(agent, "s", file(path), Predicate::Mutates, 1000, now as u64);
+            assert!(matches!(result, LeaseResult::Success { .. }));
+        }
+        let LeaseResult::Success { lease } =
+            store.acquire("b", "s", file("/4.ts"), Predicate::Mutates, 1000, 3)
+        else {
+            panic!("Expected success");
+        };
+        assert!(store.release(&lease.id));
+
+        let counts = store.lease_counts();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["a"], 2);
+        assert_eq!(counts["b"], 1);
+    }
+
+    #[test]
+    fn test_in_memory_store_counts_active_leases() {
+        assert_counts_active_leases(&mut InMemoryLeaseStore::new());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_counts_active_leases() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        assert_counts_active_leases(&mut SqliteLeaseStore::open(":memory:").unwrap());
+    }
+
+    #[test]
+    fn test_greedy_agent_yields_to_one_holding_little() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("greedy", 100);
+        client.register_agent("newcomer", 500);
+        for path in ["/a.ts", "/b.ts", "/c.ts"] {
+            client.acquire_lease("greedy", "g", "FILE", path, "MUTATES", 60_000);
+            clock.advance(1);
+        }
+        client.acquire_lease("newcomer", "n", "FILE", "/x.ts", "MUTATES", 60_000);
+        clock.advance(1);
+
+        // Plain Wait-Die: the older greedy agent waits, the newcomer dies
+        let result = client.acquire_lease("greedy", "g", "FILE", "/x.ts", "MUTATES", 60_000);
+        assert_eq!(reason(&result), Some(LeaseFailureReason::Wait));
+        let cancelled = client.cancel_pending(&WaiterFilter::Resource {
+            agent_id: "greedy".to_string(),
+            resource: file("/x.ts"),
+        });
+        assert_eq!(cancelled.len(), 1);
+        clock.advance(1);
+        let result = client.acquire_lease("newcomer", "n", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(reason(&result), Some(LeaseFailureReason::Die));
+
+        // Three leases at 1000ms each push greedy (3100) behind the
+        // newcomer (1500)
+        client.set_fair_share_policy(FairSharePolicy::new(1000));
+        clock.advance(1);
+        let result = client.acquire_lease("greedy", "g", "FILE", "/x.ts", "MUTATES", 60_000);
+        assert_eq!(reason(&result), Some(LeaseFailureReason::Die));
+        clock.advance(1);
+        let result = client.acquire_lease("newcomer", "n", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(reason(&result), Some(LeaseFailureReason::Wait));
+        // The kernel schedules manifests the same way
+        let verdict = client.declare_intent(&mutates("greedy", "/x.ts"));
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+
+        // Registered priorities are untouched
+        assert_eq!(client.agent_priority("greedy"), Some(Priority::from(100)));
+    }
+}
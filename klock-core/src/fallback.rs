@@ -0,0 +1,137 @@
+//! Fallback suggestions for contended resources.
+//!
+//! A Die verdict tells an agent to come back later, but often the work
+//! could go elsewhere right away: to another shard of a sharded table,
+//! another fixture slot, another staging environment. `FallbackGroups`
+//! names sets of such interchangeable resources. When an agent dies on a
+//! member of a group (or on anything inside one), the verdict lists the
+//! group's other members the agent could take now, so a planner can
+//! reroute the work instead of retrying.
+
+use crate::types::ResourceRef;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Named groups of interchangeable resources.
+///
+/// Serialized as a map from group names to the keys of their members:
+///
+/// ```json
+/// { "event-shards": ["DATABASE_TABLE:events_0", "DATABASE_TABLE:events_1"] }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<String, Vec<String>>",
+    into = "BTreeMap<String, Vec<String>>"
+)]
+pub struct FallbackGroups {
+    groups: BTreeMap<String, Vec<ResourceRef>>,
+}
+
+/// Uncontended alternatives to a resource an agent was refused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fallback {
+    /// The refused resource
+    pub resource: ResourceRef,
+    /// The group the alternatives come from
+    pub group: String,
+    /// Members of the group the agent could take now, in declared order
+    pub alternatives: Vec<ResourceRef>,
+}
+
+impl FallbackGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a group; it needs at least two members to offer anything.
+    pub fn insert(&mut self, name: &str, members: Vec<ResourceRef>) -> Result<(), String> {
+        if name.is_empty() {
+            return Err("Fallback group names must not be empty".to_string());
+        }
+        if members.len() < 2 {
+            return Err(format!(
+                "Fallback group '{}' needs at least two members",
+                name
+            ));
+        }
+        if let Some((i, member)) = members
+            .iter()
+            .enumerate()
+            .find(|(i, member)| members[..*i].contains(member))
+        {
+            return Err(format!(
+                "Fallback group '{}' lists {} twice (members[{}])",
+                name,
+                member.key(),
+                i
+            ));
+        }
+        self.groups.insert(name.to_string(), members);
+        Ok(())
+    }
+
+    /// Builder form of `insert`.
+    pub fn with(mut self, name: &str, members: Vec<ResourceRef>) -> Result<Self, String> {
+        self.insert(name, members)?;
+        Ok(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// For each group with a member containing `resource`: the group's
+    /// name and its members that do not overlap `resource`
+    pub fn candidates(&self, resource: &ResourceRef) -> Vec<(&str, Vec<&ResourceRef>)> {
+        self.groups
+            .iter()
+            .filter(|(_, members)| members.iter().any(|m| m.contains(resource)))
+            .map(|(name, members)| {
+                (
+                    name.as_str(),
+                    members.iter().filter(|m| !m.overlaps(resource)).collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+fn parse_key(key: &str) -> Result<ResourceRef, String> {
+    ResourceRef::from_key(key).ok_or_else(|| {
+        format!(
+            "Invalid resource key '{}': expected TYPE:path, e.g. DATABASE_TABLE:events_0",
+            key
+        )
+    })
+}
+
+impl TryFrom<BTreeMap<String, Vec<String>>> for FallbackGroups {
+    type Error = String;
+
+    fn try_from(entries: BTreeMap<String, Vec<String>>) -> Result<Self, Self::Error> {
+        let mut groups = Self::new();
+        for (name, keys) in entries {
+            let members = keys
+                .iter()
+                .map(|key| parse_key(key))
+                .collect::<Result<Vec<_>, _>>()?;
+            groups.insert(&name, members)?;
+        }
+        Ok(groups)
+    }
+}
+
+impl From<FallbackGroups> for BTreeMap<String, Vec<String>> {
+    fn from(groups: FallbackGroups) -> Self {
+        groups
+            .groups
+            .into_iter()
+            .map(|(name, members)| (name, members.iter().map(ResourceRef::key).collect()))
+            .collect()
+    }
+}
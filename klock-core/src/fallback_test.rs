@@ -0,0 +1,174 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::fallback::{Fallback, FallbackGroups};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::sync::Arc;
+
+    fn table(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::DatabaseTable, path)
+    }
+
+    fn shards() -> FallbackGroups {
+        serde_json::from_str(
+            r#"{ "event-shards": [
+                "DATABASE_TABLE:events_0", "DATABASE_TABLE:events_1",
+                "DATABASE_TABLE:events_2", "DATABASE_TABLE:events_3"
+            ] }"#,
+        )
+        .unwrap()
+    }
+
+    fn sharded_client() -> (KlockClient, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.set_fallback_groups(shards());
+        client.register_agent("senior", 100);
+        client.register_agent("other", 150);
+        client.register_agent("junior", 200);
+        (client, clock)
+    }
+
+    #[test]
+    fn test_groups_parse_and_validate() {
+        let groups = shards();
+        assert_eq!(groups.len(), 1);
+        let (name, others) = &groups.candidates(&table("events_1"))[0];
+        assert_eq!(*name, "event-shards");
+        assert_eq!(others.len(), 3);
+        assert!(groups.candidates(&table("users")).is_empty());
+
+        let round_trip: FallbackGroups =
+            serde_json::from_value(serde_json::to_value(&groups).unwrap()).unwrap();
+        assert_eq!(round_trip, groups);
+
+        for invalid in [
+            r#"{ "solo": ["DATABASE_TABLE:a"] }"#,
+            r#"{ "twice": ["DATABASE_TABLE:a", "DATABASE_TABLE:a"] }"#,
+            r#"{ "bad": ["DATABASE_TABLE:a", "nonsense"] }"#,
+        ] {
+            assert!(
+                serde_json::from_str::<FallbackGroups>(invalid).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_die_on_a_shard_suggests_the_free_ones() {
+        let (mut client, clock) = sharded_client();
+        client.acquire_lease(
+            "senior",
+            "s1",
+            "DATABASE_TABLE",
+            "events_0",
+            "MUTATES",
+            60_000,
+        );
+        clock.advance(1);
+        client.acquire_lease(
+            "other",
+            "o1",
+            "DATABASE_TABLE",
+            "events_1",
+            "MUTATES",
+            60_000,
+        );
+        client
+            .quarantine_resource(table("events_3"), None, None)
+            .unwrap();
+
+        assert_eq!(
+            client.fallbacks("junior", "j1", &table("events_0"), Predicate::Mutates),
+            vec![Fallback {
+                resource: table("events_0"),
+                group: "event-shards".to_string(),
+                alternatives: vec![table("events_2")],
+            }]
+        );
+        // No free member, no suggestion
+        clock.advance(1);
+        client.acquire_lease(
+            "other",
+            "o1",
+            "DATABASE_TABLE",
+            "events_2",
+            "MUTATES",
+            60_000,
+        );
+        assert!(
+            client
+                .fallbacks("junior", "j1", &table("events_0"), Predicate::Mutates)
+                .is_empty()
+        );
+        assert!(
+            client
+                .fallbacks("junior", "j1", &table("users"), Predicate::Mutates)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_refused_manifest_carries_fallbacks() {
+        let (mut client, clock) = sharded_client();
+        client.acquire_lease(
+            "senior",
+            "s1",
+            "DATABASE_TABLE",
+            "events_0",
+            "MUTATES",
+            60_000,
+        );
+        clock.advance(1);
+        let intent = |id: &str, object: ResourceRef| SPOTriple {
+            id: id.to_string(),
+            subject: "junior".to_string(),
+            predicate: Predicate::Mutates,
+            object,
+            timestamp: 1000,
+            confidence: Confidence::High,
+            session_id: "j1".to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        };
+        // Another session's intent takes a shard out of the running
+        client.declare_intent(&IntentManifest {
+            session_id: "o1".to_string(),
+            agent_id: "other".to_string(),
+            intents: vec![SPOTriple {
+                subject: "other".to_string(),
+                session_id: "o1".to_string(),
+                ..intent("o", table("events_2"))
+            }],
+        });
+
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "j1".to_string(),
+            agent_id: "junior".to_string(),
+            intents: vec![
+                intent("i1", table("events_0")),
+                intent("i2", ResourceRef::new(ResourceType::File, "/a.ts")),
+            ],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(verdict.fallbacks.len(), 1);
+        assert_eq!(verdict.fallbacks[0].resource, table("events_0"));
+        assert_eq!(
+            verdict.fallbacks[0].alternatives,
+            vec![table("events_1"), table("events_3")]
+        );
+
+        // Without groups, nothing is suggested
+        client.set_fallback_groups(FallbackGroups::new());
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "j1".to_string(),
+            agent_id: "junior".to_string(),
+            intents: vec![intent("i1", table("events_0"))],
+        });
+        assert!(verdict.fallbacks.is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+//! Scheduler failure injection, for tests (feature = "testing").
+//!
+//! Wait and Die verdicts on a quiet test repo are rare, so the code paths
+//! that handle them (backoff, queue polling, retries) are hard to exercise.
+//! `FaultInjectingScheduler` wraps any scheduler and forces the verdicts a
+//! `FaultInjector` has been programmed with: "the next 3 decisions for
+//! agent X on `/src/**` are Die". Each matching decision consumes one,
+//! whether it is for an acquire, an activation, a queued request or a
+//! manifest, and the wrapped scheduler decides everything else.
+
+use crate::conflict::ConflictEngine;
+use crate::registry::Priority;
+use crate::scheduler::{Scheduler, SchedulerVerdict, VerdictStatus};
+use crate::types::{Holder, Lease, Predicate, ResourceRef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A verdict to force on the next `times` matching decisions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InjectedFault {
+    /// The requesting agent; any agent when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// Requests overlapping this resource, directory or glob pattern; any
+    /// resource when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<ResourceRef>,
+    /// `Wait` or `Die`
+    pub verdict: VerdictStatus,
+    /// Decisions left to force
+    pub times: usize,
+}
+
+impl InjectedFault {
+    fn matches(&self, agent_id: &str, resource: &ResourceRef) -> bool {
+        self.agent_id.as_deref().is_none_or(|a| a == agent_id)
+            && self.resource.as_ref().is_none_or(|r| r.overlaps(resource))
+    }
+}
+
+/// The faults waiting to be forced, shared between a test and the
+/// scheduler it wrapped
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjector {
+    faults: Arc<Mutex<Vec<InjectedFault>>>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `fault` behind those already injected. Forcing `Granted` is
+    /// refused: it would hand out conflicting leases.
+    pub fn inject(&self, fault: InjectedFault) -> Result<(), String> {
+        if fault.verdict == VerdictStatus::Granted {
+            return Err("Only Wait and Die verdicts can be injected".to_string());
+        }
+        if fault.times == 0 {
+            return Err("times must be at least 1".to_string());
+        }
+        self.faults.lock().unwrap().push(fault);
+        Ok(())
+    }
+
+    /// Faults not yet used up, in injection order
+    pub fn faults(&self) -> Vec<InjectedFault> {
+        self.faults.lock().unwrap().clone()
+    }
+
+    /// Drop every pending fault. Returns the number dropped.
+    pub fn clear(&self) -> usize {
+        let mut faults = self.faults.lock().unwrap();
+        let cleared = faults.len();
+        faults.clear();
+        cleared
+    }
+
+    /// Use up one decision of the first fault matching the request
+    fn take(&self, agent_id: &str, resource: &ResourceRef) -> Option<VerdictStatus> {
+        let mut faults = self.faults.lock().unwrap();
+        let position = faults.iter().position(|f| f.matches(agent_id, resource))?;
+        let fault = &mut faults[position];
+        fault.times -= 1;
+        let verdict = fault.verdict;
+        if fault.times == 0 {
+            faults.remove(position);
+        }
+        Some(verdict)
+    }
+}
+
+/// A scheduler that forces injected verdicts and defers to `inner` for the
+/// rest
+pub struct FaultInjectingScheduler {
+    inner: Arc<dyn Scheduler>,
+    faults: FaultInjector,
+}
+
+impl FaultInjectingScheduler {
+    pub fn new(inner: Arc<dyn Scheduler>, faults: FaultInjector) -> Self {
+        Self { inner, faults }
+    }
+
+    /// The wrapped policy
+    pub fn inner(&self) -> Arc<dyn Scheduler> {
+        self.inner.clone()
+    }
+}
+
+impl Scheduler for FaultInjectingScheduler {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn name_for(&self, resource: &ResourceRef) -> &'static str {
+        self.inner.name_for(resource)
+    }
+
+    fn resolve(
+        &self,
+        requesting_agent_id: &str,
+        conflicting_holders: &[&Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        self.inner
+            .resolve(requesting_agent_id, conflicting_holders, priorities)
+    }
+
+    fn decide(
+        &self,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        let Some(status) = self.faults.take(requesting_agent_id, resource) else {
+            return self.inner.decide(
+                engine,
+                requesting_agent_id,
+                requesting_session_id,
+                requesting_predicate,
+                resource,
+                active_leases,
+                priorities,
+            );
+        };
+        let holders = engine.conflicting_leases(
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+        );
+        SchedulerVerdict {
+            status,
+            reason: Some("Injected fault".to_string()),
+            holders: holders.into_iter().map(Holder::from).collect(),
+            retry_after_ms: None,
+            preempted: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::fault::InjectedFault;
+    use crate::scheduler::{VerdictStatus, WoundWaitScheduler};
+    use crate::types::{LeaseFailureReason, LeaseResult, ResourceRef, ResourceType};
+    use std::sync::Arc;
+
+    fn fault(agent: Option<&str>, pattern: Option<&str>, verdict: VerdictStatus) -> InjectedFault {
+        InjectedFault {
+            agent_id: agent.map(str::to_string),
+            resource: pattern.map(|p| ResourceRef::new(ResourceType::File, p)),
+            verdict,
+            times: 2,
+        }
+    }
+
+    fn reason(result: &LeaseResult) -> Option<LeaseFailureReason> {
+        match result {
+            LeaseResult::Success { .. } => None,
+            LeaseResult::Failure { reason, .. } => Some(*reason),
+        }
+    }
+
+    #[test]
+    fn test_injected_die_applies_to_matching_acquires_only() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("bot", 100);
+        client.register_agent("other", 200);
+        let faults = client.enable_fault_injection();
+        faults
+            .inject(fault(Some("bot"), Some("/src/**"), VerdictStatus::Die))
+            .unwrap();
+
+        // Other agents and other resources are decided as usual
+        let result = client.acquire_lease("other", "o", "FILE", "/src/b.ts", "MUTATES", 60_000);
+        assert_eq!(reason(&result), None);
+        let result = client.acquire_lease("bot", "b", "FILE", "/docs/a.md", "MUTATES", 60_000);
+        assert_eq!(reason(&result), None);
+
+        for _ in 0..2 {
+            clock.advance(1);
+            let LeaseResult::Failure {
+                reason, wait_time, ..
+            } = client.acquire_lease("bot", "b", "FILE", "/src/a.ts", "MUTATES", 60_000)
+            else {
+                panic!("Expected an injected Die");
+            };
+            assert_eq!(reason, LeaseFailureReason::Die);
+            // Nothing is held, so the backoff policy's base
+            assert_eq!(wait_time, Some(1000));
+        }
+        assert!(faults.faults().is_empty());
+        clock.advance(1);
+        let result = client.acquire_lease("bot", "b", "FILE", "/src/a.ts", "MUTATES", 60_000);
+        assert_eq!(reason(&result), None);
+    }
+
+    #[test]
+    fn test_injection_survives_scheduler_changes() {
+        let mut client = KlockClient::new();
+        client.register_agent("bot", 100);
+        let faults = client.enable_fault_injection();
+        client.set_scheduler(Arc::new(WoundWaitScheduler));
+        assert_eq!(client.scheduler_name(), "wound-wait");
+        assert_eq!(client.fault_injector().unwrap().faults(), Vec::new());
+
+        faults
+            .inject(fault(None, None, VerdictStatus::Wait))
+            .unwrap();
+        let result = client.acquire_lease("bot", "b", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(reason(&result), Some(LeaseFailureReason::Wait));
+        assert_eq!(faults.faults()[0].times, 1);
+        assert_eq!(faults.clear(), 1);
+    }
+
+    #[test]
+    fn test_only_refusals_can_be_injected() {
+        let mut client = KlockClient::new();
+        let faults = client.enable_fault_injection();
+        assert!(
+            faults
+                .inject(fault(None, None, VerdictStatus::Granted))
+                .is_err()
+        );
+        let mut never = fault(None, None, VerdictStatus::Die);
+        never.times = 0;
+        assert!(faults.inject(never).is_err());
+        assert!(faults.faults().is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! Per-session change feeds.
+//!
+//! An agent that declares a `DEPENDS_ON` intent builds on the resource as it
+//! currently is. The session's dependency watch list is made of those
+//! intents (see `KlockClient::watched_resources`). When another agent
+//! releases a `MUTATES`, `DELETES` or `RENAMES` lease on a watched resource,
+//! its change is complete, and a `dependency_changed` event is queued on the
+//! dependent session's feed. A session whose queued acquire is granted
+//! when the resource frees up gets a `lease_granted` event, and the
+//! sessions holding a resource get a `waiter_cancelled` event when a request
+//! queued for it is abandoned. A session whose lease a senior agent or an
+//! operator revokes gets a `lease_revoked` event, and one whose lease is
+//! about to run out without a heartbeat a `lease_expiring` event (see
+//! `expiry`). Sessions of a reaped namespace get a `namespace_reaped` event.
+//! Agents drain their feed by polling it.
+//!
+//! A feed holds at most `FEED_CAPACITY` undelivered events; older ones are
+//! dropped first, and counted so the agent knows it missed some.
+
+use crate::types::{Predicate, ResourceRef};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Undelivered events kept per session
+pub const FEED_CAPACITY: usize = 256;
+
+/// An event addressed to one session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// A lease that changed a watched resource was released
+    DependencyChanged {
+        /// The resource the session depends on, as it declared it
+        watched: ResourceRef,
+        /// The resource the released lease covered
+        resource: ResourceRef,
+        predicate: Predicate,
+        /// Agent that held the lease
+        changed_by: String,
+        lease_id: String,
+        /// When the lease was released
+        at: u64,
+    },
+    /// An acquire the session queued with a Wait verdict was granted when
+    /// its resource freed up; retrying it returns this lease
+    LeaseGranted {
+        lease_id: String,
+        resource: ResourceRef,
+        predicate: Predicate,
+        expires_at: u64,
+        /// When the lease was granted
+        at: u64,
+    },
+    /// A request queued behind the session's lease was cancelled, leaving
+    /// `waiting` requests in that resource's queue
+    WaiterCancelled {
+        request_id: String,
+        /// Agent that had queued the request
+        agent_id: String,
+        resource: ResourceRef,
+        predicate: Predicate,
+        waiting: usize,
+        /// When the request was cancelled
+        at: u64,
+    },
+    /// One of the session's leases was revoked; the work it covered is no
+    /// longer protected
+    LeaseRevoked {
+        lease_id: String,
+        resource: ResourceRef,
+        predicate: Predicate,
+        /// The senior agent that preempted the lease; `None` for operators
+        revoked_by: Option<String>,
+        reason: String,
+        /// When the lease was revoked
+        at: u64,
+    },
+    /// One of the session's leases will expire at `expires_at` unless it
+    /// is heartbeated
+    LeaseExpiring {
+        lease_id: String,
+        resource: ResourceRef,
+        predicate: Predicate,
+        expires_at: u64,
+        /// Time left when the warning was issued
+        remaining_ms: u64,
+        /// When the warning was issued
+        at: u64,
+    },
+    /// The session's namespace was reaped: its leases were released and
+    /// its intents withdrawn (see `namespace`)
+    NamespaceReaped {
+        namespace: String,
+        /// Whether its TTL ran out, rather than it being deleted
+        expired: bool,
+        at: u64,
+    },
+}
+
+/// The events delivered by one poll
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeedBatch {
+    pub events: Vec<AgentEvent>,
+    /// Events dropped since the last poll because the feed was full
+    pub dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct Feed {
+    events: VecDeque<AgentEvent>,
+    dropped: u64,
+}
+
+/// Feeds by agent and session
+#[derive(Debug, Default)]
+pub struct FeedRegistry {
+    feeds: HashMap<(String, String), Feed>,
+}
+
+impl FeedRegistry {
+    /// Queue `event` on the session's feed
+    pub fn push(&mut self, agent_id: &str, session_id: &str, event: AgentEvent) {
+        let feed = self
+            .feeds
+            .entry((agent_id.to_string(), session_id.to_string()))
+            .or_default();
+        if feed.events.len() == FEED_CAPACITY {
+            feed.events.pop_front();
+            feed.dropped += 1;
+        }
+        feed.events.push_back(event);
+    }
+
+    /// Take every event queued for the session, oldest first
+    pub fn poll(&mut self, agent_id: &str, session_id: &str) -> FeedBatch {
+        match self
+            .feeds
+            .get_mut(&(agent_id.to_string(), session_id.to_string()))
+        {
+            Some(feed) => FeedBatch {
+                events: feed.events.drain(..).collect(),
+                dropped: std::mem::take(&mut feed.dropped),
+            },
+            None => FeedBatch::default(),
+        }
+    }
+
+    /// Keep only the feeds of sessions for which `keep(agent_id,
+    /// session_id)` holds. Returns the number dropped.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str, &str) -> bool) -> usize {
+        let initial = self.feeds.len();
+        self.feeds
+            .retain(|(agent_id, session_id), _| keep(agent_id, session_id));
+        initial - self.feeds.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.feeds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.feeds.is_empty()
+    }
+
+    /// Approximate footprint of all feeds and their queued events
+    pub fn estimated_bytes(&self) -> usize {
+        self.feeds
+            .iter()
+            .map(|((agent_id, session_id), feed)| {
+                agent_id.capacity()
+                    + session_id.capacity()
+                    + std::mem::size_of::<((String, String), Feed)>()
+                    + feed
+                        .events
+                        .iter()
+                        .map(|event| match event {
+                            AgentEvent::DependencyChanged {
+                                watched,
+                                resource,
+                                changed_by,
+                                lease_id,
+                                ..
+                            } => {
+                                watched.path.capacity()
+                                    + resource.path.capacity()
+                                    + changed_by.capacity()
+                                    + lease_id.capacity()
+                                    + std::mem::size_of::<AgentEvent>()
+                            }
+                            AgentEvent::LeaseGranted {
+                                lease_id, resource, ..
+                            }
+                            | AgentEvent::LeaseExpiring {
+                                lease_id, resource, ..
+                            } => {
+                                resource.path.capacity()
+                                    + lease_id.capacity()
+                                    + std::mem::size_of::<AgentEvent>()
+                            }
+                            AgentEvent::WaiterCancelled {
+                                request_id,
+                                agent_id,
+                                resource,
+                                ..
+                            } => {
+                                resource.path.capacity()
+                                    + request_id.capacity()
+                                    + agent_id.capacity()
+                                    + std::mem::size_of::<AgentEvent>()
+                            }
+                            AgentEvent::LeaseRevoked {
+                                lease_id,
+                                resource,
+                                revoked_by,
+                                reason,
+                                ..
+                            } => {
+                                resource.path.capacity()
+                                    + lease_id.capacity()
+                                    + revoked_by.as_ref().map_or(0, String::capacity)
+                                    + reason.capacity()
+                                    + std::mem::size_of::<AgentEvent>()
+                            }
+                            AgentEvent::NamespaceReaped { namespace, .. } => {
+                                namespace.capacity() + std::mem::size_of::<AgentEvent>()
+                            }
+                        })
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::feed::{AgentEvent, FEED_CAPACITY, FeedRegistry};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, LeaseResult, Predicate, ResourceRef, ResourceType, SPOTriple};
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn changed(lease_id: &str) -> AgentEvent {
+        AgentEvent::DependencyChanged {
+            watched: file("/a.ts"),
+            resource: file("/a.ts"),
+            predicate: Predicate::Mutates,
+            changed_by: "writer".to_string(),
+            lease_id: lease_id.to_string(),
+            at: 1000,
+        }
+    }
+
+    #[test]
+    fn test_feed_drops_oldest_when_full() {
+        let mut feeds = FeedRegistry::default();
+        for i in 0..FEED_CAPACITY + 2 {
+            feeds.push("reader", "s1", changed(&i.to_string()));
+        }
+        let batch = feeds.poll("reader", "s1");
+        assert_eq!(batch.dropped, 2);
+        assert_eq!(batch.events.len(), FEED_CAPACITY);
+        assert_eq!(batch.events[0], changed("2"));
+
+        let batch = feeds.poll("reader", "s1");
+        assert!(batch.events.is_empty());
+        assert_eq!(batch.dropped, 0);
+        assert!(feeds.poll("reader", "s2").events.is_empty());
+
+        assert_eq!(feeds.retain(|_, session_id| session_id != "s1"), 1);
+        assert!(feeds.is_empty());
+    }
+
+    #[test]
+    fn test_release_notifies_dependent_sessions() {
+        let mut client = KlockClient::new();
+        client.register_agent("reader", 100);
+        client.register_agent("writer", 200);
+        client.register_agent("viewer", 300);
+        client.register_agent("cleaner", 400);
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "reader".to_string(),
+            intents: vec![SPOTriple {
+                id: "t1".to_string(),
+                subject: "reader".to_string(),
+                predicate: Predicate::DependsOn,
+                object: file("/src/a.ts"),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        assert_eq!(
+            client.watched_resources("reader", "s1"),
+            vec![file("/src/a.ts")]
+        );
+
+        // Lease IDs are per agent and millisecond, so use one agent each
+        let acquire =
+            |client: &mut KlockClient, agent: &str, path: &str, predicate: &str| match client
+                .acquire_lease(agent, "w1", "FILE", path, predicate, 60_000)
+            {
+                LeaseResult::Success { lease } => lease,
+                other => panic!("Expected lease, got {:?}", other),
+            };
+        let read = acquire(&mut client, "viewer", "/src/a.ts", "CONSUMES");
+        assert!(client.release_lease(&read.id));
+        assert!(client.poll_events("reader", "s1").events.is_empty());
+
+        let mutation = acquire(&mut client, "writer", "/src/", "MUTATES");
+        let unrelated = acquire(&mut client, "cleaner", "/docs/b.md", "DELETES");
+        // Nothing is delivered while the change is in progress
+        assert!(client.poll_events("reader", "s1").events.is_empty());
+
+        assert!(client.release_lease(&unrelated.id));
+        assert!(client.release_lease(&mutation.id));
+        let batch = client.poll_events("reader", "s1");
+        assert_eq!(batch.events.len(), 1);
+        let AgentEvent::DependencyChanged {
+            watched,
+            resource,
+            changed_by,
+            lease_id,
+            ..
+        } = &batch.events[0]
+        else {
+            panic!("Expected dependency_changed");
+        };
+        assert_eq!(watched, &file("/src/a.ts"));
+        assert_eq!(resource, &file("/src/"));
+        assert_eq!(changed_by, "writer");
+        assert_eq!(lease_id, &mutation.id);
+        assert!(client.poll_events("reader", "s1").events.is_empty());
+        assert!(client.poll_events("writer", "w1").events.is_empty());
+
+        let json = serde_json::to_value(&batch.events[0]).unwrap();
+        assert_eq!(json["type"], "dependency_changed");
+    }
+}
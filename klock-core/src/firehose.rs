@@ -0,0 +1,138 @@
+//! The lease firehose.
+//!
+//! Systems that enforce or mirror leases outside Klock (a filesystem guard,
+//! a VCS hook, a replica) need every lease state change, in order, with the
+//! lease it produced. When enabled, the client copies each event its store
+//! applies into a bounded buffer, numbering the entries with a sequence
+//! that grows by one per entry. Readers pass the last sequence they
+//! processed as a cursor and resume from there. Entries beyond the buffer's
+//! capacity are dropped oldest first; a reader that fell further behind
+//! than that learns how many it `missed` and resyncs from the active
+//! leases. Sequences restart with the process, which changes the `epoch`.
+//! Reaping a namespace is recorded too, after the releases it caused.
+//! Entries carry their change in an `EventEnvelope`.
+
+use crate::event::KlockEvent;
+use crate::types::{EventEnvelope, EventPayload, Lease};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// One lease state change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirehoseEntry {
+    /// Position in the firehose, starting at 1
+    pub seq: u64,
+    #[serde(flatten)]
+    pub event: EventEnvelope,
+}
+
+/// The entries after a cursor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirehosePage {
+    /// Identifies this run of sequences; a cursor from another epoch is
+    /// meaningless
+    pub epoch: u64,
+    pub entries: Vec<FirehoseEntry>,
+    /// Pass back as the cursor to read on after these entries
+    pub next_cursor: u64,
+    /// Entries after the cursor dropped before they were read
+    pub missed: u64,
+}
+
+/// The last `capacity` lease state changes. Disabled with a capacity of 0.
+#[derive(Debug, Default)]
+pub struct Firehose {
+    capacity: usize,
+    epoch: u64,
+    entries: VecDeque<FirehoseEntry>,
+    last_seq: u64,
+}
+
+impl Firehose {
+    pub fn new(capacity: usize, epoch: u64) -> Self {
+        Self {
+            capacity,
+            epoch,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Append the change `event` made, leaving `lease` behind
+    pub fn record(&mut self, at: u64, event: &KlockEvent, lease: Option<Lease>) {
+        self.push(EventEnvelope::lease_change(event, lease, at));
+    }
+
+    /// Append the reaping of `namespace`
+    pub fn record_namespace_reaped(&mut self, at: u64, namespace: &str) {
+        self.push(EventEnvelope::new(
+            EventPayload::NamespaceReaped {
+                namespace: namespace.to_string(),
+            },
+            at,
+        ));
+    }
+
+    /// Append `event` at the next sequence
+    fn push(&mut self, event: EventEnvelope) {
+        if !self.is_enabled() {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.last_seq += 1;
+        self.entries.push_back(FirehoseEntry {
+            seq: self.last_seq,
+            event,
+        });
+    }
+
+    /// Up to `limit` entries after `cursor` (0 reads from the oldest kept)
+    pub fn read(&self, cursor: u64, limit: usize) -> FirehosePage {
+        let first = self.entries.front().map_or(self.last_seq + 1, |e| e.seq);
+        let missed = first
+            .saturating_sub(cursor.saturating_add(1))
+            .min(self.last_seq.saturating_sub(cursor));
+        let entries: Vec<FirehoseEntry> = self
+            .entries
+            .iter()
+            .skip(cursor.saturating_add(1).saturating_sub(first) as usize)
+            .take(limit)
+            .cloned()
+            .collect();
+        FirehosePage {
+            epoch: self.epoch,
+            next_cursor: entries.last().map_or(cursor.max(first - 1), |e| e.seq),
+            entries,
+            missed,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Approximate footprint of the kept entries
+    pub fn estimated_bytes(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| {
+                std::mem::size_of::<FirehoseEntry>()
+                    + entry.event.lease().map_or(0, |lease| {
+                        lease.id.capacity()
+                            + lease.agent_id.capacity()
+                            + lease.session_id.capacity()
+                            + lease.resource.path.capacity()
+                    })
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::event::KlockEvent;
+    use crate::firehose::{Firehose, FirehoseEntry};
+    use crate::types::{EVENT_SCHEMA_VERSION, EventPayload, LeaseResult, LeaseState};
+    use std::sync::Arc;
+
+    /// The lease change an entry records
+    fn change(entry: &FirehoseEntry) -> &KlockEvent {
+        match &entry.event.event {
+            EventPayload::LeaseChange { change, .. } => change,
+            other => panic!("Expected a lease change, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_firehose_disabled_by_default() {
+        let mut client = KlockClient::new();
+        client.register_agent("a", 100);
+        client.acquire_lease("a", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert!(client.firehose(0, 100).is_none());
+        assert_eq!(client.memory_report().firehose.count, 0);
+    }
+
+    #[test]
+    fn test_firehose_records_changes_in_order_with_leases() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.set_firehose_capacity(100);
+        client.register_agent("holder", 200);
+        client.register_agent("waiter", 100);
+
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("holder", "s1", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        client.acquire_lease("waiter", "s2", "FILE", "/a.ts", "MUTATES", 60_000);
+        clock.advance(10);
+        assert!(client.heartbeat_lease(&lease.id, 1010));
+        assert!(client.release_lease(&lease.id));
+
+        let page = client.firehose(0, 100).unwrap();
+        assert_eq!(page.epoch, 1000);
+        assert_eq!(page.missed, 0);
+        let seqs: Vec<u64> = page.entries.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![1, 2, 3, 4]);
+        assert_eq!(page.next_cursor, 4);
+        let kinds: Vec<&str> = page.entries.iter().map(|e| change(e).name()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "LeaseCreated",
+                "LeaseRenewed",
+                "LeaseReleased",
+                // The queued waiter is granted the freed resource
+                "LeaseCreated",
+            ]
+        );
+        let renewed = page.entries[1].event.lease().unwrap();
+        assert_eq!(renewed.expires_at, 61_010);
+        let released = page.entries[2].event.lease().unwrap();
+        assert_eq!(released.state, LeaseState::Released);
+        assert!(released.revision > renewed.revision);
+        assert_eq!(page.entries[3].event.actor.as_deref(), Some("waiter"));
+        assert!(
+            page.entries
+                .iter()
+                .all(|e| e.event.schema_version == EVENT_SCHEMA_VERSION)
+        );
+
+        // Nothing new after the cursor
+        assert!(client.firehose(4, 100).unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn test_cursor_resumes_and_reports_missed_entries() {
+        let mut firehose = Firehose::new(3, 1);
+        let event = KlockEvent::LeasesCompacted { before: 0 };
+        for at in 0..5 {
+            firehose.record(at, &event, None);
+        }
+        assert_eq!(firehose.len(), 3);
+
+        // Entries 1 and 2 were dropped before a reader at 0 got to them
+        let page = firehose.read(0, 2);
+        assert_eq!(page.missed, 2);
+        let seqs: Vec<u64> = page.entries.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![3, 4]);
+
+        let page = firehose.read(page.next_cursor, 10);
+        assert_eq!(page.missed, 0);
+        assert_eq!(page.entries.len(), 1);
+        assert!(matches!(
+            change(&page.entries[0]),
+            KlockEvent::LeasesCompacted { before: 0 }
+        ));
+        assert_eq!(page.next_cursor, 5);
+
+        let page = firehose.read(5, 10);
+        assert!(page.entries.is_empty());
+        assert_eq!(page.next_cursor, 5);
+    }
+}
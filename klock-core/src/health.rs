@@ -0,0 +1,253 @@
+//! Agent health scoring.
+//!
+//! Tracks negative signals per agent (Die-retry loops, heartbeats on dead
+//! leases, leases that expired instead of being released) over a sliding
+//! window and turns them into a 0–100 score. Policies can deprioritize or
+//! temporarily quarantine agents whose score drops too low.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A negative health signal observed for an agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HealthSignal {
+    /// The agent received a Die verdict
+    Die,
+    /// The agent heartbeated a lease that was no longer active
+    MissedHeartbeat,
+    /// One of the agent's leases expired without being released
+    Expiration,
+}
+
+/// Coarse health state derived from the score and policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HealthStatus {
+    Healthy,
+    /// Priority temporarily lowered
+    Demoted,
+    /// All acquisitions refused until the quarantine ends
+    Quarantined,
+}
+
+/// Thresholds and weights for health scoring. Actions are disabled by default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthPolicy {
+    /// Sliding window over which signals count against the score
+    pub window_ms: u64,
+    pub die_penalty: u32,
+    pub missed_heartbeat_penalty: u32,
+    pub expiration_penalty: u32,
+    /// Agents scoring below this are deprioritized (`None` = never)
+    pub deprioritize_below: Option<u32>,
+    /// Added to a demoted agent's priority timestamp, making it "younger"
+    pub deprioritize_by: u64,
+    /// Agents scoring below this are quarantined (`None` = never)
+    pub quarantine_below: Option<u32>,
+    /// How long a quarantine lasts
+    pub quarantine_ms: u64,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            window_ms: 10 * 60 * 1000,
+            die_penalty: 2,
+            missed_heartbeat_penalty: 5,
+            expiration_penalty: 10,
+            deprioritize_below: None,
+            deprioritize_by: 60 * 60 * 1000,
+            quarantine_below: None,
+            quarantine_ms: 60 * 1000,
+        }
+    }
+}
+
+/// Point-in-time health report for one agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentHealth {
+    pub agent_id: String,
+    pub score: u32,
+    pub status: HealthStatus,
+    pub dies: usize,
+    pub missed_heartbeats: usize,
+    pub expirations: usize,
+    pub quarantined_until: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct AgentRecord {
+    signals: Vec<(u64, HealthSignal)>,
+    demoted: bool,
+    quarantined_until: Option<u64>,
+}
+
+/// Per-agent health bookkeeping
+#[derive(Debug, Default)]
+pub struct HealthTracker {
+    policy: HealthPolicy,
+    agents: HashMap<String, AgentRecord>,
+}
+
+impl HealthTracker {
+    pub fn new(policy: HealthPolicy) -> Self {
+        Self {
+            policy,
+            agents: HashMap::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &HealthPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: HealthPolicy) {
+        self.policy = policy;
+    }
+
+    /// Record a negative signal for an agent
+    pub fn record(&mut self, agent_id: &str, signal: HealthSignal, now: u64) {
+        let since = now.saturating_sub(self.policy.window_ms);
+        let record = self.agents.entry(agent_id.to_string()).or_default();
+        record.signals.retain(|(at, _)| *at >= since);
+        record.signals.push((now, signal));
+    }
+
+    /// Whether any health data exists for this agent
+    pub fn knows(&self, agent_id: &str) -> bool {
+        self.agents.contains_key(agent_id)
+    }
+
+    /// Current score in 0..=100 (100 = perfectly healthy)
+    pub fn score(&self, agent_id: &str, now: u64) -> u32 {
+        let (dies, missed, expirations) = self.counts(agent_id, now);
+        let penalty = dies as u32 * self.policy.die_penalty
+            + missed as u32 * self.policy.missed_heartbeat_penalty
+            + expirations as u32 * self.policy.expiration_penalty;
+        100u32.saturating_sub(penalty)
+    }
+
+    /// Returns the end of the agent's quarantine if it is still in effect
+    pub fn quarantined_until(&self, agent_id: &str, now: u64) -> Option<u64> {
+        self.agents
+            .get(agent_id)
+            .and_then(|r| r.quarantined_until)
+            .filter(|until| *until > now)
+    }
+
+    pub fn is_demoted(&self, agent_id: &str) -> bool {
+        self.agents.get(agent_id).is_some_and(|r| r.demoted)
+    }
+
+    /// Re-evaluate the agent against the policy, entering or leaving
+    /// demotion and quarantine as needed.
+    pub fn evaluate(&mut self, agent_id: &str, now: u64) -> HealthStatus {
+        if self.quarantined_until(agent_id, now).is_some() {
+            return HealthStatus::Quarantined;
+        }
+
+        let score = self.score(agent_id, now);
+        let quarantine = self.policy.quarantine_below.is_some_and(|t| score < t);
+        let demote = self.policy.deprioritize_below.is_some_and(|t| score < t);
+        let quarantine_ms = self.policy.quarantine_ms;
+
+        let Some(record) = self.agents.get_mut(agent_id) else {
+            return HealthStatus::Healthy;
+        };
+
+        if quarantine {
+            // Serving the quarantine wipes the slate clean
+            record.quarantined_until = Some(now + quarantine_ms);
+            record.signals.clear();
+            record.demoted = false;
+            return HealthStatus::Quarantined;
+        }
+
+        record.demoted = demote;
+        if demote {
+            HealthStatus::Demoted
+        } else {
+            HealthStatus::Healthy
+        }
+    }
+
+    /// Build a report for the agent without changing any state
+    pub fn report(&self, agent_id: &str, now: u64) -> AgentHealth {
+        let (dies, missed_heartbeats, expirations) = self.counts(agent_id, now);
+        let quarantined_until = self.quarantined_until(agent_id, now);
+        let status = if quarantined_until.is_some() {
+            HealthStatus::Quarantined
+        } else if self.is_demoted(agent_id) {
+            HealthStatus::Demoted
+        } else {
+            HealthStatus::Healthy
+        };
+
+        AgentHealth {
+            agent_id: agent_id.to_string(),
+            score: self.score(agent_id, now),
+            status,
+            dies,
+            missed_heartbeats,
+            expirations,
+            quarantined_until,
+        }
+    }
+
+    /// Number of agents with a health record
+    pub fn len(&self) -> usize {
+        self.agents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    /// Approximate footprint of all health records
+    pub fn estimated_bytes(&self) -> usize {
+        self.agents
+            .iter()
+            .map(|(agent_id, record)| {
+                agent_id.capacity()
+                    + std::mem::size_of::<AgentRecord>()
+                    + record.signals.capacity() * std::mem::size_of::<(u64, HealthSignal)>()
+            })
+            .sum()
+    }
+
+    /// Drop the agent's record, signals, demotion and quarantine included.
+    /// Returns whether it had one.
+    pub fn forget(&mut self, agent_id: &str) -> bool {
+        self.agents.remove(agent_id).is_some()
+    }
+
+    /// Drop records that carry no live signal, demotion, or quarantine.
+    /// Returns the number of records removed.
+    pub fn compact(&mut self, now: u64) -> usize {
+        let since = now.saturating_sub(self.policy.window_ms);
+        let initial = self.agents.len();
+        self.agents.retain(|_, record| {
+            record.signals.retain(|(at, _)| *at >= since);
+            let quarantined = record.quarantined_until.is_some_and(|until| until > now);
+            !record.signals.is_empty() || record.demoted || quarantined
+        });
+        initial - self.agents.len()
+    }
+
+    fn counts(&self, agent_id: &str, now: u64) -> (usize, usize, usize) {
+        let since = now.saturating_sub(self.policy.window_ms);
+        let mut counts = (0, 0, 0);
+        if let Some(record) = self.agents.get(agent_id) {
+            for (at, signal) in &record.signals {
+                if *at < since {
+                    continue;
+                }
+                match signal {
+                    HealthSignal::Die => counts.0 += 1,
+                    HealthSignal::MissedHeartbeat => counts.1 += 1,
+                    HealthSignal::Expiration => counts.2 += 1,
+                }
+            }
+        }
+        counts
+    }
+}
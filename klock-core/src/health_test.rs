@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::health::{HealthPolicy, HealthSignal, HealthStatus, HealthTracker};
+    use crate::types::{LeaseFailureReason, LeaseResult};
+
+    #[test]
+    fn test_score_drops_with_signals() {
+        let mut tracker = HealthTracker::default();
+        assert_eq!(tracker.score("agent_a", 1000), 100);
+
+        tracker.record("agent_a", HealthSignal::Die, 1000);
+        tracker.record("agent_a", HealthSignal::Expiration, 1000);
+        tracker.record("agent_a", HealthSignal::MissedHeartbeat, 1000);
+
+        // 2 + 10 + 5
+        assert_eq!(tracker.score("agent_a", 1000), 83);
+        assert_eq!(tracker.score("agent_b", 1000), 100);
+    }
+
+    #[test]
+    fn test_signals_age_out_of_window() {
+        let mut tracker = HealthTracker::new(HealthPolicy {
+            window_ms: 1000,
+            ..HealthPolicy::default()
+        });
+        tracker.record("agent_a", HealthSignal::Expiration, 1000);
+        assert_eq!(tracker.score("agent_a", 1500), 90);
+        assert_eq!(tracker.score("agent_a", 2500), 100);
+    }
+
+    #[test]
+    fn test_quarantine_and_release() {
+        let mut tracker = HealthTracker::new(HealthPolicy {
+            quarantine_below: Some(95),
+            quarantine_ms: 500,
+            ..HealthPolicy::default()
+        });
+
+        tracker.record("agent_a", HealthSignal::Expiration, 1000);
+        assert_eq!(tracker.evaluate("agent_a", 1000), HealthStatus::Quarantined);
+        assert_eq!(tracker.quarantined_until("agent_a", 1200), Some(1500));
+
+        // Quarantine served: slate is clean again
+        assert_eq!(tracker.evaluate("agent_a", 1600), HealthStatus::Healthy);
+        assert_eq!(tracker.report("agent_a", 1600).score, 100);
+    }
+
+    #[test]
+    fn test_demotion_without_quarantine() {
+        let mut tracker = HealthTracker::new(HealthPolicy {
+            deprioritize_below: Some(99),
+            ..HealthPolicy::default()
+        });
+
+        tracker.record("agent_a", HealthSignal::Die, 1000);
+        assert_eq!(tracker.evaluate("agent_a", 1000), HealthStatus::Demoted);
+        assert!(tracker.is_demoted("agent_a"));
+    }
+
+    #[test]
+    fn test_client_quarantines_die_looping_agent() {
+        let mut client = KlockClient::new();
+        client.set_health_policy(HealthPolicy {
+            quarantine_below: Some(99),
+            ..HealthPolicy::default()
+        });
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+
+        assert!(matches!(
+            client.acquire_lease("senior", "s1", "FILE", "/src/app.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        assert!(matches!(
+            client.acquire_lease("junior", "s2", "FILE", "/src/app.ts", "MUTATES", 60_000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            }
+        ));
+
+        // The Die pushed the junior below the threshold; even a free resource is refused
+        assert!(matches!(
+            client.acquire_lease("junior", "s2", "FILE", "/src/other.ts", "MUTATES", 60_000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::AgentQuarantined,
+                ..
+            }
+        ));
+        assert_eq!(
+            client.agent_health("junior").status,
+            HealthStatus::Quarantined
+        );
+    }
+
+    #[test]
+    fn test_client_demotion_lowers_effective_priority() {
+        let mut client = KlockClient::new();
+        client.set_health_policy(HealthPolicy {
+            deprioritize_below: Some(99),
+            deprioritize_by: 1000,
+            ..HealthPolicy::default()
+        });
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+
+        let _ = client.acquire_lease("senior", "s1", "FILE", "/src/app.ts", "MUTATES", 60_000);
+        let _ = client.acquire_lease("junior", "s2", "FILE", "/src/app.ts", "MUTATES", 60_000);
+
+        assert_eq!(client.agent_priority("junior"), Some(1200.into()));
+        assert_eq!(client.agent_health("junior").status, HealthStatus::Demoted);
+    }
+}
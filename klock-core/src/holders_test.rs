@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::types::{Lease, LeaseResult};
+    use std::sync::Arc;
+
+    fn acquire(client: &mut KlockClient, agent_id: &str, path: &str, ttl: u64) -> Lease {
+        match client.acquire_lease(agent_id, "s1", "FILE", path, "MUTATES", ttl) {
+            LeaseResult::Success { lease } => lease,
+            other => panic!("Expected success, got {:?}", other),
+        }
+    }
+
+    fn holders(client: &KlockClient, key: &str, at: u64) -> Vec<String> {
+        client
+            .holders_at(key, at)
+            .unwrap()
+            .into_iter()
+            .map(|lease| lease.agent_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_holders_at_a_past_instant() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("a", 100);
+        client.register_agent("b", 200);
+
+        let first = acquire(&mut client, "a", "/src/a.ts", 60_000);
+        clock.advance(1_000);
+        assert!(client.release_lease(&first.id));
+        assert_eq!(client.get_lease(&first.id).unwrap().ended_at, Some(2000));
+        acquire(&mut client, "b", "/src/a.ts", 1_000);
+        clock.advance(5_000);
+        client.evict_expired();
+
+        assert!(holders(&client, "FILE:/src/a.ts", 999).is_empty());
+        assert_eq!(holders(&client, "FILE:/src/a.ts", 1500), ["a"]);
+        // The release ended the first lease; expiry ended the second at its TTL
+        assert_eq!(holders(&client, "FILE:/src/a.ts", 2000), ["b"]);
+        assert!(holders(&client, "FILE:/src/a.ts", 3000).is_empty());
+        // Overlapping resources are covered too
+        assert_eq!(holders(&client, "FILE:/src/**", 1500), ["a"]);
+        assert!(holders(&client, "FILE:/src/b.ts", 1500).is_empty());
+        assert!(client.holders_at("/src/a.ts", 1500).is_err());
+
+        // The in-memory store forgets compacted leases
+        clock.advance(10 * 60 * 1000);
+        client.compact();
+        assert!(holders(&client, "FILE:/src/a.ts", 1500).is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+//! Hybrid client mode: local decisions, central reconciliation.
+//!
+//! Latency-sensitive agents cannot afford a round trip per lease. A
+//! `LocalReplica` keeps a copy of the server's live leases, seeded from a
+//! snapshot and kept current by streaming the firehose, and decides
+//! requests against it. Requests for low-risk predicates (`Consumes` by
+//! default) that conflict with nothing it knows of are granted on the spot
+//! as provisional leases; everything else is forwarded to the server.
+//!
+//! Provisional leases are sent to the server in the background. The server
+//! stays the authority: a grant confirms the provisional lease under the
+//! server's lease ID, and a refusal turns into a `LeaseRevoked` event for
+//! the agent, since the work it started is no longer protected. The replica
+//! is transport-agnostic; the caller moves pages and requests over the
+//! wire.
+
+use crate::conflict::{ConflictDetail, ConflictEngine};
+use crate::feed::AgentEvent;
+use crate::firehose::FirehosePage;
+use crate::types::{Lease, LeaseState, Predicate, ResourceRef};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Which requests a replica may grant without asking the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HybridPolicy {
+    /// Predicates granted optimistically when nothing local conflicts
+    pub optimistic: Vec<Predicate>,
+}
+
+impl Default for HybridPolicy {
+    fn default() -> Self {
+        Self {
+            optimistic: vec![Predicate::Consumes],
+        }
+    }
+}
+
+/// A lease granted locally that the server has not confirmed yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvisionalLease {
+    /// Local ID, `provisional_<n>`
+    pub id: String,
+    pub agent_id: String,
+    pub session_id: String,
+    pub resource: ResourceRef,
+    pub predicate: Predicate,
+    pub ttl: u64,
+    pub granted_at: u64,
+}
+
+impl ProvisionalLease {
+    /// The provisional lease as the lease it stands in for
+    fn as_lease(&self) -> Lease {
+        Lease::new(
+            self.id.clone(),
+            self.agent_id.clone(),
+            self.session_id.clone(),
+            self.resource.clone(),
+            self.predicate,
+            self.ttl,
+            self.granted_at,
+        )
+    }
+}
+
+/// What a replica made of a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HybridDecision {
+    /// Granted locally; reconciled with the server later
+    Optimistic(ProvisionalLease),
+    /// Send the request to the server. `blockers` are the local leases in
+    /// the way, empty when the predicate is not optimistic.
+    Forward { blockers: Vec<ConflictDetail> },
+}
+
+/// Where a provisional lease is in reconciliation
+#[derive(Debug, Clone)]
+struct Provisional {
+    lease: ProvisionalLease,
+    sent: bool,
+    /// Released locally while its request was in flight
+    released: bool,
+}
+
+/// A local copy of the server's live leases, with the provisional leases
+/// granted against it
+#[derive(Debug, Default)]
+pub struct LocalReplica {
+    engine: ConflictEngine,
+    policy: HybridPolicy,
+    epoch: Option<u64>,
+    cursor: u64,
+    leases: BTreeMap<String, Lease>,
+    provisional: BTreeMap<String, Provisional>,
+    /// Confirmed provisional leases, by local ID, to the server's lease ID
+    confirmed: BTreeMap<String, String>,
+    next_id: u64,
+    events: Vec<AgentEvent>,
+}
+
+impl LocalReplica {
+    pub fn new(policy: HybridPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// Judge conflicts with `engine`, which should match the server's
+    pub fn with_engine(mut self, engine: ConflictEngine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Replace the replicated leases with `leases`, taken after reading the
+    /// firehose up to `cursor` in `epoch`. Entries after the cursor are
+    /// applied on top, so the snapshot may already include some of them.
+    pub fn seed(&mut self, epoch: u64, cursor: u64, leases: Vec<Lease>) {
+        self.epoch = Some(epoch);
+        self.cursor = cursor;
+        self.leases = leases
+            .into_iter()
+            .filter(|lease| !lease.is_terminal())
+            .map(|lease| (lease.id.clone(), lease))
+            .collect();
+    }
+
+    /// The cursor to read the firehose from next
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    pub fn epoch(&self) -> Option<u64> {
+        self.epoch
+    }
+
+    /// Apply a firehose page read from `cursor()`, returning how many
+    /// entries changed a lease. Fails without applying anything when the
+    /// server restarted or entries were dropped before they were read; the
+    /// caller then seeds the replica again.
+    pub fn apply_page(&mut self, page: &FirehosePage) -> Result<usize, String> {
+        if let Some(epoch) = self.epoch
+            && epoch != page.epoch
+        {
+            return Err(format!(
+                "Firehose epoch changed from {} to {}; resync the replica",
+                epoch, page.epoch
+            ));
+        }
+        if page.missed > 0 {
+            return Err(format!(
+                "Missed {} firehose entries; resync the replica",
+                page.missed
+            ));
+        }
+        for entry in &page.entries {
+            entry.event.check_version()?;
+        }
+
+        let mut applied = 0;
+        for entry in page.entries.iter().filter(|entry| entry.seq > self.cursor) {
+            // Each entry carries the lease as it left it, so applying one
+            // twice, or on top of a snapshot that already has it, is harmless
+            let Some(lease) = entry.event.lease() else {
+                continue;
+            };
+            if lease.is_terminal() {
+                self.leases.remove(&lease.id);
+            } else {
+                self.leases.insert(lease.id.clone(), lease.clone());
+            }
+            applied += 1;
+        }
+        self.epoch = Some(page.epoch);
+        self.cursor = self.cursor.max(page.next_cursor);
+        Ok(applied)
+    }
+
+    /// Decide a request at `now`. Optimistic predicates that conflict with
+    /// no live replicated or provisional lease are granted provisionally.
+    pub fn decide(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        predicate: Predicate,
+        ttl: u64,
+        now: u64,
+    ) -> HybridDecision {
+        if !self.policy.optimistic.contains(&predicate) {
+            return HybridDecision::Forward {
+                blockers: Vec::new(),
+            };
+        }
+        let blockers = self.engine.check_leases_all(
+            agent_id,
+            session_id,
+            predicate,
+            &resource,
+            &self.live_leases(now),
+        );
+        if !blockers.is_empty() {
+            return HybridDecision::Forward { blockers };
+        }
+
+        self.next_id += 1;
+        let lease = ProvisionalLease {
+            id: format!("provisional_{}", self.next_id),
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            resource,
+            predicate,
+            ttl,
+            granted_at: now,
+        };
+        self.provisional.insert(
+            lease.id.clone(),
+            Provisional {
+                lease: lease.clone(),
+                sent: false,
+                released: false,
+            },
+        );
+        HybridDecision::Optimistic(lease)
+    }
+
+    /// Active replicated leases and provisional leases live at `now`
+    pub fn live_leases(&self, now: u64) -> Vec<Lease> {
+        self.leases
+            .values()
+            .filter(|lease| lease.state == LeaseState::Active)
+            .cloned()
+            .chain(
+                self.provisional
+                    .values()
+                    .filter(|p| !p.released)
+                    .map(|p| p.lease.as_lease()),
+            )
+            .filter(|lease| lease.expires_at > now)
+            .collect()
+    }
+
+    /// Provisional leases not yet sent to the server, marked as sent
+    pub fn take_unsent(&mut self) -> Vec<ProvisionalLease> {
+        self.provisional
+            .values_mut()
+            .filter(|p| !p.sent)
+            .map(|p| {
+                p.sent = true;
+                p.lease.clone()
+            })
+            .collect()
+    }
+
+    /// The server granted provisional lease `id` as `lease_id`. Returns the
+    /// server lease to release when it was released locally in the
+    /// meantime.
+    pub fn confirm(&mut self, id: &str, lease_id: &str) -> Option<String> {
+        let provisional = self.provisional.remove(id)?;
+        if provisional.released {
+            return Some(lease_id.to_string());
+        }
+        let mut lease = provisional.lease.as_lease();
+        lease.id = lease_id.to_string();
+        // The firehose brings the server's copy; until then, keep ours
+        self.leases.entry(lease.id.clone()).or_insert(lease);
+        self.confirmed.insert(id.to_string(), lease_id.to_string());
+        None
+    }
+
+    /// The server refused provisional lease `id` at `now`, over `holder`
+    /// when it named one. The agent learns of it through a `LeaseRevoked`
+    /// event, also returned; none when it was already released.
+    pub fn refuse(
+        &mut self,
+        id: &str,
+        reason: &str,
+        holder: Option<String>,
+        now: u64,
+    ) -> Option<AgentEvent> {
+        let provisional = self.provisional.remove(id)?;
+        if provisional.released {
+            return None;
+        }
+        let event = AgentEvent::LeaseRevoked {
+            lease_id: id.to_string(),
+            resource: provisional.lease.resource,
+            predicate: provisional.lease.predicate,
+            revoked_by: holder,
+            reason: format!("The server refused the optimistic grant: {}", reason),
+            at: now,
+        };
+        self.events.push(event.clone());
+        Some(event)
+    }
+
+    /// Release a lease granted through the replica. Returns the server
+    /// lease to release, if the server holds one yet; a request in flight
+    /// is released once the server confirms it. IDs the replica did not
+    /// grant are returned as they are.
+    pub fn release(&mut self, id: &str) -> Option<String> {
+        if let Some(lease_id) = self.confirmed.remove(id) {
+            self.leases.remove(&lease_id);
+            return Some(lease_id);
+        }
+        match self.provisional.get_mut(id) {
+            Some(provisional) if provisional.sent => {
+                provisional.released = true;
+                None
+            }
+            Some(_) => {
+                self.provisional.remove(id);
+                None
+            }
+            None => Some(id.to_string()),
+        }
+    }
+
+    /// The server's ID for confirmed provisional lease `id`
+    pub fn server_lease_id(&self, id: &str) -> Option<&str> {
+        self.confirmed.get(id).map(String::as_str)
+    }
+
+    /// Whether `id` is still waiting for the server's answer
+    pub fn is_provisional(&self, id: &str) -> bool {
+        self.provisional.contains_key(id)
+    }
+
+    /// Revocations of refused provisional leases since the last call
+    pub fn take_events(&mut self) -> Vec<AgentEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
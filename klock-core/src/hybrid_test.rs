@@ -0,0 +1,294 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::feed::AgentEvent;
+    use crate::hybrid::{HybridDecision, HybridPolicy, LocalReplica, ProvisionalLease};
+    use crate::types::{LeaseResult, Predicate, ResourceRef, ResourceType};
+    use std::sync::Arc;
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn server() -> (KlockClient, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut server = KlockClient::deterministic(1, clock.clone());
+        server.set_firehose_capacity(100);
+        server.register_agent("writer", 100);
+        server.register_agent("reader", 200);
+        (server, clock)
+    }
+
+    /// A replica caught up with everything the server did so far
+    fn replica(server: &KlockClient) -> LocalReplica {
+        let mut replica = LocalReplica::new(HybridPolicy::default());
+        sync(&mut replica, server);
+        replica
+    }
+
+    fn sync(replica: &mut LocalReplica, server: &KlockClient) {
+        let page = server.firehose(replica.cursor(), 100).unwrap();
+        replica.apply_page(&page).unwrap();
+    }
+
+    fn optimistic(decision: HybridDecision) -> ProvisionalLease {
+        match decision {
+            HybridDecision::Optimistic(lease) => lease,
+            other => panic!("Expected an optimistic grant, got {:?}", other),
+        }
+    }
+
+    /// Send a provisional lease to the server as a real acquire
+    fn send(server: &mut KlockClient, lease: &ProvisionalLease) -> LeaseResult {
+        server.acquire_lease(
+            &lease.agent_id,
+            &lease.session_id,
+            "FILE",
+            &lease.resource.path,
+            "CONSUMES",
+            lease.ttl,
+        )
+    }
+
+    #[test]
+    fn test_consumes_without_local_conflict_is_granted_provisionally() {
+        let (server, _) = server();
+        let mut replica = replica(&server);
+
+        let lease = optimistic(replica.decide(
+            "reader",
+            "s1",
+            file("/src/api.ts"),
+            Predicate::Consumes,
+            60_000,
+            1000,
+        ));
+        assert_eq!(lease.id, "provisional_1");
+        assert!(replica.is_provisional(&lease.id));
+        assert_eq!(replica.take_unsent(), vec![lease]);
+        assert!(replica.take_unsent().is_empty());
+    }
+
+    #[test]
+    fn test_other_predicates_and_local_conflicts_are_forwarded() {
+        let (mut server, _) = server();
+        server.acquire_lease("writer", "s1", "FILE", "/src/api.ts", "MUTATES", 60_000);
+        let mut replica = replica(&server);
+
+        assert_eq!(
+            replica.decide(
+                "reader",
+                "s2",
+                file("/src/db.ts"),
+                Predicate::Mutates,
+                60_000,
+                1000
+            ),
+            HybridDecision::Forward {
+                blockers: Vec::new()
+            }
+        );
+        let HybridDecision::Forward { blockers } = replica.decide(
+            "reader",
+            "s2",
+            file("/src/api.ts"),
+            Predicate::Consumes,
+            60_000,
+            1000,
+        ) else {
+            panic!("Expected the request to be forwarded");
+        };
+        assert_eq!(blockers.len(), 1);
+        assert_eq!(blockers[0].holder, "writer");
+        assert!(replica.take_unsent().is_empty());
+    }
+
+    #[test]
+    fn test_server_grant_confirms_the_provisional_lease() {
+        let (mut server, _) = server();
+        let mut replica = replica(&server);
+        let lease = optimistic(replica.decide(
+            "reader",
+            "s1",
+            file("/src/api.ts"),
+            Predicate::Consumes,
+            60_000,
+            1000,
+        ));
+
+        let LeaseResult::Success { lease: granted } = send(&mut server, &lease) else {
+            panic!("Expected the server to grant it");
+        };
+        assert_eq!(replica.confirm(&lease.id, &granted.id), None);
+        assert_eq!(
+            replica.server_lease_id(&lease.id),
+            Some(granted.id.as_str())
+        );
+        assert!(!replica.is_provisional(&lease.id));
+
+        // The firehose brings the same lease; it is not counted twice
+        sync(&mut replica, &server);
+        assert_eq!(replica.live_leases(1000).len(), 1);
+        assert_eq!(replica.release(&lease.id), Some(granted.id));
+        assert!(replica.live_leases(1000).is_empty());
+        assert!(replica.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_server_refusal_surfaces_as_a_revocation() {
+        let (mut server, clock) = server();
+        let mut replica = replica(&server);
+        let lease = optimistic(replica.decide(
+            "reader",
+            "s1",
+            file("/src/api.ts"),
+            Predicate::Consumes,
+            60_000,
+            1000,
+        ));
+
+        // Before the replica hears of it, a senior writer takes the file
+        server.acquire_lease("writer", "s2", "FILE", "/src/api.ts", "MUTATES", 60_000);
+        clock.advance(50);
+        let LeaseResult::Failure {
+            reason, holders, ..
+        } = send(&mut server, &lease)
+        else {
+            panic!("Expected the server to refuse it");
+        };
+        let event = replica
+            .refuse(
+                &lease.id,
+                reason.as_str(),
+                holders.first().map(|holder| holder.agent_id.clone()),
+                1050,
+            )
+            .unwrap();
+        let AgentEvent::LeaseRevoked {
+            lease_id,
+            resource,
+            predicate,
+            revoked_by,
+            at,
+            ..
+        } = &event
+        else {
+            panic!("Expected a revocation, got {:?}", event);
+        };
+        assert_eq!(lease_id, &lease.id);
+        assert_eq!(resource, &file("/src/api.ts"));
+        assert_eq!(*predicate, Predicate::Consumes);
+        assert_eq!(revoked_by.as_deref(), Some("writer"));
+        assert_eq!(*at, 1050);
+        assert_eq!(replica.take_events(), vec![event]);
+        assert!(!replica.is_provisional(&lease.id));
+
+        // Now the replica knows the writer holds it
+        sync(&mut replica, &server);
+        assert!(matches!(
+            replica.decide(
+                "reader",
+                "s1",
+                file("/src/api.ts"),
+                Predicate::Consumes,
+                60_000,
+                1050
+            ),
+            HybridDecision::Forward { .. }
+        ));
+    }
+
+    #[test]
+    fn test_provisional_leases_conflict_with_later_requests() {
+        let policy = HybridPolicy {
+            optimistic: vec![Predicate::Consumes, Predicate::Mutates],
+        };
+        let mut replica = LocalReplica::new(policy);
+        optimistic(replica.decide(
+            "reader",
+            "s1",
+            file("/src/api.ts"),
+            Predicate::Mutates,
+            60_000,
+            1000,
+        ));
+        assert!(matches!(
+            replica.decide(
+                "writer",
+                "s2",
+                file("/src/api.ts"),
+                Predicate::Consumes,
+                60_000,
+                1000
+            ),
+            HybridDecision::Forward { .. }
+        ));
+        // Provisional leases lapse with their TTL like any other
+        optimistic(replica.decide(
+            "writer",
+            "s2",
+            file("/src/api.ts"),
+            Predicate::Consumes,
+            60_000,
+            61_000,
+        ));
+    }
+
+    #[test]
+    fn test_releasing_in_flight_releases_on_confirmation() {
+        let mut replica = LocalReplica::new(HybridPolicy::default());
+        let unsent = optimistic(replica.decide(
+            "reader",
+            "s1",
+            file("/a.ts"),
+            Predicate::Consumes,
+            60_000,
+            1000,
+        ));
+        assert_eq!(replica.release(&unsent.id), None);
+        assert!(replica.take_unsent().is_empty());
+
+        let sent = optimistic(replica.decide(
+            "reader",
+            "s1",
+            file("/b.ts"),
+            Predicate::Consumes,
+            60_000,
+            1000,
+        ));
+        replica.take_unsent();
+        assert_eq!(replica.release(&sent.id), None);
+        assert!(replica.live_leases(1000).is_empty());
+        assert_eq!(
+            replica.confirm(&sent.id, "lease_9"),
+            Some("lease_9".to_string())
+        );
+        assert_eq!(replica.server_lease_id(&sent.id), None);
+    }
+
+    #[test]
+    fn test_restart_or_gap_asks_for_a_resync() {
+        let (mut server, _) = server();
+        let mut replica = replica(&server);
+
+        let mut page = server.firehose(replica.cursor(), 100).unwrap();
+        page.epoch += 1;
+        assert!(replica.apply_page(&page).unwrap_err().contains("epoch"));
+
+        server.set_firehose_capacity(1);
+        server.acquire_lease("writer", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        server.acquire_lease("writer", "s1", "FILE", "/b.ts", "MUTATES", 60_000);
+        let page = server.firehose(0, 100).unwrap();
+        let mut fresh = LocalReplica::new(HybridPolicy::default());
+        assert!(fresh.apply_page(&page).unwrap_err().contains("Missed 1"));
+
+        // Seeding from the server's leases and reading on recovers
+        fresh.seed(page.epoch, page.next_cursor, server.get_active_leases());
+        assert_eq!(fresh.live_leases(1000).len(), 2);
+        assert_eq!(
+            fresh.apply_page(&server.firehose(fresh.cursor(), 100).unwrap()),
+            Ok(0)
+        );
+    }
+}
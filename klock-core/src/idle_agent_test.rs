@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::compaction::CompactionPolicy;
+    use crate::registry::UnregisteredAgentPolicy;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use std::sync::Arc;
+
+    fn client(idle_agent_ms: Option<u64>) -> (KlockClient, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.set_compaction_policy(CompactionPolicy {
+            idle_agent_ms,
+            ..CompactionPolicy::default()
+        });
+        for (agent_id, priority) in [("holder", 100), ("planner", 200), ("gone", 300)] {
+            client.register_agent(agent_id, priority);
+        }
+        (client, clock)
+    }
+
+    fn acquire(client: &mut KlockClient, agent_id: &str, path: &str) -> LeaseResult {
+        client.acquire_lease(agent_id, "s1", "FILE", path, "MUTATES", 600_000)
+    }
+
+    #[test]
+    fn test_compaction_unregisters_agents_that_hold_nothing() {
+        let (mut client, clock) = client(Some(10_000));
+        let LeaseResult::Success { lease } = acquire(&mut client, "holder", "/a.ts") else {
+            panic!("Expected Success");
+        };
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "planner".to_string(),
+            intents: vec![SPOTriple {
+                id: "i1".to_string(),
+                subject: "planner".to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, "/b.ts"),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+
+        clock.advance(10_000);
+        assert_eq!(client.compact().agents_unregistered, 0);
+        clock.advance(1);
+        assert_eq!(client.compact().agents_unregistered, 1);
+        assert!(client.agent_priority("gone").is_none());
+        assert!(client.agent_priority("holder").is_some());
+        assert!(client.agent_priority("planner").is_some());
+
+        // Idleness counts from the last thing the agent did
+        assert!(client.release_lease(&lease.id));
+        clock.advance(10_001);
+        assert_eq!(client.compact().agents_unregistered, 1);
+        assert!(client.agent_priority("holder").is_none());
+        assert!(client.agent_priority("planner").is_some());
+    }
+
+    #[test]
+    fn test_unregistered_idle_agents_must_register_again() {
+        let (mut client, clock) = client(None);
+        client.set_unregistered_agent_policy(UnregisteredAgentPolicy::Reject);
+        clock.advance(1_000_000);
+        assert_eq!(client.compact().agents_unregistered, 0);
+
+        client.set_compaction_policy(CompactionPolicy {
+            idle_agent_ms: Some(10_000),
+            ..CompactionPolicy::default()
+        });
+        assert_eq!(client.compact().agents_unregistered, 3);
+        assert!(matches!(
+            acquire(&mut client, "gone", "/a.ts"),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::UnregisteredAgent,
+                ..
+            }
+        ));
+
+        client.register_agent("gone", 300);
+        clock.advance(10_001);
+        assert!(matches!(
+            acquire(&mut client, "gone", "/a.ts"),
+            LeaseResult::Success { .. }
+        ));
+        assert_eq!(client.compact().agents_unregistered, 0);
+    }
+}
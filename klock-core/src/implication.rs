@@ -0,0 +1,113 @@
+//! Implication rules: operations that implicitly touch other resources.
+//!
+//! Renaming a file also changes the listing of its directory; providing a
+//! symbol also changes the file that defines it. Rules expand a requested
+//! operation into these implied operations so the `ConflictEngine` can check
+//! them alongside the original. Expansion is a single step: implied
+//! operations are not expanded again.
+
+use crate::types::{Predicate, ResourceRef, ResourceType};
+use serde::{Deserialize, Serialize};
+
+/// Where an implied operation lands, relative to the original resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImpliedTarget {
+    /// The listing of the directory containing a `FILE` resource
+    /// (`/src/auth.ts` → `/src/`). Only the directory entry itself is
+    /// touched, so it does not conflict with files inside the directory.
+    ParentDirectory,
+    /// The file defining a file-qualified `SYMBOL` (`/src/user.ts#User.login` → `/src/user.ts`)
+    ContainingFile,
+}
+
+impl ImpliedTarget {
+    /// Resolve the target for `resource`, if it has one.
+    pub fn resolve(self, resource: &ResourceRef) -> Option<ResourceRef> {
+        match (self, &resource.resource_type) {
+            (ImpliedTarget::ParentDirectory, ResourceType::File) => {
+                let trimmed = resource.path.trim_end_matches('/');
+                let idx = trimmed.rfind('/')?;
+                Some(ResourceRef::new(ResourceType::File, &trimmed[..=idx]))
+            }
+            (ImpliedTarget::ContainingFile, _) => resource.containing_file(),
+            _ => None,
+        }
+    }
+}
+
+/// "Performing `when` on a `on` resource implies performing `implies` on `target`"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImplicationRule {
+    pub name: String,
+    pub when: Predicate,
+    pub on: ResourceType,
+    pub implies: Predicate,
+    pub target: ImpliedTarget,
+}
+
+/// An operation derived from a requested one by an implication rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImpliedIntent {
+    pub predicate: Predicate,
+    pub resource: ResourceRef,
+    /// Name of the rule that produced it
+    pub rule: String,
+}
+
+impl ImplicationRule {
+    /// Renaming a file mutates its directory listing.
+    pub fn renames_mutate_parent_directory() -> Self {
+        Self {
+            name: "renames-mutate-parent-directory".into(),
+            when: Predicate::Renames,
+            on: ResourceType::File,
+            implies: Predicate::Mutates,
+            target: ImpliedTarget::ParentDirectory,
+        }
+    }
+
+    /// Providing a symbol mutates the file that defines it.
+    pub fn provides_mutate_containing_file() -> Self {
+        Self {
+            name: "provides-mutate-containing-file".into(),
+            when: Predicate::Provides,
+            on: ResourceType::Symbol,
+            implies: Predicate::Mutates,
+            target: ImpliedTarget::ContainingFile,
+        }
+    }
+
+    /// The built-in rule set.
+    pub fn standard() -> Vec<Self> {
+        vec![
+            Self::renames_mutate_parent_directory(),
+            Self::provides_mutate_containing_file(),
+        ]
+    }
+
+    /// The operation this rule derives from `predicate` on `resource`, if any.
+    pub fn apply(&self, predicate: Predicate, resource: &ResourceRef) -> Option<ImpliedIntent> {
+        if predicate != self.when || resource.resource_type != self.on {
+            return None;
+        }
+        Some(ImpliedIntent {
+            predicate: self.implies,
+            resource: self.target.resolve(resource)?,
+            rule: self.name.clone(),
+        })
+    }
+}
+
+impl ImpliedIntent {
+    /// Human-readable explanation, e.g. for verdicts
+    pub fn describe(&self, predicate: Predicate, resource: &ResourceRef) -> String {
+        format!(
+            "{:?} {} implies {:?} {} ({})",
+            predicate,
+            resource.key(),
+            self.predicate,
+            self.resource.key(),
+            self.rule
+        )
+    }
+}
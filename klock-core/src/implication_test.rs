@@ -0,0 +1,160 @@
+#[cfg(test)]
+mod tests {
+    use crate::conflict::{ConflictEngine, ConflictResult};
+    use crate::deadlock::WaitForGraph;
+    use crate::dependency::DependencyGraph;
+    use crate::implication::{ImplicationRule, ImpliedTarget};
+    use crate::state::{IntentManifest, KernelVerdictStatus, KlockKernel, StateSnapshot};
+    use crate::types::{Confidence, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::collections::HashMap;
+
+    fn make_triple(agent: &str, pred: Predicate, res: ResourceRef, session: &str) -> SPOTriple {
+        SPOTriple {
+            id: format!("t_{}_{}", agent, res.path),
+            subject: agent.to_string(),
+            predicate: pred,
+            object: res,
+            timestamp: 1000,
+            confidence: Confidence::High,
+            session_id: session.to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        }
+    }
+
+    fn standard_engine() -> ConflictEngine {
+        ImplicationRule::standard()
+            .into_iter()
+            .fold(ConflictEngine::default(), ConflictEngine::with_implication)
+    }
+
+    #[test]
+    fn targets_resolve() {
+        let file = ResourceRef::new(ResourceType::File, "/src/auth.ts");
+        assert_eq!(
+            ImpliedTarget::ParentDirectory.resolve(&file),
+            Some(ResourceRef::new(ResourceType::File, "/src/"))
+        );
+        assert_eq!(
+            ImpliedTarget::ParentDirectory.resolve(&ResourceRef::new(ResourceType::File, "/src/")),
+            Some(ResourceRef::new(ResourceType::File, "/"))
+        );
+
+        let qualified = ResourceRef::new(ResourceType::Symbol, "/src/user.ts#User.login");
+        assert_eq!(
+            ImpliedTarget::ContainingFile.resolve(&qualified),
+            Some(ResourceRef::new(ResourceType::File, "/src/user.ts"))
+        );
+        let bare = ResourceRef::new(ResourceType::Symbol, "User.login");
+        assert_eq!(ImpliedTarget::ContainingFile.resolve(&bare), None);
+    }
+
+    #[test]
+    fn renames_in_same_directory_conflict() {
+        let engine = standard_engine();
+        let existing = make_triple(
+            "agent_a",
+            Predicate::Renames,
+            ResourceRef::new(ResourceType::File, "/src/a.ts"),
+            "s1",
+        );
+        let new = make_triple(
+            "agent_b",
+            Predicate::Renames,
+            ResourceRef::new(ResourceType::File, "/src/b.ts"),
+            "s2",
+        );
+
+        match engine.check_intent(&new, std::slice::from_ref(&existing)) {
+            ConflictResult::Conflict(detail) => {
+                assert!(detail.reason.contains("renames-mutate-parent-directory"))
+            }
+            ConflictResult::Ok => panic!("Expected implied conflict"),
+        }
+
+        // Without rules the two renames are independent
+        assert_eq!(ConflictEngine::check(&new, &[existing]), ConflictResult::Ok);
+    }
+
+    #[test]
+    fn implied_listing_mutation_does_not_cover_directory_contents() {
+        let engine = standard_engine();
+        let existing = make_triple(
+            "agent_a",
+            Predicate::Consumes,
+            ResourceRef::new(ResourceType::File, "/src/b.ts"),
+            "s1",
+        );
+        let new = make_triple(
+            "agent_b",
+            Predicate::Renames,
+            ResourceRef::new(ResourceType::File, "/src/a.ts"),
+            "s2",
+        );
+
+        assert_eq!(engine.check_intent(&new, &[existing]), ConflictResult::Ok);
+    }
+
+    #[test]
+    fn providing_symbol_conflicts_with_file_mutation() {
+        let engine = standard_engine();
+        let existing = make_triple(
+            "agent_a",
+            Predicate::Mutates,
+            ResourceRef::new(ResourceType::File, "/src/user.ts"),
+            "s1",
+        );
+        let new = make_triple(
+            "agent_b",
+            Predicate::Provides,
+            ResourceRef::new(ResourceType::Symbol, "/src/user.ts#User.logout"),
+            "s2",
+        );
+
+        assert!(matches!(
+            engine.check_intent(&new, &[existing]),
+            ConflictResult::Conflict { .. }
+        ));
+    }
+
+    #[test]
+    fn kernel_verdict_lists_implied_operations() {
+        let mut priorities = HashMap::new();
+        priorities.insert("agent_a".to_string(), 100.into());
+
+        let state = StateSnapshot {
+            active_leases: vec![],
+            pending_leases: vec![],
+            active_intents: vec![],
+            priorities,
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
+        };
+        let manifest = IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "agent_a".to_string(),
+            intents: vec![make_triple(
+                "agent_a",
+                Predicate::Renames,
+                ResourceRef::new(ResourceType::File, "/src/a.ts"),
+                "s1",
+            )],
+        };
+
+        let verdict = KlockKernel::execute_with(
+            &state,
+            &manifest,
+            &standard_engine(),
+            &crate::scheduler::WaitDieScheduler,
+        );
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        assert_eq!(
+            verdict.implied,
+            vec![
+                "Renames FILE:/src/a.ts implies Mutates FILE:/src/ (renames-mutate-parent-directory)"
+                    .to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Resource index for conflict candidate lookup.
+//!
+//! With hierarchical and glob matching, a request can conflict with leases
+//! filed under other keys: ancestor directories, nested files, containing
+//! files, member symbols, and any pattern. The index narrows a request to the
+//! leases that could possibly overlap it using ordered-map lookups, so literal
+//! paths never scan every lease. Pattern leases are always candidates; a
+//! pattern request returns every indexed lease. The `ConflictEngine` makes
+//! the final decision on each candidate.
+//!
+//! Stores index their active leases by lease ID, and `KlockClient` keeps
+//! its active intents in an `IntentSet`, indexed by intent ID, so
+//! acquiring a lease or declaring an intent costs time proportional to the
+//! holders of the resources involved rather than to everything held.
+//! Granting or withdrawing an intent updates the index in place.
+
+use crate::types::{HTTP_METHODS, ResourceRef, ResourceType, SPOTriple};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Debug, Default)]
+pub struct ResourceIndex {
+    /// Literal resource key -> IDs of leases indexed under it
+    literals: BTreeMap<String, HashSet<String>>,
+    /// IDs of leases indexed under at least one pattern
+    patterns: HashSet<String>,
+    /// Lease ID -> resources it was indexed under
+    by_lease: HashMap<String, Vec<ResourceRef>>,
+}
+
+impl ResourceIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index a lease under each resource it touches (replacing any previous entry)
+    pub fn insert(&mut self, lease_id: &str, resources: Vec<ResourceRef>) {
+        self.remove(lease_id);
+        for resource in &resources {
+            if resource.is_pattern() {
+                self.patterns.insert(lease_id.to_string());
+            } else {
+                self.literals
+                    .entry(resource.key())
+                    .or_default()
+                    .insert(lease_id.to_string());
+            }
+        }
+        self.by_lease.insert(lease_id.to_string(), resources);
+    }
+
+    /// Drop a lease from the index. Returns false if it was not indexed.
+    pub fn remove(&mut self, lease_id: &str) -> bool {
+        let Some(resources) = self.by_lease.remove(lease_id) else {
+            return false;
+        };
+        self.patterns.remove(lease_id);
+        for resource in resources {
+            let key = resource.key();
+            if let Some(ids) = self.literals.get_mut(&key) {
+                ids.remove(lease_id);
+                if ids.is_empty() {
+                    self.literals.remove(&key);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.literals.clear();
+        self.patterns.clear();
+        self.by_lease.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_lease.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_lease.is_empty()
+    }
+
+    /// IDs of every lease that may overlap any of `resources`
+    pub fn candidates(&self, resources: &[ResourceRef]) -> HashSet<String> {
+        if resources.iter().any(ResourceRef::is_pattern) {
+            return self.by_lease.keys().cloned().collect();
+        }
+
+        let mut ids = self.patterns.clone();
+        for resource in resources {
+            for key in covering_keys(resource)
+                .into_iter()
+                .chain(covered_keys(resource))
+            {
+                if let Some(found) = self.literals.get(&key) {
+                    ids.extend(found.iter().cloned());
+                }
+            }
+            for prefix in covered_prefixes(resource) {
+                for (_, found) in self
+                    .literals
+                    .range(prefix.clone()..)
+                    .take_while(|(key, _)| key.starts_with(&prefix))
+                {
+                    ids.extend(found.iter().cloned());
+                }
+            }
+        }
+        ids
+    }
+}
+
+/// Active intents in declaration order, indexed by intent ID under the
+/// resources they touch. Intents sharing an ID are indexed together under
+/// it.
+#[derive(Debug, Default)]
+pub struct IntentSet {
+    /// Declaration sequence -> intent and the resources it touches
+    intents: BTreeMap<u64, (SPOTriple, Vec<ResourceRef>)>,
+    /// Intent ID -> declaration sequences of the intents with it
+    sequences: HashMap<String, Vec<u64>>,
+    index: ResourceIndex,
+    next_sequence: u64,
+}
+
+impl IntentSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The declaration sequence the next intent gets, past every one
+    /// given out so far
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Add `intent` as declared `sequence`th, indexed under `resources`
+    /// (its footprint)
+    pub fn insert(&mut self, sequence: u64, intent: SPOTriple, resources: Vec<ResourceRef>) {
+        let id = intent.id.clone();
+        self.sequences.entry(id.clone()).or_default().push(sequence);
+        self.intents.insert(sequence, (intent, resources));
+        self.next_sequence = self.next_sequence.max(sequence + 1);
+        self.reindex(&id);
+    }
+
+    /// Remove the earliest active intent with ID `id`, returning it with
+    /// its sequence
+    pub fn remove(&mut self, id: &str) -> Option<(u64, SPOTriple)> {
+        let sequence = *self.sequences.get(id)?.first()?;
+        let removed = self.take(sequence);
+        self.reindex(id);
+        removed.map(|intent| (sequence, intent))
+    }
+
+    /// Remove every active intent with ID `id`, in declaration order
+    pub fn remove_all(&mut self, id: &str) -> Vec<(u64, SPOTriple)> {
+        let sequences = self.sequences.remove(id).unwrap_or_default();
+        self.index.remove(id);
+        sequences
+            .into_iter()
+            .filter_map(|sequence| Some((sequence, self.intents.remove(&sequence)?.0)))
+            .collect()
+    }
+
+    /// Remove every intent `remove` selects. Returns them with their
+    /// sequences, in declaration order.
+    pub fn extract_if(
+        &mut self,
+        mut remove: impl FnMut(&SPOTriple) -> bool,
+    ) -> Vec<(u64, SPOTriple)> {
+        let sequences: Vec<u64> = self
+            .intents
+            .iter()
+            .filter(|(_, (intent, _))| remove(intent))
+            .map(|(sequence, _)| *sequence)
+            .collect();
+        let removed: Vec<(u64, SPOTriple)> = sequences
+            .into_iter()
+            .filter_map(|sequence| Some((sequence, self.take(sequence)?)))
+            .collect();
+        let ids: HashSet<&str> = removed
+            .iter()
+            .map(|(_, intent)| intent.id.as_str())
+            .collect();
+        for id in ids {
+            self.reindex(id);
+        }
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        self.intents.clear();
+        self.sequences.clear();
+        self.index.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.intents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intents.is_empty()
+    }
+
+    /// The intents in declaration order
+    pub fn iter(&self) -> impl Iterator<Item = &SPOTriple> {
+        self.intents.values().map(|(intent, _)| intent)
+    }
+
+    /// The intents with their sequences, in declaration order
+    pub fn entries(&self) -> impl Iterator<Item = (u64, &SPOTriple)> {
+        self.intents
+            .iter()
+            .map(|(sequence, (intent, _))| (*sequence, intent))
+    }
+
+    /// The intents with their sequences, in declaration order, to update
+    /// in place. What they touch and their IDs must not change.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (u64, &mut SPOTriple)> {
+        self.intents
+            .iter_mut()
+            .map(|(sequence, (intent, _))| (*sequence, intent))
+    }
+
+    /// The intents that may overlap any of `resources`, in declaration
+    /// order
+    pub fn candidates(&self, resources: &[ResourceRef]) -> Vec<&SPOTriple> {
+        let mut sequences: Vec<u64> = self
+            .index
+            .candidates(resources)
+            .iter()
+            .filter_map(|id| self.sequences.get(id))
+            .flatten()
+            .copied()
+            .collect();
+        sequences.sort_unstable();
+        sequences
+            .iter()
+            .map(|sequence| &self.intents[sequence].0)
+            .collect()
+    }
+
+    /// Drop the intent declared `sequence`th from the intents, leaving
+    /// the index to `reindex`
+    fn take(&mut self, sequence: u64) -> Option<SPOTriple> {
+        let (intent, _) = self.intents.remove(&sequence)?;
+        if let Some(sequences) = self.sequences.get_mut(&intent.id) {
+            sequences.retain(|s| *s != sequence);
+            if sequences.is_empty() {
+                self.sequences.remove(&intent.id);
+            }
+        }
+        Some(intent)
+    }
+
+    /// Index `id` under what its intents touch, or drop it with the last
+    fn reindex(&mut self, id: &str) {
+        let Some(sequences) = self.sequences.get(id) else {
+            self.index.remove(id);
+            return;
+        };
+        let resources = sequences
+            .iter()
+            .flat_map(|sequence| self.intents[sequence].1.iter().cloned())
+            .collect();
+        self.index.insert(id, resources);
+    }
+}
+
+/// Keys of the resource itself and every literal resource that contains it
+fn covering_keys(resource: &ResourceRef) -> Vec<String> {
+    let mut keys = vec![resource.key()];
+    match resource.resource_type {
+        ResourceType::File => keys.extend(directory_keys(&resource.path)),
+        ResourceType::Symbol => {
+            let member_start = match resource.containing_file() {
+                Some(file) => {
+                    keys.extend(directory_keys(&file.path));
+                    keys.push(file.key());
+                    file.path.len() + 1
+                }
+                None => 0,
+            };
+            keys.extend(parent_keys(resource, member_start));
+        }
+        ResourceType::DatabaseTable => {
+            if let Some((table, _)) = resource.path.split_once('#') {
+                keys.push(ResourceRef::database_table(table, None).key());
+            }
+        }
+        ResourceType::ApiEndpoint => {
+            if let Some((Some(_), route)) = resource.endpoint() {
+                keys.push(ResourceRef::api_endpoint(None, route).key());
+            }
+        }
+        _ => {}
+    }
+    keys
+}
+
+/// Keys of literal resources this one contains that share no key prefix
+/// with it: each method on a route without one
+fn covered_keys(resource: &ResourceRef) -> Vec<String> {
+    match resource.endpoint() {
+        Some((None, route)) => HTTP_METHODS
+            .iter()
+            .map(|method| ResourceRef::api_endpoint(Some(method), route).key())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Key prefixes under which resources contained by this one are filed
+fn covered_prefixes(resource: &ResourceRef) -> Vec<String> {
+    match resource.resource_type {
+        ResourceType::File if resource.is_directory() => vec![
+            format!("FILE:{}", resource.path),
+            format!("SYMBOL:{}", resource.path),
+        ],
+        ResourceType::File => vec![format!("SYMBOL:{}#", resource.path)],
+        ResourceType::Symbol => vec![format!("{}.", resource.key())],
+        ResourceType::DatabaseTable if !resource.path.contains('#') => {
+            vec![format!("{}#", resource.key())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Keys of the dotted parents of a symbol, ignoring dots before
+/// `start` (`User.auth.login` -> `User`, `User.auth`)
+fn parent_keys(resource: &ResourceRef, start: usize) -> Vec<String> {
+    resource
+        .path
+        .match_indices('.')
+        .filter(|(idx, _)| *idx > start)
+        .map(|(idx, _)| format!("{}:{}", resource.resource_type, &resource.path[..idx]))
+        .collect()
+}
+
+/// Keys of every directory above a file path (`/src/a.ts` -> `/`, `/src/`)
+fn directory_keys(path: &str) -> Vec<String> {
+    let trimmed = path.trim_end_matches('/');
+    trimmed
+        .match_indices('/')
+        .map(|(idx, _)| format!("FILE:{}", &trimmed[..=idx]))
+        .collect()
+}
@@ -0,0 +1,406 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::index::{IntentSet, ResourceIndex};
+    use crate::infrastructure::LeaseStore;
+    use crate::infrastructure_in_memory::InMemoryLeaseStore;
+    use crate::state::{IntentManifest, KernelVerdict};
+    use crate::types::{Confidence, LeaseResult, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::collections::HashSet;
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn symbol(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::Symbol, path)
+    }
+
+    fn ids(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn sample_index() -> ResourceIndex {
+        let mut index = ResourceIndex::new();
+        index.insert("src_dir", vec![file("/src/")]);
+        index.insert("user_file", vec![file("/src/user.ts")]);
+        index.insert("lib_file", vec![file("/lib/util.ts")]);
+        index.insert("login", vec![symbol("/src/user.ts#User.login")]);
+        index.insert("user_class", vec![symbol("/src/user.ts#User")]);
+        index
+    }
+
+    #[test]
+    fn test_pattern_matching() {
+        let pattern = file("/src/**/*.ts");
+        assert!(pattern.is_pattern());
+        assert!(!file("/src/a.ts").is_pattern());
+
+        assert!(pattern.matches(&file("/src/a.ts")));
+        assert!(pattern.matches(&file("/src/auth/deep/a.ts")));
+        assert!(!pattern.matches(&file("/src/a.rs")));
+        assert!(!pattern.matches(&file("/lib/a.ts")));
+
+        // `*` and `?` stop at the separator
+        assert!(!file("/src/*.ts").matches(&file("/src/auth/a.ts")));
+        assert!(file("/src/?.ts").matches(&file("/src/a.ts")));
+        assert!(!file("/src/?.ts").matches(&file("/src/ab.ts")));
+
+        // Symbols separate members with dots
+        assert!(symbol("User.*").matches(&symbol("User.login")));
+        assert!(!symbol("User.*").matches(&symbol("User.auth.login")));
+        assert!(symbol("User.**").matches(&symbol("User.auth.login")));
+    }
+
+    #[test]
+    fn test_pattern_overlaps() {
+        let ts = file("/src/**/*.ts");
+
+        // Pattern vs literal
+        assert!(ts.overlaps(&file("/src/a.ts")));
+        assert!(!ts.overlaps(&file("/src/a.rs")));
+        assert!(ts.overlaps(&file("/src/auth/")));
+        assert!(ts.overlaps(&symbol("/src/user.ts#User")));
+
+        // Pattern vs pattern
+        assert!(ts.overlaps(&file("/src/auth/*")));
+        assert!(ts.overlaps(&file("/**/user.*")));
+        assert!(!ts.overlaps(&file("/src/**/*.rs")));
+        assert!(!ts.overlaps(&file("/lib/**")));
+    }
+
+    #[test]
+    fn test_candidates_for_literal_file() {
+        let index = sample_index();
+
+        // The enclosing directory, the file, and symbols inside it
+        assert_eq!(
+            index.candidates(&[file("/src/user.ts")]),
+            ids(&["src_dir", "user_file", "login", "user_class"])
+        );
+        assert_eq!(
+            index.candidates(&[file("/lib/util.ts")]),
+            ids(&["lib_file"])
+        );
+        assert!(index.candidates(&[file("/docs/readme.md")]).is_empty());
+    }
+
+    #[test]
+    fn test_candidates_for_directory() {
+        let index = sample_index();
+
+        assert_eq!(
+            index.candidates(&[file("/src/")]),
+            ids(&["src_dir", "user_file", "login", "user_class"])
+        );
+        assert_eq!(
+            index.candidates(&[file("/")]),
+            ids(&["src_dir", "user_file", "lib_file", "login", "user_class"])
+        );
+    }
+
+    #[test]
+    fn test_candidates_for_symbol() {
+        let index = sample_index();
+
+        // A member is covered by its class, its file and their directories
+        assert_eq!(
+            index.candidates(&[symbol("/src/user.ts#User.logout")]),
+            ids(&["src_dir", "user_file", "user_class"])
+        );
+        assert_eq!(
+            index.candidates(&[symbol("/src/user.ts#User")]),
+            ids(&["src_dir", "user_file", "login", "user_class"])
+        );
+    }
+
+    #[test]
+    fn test_candidates_for_endpoints() {
+        let mut index = ResourceIndex::new();
+        index.insert(
+            "get",
+            vec![ResourceRef::api_endpoint(Some("GET"), "/users")],
+        );
+        index.insert(
+            "post",
+            vec![ResourceRef::api_endpoint(Some("POST"), "/users")],
+        );
+        index.insert("any", vec![ResourceRef::api_endpoint(None, "/orders")]);
+
+        assert_eq!(
+            index.candidates(&[ResourceRef::api_endpoint(None, "/users")]),
+            ids(&["get", "post"])
+        );
+        assert_eq!(
+            index.candidates(&[ResourceRef::api_endpoint(Some("GET"), "/users")]),
+            ids(&["get"])
+        );
+        assert_eq!(
+            index.candidates(&[ResourceRef::api_endpoint(Some("DELETE"), "/orders")]),
+            ids(&["any"])
+        );
+    }
+
+    #[test]
+    fn test_candidates_for_columns() {
+        let mut index = ResourceIndex::new();
+        index.insert(
+            "email",
+            vec![ResourceRef::database_table("users", Some("email"))],
+        );
+        index.insert(
+            "name",
+            vec![ResourceRef::database_table("users", Some("name"))],
+        );
+        index.insert("orders", vec![ResourceRef::database_table("orders", None)]);
+
+        assert_eq!(
+            index.candidates(&[ResourceRef::database_table("users", None)]),
+            ids(&["email", "name"])
+        );
+        assert_eq!(
+            index.candidates(&[ResourceRef::database_table("users", Some("email"))]),
+            ids(&["email"])
+        );
+        assert_eq!(
+            index.candidates(&[ResourceRef::database_table("orders", Some("total"))]),
+            ids(&["orders"])
+        );
+
+        // A schema is not a table of its tables
+        index.insert("public", vec![ResourceRef::database_table("public", None)]);
+        assert_eq!(
+            index.candidates(&[ResourceRef::database_table("public.users", Some("id"))]),
+            ids(&[])
+        );
+    }
+
+    #[test]
+    fn test_candidates_with_patterns() {
+        let mut index = sample_index();
+        index.insert("all_ts", vec![file("/src/**/*.ts")]);
+
+        // Pattern leases are always candidates
+        assert!(index.candidates(&[file("/lib/util.ts")]).contains("all_ts"));
+
+        // A pattern request considers every lease
+        assert_eq!(index.candidates(&[file("/lib/*.ts")]).len(), 6);
+
+        assert!(index.remove("all_ts"));
+        assert!(!index.remove("all_ts"));
+        assert_eq!(index.len(), 5);
+    }
+
+    #[test]
+    fn test_intent_set_updates_its_index_in_place() {
+        let triple = |id: &str, path: &str| SPOTriple {
+            id: id.to_string(),
+            subject: "agent".to_string(),
+            predicate: Predicate::Mutates,
+            object: file(path),
+            timestamp: 1000,
+            confidence: Confidence::High,
+            session_id: "s1".to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        };
+        let candidates = |intents: &IntentSet, path: &str| -> Vec<String> {
+            intents
+                .candidates(&[file(path)])
+                .into_iter()
+                .map(|intent| format!("{}{}", intent.id, intent.object.path))
+                .collect()
+        };
+        let mut intents = IntentSet::new();
+        for (id, path) in [
+            ("t1", "/src/b.ts"),
+            ("t2", "/src/a.ts"),
+            ("t1", "/lib/c.ts"),
+        ] {
+            let sequence = intents.next_sequence();
+            intents.insert(sequence, triple(id, path), vec![file(path)]);
+        }
+
+        // In declaration order; intents sharing an ID are candidates
+        // together, and the conflict engine tells them apart
+        assert_eq!(
+            candidates(&intents, "/src/"),
+            ["t1/src/b.ts", "t2/src/a.ts", "t1/lib/c.ts"]
+        );
+        assert_eq!(
+            candidates(&intents, "/lib/c.ts"),
+            ["t1/src/b.ts", "t1/lib/c.ts"]
+        );
+
+        // Intents sharing an ID leave one at a time, earliest first
+        let (sequence, removed) = intents.remove("t1").unwrap();
+        assert_eq!((sequence, removed.object.path.as_str()), (0, "/src/b.ts"));
+        assert_eq!(candidates(&intents, "/src/"), ["t2/src/a.ts"]);
+        assert_eq!(candidates(&intents, "/lib/c.ts"), ["t1/lib/c.ts"]);
+
+        let removed = intents.extract_if(|intent| intent.object.path.starts_with("/lib/"));
+        assert_eq!(removed.len(), 1);
+        assert!(candidates(&intents, "/lib/c.ts").is_empty());
+        assert_eq!(intents.remove_all("t2").len(), 1);
+        assert!(intents.is_empty());
+        assert!(candidates(&intents, "/").is_empty());
+    }
+
+    #[test]
+    fn test_in_memory_store_glob_lease_conflicts() {
+        let mut store = InMemoryLeaseStore::new();
+        store.register_agent_priority("older".to_string(), 100);
+        store.register_agent_priority("younger".to_string(), 200);
+
+        assert!(matches!(
+            store.acquire(
+                "older",
+                "s1",
+                file("/src/**/*.ts"),
+                Predicate::Mutates,
+                5000,
+                1000
+            ),
+            LeaseResult::Success { .. }
+        ));
+
+        assert!(matches!(
+            store.acquire(
+                "younger",
+                "s2",
+                file("/src/auth/login.ts"),
+                Predicate::Mutates,
+                5000,
+                1000
+            ),
+            LeaseResult::Failure { .. }
+        ));
+        assert!(matches!(
+            store.acquire(
+                "younger",
+                "s2",
+                file("/src/auth/login.rs"),
+                Predicate::Mutates,
+                5000,
+                1000
+            ),
+            LeaseResult::Success { .. }
+        ));
+    }
+
+    #[test]
+    fn test_in_memory_store_leases_touching() {
+        let mut store = InMemoryLeaseStore::new();
+        for (i, path) in ["/src/a.ts", "/src/b.ts", "/lib/c.ts"].iter().enumerate() {
+            let agent = format!("agent_{}", i);
+            store.register_agent_priority(agent.clone(), 100 + i as u64);
+            assert!(matches!(
+                store.acquire(&agent, "s1", file(path), Predicate::Mutates, 5000, 1000),
+                LeaseResult::Success { .. }
+            ));
+        }
+
+        let touching: HashSet<String> = store
+            .leases_touching(&[file("/src/")])
+            .into_iter()
+            .map(|lease| lease.resource.path)
+            .collect();
+        assert_eq!(touching, ids(&["/src/a.ts", "/src/b.ts"]));
+        assert!(store.leases_touching(&[file("/docs/")]).is_empty());
+    }
+
+    #[test]
+    fn test_client_checks_only_overlapping_intents() {
+        let mut client = KlockClient::new();
+        client.register_agent("a", 100);
+        client.register_agent("b", 200);
+        let intent = |id: &str, agent: &str, path: &str| IntentManifest {
+            session_id: format!("{}_session", agent),
+            agent_id: agent.to_string(),
+            intents: vec![SPOTriple {
+                id: id.to_string(),
+                subject: agent.to_string(),
+                predicate: Predicate::Mutates,
+                object: file(path),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: format!("{}_session", agent),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        };
+        let blockers = |verdict: KernelVerdict| -> Vec<String> {
+            verdict
+                .blockers
+                .into_iter()
+                .map(|b| b.resource_key())
+                .collect()
+        };
+
+        client.declare_intent(&intent("docs", "a", "/docs/readme.md"));
+        client.declare_intent(&intent("src", "a", "/src/"));
+        assert!(blockers(client.declare_intent(&intent("lib", "b", "/lib/x.ts"))).is_empty());
+        assert_eq!(
+            blockers(client.declare_intent(&intent("app", "b", "/src/app.ts"))),
+            vec!["FILE:/src/".to_string()]
+        );
+
+        // Removing an earlier intent shifts the later ones, which stay indexed
+        client.release_lease("docs");
+        client.release_lease("app");
+        assert_eq!(
+            blockers(client.declare_intent(&intent("app", "b", "/src/app.ts"))),
+            vec!["FILE:/src/".to_string()]
+        );
+        assert!(
+            blockers(client.declare_intent(&intent("readme", "b", "/docs/readme.md"))).is_empty()
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_rebuilds_index_on_reopen() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let path = std::env::temp_dir().join(format!("klock_index_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        {
+            let mut store = SqliteLeaseStore::open(path).unwrap();
+            store.register_agent_priority("older".to_string(), 100);
+            assert!(matches!(
+                store.acquire("older", "s1", file("/src/"), Predicate::Mutates, 5000, 1000),
+                LeaseResult::Success { .. }
+            ));
+        }
+
+        let mut store = SqliteLeaseStore::open(path).unwrap();
+        store.register_agent_priority("younger".to_string(), 200);
+        assert!(matches!(
+            store.acquire(
+                "younger",
+                "s2",
+                file("/src/a.ts"),
+                Predicate::Mutates,
+                5000,
+                1500
+            ),
+            LeaseResult::Failure { .. }
+        ));
+        assert!(matches!(
+            store.acquire(
+                "younger",
+                "s2",
+                file("/lib/a.ts"),
+                Predicate::Mutates,
+                5000,
+                1500
+            ),
+            LeaseResult::Success { .. }
+        ));
+
+        let _ = std::fs::remove_file(path);
+    }
+}
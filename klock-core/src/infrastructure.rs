@@ -1,4 +1,11 @@
-use crate::types::{Lease, LeaseResult, Predicate, ResourceRef};
+use crate::alias::AliasTable;
+use crate::codes::ReasonCode;
+use crate::conflict::ConflictEngine;
+use crate::event::KlockEvent;
+use crate::types::{Lease, LeaseResult, LeaseState, Predicate, ResourceRef, SPOTriple};
+use crate::wait_queue::{Waiter, WaiterFilter};
+use serde::Serialize;
+use std::collections::HashMap;
 
 // In a real system, these would likely return Results with specific error types
 // and use async/await. For the core kernel representation, we keep it synchronous
@@ -17,15 +24,351 @@ pub trait LeaseStore {
         now: u64,
     ) -> LeaseResult;
 
+    /// `acquire` for a request due by `deadline` (ms since epoch). Between
+    /// agents of equal priority the earlier deadline is senior (see
+    /// `scheduler::with_deadlines`), and the lease keeps the deadline to
+    /// weigh against later requests. The default ignores the deadline, for
+    /// stores written before deadlines existed.
+    #[allow(clippy::too_many_arguments)]
+    fn acquire_with_deadline(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        predicate: Predicate,
+        ttl: u64,
+        _deadline: Option<u64>,
+        now: u64,
+    ) -> LeaseResult {
+        self.acquire(agent_id, session_id, resource, predicate, ttl, now)
+    }
+
     /// Release an explicitly held lease
     fn release(&mut self, lease_id: &str) -> bool;
 
     /// Heartbeat an active lease to extend its TTL
     fn heartbeat(&mut self, lease_id: &str, now: u64) -> bool;
 
-    /// Get all currently active leases
+    /// Get all currently active leases, in listing order (see `sort_leases`)
     fn get_active_leases(&self) -> Vec<Lease>;
 
+    /// Get all reservations still waiting to be activated, in listing order
+    fn get_pending_leases(&self) -> Vec<Lease>;
+
+    /// Active leases held by each agent holding any, for fair-share
+    /// scheduling
+    fn lease_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for lease in self.get_active_leases() {
+            *counts.entry(lease.agent_id).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Active leases that may overlap any of `resources` (a superset of the
+    /// ones that do), in listing order, for callers that check conflicts
+    /// themselves. Stores with a resource index answer in time proportional
+    /// to the holders of those resources; the default returns every active
+    /// lease.
+    fn leases_touching(&self, _resources: &[ResourceRef]) -> Vec<Lease> {
+        self.get_active_leases()
+    }
+
+    /// A counter that moves whenever the store's leases change, including
+    /// changes other processes make to a shared backend. Stores that cannot
+    /// tell return a constant, so their snapshots never look stale.
+    fn generation(&self) -> u64 {
+        0
+    }
+
+    /// `leases_touching`, read at a single generation: the leases are a
+    /// consistent point-in-time view stamped with that generation.
+    fn read_snapshot(&self, resources: &[ResourceRef]) -> LeaseSnapshot {
+        loop {
+            let generation = self.generation();
+            let leases = self.leases_touching(resources);
+            if self.generation() == generation {
+                return LeaseSnapshot { generation, leases };
+            }
+        }
+    }
+
+    /// Look up a lease by ID, whatever its state
+    fn get_lease(&self, lease_id: &str) -> Option<Lease>;
+
     /// Evict expired leases based on the current time
     fn evict_expired(&mut self, now: u64) -> usize;
+
+    /// Reserve resources in the `Pending` state until `activate_by`.
+    /// Reservations never block other agents, and are always granted.
+    fn pre_acquire(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resources: &[(ResourceRef, Predicate)],
+        ttl: u64,
+        activate_by: u64,
+        now: u64,
+    ) -> Vec<Lease>;
+
+    /// Turn a pending reservation into an active lease
+    fn activate(&mut self, lease_id: &str, now: u64) -> LeaseResult;
+
+    /// Grant queued waiters (see `WaitQueue`) whose requests no longer
+    /// conflict, after leases ended. Returns the leases granted. Stores
+    /// without a wait queue grant nothing.
+    fn grant_waiters(&mut self, _now: u64) -> Vec<Lease> {
+        Vec::new()
+    }
+
+    /// Acquires waiting in queue, by resource and then in queue order
+    fn waiters(&self) -> Vec<Waiter> {
+        Vec::new()
+    }
+
+    /// Take the queued requests `filter` selects out of the queue. Returns
+    /// them, oldest first.
+    fn cancel_waiters(&mut self, _filter: &WaiterFilter) -> Vec<Waiter> {
+        Vec::new()
+    }
+
+    /// Drop terminal (expired, released, revoked) leases last touched before
+    /// `before`. Returns the number of leases removed.
+    fn compact(&mut self, before: u64) -> usize;
+
+    /// Lease counts and approximate footprint of the store
+    fn usage(&self) -> StoreUsage;
+
+    /// Apply one lease-state transition. Returns false if the event does
+    /// not apply (unknown lease, or a transition its state does not allow).
+    ///
+    /// Built-in stores route every mutation through here. The default maps
+    /// events onto the methods above, so stores written before events
+    /// existed keep working; it cannot express lease creation, activation,
+    /// revocation or single-lease expiry, which it reports as not applied.
+    fn apply(&mut self, event: &KlockEvent) -> bool {
+        match event {
+            KlockEvent::LeaseRenewed { lease_id, at } => self.heartbeat(lease_id, *at),
+            KlockEvent::LeaseReleased { lease_id } => self.release(lease_id),
+            KlockEvent::LeasesCompacted { before } => {
+                self.compact(*before);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// `apply`, but only if the event's lease is still at
+    /// `expected_revision`: the compare-and-set form for callers that
+    /// decided on the change from an earlier read. Returns the lease as the
+    /// change left it.
+    ///
+    /// The default checks and applies under `&mut self`, which is atomic for
+    /// stores no other process writes to; stores with shared backends check
+    /// the revision in the backend itself.
+    fn apply_at(
+        &mut self,
+        event: &KlockEvent,
+        expected_revision: u64,
+    ) -> Result<Lease, LeaseMutationError> {
+        let lease_id = event.lease_id().ok_or(LeaseMutationError::NotFound)?;
+        let lease = self
+            .get_lease(lease_id)
+            .ok_or(LeaseMutationError::NotFound)?;
+        check_revision(&lease, expected_revision)?;
+        if !self.apply(event) {
+            return Err(LeaseMutationError::InvalidState { state: lease.state });
+        }
+        self.get_lease(lease_id).ok_or(LeaseMutationError::NotFound)
+    }
+
+    /// Apply `events` in order. Returns the number that applied.
+    fn replay(&mut self, events: &[KlockEvent]) -> usize {
+        events.iter().filter(|event| self.apply(event)).count()
+    }
+
+    /// Start or stop recording the events this store applies. Stores that
+    /// predate events record nothing.
+    fn record_events(&mut self, _enabled: bool) {}
+
+    /// Drain the events recorded since the last call
+    fn take_events(&mut self) -> Vec<KlockEvent> {
+        Vec::new()
+    }
+
+    /// Record that a lease stopped being held at `at`, for `leases_at`. An
+    /// expired lease ended no later than its expiry.
+    fn record_lease_end(&mut self, _lease_id: &str, _at: u64) {}
+
+    /// Leases on resources overlapping `resource` that were held at `at`
+    /// (see `Lease::held_at`), in listing order. Only leases the store
+    /// still keeps are found: stores that compact terminal leases away
+    /// forget them, and stores without history answer with nothing.
+    fn leases_at(&self, _resource: &ResourceRef, _at: u64) -> Vec<Lease> {
+        Vec::new()
+    }
+
+    /// Persist the rename alias table, replacing what was stored. Stores
+    /// without durable storage keep nothing; the client holds the live copy.
+    fn save_aliases(&mut self, _aliases: &AliasTable) {}
+
+    /// The alias table last persisted by `save_aliases`
+    fn load_aliases(&self) -> AliasTable {
+        AliasTable::default()
+    }
+
+    /// Persist one active intent under `sequence`, its place in
+    /// declaration order, replacing what was stored under it. Intents are
+    /// keyed by sequence because their IDs may repeat. Stores without
+    /// durable storage keep nothing; the client holds the live copy.
+    fn save_intent(&mut self, _sequence: u64, _intent: &SPOTriple) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Drop the intent persisted under `sequence`, if any
+    fn delete_intent(&mut self, _sequence: u64) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// The intents persisted by `save_intent`, with their sequences, in
+    /// declaration order
+    fn load_intents(&self) -> Result<Vec<(u64, SPOTriple)>, String> {
+        Ok(Vec::new())
+    }
+
+    /// Verify the backend can currently serve reads and writes
+    fn check_ready(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Active leases as of one store generation (see `LeaseStore::read_snapshot`)
+#[derive(Debug, Clone, Default)]
+pub struct LeaseSnapshot {
+    pub generation: u64,
+    pub leases: Vec<Lease>,
+}
+
+impl LeaseSnapshot {
+    /// Whether `other` holds the same leases, ignoring heartbeats: an
+    /// evaluation against one is valid against the other
+    pub fn same_holders(&self, other: &LeaseSnapshot) -> bool {
+        fn ids(snapshot: &LeaseSnapshot) -> Vec<&str> {
+            let mut ids: Vec<&str> = snapshot.leases.iter().map(|l| l.id.as_str()).collect();
+            ids.sort_unstable();
+            ids
+        }
+        ids(self) == ids(other)
+    }
+}
+
+/// Put leases in listing order: oldest first, ties broken by ID. Every
+/// store lists leases in this order, whatever order it keeps them in, so
+/// the holder a scheduler or the kernel meets first, and every verdict and
+/// listing built from them, is the same on every run.
+pub fn sort_leases(leases: &mut [Lease]) {
+    leases.sort_by(|a, b| (a.acquired_at, &a.id).cmp(&(b.acquired_at, &b.id)));
+}
+
+/// Why a revision-checked mutation (`LeaseStore::apply_at`) did not apply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeaseMutationError {
+    /// No lease has that ID
+    NotFound,
+    /// The lease changed since the caller read it
+    Conflict { current_revision: u64 },
+    /// The lease is at the expected revision, but its state does not allow
+    /// the change (e.g. heartbeating a released lease)
+    InvalidState { state: LeaseState },
+}
+
+impl LeaseMutationError {
+    /// The catalog code reported for the refusal
+    pub fn code(&self) -> ReasonCode {
+        match self {
+            LeaseMutationError::Conflict { .. } => ReasonCode::RevisionMismatch,
+            LeaseMutationError::NotFound | LeaseMutationError::InvalidState { .. } => {
+                ReasonCode::LeaseNotFound
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for LeaseMutationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LeaseMutationError::NotFound => write!(f, "lease not found"),
+            LeaseMutationError::Conflict { current_revision } => {
+                write!(f, "lease is now at revision {}", current_revision)
+            }
+            LeaseMutationError::InvalidState { state } => {
+                write!(f, "lease is {:?}", state)
+            }
+        }
+    }
+}
+
+/// Refuse a mutation of `lease` unless it is at `expected_revision`
+pub fn check_revision(lease: &Lease, expected_revision: u64) -> Result<(), LeaseMutationError> {
+    if lease.revision == expected_revision {
+        Ok(())
+    } else {
+        Err(LeaseMutationError::Conflict {
+            current_revision: lease.revision,
+        })
+    }
+}
+
+/// Lease counts and approximate footprint reported by a store
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StoreUsage {
+    pub active_leases: usize,
+    pub pending_leases: usize,
+    pub terminal_leases: usize,
+    pub estimated_bytes: usize,
+}
+
+/// The ID of a lease granted to `agent_id` at `now`: `lease_<agent>_<now>`,
+/// suffixed `_2`, `_3`, ... while `taken` says a lease already has it, since
+/// an agent can be granted several leases in one millisecond
+pub fn lease_id_for(agent_id: &str, now: u64, taken: impl Fn(&str) -> bool) -> String {
+    let base = format!("lease_{}_{}", agent_id, now);
+    let mut lease_id = base.clone();
+    let mut n = 1;
+    while taken(&lease_id) {
+        n += 1;
+        lease_id = format!("{}_{}", base, n);
+    }
+    lease_id
+}
+
+/// Splits the conflicting holders of a pending reservation into the leases
+/// that predate it (which the scheduler must still resolve) and the IDs of
+/// leases granted after the reservation was taken (which the reserver wins
+/// against and which must be revoked on activation).
+pub fn partition_for_activation(
+    engine: &ConflictEngine,
+    reservation: &Lease,
+    active_leases: &[Lease],
+) -> (Vec<Lease>, Vec<String>) {
+    let latecomers: Vec<String> = engine
+        .conflicting_leases(
+            &reservation.agent_id,
+            &reservation.session_id,
+            reservation.predicate,
+            &reservation.resource,
+            active_leases,
+        )
+        .into_iter()
+        .filter(|holder| holder.acquired_at >= reservation.acquired_at)
+        .map(|holder| holder.id.clone())
+        .collect();
+
+    let earlier = active_leases
+        .iter()
+        .filter(|lease| !latecomers.contains(&lease.id))
+        .cloned()
+        .collect();
+
+    (earlier, latecomers)
 }
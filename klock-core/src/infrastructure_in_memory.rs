@@ -1,29 +1,156 @@
-use crate::infrastructure::LeaseStore;
-use crate::scheduler::{VerdictStatus, WaitDieScheduler};
-use crate::types::{Lease, LeaseFailureReason, LeaseResult, Predicate, ResourceRef};
+use crate::conflict::ConflictEngine;
+use crate::event::{KlockEvent, apply_to_lease};
+use crate::fair_share::FairSharePolicy;
+use crate::index::ResourceIndex;
+use crate::infrastructure::{
+    LeaseStore, StoreUsage, lease_id_for, partition_for_activation, sort_leases,
+};
+use crate::registry::{AgentRegistry, InMemoryAgentRegistry, Priority};
+use crate::scheduler::{Scheduler, VerdictStatus, WaitDieScheduler, with_deadlines};
+use crate::types::{Lease, LeaseFailureReason, LeaseResult, LeaseState, Predicate, ResourceRef};
+use crate::wait_queue::{WaitQueue, Waiter, WaiterFilter, estimated_wait_ms};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 pub struct InMemoryLeaseStore {
     // Map of Lease ID -> Lease
     leases: HashMap<String, Lease>,
-    // Map of Agent ID -> Priority (Timestamp)
-    priorities: HashMap<String, u64>,
+    // Active leases by the resources they touch
+    index: ResourceIndex,
+    // Agent ID -> Priority (Timestamp), possibly shared with the client
+    registry: Arc<dyn AgentRegistry>,
+    // Conflict detection rules (built-in matrix by default)
+    engine: Arc<ConflictEngine>,
+    // Conflict resolution policy (Wait-Die by default)
+    scheduler: Arc<dyn Scheduler>,
+    // Penalty for the leases each agent holds (disabled by default)
+    fair_share: FairSharePolicy,
+    // Applied events, while recording is enabled
+    events: Option<Vec<KlockEvent>>,
+    // Number of events applied
+    generation: u64,
+    // Acquires waiting for their resource
+    queue: WaitQueue,
 }
 
 impl InMemoryLeaseStore {
     pub fn new() -> Self {
         Self {
             leases: HashMap::new(),
-            priorities: HashMap::new(),
+            index: ResourceIndex::new(),
+            registry: Arc::new(InMemoryAgentRegistry::new()),
+            engine: Arc::new(ConflictEngine::default()),
+            scheduler: Arc::new(WaitDieScheduler),
+            fair_share: FairSharePolicy::default(),
+            events: None,
+            generation: 0,
+            queue: WaitQueue::new(),
         }
     }
 
-    pub fn register_agent_priority(&mut self, agent_id: String, priority_timestamp: u64) {
-        self.priorities.insert(agent_id, priority_timestamp);
+    /// Replace the rules used to detect conflicts between leases.
+    pub fn set_conflict_engine(&mut self, engine: Arc<ConflictEngine>) {
+        self.engine = engine;
+        // Implication rules change what each lease touches
+        self.index.clear();
+        let active: Vec<Lease> = self.get_active_leases();
+        for lease in &active {
+            self.index_lease(lease);
+        }
+    }
+
+    /// Replace the scheduling policy used to resolve acquire conflicts.
+    pub fn set_scheduler(&mut self, scheduler: Arc<dyn Scheduler>) {
+        self.scheduler = scheduler;
+    }
+
+    /// Use `scheduler` instead of Wait-Die from the start.
+    pub fn with_scheduler(mut self, scheduler: Arc<dyn Scheduler>) -> Self {
+        self.set_scheduler(scheduler);
+        self
+    }
+
+    /// Penalize agents for the active leases they hold when scheduling.
+    pub fn set_fair_share(&mut self, policy: FairSharePolicy) {
+        self.fair_share = policy;
+    }
+
+    /// Replace the registry consulted for agent priorities.
+    pub fn set_registry(&mut self, registry: Arc<dyn AgentRegistry>) {
+        self.registry = registry;
     }
 
-    pub fn get_priorities(&self) -> HashMap<String, u64> {
-        self.priorities.clone()
+    pub fn registry(&self) -> Arc<dyn AgentRegistry> {
+        self.registry.clone()
+    }
+
+    pub fn register_agent_priority(&mut self, agent_id: String, priority: impl Into<Priority>) {
+        self.registry.register(agent_id, priority.into());
+    }
+
+    pub fn get_priorities(&self) -> HashMap<String, Priority> {
+        self.registry.priorities()
+    }
+}
+
+impl InMemoryLeaseStore {
+    fn index_lease(&mut self, lease: &Lease) {
+        let resources = self
+            .engine
+            .footprint_resources(lease.predicate, &lease.resource);
+        self.index.insert(&lease.id, resources);
+    }
+
+    /// Apply a transition to one existing lease, keeping the index in step
+    fn apply_to_existing(&mut self, event: &KlockEvent) -> bool {
+        let Some(lease) = event.lease_id().and_then(|id| self.leases.get_mut(id)) else {
+            return false;
+        };
+        let was_active = lease.state == LeaseState::Active;
+        if !apply_to_lease(lease, event) {
+            return false;
+        }
+        let lease = lease.clone();
+        match (was_active, lease.state == LeaseState::Active) {
+            (false, true) => self.index_lease(&lease),
+            (true, false) => {
+                self.index.remove(&lease.id);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Registered priorities, as the scheduler sees them under fair share
+    fn scheduling_priorities(&self) -> HashMap<String, Priority> {
+        let mut priorities = self.registry.priorities();
+        if self.fair_share.is_enabled() {
+            self.fair_share.apply(&mut priorities, &self.lease_counts());
+        }
+        priorities
+    }
+
+    /// Leases in `state`, in listing order
+    fn in_state(&self, state: LeaseState) -> Vec<Lease> {
+        let mut leases: Vec<Lease> = self
+            .leases
+            .values()
+            .filter(|l| l.state == state)
+            .cloned()
+            .collect();
+        sort_leases(&mut leases);
+        leases
+    }
+
+    /// Active leases that may overlap `predicate` on `resource`
+    fn candidate_leases(&self, predicate: Predicate, resource: &ResourceRef) -> Vec<Lease> {
+        self.leases_touching(&self.engine.footprint_resources(predicate, resource))
+    }
+}
+
+impl Default for InMemoryLeaseStore {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -36,87 +163,420 @@ impl LeaseStore for InMemoryLeaseStore {
         predicate: Predicate,
         ttl: u64,
         now: u64,
+    ) -> LeaseResult {
+        self.acquire_with_deadline(agent_id, session_id, resource, predicate, ttl, None, now)
+    }
+
+    fn acquire_with_deadline(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        predicate: Predicate,
+        ttl: u64,
+        deadline: Option<u64>,
+        now: u64,
     ) -> LeaseResult {
         // Clean up expired leases first
         self.evict_expired(now);
 
-        let active_leases = self.get_active_leases();
+        // A waiter granted while it waited retries into its lease
+        if let Some(lease) = self
+            .queue
+            .claim(agent_id, session_id, &resource, predicate)
+            .and_then(|lease_id| self.get_lease(&lease_id))
+            .filter(|lease| lease.state == LeaseState::Active)
+        {
+            return LeaseResult::Success { lease };
+        }
+
+        let active_leases = self.candidate_leases(predicate, &resource);
 
-        // 1. Check Wait-Die Scheduler
-        let verdict = WaitDieScheduler::decide(
+        // 1. Consult the scheduler
+        let verdict = self.scheduler.decide_at(
+            &self.engine,
             agent_id,
+            session_id,
             predicate,
             &resource,
             &active_leases,
-            &self.priorities,
+            &with_deadlines(
+                self.scheduling_priorities(),
+                agent_id,
+                deadline,
+                &active_leases,
+            ),
+            now,
+        );
+
+        if verdict.status != VerdictStatus::Wait {
+            self.queue
+                .remove(agent_id, session_id, &resource, predicate);
+        }
+        match verdict.status {
+            VerdictStatus::Wait => {
+                let (request_id, position) = self.queue.enqueue(Waiter {
+                    deadline,
+                    ..Waiter::new(agent_id, session_id, resource.clone(), predicate, ttl, now)
+                });
+                let wait_time = estimated_wait_ms(
+                    &self.engine,
+                    predicate,
+                    &verdict.holders,
+                    &self.queue.ahead(agent_id, session_id, &resource, predicate),
+                    now,
+                );
+                LeaseResult::Failure {
+                    reason: LeaseFailureReason::Wait,
+                    existing_lease: None, // Simplified for now
+                    wait_time: Some(wait_time),
+                    queue_position: Some(position),
+                    request_id: Some(request_id),
+                    holders: verdict.holders.clone(),
+                }
+            }
+            VerdictStatus::Die => LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                existing_lease: None,
+                wait_time: verdict.retry_after_ms,
+                queue_position: None,
+                request_id: None,
+                holders: verdict.holders.clone(),
+            },
+            VerdictStatus::Granted => {
+                // Preemptive policies name the junior leases to revoke
+                for preempted_id in &verdict.preempted {
+                    self.apply(&KlockEvent::LeaseRevoked {
+                        lease_id: preempted_id.clone(),
+                        reason: format!("Wounded by senior agent '{}'", agent_id),
+                    });
+                }
+
+                let lease = Lease {
+                    deadline,
+                    ..Lease::new(
+                        lease_id_for(agent_id, now, |id| self.leases.contains_key(id)),
+                        agent_id.to_string(),
+                        session_id.to_string(),
+                        resource,
+                        predicate,
+                        ttl,
+                        now,
+                    )
+                };
+
+                self.apply(&KlockEvent::LeaseCreated {
+                    lease: lease.clone(),
+                });
+
+                LeaseResult::Success { lease }
+            }
+        }
+    }
+
+    fn pre_acquire(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resources: &[(ResourceRef, Predicate)],
+        ttl: u64,
+        activate_by: u64,
+        now: u64,
+    ) -> Vec<Lease> {
+        let mut reserved = Vec::with_capacity(resources.len());
+        for (resource, predicate) in resources {
+            let lease = Lease::new(
+                lease_id_for(agent_id, now, |id| self.leases.contains_key(id)),
+                agent_id.to_string(),
+                session_id.to_string(),
+                resource.clone(),
+                *predicate,
+                ttl,
+                now,
+            )
+            .into_pending(activate_by);
+
+            self.apply(&KlockEvent::LeaseCreated {
+                lease: lease.clone(),
+            });
+            reserved.push(lease);
+        }
+        reserved
+    }
+
+    fn activate(&mut self, lease_id: &str, now: u64) -> LeaseResult {
+        self.evict_expired(now);
+
+        let reservation = match self.leases.get(lease_id) {
+            Some(lease) if lease.state == LeaseState::Pending => lease.clone(),
+            _ => {
+                return LeaseResult::rejected(LeaseFailureReason::ReservationExpired, None);
+            }
+        };
+
+        // Leases granted after the reservation never block it
+        let candidates = self.candidate_leases(reservation.predicate, &reservation.resource);
+        let (earlier, latecomers) =
+            partition_for_activation(&self.engine, &reservation, &candidates);
+
+        let verdict = self.scheduler.decide_at(
+            &self.engine,
+            &reservation.agent_id,
+            &reservation.session_id,
+            reservation.predicate,
+            &reservation.resource,
+            &earlier,
+            &self.scheduling_priorities(),
+            now,
         );
 
         match verdict.status {
             VerdictStatus::Wait => LeaseResult::Failure {
                 reason: LeaseFailureReason::Wait,
-                existing_lease: None, // Simplified for now
-                wait_time: None,
+                existing_lease: None,
+                wait_time: Some(estimated_wait_ms(
+                    &self.engine,
+                    reservation.predicate,
+                    &verdict.holders,
+                    &[],
+                    now,
+                )),
+                queue_position: None,
+                request_id: None,
+                holders: verdict.holders.clone(),
             },
             VerdictStatus::Die => LeaseResult::Failure {
                 reason: LeaseFailureReason::Die,
                 existing_lease: None,
                 wait_time: verdict.retry_after_ms,
+                queue_position: None,
+                request_id: None,
+                holders: verdict.holders.clone(),
             },
             VerdictStatus::Granted => {
-                let lease_id = format!("lease_{}_{}", agent_id, now);
-                let lease = Lease::new(
-                    lease_id.clone(),
-                    agent_id.to_string(),
-                    session_id.to_string(),
-                    resource,
-                    predicate,
-                    ttl,
-                    now,
-                );
+                for preempted_id in &verdict.preempted {
+                    self.apply(&KlockEvent::LeaseRevoked {
+                        lease_id: preempted_id.clone(),
+                        reason: format!("Wounded by senior agent '{}'", reservation.agent_id),
+                    });
+                }
+                for latecomer_id in &latecomers {
+                    self.apply(&KlockEvent::LeaseRevoked {
+                        lease_id: latecomer_id.clone(),
+                        reason: format!("Granted after reservation '{}', now activated", lease_id),
+                    });
+                }
 
-                self.leases.insert(lease_id, lease.clone());
+                self.apply(&KlockEvent::LeaseActivated {
+                    lease_id: lease_id.to_string(),
+                    at: now,
+                });
+                let lease = self.leases[lease_id].clone();
 
                 LeaseResult::Success { lease }
             }
         }
     }
 
-    fn release(&mut self, lease_id: &str) -> bool {
-        if let Some(lease) = self.leases.get_mut(lease_id) {
-            lease.state = crate::types::LeaseState::Released;
-            true
-        } else {
-            false
+    fn grant_waiters(&mut self, now: u64) -> Vec<Lease> {
+        self.queue.expire(now);
+        let mut granted = Vec::new();
+        // Each grant lets the waiter behind it have a go
+        loop {
+            // Counted afresh each pass, so grants weigh on later ones
+            let priorities = self.scheduling_priorities();
+            let mut progressed = false;
+            for waiter in self.queue.heads() {
+                let active_leases = self.candidate_leases(waiter.predicate, &waiter.resource);
+                let verdict = self.scheduler.decide(
+                    &self.engine,
+                    &waiter.agent_id,
+                    &waiter.session_id,
+                    waiter.predicate,
+                    &waiter.resource,
+                    &active_leases,
+                    &priorities,
+                );
+                // Waiters never wound holders
+                if verdict.status != VerdictStatus::Granted || !verdict.preempted.is_empty() {
+                    continue;
+                }
+                let lease = Lease {
+                    deadline: waiter.deadline,
+                    ..Lease::new(
+                        lease_id_for(&waiter.agent_id, now, |id| self.leases.contains_key(id)),
+                        waiter.agent_id.clone(),
+                        waiter.session_id.clone(),
+                        waiter.resource.clone(),
+                        waiter.predicate,
+                        waiter.ttl,
+                        now,
+                    )
+                };
+                self.apply(&KlockEvent::LeaseCreated {
+                    lease: lease.clone(),
+                });
+                self.queue.granted(&waiter, &lease);
+                granted.push(lease);
+                progressed = true;
+            }
+            if !progressed {
+                return granted;
+            }
         }
     }
 
+    fn waiters(&self) -> Vec<Waiter> {
+        self.queue.waiters()
+    }
+
+    fn cancel_waiters(&mut self, filter: &WaiterFilter) -> Vec<Waiter> {
+        self.queue.cancel(filter)
+    }
+
+    fn release(&mut self, lease_id: &str) -> bool {
+        self.apply(&KlockEvent::LeaseReleased {
+            lease_id: lease_id.to_string(),
+        })
+    }
+
     fn heartbeat(&mut self, lease_id: &str, now: u64) -> bool {
-        if let Some(lease) = self.leases.get_mut(lease_id) {
-            if lease.state == crate::types::LeaseState::Active {
-                lease.last_heartbeat = now;
-                lease.expires_at = now + lease.ttl;
-                return true;
+        self.apply(&KlockEvent::LeaseRenewed {
+            lease_id: lease_id.to_string(),
+            at: now,
+        })
+    }
+
+    fn get_active_leases(&self) -> Vec<Lease> {
+        self.in_state(LeaseState::Active)
+    }
+
+    fn lease_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for lease in self.leases.values() {
+            if lease.state == LeaseState::Active {
+                *counts.entry(lease.agent_id.clone()).or_insert(0) += 1;
             }
         }
-        false
+        counts
     }
 
-    fn get_active_leases(&self) -> Vec<Lease> {
-        self.leases
-            .values()
-            .filter(|l| l.state == crate::types::LeaseState::Active)
+    fn leases_touching(&self, resources: &[ResourceRef]) -> Vec<Lease> {
+        let mut leases: Vec<Lease> = self
+            .index
+            .candidates(resources)
+            .iter()
+            .filter_map(|id| self.leases.get(id))
+            .filter(|l| l.state == LeaseState::Active)
             .cloned()
-            .collect()
+            .collect();
+        sort_leases(&mut leases);
+        leases
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn get_pending_leases(&self) -> Vec<Lease> {
+        self.in_state(LeaseState::Pending)
+    }
+
+    fn get_lease(&self, lease_id: &str) -> Option<Lease> {
+        self.leases.get(lease_id).cloned()
     }
 
     fn evict_expired(&mut self, now: u64) -> usize {
-        let mut expired_count = 0;
-        for lease in self.leases.values_mut() {
-            if lease.state == crate::types::LeaseState::Active && lease.expires_at < now {
-                lease.state = crate::types::LeaseState::Expired;
-                expired_count += 1;
+        let mut expired: Vec<Lease> = self
+            .leases
+            .values()
+            .filter(|lease| !lease.is_terminal() && lease.expires_at < now)
+            .cloned()
+            .collect();
+        sort_leases(&mut expired);
+        expired
+            .into_iter()
+            .filter(|lease| {
+                self.apply(&KlockEvent::LeaseExpired {
+                    lease_id: lease.id.clone(),
+                })
+            })
+            .count()
+    }
+
+    fn compact(&mut self, before: u64) -> usize {
+        let initial = self.leases.len();
+        self.apply(&KlockEvent::LeasesCompacted { before });
+        initial - self.leases.len()
+    }
+
+    fn apply(&mut self, event: &KlockEvent) -> bool {
+        let applied = match event {
+            KlockEvent::LeaseCreated { lease } => {
+                // An ID reused within the same millisecond replaces the lease
+                self.index.remove(&lease.id);
+                if lease.state == LeaseState::Active {
+                    self.index_lease(lease);
+                }
+                self.leases.insert(lease.id.clone(), lease.clone());
+                true
+            }
+            KlockEvent::LeasesCompacted { before } => {
+                self.leases
+                    .retain(|_, lease| !(lease.is_terminal() && lease.last_heartbeat < *before));
+                self.leases.shrink_to_fit();
+                true
+            }
+            _ => self.apply_to_existing(event),
+        };
+        if applied {
+            self.generation += 1;
+            if let Some(events) = &mut self.events {
+                events.push(event.clone());
+            }
+        }
+        applied
+    }
+
+    fn record_events(&mut self, enabled: bool) {
+        if !enabled {
+            self.events = None;
+        } else if self.events.is_none() {
+            self.events = Some(Vec::new());
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<KlockEvent> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn record_lease_end(&mut self, lease_id: &str, at: u64) {
+        if let Some(lease) = self.leases.get_mut(lease_id) {
+            lease.ended_at = Some(at.min(lease.expires_at));
+        }
+    }
+
+    fn leases_at(&self, resource: &ResourceRef, at: u64) -> Vec<Lease> {
+        let mut leases: Vec<Lease> = self
+            .leases
+            .values()
+            .filter(|l| l.held_at(at) && l.resource.overlaps(resource))
+            .cloned()
+            .collect();
+        sort_leases(&mut leases);
+        leases
+    }
+
+    fn usage(&self) -> StoreUsage {
+        let mut usage = StoreUsage::default();
+        for lease in self.leases.values() {
+            match lease.state {
+                LeaseState::Active => usage.active_leases += 1,
+                LeaseState::Pending => usage.pending_leases += 1,
+                _ => usage.terminal_leases += 1,
             }
+            usage.estimated_bytes += lease.estimated_bytes();
         }
-        expired_count
+        usage
     }
 }
@@ -0,0 +1,467 @@
+//! Dual-write storage for moving a live deployment between backends.
+//!
+//! A `MirroredStore` serves every read from its primary backend and mirrors
+//! every change onto a secondary one. Mirroring replays the events the
+//! primary records (see `event.rs`) rather than repeating the calls, so
+//! both backends hold the same lease IDs and timestamps. When the secondary
+//! has kept up for long enough, the deployment is switched over to it.
+//!
+//! The secondary never fails a request: an event it cannot apply (because
+//! it is unavailable, or has drifted from the primary) is counted as a
+//! divergence in the `MirrorReport`, and `parity` lists the leases that
+//! differ.
+//!
+//! Deployments that do not share a process replicate through the firehose
+//! instead: `replay` applies the lease changes read from another server's
+//! `EventEnvelope`s to a local backend.
+
+use crate::alias::AliasTable;
+use crate::client::LeaseStoreExt;
+use crate::conflict::ConflictEngine;
+use crate::event::KlockEvent;
+use crate::fair_share::FairSharePolicy;
+use crate::infrastructure::{LeaseMutationError, LeaseStore, StoreUsage};
+use crate::registry::{AgentRegistry, Priority};
+use crate::scheduler::Scheduler;
+use crate::types::{
+    EventEnvelope, EventPayload, Lease, LeaseResult, Predicate, ResourceRef, SPOTriple,
+};
+use crate::wait_queue::{Waiter, WaiterFilter};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// How closely the secondary backend is following the primary
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MirrorReport {
+    /// Events applied to both backends
+    pub events_mirrored: u64,
+    /// Events the secondary could not apply
+    pub divergences: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_divergence: Option<String>,
+    /// Live leases and agent priorities that currently differ between the
+    /// backends; empty when they are in parity
+    pub differences: Vec<String>,
+}
+
+/// Copy live (active and pending) leases, agent priorities, rename aliases
+/// and persisted intents from one backend into another. Terminal lease history is not
+/// copied. Returns the number of leases copied, or the error reading or
+/// writing intents; leases are copied regardless.
+pub fn copy_state(from: &dyn LeaseStoreExt, to: &mut dyn LeaseStoreExt) -> Result<usize, String> {
+    let registry = to.registry();
+    for (agent_id, priority) in from.get_priorities() {
+        registry.register(agent_id, priority);
+    }
+    to.save_aliases(&from.load_aliases());
+    let intents_copied = from.load_intents().and_then(|intents| {
+        intents
+            .iter()
+            .try_for_each(|(sequence, intent)| to.save_intent(*sequence, intent))
+    });
+
+    let leases_copied = live_leases(from)
+        .into_values()
+        .filter(|lease| {
+            to.apply(&KlockEvent::LeaseCreated {
+                lease: lease.clone(),
+            })
+        })
+        .count();
+    intents_copied.map(|_| leases_copied)
+}
+
+/// Differences in live leases and agent priorities between two backends,
+/// in a stable order. Empty when they agree.
+pub fn parity(primary: &dyn LeaseStoreExt, secondary: &dyn LeaseStoreExt) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    let expected = live_leases(primary);
+    let mut actual = live_leases(secondary);
+    for (id, lease) in &expected {
+        match actual.remove(id) {
+            None => differences.push(format!("lease {}: missing from secondary", id)),
+            Some(other) if describe(&other) != describe(lease) => differences.push(format!(
+                "lease {}: primary has {}, secondary has {}",
+                id,
+                describe(lease),
+                describe(&other)
+            )),
+            Some(_) => {}
+        }
+    }
+    for id in actual.keys() {
+        differences.push(format!("lease {}: only on secondary", id));
+    }
+
+    let expected: BTreeMap<_, _> = primary.get_priorities().into_iter().collect();
+    let actual = secondary.get_priorities();
+    for (agent_id, priority) in &expected {
+        if actual.get(agent_id) != Some(priority) {
+            differences.push(format!(
+                "agent {}: priority {} on primary, {} on secondary",
+                agent_id,
+                priority,
+                actual
+                    .get(agent_id)
+                    .map_or("none".to_string(), |p| p.to_string())
+            ));
+        }
+    }
+
+    differences
+}
+
+/// Apply the lease change an envelope carries to `to`. Returns whether it
+/// applied; events that change no lease are skipped, and envelopes of a
+/// newer schema are refused.
+pub fn replay(to: &mut dyn LeaseStore, envelope: &EventEnvelope) -> Result<bool, String> {
+    envelope.check_version()?;
+    match &envelope.event {
+        EventPayload::LeaseChange { change, .. } => Ok(to.apply(change)),
+        _ => Ok(false),
+    }
+}
+
+fn live_leases(store: &dyn LeaseStoreExt) -> BTreeMap<String, Lease> {
+    store
+        .get_active_leases()
+        .into_iter()
+        .chain(store.get_pending_leases())
+        .map(|lease| (lease.id.clone(), lease))
+        .collect()
+}
+
+fn describe(lease: &Lease) -> String {
+    format!(
+        "{:?} {:?} {} by {}/{} (acquired {}, ttl {}, heartbeat {}, expires {})",
+        lease.state,
+        lease.predicate,
+        lease.resource.key(),
+        lease.agent_id,
+        lease.session_id,
+        lease.acquired_at,
+        lease.ttl,
+        lease.last_heartbeat,
+        lease.expires_at
+    )
+}
+
+/// A store that writes to two backends and reads from the first
+pub struct MirroredStore {
+    primary: Box<dyn LeaseStoreExt + Send>,
+    secondary: Box<dyn LeaseStoreExt + Send>,
+    events: Option<Vec<KlockEvent>>,
+    events_mirrored: u64,
+    divergences: u64,
+    last_divergence: Option<String>,
+}
+
+impl MirroredStore {
+    /// Start mirroring `primary` onto `secondary`, first copying the
+    /// primary's live state across.
+    pub fn new(
+        mut primary: Box<dyn LeaseStoreExt + Send>,
+        mut secondary: Box<dyn LeaseStoreExt + Send>,
+    ) -> Self {
+        let copied = copy_state(primary.as_ref(), secondary.as_mut());
+        primary.record_events(true);
+        // Anything the primary recorded before now is already copied
+        primary.take_events();
+        let mut store = Self {
+            primary,
+            secondary,
+            events: None,
+            events_mirrored: 0,
+            divergences: 0,
+            last_divergence: None,
+        };
+        store.diverged_on(copied.map(|_| ()));
+        store
+    }
+
+    /// Divergence counters and the current differences between backends
+    pub fn report(&self) -> MirrorReport {
+        MirrorReport {
+            events_mirrored: self.events_mirrored,
+            divergences: self.divergences,
+            last_divergence: self.last_divergence.clone(),
+            differences: parity(self.primary.as_ref(), self.secondary.as_ref()),
+        }
+    }
+
+    /// Count a write the secondary failed as a divergence
+    fn diverged_on(&mut self, mirrored: Result<(), String>) {
+        if let Err(e) = mirrored {
+            self.divergences += 1;
+            self.last_divergence = Some(format!("secondary failed: {}", e));
+        }
+    }
+
+    /// Replay what the primary just did onto the secondary
+    fn mirror(&mut self) {
+        for event in self.primary.take_events() {
+            if self.secondary.apply(&event) {
+                self.events_mirrored += 1;
+            } else {
+                self.divergences += 1;
+                self.last_divergence = Some(match event.lease_id() {
+                    Some(id) => format!("secondary rejected {} for lease {}", event.name(), id),
+                    None => format!("secondary rejected {}", event.name()),
+                });
+            }
+            if let Some(events) = &mut self.events {
+                events.push(event);
+            }
+        }
+    }
+}
+
+impl LeaseStore for MirroredStore {
+    fn acquire(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        predicate: Predicate,
+        ttl: u64,
+        now: u64,
+    ) -> LeaseResult {
+        let result = self
+            .primary
+            .acquire(agent_id, session_id, resource, predicate, ttl, now);
+        self.mirror();
+        result
+    }
+
+    fn acquire_with_deadline(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        predicate: Predicate,
+        ttl: u64,
+        deadline: Option<u64>,
+        now: u64,
+    ) -> LeaseResult {
+        let result = self.primary.acquire_with_deadline(
+            agent_id, session_id, resource, predicate, ttl, deadline, now,
+        );
+        self.mirror();
+        result
+    }
+
+    fn release(&mut self, lease_id: &str) -> bool {
+        let released = self.primary.release(lease_id);
+        self.mirror();
+        released
+    }
+
+    fn heartbeat(&mut self, lease_id: &str, now: u64) -> bool {
+        let renewed = self.primary.heartbeat(lease_id, now);
+        self.mirror();
+        renewed
+    }
+
+    fn get_active_leases(&self) -> Vec<Lease> {
+        self.primary.get_active_leases()
+    }
+
+    fn lease_counts(&self) -> HashMap<String, usize> {
+        self.primary.lease_counts()
+    }
+
+    fn leases_touching(&self, resources: &[ResourceRef]) -> Vec<Lease> {
+        self.primary.leases_touching(resources)
+    }
+
+    fn generation(&self) -> u64 {
+        self.primary.generation()
+    }
+
+    fn get_pending_leases(&self) -> Vec<Lease> {
+        self.primary.get_pending_leases()
+    }
+
+    fn get_lease(&self, lease_id: &str) -> Option<Lease> {
+        self.primary.get_lease(lease_id)
+    }
+
+    fn grant_waiters(&mut self, now: u64) -> Vec<Lease> {
+        let granted = self.primary.grant_waiters(now);
+        self.mirror();
+        granted
+    }
+
+    fn waiters(&self) -> Vec<Waiter> {
+        self.primary.waiters()
+    }
+
+    fn cancel_waiters(&mut self, filter: &WaiterFilter) -> Vec<Waiter> {
+        self.primary.cancel_waiters(filter)
+    }
+
+    fn evict_expired(&mut self, now: u64) -> usize {
+        let evicted = self.primary.evict_expired(now);
+        self.mirror();
+        evicted
+    }
+
+    fn pre_acquire(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resources: &[(ResourceRef, Predicate)],
+        ttl: u64,
+        activate_by: u64,
+        now: u64,
+    ) -> Vec<Lease> {
+        let reserved =
+            self.primary
+                .pre_acquire(agent_id, session_id, resources, ttl, activate_by, now);
+        self.mirror();
+        reserved
+    }
+
+    fn activate(&mut self, lease_id: &str, now: u64) -> LeaseResult {
+        let result = self.primary.activate(lease_id, now);
+        self.mirror();
+        result
+    }
+
+    fn compact(&mut self, before: u64) -> usize {
+        let removed = self.primary.compact(before);
+        self.mirror();
+        removed
+    }
+
+    fn usage(&self) -> StoreUsage {
+        self.primary.usage()
+    }
+
+    fn apply(&mut self, event: &KlockEvent) -> bool {
+        let applied = self.primary.apply(event);
+        self.mirror();
+        applied
+    }
+
+    fn apply_at(
+        &mut self,
+        event: &KlockEvent,
+        expected_revision: u64,
+    ) -> Result<Lease, LeaseMutationError> {
+        let result = self.primary.apply_at(event, expected_revision);
+        self.mirror();
+        result
+    }
+
+    fn record_events(&mut self, enabled: bool) {
+        if !enabled {
+            self.events = None;
+        } else if self.events.is_none() {
+            self.events = Some(Vec::new());
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<KlockEvent> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn record_lease_end(&mut self, lease_id: &str, at: u64) {
+        self.primary.record_lease_end(lease_id, at);
+        self.secondary.record_lease_end(lease_id, at);
+    }
+
+    fn leases_at(&self, resource: &ResourceRef, at: u64) -> Vec<Lease> {
+        self.primary.leases_at(resource, at)
+    }
+
+    fn save_aliases(&mut self, aliases: &AliasTable) {
+        self.primary.save_aliases(aliases);
+        self.secondary.save_aliases(aliases);
+    }
+
+    fn load_aliases(&self) -> AliasTable {
+        self.primary.load_aliases()
+    }
+
+    fn save_intent(&mut self, sequence: u64, intent: &SPOTriple) -> Result<(), String> {
+        self.primary.save_intent(sequence, intent)?;
+        let mirrored = self.secondary.save_intent(sequence, intent);
+        self.diverged_on(mirrored);
+        Ok(())
+    }
+
+    fn delete_intent(&mut self, sequence: u64) -> Result<(), String> {
+        self.primary.delete_intent(sequence)?;
+        let mirrored = self.secondary.delete_intent(sequence);
+        self.diverged_on(mirrored);
+        Ok(())
+    }
+
+    fn load_intents(&self) -> Result<Vec<(u64, SPOTriple)>, String> {
+        self.primary.load_intents()
+    }
+
+    /// Only the primary must be ready; the secondary's failures show up as
+    /// divergences instead of failing requests.
+    fn check_ready(&self) -> Result<(), String> {
+        self.primary.check_ready()
+    }
+}
+
+impl LeaseStoreExt for MirroredStore {
+    fn registry(&self) -> Arc<dyn AgentRegistry> {
+        Arc::new(MirroredRegistry {
+            primary: self.primary.registry(),
+            secondary: self.secondary.registry(),
+        })
+    }
+    fn set_registry(&mut self, registry: Arc<dyn AgentRegistry>) {
+        self.primary.set_registry(registry.clone());
+        self.secondary.set_registry(registry);
+    }
+    fn set_scheduler(&mut self, scheduler: Arc<dyn Scheduler>) {
+        self.primary.set_scheduler(scheduler.clone());
+        self.secondary.set_scheduler(scheduler);
+    }
+    fn set_fair_share(&mut self, policy: FairSharePolicy) {
+        self.primary.set_fair_share(policy.clone());
+        self.secondary.set_fair_share(policy);
+    }
+    fn set_conflict_engine(&mut self, engine: Arc<ConflictEngine>) {
+        self.primary.set_conflict_engine(engine.clone());
+        self.secondary.set_conflict_engine(engine);
+    }
+    fn mirror_report(&self) -> Option<MirrorReport> {
+        Some(self.report())
+    }
+}
+
+/// Registers agents with both backends' registries, reading from the
+/// primary's
+struct MirroredRegistry {
+    primary: Arc<dyn AgentRegistry>,
+    secondary: Arc<dyn AgentRegistry>,
+}
+
+impl AgentRegistry for MirroredRegistry {
+    fn register(&self, agent_id: String, priority: Priority) {
+        self.secondary.register(agent_id.clone(), priority);
+        self.primary.register(agent_id, priority);
+    }
+
+    fn priority(&self, agent_id: &str) -> Option<Priority> {
+        self.primary.priority(agent_id)
+    }
+
+    fn priorities(&self) -> HashMap<String, Priority> {
+        self.primary.priorities()
+    }
+
+    fn unregister(&self, agent_id: &str) -> bool {
+        self.secondary.unregister(agent_id);
+        self.primary.unregister(agent_id)
+    }
+}
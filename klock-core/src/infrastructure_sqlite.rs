@@ -1,5 +1,6 @@
 //! SQLite-backed LeaseStore implementation.
-//! Provides persistent lease storage across server restarts.
+//! Provides persistent lease, rename alias and intent storage across server
+//! restarts.
 //!
 //! Enable with the `sqlite` feature flag:
 //! ```toml
@@ -8,17 +9,42 @@
 
 use rusqlite::{Connection, params};
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::infrastructure::LeaseStore;
-use crate::scheduler::{VerdictStatus, WaitDieScheduler};
+use crate::alias::{AliasTable, RenameAlias};
+use crate::conflict::ConflictEngine;
+use crate::event::{KlockEvent, apply_to_lease};
+use crate::fair_share::FairSharePolicy;
+use crate::index::ResourceIndex;
+use crate::infrastructure::{
+    LeaseMutationError, LeaseStore, StoreUsage, check_revision, lease_id_for,
+    partition_for_activation,
+};
+use crate::registry::{AgentRegistry, Priority, SqliteAgentRegistry};
+use crate::scheduler::{Scheduler, VerdictStatus, WaitDieScheduler, with_deadlines};
 use crate::types::*;
+use crate::wait_queue::{WaitQueue, Waiter, WaiterFilter, estimated_wait_ms};
 
 /// A persistent lease store backed by SQLite.
 ///
-/// Uses WAL mode for concurrent read performance.
+/// Uses WAL mode for concurrent read performance. Active leases are also
+/// indexed in memory by resource, which assumes this store is the only
+/// writer to the database.
 pub struct SqliteLeaseStore {
     conn: Connection,
-    priorities: HashMap<String, u64>,
+    index: ResourceIndex,
+    registry: Arc<dyn AgentRegistry>,
+    engine: Arc<ConflictEngine>,
+    scheduler: Arc<dyn Scheduler>,
+    /// Penalty for the leases each agent holds (disabled by default)
+    fair_share: FairSharePolicy,
+    /// Applied events, while recording is enabled
+    events: Option<Vec<KlockEvent>>,
+    /// Number of events applied through this store
+    generation: u64,
+    /// Acquires waiting for their resource; kept in memory, so a restart
+    /// empties the queues
+    queue: WaitQueue,
 }
 
 impl SqliteLeaseStore {
@@ -42,47 +68,232 @@ impl SqliteLeaseStore {
                 acquired_at INTEGER NOT NULL,
                 ttl         INTEGER NOT NULL,
                 expires_at  INTEGER NOT NULL,
-                last_heartbeat INTEGER NOT NULL
+                last_heartbeat INTEGER NOT NULL,
+                revision    INTEGER NOT NULL DEFAULT 0,
+                revoked_reason TEXT,
+                renewals    INTEGER NOT NULL DEFAULT 0,
+                deadline    INTEGER,
+                ended_at    INTEGER
             );
             CREATE INDEX IF NOT EXISTS idx_leases_state ON leases(state);
             CREATE INDEX IF NOT EXISTS idx_leases_resource ON leases(res_type, res_path);
-
-            CREATE TABLE IF NOT EXISTS agent_priorities (
-                agent_id TEXT PRIMARY KEY,
-                priority INTEGER NOT NULL
+            CREATE TABLE IF NOT EXISTS rename_aliases (
+                res_type    TEXT NOT NULL,
+                from_path   TEXT NOT NULL,
+                to_path     TEXT NOT NULL,
+                agent_id    TEXT NOT NULL,
+                session_id  TEXT NOT NULL,
+                created_at  INTEGER NOT NULL,
+                PRIMARY KEY (res_type, from_path, to_path)
+            );
+            CREATE TABLE IF NOT EXISTS active_intents (
+                position    INTEGER PRIMARY KEY,
+                id          TEXT NOT NULL,
+                subject     TEXT NOT NULL,
+                predicate   TEXT NOT NULL,
+                res_type    TEXT NOT NULL,
+                res_path    TEXT NOT NULL,
+                timestamp   INTEGER NOT NULL,
+                confidence  TEXT NOT NULL,
+                session_id  TEXT NOT NULL,
+                renamed_to  TEXT,
+                expires_at  INTEGER,
+                long_lived  INTEGER NOT NULL DEFAULT 0
             );",
         )?;
 
-        // Load priorities into memory for fast access
-        let mut priorities = HashMap::new();
-        {
-            let mut stmt = conn.prepare("SELECT agent_id, priority FROM agent_priorities")?;
-            let rows = stmt.query_map([], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
-            })?;
-            for row in rows {
-                let (agent_id, priority) = row?;
-                priorities.insert(agent_id, priority);
-            }
+        // Databases created before lease revisions lack the column
+        let has_revision = conn.prepare("SELECT revision FROM leases LIMIT 0").is_ok();
+        if !has_revision {
+            conn.execute_batch(
+                "ALTER TABLE leases ADD COLUMN revision INTEGER NOT NULL DEFAULT 0",
+            )?;
+        }
+        // ... and those created before revocation reasons lack this one
+        let has_revoked_reason = conn
+            .prepare("SELECT revoked_reason FROM leases LIMIT 0")
+            .is_ok();
+        if !has_revoked_reason {
+            conn.execute_batch("ALTER TABLE leases ADD COLUMN revoked_reason TEXT")?;
+        }
+        // ... and those created before renewal counts lack this one
+        let has_renewals = conn.prepare("SELECT renewals FROM leases LIMIT 0").is_ok();
+        if !has_renewals {
+            conn.execute_batch(
+                "ALTER TABLE leases ADD COLUMN renewals INTEGER NOT NULL DEFAULT 0",
+            )?;
+        }
+        // ... and those created before deadlines lack this one
+        let has_deadline = conn.prepare("SELECT deadline FROM leases LIMIT 0").is_ok();
+        if !has_deadline {
+            conn.execute_batch("ALTER TABLE leases ADD COLUMN deadline INTEGER")?;
+        }
+        // ... and those created before end times lack this one
+        let has_ended_at = conn.prepare("SELECT ended_at FROM leases LIMIT 0").is_ok();
+        if !has_ended_at {
+            conn.execute_batch("ALTER TABLE leases ADD COLUMN ended_at INTEGER")?;
         }
+        // Intent tables created before long-lived intents lack this one
+        let has_long_lived = conn
+            .prepare("SELECT long_lived FROM active_intents LIMIT 0")
+            .is_ok();
+        if !has_long_lived {
+            conn.execute_batch(
+                "ALTER TABLE active_intents ADD COLUMN long_lived INTEGER NOT NULL DEFAULT 0",
+            )?;
+        }
+
+        // Priorities live in the same database, behind their own registry
+        let registry = Arc::new(SqliteAgentRegistry::open(path)?);
 
-        Ok(Self { conn, priorities })
+        let mut store = Self {
+            conn,
+            index: ResourceIndex::new(),
+            registry,
+            engine: Arc::new(ConflictEngine::default()),
+            scheduler: Arc::new(WaitDieScheduler),
+            fair_share: FairSharePolicy::default(),
+            events: None,
+            generation: 0,
+            queue: WaitQueue::new(),
+        };
+        store.rebuild_index();
+        Ok(store)
     }
 
-    /// Register an agent with a priority timestamp.
-    pub fn register_agent_priority(&mut self, agent_id: String, priority: u64) {
-        self.conn
+    /// Replace the rules used to detect conflicts between leases.
+    pub fn set_conflict_engine(&mut self, engine: Arc<ConflictEngine>) {
+        self.engine = engine;
+        // Implication rules change what each lease touches
+        self.rebuild_index();
+    }
+
+    fn rebuild_index(&mut self) {
+        self.index.clear();
+        for lease in self.get_active_leases() {
+            self.index_lease(&lease);
+        }
+    }
+
+    fn index_lease(&mut self, lease: &Lease) {
+        let resources = self
+            .engine
+            .footprint_resources(lease.predicate, &lease.resource);
+        self.index.insert(&lease.id, resources);
+    }
+
+    /// Apply a transition to one stored lease, keeping the index in step.
+    /// The row is only written while it is still at the revision read, so a
+    /// change another process made in between is never overwritten.
+    fn apply_to_existing(
+        &mut self,
+        event: &KlockEvent,
+        expected_revision: Option<u64>,
+    ) -> Result<Lease, LeaseMutationError> {
+        let mut lease = event
+            .lease_id()
+            .and_then(|id| self.get_lease(id))
+            .ok_or(LeaseMutationError::NotFound)?;
+        if let Some(expected) = expected_revision {
+            check_revision(&lease, expected)?;
+        }
+        let read_revision = lease.revision;
+        let was_active = lease.state == LeaseState::Active;
+        if !apply_to_lease(&mut lease, event) {
+            return Err(LeaseMutationError::InvalidState { state: lease.state });
+        }
+        let rows = self
+            .conn
             .execute(
-                "INSERT OR REPLACE INTO agent_priorities (agent_id, priority) VALUES (?1, ?2)",
-                params![agent_id, priority],
+                "UPDATE leases SET state = ?1, last_heartbeat = ?2, expires_at = ?3, revision = ?4,
+                 revoked_reason = ?5, renewals = ?6 WHERE id = ?7 AND revision = ?8",
+                params![
+                    format!("{:?}", lease.state),
+                    lease.last_heartbeat,
+                    lease.expires_at,
+                    lease.revision,
+                    lease.revoked_reason,
+                    lease.renewals,
+                    lease.id,
+                    read_revision,
+                ],
             )
-            .ok();
-        self.priorities.insert(agent_id, priority);
+            .unwrap_or(0);
+        if rows == 0 {
+            return Err(match self.get_lease(&lease.id) {
+                Some(current) if current.revision != read_revision => {
+                    LeaseMutationError::Conflict {
+                        current_revision: current.revision,
+                    }
+                }
+                _ => LeaseMutationError::NotFound,
+            });
+        }
+        match (was_active, lease.state == LeaseState::Active) {
+            (false, true) => self.index_lease(&lease),
+            (true, false) => {
+                self.index.remove(&lease.id);
+            }
+            _ => {}
+        }
+        Ok(lease)
+    }
+
+    /// Count an applied event, and record it while recording is enabled
+    fn applied(&mut self, event: &KlockEvent) {
+        self.generation += 1;
+        if let Some(events) = &mut self.events {
+            events.push(event.clone());
+        }
+    }
+
+    /// Registered priorities, as the scheduler sees them under fair share
+    fn scheduling_priorities(&self) -> HashMap<String, Priority> {
+        let mut priorities = self.registry.priorities();
+        if self.fair_share.is_enabled() {
+            self.fair_share.apply(&mut priorities, &self.lease_counts());
+        }
+        priorities
+    }
+
+    /// Active leases that may overlap `predicate` on `resource`
+    fn candidate_leases(&self, predicate: Predicate, resource: &ResourceRef) -> Vec<Lease> {
+        self.leases_touching(&self.engine.footprint_resources(predicate, resource))
+    }
+
+    /// Replace the scheduling policy used to resolve acquire conflicts.
+    pub fn set_scheduler(&mut self, scheduler: Arc<dyn Scheduler>) {
+        self.scheduler = scheduler;
+    }
+
+    /// Use `scheduler` instead of Wait-Die from the start.
+    pub fn with_scheduler(mut self, scheduler: Arc<dyn Scheduler>) -> Self {
+        self.set_scheduler(scheduler);
+        self
+    }
+
+    /// Penalize agents for the active leases they hold when scheduling.
+    pub fn set_fair_share(&mut self, policy: FairSharePolicy) {
+        self.fair_share = policy;
+    }
+
+    /// Replace the registry consulted for agent priorities.
+    pub fn set_registry(&mut self, registry: Arc<dyn AgentRegistry>) {
+        self.registry = registry;
+    }
+
+    pub fn registry(&self) -> Arc<dyn AgentRegistry> {
+        self.registry.clone()
+    }
+
+    /// Register an agent with a priority (a bare timestamp is normal class).
+    pub fn register_agent_priority(&mut self, agent_id: String, priority: impl Into<Priority>) {
+        self.registry.register(agent_id, priority.into());
     }
 
     /// Get the priority map (for scheduler).
-    pub fn get_priorities(&self) -> HashMap<String, u64> {
-        self.priorities.clone()
+    pub fn get_priorities(&self) -> HashMap<String, Priority> {
+        self.registry.priorities()
     }
 
     fn parse_predicate(s: &str) -> Predicate {
@@ -93,6 +304,7 @@ impl SqliteLeaseStore {
             "Deletes" => Predicate::Deletes,
             "DependsOn" => Predicate::DependsOn,
             "Renames" => Predicate::Renames,
+            "Excludes" => Predicate::Excludes,
             _ => Predicate::Consumes,
         }
     }
@@ -108,12 +320,21 @@ impl SqliteLeaseStore {
         }
     }
 
+    fn parse_confidence(s: &str) -> Confidence {
+        match s {
+            "High" => Confidence::High,
+            "Medium" => Confidence::Medium,
+            _ => Confidence::Low,
+        }
+    }
+
     fn parse_lease_state(s: &str) -> LeaseState {
         match s {
             "Active" => LeaseState::Active,
             "Expired" => LeaseState::Expired,
             "Released" => LeaseState::Released,
             "Revoked" => LeaseState::Revoked,
+            "Pending" => LeaseState::Pending,
             _ => LeaseState::Active,
         }
     }
@@ -137,8 +358,40 @@ impl SqliteLeaseStore {
             ttl: row.get(8)?,
             expires_at: row.get(9)?,
             last_heartbeat: row.get(10)?,
+            revision: row.get(11)?,
+            revoked_reason: row.get(12)?,
+            renewals: row.get(13)?,
+            deadline: row.get(14)?,
+            ended_at: row.get(15)?,
         })
     }
+
+    fn insert_lease(&self, lease: &Lease) -> bool {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO leases (id, agent_id, session_id, res_type, res_path, predicate, state, acquired_at, ttl, expires_at, last_heartbeat, revision, revoked_reason, renewals, deadline, ended_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+                params![
+                    lease.id,
+                    lease.agent_id,
+                    lease.session_id,
+                    format!("{:?}", lease.resource.resource_type),
+                    lease.resource.path,
+                    format!("{:?}", lease.predicate),
+                    format!("{:?}", lease.state),
+                    lease.acquired_at,
+                    lease.ttl,
+                    lease.expires_at,
+                    lease.last_heartbeat,
+                    lease.revision,
+                    lease.revoked_reason,
+                    lease.renewals,
+                    lease.deadline,
+                    lease.ended_at,
+                ],
+            )
+            .is_ok()
+    }
 }
 
 impl LeaseStore for SqliteLeaseStore {
@@ -150,126 +403,645 @@ impl LeaseStore for SqliteLeaseStore {
         predicate: Predicate,
         ttl: u64,
         now: u64,
+    ) -> LeaseResult {
+        self.acquire_with_deadline(agent_id, session_id, resource, predicate, ttl, None, now)
+    }
+
+    fn acquire_with_deadline(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        predicate: Predicate,
+        ttl: u64,
+        deadline: Option<u64>,
+        now: u64,
     ) -> LeaseResult {
         // Evict expired first
         self.evict_expired(now);
 
-        let active_leases = self.get_active_leases();
+        // A waiter granted while it waited retries into its lease
+        if let Some(lease) = self
+            .queue
+            .claim(agent_id, session_id, &resource, predicate)
+            .and_then(|lease_id| self.get_lease(&lease_id))
+            .filter(|lease| lease.state == LeaseState::Active)
+        {
+            return LeaseResult::Success { lease };
+        }
+
+        let active_leases = self.candidate_leases(predicate, &resource);
 
-        // Check Wait-Die scheduler
-        let verdict = WaitDieScheduler::decide(
+        // Consult the scheduler
+        let verdict = self.scheduler.decide_at(
+            &self.engine,
             agent_id,
+            session_id,
             predicate,
             &resource,
             &active_leases,
-            &self.priorities,
+            &with_deadlines(
+                self.scheduling_priorities(),
+                agent_id,
+                deadline,
+                &active_leases,
+            ),
+            now,
+        );
+
+        if verdict.status != VerdictStatus::Wait {
+            self.queue
+                .remove(agent_id, session_id, &resource, predicate);
+        }
+        match verdict.status {
+            VerdictStatus::Wait => {
+                let (request_id, position) = self.queue.enqueue(Waiter {
+                    deadline,
+                    ..Waiter::new(agent_id, session_id, resource.clone(), predicate, ttl, now)
+                });
+                let wait_time = estimated_wait_ms(
+                    &self.engine,
+                    predicate,
+                    &verdict.holders,
+                    &self.queue.ahead(agent_id, session_id, &resource, predicate),
+                    now,
+                );
+                LeaseResult::Failure {
+                    reason: LeaseFailureReason::Wait,
+                    existing_lease: None,
+                    wait_time: Some(wait_time),
+                    queue_position: Some(position),
+                    request_id: Some(request_id),
+                    holders: verdict.holders.clone(),
+                }
+            }
+            VerdictStatus::Die => LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                existing_lease: None,
+                wait_time: verdict.retry_after_ms,
+                queue_position: None,
+                request_id: None,
+                holders: verdict.holders.clone(),
+            },
+            VerdictStatus::Granted => {
+                // Preemptive policies name the junior leases to revoke
+                for preempted_id in &verdict.preempted {
+                    self.apply(&KlockEvent::LeaseRevoked {
+                        lease_id: preempted_id.clone(),
+                        reason: format!("Wounded by senior agent '{}'", agent_id),
+                    });
+                }
+
+                let lease_id = lease_id_for(agent_id, now, |id| self.get_lease(id).is_some());
+                let lease = Lease {
+                    deadline,
+                    ..Lease::new(
+                        lease_id,
+                        agent_id.to_string(),
+                        session_id.to_string(),
+                        resource,
+                        predicate,
+                        ttl,
+                        now,
+                    )
+                };
+
+                self.apply(&KlockEvent::LeaseCreated {
+                    lease: lease.clone(),
+                });
+
+                LeaseResult::Success { lease }
+            }
+        }
+    }
+
+    fn pre_acquire(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resources: &[(ResourceRef, Predicate)],
+        ttl: u64,
+        activate_by: u64,
+        now: u64,
+    ) -> Vec<Lease> {
+        let mut reserved = Vec::with_capacity(resources.len());
+        for (resource, predicate) in resources {
+            let lease = Lease::new(
+                lease_id_for(agent_id, now, |id| self.get_lease(id).is_some()),
+                agent_id.to_string(),
+                session_id.to_string(),
+                resource.clone(),
+                *predicate,
+                ttl,
+                now,
+            )
+            .into_pending(activate_by);
+
+            self.apply(&KlockEvent::LeaseCreated {
+                lease: lease.clone(),
+            });
+            reserved.push(lease);
+        }
+        reserved
+    }
+
+    fn activate(&mut self, lease_id: &str, now: u64) -> LeaseResult {
+        self.evict_expired(now);
+
+        let reservation = match self.get_lease(lease_id) {
+            Some(lease) if lease.state == LeaseState::Pending => lease,
+            _ => {
+                return LeaseResult::rejected(LeaseFailureReason::ReservationExpired, None);
+            }
+        };
+
+        // Leases granted after the reservation never block it
+        let candidates = self.candidate_leases(reservation.predicate, &reservation.resource);
+        let (earlier, latecomers) =
+            partition_for_activation(&self.engine, &reservation, &candidates);
+
+        let verdict = self.scheduler.decide_at(
+            &self.engine,
+            &reservation.agent_id,
+            &reservation.session_id,
+            reservation.predicate,
+            &reservation.resource,
+            &earlier,
+            &self.scheduling_priorities(),
+            now,
         );
 
         match verdict.status {
             VerdictStatus::Wait => LeaseResult::Failure {
                 reason: LeaseFailureReason::Wait,
                 existing_lease: None,
-                wait_time: None,
+                wait_time: Some(estimated_wait_ms(
+                    &self.engine,
+                    reservation.predicate,
+                    &verdict.holders,
+                    &[],
+                    now,
+                )),
+                queue_position: None,
+                request_id: None,
+                holders: verdict.holders.clone(),
             },
             VerdictStatus::Die => LeaseResult::Failure {
                 reason: LeaseFailureReason::Die,
                 existing_lease: None,
                 wait_time: verdict.retry_after_ms,
+                queue_position: None,
+                request_id: None,
+                holders: verdict.holders.clone(),
             },
             VerdictStatus::Granted => {
-                let lease_id = format!("lease_{}_{}", agent_id, now);
-                let lease = Lease::new(
-                    lease_id.clone(),
-                    agent_id.to_string(),
-                    session_id.to_string(),
-                    resource.clone(),
-                    predicate,
-                    ttl,
-                    now,
-                );
+                for preempted_id in &verdict.preempted {
+                    self.apply(&KlockEvent::LeaseRevoked {
+                        lease_id: preempted_id.clone(),
+                        reason: format!("Wounded by senior agent '{}'", reservation.agent_id),
+                    });
+                }
+                for latecomer_id in &latecomers {
+                    self.apply(&KlockEvent::LeaseRevoked {
+                        lease_id: latecomer_id.clone(),
+                        reason: format!("Granted after reservation '{}', now activated", lease_id),
+                    });
+                }
 
-                self.conn
-                    .execute(
-                        "INSERT INTO leases (id, agent_id, session_id, res_type, res_path, predicate, state, acquired_at, ttl, expires_at, last_heartbeat)
-                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'Active', ?7, ?8, ?9, ?10)",
-                        params![
-                            lease.id,
-                            lease.agent_id,
-                            lease.session_id,
-                            format!("{:?}", resource.resource_type),
-                            resource.path,
-                            format!("{:?}", predicate),
-                            lease.acquired_at,
-                            lease.ttl,
-                            lease.expires_at,
-                            lease.last_heartbeat,
-                        ],
-                    )
-                    .ok();
+                let mut lease = reservation;
+                let activated = KlockEvent::LeaseActivated {
+                    lease_id: lease_id.to_string(),
+                    at: now,
+                };
+                self.apply(&activated);
+                apply_to_lease(&mut lease, &activated);
 
                 LeaseResult::Success { lease }
             }
         }
     }
 
+    fn grant_waiters(&mut self, now: u64) -> Vec<Lease> {
+        self.queue.expire(now);
+        let mut granted = Vec::new();
+        // Each grant lets the waiter behind it have a go
+        loop {
+            // Counted afresh each pass, so grants weigh on later ones
+            let priorities = self.scheduling_priorities();
+            let mut progressed = false;
+            for waiter in self.queue.heads() {
+                let active_leases = self.candidate_leases(waiter.predicate, &waiter.resource);
+                let verdict = self.scheduler.decide(
+                    &self.engine,
+                    &waiter.agent_id,
+                    &waiter.session_id,
+                    waiter.predicate,
+                    &waiter.resource,
+                    &active_leases,
+                    &priorities,
+                );
+                // Waiters never wound holders
+                if verdict.status != VerdictStatus::Granted || !verdict.preempted.is_empty() {
+                    continue;
+                }
+                let lease = Lease {
+                    deadline: waiter.deadline,
+                    ..Lease::new(
+                        lease_id_for(&waiter.agent_id, now, |id| self.get_lease(id).is_some()),
+                        waiter.agent_id.clone(),
+                        waiter.session_id.clone(),
+                        waiter.resource.clone(),
+                        waiter.predicate,
+                        waiter.ttl,
+                        now,
+                    )
+                };
+                self.apply(&KlockEvent::LeaseCreated {
+                    lease: lease.clone(),
+                });
+                self.queue.granted(&waiter, &lease);
+                granted.push(lease);
+                progressed = true;
+            }
+            if !progressed {
+                return granted;
+            }
+        }
+    }
+
+    fn waiters(&self) -> Vec<Waiter> {
+        self.queue.waiters()
+    }
+
+    fn cancel_waiters(&mut self, filter: &WaiterFilter) -> Vec<Waiter> {
+        self.queue.cancel(filter)
+    }
+
     fn release(&mut self, lease_id: &str) -> bool {
-        let rows = self
+        self.apply(&KlockEvent::LeaseReleased {
+            lease_id: lease_id.to_string(),
+        })
+    }
+
+    fn heartbeat(&mut self, lease_id: &str, now: u64) -> bool {
+        self.apply(&KlockEvent::LeaseRenewed {
+            lease_id: lease_id.to_string(),
+            at: now,
+        })
+    }
+
+    fn get_active_leases(&self) -> Vec<Lease> {
+        let mut stmt = self
             .conn
-            .execute(
-                "UPDATE leases SET state = 'Released' WHERE id = ?1 AND state = 'Active'",
-                params![lease_id],
+            .prepare(
+                "SELECT id, agent_id, session_id, res_type, res_path, predicate, state, acquired_at, ttl, expires_at, last_heartbeat, revision, revoked_reason, renewals, deadline, ended_at
+                 FROM leases WHERE state = 'Active'
+                 ORDER BY acquired_at, id",
             )
-            .unwrap_or(0);
-        rows > 0
+            .expect("Failed to prepare statement");
+
+        stmt.query_map([], Self::row_to_lease)
+            .expect("Failed to query leases")
+            .filter_map(|r| r.ok())
+            .collect()
     }
 
-    fn heartbeat(&mut self, lease_id: &str, now: u64) -> bool {
-        // Get the lease's TTL to calculate new expiry
-        let ttl: Option<u64> = self
+    fn lease_counts(&self) -> HashMap<String, usize> {
+        let mut stmt = self
             .conn
-            .query_row(
-                "SELECT ttl FROM leases WHERE id = ?1 AND state = 'Active'",
-                params![lease_id],
-                |row| row.get(0),
+            .prepare(
+                "SELECT agent_id, COUNT(*) FROM leases WHERE state = 'Active' GROUP BY agent_id",
             )
-            .ok();
+            .expect("Failed to prepare statement");
 
-        if let Some(ttl) = ttl {
-            let new_expires = now + ttl;
-            let rows = self
-                .conn
-                .execute(
-                    "UPDATE leases SET last_heartbeat = ?1, expires_at = ?2 WHERE id = ?3 AND state = 'Active'",
-                    params![now, new_expires, lease_id],
-                )
-                .unwrap_or(0);
-            rows > 0
-        } else {
-            false
+        stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+        })
+        .expect("Failed to query lease counts")
+        .filter_map(|r| r.ok())
+        .collect()
+    }
+
+    fn leases_touching(&self, resources: &[ResourceRef]) -> Vec<Lease> {
+        let ids: Vec<String> = self.index.candidates(resources).into_iter().collect();
+        if ids.is_empty() {
+            return Vec::new();
         }
+
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT id, agent_id, session_id, res_type, res_path, predicate, state, acquired_at, ttl, expires_at, last_heartbeat, revision, revoked_reason, renewals, deadline, ended_at
+                 FROM leases WHERE state = 'Active' AND id IN ({})
+                 ORDER BY acquired_at, id",
+                placeholders
+            ))
+            .expect("Failed to prepare statement");
+
+        stmt.query_map(rusqlite::params_from_iter(ids.iter()), Self::row_to_lease)
+            .expect("Failed to query leases")
+            .filter_map(|r| r.ok())
+            .collect()
     }
 
-    fn get_active_leases(&self) -> Vec<Lease> {
+    /// Changes made through this store, plus SQLite's `data_version`,
+    /// which moves when another connection commits to the database
+    fn generation(&self) -> u64 {
+        let data_version: i64 = self
+            .conn
+            .query_row("PRAGMA data_version", [], |row| row.get(0))
+            .unwrap_or(0);
+        self.generation + data_version as u64
+    }
+
+    fn get_pending_leases(&self) -> Vec<Lease> {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT id, agent_id, session_id, res_type, res_path, predicate, state, acquired_at, ttl, expires_at, last_heartbeat
-                 FROM leases WHERE state = 'Active'",
+                "SELECT id, agent_id, session_id, res_type, res_path, predicate, state, acquired_at, ttl, expires_at, last_heartbeat, revision, revoked_reason, renewals, deadline, ended_at
+                 FROM leases WHERE state = 'Pending'
+                 ORDER BY acquired_at, id",
             )
             .expect("Failed to prepare statement");
 
-        stmt.query_map([], |row| Self::row_to_lease(row))
+        stmt.query_map([], Self::row_to_lease)
             .expect("Failed to query leases")
             .filter_map(|r| r.ok())
             .collect()
     }
 
+    fn get_lease(&self, lease_id: &str) -> Option<Lease> {
+        self.conn
+            .query_row(
+                "SELECT id, agent_id, session_id, res_type, res_path, predicate, state, acquired_at, ttl, expires_at, last_heartbeat, revision, revoked_reason, renewals, deadline, ended_at
+                 FROM leases WHERE id = ?1",
+                params![lease_id],
+                Self::row_to_lease,
+            )
+            .ok()
+    }
+
     fn evict_expired(&mut self, now: u64) -> usize {
+        let expired: Vec<String> = match self.conn.prepare(
+            "SELECT id FROM leases WHERE state IN ('Active', 'Pending') AND expires_at < ?1
+             ORDER BY acquired_at, id",
+        ) {
+            Ok(mut stmt) => stmt
+                .query_map(params![now], |row| row.get(0))
+                .map(|rows| rows.filter_map(|r| r.ok()).collect())
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        if expired.is_empty() {
+            return 0;
+        }
+
+        // One transaction for the whole sweep rather than one per lease
+        self.conn.execute_batch("BEGIN").ok();
+        let count = expired
+            .into_iter()
+            .filter(|lease_id| {
+                self.apply(&KlockEvent::LeaseExpired {
+                    lease_id: lease_id.clone(),
+                })
+            })
+            .count();
+        self.conn.execute_batch("COMMIT").ok();
+        count
+    }
+
+    fn compact(&mut self, before: u64) -> usize {
+        self.apply(&KlockEvent::LeasesCompacted { before });
+        0
+    }
+
+    fn apply(&mut self, event: &KlockEvent) -> bool {
+        let applied = match event {
+            KlockEvent::LeaseCreated { lease } => {
+                // An ID reused within the same millisecond replaces the lease
+                self.index.remove(&lease.id);
+                let inserted = self.insert_lease(lease);
+                if inserted && lease.state == LeaseState::Active {
+                    self.index_lease(lease);
+                }
+                inserted
+            }
+            // Terminal rows are the lease history; they live on disk, not in
+            // process memory, so there is nothing to compact here.
+            KlockEvent::LeasesCompacted { .. } => true,
+            _ => self.apply_to_existing(event, None).is_ok(),
+        };
+        if applied {
+            self.applied(event);
+        }
+        applied
+    }
+
+    fn apply_at(
+        &mut self,
+        event: &KlockEvent,
+        expected_revision: u64,
+    ) -> Result<Lease, LeaseMutationError> {
+        let lease = self.apply_to_existing(event, Some(expected_revision))?;
+        self.applied(event);
+        Ok(lease)
+    }
+
+    fn record_events(&mut self, enabled: bool) {
+        if !enabled {
+            self.events = None;
+        } else if self.events.is_none() {
+            self.events = Some(Vec::new());
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<KlockEvent> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn record_lease_end(&mut self, lease_id: &str, at: u64) {
+        self.conn
+            .execute(
+                "UPDATE leases SET ended_at = MIN(?2, expires_at) WHERE id = ?1",
+                params![lease_id, at],
+            )
+            .ok();
+    }
+
+    /// Reads the lease history kept on disk, which compaction never
+    /// discards
+    fn leases_at(&self, resource: &ResourceRef, at: u64) -> Vec<Lease> {
+        let Ok(mut stmt) = self.conn.prepare(
+            "SELECT id, agent_id, session_id, res_type, res_path, predicate, state, acquired_at, ttl, expires_at, last_heartbeat, revision, revoked_reason, renewals, deadline, ended_at
+             FROM leases WHERE state != 'Pending' AND acquired_at <= ?1
+                 AND ?1 < COALESCE(ended_at, expires_at)
+             ORDER BY acquired_at, id",
+        ) else {
+            return Vec::new();
+        };
+        stmt.query_map(params![at], Self::row_to_lease)
+            .map(|rows| {
+                rows.filter_map(|r| r.ok())
+                    .filter(|lease| lease.resource.overlaps(resource))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn save_aliases(&mut self, aliases: &AliasTable) {
+        let Ok(tx) = self.conn.transaction() else {
+            return;
+        };
+        tx.execute("DELETE FROM rename_aliases", []).ok();
+        for alias in aliases.aliases() {
+            tx.execute(
+                "INSERT OR REPLACE INTO rename_aliases (res_type, from_path, to_path, agent_id, session_id, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    format!("{:?}", alias.from.resource_type),
+                    alias.from.path,
+                    alias.to.path,
+                    alias.agent_id,
+                    alias.session_id,
+                    alias.created_at,
+                ],
+            )
+            .ok();
+        }
+        tx.commit().ok();
+    }
+
+    fn load_aliases(&self) -> AliasTable {
+        let mut table = AliasTable::new();
+        let Ok(mut stmt) = self.conn.prepare(
+            "SELECT res_type, from_path, to_path, agent_id, session_id, created_at
+             FROM rename_aliases ORDER BY created_at",
+        ) else {
+            return table;
+        };
+        let rows = stmt.query_map([], |row| {
+            let res_type = Self::parse_resource_type(&row.get::<_, String>(0)?);
+            Ok(RenameAlias {
+                from: ResourceRef::new(res_type.clone(), row.get::<_, String>(1)?),
+                to: ResourceRef::new(res_type, row.get::<_, String>(2)?),
+                agent_id: row.get(3)?,
+                session_id: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        });
+        if let Ok(rows) = rows {
+            for alias in rows.filter_map(|r| r.ok()) {
+                table.insert(alias);
+            }
+        }
+        table
+    }
+
+    fn save_intent(&mut self, sequence: u64, intent: &SPOTriple) -> Result<(), String> {
         self.conn
             .execute(
-                "UPDATE leases SET state = 'Expired' WHERE state = 'Active' AND expires_at < ?1",
-                params![now],
+                "INSERT OR REPLACE INTO active_intents (position, id, subject, predicate, res_type, res_path, timestamp, confidence, session_id, renamed_to, expires_at, long_lived)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    sequence as i64,
+                    intent.id,
+                    intent.subject,
+                    format!("{:?}", intent.predicate),
+                    format!("{:?}", intent.object.resource_type),
+                    intent.object.path,
+                    intent.timestamp,
+                    format!("{:?}", intent.confidence),
+                    intent.session_id,
+                    intent.renamed_to,
+                    intent.expires_at,
+                    intent.long_lived,
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Failed to save intent '{}': {}", intent.id, e))
+    }
+
+    fn delete_intent(&mut self, sequence: u64) -> Result<(), String> {
+        self.conn
+            .execute(
+                "DELETE FROM active_intents WHERE position = ?1",
+                params![sequence as i64],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Failed to delete intent: {}", e))
+    }
+
+    fn load_intents(&self) -> Result<Vec<(u64, SPOTriple)>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT position, id, subject, predicate, res_type, res_path, timestamp, confidence, session_id, renamed_to, expires_at, long_lived
+                 FROM active_intents ORDER BY position",
             )
-            .unwrap_or(0)
+            .map_err(|e| format!("Failed to load intents: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)? as u64,
+                    SPOTriple {
+                        id: row.get(1)?,
+                        subject: row.get(2)?,
+                        predicate: Self::parse_predicate(&row.get::<_, String>(3)?),
+                        object: ResourceRef::new(
+                            Self::parse_resource_type(&row.get::<_, String>(4)?),
+                            row.get::<_, String>(5)?,
+                        ),
+                        timestamp: row.get(6)?,
+                        confidence: Self::parse_confidence(&row.get::<_, String>(7)?),
+                        session_id: row.get(8)?,
+                        renamed_to: row.get(9)?,
+                        expires_at: row.get(10)?,
+                        long_lived: row.get(11)?,
+                    },
+                ))
+            })
+            .map_err(|e| format!("Failed to load intents: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to load intents: {}", e))
+    }
+
+    fn check_ready(&self) -> Result<(), String> {
+        // Rewriting the header's user_version is a cheap write probe that
+        // fails on read-only files and mounts
+        let version: i64 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("SQLite read failed: {}", e))?;
+        self.conn
+            .pragma_update(None, "user_version", version)
+            .map_err(|e| format!("SQLite write failed: {}", e))
+    }
+
+    fn usage(&self) -> StoreUsage {
+        let count = |state_clause: &str| -> usize {
+            self.conn
+                .query_row(
+                    &format!("SELECT COUNT(*) FROM leases WHERE {}", state_clause),
+                    [],
+                    |row| row.get::<_, i64>(0),
+                )
+                .unwrap_or(0) as usize
+        };
+        let page_count: i64 = self
+            .conn
+            .query_row("PRAGMA page_count", [], |row| row.get(0))
+            .unwrap_or(0);
+        let page_size: i64 = self
+            .conn
+            .query_row("PRAGMA page_size", [], |row| row.get(0))
+            .unwrap_or(0);
+
+        StoreUsage {
+            active_leases: count("state = 'Active'"),
+            pending_leases: count("state = 'Pending'"),
+            terminal_leases: count("state IN ('Expired', 'Released', 'Revoked')"),
+            estimated_bytes: (page_count * page_size) as usize,
+        }
     }
 }
@@ -1,8 +1,23 @@
 #[cfg(test)]
 mod tests {
-    use crate::infrastructure::LeaseStore;
+    use crate::activity::{ActivityKind, TimelineQuery};
+    use crate::client::{KlockClient, LeaseStoreExt};
+    use crate::clock::ManualClock;
+    use crate::codes::ReasonCode;
+    use crate::conflict::{CompatibilityMatrix, ConflictEngine};
+    use crate::event::KlockEvent;
+    use crate::fair_share::FairSharePolicy;
+    use crate::infrastructure::{LeaseMutationError, LeaseSnapshot, LeaseStore, StoreUsage};
     use crate::infrastructure_in_memory::InMemoryLeaseStore;
-    use crate::types::{LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType};
+    use crate::registry::AgentRegistry;
+    use crate::scheduler::{Scheduler, WoundWaitScheduler};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, Lease, LeaseFailureReason, LeaseResult, LeaseState, Predicate, ResourceRef,
+        ResourceType, SPOTriple,
+    };
+    use std::cell::Cell;
+    use std::sync::Arc;
 
     #[test]
     fn test_in_memory_store_acquire_and_release() {
@@ -76,4 +91,977 @@ mod tests {
         assert_eq!(store.evict_expired(7000), 1);
         assert_eq!(store.get_active_leases().len(), 0);
     }
+
+    #[test]
+    fn test_in_memory_store_wound_wait_revokes_junior() {
+        let mut store = InMemoryLeaseStore::new();
+        store.set_scheduler(Arc::new(WoundWaitScheduler));
+        store.register_agent_priority("older".to_string(), 100);
+        store.register_agent_priority("younger".to_string(), 200);
+
+        let res = ResourceRef::new(ResourceType::File, "/test");
+
+        // Younger acquires first
+        assert!(matches!(
+            store.acquire("younger", "s2", res.clone(), Predicate::Mutates, 5000, 1000),
+            LeaseResult::Success { .. }
+        ));
+
+        // Older wounds the younger holder and takes the resource
+        let lease = match store.acquire("older", "s1", res, Predicate::Mutates, 5000, 1001) {
+            LeaseResult::Success { lease } => lease,
+            _ => panic!("Expected Success"),
+        };
+
+        let active = store.get_active_leases();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].id, lease.id);
+    }
+
+    #[test]
+    fn test_in_memory_store_custom_conflict_engine() {
+        let mut store = InMemoryLeaseStore::new();
+        store.set_conflict_engine(Arc::new(
+            ConflictEngine::with_matrix(
+                CompatibilityMatrix::default().allow(Predicate::Mutates, Predicate::Mutates),
+            )
+            .unwrap(),
+        ));
+        store.register_agent_priority("older".to_string(), 100);
+        store.register_agent_priority("younger".to_string(), 200);
+
+        let res = ResourceRef::new(ResourceType::File, "/crdt/doc.json");
+        assert!(matches!(
+            store.acquire("older", "s1", res.clone(), Predicate::Mutates, 5000, 1000),
+            LeaseResult::Success { .. }
+        ));
+        assert!(matches!(
+            store.acquire("younger", "s2", res.clone(), Predicate::Mutates, 5000, 1001),
+            LeaseResult::Success { .. }
+        ));
+        assert!(matches!(
+            store.acquire("younger", "s2", res, Predicate::Deletes, 5000, 1002),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            }
+        ));
+    }
+
+    /// Column leases coexist; a table-level lease conflicts with them.
+    /// "older" and "younger" must be registered at 100 and 200.
+    fn assert_column_granularity(store: &mut dyn LeaseStore) {
+        let email = ResourceRef::database_table("users", Some("email"));
+        let name = ResourceRef::database_table("users", Some("name"));
+        let table = ResourceRef::database_table("users", None);
+
+        assert!(matches!(
+            store.acquire("older", "s1", email, Predicate::Mutates, 5000, 1000),
+            LeaseResult::Success { .. }
+        ));
+        assert!(matches!(
+            store.acquire("younger", "s2", name, Predicate::Mutates, 5000, 1001),
+            LeaseResult::Success { .. }
+        ));
+        assert!(matches!(
+            store.acquire("younger", "s2", table, Predicate::Deletes, 5000, 1002),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_in_memory_store_column_granularity() {
+        let mut store = InMemoryLeaseStore::new();
+        store.register_agent_priority("older".to_string(), 100);
+        store.register_agent_priority("younger".to_string(), 200);
+        assert_column_granularity(&mut store);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_column_granularity() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let mut store = SqliteLeaseStore::open(":memory:").unwrap();
+        store.register_agent_priority("older".to_string(), 100);
+        store.register_agent_priority("younger".to_string(), 200);
+        assert_column_granularity(&mut store);
+    }
+
+    /// Stores list leases oldest first, ties broken by ID, whatever order
+    /// they were taken in. "a", "b" and "c" must be registered.
+    fn assert_listing_order(store: &mut dyn LeaseStore) {
+        let file = |path: &str| ResourceRef::new(ResourceType::File, path);
+        for (agent, path, now) in [
+            ("c", "/c.ts", 1002),
+            ("b", "/b.ts", 1000),
+            ("a", "/a.ts", 1000),
+            ("a", "/d.ts", 1001),
+        ] {
+            store.acquire(agent, "s1", file(path), Predicate::Mutates, 60_000, now);
+        }
+        store.pre_acquire(
+            "c",
+            "s2",
+            &[(file("/e.ts"), Predicate::Mutates)],
+            60_000,
+            5000,
+            1003,
+        );
+        store.pre_acquire(
+            "b",
+            "s2",
+            &[(file("/f.ts"), Predicate::Mutates)],
+            60_000,
+            5000,
+            1001,
+        );
+
+        let ids = |leases: Vec<Lease>| leases.into_iter().map(|l| l.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(store.get_active_leases()),
+            vec![
+                "lease_a_1000",
+                "lease_b_1000",
+                "lease_a_1001",
+                "lease_c_1002"
+            ]
+        );
+        assert_eq!(
+            ids(store.leases_touching(&[file("/c.ts"), file("/a.ts")])),
+            vec!["lease_a_1000", "lease_c_1002"]
+        );
+        assert_eq!(
+            ids(store.get_pending_leases()),
+            vec!["lease_b_1001", "lease_c_1003"]
+        );
+    }
+
+    #[test]
+    fn test_in_memory_store_listing_order() {
+        let mut store = InMemoryLeaseStore::new();
+        for (agent, priority) in [("a", 100), ("b", 200), ("c", 300)] {
+            store.register_agent_priority(agent.to_string(), priority);
+        }
+        assert_listing_order(&mut store);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_listing_order() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let mut store = SqliteLeaseStore::open(":memory:").unwrap();
+        for (agent, priority) in [("a", 100), ("b", 200), ("c", 300)] {
+            store.register_agent_priority(agent.to_string(), priority);
+        }
+        assert_listing_order(&mut store);
+    }
+
+    /// Stores apply the engine's reentrancy rule: an agent's leases never
+    /// block it in the same session, and do in its other sessions.
+    /// "agent" must be registered.
+    fn assert_session_reentrancy(store: &mut dyn LeaseStore) {
+        let file = ResourceRef::new(ResourceType::File, "/src/a.ts");
+        assert!(matches!(
+            store.acquire("agent", "s1", file.clone(), Predicate::Mutates, 5000, 1000),
+            LeaseResult::Success { .. }
+        ));
+        assert!(matches!(
+            store.acquire("agent", "s1", file.clone(), Predicate::Mutates, 5000, 1001),
+            LeaseResult::Success { .. }
+        ));
+        assert!(matches!(
+            store.acquire("agent", "s2", file.clone(), Predicate::Mutates, 5000, 1002),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            }
+        ));
+
+        // The same leases, checked directly, give the same answers
+        let held = store.get_active_leases();
+        let engine = ConflictEngine::default();
+        assert!(
+            engine
+                .check_leases_all("agent", "s1", Predicate::Mutates, &file, &held)
+                .is_empty()
+        );
+        assert_eq!(
+            engine
+                .check_leases_all("agent", "s2", Predicate::Mutates, &file, &held)
+                .len(),
+            2
+        );
+
+        // A second lease in the same millisecond gets its own ID
+        let other = ResourceRef::new(ResourceType::File, "/src/b.ts");
+        let LeaseResult::Success { lease } =
+            store.acquire("agent", "s1", other, Predicate::Mutates, 5000, 1001)
+        else {
+            panic!("Expected success");
+        };
+        assert_eq!(lease.id, "lease_agent_1001_2");
+        assert_eq!(store.get_active_leases().len(), 3);
+    }
+
+    #[test]
+    fn test_in_memory_store_session_reentrancy() {
+        let mut store = InMemoryLeaseStore::new();
+        store.register_agent_priority("agent".to_string(), 100);
+        assert_session_reentrancy(&mut store);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_session_reentrancy() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let mut store = SqliteLeaseStore::open(":memory:").unwrap();
+        store.register_agent_priority("agent".to_string(), 100);
+        assert_session_reentrancy(&mut store);
+    }
+
+    /// Waiters granted in separate passes in the same millisecond get their
+    /// own lease IDs. "old" and "young" must be registered, "old" senior.
+    fn assert_waiters_granted_in_one_millisecond(store: &mut dyn LeaseStore) {
+        let file = |path: &str| ResourceRef::new(ResourceType::File, path);
+        let mut held = Vec::new();
+        for path in ["/a.ts", "/b.ts"] {
+            let LeaseResult::Success { lease } =
+                store.acquire("young", "s1", file(path), Predicate::Mutates, 60_000, 1000)
+            else {
+                panic!("Expected success");
+            };
+            held.push(lease.id);
+            let result = store.acquire("old", "s2", file(path), Predicate::Mutates, 60_000, 1001);
+            assert!(matches!(
+                result,
+                LeaseResult::Failure {
+                    reason: LeaseFailureReason::Wait,
+                    ..
+                }
+            ));
+        }
+
+        let mut granted = Vec::new();
+        for lease_id in &held {
+            assert!(store.release(lease_id));
+            granted.extend(store.grant_waiters(2000));
+        }
+        let ids: Vec<&str> = granted.iter().map(|lease| lease.id.as_str()).collect();
+        assert_eq!(ids, vec!["lease_old_2000", "lease_old_2000_2"]);
+        let active = store.get_active_leases();
+        assert_eq!(active.len(), 2);
+        assert!(active.iter().all(|lease| lease.agent_id == "old"));
+    }
+
+    #[test]
+    fn test_in_memory_store_grants_waiters_in_one_millisecond() {
+        let mut store = InMemoryLeaseStore::new();
+        store.register_agent_priority("old".to_string(), 100);
+        store.register_agent_priority("young".to_string(), 200);
+        assert_waiters_granted_in_one_millisecond(&mut store);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_grants_waiters_in_one_millisecond() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let mut store = SqliteLeaseStore::open(":memory:").unwrap();
+        store.register_agent_priority("old".to_string(), 100);
+        store.register_agent_priority("young".to_string(), 200);
+        assert_waiters_granted_in_one_millisecond(&mut store);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_persists_excludes() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let mut store = SqliteLeaseStore::open(":memory:").unwrap();
+        store.register_agent_priority("migrator".to_string(), 100);
+        store.register_agent_priority("reader".to_string(), 200);
+        let res = ResourceRef::new(ResourceType::DatabaseTable, "users");
+        assert!(matches!(
+            store.acquire(
+                "migrator",
+                "s1",
+                res.clone(),
+                Predicate::Excludes,
+                5000,
+                1000
+            ),
+            LeaseResult::Success { .. }
+        ));
+        assert_eq!(store.get_active_leases()[0].predicate, Predicate::Excludes);
+        // Even a read is refused while the resource is excluded
+        assert!(matches!(
+            store.acquire("reader", "s2", res, Predicate::Consumes, 5000, 1001),
+            LeaseResult::Failure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_in_memory_store_pending_reservation_does_not_block() {
+        let mut store = InMemoryLeaseStore::new();
+        store.register_agent_priority("pipeline".to_string(), 100);
+        store.register_agent_priority("other".to_string(), 200);
+
+        let res = ResourceRef::new(ResourceType::File, "/test");
+        let reserved = store.pre_acquire(
+            "pipeline",
+            "stage_2",
+            &[(res.clone(), Predicate::Mutates)],
+            5000,
+            10_000,
+            1000,
+        );
+        assert_eq!(reserved.len(), 1);
+        assert!(store.get_active_leases().is_empty());
+
+        // A junior agent can still take the resource while the spare is pending
+        assert!(matches!(
+            store.acquire("other", "s2", res, Predicate::Mutates, 5000, 2000),
+            LeaseResult::Success { .. }
+        ));
+    }
+
+    /// Reservations made in separate calls in the same millisecond keep
+    /// their own leases. "pipeline" must be registered.
+    fn assert_reservations_in_one_millisecond(store: &mut dyn LeaseStore) {
+        let file = |path: &str| ResourceRef::new(ResourceType::File, path);
+        let mut reserved = Vec::new();
+        for path in ["/a.ts", "/b.ts"] {
+            reserved.extend(store.pre_acquire(
+                "pipeline",
+                "stage_2",
+                &[(file(path), Predicate::Mutates)],
+                5000,
+                10_000,
+                1000,
+            ));
+        }
+        let ids: Vec<&str> = reserved.iter().map(|lease| lease.id.as_str()).collect();
+        assert_eq!(ids, vec!["lease_pipeline_1000", "lease_pipeline_1000_2"]);
+        assert_eq!(store.get_pending_leases().len(), 2);
+
+        for lease in &reserved {
+            assert!(matches!(
+                store.activate(&lease.id, 2000),
+                LeaseResult::Success { .. }
+            ));
+        }
+        assert_eq!(store.get_active_leases().len(), 2);
+    }
+
+    #[test]
+    fn test_in_memory_store_reservations_in_one_millisecond() {
+        let mut store = InMemoryLeaseStore::new();
+        store.register_agent_priority("pipeline".to_string(), 100);
+        assert_reservations_in_one_millisecond(&mut store);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_reservations_in_one_millisecond() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let mut store = SqliteLeaseStore::open(":memory:").unwrap();
+        store.register_agent_priority("pipeline".to_string(), 100);
+        assert_reservations_in_one_millisecond(&mut store);
+    }
+
+    #[test]
+    fn test_in_memory_store_activation_wins_against_latecomers() {
+        let mut store = InMemoryLeaseStore::new();
+        // The latecomer is senior, so a plain acquire would make the reserver DIE
+        store.register_agent_priority("latecomer".to_string(), 100);
+        store.register_agent_priority("pipeline".to_string(), 200);
+
+        let res = ResourceRef::new(ResourceType::File, "/test");
+        let reserved = store.pre_acquire(
+            "pipeline",
+            "stage_2",
+            &[(res.clone(), Predicate::Mutates)],
+            5000,
+            10_000,
+            1000,
+        );
+        assert!(matches!(
+            store.acquire("latecomer", "s2", res, Predicate::Mutates, 5000, 2000),
+            LeaseResult::Success { .. }
+        ));
+
+        let lease = match store.activate(&reserved[0].id, 3000) {
+            LeaseResult::Success { lease } => lease,
+            _ => panic!("Expected Success"),
+        };
+        assert_eq!(lease.expires_at, 8000);
+
+        let active = store.get_active_leases();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].agent_id, "pipeline");
+    }
+
+    #[test]
+    fn test_in_memory_store_reservation_expires_unactivated() {
+        let mut store = InMemoryLeaseStore::new();
+        store.register_agent_priority("pipeline".to_string(), 100);
+
+        let res = ResourceRef::new(ResourceType::File, "/test");
+        let reserved = store.pre_acquire(
+            "pipeline",
+            "stage_2",
+            &[(res, Predicate::Mutates)],
+            5000,
+            2000,
+            1000,
+        );
+
+        assert!(matches!(
+            store.activate(&reserved[0].id, 3000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::ReservationExpired,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_in_memory_store_read_snapshot_tracks_generation() {
+        let mut store = InMemoryLeaseStore::new();
+        let res = ResourceRef::new(ResourceType::File, "/test");
+        let empty = store.read_snapshot(std::slice::from_ref(&res));
+        assert!(empty.leases.is_empty());
+
+        let LeaseResult::Success { lease } =
+            store.acquire("agent_1", "s1", res.clone(), Predicate::Mutates, 5000, 1000)
+        else {
+            panic!("Expected Success");
+        };
+        let held = store.read_snapshot(std::slice::from_ref(&res));
+        assert!(held.generation > empty.generation);
+        assert!(!held.same_holders(&empty));
+
+        // A heartbeat moves the generation but keeps the holders
+        assert!(store.heartbeat(&lease.id, 2000));
+        let renewed = store.read_snapshot(std::slice::from_ref(&res));
+        assert!(renewed.generation > held.generation);
+        assert!(renewed.same_holders(&held));
+    }
+
+    /// Revision-checked mutations apply only at the lease's current
+    /// revision, and every applied change bumps it
+    fn assert_revision_checked(store: &mut dyn LeaseStore) {
+        let file = ResourceRef::new(ResourceType::File, "/src/a.ts");
+        let LeaseResult::Success { lease } =
+            store.acquire("agent", "s1", file, Predicate::Mutates, 5000, 1000)
+        else {
+            panic!("Expected success");
+        };
+        assert_eq!(lease.revision, 0);
+
+        let renew = |at| KlockEvent::LeaseRenewed {
+            lease_id: lease.id.clone(),
+            at,
+        };
+        let renewed = store.apply_at(&renew(2000), 0).unwrap();
+        assert_eq!(
+            (renewed.revision, renewed.expires_at, renewed.renewals),
+            (1, 7000, 1)
+        );
+        assert_eq!(
+            store.apply_at(&renew(3000), 0).unwrap_err(),
+            LeaseMutationError::Conflict {
+                current_revision: 1
+            }
+        );
+        assert_eq!(store.get_lease(&lease.id).unwrap().expires_at, 7000);
+
+        let release = KlockEvent::LeaseReleased {
+            lease_id: lease.id.clone(),
+        };
+        assert_eq!(
+            store.apply_at(&release, 1).unwrap().state,
+            LeaseState::Released
+        );
+        assert_eq!(
+            store.apply_at(&renew(4000), 2).unwrap_err(),
+            LeaseMutationError::InvalidState {
+                state: LeaseState::Released
+            }
+        );
+        let unknown = KlockEvent::LeaseReleased {
+            lease_id: "missing".to_string(),
+        };
+        assert_eq!(
+            store.apply_at(&unknown, 0).unwrap_err(),
+            LeaseMutationError::NotFound
+        );
+    }
+
+    #[test]
+    fn test_in_memory_store_revision_checked_mutations() {
+        assert_revision_checked(&mut InMemoryLeaseStore::new());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_revision_checked_mutations() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        assert_revision_checked(&mut SqliteLeaseStore::open(":memory:").unwrap());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_revision_check_sees_other_connections() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let path =
+            std::env::temp_dir().join(format!("klock_revision_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut agent = SqliteLeaseStore::open(path).unwrap();
+        let mut admin = SqliteLeaseStore::open(path).unwrap();
+        let res = ResourceRef::new(ResourceType::File, "/test");
+        let LeaseResult::Success { lease } =
+            agent.acquire("agent_1", "s1", res, Predicate::Mutates, 5000, 1000)
+        else {
+            panic!("Expected success");
+        };
+
+        // The admin revokes at the revision it read; the agent's release,
+        // decided from the same read, must not overwrite it
+        let revoke = KlockEvent::LeaseRevoked {
+            lease_id: lease.id.clone(),
+            reason: "Revoked by an operator".to_string(),
+        };
+        assert!(admin.apply_at(&revoke, lease.revision).is_ok());
+        let release = KlockEvent::LeaseReleased {
+            lease_id: lease.id.clone(),
+        };
+        assert_eq!(
+            agent.apply_at(&release, lease.revision).unwrap_err(),
+            LeaseMutationError::Conflict {
+                current_revision: 1
+            }
+        );
+        let stored = agent.get_lease(&lease.id).unwrap();
+        assert_eq!(stored.state, LeaseState::Revoked);
+        assert_eq!(
+            stored.revoked_reason.as_deref(),
+            Some("Revoked by an operator")
+        );
+
+        drop(agent);
+        drop(admin);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_client_revision_checked_release_and_revoke() {
+        let mut client = KlockClient::new();
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("agent", "s1", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        let now = client.now();
+        assert_eq!(
+            client
+                .heartbeat_lease_if(&lease.id, 0, now)
+                .unwrap()
+                .revision,
+            1
+        );
+        let stale = client.release_lease_if(&lease.id, 0).unwrap_err();
+        assert_eq!(stale.code(), ReasonCode::RevisionMismatch);
+        assert_eq!(client.get_active_leases().len(), 1);
+
+        let revoked = client.revoke_lease(&lease.id, None, None).unwrap();
+        assert_eq!((revoked.state, revoked.revision), (LeaseState::Revoked, 2));
+        assert!(client.get_active_leases().is_empty());
+        let timeline = client.agent_timeline("agent", &TimelineQuery::default());
+        assert_eq!(timeline.last().unwrap().kind, ActivityKind::Revoked);
+        assert_eq!(
+            client
+                .revoke_lease(&lease.id, None, None)
+                .unwrap_err()
+                .code(),
+            ReasonCode::LeaseNotFound
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_report_shows_lease_state_and_drift() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("agent", "s1", "FILE", "/a.ts", "MUTATES", 10_000)
+        else {
+            panic!("Expected success");
+        };
+
+        clock.advance(4000);
+        let report = client
+            .heartbeat_lease_report(&lease.id, None, Some(5200))
+            .unwrap();
+        assert!(report.renewed);
+        assert_eq!(report.state, Some(LeaseState::Active));
+        assert_eq!((report.remaining_ttl, report.renewals), (10_000, 1));
+        assert_eq!(report.clock_drift_ms, Some(200));
+        assert!(report.drift_warning.is_none());
+
+        let stale = client.heartbeat_lease_report(&lease.id, Some(0), None);
+        assert_eq!(
+            stale.unwrap_err(),
+            LeaseMutationError::Conflict {
+                current_revision: 1
+            }
+        );
+
+        client
+            .revoke_lease(&lease.id, None, Some("handed over"))
+            .unwrap();
+        let report = client
+            .heartbeat_lease_report(&lease.id, None, Some(2000))
+            .unwrap();
+        assert!(!report.renewed);
+        assert_eq!(report.state, Some(LeaseState::Revoked));
+        assert_eq!(report.remaining_ttl, 0);
+        assert_eq!(report.revoked_reason.as_deref(), Some("handed over"));
+        assert_eq!(report.clock_drift_ms, Some(-3000));
+        assert!(report.drift_warning.unwrap().contains("3000ms behind"));
+
+        let report = client
+            .heartbeat_lease_report("missing", None, None)
+            .unwrap();
+        assert_eq!((report.renewed, report.state), (false, None));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_generation_sees_other_connections() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let path =
+            std::env::temp_dir().join(format!("klock_generation_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let reader = SqliteLeaseStore::open(path).unwrap();
+        let mut writer = SqliteLeaseStore::open(path).unwrap();
+        let before = reader.generation();
+        assert_eq!(reader.generation(), before);
+
+        let res = ResourceRef::new(ResourceType::File, "/test");
+        assert!(matches!(
+            writer.acquire("agent_1", "s1", res.clone(), Predicate::Mutates, 5000, 1000),
+            LeaseResult::Success { .. }
+        ));
+        assert_ne!(reader.generation(), before);
+
+        drop(reader);
+        drop(writer);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_intents_survive_restart() {
+        let path =
+            std::env::temp_dir().join(format!("klock_intent_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let manifest = |agent_id: &str, session_id: &str, path: &str| IntentManifest {
+            session_id: session_id.to_string(),
+            agent_id: agent_id.to_string(),
+            intents: vec![SPOTriple {
+                id: format!("t_{}{}", agent_id, path),
+                subject: agent_id.to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, path),
+                timestamp: 1000,
+                confidence: Confidence::Medium,
+                session_id: session_id.to_string(),
+                renamed_to: None,
+                expires_at: Some(4_000_000_000_000),
+                long_lived: false,
+            }],
+        };
+        let conflicts = |client: &KlockClient, path: &str| {
+            client
+                .summarize_intent(&manifest("other", "s9", path))
+                .conflicts
+        };
+
+        {
+            let mut client = KlockClient::with_sqlite(path).unwrap();
+            client.register_agent("planner", 100);
+            for (session_id, path) in [("s1", "/a.ts"), ("s2", "/b.ts")] {
+                let verdict = client.declare_intent(&manifest("planner", session_id, path));
+                assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+            }
+            assert_eq!(client.withdraw_session_intents("planner", "s2").len(), 1);
+        }
+
+        let mut client = KlockClient::with_sqlite(path).unwrap();
+        assert_eq!(conflicts(&client, "/a.ts"), 1);
+        assert_eq!(conflicts(&client, "/b.ts"), 0);
+
+        let withdrawn = client.withdraw_session_intents("planner", "s1");
+        assert_eq!(withdrawn, manifest("planner", "s1", "/a.ts").intents);
+        drop(client);
+        let client = KlockClient::with_sqlite(path).unwrap();
+        assert_eq!(conflicts(&client, "/a.ts"), 0);
+
+        drop(client);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_persists_intents_one_row_at_a_time() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let path =
+            std::env::temp_dir().join(format!("klock_intent_rows_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let intent = |id: &str, path: &str| SPOTriple {
+            id: id.to_string(),
+            subject: "planner".to_string(),
+            predicate: Predicate::Mutates,
+            object: ResourceRef::new(ResourceType::File, path),
+            timestamp: 1000,
+            confidence: Confidence::High,
+            session_id: "s1".to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        };
+
+        let mut store = SqliteLeaseStore::open(path).unwrap();
+        store.save_intent(0, &intent("t1", "/a.ts")).unwrap();
+        store.save_intent(1, &intent("t2", "/b.ts")).unwrap();
+        // IDs may repeat; the sequence tells the rows apart
+        store.save_intent(5, &intent("t1", "/c.ts")).unwrap();
+        store.delete_intent(0).unwrap();
+        store.delete_intent(3).unwrap();
+        let loaded: Vec<(u64, String)> = store
+            .load_intents()
+            .unwrap()
+            .into_iter()
+            .map(|(sequence, intent)| (sequence, intent.object.path))
+            .collect();
+        assert_eq!(loaded, [(1, "/b.ts".to_string()), (5, "/c.ts".to_string())]);
+
+        // Failed writes reach the caller, and the client's readiness
+        let mut client = KlockClient::with_sqlite(path).unwrap();
+        assert_eq!(client.snapshot().active_intents.len(), 2);
+        rusqlite::Connection::open(path)
+            .unwrap()
+            .execute("DROP TABLE active_intents", [])
+            .unwrap();
+        assert!(store.save_intent(6, &intent("t3", "/d.ts")).is_err());
+        assert!(store.load_intents().is_err());
+        assert!(client.storage_ready().is_ok());
+        let manifest = IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "planner".to_string(),
+            intents: vec![intent("t3", "/d.ts")],
+        };
+        client.register_agent("planner", 100);
+        assert_eq!(
+            client.declare_intent(&manifest).status,
+            KernelVerdictStatus::Granted
+        );
+        let error = client.storage_ready().unwrap_err();
+        assert!(error.contains("Intents not persisted"), "{}", error);
+
+        drop(client);
+        drop(store);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_answers_holders_at_from_history() {
+        let path =
+            std::env::temp_dir().join(format!("klock_history_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let holders = |client: &KlockClient, at: u64| -> Vec<(String, Option<u64>)> {
+            client
+                .holders_at("FILE:/src/a.ts", at)
+                .unwrap()
+                .into_iter()
+                .map(|lease| (lease.agent_id, lease.ended_at))
+                .collect()
+        };
+
+        {
+            let clock = Arc::new(ManualClock::new(1000));
+            let mut client = KlockClient::with_sqlite(path).unwrap();
+            client.set_clock(clock.clone());
+            client.register_agent("a", 100);
+            client.register_agent("b", 200);
+            let LeaseResult::Success { lease } =
+                client.acquire_lease("a", "s1", "FILE", "/src/a.ts", "MUTATES", 60_000)
+            else {
+                panic!("Expected the first acquire to succeed");
+            };
+            clock.advance(1_000);
+            assert!(client.release_lease(&lease.id));
+            let acquired = client.acquire_lease("b", "s2", "FILE", "/src/a.ts", "MUTATES", 60_000);
+            assert!(matches!(acquired, LeaseResult::Success { .. }));
+            // Compaction keeps the history on disk
+            clock.advance(60 * 60 * 1000);
+            client.compact();
+        }
+
+        let client = KlockClient::with_sqlite(path).unwrap();
+        assert_eq!(holders(&client, 1500), [("a".to_string(), Some(2000))]);
+        // Compaction expired the second lease, which ended at its expiry
+        assert_eq!(holders(&client, 2000), [("b".to_string(), Some(62_000))]);
+        assert!(holders(&client, 62_000).is_empty());
+
+        drop(client);
+        let _ = std::fs::remove_file(path);
+    }
+
+    /// A store whose leases change between every two reads, as if other
+    /// writers kept racing the client
+    struct ChurningStore {
+        inner: InMemoryLeaseStore,
+        reads: Cell<u64>,
+    }
+
+    impl LeaseStore for ChurningStore {
+        fn acquire(
+            &mut self,
+            agent_id: &str,
+            session_id: &str,
+            resource: ResourceRef,
+            predicate: Predicate,
+            ttl: u64,
+            now: u64,
+        ) -> LeaseResult {
+            self.inner
+                .acquire(agent_id, session_id, resource, predicate, ttl, now)
+        }
+        fn release(&mut self, lease_id: &str) -> bool {
+            self.inner.release(lease_id)
+        }
+        fn heartbeat(&mut self, lease_id: &str, now: u64) -> bool {
+            self.inner.heartbeat(lease_id, now)
+        }
+        fn get_active_leases(&self) -> Vec<Lease> {
+            self.inner.get_active_leases()
+        }
+        fn get_pending_leases(&self) -> Vec<Lease> {
+            self.inner.get_pending_leases()
+        }
+        fn generation(&self) -> u64 {
+            self.reads.get()
+        }
+        /// Each read sees a lease taken (elsewhere) since the last one
+        fn read_snapshot(&self, _resources: &[ResourceRef]) -> LeaseSnapshot {
+            let generation = self.reads.get();
+            self.reads.set(generation + 1);
+            LeaseSnapshot {
+                generation,
+                leases: vec![Lease::new(
+                    format!("lease_racer_{}", generation),
+                    "racer".to_string(),
+                    "s1".to_string(),
+                    ResourceRef::new(ResourceType::File, "/elsewhere"),
+                    Predicate::Mutates,
+                    5000,
+                    1000,
+                )],
+            }
+        }
+        fn get_lease(&self, lease_id: &str) -> Option<Lease> {
+            self.inner.get_lease(lease_id)
+        }
+        fn evict_expired(&mut self, now: u64) -> usize {
+            self.inner.evict_expired(now)
+        }
+        fn pre_acquire(
+            &mut self,
+            agent_id: &str,
+            session_id: &str,
+            resources: &[(ResourceRef, Predicate)],
+            ttl: u64,
+            activate_by: u64,
+            now: u64,
+        ) -> Vec<Lease> {
+            self.inner
+                .pre_acquire(agent_id, session_id, resources, ttl, activate_by, now)
+        }
+        fn activate(&mut self, lease_id: &str, now: u64) -> LeaseResult {
+            self.inner.activate(lease_id, now)
+        }
+        fn compact(&mut self, before: u64) -> usize {
+            self.inner.compact(before)
+        }
+        fn usage(&self) -> StoreUsage {
+            self.inner.usage()
+        }
+    }
+
+    impl LeaseStoreExt for ChurningStore {
+        fn registry(&self) -> Arc<dyn AgentRegistry> {
+            self.inner.registry()
+        }
+        fn set_registry(&mut self, registry: Arc<dyn AgentRegistry>) {
+            self.inner.set_registry(registry);
+        }
+        fn set_scheduler(&mut self, scheduler: Arc<dyn Scheduler>) {
+            self.inner.set_scheduler(scheduler);
+        }
+        fn set_fair_share(&mut self, policy: FairSharePolicy) {
+            self.inner.set_fair_share(policy);
+        }
+        fn set_conflict_engine(&mut self, engine: Arc<ConflictEngine>) {
+            self.inner.set_conflict_engine(engine);
+        }
+    }
+
+    #[test]
+    fn test_client_does_not_grant_on_changing_state() {
+        let mut client = KlockClient::with_store(Box::new(ChurningStore {
+            inner: InMemoryLeaseStore::new(),
+            reads: Cell::new(0),
+        }));
+        client.register_agent("agent_1", 100);
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "agent_1".to_string(),
+            intents: vec![SPOTriple {
+                id: "t1".to_string(),
+                subject: "agent_1".to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, "/test"),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Wait);
+        assert_eq!(verdict.code, Some(ReasonCode::StateChanged));
+        assert_eq!(client.memory_report().intents.count, 0);
+    }
 }
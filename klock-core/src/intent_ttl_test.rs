@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::{Clock, ManualClock};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::sync::Arc;
+
+    fn manifest(agent_id: &str, path: &str) -> IntentManifest {
+        IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: agent_id.to_string(),
+            intents: vec![SPOTriple {
+                id: format!("t_{}_{}", agent_id, path),
+                subject: agent_id.to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, path),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        }
+    }
+
+    fn client() -> (KlockClient, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.set_intent_ttl(Some(5_000));
+        client.register_agent("crashed", 100);
+        client.register_agent("other", 200);
+        (client, clock)
+    }
+
+    #[test]
+    fn test_expired_intents_block_nobody() {
+        let (mut client, clock) = client();
+        let verdict = client.declare_intent(&manifest("crashed", "/src/a.ts"));
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        let conflicts = |client: &KlockClient| {
+            client
+                .summarize_intent(&manifest("other", "/src/a.ts"))
+                .conflicts
+        };
+        assert_eq!(conflicts(&client), 1);
+
+        // The agent never comes back
+        clock.advance(5_001);
+        assert_eq!(conflicts(&client), 0);
+        let verdict = client.declare_intent(&manifest("other", "/src/a.ts"));
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        assert!(verdict.conflicts.is_empty());
+        // Declaring evicted the expired intent
+        assert_eq!(client.memory_report().intents.count, 1);
+
+        clock.advance(5_001);
+        assert_eq!(client.evict_expired_intents(clock.now_ms()), 1);
+        assert_eq!(client.memory_report().intents.count, 0);
+    }
+
+    #[test]
+    fn test_heartbeats_renew_a_sessions_intents() {
+        let (mut client, clock) = client();
+        let acquisition = client.acquire_manifest(&manifest("crashed", "/src/a.ts"), 60_000);
+        assert!(acquisition.acquired());
+        let lease_id = acquisition.leases[0].id.clone();
+
+        clock.advance(4_000);
+        assert!(client.heartbeat_lease(&lease_id, clock.now_ms()));
+        clock.advance(4_000);
+        assert_eq!(client.evict_expired_intents(clock.now_ms()), 0);
+
+        // Without heartbeats the intent lapses, though its lease lives on
+        clock.advance(5_001);
+        assert_eq!(client.evict_expired_intents(clock.now_ms()), 1);
+        assert_eq!(client.get_active_leases().len(), 1);
+
+        // Intents without a TTL never expire
+        client.set_intent_ttl(None);
+        let verdict = client.declare_intent(&manifest("other", "/src/b.ts"));
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        clock.advance(1_000_000);
+        assert_eq!(client.evict_expired_intents(clock.now_ms()), 0);
+    }
+}
@@ -4,24 +4,183 @@
 //! Provides O(1) conflict detection, Wait-Die scheduling, and
 //! intent-based lease management for multi-agent systems.
 
+pub mod activity;
+pub mod aging;
+pub mod alias;
+pub mod audit;
+pub mod backoff;
+pub mod barrier;
+pub mod capacity;
+pub mod ceiling;
 pub mod client;
+pub mod clock;
+pub mod codes;
+pub mod compaction;
 pub mod conflict;
+pub mod conformance;
+pub mod containment;
+pub mod cosign;
+pub mod deadlock;
+pub mod debounce;
+pub mod dependency;
+pub mod equivalence;
+pub mod event;
+pub mod expiry;
+pub mod fair_share;
+pub mod fallback;
+#[cfg(feature = "testing")]
+pub mod fault;
+pub mod feed;
+pub mod firehose;
+pub mod health;
+pub mod hybrid;
+pub mod implication;
+pub mod index;
 pub mod infrastructure;
 #[path = "infrastructure_in_memory.rs"]
 pub mod infrastructure_in_memory;
+#[path = "infrastructure_mirror.rs"]
+pub mod infrastructure_mirror;
 #[cfg(feature = "sqlite")]
 #[path = "infrastructure_sqlite.rs"]
 pub mod infrastructure_sqlite;
+pub mod maintenance;
+pub mod namespace;
+pub mod policy_hook;
+pub mod policy_registry;
+pub mod preemption;
+pub mod quarantine;
+pub mod refinement;
+pub mod registry;
+pub mod release;
+pub mod remediation;
 pub mod scheduler;
+pub mod semaphore;
+pub mod session;
+pub mod skew;
 pub mod state;
+pub mod summary;
+pub mod template;
 pub mod types;
+pub mod usage;
+pub mod wait_queue;
 
+#[cfg(test)]
+mod activity_test;
+#[cfg(test)]
+mod admission_test;
+#[cfg(test)]
+mod aging_test;
+#[cfg(test)]
+mod alias_test;
+#[cfg(test)]
+mod audit_test;
+#[cfg(test)]
+mod backoff_test;
+#[cfg(test)]
+mod barrier_test;
+#[cfg(test)]
+mod capacity_test;
+#[cfg(test)]
+mod ceiling_test;
+#[cfg(test)]
+mod clock_test;
+#[cfg(test)]
+mod codes_test;
+#[cfg(test)]
+mod compaction_test;
 #[cfg(test)]
 mod conflict_test;
 #[cfg(test)]
+mod conformance_test;
+#[cfg(test)]
+mod containment_test;
+#[cfg(test)]
+mod cosign_test;
+#[cfg(test)]
+mod deadlock_test;
+#[cfg(test)]
+mod debounce_test;
+#[cfg(test)]
+mod dependency_test;
+#[cfg(test)]
+mod envelope_test;
+#[cfg(test)]
+mod equivalence_test;
+#[cfg(test)]
+mod event_test;
+#[cfg(test)]
+mod expiry_test;
+#[cfg(test)]
+mod fair_share_test;
+#[cfg(test)]
+mod fallback_test;
+#[cfg(all(test, feature = "testing"))]
+mod fault_test;
+#[cfg(test)]
+mod feed_test;
+#[cfg(test)]
+mod firehose_test;
+#[cfg(test)]
+mod health_test;
+#[cfg(test)]
+mod holders_test;
+#[cfg(test)]
+mod hybrid_test;
+#[cfg(test)]
+mod idle_agent_test;
+#[cfg(test)]
+mod implication_test;
+#[cfg(test)]
+mod index_test;
+#[cfg(test)]
 #[path = "infrastructure_test.rs"]
 mod infrastructure_test;
 #[cfg(test)]
+mod intent_ttl_test;
+#[cfg(test)]
+mod maintenance_test;
+#[cfg(test)]
+mod mirror_test;
+#[cfg(test)]
+mod namespace_test;
+#[cfg(test)]
+mod normalize_test;
+#[cfg(test)]
+mod policy_hook_test;
+#[cfg(test)]
+mod policy_registry_test;
+#[cfg(test)]
+mod preemption_test;
+#[cfg(test)]
+mod quarantine_test;
+#[cfg(test)]
+mod refinement_test;
+#[cfg(test)]
+mod registry_test;
+#[cfg(test)]
+mod release_test;
+#[cfg(test)]
+mod remediation_test;
+#[cfg(test)]
 mod scheduler_test;
 #[cfg(test)]
+mod semaphore_test;
+#[cfg(test)]
+mod session_test;
+#[cfg(test)]
+mod skew_test;
+#[cfg(test)]
+mod snapshot_test;
+#[cfg(test)]
 mod state_test;
+#[cfg(test)]
+mod summary_test;
+#[cfg(test)]
+mod template_test;
+#[cfg(test)]
+mod usage_test;
+#[cfg(test)]
+mod wait_queue_test;
+#[cfg(test)]
+mod withdraw_test;
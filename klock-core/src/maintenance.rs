@@ -0,0 +1,186 @@
+//! Time-boxed maintenance windows.
+//!
+//! Operators schedule exclusive access ahead of time: "Fridays 02:00–03:00
+//! UTC, only `migrator` may touch `DATABASE_TABLE:prod.*`". While a window is
+//! open, every acquire, activation and intent that overlaps its resource is
+//! refused with `MaintenanceWindow` unless the requester is one of the
+//! window's allowed agents. Leases already held are left alone, so windows
+//! are best scheduled a lease TTL after the work they displace has stopped.
+//!
+//! A window opens at `start` and closes at `end` (ms since the epoch);
+//! windows with `repeat_every_ms` reopen every period after that, e.g.
+//! weekly with `604800000`.
+
+use crate::types::ResourceRef;
+use serde::{Deserialize, Serialize};
+
+/// A scheduled period of exclusive access to a resource
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    pub name: String,
+    /// The reserved resource; directories and glob patterns reserve
+    /// everything they cover
+    pub resource: ResourceRef,
+    /// Agents that may still acquire and declare on the resource
+    #[serde(default)]
+    pub allowed_agents: Vec<String>,
+    /// First opening, in ms since the epoch
+    pub start: u64,
+    /// First closing, in ms since the epoch
+    pub end: u64,
+    /// Reopen every this many ms after `start`; `None` for a one-off window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_every_ms: Option<u64>,
+}
+
+/// One opening of a window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScheduledWindow {
+    pub name: String,
+    pub resource: ResourceRef,
+    pub allowed_agents: Vec<String>,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl MaintenanceWindow {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("Maintenance window name is required".to_string());
+        }
+        if self.resource.path.is_empty() {
+            return Err(format!(
+                "Maintenance window '{}' has an empty resource path",
+                self.name
+            ));
+        }
+        if self.end <= self.start {
+            return Err(format!(
+                "Maintenance window '{}' must end after it starts",
+                self.name
+            ));
+        }
+        if let Some(period) = self.repeat_every_ms
+            && period < self.end - self.start
+        {
+            return Err(format!(
+                "Maintenance window '{}' repeats every {} ms but lasts {} ms",
+                self.name,
+                period,
+                self.end - self.start
+            ));
+        }
+        Ok(())
+    }
+
+    /// The opening in progress at `now`, or else the next one, as
+    /// `(start, end)`; `None` once a one-off window has closed
+    pub fn occurrence(&self, now: u64) -> Option<(u64, u64)> {
+        let duration = self.end - self.start;
+        match self.repeat_every_ms {
+            _ if now < self.end => Some((self.start, self.end)),
+            None => None,
+            Some(period) => {
+                // The latest opening at or before now, if still open
+                let start = self.start + (now - self.start) / period * period;
+                if now < start + duration {
+                    Some((start, start + duration))
+                } else {
+                    Some((start + period, start + period + duration))
+                }
+            }
+        }
+    }
+
+    /// When the window open at `now` closes, if it is open
+    pub fn open_until(&self, now: u64) -> Option<u64> {
+        self.occurrence(now)
+            .filter(|(start, _)| *start <= now)
+            .map(|(_, end)| end)
+    }
+
+    pub fn allows(&self, agent_id: &str) -> bool {
+        self.allowed_agents.iter().any(|a| a == agent_id)
+    }
+}
+
+/// The maintenance windows known to a client
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceSchedule {
+    windows: Vec<MaintenanceWindow>,
+}
+
+impl MaintenanceSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A schedule of `windows`, rejecting invalid windows and duplicate names
+    pub fn with_windows(windows: Vec<MaintenanceWindow>) -> Result<Self, String> {
+        let mut schedule = Self::new();
+        for window in windows {
+            schedule.add(window)?;
+        }
+        Ok(schedule)
+    }
+
+    pub fn add(&mut self, window: MaintenanceWindow) -> Result<(), String> {
+        window.validate()?;
+        if self.windows.iter().any(|w| w.name == window.name) {
+            return Err(format!(
+                "Maintenance window '{}' is defined twice",
+                window.name
+            ));
+        }
+        self.windows.push(window);
+        Ok(())
+    }
+
+    pub fn windows(&self) -> &[MaintenanceWindow] {
+        &self.windows
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// The open window that keeps `agent_id` off `resource` at `now`, and
+    /// when it closes
+    pub fn blocking(
+        &self,
+        agent_id: &str,
+        resource: &ResourceRef,
+        now: u64,
+    ) -> Option<(&MaintenanceWindow, u64)> {
+        self.windows.iter().find_map(|window| {
+            let end = window.open_until(now)?;
+            (!window.allows(agent_id) && window.resource.overlaps(resource))
+                .then_some((window, end))
+        })
+    }
+
+    /// The current or next opening of every window that has not closed for
+    /// good, soonest first
+    pub fn upcoming(&self, now: u64) -> Vec<ScheduledWindow> {
+        let mut upcoming: Vec<ScheduledWindow> = self
+            .windows
+            .iter()
+            .filter_map(|window| {
+                let (start, end) = window.occurrence(now)?;
+                Some(ScheduledWindow {
+                    name: window.name.clone(),
+                    resource: window.resource.clone(),
+                    allowed_agents: window.allowed_agents.clone(),
+                    start,
+                    end,
+                })
+            })
+            .collect();
+        upcoming.sort_by(|a, b| (a.start, &a.name).cmp(&(b.start, &b.name)));
+        upcoming
+    }
+}
@@ -0,0 +1,181 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::codes::ReasonCode;
+    use crate::maintenance::{MaintenanceSchedule, MaintenanceWindow};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use std::sync::Arc;
+
+    const WEEK: u64 = 604_800_000;
+
+    fn table(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::DatabaseTable, path)
+    }
+
+    fn window(name: &str, start: u64, end: u64, repeat_every_ms: Option<u64>) -> MaintenanceWindow {
+        MaintenanceWindow {
+            name: name.to_string(),
+            resource: table("prod.*"),
+            allowed_agents: vec!["migrator".to_string()],
+            start,
+            end,
+            repeat_every_ms,
+        }
+    }
+
+    #[test]
+    fn test_recurring_windows_open_and_close() {
+        let schedule =
+            MaintenanceSchedule::with_windows(vec![window("weekly", 1000, 2000, Some(WEEK))])
+                .unwrap();
+
+        assert!(
+            schedule
+                .blocking("bot", &table("prod.users"), 999)
+                .is_none()
+        );
+        let (open, end) = schedule
+            .blocking("bot", &table("prod.users"), 1500)
+            .unwrap();
+        assert_eq!((open.name.as_str(), end), ("weekly", 2000));
+        // Allowed agents and other resources pass
+        assert!(
+            schedule
+                .blocking("migrator", &table("prod.users"), 1500)
+                .is_none()
+        );
+        assert!(
+            schedule
+                .blocking("bot", &table("staging.users"), 1500)
+                .is_none()
+        );
+        // Closed until the next week, then open again
+        assert!(
+            schedule
+                .blocking("bot", &table("prod.users"), 2000)
+                .is_none()
+        );
+        assert_eq!(
+            schedule
+                .blocking("bot", &table("prod.users"), WEEK + 1000)
+                .map(|(_, end)| end),
+            Some(WEEK + 2000)
+        );
+
+        let next = schedule.upcoming(2500);
+        assert_eq!((next[0].start, next[0].end), (WEEK + 1000, WEEK + 2000));
+    }
+
+    #[test]
+    fn test_schedule_validates_windows() {
+        assert!(MaintenanceSchedule::with_windows(vec![window("w", 2000, 1000, None)]).is_err());
+        assert!(MaintenanceSchedule::with_windows(vec![window("w", 0, 2000, Some(1000))]).is_err());
+        assert!(
+            MaintenanceSchedule::with_windows(vec![
+                window("w", 0, 10, None),
+                window("w", 20, 30, None),
+            ])
+            .is_err()
+        );
+
+        // Closed one-off windows drop out of the upcoming list
+        let schedule = MaintenanceSchedule::with_windows(vec![
+            window("later", 5000, 6000, None),
+            window("done", 0, 10, None),
+            window("soon", 3000, 4000, None),
+        ])
+        .unwrap();
+        let names: Vec<String> = schedule.upcoming(100).into_iter().map(|w| w.name).collect();
+        assert_eq!(names, ["soon", "later"]);
+    }
+
+    #[test]
+    fn test_client_enforces_windows_on_acquire_and_declare() {
+        let clock = Arc::new(ManualClock::new(500));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client
+            .set_maintenance_windows(vec![window("migration", 1000, 2000, None)])
+            .unwrap();
+        let reservation = client
+            .pre_acquire(
+                "bot",
+                "s",
+                &[(table("prod.orders"), Predicate::Mutates)],
+                60_000,
+                5000,
+            )
+            .unwrap()
+            .remove(0);
+
+        clock.set(1200);
+        let LeaseResult::Failure {
+            reason, wait_time, ..
+        } = client.acquire_lease(
+            "bot",
+            "s",
+            "DATABASE_TABLE",
+            "prod.users",
+            "MUTATES",
+            60_000,
+        )
+        else {
+            panic!("Expected failure");
+        };
+        assert_eq!(reason, LeaseFailureReason::MaintenanceWindow);
+        assert_eq!(reason.code(), ReasonCode::MaintenanceWindow);
+        assert_eq!(wait_time, Some(800));
+        assert!(matches!(
+            client.activate_lease(&reservation.id),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::MaintenanceWindow,
+                ..
+            }
+        ));
+
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s".to_string(),
+            agent_id: "bot".to_string(),
+            intents: vec![SPOTriple {
+                id: "t1".to_string(),
+                subject: "bot".to_string(),
+                predicate: Predicate::Consumes,
+                object: table("prod.users"),
+                timestamp: 1200,
+                confidence: Confidence::High,
+                session_id: "s".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(verdict.code, Some(ReasonCode::MaintenanceWindow));
+        assert_eq!(verdict.retry_after_ms, Some(800));
+        assert!(verdict.reason.unwrap().contains("'migration'"));
+
+        // The allowed agent works through the window
+        assert!(matches!(
+            client.acquire_lease(
+                "migrator",
+                "m",
+                "DATABASE_TABLE",
+                "prod.users",
+                "MUTATES",
+                60_000
+            ),
+            LeaseResult::Success { .. }
+        ));
+
+        clock.set(2000);
+        assert!(client.upcoming_maintenance().is_empty());
+        assert!(matches!(
+            client.activate_lease(&reservation.id),
+            LeaseResult::Success { .. }
+        ));
+    }
+}
@@ -0,0 +1,159 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::event::KlockEvent;
+    use crate::infrastructure::LeaseStore;
+    use crate::infrastructure_in_memory::InMemoryLeaseStore;
+    use crate::infrastructure_mirror::{MirroredStore, copy_state, parity, replay};
+    use crate::types::{
+        EventEnvelope, LeaseResult, LeaseState, Predicate, ResourceRef, ResourceType,
+    };
+    use std::sync::Arc;
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn acquire(store: &mut dyn LeaseStore, agent: &str, path: &str, now: u64) -> String {
+        match store.acquire(agent, "s1", file(path), Predicate::Mutates, 5000, now) {
+            LeaseResult::Success { lease } => lease.id,
+            other => panic!("Expected lease, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_copy_state_reaches_parity() {
+        let mut source = InMemoryLeaseStore::new();
+        source.register_agent_priority("a".to_string(), 100);
+        let held = acquire(&mut source, "a", "/a.ts", 1000);
+        let released = acquire(&mut source, "a", "/b.ts", 1001);
+        assert!(source.release(&released));
+        source.pre_acquire(
+            "a",
+            "s1",
+            &[(file("/c.ts"), Predicate::Mutates)],
+            5000,
+            3000,
+            1000,
+        );
+
+        let mut target = InMemoryLeaseStore::new();
+        assert_eq!(parity(&source, &target).len(), 3);
+
+        // Live leases only: the released one stays behind
+        assert_eq!(copy_state(&source, &mut target), Ok(2));
+        assert!(parity(&source, &target).is_empty());
+        assert!(target.get_lease(&held).is_some());
+        assert!(target.get_lease(&released).is_none());
+
+        assert!(target.heartbeat(&held, 2000));
+        assert_eq!(parity(&source, &target).len(), 1);
+    }
+
+    #[test]
+    fn test_client_mirrors_every_change() {
+        let mut client = KlockClient::new();
+        client.register_agent("a", 100);
+        let LeaseResult::Success { lease: before } =
+            client.acquire_lease("a", "s1", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected lease");
+        };
+        assert!(client.mirror_report().is_none());
+
+        client.mirror_to(Box::new(InMemoryLeaseStore::new()));
+        client.register_agent("b", 200);
+        let LeaseResult::Success { lease: after } =
+            client.acquire_lease("b", "s2", "FILE", "/b.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected lease");
+        };
+        assert!(client.heartbeat_lease(&before.id, before.last_heartbeat + 1));
+        assert!(client.release_lease(&after.id));
+
+        let report = client.mirror_report().unwrap();
+        assert_eq!(report.events_mirrored, 3);
+        assert_eq!(report.divergences, 0);
+        assert!(report.differences.is_empty(), "{:?}", report.differences);
+        assert_eq!(client.get_active_leases().len(), 1);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_divergence_is_reported_not_fatal() {
+        use crate::infrastructure_sqlite::SqliteLeaseStore;
+
+        let path =
+            std::env::temp_dir().join(format!("klock_mirror_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut primary = InMemoryLeaseStore::new();
+        let id = acquire(&mut primary, "a", "/a.ts", 1000);
+        let mut mirror = MirroredStore::new(
+            Box::new(primary),
+            Box::new(SqliteLeaseStore::open(path).unwrap()),
+        );
+        assert!(mirror.report().differences.is_empty());
+
+        // Someone else releases the lease behind the mirror's back
+        assert!(SqliteLeaseStore::open(path).unwrap().release(&id));
+
+        // The primary still serves the heartbeat
+        assert!(mirror.heartbeat(&id, 2000));
+        let report = mirror.report();
+        assert_eq!(report.divergences, 1);
+        assert!(report.last_divergence.unwrap().contains("LeaseRenewed"));
+        assert_eq!(
+            report.differences,
+            vec![format!("lease {}: missing from secondary", id)]
+        );
+
+        drop(mirror);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_replaying_the_firehose_replicates_leases() {
+        let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        client.set_firehose_capacity(100);
+        client.register_agent("a", 100);
+        let mut lease_ids = Vec::new();
+        for path in ["/a.ts", "/b.ts"] {
+            match client.acquire_lease("a", "s1", "FILE", path, "MUTATES", 60_000) {
+                LeaseResult::Success { lease } => lease_ids.push(lease.id),
+                other => panic!("Expected lease, got {:?}", other),
+            }
+        }
+        assert!(client.heartbeat_lease(&lease_ids[0], 2000));
+        assert!(client.release_lease(&lease_ids[1]));
+
+        // Entries cross the wire as JSON
+        let mut replica = InMemoryLeaseStore::new();
+        for entry in client.firehose(0, 100).unwrap().entries {
+            let json = serde_json::to_string(&entry.event).unwrap();
+            let envelope: EventEnvelope = serde_json::from_str(&json).unwrap();
+            assert!(replay(&mut replica, &envelope).unwrap());
+        }
+        let held = replica.get_lease(&lease_ids[0]).unwrap();
+        assert_eq!(held.state, LeaseState::Active);
+        assert_eq!(held.renewals, 1);
+        let released = replica.get_lease(&lease_ids[1]).unwrap();
+        assert_eq!(released.state, LeaseState::Released);
+
+        let mut newer = EventEnvelope::lease_change(
+            &KlockEvent::LeaseReleased {
+                lease_id: lease_ids[0].clone(),
+            },
+            None,
+            3000,
+        );
+        newer.schema_version += 1;
+        assert!(replay(&mut replica, &newer).is_err());
+        assert_eq!(
+            replica.get_lease(&lease_ids[0]).unwrap().state,
+            LeaseState::Active
+        );
+    }
+}
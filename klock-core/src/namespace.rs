@@ -0,0 +1,313 @@
+//! Namespaces: scopes for throwaway agents.
+//!
+//! CI pipelines start agents for one run and rarely clean up after them:
+//! their leases run out eventually, but their intents, priorities, health
+//! records and timelines stay. A run declares a namespace, and every agent
+//! whose ID starts with `<name>/` belongs to it. Once a namespace's TTL
+//! runs out, or it is deleted, the client reaps it: it releases its agents'
+//! leases, cancels their queued requests, withdraws their intents, and
+//! forgets their priorities, health, retry and aging records, default
+//! sessions and timelines. Each session that held something there gets a
+//! `namespace_reaped` event, and the firehose records the reaping, so a
+//! runner that outlived its namespace can tell.
+//!
+//! A conflict engine built `with_isolated_namespaces` also keeps their
+//! resources apart: agents in different namespaces (or in one and outside
+//! every namespace) never conflict, except on the resources a
+//! `NamespaceBridges` policy declares shared, such as a schema two projects
+//! both migrate.
+
+use crate::types::ResourceRef;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The namespace an agent belongs to: the part of its ID before the first
+/// `/`, if there is one
+pub fn namespace_of(agent_id: &str) -> Option<&str> {
+    agent_id
+        .split_once('/')
+        .map(|(namespace, _)| namespace)
+        .filter(|namespace| !namespace.is_empty())
+}
+
+/// A declared namespace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Namespace {
+    pub name: String,
+    pub created_at: u64,
+    /// When the namespace is reaped; `None` keeps it until deleted
+    pub expires_at: Option<u64>,
+}
+
+impl Namespace {
+    /// Whether `agent_id` belongs to the namespace
+    pub fn contains(&self, agent_id: &str) -> bool {
+        namespace_of(agent_id) == Some(self.name.as_str())
+    }
+}
+
+/// What reaping a namespace removed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceReaping {
+    pub namespace: String,
+    /// Whether its TTL ran out, rather than it being deleted
+    pub expired: bool,
+    /// The agents of the namespace the client knew about
+    pub agents: Vec<String>,
+    pub leases_released: Vec<String>,
+    pub waiters_cancelled: usize,
+    pub intents_removed: usize,
+}
+
+/// Declared namespaces by name
+#[derive(Debug, Default)]
+pub struct NamespaceRegistry {
+    namespaces: HashMap<String, Namespace>,
+}
+
+impl NamespaceRegistry {
+    /// Declare a namespace expiring `expires_in` ms after `now`, or never.
+    /// Declaring an existing namespace again only resets its expiry, so a
+    /// long run can keep its namespace alive.
+    pub fn declare(
+        &mut self,
+        name: &str,
+        expires_in: Option<u64>,
+        now: u64,
+    ) -> Result<Namespace, String> {
+        if name.is_empty() || name.contains('/') {
+            return Err(format!(
+                "Invalid namespace '{}': use a non-empty name without '/'",
+                name
+            ));
+        }
+        if expires_in == Some(0) {
+            return Err("expires_in must be positive".to_string());
+        }
+        let namespace = self
+            .namespaces
+            .entry(name.to_string())
+            .or_insert_with(|| Namespace {
+                name: name.to_string(),
+                created_at: now,
+                expires_at: None,
+            });
+        namespace.expires_at = expires_in.map(|ttl| now.saturating_add(ttl));
+        Ok(namespace.clone())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Namespace> {
+        self.namespaces.get(name)
+    }
+
+    /// Every namespace, by name
+    pub fn list(&self) -> Vec<Namespace> {
+        let mut namespaces: Vec<Namespace> = self.namespaces.values().cloned().collect();
+        namespaces.sort_by(|a, b| a.name.cmp(&b.name));
+        namespaces
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Namespace> {
+        self.namespaces.remove(name)
+    }
+
+    /// Remove and return the namespaces expired at `now`, by name
+    pub fn take_expired(&mut self, now: u64) -> Vec<Namespace> {
+        let mut expired: Vec<Namespace> = self
+            .namespaces
+            .values()
+            .filter(|namespace| namespace.expires_at.is_some_and(|at| at <= now))
+            .cloned()
+            .collect();
+        expired.sort_by(|a, b| a.name.cmp(&b.name));
+        for namespace in &expired {
+            self.namespaces.remove(&namespace.name);
+        }
+        expired
+    }
+
+    pub fn len(&self) -> usize {
+        self.namespaces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.namespaces.is_empty()
+    }
+}
+
+/// One side of a bridge: a resource as the agents of `namespace` name it,
+/// or agents outside every namespace for `None`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeEnd {
+    pub namespace: Option<String>,
+    pub resource: ResourceRef,
+}
+
+impl BridgeEnd {
+    pub fn new(namespace: Option<&str>, resource: ResourceRef) -> Self {
+        Self {
+            namespace: namespace.map(str::to_string),
+            resource,
+        }
+    }
+
+    /// Parse `<namespace>/<resource key>`, or a bare resource key for
+    /// agents outside every namespace
+    pub fn parse(end: &str) -> Result<Self, String> {
+        let (namespace, key) = match end.split_once('/') {
+            Some((namespace, key)) if !namespace.contains(':') => (Some(namespace), key),
+            _ => (None, end),
+        };
+        let resource = ResourceRef::from_key(key).ok_or_else(|| {
+            format!(
+                "Invalid bridge end '{}': expected [namespace/]TYPE:path, e.g. billing/DATABASE_TABLE:shared.accounts",
+                end
+            )
+        })?;
+        Ok(Self::new(namespace, resource))
+    }
+
+    pub fn key(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}/{}", namespace, self.resource.key()),
+            None => self.resource.key(),
+        }
+    }
+}
+
+/// Resources shared across isolated namespaces.
+///
+/// Each bridge maps a resource of one namespace to one of another, in both
+/// directions: an operation on anything overlapping one end conflicts with
+/// the other namespace's operations as if it were on the other end.
+/// Serialized as a list of bridges:
+///
+/// ```json
+/// [{ "from": "billing/DATABASE_TABLE:shared.accounts", "to": "crm/DATABASE_TABLE:shared.accounts" }]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<RawBridge>", into = "Vec<RawBridge>")]
+pub struct NamespaceBridges {
+    bridges: Vec<(BridgeEnd, BridgeEnd)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawBridge {
+    from: String,
+    to: String,
+}
+
+impl NamespaceBridges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bridge `from` and `to`, which must lie in different namespaces
+    pub fn insert(&mut self, from: BridgeEnd, to: BridgeEnd) -> Result<(), String> {
+        for namespace in [&from.namespace, &to.namespace].into_iter().flatten() {
+            if namespace.is_empty() || namespace.contains('/') {
+                return Err(format!(
+                    "Invalid namespace '{}': use a non-empty name without '/'",
+                    namespace
+                ));
+            }
+        }
+        if from.namespace == to.namespace {
+            return Err(format!(
+                "Cannot bridge {} to {}: both are in one namespace",
+                from.key(),
+                to.key()
+            ));
+        }
+        let known = self
+            .bridges
+            .iter()
+            .any(|(a, b)| (a == &from && b == &to) || (a == &to && b == &from));
+        if !known {
+            self.bridges.push((from, to));
+        }
+        Ok(())
+    }
+
+    /// Builder form of `insert`.
+    pub fn with(mut self, from: BridgeEnd, to: BridgeEnd) -> Result<Self, String> {
+        self.insert(from, to)?;
+        Ok(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bridges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bridges.is_empty()
+    }
+
+    /// The resources of namespace `to` bridged to `resource` of namespace
+    /// `from`
+    pub fn bridged(
+        &self,
+        from: Option<&str>,
+        resource: &ResourceRef,
+        to: Option<&str>,
+    ) -> Vec<ResourceRef> {
+        let mut bridged: Vec<ResourceRef> = Vec::new();
+        for (a, b) in &self.bridges {
+            for (side, other) in [(a, b), (b, a)] {
+                if side.namespace.as_deref() == from
+                    && other.namespace.as_deref() == to
+                    && side.resource.overlaps(resource)
+                    && !bridged.contains(&other.resource)
+                {
+                    bridged.push(other.resource.clone());
+                }
+            }
+        }
+        bridged
+    }
+
+    /// The resources bridged to `resource` from any namespace: what an
+    /// index lookup for it must also cover
+    pub fn counterparts(&self, resource: &ResourceRef) -> Vec<ResourceRef> {
+        let mut counterparts: Vec<ResourceRef> = Vec::new();
+        for (a, b) in &self.bridges {
+            for (side, other) in [(a, b), (b, a)] {
+                if side.resource.overlaps(resource)
+                    && &other.resource != resource
+                    && !counterparts.contains(&other.resource)
+                {
+                    counterparts.push(other.resource.clone());
+                }
+            }
+        }
+        counterparts
+    }
+}
+
+impl TryFrom<Vec<RawBridge>> for NamespaceBridges {
+    type Error = String;
+
+    fn try_from(raw: Vec<RawBridge>) -> Result<Self, Self::Error> {
+        let mut bridges = Self::new();
+        for bridge in raw {
+            bridges.insert(
+                BridgeEnd::parse(&bridge.from)?,
+                BridgeEnd::parse(&bridge.to)?,
+            )?;
+        }
+        Ok(bridges)
+    }
+}
+
+impl From<NamespaceBridges> for Vec<RawBridge> {
+    fn from(bridges: NamespaceBridges) -> Self {
+        bridges
+            .bridges
+            .into_iter()
+            .map(|(from, to)| RawBridge {
+                from: from.key(),
+                to: to.key(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,336 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::conflict::ConflictEngine;
+    use crate::feed::AgentEvent;
+    use crate::namespace::{BridgeEnd, NamespaceBridges, NamespaceRegistry, namespace_of};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, EventPayload, LeaseResult, LeaseState, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use std::sync::Arc;
+
+    fn intent(agent_id: &str, path: &str) -> IntentManifest {
+        IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: agent_id.to_string(),
+            intents: vec![SPOTriple {
+                id: format!("{}:{}", agent_id, path),
+                subject: agent_id.to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, path),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_expired_namespace_is_reaped_on_compaction() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.set_firehose_capacity(100);
+        client.declare_namespace("ci-42", Some(5_000)).unwrap();
+        client.register_agent("ci-42/builder", 100);
+        client.register_agent("ci-42/tester", 200);
+        client.register_agent("human", 300);
+
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("ci-42/builder", "s1", "FILE", "/a.ts", "MUTATES", 600_000)
+        else {
+            panic!("Expected success");
+        };
+        clock.advance(1);
+        client.declare_intent(&intent("ci-42/tester", "/b.ts"));
+        clock.advance(1);
+        client.acquire_lease("human", "s9", "FILE", "/c.ts", "MUTATES", 600_000);
+
+        // Not yet expired
+        clock.advance(4_000);
+        assert_eq!(client.compact().namespaces_reaped, 0);
+        assert!(client.namespace("ci-42").is_some());
+
+        clock.advance(1_000);
+        assert_eq!(client.compact().namespaces_reaped, 1);
+        assert!(client.namespaces().is_empty());
+        let active = client.get_active_leases();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].agent_id, "human");
+        assert!(client.agent_priority("ci-42/builder").is_none());
+        assert!(client.agent_priority("ci-42/tester").is_none());
+        assert!(client.agent_priority("human").is_some());
+        // The intent on /b.ts is gone with its agent
+        assert!(matches!(
+            client.acquire_lease("human", "s9", "FILE", "/b.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+
+        let reaped = |client: &mut KlockClient, agent_id: &str| {
+            client
+                .poll_events(agent_id, "s1")
+                .events
+                .into_iter()
+                .any(|event| {
+                    event
+                        == AgentEvent::NamespaceReaped {
+                            namespace: "ci-42".to_string(),
+                            expired: true,
+                            at: 6002,
+                        }
+                })
+        };
+        assert!(reaped(&mut client, "ci-42/builder"));
+        assert!(reaped(&mut client, "ci-42/tester"));
+        assert!(
+            client
+                .get_lease(&lease.id)
+                .is_none_or(|l| l.state != LeaseState::Active)
+        );
+
+        let page = client.firehose(0, 100).unwrap();
+        let reaping = page
+            .entries
+            .iter()
+            .find(|entry| entry.event.event_type() == "namespace_reaped")
+            .unwrap();
+        assert_eq!(reaping.event.occurred_at, 6002);
+        assert!(matches!(
+            &reaping.event.event,
+            EventPayload::NamespaceReaped { namespace } if namespace == "ci-42"
+        ));
+        assert!(reaping.event.actor.is_none());
+    }
+
+    #[test]
+    fn test_declare_and_delete_namespaces() {
+        assert_eq!(namespace_of("ci-1/agent"), Some("ci-1"));
+        assert_eq!(namespace_of("agent"), None);
+        assert_eq!(namespace_of("/agent"), None);
+
+        let mut registry = NamespaceRegistry::default();
+        for invalid in ["", "a/b"] {
+            assert!(registry.declare(invalid, None, 1000).is_err());
+        }
+        assert!(registry.declare("ci", Some(0), 1000).is_err());
+        registry.declare("ci", Some(100), 1000).unwrap();
+        // Declaring again pushes the expiry back, keeping the creation time
+        let renewed = registry.declare("ci", Some(100), 1050).unwrap();
+        assert_eq!((renewed.created_at, renewed.expires_at), (1000, Some(1150)));
+        assert!(registry.take_expired(1100).is_empty());
+        assert_eq!(registry.take_expired(1150).len(), 1);
+        assert!(registry.is_empty());
+
+        let mut client = KlockClient::new();
+        client.declare_namespace("ci-7", None).unwrap();
+        client.register_agent("ci-7/a", 100);
+        client.register_agent("ci-70/a", 100);
+        client.acquire_lease("ci-7/a", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert!(client.delete_namespace("missing").is_none());
+        let reaping = client.delete_namespace("ci-7").unwrap();
+        assert!(!reaping.expired);
+        assert_eq!(reaping.agents, ["ci-7/a"]);
+        assert_eq!(reaping.leases_released.len(), 1);
+        assert!(client.get_active_leases().is_empty());
+        assert!(client.agent_priority("ci-70/a").is_some());
+        assert!(client.delete_namespace("ci-7").is_none());
+    }
+
+    fn accounts(namespace: &str) -> BridgeEnd {
+        BridgeEnd::new(
+            Some(namespace),
+            ResourceRef::database_table("shared.accounts", None),
+        )
+    }
+
+    #[test]
+    fn test_namespace_bridges_json_roundtrip() {
+        let bridges: NamespaceBridges = serde_json::from_str(
+            r#"[{ "from": "billing/DATABASE_TABLE:shared.accounts", "to": "DATABASE_TABLE:accounts" }]"#,
+        )
+        .unwrap();
+        assert_eq!(bridges.len(), 1);
+        assert_eq!(
+            bridges.bridged(
+                None,
+                &ResourceRef::database_table("accounts", Some("email")),
+                Some("billing")
+            ),
+            vec![ResourceRef::database_table("shared.accounts", None)]
+        );
+        assert!(
+            bridges
+                .bridged(
+                    Some("crm"),
+                    &ResourceRef::database_table("accounts", None),
+                    Some("billing")
+                )
+                .is_empty()
+        );
+        assert_eq!(
+            serde_json::to_string(&bridges).unwrap(),
+            r#"[{"from":"billing/DATABASE_TABLE:shared.accounts","to":"DATABASE_TABLE:accounts"}]"#
+        );
+
+        for invalid in [
+            r#"[{ "from": "billing/accounts", "to": "crm/DATABASE_TABLE:accounts" }]"#,
+            r#"[{ "from": "/DATABASE_TABLE:a", "to": "crm/DATABASE_TABLE:a" }]"#,
+            r#"[{ "from": "crm/DATABASE_TABLE:a", "to": "crm/DATABASE_TABLE:b" }]"#,
+        ] {
+            assert!(serde_json::from_str::<NamespaceBridges>(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn test_isolated_namespaces_only_conflict_through_bridges() {
+        let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        client.set_conflict_engine(
+            ConflictEngine::default().with_isolated_namespaces(
+                NamespaceBridges::new()
+                    .with(accounts("billing"), accounts("crm"))
+                    .unwrap(),
+            ),
+        );
+        client.register_agent("billing/migrator", 100);
+        client.register_agent("crm/migrator", 200);
+        client.register_agent("crm/linter", 300);
+        client.register_agent("human", 400);
+
+        for path in ["/src/app.rs", "/shared.sql"] {
+            assert!(matches!(
+                client.acquire_lease("billing/migrator", "s1", "FILE", path, "MUTATES", 60_000),
+                LeaseResult::Success { .. }
+            ));
+        }
+        assert!(matches!(
+            client.acquire_lease(
+                "billing/migrator",
+                "s1",
+                "DATABASE_TABLE",
+                "shared.accounts",
+                "MUTATES",
+                60_000
+            ),
+            LeaseResult::Success { .. }
+        ));
+
+        // Other namespaces, and agents outside any, have their own files
+        for agent_id in ["crm/migrator", "human"] {
+            assert!(matches!(
+                client.acquire_lease(agent_id, "s2", "FILE", "/src/app.rs", "MUTATES", 60_000),
+                LeaseResult::Success { .. }
+            ));
+        }
+        // The bridged table is shared, down to its columns
+        assert!(matches!(
+            client.acquire_lease(
+                "crm/migrator",
+                "s2",
+                "DATABASE_TABLE",
+                "shared.accounts#email",
+                "MUTATES",
+                60_000
+            ),
+            LeaseResult::Failure { .. }
+        ));
+        assert!(matches!(
+            client.acquire_lease(
+                "human",
+                "s2",
+                "DATABASE_TABLE",
+                "shared.accounts",
+                "MUTATES",
+                60_000
+            ),
+            LeaseResult::Success { .. }
+        ));
+        // Within a namespace everything still conflicts
+        assert!(matches!(
+            client.acquire_lease("crm/linter", "s3", "FILE", "/src/app.rs", "MUTATES", 60_000),
+            LeaseResult::Failure { .. }
+        ));
+
+        assert_eq!(
+            client
+                .declare_intent(&intent("crm/linter", "/shared.sql"))
+                .status,
+            KernelVerdictStatus::Granted
+        );
+        let verdict = client.declare_intent(&IntentManifest {
+            intents: vec![SPOTriple {
+                object: ResourceRef::database_table("shared.accounts", None),
+                ..intent("crm/linter", "/shared.sql").intents.remove(0)
+            }],
+            ..intent("crm/linter", "/shared.sql")
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(
+            verdict.blockers[0].reason,
+            "Conflict: Mutates vs held Mutates (implied via bridge to DATABASE_TABLE:shared.accounts)"
+        );
+    }
+
+    #[test]
+    fn test_isolated_namespaces_keep_intents_apart() {
+        let engine = ConflictEngine::default().with_isolated_namespaces(
+            NamespaceBridges::new()
+                .with(
+                    accounts("billing"),
+                    BridgeEnd::new(None, ResourceRef::database_table("accounts", None)),
+                )
+                .unwrap(),
+        );
+        let triple = |agent_id: &str, object: ResourceRef| SPOTriple {
+            object,
+            ..intent(agent_id, "/").intents.remove(0)
+        };
+        let held = [
+            triple("billing/a", ResourceRef::new(ResourceType::File, "/app.rs")),
+            triple(
+                "billing/a",
+                ResourceRef::database_table("shared.accounts", None),
+            ),
+        ];
+
+        let app = ResourceRef::new(ResourceType::File, "/app.rs");
+        assert!(
+            engine
+                .check_intent_all(&triple("crm/b", app.clone()), &held)
+                .is_empty()
+        );
+        assert!(
+            engine
+                .check_intent_all(&triple("human", app.clone()), &held)
+                .is_empty()
+        );
+        assert_eq!(
+            engine
+                .check_intent_all(&triple("billing/b", app), &held)
+                .len(),
+            1
+        );
+        let conflicts = engine.check_intent_all(
+            &triple("human", ResourceRef::database_table("accounts", Some("id"))),
+            &held,
+        );
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].resource,
+            ResourceRef::database_table("shared.accounts", None)
+        );
+        // The counterpart is indexed too, so lookups find the other side
+        assert!(
+            engine
+                .footprint_resources(
+                    Predicate::Mutates,
+                    &ResourceRef::database_table("accounts", None)
+                )
+                .contains(&ResourceRef::database_table("shared.accounts", None))
+        );
+    }
+}
@@ -0,0 +1,190 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::types::normalize::PathNormalization;
+    use crate::types::{LeaseResult, ResourceRef, ResourceType};
+    use std::sync::Arc;
+
+    fn file(rules: &PathNormalization, path: &str) -> String {
+        rules.apply(&ResourceType::File, path)
+    }
+
+    #[test]
+    fn test_file_spellings_collapse() {
+        let rules = PathNormalization::default();
+        for path in [
+            "src/app.ts",
+            "/src/app.ts",
+            "./src/app.ts",
+            "/src//./app.ts",
+            "src\\app.ts",
+            "/src/lib/../app.ts",
+            "/../src/app.ts",
+        ] {
+            assert_eq!(file(&rules, path), "/src/app.ts", "{}", path);
+        }
+
+        // Directories keep one trailing slash; the root stays the root
+        assert_eq!(file(&rules, "src//"), "/src/");
+        assert_eq!(file(&rules, "/src/."), "/src/");
+        assert_eq!(file(&rules, "./"), "/");
+        assert_eq!(file(&rules, ""), "");
+
+        // `..` never steps back over a glob
+        assert_eq!(file(&rules, "/src/**/../a.ts"), "/src/**/../a.ts");
+        assert_eq!(file(&rules, "src\\**\\*.ts"), "/src/**/*.ts");
+        assert_eq!(file(&rules, "/src/App.ts"), "/src/App.ts");
+    }
+
+    #[test]
+    fn test_case_folding_and_other_types() {
+        let folded = PathNormalization {
+            case_fold: true,
+            ..Default::default()
+        };
+        assert_eq!(file(&folded, "/src/App.ts"), file(&folded, "src/app.ts"));
+        assert_eq!(
+            folded.apply(&ResourceType::Symbol, "src\\User.ts#User.Login"),
+            "/src/user.ts#User.Login"
+        );
+
+        // Symbols, tables, keys and routes are spelled as given
+        for resource_type in [
+            ResourceType::Symbol,
+            ResourceType::ApiEndpoint,
+            ResourceType::DatabaseTable,
+        ] {
+            assert_eq!(
+                folded.apply(&resource_type, "User.authenticate/"),
+                "User.authenticate/"
+            );
+        }
+    }
+
+    #[test]
+    fn test_paths_are_made_relative_to_the_workspace_root() {
+        let rules = PathNormalization {
+            roots: vec!["/repo".to_string(), "/repo/vendor/lib/".to_string()],
+            ..Default::default()
+        };
+        for path in [
+            "src/app.ts",
+            "./src/app.ts",
+            "/repo/src/app.ts",
+            "/src/app.ts",
+        ] {
+            assert_eq!(file(&rules, path), "/src/app.ts", "{}", path);
+        }
+        assert_eq!(file(&rules, "/repo"), "/");
+        assert_eq!(file(&rules, "/repo/src/"), "/src/");
+        // Whole segments only, and the longest root wins
+        assert_eq!(file(&rules, "/repository/a.ts"), "/repository/a.ts");
+        assert_eq!(file(&rules, "/repo/vendor/lib/x.ts"), "/x.ts");
+        assert_eq!(
+            rules.apply(&ResourceType::Symbol, "/repo/src/user.ts#User"),
+            "/src/user.ts#User"
+        );
+
+        // Windows checkouts, folded like any other path
+        let windows = PathNormalization {
+            case_fold: true,
+            roots: vec!["C:\\Work\\Repo".to_string()],
+        };
+        assert_eq!(file(&windows, "c:\\work\\repo\\src\\App.ts"), "/src/app.ts");
+    }
+
+    #[test]
+    fn test_escaping_the_workspace_root_is_an_error() {
+        let rules = PathNormalization {
+            roots: vec!["/repo".to_string()],
+            ..Default::default()
+        };
+        for path in ["../secrets.env", "/repo/../etc/passwd", "src/../../x.ts"] {
+            let err = rules.try_apply(&ResourceType::File, path).unwrap_err();
+            assert!(err.contains("escapes the workspace root"), "{}", err);
+            // `apply` clamps instead
+            assert!(!file(&rules, path).contains(".."), "{}", path);
+        }
+        assert!(rules.try_apply(&ResourceType::Symbol, "../a.ts#A").is_err());
+        assert_eq!(
+            rules.try_apply(&ResourceType::File, "/repo/src/../app.ts"),
+            Ok("/app.ts".to_string())
+        );
+        assert_eq!(
+            rules.try_apply(&ResourceType::ApiEndpoint, "../x"),
+            Ok("../x".to_string())
+        );
+
+        // Without roots there is nothing to escape
+        assert_eq!(
+            PathNormalization::default().try_apply(&ResourceType::File, "../a.ts"),
+            Ok("/a.ts".to_string())
+        );
+    }
+
+    #[test]
+    fn test_constructed_and_deserialized_refs_are_normalized() {
+        let built = ResourceRef::new(ResourceType::File, "./src\\app.ts");
+        assert_eq!(built.path, "/src/app.ts");
+        assert_eq!(built.key(), "FILE:/src/app.ts");
+
+        let parsed: ResourceRef =
+            serde_json::from_str(r#"{"resource_type":"File","path":"src/lib/../app.ts"}"#).unwrap();
+        assert_eq!(parsed, built);
+        assert!(parsed.overlaps(&ResourceRef::new(ResourceType::File, "src/")));
+    }
+
+    #[test]
+    fn test_clients_apply_their_own_rules_per_namespace() {
+        let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        client.set_path_normalization(PathNormalization {
+            roots: vec!["/repo".to_string()],
+            ..Default::default()
+        });
+        client.set_namespace_path_normalization(
+            "ci",
+            Some(PathNormalization {
+                case_fold: true,
+                roots: vec!["/home/ci/checkout".to_string()],
+            }),
+        );
+        client.register_agent("dev", 100);
+        client.register_agent("ci/build", 200);
+
+        // Construction applies no roots; the client does
+        assert_eq!(
+            ResourceRef::new(ResourceType::File, "/repo/src/app.ts").path,
+            "/repo/src/app.ts"
+        );
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("dev", "s1", "FILE", "/repo/src/app.ts", "MUTATES", 5000)
+        else {
+            panic!("dev should get the lease");
+        };
+        assert_eq!(lease.resource.path, "/src/app.ts");
+
+        // The namespace's checkout is the same workspace
+        let result = client.acquire_lease(
+            "ci/build",
+            "s1",
+            "FILE",
+            "/home/ci/checkout/src/App.ts",
+            "MUTATES",
+            5000,
+        );
+        assert!(
+            matches!(result, LeaseResult::Failure { .. }),
+            "{:?}",
+            result
+        );
+        assert_eq!(
+            client.path_normalization("ci/build").roots,
+            ["/home/ci/checkout"]
+        );
+
+        // Without its own rules, a namespace falls back to the client's
+        client.set_namespace_path_normalization("ci", None);
+        assert_eq!(client.path_normalization("ci/build").roots, ["/repo"]);
+    }
+}
@@ -0,0 +1,217 @@
+//! Scripted admission policy.
+//!
+//! Quarantines, maintenance windows and retry limits are declarative, and
+//! some rules do not fit them ("interns may not touch migrations on
+//! Fridays"). A `PolicyHook` runs arbitrary logic instead. The client asks
+//! it about every acquire, reservation and activation, and every intent
+//! of a manifest, once the built-in admission checks have passed and
+//! before the scheduler runs.
+//! The hook allows the request, denies it with a reason, or changes the
+//! TTL or deadline of an acquire.
+//!
+//! `WasmPolicy` (feature `wasm-policy`) runs a user-supplied WebAssembly
+//! module as the hook, sandboxed: the module may import nothing, each call
+//! gets a fresh instance with a fuel budget and a memory cap, and a script
+//! that traps, runs out of either or answers nonsense denies the request.
+
+use crate::registry::Priority;
+use crate::types::{Predicate, ResourceRef};
+use serde::{Deserialize, Serialize};
+
+/// What the request being admitted is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyRequestKind {
+    /// `acquire_lease`, a reservation (`pre_acquire`) or its activation
+    Acquire,
+    /// One intent of a declared manifest
+    Intent,
+}
+
+/// The context a policy decides on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PolicyRequest {
+    pub kind: PolicyRequestKind,
+    pub agent_id: String,
+    pub session_id: String,
+    pub resource: ResourceRef,
+    pub predicate: Predicate,
+    /// Requested TTL; intents have none
+    pub ttl: Option<u64>,
+    /// Requested deadline, if any (see `acquire_lease_with_deadline`)
+    pub deadline: Option<u64>,
+    /// The agent's registered priority, if it is registered
+    pub priority: Option<Priority>,
+    pub now: u64,
+}
+
+/// A policy's answer. Serialized with a `decision` tag:
+///
+/// ```json
+/// { "decision": "deny", "reason": "Migrations are frozen on Fridays" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "lowercase")]
+pub enum PolicyDecision {
+    Allow,
+    Deny {
+        reason: String,
+    },
+    /// Allow with another TTL or deadline; fields left out (or a zero TTL)
+    /// are unchanged. Intents have neither, so for them this is `Allow`.
+    Modify {
+        #[serde(default)]
+        ttl: Option<u64>,
+        #[serde(default)]
+        deadline: Option<u64>,
+    },
+}
+
+/// Admission logic consulted before the scheduler (see the module docs)
+pub trait PolicyHook: Send + Sync {
+    fn evaluate(&self, request: &PolicyRequest) -> PolicyDecision;
+}
+
+#[cfg(feature = "wasm-policy")]
+pub use wasm::{WasmLimits, WasmPolicy};
+
+#[cfg(feature = "wasm-policy")]
+mod wasm {
+    use super::{PolicyDecision, PolicyHook, PolicyRequest};
+    use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+    /// Resources one evaluation may use
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct WasmLimits {
+        /// Fuel per call, roughly one unit per WebAssembly instruction
+        pub fuel: u64,
+        /// Largest linear memory the module may grow to, in bytes
+        pub memory_bytes: usize,
+    }
+
+    impl Default for WasmLimits {
+        fn default() -> Self {
+            Self {
+                fuel: 10_000_000,
+                memory_bytes: 16 << 20,
+            }
+        }
+    }
+
+    /// A policy script compiled to WebAssembly.
+    ///
+    /// The module exports its `memory`, `alloc(len: i32) -> i32`, which
+    /// returns where the host may write `len` bytes, and `evaluate(ptr: i32,
+    /// len: i32) -> i64`. The host writes the `PolicyRequest` as JSON to
+    /// memory from `alloc` and calls `evaluate` on it, which returns where
+    /// its `PolicyDecision` JSON lies, as `ptr << 32 | len`.
+    pub struct WasmPolicy {
+        engine: Engine,
+        module: Module,
+        limits: WasmLimits,
+    }
+
+    impl std::fmt::Debug for WasmPolicy {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("WasmPolicy")
+                .field("limits", &self.limits)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl WasmPolicy {
+        /// Compile a module, given as WebAssembly binary or text, and check
+        /// its imports and exports.
+        pub fn new(source: &[u8], limits: WasmLimits) -> Result<Self, String> {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config).map_err(|e| e.to_string())?;
+            let module = Module::new(&engine, source)
+                .map_err(|e| format!("Invalid policy module: {}", e))?;
+            if let Some(import) = module.imports().next() {
+                return Err(format!(
+                    "Policy modules may not import anything; this one imports {}.{}",
+                    import.module(),
+                    import.name()
+                ));
+            }
+            for export in ["memory", "alloc", "evaluate"] {
+                if module.get_export(export).is_none() {
+                    return Err(format!("Policy module does not export '{}'", export));
+                }
+            }
+            Ok(Self {
+                engine,
+                module,
+                limits,
+            })
+        }
+
+        /// `new` with the module at `path`
+        pub fn open(path: &str, limits: WasmLimits) -> Result<Self, String> {
+            let source = std::fs::read(path)
+                .map_err(|e| format!("Cannot read policy module {}: {}", path, e))?;
+            Self::new(&source, limits)
+        }
+
+        pub fn limits(&self) -> WasmLimits {
+            self.limits
+        }
+
+        /// Run the script on `request` in a fresh instance
+        pub fn call(&self, request: &PolicyRequest) -> Result<PolicyDecision, String> {
+            let limits = StoreLimitsBuilder::new()
+                .memory_size(self.limits.memory_bytes)
+                .instances(1)
+                .build();
+            let mut store: Store<StoreLimits> = Store::new(&self.engine, limits);
+            store.limiter(|limits| limits);
+            store
+                .set_fuel(self.limits.fuel)
+                .map_err(|e| e.to_string())?;
+
+            let instance =
+                Instance::new(&mut store, &self.module, &[]).map_err(|e| e.to_string())?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or("'memory' is not a memory")?;
+            let alloc = instance
+                .get_typed_func::<i32, i32>(&mut store, "alloc")
+                .map_err(|e| format!("'alloc': {}", e))?;
+            let evaluate = instance
+                .get_typed_func::<(i32, i32), i64>(&mut store, "evaluate")
+                .map_err(|e| format!("'evaluate': {}", e))?;
+
+            let input = serde_json::to_vec(request).map_err(|e| e.to_string())?;
+            let len = i32::try_from(input.len()).map_err(|e| e.to_string())?;
+            let ptr = alloc.call(&mut store, len).map_err(trap)?;
+            memory
+                .write(&mut store, ptr as u32 as usize, &input)
+                .map_err(|e| format!("'alloc' returned {}: {}", ptr, e))?;
+            let packed = evaluate.call(&mut store, (ptr, len)).map_err(trap)? as u64;
+
+            let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+            let output = memory
+                .data(&store)
+                .get(out_ptr..out_ptr.saturating_add(out_len))
+                .ok_or_else(|| format!("'evaluate' returned {:#x}, outside memory", packed))?;
+            serde_json::from_slice(output).map_err(|e| format!("Invalid decision: {}", e))
+        }
+    }
+
+    /// Traps, including running out of fuel or memory, with their cause
+    fn trap(error: wasmtime::Error) -> String {
+        match error.downcast_ref::<wasmtime::Trap>() {
+            Some(trap) => trap.to_string(),
+            None => error.to_string(),
+        }
+    }
+
+    impl PolicyHook for WasmPolicy {
+        fn evaluate(&self, request: &PolicyRequest) -> PolicyDecision {
+            self.call(request).unwrap_or_else(|e| PolicyDecision::Deny {
+                reason: format!("Policy script failed: {}", e),
+            })
+        }
+    }
+}
@@ -0,0 +1,314 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::codes::ReasonCode;
+    use crate::policy_hook::{PolicyDecision, PolicyHook, PolicyRequest};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use std::sync::Arc;
+
+    /// Interns stay out of migrations; everyone else gets at most 5s leases
+    struct MigrationGuard;
+
+    impl PolicyHook for MigrationGuard {
+        fn evaluate(&self, request: &PolicyRequest) -> PolicyDecision {
+            if request.agent_id.starts_with("intern")
+                && request.resource.path.starts_with("/migrations/")
+            {
+                return PolicyDecision::Deny {
+                    reason: format!("{} may not touch migrations", request.agent_id),
+                };
+            }
+            match request.ttl {
+                Some(ttl) if ttl > 5_000 => PolicyDecision::Modify {
+                    ttl: Some(5_000),
+                    deadline: None,
+                },
+                _ => PolicyDecision::Allow,
+            }
+        }
+    }
+
+    fn guarded_client() -> KlockClient {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock);
+        client.register_agent("intern-1", 100);
+        client.register_agent("senior", 200);
+        client.set_policy_hook(Some(Arc::new(MigrationGuard)));
+        client
+    }
+
+    #[test]
+    fn test_hook_denies_and_modifies_acquires() {
+        let mut client = guarded_client();
+        let denied = client.acquire_lease(
+            "intern-1",
+            "s1",
+            "FILE",
+            "/migrations/001.sql",
+            "MUTATES",
+            1000,
+        );
+        assert!(matches!(
+            denied,
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::PolicyDenied,
+                ..
+            }
+        ));
+        assert_eq!(
+            client.last_policy_denial(),
+            Some("intern-1 may not touch migrations")
+        );
+        assert!(client.get_active_leases().is_empty());
+
+        let LeaseResult::Success { lease } = client.acquire_lease(
+            "senior",
+            "s1",
+            "FILE",
+            "/migrations/001.sql",
+            "MUTATES",
+            60_000,
+        ) else {
+            panic!("Expected success");
+        };
+        assert_eq!(lease.ttl, 5_000);
+        assert_eq!(client.last_policy_denial(), None);
+
+        client.set_policy_hook(None);
+        assert!(matches!(
+            client.acquire_lease(
+                "intern-1",
+                "s1",
+                "FILE",
+                "/migrations/002.sql",
+                "MUTATES",
+                1000
+            ),
+            LeaseResult::Success { .. }
+        ));
+    }
+
+    #[test]
+    fn test_hook_covers_reservations_and_activations() {
+        let mut client = guarded_client();
+        let migration = [(
+            ResourceRef::new(ResourceType::File, "/migrations/002.sql"),
+            Predicate::Mutates,
+        )];
+        let error = client
+            .pre_acquire("intern-1", "s1", &migration, 60_000, 30_000)
+            .unwrap_err();
+        assert!(error.contains("may not touch migrations"), "{}", error);
+        assert_eq!(
+            client.last_policy_denial(),
+            Some("intern-1 may not touch migrations")
+        );
+
+        let reserved = client
+            .pre_acquire("senior", "s2", &migration, 60_000, 30_000)
+            .unwrap();
+        assert_eq!(reserved[0].ttl, 5_000);
+        client.release_lease(&reserved[0].id);
+
+        // Reserved before the hook was set, the activation is still denied
+        client.set_policy_hook(None);
+        let reserved = client
+            .pre_acquire("intern-1", "s1", &migration, 60_000, 30_000)
+            .unwrap();
+        client.set_policy_hook(Some(Arc::new(MigrationGuard)));
+        let result = client.activate_lease(&reserved[0].id);
+        assert!(matches!(
+            result,
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::PolicyDenied,
+                ..
+            }
+        ));
+        assert!(client.get_active_leases().is_empty());
+    }
+
+    #[test]
+    fn test_hook_rejects_manifests() {
+        let mut client = guarded_client();
+        let intent = |id: &str, path: &str| SPOTriple {
+            id: id.to_string(),
+            subject: "intern-1".to_string(),
+            predicate: Predicate::Mutates,
+            object: ResourceRef::new(ResourceType::File, path),
+            timestamp: 1000,
+            confidence: Confidence::High,
+            session_id: "s1".to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        };
+        let manifest = |intents| IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "intern-1".to_string(),
+            intents,
+        };
+
+        let verdict = client.declare_intent(&manifest(vec![
+            intent("i1", "/src/app.ts"),
+            intent("i2", "/migrations/001.sql"),
+        ]));
+        assert_eq!(verdict.status, KernelVerdictStatus::Rejected);
+        assert_eq!(verdict.code, Some(ReasonCode::PolicyDenied));
+        assert_eq!(
+            verdict.reason.as_deref(),
+            Some("intern-1 may not touch migrations")
+        );
+
+        let verdict = client.declare_intent(&manifest(vec![intent("i1", "/src/app.ts")]));
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+    }
+
+    #[test]
+    fn test_decisions_parse() {
+        for (json, decision) in [
+            (r#"{"decision":"allow"}"#, PolicyDecision::Allow),
+            (
+                r#"{"decision":"deny","reason":"no"}"#,
+                PolicyDecision::Deny {
+                    reason: "no".to_string(),
+                },
+            ),
+            (
+                r#"{"decision":"modify","ttl":100}"#,
+                PolicyDecision::Modify {
+                    ttl: Some(100),
+                    deadline: None,
+                },
+            ),
+        ] {
+            assert_eq!(
+                serde_json::from_str::<PolicyDecision>(json).unwrap(),
+                decision
+            );
+        }
+        assert!(serde_json::from_str::<PolicyDecision>(r#"{"decision":"maybe"}"#).is_err());
+    }
+
+    #[cfg(feature = "wasm-policy")]
+    mod wasm {
+        use super::*;
+        use crate::policy_hook::{PolicyRequestKind, WasmLimits, WasmPolicy};
+
+        /// Denies any request whose JSON mentions "Mutates"
+        const READ_ONLY: &str = r#"(module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "\"Mutates\"")
+          (data (i32.const 16) "{\"decision\":\"allow\"}")
+          (data (i32.const 48) "{\"decision\":\"deny\",\"reason\":\"read-only policy\"}")
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "evaluate") (param $ptr i32) (param $len i32) (result i64)
+            (local $i i32) (local $j i32)
+            (block $done
+              (loop $scan
+                (br_if $done
+                  (i32.gt_s (i32.add (local.get $i) (i32.const 9)) (local.get $len)))
+                (local.set $j (i32.const 0))
+                (block $mismatch
+                  (loop $cmp
+                    (br_if $mismatch
+                      (i32.ne
+                        (i32.load8_u
+                          (i32.add (i32.add (local.get $ptr) (local.get $i)) (local.get $j)))
+                        (i32.load8_u (local.get $j))))
+                    (local.set $j (i32.add (local.get $j) (i32.const 1)))
+                    (br_if $cmp (i32.lt_u (local.get $j) (i32.const 9)))
+                    (return (i64.or (i64.shl (i64.const 48) (i64.const 32)) (i64.const 47)))))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $scan)))
+            (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 20))))"#;
+
+        fn request(predicate: Predicate) -> PolicyRequest {
+            PolicyRequest {
+                kind: PolicyRequestKind::Acquire,
+                agent_id: "bot".to_string(),
+                session_id: "s1".to_string(),
+                resource: ResourceRef::new(ResourceType::File, "/a.ts"),
+                predicate,
+                ttl: Some(1000),
+                deadline: None,
+                priority: None,
+                now: 1000,
+            }
+        }
+
+        fn denial(policy: &WasmPolicy) -> String {
+            match policy.evaluate(&request(Predicate::Consumes)) {
+                PolicyDecision::Deny { reason } => reason,
+                other => panic!("Expected a denial, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_script_sees_the_request() {
+            let policy = WasmPolicy::new(READ_ONLY.as_bytes(), WasmLimits::default()).unwrap();
+            assert_eq!(
+                policy.evaluate(&request(Predicate::Consumes)),
+                PolicyDecision::Allow
+            );
+            assert_eq!(
+                policy.evaluate(&request(Predicate::Mutates)),
+                PolicyDecision::Deny {
+                    reason: "read-only policy".to_string()
+                }
+            );
+
+            let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+            client.register_agent("bot", 100);
+            client.set_policy_hook(Some(Arc::new(policy)));
+            assert!(matches!(
+                client.acquire_lease("bot", "s1", "FILE", "/a.ts", "MUTATES", 1000),
+                LeaseResult::Failure {
+                    reason: LeaseFailureReason::PolicyDenied,
+                    ..
+                }
+            ));
+            assert_eq!(client.last_policy_denial(), Some("read-only policy"));
+        }
+
+        #[test]
+        fn test_scripts_are_sandboxed() {
+            let spinning = r#"(module
+              (memory (export "memory") 1)
+              (func (export "alloc") (param i32) (result i32) (i32.const 0))
+              (func (export "evaluate") (param i32 i32) (result i64)
+                (loop $forever (br $forever))
+                (i64.const 0)))"#;
+            let policy = WasmPolicy::new(spinning.as_bytes(), WasmLimits::default()).unwrap();
+            assert!(denial(&policy).contains("fuel"), "{}", denial(&policy));
+
+            // 4 MiB of memory against a 1 MiB cap
+            let greedy = r#"(module
+              (memory (export "memory") 64)
+              (func (export "alloc") (param i32) (result i32) (i32.const 0))
+              (func (export "evaluate") (param i32 i32) (result i64) (i64.const 0)))"#;
+            let limits = WasmLimits {
+                memory_bytes: 1 << 20,
+                ..WasmLimits::default()
+            };
+            let policy = WasmPolicy::new(greedy.as_bytes(), limits).unwrap();
+            assert!(denial(&policy).starts_with("Policy script failed"));
+
+            let importing = r#"(module
+              (import "env" "clock" (func))
+              (memory (export "memory") 1)
+              (func (export "alloc") (param i32) (result i32) (i32.const 0))
+              (func (export "evaluate") (param i32 i32) (result i64) (i64.const 0)))"#;
+            assert!(
+                WasmPolicy::new(importing.as_bytes(), WasmLimits::default())
+                    .unwrap_err()
+                    .contains("env.clock")
+            );
+            assert!(WasmPolicy::new(b"(module)", WasmLimits::default()).is_err());
+        }
+    }
+}
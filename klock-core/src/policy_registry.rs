@@ -0,0 +1,289 @@
+//! Scheduling policy per resource type.
+//!
+//! One policy rarely suits every resource: files are held for minutes and
+//! worth waiting for, while a database table is held for a statement and a
+//! conflicting agent should rather retry. A `PolicyRegistry` is a
+//! `Scheduler` that decides each request with the policy registered for the
+//! requested resource's type, and with its default policy for the others.
+//! The policy is looked up at decision time, so the kernel, both lease
+//! stores and the scheduler wrappers (backoff, auditing, fault injection)
+//! consult it without knowing about it.
+//!
+//! For the same reason the registry applies the `UnregisteredAgentPolicy`:
+//! before any policy sees a request, agents without a priority get the
+//! lowest one, or the request is refused if one of them is in its way.
+
+use crate::conflict::ConflictEngine;
+use crate::registry::{Priority, UnregisteredAgentPolicy};
+use crate::scheduler::{
+    Scheduler, SchedulerVerdict, VerdictStatus, WaitDieScheduler, scheduler_by_name,
+};
+use crate::types::{Holder, Lease, Predicate, ResourceRef, ResourceType};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A default policy plus overrides by resource type
+#[derive(Clone)]
+pub struct PolicyRegistry {
+    default: Arc<dyn Scheduler>,
+    by_type: HashMap<ResourceType, Arc<dyn Scheduler>>,
+    unregistered: UnregisteredAgentPolicy,
+}
+
+impl Default for PolicyRegistry {
+    /// Wait-Die for every resource type
+    fn default() -> Self {
+        Self::new(Arc::new(WaitDieScheduler))
+    }
+}
+
+impl std::fmt::Debug for PolicyRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PolicyRegistry")
+            .field("default", &self.default.name())
+            .field("overrides", &self.overrides())
+            .field("unregistered", &self.unregistered)
+            .finish()
+    }
+}
+
+impl PolicyRegistry {
+    /// `default` for every resource type
+    pub fn new(default: Arc<dyn Scheduler>) -> Self {
+        Self {
+            default,
+            by_type: HashMap::new(),
+            unregistered: UnregisteredAgentPolicy::default(),
+        }
+    }
+
+    /// Decide requests on `resource_type` with `scheduler`
+    pub fn with(mut self, resource_type: ResourceType, scheduler: Arc<dyn Scheduler>) -> Self {
+        self.set(resource_type, Some(scheduler));
+        self
+    }
+
+    /// Decide requests on `resource_type` with `scheduler`, or with the
+    /// default policy again for `None`
+    pub fn set(&mut self, resource_type: ResourceType, scheduler: Option<Arc<dyn Scheduler>>) {
+        match scheduler {
+            Some(scheduler) => self.by_type.insert(resource_type, scheduler),
+            None => self.by_type.remove(&resource_type),
+        };
+    }
+
+    pub fn set_default(&mut self, scheduler: Arc<dyn Scheduler>) {
+        self.default = scheduler;
+    }
+
+    pub fn default_policy(&self) -> &Arc<dyn Scheduler> {
+        &self.default
+    }
+
+    /// The policy that decides requests on `resource_type`
+    pub fn policy_for(&self, resource_type: &ResourceType) -> &Arc<dyn Scheduler> {
+        self.by_type.get(resource_type).unwrap_or(&self.default)
+    }
+
+    /// How requests involving agents without a priority are decided
+    pub fn set_unregistered_agents(&mut self, policy: UnregisteredAgentPolicy) {
+        self.unregistered = policy;
+    }
+
+    pub fn unregistered_agents(&self) -> UnregisteredAgentPolicy {
+        self.unregistered
+    }
+
+    /// `priorities` as the policies should see them for a request by
+    /// `requester` against `holders`: agents without a priority get the
+    /// lowest one, or, under `Reject`, the request is refused with a Die
+    /// verdict if the requester or a conflicting holder has none.
+    fn screen<'p>(
+        &self,
+        requester: &str,
+        holders: &[&Lease],
+        priorities: &'p HashMap<String, Priority>,
+    ) -> Result<Cow<'p, HashMap<String, Priority>>, SchedulerVerdict> {
+        let mut unknown: Vec<&str> = Vec::new();
+        for agent_id in
+            std::iter::once(requester).chain(holders.iter().map(|lease| lease.agent_id.as_str()))
+        {
+            if !priorities.contains_key(agent_id) && !unknown.contains(&agent_id) {
+                unknown.push(agent_id);
+            }
+        }
+        if unknown.is_empty() {
+            return Ok(Cow::Borrowed(priorities));
+        }
+        if self.unregistered == UnregisteredAgentPolicy::Reject {
+            return Err(SchedulerVerdict {
+                status: VerdictStatus::Die,
+                reason: Some(format!(
+                    "Unregistered agent(s) {}: register before acquiring.",
+                    unknown.join(", ")
+                )),
+                holders: holders.iter().copied().map(Holder::from).collect(),
+                retry_after_ms: None,
+                preempted: Vec::new(),
+            });
+        }
+        let mut completed = priorities.clone();
+        for agent_id in unknown {
+            completed.insert(agent_id.to_string(), Priority::lowest());
+        }
+        Ok(Cow::Owned(completed))
+    }
+
+    /// `screen` for a request against every conflicting lease among
+    /// `active_leases`
+    #[allow(clippy::too_many_arguments)]
+    fn screen_leases<'p>(
+        &self,
+        engine: &ConflictEngine,
+        requester: &str,
+        session_id: &str,
+        predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &'p HashMap<String, Priority>,
+    ) -> Result<Cow<'p, HashMap<String, Priority>>, SchedulerVerdict> {
+        let everyone_registered = std::iter::once(requester)
+            .chain(active_leases.iter().map(|lease| lease.agent_id.as_str()))
+            .all(|agent_id| priorities.contains_key(agent_id));
+        if everyone_registered {
+            return Ok(Cow::Borrowed(priorities));
+        }
+        let holders =
+            engine.conflicting_leases(requester, session_id, predicate, resource, active_leases);
+        self.screen(requester, &holders, priorities)
+    }
+
+    /// The overridden resource types and their policies' names, by type
+    pub fn overrides(&self) -> Vec<(ResourceType, &'static str)> {
+        let mut overrides: Vec<_> = self
+            .by_type
+            .iter()
+            .map(|(resource_type, scheduler)| (resource_type.clone(), scheduler.name()))
+            .collect();
+        overrides.sort_by_key(|(resource_type, _)| resource_type.to_string());
+        overrides
+    }
+}
+
+/// Parse a `TYPE=policy` override such as `DATABASE_TABLE=no-wait`, with a
+/// built-in policy name (see `scheduler_by_name`).
+pub fn parse_override(spec: &str) -> Result<(ResourceType, Arc<dyn Scheduler>), String> {
+    let (resource_type, name) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("Expected TYPE=policy, got '{}'", spec))?;
+    let resource_type = match resource_type.trim().to_uppercase().as_str() {
+        "FILE" => ResourceType::File,
+        "SYMBOL" => ResourceType::Symbol,
+        "API_ENDPOINT" => ResourceType::ApiEndpoint,
+        "DATABASE_TABLE" => ResourceType::DatabaseTable,
+        "CONFIG_KEY" => ResourceType::ConfigKey,
+        other => {
+            return Err(format!(
+                "Unknown resource type '{}': use FILE, SYMBOL, API_ENDPOINT, DATABASE_TABLE or CONFIG_KEY",
+                other
+            ));
+        }
+    };
+    Ok((resource_type, scheduler_by_name(name.trim())?))
+}
+
+impl Scheduler for PolicyRegistry {
+    /// The default policy's name
+    fn name(&self) -> &'static str {
+        self.default.name()
+    }
+
+    fn name_for(&self, resource: &ResourceRef) -> &'static str {
+        self.policy_for(&resource.resource_type).name_for(resource)
+    }
+
+    /// Without a resource there is no type to go by: the default decides
+    fn resolve(
+        &self,
+        requesting_agent_id: &str,
+        conflicting_holders: &[&Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        match self.screen(requesting_agent_id, conflicting_holders, priorities) {
+            Ok(priorities) => {
+                self.default
+                    .resolve(requesting_agent_id, conflicting_holders, &priorities)
+            }
+            Err(verdict) => verdict,
+        }
+    }
+
+    fn decide(
+        &self,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        let priorities = match self.screen_leases(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+        ) {
+            Ok(priorities) => priorities,
+            Err(verdict) => return verdict,
+        };
+        self.policy_for(&resource.resource_type).decide(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            &priorities,
+        )
+    }
+
+    fn decide_at(
+        &self,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+        now: u64,
+    ) -> SchedulerVerdict {
+        let priorities = match self.screen_leases(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+        ) {
+            Ok(priorities) => priorities,
+            Err(verdict) => return verdict,
+        };
+        self.policy_for(&resource.resource_type).decide_at(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            &priorities,
+            now,
+        )
+    }
+}
@@ -0,0 +1,163 @@
+#[cfg(test)]
+mod tests {
+    use crate::audit::{AuditLog, AuditQuery};
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::policy_registry::{PolicyRegistry, parse_override};
+    use crate::scheduler::{NoWaitScheduler, Scheduler, WoundWaitScheduler, scheduler_by_name};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use std::sync::Arc;
+
+    fn failure(result: LeaseResult) -> LeaseFailureReason {
+        match result {
+            LeaseResult::Failure { reason, .. } => reason,
+            other => panic!("Expected a failure, got {:?}", other),
+        }
+    }
+
+    /// A junior holding a file and a table, and a senior wanting both
+    fn contended_client() -> (KlockClient, Arc<AuditLog>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        let audit = Arc::new(AuditLog::new(100));
+        client.set_audit_sink(Some(audit.clone()));
+        client.set_resource_type_scheduler(
+            ResourceType::DatabaseTable,
+            Some(Arc::new(NoWaitScheduler)),
+        );
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+        client.acquire_lease("junior", "j1", "FILE", "/a.ts", "MUTATES", 60_000);
+        clock.advance(1);
+        client.acquire_lease("junior", "j1", "DATABASE_TABLE", "users", "MUTATES", 60_000);
+        clock.advance(1);
+        (client, audit)
+    }
+
+    #[test]
+    fn test_each_resource_type_gets_its_policy() {
+        let (mut client, audit) = contended_client();
+        assert_eq!(client.scheduler_name(), "wait-die");
+        assert_eq!(
+            client.resource_type_schedulers(),
+            vec![(ResourceType::DatabaseTable, "no-wait")]
+        );
+
+        // Wait-Die on files: the senior waits
+        assert_eq!(
+            failure(client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000)),
+            LeaseFailureReason::Wait
+        );
+        // No-Wait on tables: the senior dies all the same
+        assert_eq!(
+            failure(client.acquire_lease(
+                "senior",
+                "s1",
+                "DATABASE_TABLE",
+                "users",
+                "MUTATES",
+                60_000
+            )),
+            LeaseFailureReason::Die
+        );
+        let policies: Vec<_> = audit
+            .records(&AuditQuery::default())
+            .into_iter()
+            .filter(|r| r.agent_id == "senior")
+            .map(|r| r.scheduler)
+            .collect();
+        assert_eq!(policies, ["wait-die", "no-wait"]);
+
+        // The kernel consults the same registry for manifests
+        let intent = |id: &str, object: ResourceRef| SPOTriple {
+            id: id.to_string(),
+            subject: "senior".to_string(),
+            predicate: Predicate::Mutates,
+            object,
+            timestamp: 1000,
+            confidence: Confidence::High,
+            session_id: "s2".to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        };
+        let declare = |client: &mut KlockClient, object: ResourceRef| {
+            client
+                .declare_intent(&IntentManifest {
+                    session_id: "s2".to_string(),
+                    agent_id: "senior".to_string(),
+                    intents: vec![intent("i1", object)],
+                })
+                .status
+        };
+        assert_eq!(
+            declare(&mut client, ResourceRef::new(ResourceType::File, "/a.ts")),
+            KernelVerdictStatus::Wait
+        );
+        assert_eq!(
+            declare(
+                &mut client,
+                ResourceRef::new(ResourceType::DatabaseTable, "users")
+            ),
+            KernelVerdictStatus::Die
+        );
+
+        // Changing the default leaves the override alone, and clearing the
+        // override hands tables back to the default
+        client.set_scheduler(Arc::new(WoundWaitScheduler));
+        assert_eq!(
+            failure(client.acquire_lease(
+                "senior",
+                "s1",
+                "DATABASE_TABLE",
+                "users",
+                "MUTATES",
+                60_000
+            )),
+            LeaseFailureReason::Die
+        );
+        client.set_resource_type_scheduler(ResourceType::DatabaseTable, None);
+        assert!(client.resource_type_schedulers().is_empty());
+        assert!(matches!(
+            client.acquire_lease("senior", "s1", "DATABASE_TABLE", "users", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+    }
+
+    #[test]
+    fn test_registry_names_and_overrides() {
+        let registry = PolicyRegistry::new(scheduler_by_name("fifo").unwrap())
+            .with(ResourceType::File, Arc::new(WoundWaitScheduler))
+            .with(ResourceType::ConfigKey, Arc::new(NoWaitScheduler));
+        assert_eq!(registry.name(), "fifo");
+        assert_eq!(
+            registry.name_for(&ResourceRef::new(ResourceType::File, "/a.ts")),
+            "wound-wait"
+        );
+        assert_eq!(
+            registry.name_for(&ResourceRef::new(ResourceType::Symbol, "a.b")),
+            "fifo"
+        );
+        assert_eq!(
+            registry.overrides(),
+            vec![
+                (ResourceType::ConfigKey, "no-wait"),
+                (ResourceType::File, "wound-wait"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_override() {
+        let (resource_type, scheduler) = parse_override("database_table=no-wait").unwrap();
+        assert_eq!(resource_type, ResourceType::DatabaseTable);
+        assert_eq!(scheduler.name(), "no-wait");
+        for invalid in ["FILE", "FILE=lottery", "QUEUE=fifo", "=wait-die"] {
+            assert!(parse_override(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Preemptive revocation.
+//!
+//! Under Wait-Die a senior that meets a junior's lease waits for it, however
+//! urgent its own work is. With a preemption policy enabled, a senior agent
+//! may instead take the lease away: `KlockClient::preempt_lease` revokes it,
+//! records the senior's reason on the lease, and tells the junior's session
+//! through a `lease_revoked` event. Only agents older than the holder by at
+//! least the policy's gap may preempt, so near-peers still queue.
+
+use crate::codes::ReasonCode;
+use crate::infrastructure::LeaseMutationError;
+use crate::registry::Priority;
+use serde::{Deserialize, Serialize};
+
+/// Who may revoke whose leases. Disabled by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreemptionPolicy {
+    /// How much older (lower priority timestamp) than a holder in its own
+    /// class an agent must be to revoke its lease; an agent of a more urgent
+    /// class may revoke any lease of a less urgent one (`None` = preemption
+    /// disabled)
+    pub min_priority_gap: Option<u64>,
+}
+
+impl PreemptionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.min_priority_gap.is_some()
+    }
+
+    /// Whether an agent with priority `requester` may revoke a lease held
+    /// at priority `holder`
+    pub fn allows(&self, requester: Priority, holder: Priority) -> bool {
+        self.min_priority_gap.is_some_and(|gap| {
+            requester.class < holder.class
+                || (requester.class == holder.class
+                    && requester.timestamp < holder.timestamp
+                    && holder.timestamp - requester.timestamp >= gap)
+        })
+    }
+}
+
+/// Why a preemption was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreemptionError {
+    /// No preemption policy is enabled
+    Disabled,
+    /// The requesting agent is not registered
+    UnknownAgent,
+    /// The requester is not senior enough to the holder
+    NotSenior {
+        requester: Priority,
+        holder: Priority,
+    },
+    /// The lease is gone, or not active
+    Lease(LeaseMutationError),
+}
+
+impl PreemptionError {
+    /// The catalog code reported for the refusal
+    pub fn code(&self) -> ReasonCode {
+        match self {
+            PreemptionError::Disabled | PreemptionError::NotSenior { .. } => {
+                ReasonCode::PreemptionRefused
+            }
+            PreemptionError::UnknownAgent => ReasonCode::NotFound,
+            PreemptionError::Lease(error) => error.code(),
+        }
+    }
+}
+
+impl std::fmt::Display for PreemptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreemptionError::Disabled => write!(f, "preemption is disabled"),
+            PreemptionError::UnknownAgent => write!(f, "agent is not registered"),
+            PreemptionError::NotSenior { requester, holder } => write!(
+                f,
+                "priority {} is not senior enough to preempt a holder at {}",
+                requester, holder
+            ),
+            PreemptionError::Lease(error) => write!(f, "{}", error),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::codes::ReasonCode;
+    use crate::feed::AgentEvent;
+    use crate::preemption::{PreemptionError, PreemptionPolicy};
+    use crate::types::{LeaseResult, LeaseState, Predicate};
+
+    fn client_with_junior_lease() -> (KlockClient, String) {
+        let mut client = KlockClient::new();
+        client.register_agent("senior", 100);
+        client.register_agent("peer", 150);
+        client.register_agent("junior", 200);
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("junior", "s1", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        (client, lease.id)
+    }
+
+    #[test]
+    fn test_preemption_disabled_by_default() {
+        let (mut client, lease_id) = client_with_junior_lease();
+        let err = client
+            .preempt_lease("senior", &lease_id, "hotfix")
+            .unwrap_err();
+        assert_eq!(err, PreemptionError::Disabled);
+        assert_eq!(err.code(), ReasonCode::PreemptionRefused);
+        assert_eq!(
+            client.get_lease(&lease_id).unwrap().state,
+            LeaseState::Active
+        );
+    }
+
+    #[test]
+    fn test_senior_revokes_junior_lease_and_junior_is_told() {
+        let (mut client, lease_id) = client_with_junior_lease();
+        client.set_preemption_policy(PreemptionPolicy {
+            min_priority_gap: Some(60),
+        });
+
+        // Neither the junior itself nor a near-peer may take it
+        assert!(matches!(
+            client.preempt_lease("junior", &lease_id, "mine"),
+            Err(PreemptionError::NotSenior { .. })
+        ));
+        assert_eq!(
+            client
+                .preempt_lease("peer", &lease_id, "hotfix")
+                .unwrap_err(),
+            PreemptionError::NotSenior {
+                requester: 150.into(),
+                holder: 200.into()
+            }
+        );
+        assert_eq!(
+            client
+                .preempt_lease("stranger", &lease_id, "hotfix")
+                .unwrap_err(),
+            PreemptionError::UnknownAgent
+        );
+
+        let revoked = client.preempt_lease("senior", &lease_id, "hotfix").unwrap();
+        assert_eq!(revoked.state, LeaseState::Revoked);
+        let stored = client.get_lease(&lease_id).unwrap();
+        assert_eq!(
+            stored.revoked_reason.as_deref(),
+            Some("Preempted by senior agent 'senior': hotfix")
+        );
+        assert!(!client.heartbeat_lease(&lease_id, client.now()));
+
+        let batch = client.poll_events("junior", "s1");
+        let [
+            AgentEvent::LeaseRevoked {
+                lease_id: revoked_id,
+                predicate,
+                revoked_by,
+                ..
+            },
+        ] = batch.events.as_slice()
+        else {
+            panic!("Expected lease_revoked, got {:?}", batch.events);
+        };
+        assert_eq!(revoked_id, &lease_id);
+        assert_eq!(*predicate, Predicate::Mutates);
+        assert_eq!(revoked_by.as_deref(), Some("senior"));
+
+        // Only active leases can be preempted
+        assert_eq!(
+            client
+                .preempt_lease("senior", &lease_id, "again")
+                .unwrap_err()
+                .code(),
+            ReasonCode::LeaseNotFound
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Resource quarantine.
+//!
+//! Operators freeze resources under incident ("nobody touches
+//! `/src/payments/**` until this is resolved") by quarantining a resource or
+//! glob pattern. While a quarantine is in force, every acquire, activation
+//! and intent that overlaps it is refused with `Quarantined` before the
+//! scheduler is consulted, whoever the requester is. Leases already held are
+//! left alone. A quarantine lasts until it is removed or its optional expiry
+//! passes.
+
+use crate::types::ResourceRef;
+use serde::{Deserialize, Serialize};
+
+/// One frozen resource or pattern
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceQuarantine {
+    /// The frozen resource; directories and glob patterns freeze everything
+    /// they cover
+    pub resource: ResourceRef,
+    /// Why it is frozen, e.g. an incident ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub created_at: u64,
+    /// When the quarantine lifts on its own; `None` until removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl ResourceQuarantine {
+    fn in_force(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|at| at >= now)
+    }
+}
+
+/// All quarantines known to a client, one per resource key
+#[derive(Debug, Default)]
+pub struct QuarantineRegistry {
+    entries: Vec<ResourceQuarantine>,
+}
+
+impl QuarantineRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Quarantine `resource` until `expires_at` (or until removed), replacing
+    /// any quarantine already on the same resource.
+    pub fn add(
+        &mut self,
+        resource: ResourceRef,
+        reason: Option<String>,
+        expires_at: Option<u64>,
+        now: u64,
+    ) -> Result<ResourceQuarantine, String> {
+        if resource.path.is_empty() {
+            return Err("Quarantined resource path must not be empty".to_string());
+        }
+        if let Some(at) = expires_at
+            && at <= now
+        {
+            return Err(format!(
+                "expires_at ({}) must be in the future (now = {})",
+                at, now
+            ));
+        }
+        let quarantine = ResourceQuarantine {
+            resource,
+            reason,
+            created_at: now,
+            expires_at,
+        };
+        self.entries.retain(|q| q.resource != quarantine.resource);
+        self.entries.push(quarantine.clone());
+        Ok(quarantine)
+    }
+
+    /// Lift the quarantine on exactly `resource`. Returns false if there was
+    /// none.
+    pub fn remove(&mut self, resource: &ResourceRef) -> bool {
+        let initial = self.entries.len();
+        self.entries.retain(|q| q.resource != *resource);
+        self.entries.len() != initial
+    }
+
+    /// Quarantines in force at `now`, oldest first
+    pub fn list(&self, now: u64) -> Vec<ResourceQuarantine> {
+        self.entries
+            .iter()
+            .filter(|q| q.in_force(now))
+            .cloned()
+            .collect()
+    }
+
+    /// The quarantine in force at `now` that covers any part of `resource`
+    pub fn blocking(&self, resource: &ResourceRef, now: u64) -> Option<&ResourceQuarantine> {
+        self.entries
+            .iter()
+            .find(|q| q.in_force(now) && q.resource.overlaps(resource))
+    }
+
+    /// Drop quarantines whose expiry has passed. Returns the number dropped.
+    pub fn purge_expired(&mut self, now: u64) -> usize {
+        let initial = self.entries.len();
+        self.entries.retain(|q| q.in_force(now));
+        initial - self.entries.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Approximate footprint of all quarantines
+    pub fn estimated_bytes(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|q| {
+                std::mem::size_of::<ResourceQuarantine>()
+                    + q.resource.path.capacity()
+                    + q.reason.as_ref().map_or(0, String::capacity)
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::quarantine::QuarantineRegistry;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn failure_reason(result: LeaseResult) -> LeaseFailureReason {
+        match result {
+            LeaseResult::Failure { reason, .. } => reason,
+            other => panic!("Expected failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_patterns_block_overlapping_resources() {
+        let mut quarantines = QuarantineRegistry::new();
+        quarantines
+            .add(
+                file("/src/payments/**"),
+                Some("INC-42".to_string()),
+                Some(5000),
+                1000,
+            )
+            .unwrap();
+
+        assert!(
+            quarantines
+                .blocking(&file("/src/payments/charge.ts"), 2000)
+                .is_some()
+        );
+        assert!(quarantines.blocking(&file("/src/"), 2000).is_some());
+        assert!(quarantines.blocking(&file("/src/auth.ts"), 2000).is_none());
+
+        // Expired quarantines stop blocking and are purged
+        assert!(
+            quarantines
+                .blocking(&file("/src/payments/charge.ts"), 6000)
+                .is_none()
+        );
+        assert!(quarantines.list(6000).is_empty());
+        assert_eq!(quarantines.purge_expired(6000), 1);
+        assert!(quarantines.is_empty());
+    }
+
+    #[test]
+    fn test_add_replaces_and_validates() {
+        let mut quarantines = QuarantineRegistry::new();
+        assert!(quarantines.add(file(""), None, None, 1000).is_err());
+        assert!(
+            quarantines
+                .add(file("/src/"), None, Some(1000), 1000)
+                .is_err()
+        );
+
+        quarantines
+            .add(file("/src/"), None, Some(2000), 1000)
+            .unwrap();
+        quarantines
+            .add(file("/src/"), Some("extended".to_string()), None, 1500)
+            .unwrap();
+        assert_eq!(quarantines.len(), 1);
+        assert_eq!(
+            quarantines.list(9000)[0].reason.as_deref(),
+            Some("extended")
+        );
+
+        assert!(quarantines.remove(&file("/src/")));
+        assert!(!quarantines.remove(&file("/src/")));
+    }
+
+    #[test]
+    fn test_client_refuses_quarantined_work() {
+        let mut client = KlockClient::new();
+        client.register_agent("agent", 100);
+        let LeaseResult::Success { lease: held } = client.acquire_lease(
+            "agent",
+            "s1",
+            "FILE",
+            "/src/payments/a.ts",
+            "MUTATES",
+            60_000,
+        ) else {
+            panic!("Expected lease");
+        };
+
+        client
+            .quarantine_resource(file("/src/payments/"), Some("INC-42".to_string()), None)
+            .unwrap();
+        assert_eq!(client.quarantines().len(), 1);
+
+        // Held leases survive; new work is refused before scheduling
+        assert!(client.heartbeat_lease(&held.id, held.last_heartbeat + 1));
+        assert_eq!(
+            failure_reason(client.acquire_lease(
+                "agent",
+                "s1",
+                "FILE",
+                "/src/payments/b.ts",
+                "CONSUMES",
+                60_000
+            )),
+            LeaseFailureReason::Quarantined
+        );
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "agent".to_string(),
+            intents: vec![SPOTriple {
+                id: "t1".to_string(),
+                subject: "agent".to_string(),
+                predicate: Predicate::Mutates,
+                object: file("/src/payments/b.ts"),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert!(verdict.reason.unwrap().contains("INC-42"));
+        // Refusals are not the agent's fault
+        assert_eq!(client.agent_health("agent").dies, 0);
+
+        assert!(client.lift_quarantine(&file("/src/payments/")));
+        assert!(matches!(
+            client.acquire_lease(
+                "agent",
+                "s1",
+                "FILE",
+                "/src/payments/b.ts",
+                "CONSUMES",
+                60_000
+            ),
+            LeaseResult::Success { .. }
+        ));
+        assert_eq!(client.memory_report().quarantines.count, 0);
+    }
+}
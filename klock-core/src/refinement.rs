@@ -0,0 +1,65 @@
+//! Symbol-level refinement of file-level conflicts.
+//!
+//! Agents often declare a whole file alongside the symbols they will touch
+//! in it: `PROVIDES /src/api.ts` and `PROVIDES /src/api.ts#createUser`.
+//! Two agents adding different functions to one busy file then conflict
+//! on the file, and the junior dies although their work never meets. With
+//! a `SymbolRefinement` installed (`ConflictEngine::with_symbol_refinement`)
+//! the kernel looks one level down: when both sides of a conflict in a
+//! file declared symbols in that file, none of one side's overlapping the
+//! other's, and both predicates are ones the refinement relaxes, the
+//! conflict is reported as a warning instead of refusing the manifest.
+//! Agents then lease the symbols they declared; direct lease acquisitions
+//! are not refined.
+
+use crate::types::{Predicate, ResourceRef, ResourceType};
+use serde::{Deserialize, Serialize};
+
+fn relaxed_predicates() -> Vec<Predicate> {
+    vec![Predicate::Provides]
+}
+
+/// Which file-level conflicts the kernel may refine by symbol
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolRefinement {
+    /// Conflicts are refined only when both sides' predicates are listed
+    #[serde(default = "relaxed_predicates")]
+    pub predicates: Vec<Predicate>,
+}
+
+impl Default for SymbolRefinement {
+    fn default() -> Self {
+        Self {
+            predicates: relaxed_predicates(),
+        }
+    }
+}
+
+impl SymbolRefinement {
+    /// Whether a conflict between these predicates may be refined
+    pub fn relaxes(&self, held: Predicate, requesting: Predicate) -> bool {
+        self.predicates.contains(&held) && self.predicates.contains(&requesting)
+    }
+}
+
+/// The single file a resource lies in: the file itself, or the file a
+/// file-qualified symbol is defined in. Directories and patterns have none.
+pub fn file_of(resource: &ResourceRef) -> Option<ResourceRef> {
+    let file = match resource.resource_type {
+        ResourceType::File => resource.clone(),
+        ResourceType::Symbol => resource.containing_file()?,
+        _ => return None,
+    };
+    (!file.is_directory() && !file.is_pattern()).then_some(file)
+}
+
+/// Whether two sides' symbols in one file are all disjoint, given each
+/// side's file-qualified symbols there. A side that declared no symbol in
+/// the file may touch any of them, so nothing is disjoint.
+pub fn disjoint(held: &[ResourceRef], requesting: &[ResourceRef]) -> bool {
+    !held.is_empty()
+        && !requesting.is_empty()
+        && held
+            .iter()
+            .all(|h| requesting.iter().all(|r| !h.overlaps(r)))
+}
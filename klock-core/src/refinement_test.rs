@@ -0,0 +1,177 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::conflict::ConflictEngine;
+    use crate::containment::ContainmentRegistry;
+    use crate::refinement::{SymbolRefinement, disjoint, file_of};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::sync::Arc;
+
+    fn intent(agent_id: &str, predicate: Predicate, resource: ResourceRef) -> SPOTriple {
+        SPOTriple {
+            id: format!("{}_{:?}_{}", agent_id, predicate, resource.path),
+            subject: agent_id.to_string(),
+            predicate,
+            object: resource,
+            timestamp: 1000,
+            confidence: Confidence::High,
+            session_id: "s1".to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        }
+    }
+
+    fn manifest(agent_id: &str, intents: &[(Predicate, ResourceRef)]) -> IntentManifest {
+        IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: agent_id.to_string(),
+            intents: intents
+                .iter()
+                .map(|(predicate, resource)| intent(agent_id, *predicate, resource.clone()))
+                .collect(),
+        }
+    }
+
+    fn file() -> ResourceRef {
+        ResourceRef::new(ResourceType::File, "/src/api.ts")
+    }
+
+    fn symbol(name: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::Symbol, format!("/src/api.ts#{}", name))
+    }
+
+    /// A client where `senior` declared and leased the file and
+    /// `createUser` in it
+    fn client(refinement: Option<SymbolRefinement>) -> KlockClient {
+        let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        client.set_conflict_engine(ConflictEngine::default().with_symbol_refinement(refinement));
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+        let acquisition = client.acquire_manifest(
+            &manifest(
+                "senior",
+                &[
+                    (Predicate::Provides, file()),
+                    (Predicate::Provides, symbol("createUser")),
+                ],
+            ),
+            60_000,
+        );
+        assert!(acquisition.acquired());
+        client
+    }
+
+    fn status(
+        client: &mut KlockClient,
+        intents: &[(Predicate, ResourceRef)],
+    ) -> KernelVerdictStatus {
+        client.declare_intent(&manifest("junior", intents)).status
+    }
+
+    #[test]
+    fn test_symbols_in_one_file_are_compared() {
+        assert_eq!(file_of(&symbol("createUser")), Some(file()));
+        assert_eq!(file_of(&file()), Some(file()));
+        assert_eq!(
+            file_of(&ResourceRef::new(ResourceType::File, "/src/")),
+            None
+        );
+        assert_eq!(
+            file_of(&ResourceRef::new(ResourceType::Symbol, "createUser")),
+            None
+        );
+
+        assert!(disjoint(&[symbol("createUser")], &[symbol("deleteUser")]));
+        assert!(!disjoint(&[symbol("User")], &[symbol("User.login")]));
+        assert!(!disjoint(&[], &[symbol("deleteUser")]));
+
+        let refinement = SymbolRefinement::default();
+        assert!(refinement.relaxes(Predicate::Provides, Predicate::Provides));
+        assert!(!refinement.relaxes(Predicate::Provides, Predicate::Mutates));
+
+        // Bare symbols count once containment places them in the file
+        let engine = ConflictEngine::default()
+            .with_containment(
+                ContainmentRegistry::new()
+                    .with("login", "/src/api.ts")
+                    .unwrap(),
+            )
+            .with_symbol_refinement(Some(refinement));
+        let bare = ResourceRef::new(ResourceType::Symbol, "login");
+        assert!(engine.refines(
+            Predicate::Provides,
+            &file(),
+            [&bare],
+            Predicate::Provides,
+            &file(),
+            [&symbol("logout")],
+        ));
+        assert!(!engine.refines(
+            Predicate::Provides,
+            &file(),
+            [&bare],
+            Predicate::Provides,
+            &file(),
+            [&symbol("login")],
+        ));
+        assert!(!ConflictEngine::default().refines(
+            Predicate::Provides,
+            &file(),
+            [&symbol("login")],
+            Predicate::Provides,
+            &file(),
+            [&symbol("logout")],
+        ));
+    }
+
+    #[test]
+    fn test_disjoint_symbols_turn_file_conflicts_into_warnings() {
+        let adding = [
+            (Predicate::Provides, file()),
+            (Predicate::Provides, symbol("deleteUser")),
+        ];
+        // Without refinement the file-level conflict refuses the junior
+        let mut strict = client(None);
+        assert_ne!(status(&mut strict, &adding), KernelVerdictStatus::Granted);
+
+        let mut client = client(Some(SymbolRefinement::default()));
+        let verdict = client.declare_intent(&manifest("junior", &adding));
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        assert!(verdict.blockers.is_empty() && verdict.conflicts.is_empty());
+        assert!(verdict.warnings.iter().all(|w| w.holder == "senior"));
+        assert!(verdict.warnings.iter().any(|w| w.lease_id.is_some()));
+        assert!(verdict.warnings.iter().any(|w| w.lease_id.is_none()));
+    }
+
+    #[test]
+    fn test_overlapping_or_undeclared_symbols_still_conflict() {
+        let mut client = client(Some(SymbolRefinement::default()));
+        // The same symbol, a member of it, or no symbol at all
+        for intents in [
+            vec![
+                (Predicate::Provides, file()),
+                (Predicate::Provides, symbol("createUser")),
+            ],
+            vec![(Predicate::Provides, symbol("createUser.validate"))],
+            vec![(Predicate::Provides, file())],
+        ] {
+            assert_ne!(status(&mut client, &intents), KernelVerdictStatus::Granted);
+        }
+        // Predicates the refinement does not relax
+        let mutating = [
+            (Predicate::Mutates, file()),
+            (Predicate::Mutates, symbol("deleteUser")),
+        ];
+        assert_ne!(status(&mut client, &mutating), KernelVerdictStatus::Granted);
+
+        // A symbol alone, disjoint from the holder's, is granted
+        let only_symbol = [(Predicate::Provides, symbol("deleteUser"))];
+        assert_eq!(
+            status(&mut client, &only_symbol),
+            KernelVerdictStatus::Granted
+        );
+    }
+}
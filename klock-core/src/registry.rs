@@ -0,0 +1,533 @@
+//! Agent registry: the source of truth for agent priorities.
+//!
+//! Priorities belong to agents, not to leases, so they live behind their own
+//! trait. Registries use interior mutability so a single instance can be
+//! shared between `KlockClient` (which registers agents) and the lease store
+//! (whose scheduler reads priorities on every acquire).
+//!
+//! A priority is two-level: a `PriorityClass`, then a timestamp within the
+//! class. The class always dominates, so a critical agent is senior to
+//! every normal one however young it is, and Wait-Die (or any timestamp
+//! rule) applies between agents of the same class. Schedulers also see a
+//! request deadline, which only breaks ties between equal timestamps.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// How urgent an agent's work is. Earlier classes are senior to later ones.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PriorityClass {
+    Critical,
+    #[default]
+    Normal,
+    Background,
+}
+
+impl PriorityClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PriorityClass::Critical => "critical",
+            PriorityClass::Normal => "normal",
+            PriorityClass::Background => "background",
+        }
+    }
+}
+
+impl std::str::FromStr for PriorityClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "critical" => Ok(PriorityClass::Critical),
+            "normal" => Ok(PriorityClass::Normal),
+            "background" => Ok(PriorityClass::Background),
+            _ => Err(format!(
+                "Unknown priority class '{}': use critical, normal or background",
+                s
+            )),
+        }
+    }
+}
+
+/// An agent's priority: its class, then its timestamp within the class
+/// (lower = older = senior). Ordered by class first, so comparing two
+/// priorities compares timestamps only within a class. Equal timestamps are
+/// ordered by deadline, earliest first (EDF), and a priority without one
+/// comes after any with one.
+///
+/// A bare timestamp converts to a normal-class priority, as registered
+/// before classes existed; normal priorities also serialize as a bare
+/// timestamp, and others as `{"class": ..., "timestamp": ...}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "PriorityRepr", into = "PriorityRepr")]
+pub struct Priority {
+    pub class: PriorityClass,
+    pub timestamp: u64,
+    /// When the request being scheduled must be done by (ms since epoch).
+    /// Registered priorities have none; stores stamp it per request (see
+    /// `scheduler::with_deadlines`).
+    pub deadline: Option<u64>,
+}
+
+impl Priority {
+    pub fn new(class: PriorityClass, timestamp: u64) -> Self {
+        Self {
+            class,
+            timestamp,
+            deadline: None,
+        }
+    }
+
+    /// The same priority for a request due by `deadline`
+    pub fn with_deadline(self, deadline: Option<u64>) -> Self {
+        Self { deadline, ..self }
+    }
+
+    fn sort_key(&self) -> (PriorityClass, u64, bool, Option<u64>) {
+        (
+            self.class,
+            self.timestamp,
+            self.deadline.is_none(),
+            self.deadline,
+        )
+    }
+
+    /// The same class at another timestamp. Aging, ceilings and health
+    /// penalties move an agent within its class, never across classes.
+    pub fn with_timestamp(self, timestamp: u64) -> Self {
+        Self { timestamp, ..self }
+    }
+
+    /// Junior to every registered agent: the youngest background agent
+    pub fn lowest() -> Self {
+        Self::new(PriorityClass::Background, u64::MAX)
+    }
+}
+
+impl From<u64> for Priority {
+    fn from(timestamp: u64) -> Self {
+        Self::new(PriorityClass::Normal, timestamp)
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Normal priorities print as their timestamp, others as `class:timestamp`,
+/// followed by the deadline if there is one
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.class {
+            PriorityClass::Normal => write!(f, "{}", self.timestamp)?,
+            class => write!(f, "{}:{}", class.as_str(), self.timestamp)?,
+        }
+        match self.deadline {
+            Some(deadline) => write!(f, ", due {}", deadline),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PriorityRepr {
+    Timestamp(u64),
+    Classed {
+        #[serde(default)]
+        class: PriorityClass,
+        timestamp: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        deadline: Option<u64>,
+    },
+}
+
+impl From<PriorityRepr> for Priority {
+    fn from(repr: PriorityRepr) -> Self {
+        match repr {
+            PriorityRepr::Timestamp(timestamp) => timestamp.into(),
+            PriorityRepr::Classed {
+                class,
+                timestamp,
+                deadline,
+            } => Self::new(class, timestamp).with_deadline(deadline),
+        }
+    }
+}
+
+impl From<Priority> for PriorityRepr {
+    fn from(priority: Priority) -> Self {
+        match (priority.class, priority.deadline) {
+            (PriorityClass::Normal, None) => PriorityRepr::Timestamp(priority.timestamp),
+            (class, deadline) => PriorityRepr::Classed {
+                class,
+                timestamp: priority.timestamp,
+                deadline,
+            },
+        }
+    }
+}
+
+/// What happens to an agent that requests or holds something without a
+/// registered priority, which Wait-Die and Wound-Wait cannot place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnregisteredAgentPolicy {
+    /// Register it on its first request as a normal-class agent, as young
+    /// as that request. Holders still unknown count as lowest.
+    AutoRegister,
+    /// Refuse its requests, and refuse any request that conflicts with a
+    /// lease it holds rather than guess who is senior
+    Reject,
+    /// Give it `Priority::lowest`: it yields to every registered agent,
+    /// and its leases are the first to be waited on or wounded
+    #[default]
+    TreatAsLowest,
+}
+
+impl UnregisteredAgentPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnregisteredAgentPolicy::AutoRegister => "auto-register",
+            UnregisteredAgentPolicy::Reject => "reject",
+            UnregisteredAgentPolicy::TreatAsLowest => "treat-as-lowest",
+        }
+    }
+}
+
+impl std::str::FromStr for UnregisteredAgentPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto-register" => Ok(UnregisteredAgentPolicy::AutoRegister),
+            "reject" => Ok(UnregisteredAgentPolicy::Reject),
+            "treat-as-lowest" => Ok(UnregisteredAgentPolicy::TreatAsLowest),
+            _ => Err(format!(
+                "Unknown unregistered-agent policy '{}': use auto-register, reject or treat-as-lowest",
+                s
+            )),
+        }
+    }
+}
+
+/// Defines the contract for agent priority backends.
+pub trait AgentRegistry: Send + Sync {
+    /// Register (or re-register) an agent with a priority
+    fn register(&self, agent_id: String, priority: Priority);
+
+    /// Look up one agent's priority
+    fn priority(&self, agent_id: &str) -> Option<Priority> {
+        self.priorities().get(agent_id).copied()
+    }
+
+    /// Full priority map, as consumed by schedulers
+    fn priorities(&self) -> HashMap<String, Priority>;
+
+    /// Forget an agent. Returns whether it was registered; registries that
+    /// cannot forget keep it and return false.
+    fn unregister(&self, _agent_id: &str) -> bool {
+        false
+    }
+}
+
+/// Volatile registry kept in process memory.
+#[derive(Debug, Default)]
+pub struct InMemoryAgentRegistry {
+    priorities: RwLock<HashMap<String, Priority>>,
+}
+
+impl InMemoryAgentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AgentRegistry for InMemoryAgentRegistry {
+    fn register(&self, agent_id: String, priority: Priority) {
+        self.priorities.write().unwrap().insert(agent_id, priority);
+    }
+
+    fn priority(&self, agent_id: &str) -> Option<Priority> {
+        self.priorities.read().unwrap().get(agent_id).copied()
+    }
+
+    fn priorities(&self) -> HashMap<String, Priority> {
+        self.priorities.read().unwrap().clone()
+    }
+
+    fn unregister(&self, agent_id: &str) -> bool {
+        self.priorities.write().unwrap().remove(agent_id).is_some()
+    }
+}
+
+/// Registry persisted in the `agent_priorities` table of a SQLite database.
+///
+/// Priorities are cached in memory for fast scheduler access and written
+/// through on registration.
+#[cfg(feature = "sqlite")]
+pub struct SqliteAgentRegistry {
+    conn: std::sync::Mutex<rusqlite::Connection>,
+    cache: RwLock<HashMap<String, Priority>>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteAgentRegistry {
+    /// Open (or create) the registry table in the SQLite database at `path`.
+    pub fn open(path: &str) -> Result<Self, rusqlite::Error> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS agent_priorities (
+                agent_id TEXT PRIMARY KEY,
+                priority INTEGER NOT NULL,
+                class TEXT NOT NULL DEFAULT 'normal'
+            );",
+        )?;
+        // Tables created before priority classes registered everyone as normal
+        if conn
+            .prepare("SELECT class FROM agent_priorities LIMIT 0")
+            .is_err()
+        {
+            conn.execute_batch(
+                "ALTER TABLE agent_priorities ADD COLUMN class TEXT NOT NULL DEFAULT 'normal'",
+            )?;
+        }
+
+        let mut cache = HashMap::new();
+        {
+            let mut stmt =
+                conn.prepare("SELECT agent_id, priority, class FROM agent_priorities")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, u64>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?;
+            for row in rows {
+                let (agent_id, timestamp, class) = row?;
+                let class = class.parse().unwrap_or_default();
+                cache.insert(agent_id, Priority::new(class, timestamp));
+            }
+        }
+
+        Ok(Self {
+            conn: std::sync::Mutex::new(conn),
+            cache: RwLock::new(cache),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl AgentRegistry for SqliteAgentRegistry {
+    fn register(&self, agent_id: String, priority: Priority) {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO agent_priorities (agent_id, priority, class)
+                 VALUES (?1, ?2, ?3)",
+                rusqlite::params![agent_id, priority.timestamp, priority.class.as_str()],
+            )
+            .ok();
+        self.cache.write().unwrap().insert(agent_id, priority);
+    }
+
+    fn priority(&self, agent_id: &str) -> Option<Priority> {
+        self.cache.read().unwrap().get(agent_id).copied()
+    }
+
+    fn priorities(&self) -> HashMap<String, Priority> {
+        self.cache.read().unwrap().clone()
+    }
+
+    fn unregister(&self, agent_id: &str) -> bool {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "DELETE FROM agent_priorities WHERE agent_id = ?1",
+                rusqlite::params![agent_id],
+            )
+            .ok();
+        self.cache.write().unwrap().remove(agent_id).is_some()
+    }
+}
+
+/// Registry backed by a remote HTTP priority provider.
+///
+/// The provider must answer `GET <url>` with a JSON object mapping agent IDs
+/// to priorities, as timestamps or with a class (`{"refactor-bot": 100,
+/// "pager-bot": {"class": "critical", "timestamp": 300}}`), accept
+/// `POST <url>` with `{"agent_id": ..., "priority": ..., "class": ...}`
+/// and `DELETE <url>` with `{"agent_id": ...}`. The map is cached and
+/// refreshed at most every `refresh_ms`, fetched without holding the cache
+/// so lookups are not held up by a slow provider; if the provider is
+/// unreachable the last known map keeps being served.
+///
+/// The cache only changes once the provider has accepted a write; a
+/// rejected one is logged and leaves the agent's priority as it was.
+/// Writes accepted while a fetch is in flight are applied again on top of
+/// the fetched map, which may predate them.
+#[cfg(feature = "http-registry")]
+pub struct HttpAgentRegistry {
+    url: String,
+    refresh_ms: u64,
+    agent: ureq::Agent,
+    cache: RwLock<HttpCache>,
+}
+
+#[cfg(feature = "http-registry")]
+#[derive(Default)]
+pub(crate) struct HttpCache {
+    fetched_at: Option<std::time::Instant>,
+    pub(crate) priorities: HashMap<String, Priority>,
+    /// Accepted writes so far
+    pub(crate) writes: u64,
+    /// Accepted writes a fetch may not reflect yet, by agent: the write
+    /// count after each, and the priority it left (`None` if unregistered)
+    pending: HashMap<String, (u64, Option<Priority>)>,
+    /// Write count when the applied map's fetch started
+    fetched_after: u64,
+}
+
+#[cfg(feature = "http-registry")]
+impl HttpCache {
+    pub(crate) fn write(
+        &mut self,
+        agent_id: String,
+        priority: Option<Priority>,
+    ) -> Option<Priority> {
+        self.writes += 1;
+        self.pending
+            .insert(agent_id.clone(), (self.writes, priority));
+        match priority {
+            Some(priority) => self.priorities.insert(agent_id, priority),
+            None => self.priorities.remove(&agent_id),
+        }
+    }
+
+    /// Serve `fresh`, fetched from a fetch started after `writes_before`
+    /// writes, keeping the writes accepted since
+    pub(crate) fn apply_fetch(&mut self, mut fresh: HashMap<String, Priority>, writes_before: u64) {
+        if writes_before < self.fetched_after {
+            // A fetch started later has been applied already
+            return;
+        }
+        self.pending.retain(|_, (write, _)| *write > writes_before);
+        for (agent_id, (_, priority)) in &self.pending {
+            match priority {
+                Some(priority) => fresh.insert(agent_id.clone(), *priority),
+                None => fresh.remove(agent_id),
+            };
+        }
+        self.priorities = fresh;
+        self.fetched_after = writes_before;
+    }
+}
+
+#[cfg(feature = "http-registry")]
+impl HttpAgentRegistry {
+    pub fn new(url: impl Into<String>, refresh_ms: u64) -> Self {
+        Self {
+            url: url.into(),
+            refresh_ms,
+            agent: ureq::AgentBuilder::new()
+                .timeout(std::time::Duration::from_secs(5))
+                .build(),
+            cache: RwLock::new(HttpCache::default()),
+        }
+    }
+
+    fn fetch(&self) -> Option<HashMap<String, Priority>> {
+        let body = self.agent.get(&self.url).call().ok()?.into_string().ok()?;
+        serde_json::from_str(&body).ok()
+    }
+
+    fn refresh_if_stale(&self) {
+        let writes_before = {
+            let mut cache = self.cache.write().unwrap();
+            let stale = match cache.fetched_at {
+                Some(at) => at.elapsed().as_millis() as u64 >= self.refresh_ms,
+                None => true,
+            };
+            if !stale {
+                return;
+            }
+            // Claim the refresh, so concurrent lookups serve the cached map
+            // instead of fetching too
+            cache.fetched_at = Some(std::time::Instant::now());
+            cache.writes
+        };
+
+        if let Some(fresh) = self.fetch() {
+            self.cache
+                .write()
+                .unwrap()
+                .apply_fetch(fresh, writes_before);
+        }
+    }
+
+    /// Send a write to the provider, logging it if it is refused
+    fn send(&self, method: &str, agent_id: &str, body: serde_json::Value) -> bool {
+        match self
+            .agent
+            .request(method, &self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string())
+        {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::error!(
+                    "Priority provider refused {} for agent {}: {}",
+                    method,
+                    agent_id,
+                    e
+                );
+                false
+            }
+        }
+    }
+}
+
+#[cfg(feature = "http-registry")]
+impl AgentRegistry for HttpAgentRegistry {
+    fn register(&self, agent_id: String, priority: Priority) {
+        let body = serde_json::json!({
+            "agent_id": agent_id,
+            "priority": priority.timestamp,
+            "class": priority.class,
+        });
+        if self.send("POST", &agent_id, body) {
+            self.cache.write().unwrap().write(agent_id, Some(priority));
+        }
+    }
+
+    fn priorities(&self) -> HashMap<String, Priority> {
+        self.refresh_if_stale();
+        self.cache.read().unwrap().priorities.clone()
+    }
+
+    fn unregister(&self, agent_id: &str) -> bool {
+        let body = serde_json::json!({ "agent_id": agent_id });
+        self.send("DELETE", agent_id, body)
+            && self
+                .cache
+                .write()
+                .unwrap()
+                .write(agent_id.to_string(), None)
+                .is_some()
+    }
+}
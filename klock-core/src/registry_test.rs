@@ -0,0 +1,291 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::{KlockClient, LeaseStoreExt};
+    use crate::clock::ManualClock;
+    use crate::codes::ReasonCode;
+    use crate::infrastructure::LeaseStore;
+    use crate::infrastructure_in_memory::InMemoryLeaseStore;
+    use crate::registry::{
+        AgentRegistry, InMemoryAgentRegistry, Priority, PriorityClass, UnregisteredAgentPolicy,
+    };
+    use crate::scheduler::WoundWaitScheduler;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_in_memory_registry_round_trip() {
+        let registry = InMemoryAgentRegistry::new();
+        registry.register("agent_a".to_string(), 100.into());
+        registry.register("agent_a".to_string(), 150.into());
+
+        assert_eq!(registry.priority("agent_a"), Some(150.into()));
+        assert_eq!(registry.priority("agent_b"), None);
+        assert_eq!(registry.priorities().len(), 1);
+    }
+
+    #[test]
+    fn test_store_adapters_delegate_to_registry() {
+        let registry = Arc::new(InMemoryAgentRegistry::new());
+        let mut store = InMemoryLeaseStore::new();
+        store.set_registry(registry.clone());
+
+        LeaseStoreExt::register_agent_priority(&mut store, "agent_a".to_string(), 42.into());
+        assert_eq!(registry.priority("agent_a"), Some(42.into()));
+        assert_eq!(
+            LeaseStoreExt::get_priorities(&store).get("agent_a"),
+            Some(&42.into())
+        );
+    }
+
+    #[test]
+    fn test_store_scheduler_reads_shared_registry() {
+        let registry = Arc::new(InMemoryAgentRegistry::new());
+        registry.register("older".to_string(), 100.into());
+        registry.register("younger".to_string(), 200.into());
+
+        let mut store = InMemoryLeaseStore::new();
+        store.set_registry(registry);
+
+        let res = ResourceRef::new(ResourceType::File, "/test");
+        let _ = store.acquire("older", "s1", res.clone(), Predicate::Mutates, 5000, 1000);
+        assert!(matches!(
+            store.acquire("younger", "s2", res, Predicate::Mutates, 5000, 1000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_client_set_registry_preserves_register_agent() {
+        let registry = Arc::new(InMemoryAgentRegistry::new());
+        let mut client = KlockClient::new();
+        client.set_registry(registry.clone());
+
+        client.register_agent("agent_a", 7);
+        assert_eq!(registry.priority("agent_a"), Some(7.into()));
+        assert_eq!(client.agent_priority("agent_a"), Some(7.into()));
+    }
+
+    #[test]
+    fn test_promoted_agent_is_judged_at_new_priority() {
+        let mut client = KlockClient::new();
+        client.register_agent("holder", 100);
+        client.register_agent("worker", 200);
+        client.acquire_lease("holder", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+
+        let acquire = |client: &mut KlockClient| match client
+            .acquire_lease("worker", "s2", "FILE", "/a.ts", "MUTATES", 60_000)
+        {
+            LeaseResult::Failure { reason, .. } => reason,
+            other => panic!("Expected a refusal, got {:?}", other),
+        };
+        assert_eq!(acquire(&mut client), LeaseFailureReason::Die);
+
+        // Now senior to the holder, the worker waits instead of dying
+        assert_eq!(client.update_agent_priority("worker", 50), Some(200));
+        assert_eq!(client.agent_priority("worker"), Some(50.into()));
+        assert_eq!(acquire(&mut client), LeaseFailureReason::Wait);
+
+        assert_eq!(client.update_agent_priority("stranger", 10), None);
+        assert_eq!(client.agent_priority("stranger"), None);
+    }
+
+    #[test]
+    fn test_priority_class_survives_updates_and_serde() {
+        let mut client = KlockClient::new();
+        client.register_agent_in_class("pager", PriorityClass::Critical, 900);
+        assert_eq!(client.update_agent_priority("pager", 800), Some(900));
+        let priority = client.agent_priority("pager").unwrap();
+        assert_eq!(priority, Priority::new(PriorityClass::Critical, 800));
+        assert!(priority < Priority::from(1));
+
+        // Normal priorities stay bare timestamps on the wire
+        assert_eq!(
+            serde_json::to_value(Priority::from(100)).unwrap(),
+            serde_json::json!(100)
+        );
+        assert_eq!(
+            serde_json::to_value(priority).unwrap(),
+            serde_json::json!({ "class": "critical", "timestamp": 800 })
+        );
+        let parsed: Priority =
+            serde_json::from_value(serde_json::json!({ "timestamp": 5 })).unwrap();
+        assert_eq!(parsed, Priority::from(5));
+        assert!("urgent".parse::<PriorityClass>().is_err());
+    }
+
+    fn failure(result: LeaseResult) -> LeaseFailureReason {
+        match result {
+            LeaseResult::Failure { reason, .. } => reason,
+            other => panic!("Expected a failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unregistered_agents_rank_lowest_by_default() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        assert_eq!(
+            client.unregistered_agent_policy(),
+            UnregisteredAgentPolicy::TreatAsLowest
+        );
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+        assert!(matches!(
+            client.acquire_lease("ghost", "g1", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        clock.advance(1);
+        client.acquire_lease("junior", "j1", "FILE", "/b.ts", "MUTATES", 60_000);
+        clock.advance(1);
+
+        // An unknown holder ranks lowest, so the senior requester waits
+        // under Wait-Die
+        assert_eq!(
+            failure(client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000)),
+            LeaseFailureReason::Wait
+        );
+        // The unknown agent is junior to everyone, the youngest included
+        assert_eq!(
+            failure(client.acquire_lease("ghost", "g1", "FILE", "/b.ts", "MUTATES", 60_000)),
+            LeaseFailureReason::Die
+        );
+        // Under Wound-Wait the lowest holder is the one wounded
+        client.set_scheduler(Arc::new(WoundWaitScheduler));
+        assert!(matches!(
+            client.acquire_lease("junior", "j1", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        assert_eq!(client.agent_priority("ghost"), None);
+    }
+
+    #[test]
+    fn test_unregistered_agents_auto_registered_or_rejected() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("senior", 100);
+        client.acquire_lease("ghost", "g1", "FILE", "/a.ts", "MUTATES", 60_000);
+        clock.advance(1);
+
+        client.set_unregistered_agent_policy("auto-register".parse().unwrap());
+        assert!(matches!(
+            client.acquire_lease("newcomer", "n1", "FILE", "/b.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        assert_eq!(
+            client.agent_priority("newcomer"),
+            Some(Priority::from(1001))
+        );
+
+        client.set_unregistered_agent_policy(UnregisteredAgentPolicy::Reject);
+        assert_eq!(
+            failure(client.acquire_lease("stranger", "x1", "FILE", "/c.ts", "MUTATES", 60_000)),
+            LeaseFailureReason::UnregisteredAgent
+        );
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "x1".to_string(),
+            agent_id: "stranger".to_string(),
+            intents: vec![SPOTriple {
+                id: "i1".to_string(),
+                subject: "stranger".to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, "/c.ts"),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "x1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Rejected);
+        assert_eq!(verdict.code, Some(ReasonCode::UnregisteredAgent));
+        assert!(
+            client
+                .pre_acquire(
+                    "stranger",
+                    "x1",
+                    &[(
+                        ResourceRef::new(ResourceType::File, "/c.ts"),
+                        Predicate::Mutates
+                    )],
+                    60_000,
+                    120_000,
+                )
+                .is_err()
+        );
+        // Nobody guesses who is senior to an unknown holder
+        match client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000) {
+            LeaseResult::Failure {
+                reason, holders, ..
+            } => {
+                assert_eq!(reason, LeaseFailureReason::Die);
+                assert_eq!(holders[0].agent_id, "ghost");
+            }
+            other => panic!("Expected a failure, got {:?}", other),
+        }
+        assert!("ignore".parse::<UnregisteredAgentPolicy>().is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_registry_persists_across_reopen() {
+        use crate::registry::SqliteAgentRegistry;
+
+        let path =
+            std::env::temp_dir().join(format!("klock_registry_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        {
+            let registry = SqliteAgentRegistry::open(path).unwrap();
+            registry.register("agent_a".to_string(), 100.into());
+            registry.register(
+                "agent_b".to_string(),
+                Priority::new(PriorityClass::Critical, 300),
+            );
+        }
+        let registry = SqliteAgentRegistry::open(path).unwrap();
+        assert_eq!(registry.priority("agent_a"), Some(100.into()));
+        assert_eq!(
+            registry.priority("agent_b"),
+            Some(Priority::new(PriorityClass::Critical, 300))
+        );
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "http-registry")]
+    #[test]
+    fn test_http_cache_keeps_writes_made_during_a_fetch() {
+        use crate::registry::HttpCache;
+
+        let mut cache = HttpCache::default();
+        cache.write("a".to_string(), Some(Priority::from(100)));
+        // A fetch starts after the first write...
+        let writes_before = cache.writes;
+        cache.write("b".to_string(), Some(Priority::from(200)));
+        cache.write("c".to_string(), None);
+        // ...and returns a map from before the later ones
+        let fetched: std::collections::HashMap<String, Priority> = [
+            ("a".to_string(), Priority::from(150)),
+            ("c".to_string(), Priority::from(300)),
+        ]
+        .into();
+        cache.apply_fetch(fetched.clone(), writes_before);
+
+        // The provider's view of "a" wins; "b" and "c" keep the local writes
+        assert_eq!(cache.priorities.get("a"), Some(&Priority::from(150)));
+        assert_eq!(cache.priorities.get("b"), Some(&Priority::from(200)));
+        assert!(!cache.priorities.contains_key("c"));
+
+        // A fetch that started earlier than the applied one is dropped
+        cache.apply_fetch(fetched, writes_before - 1);
+        assert_eq!(cache.priorities.get("b"), Some(&Priority::from(200)));
+    }
+}
@@ -0,0 +1,86 @@
+//! Checked batch release.
+//!
+//! An agent finishing a task releases everything it held at once, and often
+//! wants to know the protection held up while it worked: that no lease
+//! changed under it, and that nobody slipped in a conflicting lease (after
+//! an expiry, a revocation or a rename) before it was done.
+//! `KlockClient::release_many_checked` checks such `ReleaseCondition`s and
+//! releases the batch only if every lease in it can be released and every
+//! condition holds; otherwise it releases nothing and says, per lease, why.
+
+use crate::codes::ReasonCode;
+use crate::types::LeaseState;
+use serde::{Deserialize, Serialize};
+
+/// A post-condition of a checked release, on one lease of the batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "condition", rename_all = "snake_case")]
+pub enum ReleaseCondition {
+    /// The lease is still at `revision`: nothing renewed, activated or
+    /// otherwise changed it since the caller read it
+    Revision { lease_id: String, revision: u64 },
+    /// No other session acquired a lease conflicting with this one on an
+    /// overlapping resource since this lease was acquired, whether or not
+    /// it is still held
+    NoConflictingLease { lease_id: String },
+}
+
+impl ReleaseCondition {
+    /// The lease the condition is on
+    pub fn lease_id(&self) -> &str {
+        match self {
+            ReleaseCondition::Revision { lease_id, .. }
+            | ReleaseCondition::NoConflictingLease { lease_id } => lease_id,
+        }
+    }
+}
+
+/// What a checked release did with one lease
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ReleaseStatus {
+    Released,
+    /// No lease has the ID
+    NotFound,
+    /// The lease had already ended
+    NotHeld {
+        state: LeaseState,
+    },
+    /// One of the lease's conditions failed
+    ConditionFailed {
+        reason: String,
+    },
+    /// The lease could have been released, but another in the batch could
+    /// not, so it is still held
+    Aborted,
+}
+
+impl ReleaseStatus {
+    /// The catalog code of a lease that blocked the batch
+    pub fn code(&self) -> Option<ReasonCode> {
+        match self {
+            ReleaseStatus::Released | ReleaseStatus::Aborted => None,
+            ReleaseStatus::NotFound | ReleaseStatus::NotHeld { .. } => {
+                Some(ReasonCode::LeaseNotFound)
+            }
+            ReleaseStatus::ConditionFailed { .. } => Some(ReasonCode::PostconditionFailed),
+        }
+    }
+}
+
+/// One lease's part in a checked release
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaseRelease {
+    pub lease_id: String,
+    #[serde(flatten)]
+    pub status: ReleaseStatus,
+}
+
+/// The outcome of a checked release: all of the batch or none of it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchRelease {
+    /// Whether the leases were released; if not, none were
+    pub released: bool,
+    /// Each lease of the batch, in the order given
+    pub results: Vec<LeaseRelease>,
+}
@@ -0,0 +1,173 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::{Clock, ManualClock};
+    use crate::release::{LeaseRelease, ReleaseCondition, ReleaseStatus};
+    use crate::types::{LeaseResult, LeaseState, Predicate, ResourceRef, ResourceType};
+    use std::sync::Arc;
+
+    fn lease_id(result: LeaseResult) -> String {
+        match result {
+            LeaseResult::Success { lease } => lease.id,
+            other => panic!("Expected success, got {:?}", other),
+        }
+    }
+
+    fn statuses(results: &[LeaseRelease]) -> Vec<&ReleaseStatus> {
+        results.iter().map(|r| &r.status).collect()
+    }
+
+    fn setup() -> (KlockClient, Arc<ManualClock>, Vec<String>) {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("a", 100);
+        client.register_agent("b", 200);
+        let mut ids = Vec::new();
+        for path in ["/a.ts", "/b.ts"] {
+            ids.push(lease_id(
+                client.acquire_lease("a", "s1", "FILE", path, "MUTATES", 60_000),
+            ));
+            clock.advance(1);
+        }
+        (client, clock, ids)
+    }
+
+    fn state(client: &KlockClient, lease_id: &str) -> LeaseState {
+        client.get_lease(lease_id).unwrap().state
+    }
+
+    #[test]
+    fn test_batch_releases_when_conditions_hold() {
+        let (mut client, _, ids) = setup();
+        let conditions = [
+            ReleaseCondition::Revision {
+                lease_id: ids[0].clone(),
+                revision: 0,
+            },
+            ReleaseCondition::NoConflictingLease {
+                lease_id: ids[1].clone(),
+            },
+        ];
+        let batch = client.release_many_checked(&ids, &conditions).unwrap();
+        assert!(batch.released);
+        assert_eq!(
+            statuses(&batch.results),
+            [&ReleaseStatus::Released, &ReleaseStatus::Released]
+        );
+        assert!(client.get_active_leases().is_empty());
+
+        // Released leases are not held any more
+        let batch = client.release_many_checked(&ids[..1], &[]).unwrap();
+        assert!(!batch.released);
+        assert_eq!(
+            batch.results[0].status,
+            ReleaseStatus::NotHeld {
+                state: LeaseState::Released
+            }
+        );
+    }
+
+    #[test]
+    fn test_failed_condition_releases_nothing() {
+        let (mut client, clock, mut ids) = setup();
+        assert!(client.heartbeat_lease(&ids[0], clock.now_ms()));
+        ids.push("missing".to_string());
+        let batch = client
+            .release_many_checked(
+                &ids,
+                &[ReleaseCondition::Revision {
+                    lease_id: ids[0].clone(),
+                    revision: 0,
+                }],
+            )
+            .unwrap();
+        assert!(!batch.released);
+        assert_eq!(
+            statuses(&batch.results),
+            [
+                &ReleaseStatus::ConditionFailed {
+                    reason: "Lease is at revision 1, not 0".to_string()
+                },
+                &ReleaseStatus::Aborted,
+                &ReleaseStatus::NotFound,
+            ]
+        );
+        assert_eq!(client.get_active_leases().len(), 2);
+
+        assert_eq!(
+            serde_json::to_value(&batch.results[0]).unwrap(),
+            serde_json::json!({
+                "lease_id": ids[0],
+                "status": "condition_failed",
+                "reason": "Lease is at revision 1, not 0",
+            })
+        );
+    }
+
+    #[test]
+    fn test_conflicting_leases_that_came_and_went_are_caught() {
+        let (mut client, clock, ids) = setup();
+        // A reservation blocks no one, so b gets in while a prepares
+        let reserved = client
+            .pre_acquire(
+                "a",
+                "s1",
+                &[(
+                    ResourceRef::new(ResourceType::File, "/c.ts"),
+                    Predicate::Mutates,
+                )],
+                60_000,
+                120_000,
+            )
+            .unwrap();
+        clock.advance(1);
+        let intruder =
+            lease_id(client.acquire_lease("b", "s2", "FILE", "/c.ts", "MUTATES", 60_000));
+        assert!(client.release_lease(&intruder));
+        // The agent's own session does not count
+        clock.advance(1);
+        client.acquire_lease("a", "s1", "FILE", "/a.ts", "CONSUMES", 60_000);
+
+        let batch = vec![ids[0].clone(), reserved[0].id.clone()];
+        let no_conflicts: Vec<_> = batch
+            .iter()
+            .map(|lease_id| ReleaseCondition::NoConflictingLease {
+                lease_id: lease_id.clone(),
+            })
+            .collect();
+        let result = client.release_many_checked(&batch, &no_conflicts).unwrap();
+        assert!(!result.released);
+        assert_eq!(result.results[0].status, ReleaseStatus::Aborted);
+        let ReleaseStatus::ConditionFailed { reason } = &result.results[1].status else {
+            panic!("Expected a failed condition, got {:?}", result.results[1]);
+        };
+        assert!(reason.starts_with("'b' acquired a conflicting lease on FILE:/c.ts"));
+        assert_eq!(state(&client, &reserved[0].id), LeaseState::Pending);
+        assert_eq!(state(&client, &ids[0]), LeaseState::Active);
+
+        // Without the condition the same batch goes through
+        assert!(client.release_many_checked(&batch, &[]).unwrap().released);
+        assert_eq!(state(&client, &reserved[0].id), LeaseState::Released);
+    }
+
+    #[test]
+    fn test_malformed_batches_are_refused() {
+        let (mut client, _, ids) = setup();
+        assert!(
+            client
+                .release_many_checked(&[ids[0].clone(), ids[0].clone()], &[])
+                .is_err()
+        );
+        assert!(
+            client
+                .release_many_checked(
+                    &ids[..1],
+                    &[ReleaseCondition::NoConflictingLease {
+                        lease_id: ids[1].clone()
+                    }]
+                )
+                .is_err()
+        );
+        assert_eq!(client.get_active_leases().len(), 2);
+    }
+}
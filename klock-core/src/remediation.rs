@@ -0,0 +1,92 @@
+//! Suggested remediation for refused manifests.
+//!
+//! A Wait or Die verdict already says who is in the way; the kernel turns
+//! that into something an agent can act on. Each blocking lease becomes a
+//! lease to wait for, with when it runs out; each blocking intent names the
+//! session to wait for; and when everything in the way lies inside the
+//! requested resource, the verdict suggests declaring a narrower one, e.g.
+//! the symbols actually changed instead of the whole file.
+
+use crate::conflict::{ConflictDetail, ResourceRelation};
+use crate::types::{Lease, ResourceRef, SPOTriple};
+use serde::{Deserialize, Serialize};
+
+/// One thing a refused agent can do to get its manifest granted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Suggestion {
+    /// Declare again once the lease is released or runs out at
+    /// `expires_at` (unless renewed)
+    WaitForLease {
+        lease_id: String,
+        holder: String,
+        resource: ResourceRef,
+        expires_at: u64,
+    },
+    /// Declare again once the holder's session releases or withdraws its
+    /// intent on `resource`
+    WaitForSession {
+        holder: String,
+        session_id: String,
+        resource: ResourceRef,
+    },
+    /// Declare a narrower resource inside `resource` that leaves out
+    /// `taken`, the parts of it others hold
+    Narrow {
+        intent_id: String,
+        resource: ResourceRef,
+        taken: Vec<ResourceRef>,
+    },
+}
+
+/// Suggestions for `intent`, refused over `blockers`, with `leases` the
+/// active leases it was judged against
+pub fn suggest(
+    intent: &SPOTriple,
+    blockers: &[ConflictDetail],
+    leases: &[Lease],
+) -> Vec<Suggestion> {
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    for blocker in blockers {
+        let suggestion = match &blocker.lease_id {
+            Some(lease_id) => {
+                let Some(lease) = leases.iter().find(|lease| &lease.id == lease_id) else {
+                    continue;
+                };
+                Suggestion::WaitForLease {
+                    lease_id: lease_id.clone(),
+                    holder: blocker.holder.clone(),
+                    resource: blocker.resource.clone(),
+                    expires_at: lease.expires_at,
+                }
+            }
+            None => Suggestion::WaitForSession {
+                holder: blocker.holder.clone(),
+                session_id: blocker.session_id.clone(),
+                resource: blocker.resource.clone(),
+            },
+        };
+        if !suggestions.contains(&suggestion) {
+            suggestions.push(suggestion);
+        }
+    }
+
+    if !blockers.is_empty()
+        && blockers
+            .iter()
+            .all(|blocker| blocker.relation == ResourceRelation::Descendant)
+    {
+        let mut taken: Vec<ResourceRef> = Vec::new();
+        for blocker in blockers {
+            if !taken.contains(&blocker.resource) {
+                taken.push(blocker.resource.clone());
+            }
+        }
+        suggestions.push(Suggestion::Narrow {
+            intent_id: intent.id.clone(),
+            resource: intent.object.clone(),
+            taken,
+        });
+    }
+    suggestions
+}
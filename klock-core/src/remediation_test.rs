@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::remediation::Suggestion;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::sync::Arc;
+
+    fn manifest(agent_id: &str, resource: ResourceRef) -> IntentManifest {
+        IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: agent_id.to_string(),
+            intents: vec![SPOTriple {
+                id: format!("t_{}", agent_id),
+                subject: agent_id.to_string(),
+                predicate: Predicate::Mutates,
+                object: resource,
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        }
+    }
+
+    fn file() -> ResourceRef {
+        ResourceRef::new(ResourceType::File, "/src/api.ts")
+    }
+
+    fn client() -> KlockClient {
+        let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+        client
+    }
+
+    #[test]
+    fn test_wait_names_the_lease_to_wait_for() {
+        let mut client = client();
+        let acquisition = client.acquire_manifest(&manifest("junior", file()), 60_000);
+        assert!(acquisition.acquired());
+
+        let verdict = client.declare_intent(&manifest("senior", file()));
+        assert_eq!(verdict.status, KernelVerdictStatus::Wait);
+        assert_eq!(
+            verdict.suggestions,
+            [
+                Suggestion::WaitForSession {
+                    holder: "junior".to_string(),
+                    session_id: "s1".to_string(),
+                    resource: file(),
+                },
+                Suggestion::WaitForLease {
+                    lease_id: acquisition.leases[0].id.clone(),
+                    holder: "junior".to_string(),
+                    resource: file(),
+                    expires_at: 61_000,
+                },
+            ]
+        );
+
+        // Granted verdicts suggest nothing, and leave the field out
+        let verdict = client.declare_intent(&manifest(
+            "senior",
+            ResourceRef::new(ResourceType::File, "/src/other.ts"),
+        ));
+        assert!(verdict.suggestions.is_empty());
+        assert!(serde_json::to_value(&verdict).unwrap()["suggestions"].is_null());
+    }
+
+    #[test]
+    fn test_die_inside_the_request_suggests_narrowing_it() {
+        let mut client = client();
+        let symbol = ResourceRef::new(ResourceType::Symbol, "/src/api.ts#createUser");
+        let acquisition = client.acquire_manifest(&manifest("senior", symbol.clone()), 60_000);
+        assert!(acquisition.acquired());
+
+        let verdict = client.declare_intent(&manifest("junior", file()));
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(
+            verdict.suggestions.last(),
+            Some(&Suggestion::Narrow {
+                intent_id: "t_junior".to_string(),
+                resource: file(),
+                taken: vec![symbol.clone()],
+            })
+        );
+        let json = serde_json::to_value(&verdict).unwrap();
+        assert_eq!(json["suggestions"][1]["kind"], "wait_for_lease");
+        assert_eq!(json["suggestions"][2]["kind"], "narrow");
+
+        // The narrower request goes through
+        let verdict = client.declare_intent(&manifest(
+            "junior",
+            ResourceRef::new(ResourceType::Symbol, "/src/api.ts#deleteUser"),
+        ));
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+    }
+}
@@ -1,8 +1,12 @@
+use crate::backoff::BackoffPolicy;
 use crate::conflict::ConflictEngine;
-use crate::types::{Lease, Predicate, ResourceRef};
+use crate::registry::Priority;
+use crate::types::{Holder, Lease, Predicate, ResourceRef};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VerdictStatus {
     Granted,
     Wait,
@@ -13,92 +17,603 @@ pub enum VerdictStatus {
 pub struct SchedulerVerdict {
     pub status: VerdictStatus,
     pub reason: Option<String>,
-    pub held_by: Option<String>,
+    /// Every conflicting holder, the one the verdict was decided on first
+    pub holders: Vec<Holder>,
+    /// How long a refused requester should back off. Policies leave it
+    /// unset; the client's `BackoffPolicy` fills it in on every Die (see
+    /// `backoff`).
     pub retry_after_ms: Option<u64>,
+    /// Lease IDs the store must revoke before honouring a `Granted` verdict
+    /// (used by preemptive policies such as Wound-Wait)
+    pub preempted: Vec<String>,
 }
 
+impl SchedulerVerdict {
+    fn granted() -> Self {
+        Self {
+            status: VerdictStatus::Granted,
+            reason: None,
+            holders: Vec::new(),
+            retry_after_ms: None,
+            preempted: Vec::new(),
+        }
+    }
+
+    /// The agent the verdict was decided on
+    pub fn held_by(&self) -> Option<&str> {
+        self.holders.first().map(|h| h.agent_id.as_str())
+    }
+}
+
+/// `holders` as `Holder`s, with `decisive` moved to the front
+fn holders_from(decisive: &Lease, holders: &[&Lease]) -> Vec<Holder> {
+    std::iter::once(decisive)
+        .chain(holders.iter().copied().filter(|l| l.id != decisive.id))
+        .map(Holder::from)
+        .collect()
+}
+
+/// A pluggable scheduling policy.
+///
+/// The kernel and the lease stores call through this trait whenever a request
+/// conflicts with held leases, so custom policies (priority ceilings, lottery
+/// scheduling, ...) can be swapped in without touching the kernel. Conflict
+/// detection itself belongs to the `ConflictEngine`; policies only decide how
+/// to resolve the conflicts it finds.
+pub trait Scheduler: Send + Sync {
+    /// Short, stable name of the policy (e.g. "wait-die")
+    fn name(&self) -> &'static str;
+
+    /// Name of the policy that decides on `resource`; the same as `name`
+    /// unless the policy depends on the resource type (see
+    /// `policy_registry`)
+    fn name_for(&self, _resource: &ResourceRef) -> &'static str {
+        self.name()
+    }
+
+    /// Resolve a request against the (non-empty) set of conflicting holders.
+    fn resolve(
+        &self,
+        requesting_agent_id: &str,
+        conflicting_holders: &[&Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict;
+
+    /// Decide whether `requesting_agent_id`, working in
+    /// `requesting_session_id`, may perform `requesting_predicate` on
+    /// `resource` given the currently active leases and agent priorities.
+    /// The agent's own leases in that session never conflict.
+    #[allow(clippy::too_many_arguments)]
+    fn decide(
+        &self,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        let holders = engine.conflicting_leases(
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+        );
+
+        if holders.is_empty() {
+            return SchedulerVerdict::granted();
+        }
+
+        self.resolve(requesting_agent_id, &holders, priorities)
+    }
+
+    /// `decide` at time `now`, for wrappers whose part of the verdict
+    /// depends on the time, such as the backoff of a Die verdict
+    #[allow(clippy::too_many_arguments)]
+    fn decide_at(
+        &self,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+        _now: u64,
+    ) -> SchedulerVerdict {
+        self.decide(
+            engine,
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+        )
+    }
+}
+
+/// `priorities` for a request due by `deadline`: the requester's carries
+/// it, and each other agent's the earliest deadline among its `leases`, so
+/// that equal priorities go to the earlier deadline (EDF). Agents without a
+/// priority are left without one.
+pub fn with_deadlines(
+    mut priorities: HashMap<String, Priority>,
+    requesting_agent_id: &str,
+    deadline: Option<u64>,
+    leases: &[Lease],
+) -> HashMap<String, Priority> {
+    for lease in leases {
+        let Some(due) = lease.deadline else {
+            continue;
+        };
+        if lease.agent_id == requesting_agent_id {
+            continue;
+        }
+        if let Some(priority) = priorities.get_mut(&lease.agent_id) {
+            priority.deadline = Some(priority.deadline.map_or(due, |d| d.min(due)));
+        }
+    }
+    if let Some(priority) = priorities.get_mut(requesting_agent_id) {
+        priority.deadline = deadline;
+    }
+    priorities
+}
+
+/// Look up a built-in policy by its `Scheduler::name` ("wait-die",
+/// "wound-wait", "fifo" or "no-wait"), for choosing the policy from
+/// configuration.
+pub fn scheduler_by_name(name: &str) -> Result<Arc<dyn Scheduler>, String> {
+    match name {
+        "wait-die" => Ok(Arc::new(WaitDieScheduler)),
+        "wound-wait" => Ok(Arc::new(WoundWaitScheduler)),
+        "fifo" => Ok(Arc::new(FifoScheduler)),
+        "no-wait" => Ok(Arc::new(NoWaitScheduler)),
+        _ => Err(format!(
+            "Unknown scheduler '{}': use wait-die, wound-wait, fifo or no-wait",
+            name
+        )),
+    }
+}
+
+fn missing_priority_verdict(holders: &[&Lease]) -> SchedulerVerdict {
+    SchedulerVerdict {
+        status: VerdictStatus::Die,
+        reason: Some("Missing agent priority. Cannot ensure deadlock safety.".into()),
+        holders: holders.iter().copied().map(Holder::from).collect(),
+        retry_after_ms: None,
+        preempted: Vec::new(),
+    }
+}
+
+/// The rule of Wait-Die that decided a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitDieBranch {
+    /// No held lease conflicts: granted
+    NoConflict,
+    /// The requester has no priority, so deadlock safety cannot be ensured:
+    /// it dies
+    MissingPriority,
+    /// No conflicting holder has a priority to compare with: granted
+    UnrankedHolders,
+    /// The requester is senior to the first holder with a priority: it
+    /// waits
+    SeniorWaits,
+    /// The requester is not senior to the first holder with a priority: it
+    /// dies
+    JuniorDies,
+}
+
+/// What Wait-Die made of one conflicting holder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HolderOutcome {
+    /// The holder has no priority and was passed over
+    Skipped,
+    /// The verdict was decided on this holder
+    Decisive,
+    /// A holder before it decided the verdict
+    NotReached,
+}
+
+/// One conflicting holder as Wait-Die compared it with the requester
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HolderComparison {
+    #[serde(flatten)]
+    pub holder: Holder,
+    pub priority: Option<Priority>,
+    pub outcome: HolderOutcome,
+}
+
+/// Why Wait-Die decides a request the way it does (see
+/// `WaitDieScheduler::explain`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchedulerExplanation {
+    pub agent_id: String,
+    pub session_id: String,
+    pub predicate: Predicate,
+    pub resource: ResourceRef,
+    pub requester_priority: Option<Priority>,
+    /// The conflicting holders, in the order they were considered
+    pub holders: Vec<HolderComparison>,
+    pub branch: WaitDieBranch,
+    pub status: VerdictStatus,
+    pub reason: Option<String>,
+    pub retry_after_ms: Option<u64>,
+}
+
+/// Non-preemptive Wait-Die: seniors wait for juniors, juniors die.
+#[derive(Debug, Clone, Copy, Default)]
 pub struct WaitDieScheduler;
 
 impl WaitDieScheduler {
+    /// Decide using the built-in compatibility matrix.
     pub fn decide(
         requesting_agent_id: &str,
+        requesting_session_id: &str,
         requesting_predicate: Predicate,
         resource: &ResourceRef,
         active_leases: &[Lease],
-        priorities: &HashMap<String, u64>,
+        priorities: &HashMap<String, Priority>,
     ) -> SchedulerVerdict {
-        let key = resource.key();
-
-        // 1. Find conflicting holders
-        let mut conflicting_holders = Vec::new();
-        for lease in active_leases {
-            if lease.resource.key() == key
-                && lease.agent_id != requesting_agent_id // Skip self
-                && ConflictEngine::check_pair(lease.predicate, requesting_predicate)
-            {
-                conflicting_holders.push(lease);
-            }
+        Scheduler::decide(
+            &WaitDieScheduler,
+            &ConflictEngine::default(),
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+        )
+    }
+
+    /// How Wait-Die decides the request at `now`, as `decide_at` would
+    /// with Die verdicts backing off under `backoff` (as on a first
+    /// refusal), with every conflicting holder it considered, the
+    /// priorities it compared and the rule that fired. Changes nothing.
+    #[allow(clippy::too_many_arguments)]
+    pub fn explain(
+        backoff: &BackoffPolicy,
+        engine: &ConflictEngine,
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+        now: u64,
+    ) -> SchedulerExplanation {
+        let holders = engine.conflicting_leases(
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+        );
+        let (mut verdict, branch, comparisons) = if holders.is_empty() {
+            (
+                SchedulerVerdict::granted(),
+                WaitDieBranch::NoConflict,
+                Vec::new(),
+            )
+        } else {
+            wait_die(requesting_agent_id, &holders, priorities)
+        };
+        if verdict.status == VerdictStatus::Die {
+            verdict.retry_after_ms =
+                Some(backoff.retry_after_ms(requesting_agent_id, &holders, 1, now));
+        }
+        SchedulerExplanation {
+            agent_id: requesting_agent_id.to_string(),
+            session_id: requesting_session_id.to_string(),
+            predicate: requesting_predicate,
+            resource: resource.clone(),
+            requester_priority: priorities.get(requesting_agent_id).copied(),
+            holders: comparisons,
+            branch,
+            status: verdict.status,
+            reason: verdict.reason,
+            retry_after_ms: verdict.retry_after_ms,
         }
+    }
+}
+
+/// Wait-Die against the (non-empty) conflicting holders: the verdict, the
+/// rule that fired and what became of each holder
+fn wait_die(
+    requesting_agent_id: &str,
+    conflicting_holders: &[&Lease],
+    priorities: &HashMap<String, Priority>,
+) -> (SchedulerVerdict, WaitDieBranch, Vec<HolderComparison>) {
+    let mut comparisons: Vec<HolderComparison> = conflicting_holders
+        .iter()
+        .map(|lease| HolderComparison {
+            holder: Holder::from(*lease),
+            priority: priorities.get(&lease.agent_id).copied(),
+            outcome: HolderOutcome::NotReached,
+        })
+        .collect();
 
-        if conflicting_holders.is_empty() {
-            return SchedulerVerdict {
-                status: VerdictStatus::Granted,
-                reason: None,
-                held_by: None,
-                retry_after_ms: None,
-            };
+    // Fetch requester priority (class, then timestamp - lower is senior)
+    let requester_priority = match priorities.get(requesting_agent_id) {
+        Some(p) => *p,
+        None => {
+            return (
+                missing_priority_verdict(conflicting_holders),
+                WaitDieBranch::MissingPriority,
+                comparisons,
+            );
         }
+    };
 
-        // 2. Fetch requester priority (timestamp - lower is older/higher priority)
-        let requester_priority = match priorities.get(requesting_agent_id) {
-            Some(p) => *p,
+    // Apply Wait-Die logic against all conflicting holders
+    for (holder, comparison) in conflicting_holders.iter().zip(comparisons.iter_mut()) {
+        let holder_priority = match comparison.priority {
+            Some(p) => p,
             None => {
-                return SchedulerVerdict {
-                    status: VerdictStatus::Die,
-                    reason: Some("Missing agent priority. Cannot ensure deadlock safety.".into()),
-                    held_by: None,
-                    retry_after_ms: Some(1000), // Base backoff
-                };
+                // If holder has no priority, assume they are younger
+                comparison.outcome = HolderOutcome::Skipped;
+                continue;
             }
         };
+        comparison.outcome = HolderOutcome::Decisive;
 
-        // 3. Apply Wait-Die logic against all conflicting holders
-        for holder in conflicting_holders {
-            let holder_priority = match priorities.get(&holder.agent_id) {
-                Some(p) => *p,
-                None => continue, // If holder has no priority, assume they are younger
-            };
-
-            if requester_priority < holder_priority {
-                // Requester is OLDER (lower timestamp) -> WAIT
-                return SchedulerVerdict {
+        let (verdict, branch) = if requester_priority < holder_priority {
+            // Requester is OLDER (lower timestamp) -> WAIT
+            (
+                SchedulerVerdict {
                     status: VerdictStatus::Wait,
                     reason: Some(format!(
                         "Senior ({}) waiting for Junior ({}) to complete.",
                         requester_priority, holder_priority
                     )),
-                    held_by: Some(holder.agent_id.clone()),
+                    holders: holders_from(holder, conflicting_holders),
                     retry_after_ms: None,
-                };
-            } else {
-                // Requester is YOUNGER (higher timestamp) -> DIE
-                return SchedulerVerdict {
+                    preempted: Vec::new(),
+                },
+                WaitDieBranch::SeniorWaits,
+            )
+        } else {
+            // Requester is YOUNGER (higher timestamp) -> DIE
+            (
+                SchedulerVerdict {
                     status: VerdictStatus::Die,
                     reason: Some(format!(
                         "Conflict: Senior ({}) vs Junior ({}). Junior must DIE.",
                         holder_priority, requester_priority
                     )),
-                    held_by: Some(holder.agent_id.clone()),
-                    retry_after_ms: Some(1000),
-                };
+                    holders: holders_from(holder, conflicting_holders),
+                    retry_after_ms: None,
+                    preempted: Vec::new(),
+                },
+                WaitDieBranch::JuniorDies,
+            )
+        };
+        return (verdict, branch, comparisons);
+    }
+
+    (
+        SchedulerVerdict::granted(),
+        WaitDieBranch::UnrankedHolders,
+        comparisons,
+    )
+}
+
+impl Scheduler for WaitDieScheduler {
+    fn name(&self) -> &'static str {
+        "wait-die"
+    }
+
+    fn resolve(
+        &self,
+        requesting_agent_id: &str,
+        conflicting_holders: &[&Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        wait_die(requesting_agent_id, conflicting_holders, priorities).0
+    }
+}
+
+/// Preemptive Wound-Wait: seniors wound (revoke) juniors, juniors wait.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WoundWaitScheduler;
+
+impl WoundWaitScheduler {
+    /// Decide using the built-in compatibility matrix.
+    pub fn decide(
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        Scheduler::decide(
+            &WoundWaitScheduler,
+            &ConflictEngine::default(),
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+        )
+    }
+}
+
+impl Scheduler for WoundWaitScheduler {
+    fn name(&self) -> &'static str {
+        "wound-wait"
+    }
+
+    fn resolve(
+        &self,
+        requesting_agent_id: &str,
+        conflicting_holders: &[&Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        let requester_priority = match priorities.get(requesting_agent_id) {
+            Some(p) => *p,
+            None => return missing_priority_verdict(conflicting_holders),
+        };
+
+        // A single senior holder forces the requester to wait; otherwise every
+        // conflicting holder is junior and gets wounded.
+        let mut wounded = Vec::new();
+        for holder in conflicting_holders {
+            match priorities.get(&holder.agent_id) {
+                Some(&holder_priority) if holder_priority < requester_priority => {
+                    // Requester is YOUNGER -> WAIT for the senior holder
+                    return SchedulerVerdict {
+                        status: VerdictStatus::Wait,
+                        reason: Some(format!(
+                            "Junior ({}) waiting for Senior ({}) to complete.",
+                            requester_priority, holder_priority
+                        )),
+                        holders: holders_from(holder, conflicting_holders),
+                        retry_after_ms: None,
+                        preempted: Vec::new(),
+                    };
+                }
+                // Requester is OLDER (or holder is unregistered) -> WOUND
+                _ => wounded.push(holder),
             }
         }
 
         SchedulerVerdict {
             status: VerdictStatus::Granted,
-            reason: None,
-            held_by: None,
+            reason: Some(format!(
+                "Senior ({}) wounds {} junior holder(s).",
+                requester_priority,
+                wounded.len()
+            )),
+            holders: wounded.iter().map(|l| Holder::from(**l)).collect(),
+            retry_after_ms: None,
+            preempted: wounded.iter().map(|l| l.id.clone()).collect(),
+        }
+    }
+}
+
+/// First-come, first-served: every conflicting requester waits for the
+/// holders, whatever its priority, and no one is aborted or preempted.
+///
+/// Agents that wait while holding other leases can wait on each other in a
+/// cycle, which only lease expiry breaks, so pair this policy with short TTLs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FifoScheduler;
+
+impl FifoScheduler {
+    /// Decide using the built-in compatibility matrix.
+    pub fn decide(
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        Scheduler::decide(
+            &FifoScheduler,
+            &ConflictEngine::default(),
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+        )
+    }
+}
+
+impl Scheduler for FifoScheduler {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn resolve(
+        &self,
+        _requesting_agent_id: &str,
+        conflicting_holders: &[&Lease],
+        _priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        // Wait on the holder that got there first
+        let Some(first) = conflicting_holders
+            .iter()
+            .min_by(|a, b| a.acquired_at.cmp(&b.acquired_at).then(a.id.cmp(&b.id)))
+        else {
+            return SchedulerVerdict::granted();
+        };
+        SchedulerVerdict {
+            status: VerdictStatus::Wait,
+            reason: Some(format!(
+                "Waiting for {} (holding since {}).",
+                first.agent_id, first.acquired_at
+            )),
+            holders: holders_from(first, conflicting_holders),
+            retry_after_ms: None,
+            preempted: Vec::new(),
+        }
+    }
+}
+
+/// No-Wait: every conflicting requester dies at once, whatever its
+/// priority, and no one waits or is preempted.
+///
+/// Nothing ever waits, so nothing can deadlock; suited to resources held
+/// briefly, such as database tables, where retrying beats queueing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoWaitScheduler;
+
+impl NoWaitScheduler {
+    /// Decide using the built-in compatibility matrix.
+    pub fn decide(
+        requesting_agent_id: &str,
+        requesting_session_id: &str,
+        requesting_predicate: Predicate,
+        resource: &ResourceRef,
+        active_leases: &[Lease],
+        priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        Scheduler::decide(
+            &NoWaitScheduler,
+            &ConflictEngine::default(),
+            requesting_agent_id,
+            requesting_session_id,
+            requesting_predicate,
+            resource,
+            active_leases,
+            priorities,
+        )
+    }
+}
+
+impl Scheduler for NoWaitScheduler {
+    fn name(&self) -> &'static str {
+        "no-wait"
+    }
+
+    fn resolve(
+        &self,
+        _requesting_agent_id: &str,
+        conflicting_holders: &[&Lease],
+        _priorities: &HashMap<String, Priority>,
+    ) -> SchedulerVerdict {
+        let Some(first) = conflicting_holders.first() else {
+            return SchedulerVerdict::granted();
+        };
+        SchedulerVerdict {
+            status: VerdictStatus::Die,
+            reason: Some(format!(
+                "Conflict with {}: no-wait policy never waits.",
+                first.agent_id
+            )),
+            holders: holders_from(first, conflicting_holders),
             retry_after_ms: None,
+            preempted: Vec::new(),
         }
     }
 }
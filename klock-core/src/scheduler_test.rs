@@ -1,8 +1,22 @@
 #[cfg(test)]
 mod tests {
-    use crate::scheduler::{VerdictStatus, WaitDieScheduler};
-    use crate::types::{Lease, Predicate, ResourceRef, ResourceType};
+    use crate::backoff::{BackoffPolicy, BackoffScheduler};
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::conflict::ConflictEngine;
+    use crate::preemption::PreemptionPolicy;
+    use crate::registry::{Priority, PriorityClass};
+    use crate::scheduler::{
+        FifoScheduler, HolderOutcome, Scheduler, SchedulerVerdict, VerdictStatus, WaitDieBranch,
+        WaitDieScheduler, WoundWaitScheduler, scheduler_by_name,
+    };
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, Holder, Lease, LeaseFailureReason, LeaseResult, LeaseState, Predicate,
+        ResourceRef, ResourceType, SPOTriple,
+    };
     use std::collections::HashMap;
+    use std::sync::Arc;
 
     fn create_lease(agent_id: &str, predicate: Predicate) -> Lease {
         Lease::new(
@@ -19,13 +33,14 @@ mod tests {
     #[test]
     fn test_wait_die_older_waits() {
         let mut priorities = HashMap::new();
-        priorities.insert("older".to_string(), 100);
-        priorities.insert("younger".to_string(), 200);
+        priorities.insert("older".to_string(), 100.into());
+        priorities.insert("younger".to_string(), 200.into());
 
         let active = vec![create_lease("younger", Predicate::Mutates)];
 
         let verdict = WaitDieScheduler::decide(
             "older",
+            "s2",
             Predicate::Mutates, // Conflicts with Mutates
             &ResourceRef::new(ResourceType::File, "/src/test.ts"),
             &active,
@@ -35,16 +50,74 @@ mod tests {
         assert_eq!(verdict.status, VerdictStatus::Wait);
     }
 
+    #[test]
+    fn test_priority_class_dominates_timestamp() {
+        let priorities = HashMap::from([
+            (
+                "pager".to_string(),
+                Priority::new(PriorityClass::Critical, 900),
+            ),
+            ("worker".to_string(), 100.into()),
+            (
+                "indexer".to_string(),
+                Priority::new(PriorityClass::Background, 50),
+            ),
+        ]);
+        let resource = ResourceRef::new(ResourceType::File, "/src/test.ts");
+        let decide = |scheduler: &dyn Scheduler, requester: &str, holder: &str| {
+            let active = vec![create_lease(holder, Predicate::Mutates)];
+            scheduler.decide(
+                &ConflictEngine::default(),
+                requester,
+                "s2",
+                Predicate::Mutates,
+                &resource,
+                &active,
+                &priorities,
+            )
+        };
+
+        // The younger critical agent is senior to the normal one...
+        let verdict = decide(&WaitDieScheduler, "worker", "pager");
+        assert_eq!(verdict.status, VerdictStatus::Die);
+        assert_eq!(
+            verdict.reason.as_deref(),
+            Some("Conflict: Senior (critical:900) vs Junior (100). Junior must DIE.")
+        );
+        let verdict = decide(&WoundWaitScheduler, "pager", "worker");
+        assert_eq!(verdict.status, VerdictStatus::Granted);
+        assert_eq!(verdict.preempted, vec!["l1".to_string()]);
+
+        // ...and the older background agent junior to it
+        assert_eq!(
+            decide(&WaitDieScheduler, "worker", "indexer").status,
+            VerdictStatus::Wait
+        );
+        assert_eq!(
+            decide(&WoundWaitScheduler, "indexer", "worker").status,
+            VerdictStatus::Wait
+        );
+
+        // Preemption needs the gap within a class, not across classes
+        let policy = PreemptionPolicy {
+            min_priority_gap: Some(60),
+        };
+        assert!(policy.allows(priorities["pager"], priorities["worker"]));
+        assert!(!policy.allows(priorities["indexer"], priorities["worker"]));
+        assert!(!policy.allows(150.into(), priorities["worker"]));
+    }
+
     #[test]
     fn test_wait_die_younger_dies() {
         let mut priorities = HashMap::new();
-        priorities.insert("older".to_string(), 100);
-        priorities.insert("younger".to_string(), 200);
+        priorities.insert("older".to_string(), 100.into());
+        priorities.insert("younger".to_string(), 200.into());
 
         let active = vec![create_lease("older", Predicate::Mutates)];
 
         let verdict = WaitDieScheduler::decide(
             "younger",
+            "s2",
             Predicate::Mutates, // Conflicts with Mutates
             &ResourceRef::new(ResourceType::File, "/src/test.ts"),
             &active,
@@ -53,4 +126,533 @@ mod tests {
 
         assert_eq!(verdict.status, VerdictStatus::Die);
     }
+
+    #[test]
+    fn test_wait_die_directory_lease_covers_files() {
+        let mut priorities = HashMap::new();
+        priorities.insert("older".to_string(), 100.into());
+        priorities.insert("younger".to_string(), 200.into());
+
+        let active = vec![create_lease("older", Predicate::Mutates)];
+
+        // The requested directory contains the held file
+        let verdict = WaitDieScheduler::decide(
+            "younger",
+            "s2",
+            Predicate::Mutates,
+            &ResourceRef::new(ResourceType::File, "/src/"),
+            &active,
+            &priorities,
+        );
+        assert_eq!(verdict.status, VerdictStatus::Die);
+
+        let verdict = WaitDieScheduler::decide(
+            "younger",
+            "s2",
+            Predicate::Mutates,
+            &ResourceRef::new(ResourceType::File, "/lib/"),
+            &active,
+            &priorities,
+        );
+        assert_eq!(verdict.status, VerdictStatus::Granted);
+    }
+
+    #[test]
+    fn test_wound_wait_older_wounds_younger() {
+        let mut priorities = HashMap::new();
+        priorities.insert("older".to_string(), 100.into());
+        priorities.insert("younger".to_string(), 200.into());
+
+        let active = vec![create_lease("younger", Predicate::Mutates)];
+
+        let verdict = WoundWaitScheduler::decide(
+            "older",
+            "s2",
+            Predicate::Mutates,
+            &ResourceRef::new(ResourceType::File, "/src/test.ts"),
+            &active,
+            &priorities,
+        );
+
+        assert_eq!(verdict.status, VerdictStatus::Granted);
+        assert_eq!(verdict.preempted, vec!["l1".to_string()]);
+    }
+
+    #[test]
+    fn test_wound_wait_younger_waits() {
+        let mut priorities = HashMap::new();
+        priorities.insert("older".to_string(), 100.into());
+        priorities.insert("younger".to_string(), 200.into());
+
+        let active = vec![create_lease("older", Predicate::Mutates)];
+
+        let verdict = WoundWaitScheduler::decide(
+            "younger",
+            "s2",
+            Predicate::Mutates,
+            &ResourceRef::new(ResourceType::File, "/src/test.ts"),
+            &active,
+            &priorities,
+        );
+
+        assert_eq!(verdict.status, VerdictStatus::Wait);
+        assert_eq!(verdict.held_by(), Some("older"));
+        assert!(verdict.preempted.is_empty());
+    }
+
+    #[test]
+    fn test_schedulers_callable_through_trait_object() {
+        let mut priorities = HashMap::new();
+        priorities.insert("older".to_string(), 100.into());
+        priorities.insert("younger".to_string(), 200.into());
+
+        let active = vec![create_lease("older", Predicate::Mutates)];
+        let policies: Vec<Box<dyn Scheduler>> =
+            vec![Box::new(WaitDieScheduler), Box::new(WoundWaitScheduler)];
+
+        let statuses: Vec<VerdictStatus> = policies
+            .iter()
+            .map(|policy| {
+                policy
+                    .decide(
+                        &ConflictEngine::default(),
+                        "younger",
+                        "s2",
+                        Predicate::Mutates,
+                        &ResourceRef::new(ResourceType::File, "/src/test.ts"),
+                        &active,
+                        &priorities,
+                    )
+                    .status
+            })
+            .collect();
+
+        assert_eq!(statuses, vec![VerdictStatus::Die, VerdictStatus::Wait]);
+    }
+
+    #[test]
+    fn test_scheduler_by_name() {
+        assert_eq!(scheduler_by_name("wait-die").unwrap().name(), "wait-die");
+        assert_eq!(
+            scheduler_by_name("wound-wait").unwrap().name(),
+            "wound-wait"
+        );
+        assert_eq!(scheduler_by_name("fifo").unwrap().name(), "fifo");
+        assert_eq!(scheduler_by_name("no-wait").unwrap().name(), "no-wait");
+        assert!(scheduler_by_name("lottery").is_err());
+    }
+
+    #[test]
+    fn test_client_with_wound_wait_scheduler() {
+        let mut client =
+            KlockClient::new().with_scheduler(scheduler_by_name("wound-wait").unwrap());
+        assert_eq!(client.scheduler_name(), "wound-wait");
+        client.register_agent("older", 100);
+        client.register_agent("younger", 200);
+
+        let LeaseResult::Success { lease: junior } =
+            client.acquire_lease("younger", "s2", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        // The senior wounds the junior holder instead of waiting
+        assert!(matches!(
+            client.acquire_lease("older", "s1", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+        assert_eq!(
+            client.get_lease(&junior.id).unwrap().state,
+            LeaseState::Revoked
+        );
+        // The junior now waits for the senior rather than dying
+        assert!(matches!(
+            client.acquire_lease("younger", "s2", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Wait,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_fifo_waits_for_first_holder_regardless_of_priority() {
+        let mut priorities = HashMap::new();
+        priorities.insert("older".to_string(), 100.into());
+        priorities.insert("younger".to_string(), 200.into());
+        let resource = ResourceRef::new(ResourceType::File, "/src/test.ts");
+
+        for (requester, holder) in [("older", "younger"), ("younger", "older")] {
+            let active = vec![create_lease(holder, Predicate::Mutates)];
+            let verdict = FifoScheduler::decide(
+                requester,
+                "s2",
+                Predicate::Mutates,
+                &resource,
+                &active,
+                &priorities,
+            );
+            assert_eq!(verdict.status, VerdictStatus::Wait);
+            assert_eq!(verdict.held_by(), Some(holder));
+            assert!(verdict.preempted.is_empty());
+        }
+
+        // Shared readers acquired in turn; a writer waits on the earliest
+        let mut first = create_lease("younger", Predicate::Consumes);
+        first.acquired_at = 500;
+        let second = create_lease("older", Predicate::Consumes);
+        let verdict = FifoScheduler::decide(
+            "newcomer",
+            "s3",
+            Predicate::Mutates,
+            &resource,
+            &[second, first],
+            &priorities,
+        );
+        assert_eq!(verdict.held_by(), Some("younger"));
+    }
+
+    /// Refuses every conflict, to check a user-defined policy reaches both
+    /// the kernel and the store
+    struct RefuseAll;
+
+    impl Scheduler for RefuseAll {
+        fn name(&self) -> &'static str {
+            "refuse-all"
+        }
+
+        fn resolve(
+            &self,
+            _requesting_agent_id: &str,
+            conflicting_holders: &[&Lease],
+            _priorities: &HashMap<String, Priority>,
+        ) -> SchedulerVerdict {
+            SchedulerVerdict {
+                status: VerdictStatus::Die,
+                reason: Some("refused".to_string()),
+                holders: vec![Holder::from(conflicting_holders[0])],
+                retry_after_ms: None,
+                preempted: Vec::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_client_uses_user_defined_policy() {
+        let mut client = KlockClient::new().with_scheduler(Arc::new(RefuseAll));
+        client.register_agent("older", 100);
+        client.register_agent("younger", 200);
+        assert!(matches!(
+            client.acquire_lease("younger", "s2", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Success { .. }
+        ));
+
+        // Wait-Die would make the senior wait; this policy refuses it outright
+        assert!(matches!(
+            client.acquire_lease("older", "s1", "FILE", "/a.ts", "MUTATES", 60_000),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            }
+        ));
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "older".to_string(),
+            intents: vec![SPOTriple {
+                id: "i1".to_string(),
+                subject: "older".to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, "/a.ts"),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: "s1".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+    }
+
+    #[test]
+    fn test_die_backs_off_until_holder_lease_runs_out() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("older", 100);
+        client.register_agent("younger", 200);
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("older", "s1", "FILE", "/a.ts", "MUTATES", 10_000)
+        else {
+            panic!("Expected success");
+        };
+
+        let wait_time = |client: &mut KlockClient| match client
+            .acquire_lease("younger", "s2", "FILE", "/a.ts", "MUTATES", 60_000)
+        {
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Die,
+                wait_time,
+                ..
+            } => wait_time,
+            other => panic!("Expected Die, got {:?}", other),
+        };
+        clock.advance(3000);
+        assert_eq!(wait_time(&mut client), Some(7000));
+
+        // A heartbeat moves the expiry, and the backoff with it
+        assert!(client.heartbeat_lease(&lease.id, 5000));
+        clock.set(6000);
+        assert_eq!(wait_time(&mut client), Some(9000));
+
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s2".to_string(),
+            agent_id: "younger".to_string(),
+            intents: vec![SPOTriple {
+                id: "i1".to_string(),
+                subject: "younger".to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, "/a.ts"),
+                timestamp: 6000,
+                confidence: Confidence::High,
+                session_id: "s2".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(verdict.retry_after_ms, Some(9000));
+
+        // Never an immediate retry, however close the expiry
+        clock.set(14_990);
+        assert_eq!(wait_time(&mut client), Some(100));
+    }
+
+    #[test]
+    fn test_refusals_name_every_holder() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("writer", 100);
+        client.register_agent("reader_a", 200);
+        client.register_agent("reader_b", 300);
+        client.acquire_lease("reader_a", "s1", "FILE", "/a.ts", "CONSUMES", 10_000);
+        clock.advance(1);
+        client.acquire_lease("reader_b", "s2", "FILE", "/a.ts", "CONSUMES", 20_000);
+        clock.advance(1);
+
+        let LeaseResult::Failure {
+            reason: LeaseFailureReason::Wait,
+            holders,
+            ..
+        } = client.acquire_lease("writer", "s3", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected Wait");
+        };
+        let named: Vec<(&str, u64)> = holders
+            .iter()
+            .map(|h| (h.agent_id.as_str(), h.expires_at))
+            .collect();
+        assert_eq!(named, vec![("reader_a", 11_000), ("reader_b", 21_001)]);
+
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s3".to_string(),
+            agent_id: "writer".to_string(),
+            intents: vec![SPOTriple {
+                id: "i1".to_string(),
+                subject: "writer".to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, "/a.ts"),
+                timestamp: 1002,
+                confidence: Confidence::High,
+                session_id: "s3".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Wait);
+        assert_eq!(verdict.held_by.as_deref(), Some("reader_a"));
+        assert_eq!(verdict.holders, holders);
+    }
+
+    #[test]
+    fn test_equal_priorities_go_to_the_earlier_deadline() {
+        let due = |deadline| Priority::from(100).with_deadline(Some(deadline));
+        assert!(due(20_000) < due(50_000));
+        assert!(due(50_000) < Priority::from(100));
+        assert!(Priority::from(99) < due(20_000));
+        assert_eq!(
+            serde_json::to_value(due(20_000)).unwrap(),
+            serde_json::json!({ "class": "normal", "timestamp": 100, "deadline": 20_000 })
+        );
+
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        for agent in ["holder", "urgent", "relaxed"] {
+            client.register_agent(agent, 100);
+        }
+        let acquire = |client: &mut KlockClient, agent: &str, deadline: Option<u64>| {
+            client.acquire_lease_with_deadline(
+                agent, "s1", "FILE", "/a.ts", "MUTATES", 10_000, deadline,
+            )
+        };
+        let LeaseResult::Success { lease: held } = acquire(&mut client, "holder", Some(50_000))
+        else {
+            panic!("Expected success");
+        };
+        assert_eq!(held.deadline, Some(50_000));
+        clock.advance(1);
+
+        // The earlier deadline is senior, so it waits instead of dying
+        assert!(matches!(
+            acquire(&mut client, "urgent", Some(20_000)),
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Wait,
+                ..
+            }
+        ));
+        for deadline in [None, Some(60_000)] {
+            assert!(matches!(
+                acquire(&mut client, "relaxed", deadline),
+                LeaseResult::Failure {
+                    reason: LeaseFailureReason::Die,
+                    ..
+                }
+            ));
+        }
+
+        // The queued request keeps its deadline on the lease it is granted
+        assert!(client.release_lease(&held.id));
+        let LeaseResult::Success { lease } = acquire(&mut client, "urgent", Some(20_000)) else {
+            panic!("Expected the queued grant");
+        };
+        assert_eq!(lease.deadline, Some(20_000));
+    }
+
+    #[test]
+    fn test_wait_die_explain_traces_the_deciding_holder() {
+        let unranked = Lease::new(
+            "l0".to_string(),
+            "ghost".to_string(),
+            "s0".to_string(),
+            ResourceRef::new(ResourceType::File, "/src/test.ts"),
+            Predicate::Consumes,
+            5000,
+            1000,
+        );
+        let active = vec![unranked, create_lease("younger", Predicate::Mutates)];
+        let mut priorities = HashMap::new();
+        priorities.insert("older".to_string(), 100.into());
+        priorities.insert("younger".to_string(), 200.into());
+        let explain = |agent_id: &str, priorities: &HashMap<String, Priority>| {
+            WaitDieScheduler::explain(
+                &BackoffPolicy::default(),
+                &ConflictEngine::default(),
+                agent_id,
+                "s9",
+                Predicate::Mutates,
+                &ResourceRef::new(ResourceType::File, "/src/test.ts"),
+                &active,
+                priorities,
+                1000,
+            )
+        };
+
+        let explanation = explain("older", &priorities);
+        assert_eq!(explanation.branch, WaitDieBranch::SeniorWaits);
+        assert_eq!(explanation.status, VerdictStatus::Wait);
+        assert_eq!(explanation.requester_priority, Some(100.into()));
+        let outcomes: Vec<_> = explanation
+            .holders
+            .iter()
+            .map(|h| (h.holder.agent_id.as_str(), h.priority, h.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("ghost", None, HolderOutcome::Skipped),
+                ("younger", Some(200.into()), HolderOutcome::Decisive),
+            ]
+        );
+
+        // The same verdict as deciding it
+        priorities.insert("youngest".to_string(), 300.into());
+        let explanation = explain("youngest", &priorities);
+        assert_eq!(explanation.branch, WaitDieBranch::JuniorDies);
+        assert_eq!(explanation.retry_after_ms, Some(5000));
+        let verdict = Scheduler::decide_at(
+            &BackoffScheduler::new(
+                Arc::new(WaitDieScheduler),
+                BackoffPolicy::default(),
+                Arc::new(ManualClock::new(1000)),
+            ),
+            &ConflictEngine::default(),
+            "youngest",
+            "s9",
+            Predicate::Mutates,
+            &ResourceRef::new(ResourceType::File, "/src/test.ts"),
+            &active,
+            &priorities,
+            1000,
+        );
+        assert_eq!(explanation.status, verdict.status);
+        assert_eq!(explanation.reason, verdict.reason);
+        assert_eq!(explanation.retry_after_ms, verdict.retry_after_ms);
+
+        assert_eq!(
+            explain("nobody", &priorities).branch,
+            WaitDieBranch::MissingPriority
+        );
+        priorities.remove("younger");
+        let explanation = explain("older", &priorities);
+        assert_eq!(explanation.branch, WaitDieBranch::UnrankedHolders);
+        assert_eq!(explanation.status, VerdictStatus::Granted);
+    }
+
+    #[test]
+    fn test_client_explains_intents_without_declaring_them() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+        client.acquire_lease("senior", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        clock.advance(1);
+        let intent = |path: &str| SPOTriple {
+            id: path.to_string(),
+            subject: "junior".to_string(),
+            predicate: Predicate::Mutates,
+            object: ResourceRef::new(ResourceType::File, path),
+            timestamp: 1001,
+            confidence: Confidence::High,
+            session_id: "s2".to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
+        };
+        let manifest = IntentManifest {
+            session_id: "s2".to_string(),
+            agent_id: "junior".to_string(),
+            intents: vec![intent("/a.ts"), intent("/b.ts")],
+        };
+
+        let explanations = client.explain_intent(&manifest);
+        let branches: Vec<_> = explanations.iter().map(|e| e.branch).collect();
+        assert_eq!(
+            branches,
+            [WaitDieBranch::JuniorDies, WaitDieBranch::NoConflict]
+        );
+        assert_eq!(client.memory_report().intents.count, 0);
+        assert_eq!(
+            client.declare_intent(&manifest).status,
+            KernelVerdictStatus::Die
+        );
+
+        // Unregistered agents are ranked lowest, as the scheduler ranks them
+        let manifest = IntentManifest {
+            agent_id: "stranger".to_string(),
+            ..manifest
+        };
+        let explanation = &client.explain_intent(&manifest)[0];
+        assert_eq!(explanation.requester_priority, Some(Priority::lowest()));
+        assert_eq!(explanation.branch, WaitDieBranch::JuniorDies);
+    }
 }
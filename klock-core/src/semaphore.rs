@@ -0,0 +1,239 @@
+//! Named counting semaphores.
+//!
+//! Leases give one agent (or a set of compatible readers) a resource; a
+//! semaphore caps how many agents may use a shared facility at once, e.g.
+//! "at most 4 agents running integration tests". The first acquire of a name
+//! declares its capacity; every acquire takes one permit for `ttl`
+//! milliseconds. Permits that are not released are reclaimed when their TTL
+//! elapses.
+//!
+//! When no permit is free, Wait-Die decides whether the requester should
+//! wait or abort: it may wait only if it is senior to every other holder, so
+//! waits always point from older to younger agents and cannot deadlock.
+
+use crate::registry::Priority;
+use crate::types::LeaseFailureReason;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// One permit held on a semaphore
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SemaphorePermit {
+    pub id: String,
+    pub semaphore: String,
+    pub agent_id: String,
+    pub acquired_at: u64,
+    pub expires_at: u64,
+}
+
+/// Point-in-time view of a semaphore
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SemaphoreStatus {
+    pub name: String,
+    /// Total permits
+    pub permits: usize,
+    /// Permits not currently held
+    pub available: usize,
+    pub holders: Vec<SemaphorePermit>,
+}
+
+/// Result of attempting to take a permit
+#[derive(Debug, Clone)]
+pub enum SemaphoreResult {
+    Success {
+        permit: SemaphorePermit,
+    },
+    /// All permits are held. `reason` is `Wait` or `Die`; `wait_time` is how
+    /// long until the earliest held permit expires.
+    Failure {
+        reason: LeaseFailureReason,
+        wait_time: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Semaphore {
+    permits: usize,
+    held: Vec<SemaphorePermit>,
+}
+
+/// All semaphores known to a client, keyed by name. Semaphores with no
+/// holders are dropped, so a name can be redeclared with a new capacity once
+/// it is idle.
+#[derive(Debug, Default)]
+pub struct SemaphoreRegistry {
+    semaphores: HashMap<String, Semaphore>,
+    id_counter: u64,
+}
+
+impl SemaphoreRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take one permit of the semaphore `name`, declaring it with `permits`
+    /// total permits if it does not exist yet.
+    ///
+    /// Errors if the arguments are invalid or `name` is in use with a
+    /// different capacity.
+    pub fn acquire(
+        &mut self,
+        name: &str,
+        agent_id: &str,
+        permits: usize,
+        ttl: u64,
+        priorities: &HashMap<String, Priority>,
+        now: u64,
+    ) -> Result<SemaphoreResult, String> {
+        if name.is_empty() {
+            return Err("Semaphore name must not be empty".to_string());
+        }
+        if agent_id.is_empty() {
+            return Err("agent_id must not be empty".to_string());
+        }
+        if permits == 0 {
+            return Err("Semaphore permits must be greater than 0".to_string());
+        }
+        if ttl == 0 {
+            return Err("ttl must be greater than 0".to_string());
+        }
+
+        self.reclaim_expired(now);
+        let semaphore = self
+            .semaphores
+            .entry(name.to_string())
+            .or_insert_with(|| Semaphore {
+                permits,
+                held: Vec::new(),
+            });
+        if semaphore.permits != permits {
+            return Err(format!(
+                "Semaphore '{}' is declared with {} permits, not {}",
+                name, semaphore.permits, permits
+            ));
+        }
+
+        if semaphore.held.len() >= semaphore.permits {
+            let wait_time = semaphore
+                .held
+                .iter()
+                .map(|p| p.expires_at.saturating_sub(now))
+                .min();
+            return Ok(SemaphoreResult::Failure {
+                reason: wait_or_die(agent_id, &semaphore.held, priorities),
+                wait_time,
+            });
+        }
+
+        self.id_counter += 1;
+        let permit = SemaphorePermit {
+            id: format!("permit_{}_{}_{}", name, agent_id, self.id_counter),
+            semaphore: name.to_string(),
+            agent_id: agent_id.to_string(),
+            acquired_at: now,
+            expires_at: now + ttl,
+        };
+        semaphore.held.push(permit.clone());
+        Ok(SemaphoreResult::Success { permit })
+    }
+
+    /// Return a permit early. Returns false if it is unknown or already
+    /// reclaimed.
+    pub fn release(&mut self, permit_id: &str) -> bool {
+        let Some((name, index)) = self.semaphores.iter().find_map(|(name, semaphore)| {
+            semaphore
+                .held
+                .iter()
+                .position(|p| p.id == permit_id)
+                .map(|index| (name.clone(), index))
+        }) else {
+            return false;
+        };
+        if let Some(semaphore) = self.semaphores.get_mut(&name) {
+            semaphore.held.remove(index);
+            if semaphore.held.is_empty() {
+                self.semaphores.remove(&name);
+            }
+        }
+        true
+    }
+
+    /// Status as of `now`; permits past their TTL are not counted.
+    pub fn status(&self, name: &str, now: u64) -> Option<SemaphoreStatus> {
+        let semaphore = self.semaphores.get(name)?;
+        let holders: Vec<SemaphorePermit> = semaphore
+            .held
+            .iter()
+            .filter(|p| p.expires_at >= now)
+            .cloned()
+            .collect();
+        Some(SemaphoreStatus {
+            name: name.to_string(),
+            permits: semaphore.permits,
+            available: semaphore.permits.saturating_sub(holders.len()),
+            holders,
+        })
+    }
+
+    /// Reclaim permits whose TTL has elapsed. Returns the number reclaimed.
+    pub fn reclaim_expired(&mut self, now: u64) -> usize {
+        let mut reclaimed = 0;
+        self.semaphores.retain(|_, semaphore| {
+            let initial = semaphore.held.len();
+            semaphore.held.retain(|p| p.expires_at >= now);
+            reclaimed += initial - semaphore.held.len();
+            !semaphore.held.is_empty()
+        });
+        reclaimed
+    }
+
+    pub fn len(&self) -> usize {
+        self.semaphores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.semaphores.is_empty()
+    }
+
+    /// Approximate footprint of all semaphores and their permits
+    pub fn estimated_bytes(&self) -> usize {
+        self.semaphores
+            .iter()
+            .map(|(name, semaphore)| {
+                name.capacity()
+                    + std::mem::size_of::<(String, Semaphore)>()
+                    + semaphore
+                        .held
+                        .iter()
+                        .map(|p| {
+                            std::mem::size_of::<SemaphorePermit>()
+                                + p.id.capacity()
+                                + p.semaphore.capacity()
+                                + p.agent_id.capacity()
+                        })
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+}
+
+/// Wait if the requester is senior to every other holder, otherwise die.
+/// Unregistered requesters always die; unregistered holders count as junior.
+fn wait_or_die(
+    agent_id: &str,
+    held: &[SemaphorePermit],
+    priorities: &HashMap<String, Priority>,
+) -> LeaseFailureReason {
+    let Some(&requester) = priorities.get(agent_id) else {
+        return LeaseFailureReason::Die;
+    };
+    let senior_to_all = held
+        .iter()
+        .filter(|p| p.agent_id != agent_id)
+        .all(|p| priorities.get(&p.agent_id).is_none_or(|&h| requester < h));
+    if senior_to_all {
+        LeaseFailureReason::Wait
+    } else {
+        LeaseFailureReason::Die
+    }
+}
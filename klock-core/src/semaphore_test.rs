@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::registry::Priority;
+    use crate::semaphore::{SemaphoreRegistry, SemaphoreResult};
+    use crate::types::LeaseFailureReason;
+    use std::collections::HashMap;
+
+    fn priorities() -> HashMap<String, Priority> {
+        HashMap::from([
+            ("oldest".to_string(), 100.into()),
+            ("middle".to_string(), 200.into()),
+            ("youngest".to_string(), 300.into()),
+        ])
+    }
+
+    fn acquire(semaphores: &mut SemaphoreRegistry, agent_id: &str, now: u64) -> SemaphoreResult {
+        semaphores
+            .acquire("integration-tests", agent_id, 2, 5000, &priorities(), now)
+            .unwrap()
+    }
+
+    fn failure_reason(result: SemaphoreResult) -> LeaseFailureReason {
+        match result {
+            SemaphoreResult::Failure { reason, .. } => reason,
+            other => panic!("Expected failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_permits_limit_concurrency() {
+        let mut semaphores = SemaphoreRegistry::new();
+        assert!(matches!(
+            acquire(&mut semaphores, "middle", 1000),
+            SemaphoreResult::Success { .. }
+        ));
+        let SemaphoreResult::Success { permit } = acquire(&mut semaphores, "youngest", 1000) else {
+            panic!("Expected a permit");
+        };
+
+        let status = semaphores.status("integration-tests", 1000).unwrap();
+        assert_eq!(status.available, 0);
+        assert_eq!(status.holders.len(), 2);
+
+        // Full: the oldest agent may wait, a younger one must die
+        assert_eq!(
+            failure_reason(acquire(&mut semaphores, "oldest", 1100)),
+            LeaseFailureReason::Wait
+        );
+        assert_eq!(
+            failure_reason(
+                semaphores
+                    .acquire(
+                        "integration-tests",
+                        "unregistered",
+                        2,
+                        5000,
+                        &priorities(),
+                        1100
+                    )
+                    .unwrap()
+            ),
+            LeaseFailureReason::Die
+        );
+
+        assert!(semaphores.release(&permit.id));
+        assert!(!semaphores.release(&permit.id));
+        assert!(matches!(
+            acquire(&mut semaphores, "oldest", 1200),
+            SemaphoreResult::Success { .. }
+        ));
+    }
+
+    #[test]
+    fn test_junior_dies_against_senior_holder() {
+        let mut semaphores = SemaphoreRegistry::new();
+        acquire(&mut semaphores, "oldest", 1000);
+        acquire(&mut semaphores, "youngest", 1000);
+
+        match acquire(&mut semaphores, "middle", 2000) {
+            SemaphoreResult::Failure { reason, wait_time } => {
+                assert_eq!(reason, LeaseFailureReason::Die);
+                assert_eq!(wait_time, Some(4000));
+            }
+            other => panic!("Expected failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_expired_permits_are_reclaimed() {
+        let mut semaphores = SemaphoreRegistry::new();
+        acquire(&mut semaphores, "middle", 1000);
+        acquire(&mut semaphores, "youngest", 1000);
+
+        assert_eq!(
+            semaphores
+                .status("integration-tests", 7000)
+                .unwrap()
+                .available,
+            2
+        );
+        assert!(matches!(
+            acquire(&mut semaphores, "oldest", 7000),
+            SemaphoreResult::Success { .. }
+        ));
+        assert_eq!(
+            semaphores
+                .status("integration-tests", 7000)
+                .unwrap()
+                .holders
+                .len(),
+            1
+        );
+
+        // Once idle, the semaphore is dropped and may be redeclared
+        assert_eq!(semaphores.reclaim_expired(20_000), 1);
+        assert!(semaphores.is_empty());
+        assert!(
+            semaphores
+                .acquire(
+                    "integration-tests",
+                    "oldest",
+                    4,
+                    5000,
+                    &priorities(),
+                    20_000
+                )
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_invalid_acquires_rejected() {
+        let mut semaphores = SemaphoreRegistry::new();
+        let p = priorities();
+        assert!(semaphores.acquire("", "oldest", 2, 5000, &p, 1000).is_err());
+        assert!(
+            semaphores
+                .acquire("s", "oldest", 0, 5000, &p, 1000)
+                .is_err()
+        );
+        assert!(semaphores.acquire("s", "oldest", 2, 0, &p, 1000).is_err());
+        assert!(semaphores.acquire("s", "oldest", 2, 5000, &p, 1000).is_ok());
+        // Capacity is fixed while the semaphore is in use
+        assert!(
+            semaphores
+                .acquire("s", "middle", 3, 5000, &p, 1000)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_client_semaphore_round_trip() {
+        let mut client = KlockClient::new();
+        client.register_agent("senior", 100);
+        client.register_agent("junior", 200);
+
+        let Ok(SemaphoreResult::Success { permit }) =
+            client.acquire_semaphore("senior", "ci", 1, 60_000)
+        else {
+            panic!("Expected a permit");
+        };
+        assert!(matches!(
+            client.acquire_semaphore("junior", "ci", 1, 60_000),
+            Ok(SemaphoreResult::Failure {
+                reason: LeaseFailureReason::Die,
+                ..
+            })
+        ));
+        assert_eq!(client.semaphore_status("ci").unwrap().available, 0);
+        assert_eq!(client.memory_report().semaphores.count, 1);
+
+        assert!(client.release_semaphore(&permit.id));
+        assert!(client.semaphore_status("ci").is_none());
+        assert!(matches!(
+            client.acquire_semaphore("junior", "ci", 1, 60_000),
+            Ok(SemaphoreResult::Success { .. })
+        ));
+    }
+}
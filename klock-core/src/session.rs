@@ -0,0 +1,330 @@
+//! Implicit per-agent sessions.
+//!
+//! Sessions let one agent run several independent workflows, but simple
+//! integrations have only one and end up inventing session IDs. Such callers
+//! can leave the session out: the client then uses the agent's default
+//! session, creating it on first use. A default session lives as long as the
+//! agent keeps using it (acquiring, declaring intents, heartbeating leases
+//! in it); after `idle_ttl_ms` without use it ends, and the agent's next
+//! request starts a new one.
+//!
+//! Agents that want their work cleaned up when they stop begin a session
+//! explicitly, with its own TTL. It lives as long as it is used within the
+//! TTL; when the agent ends it or it expires, the client releases its
+//! leases and withdraws its intents, and later requests in it are refused
+//! with `SessionExpired`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How long default sessions outlive their last use, and ended sessions
+/// are remembered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionPolicy {
+    /// Idle time after which an agent's default session ends
+    pub idle_ttl_ms: u64,
+    /// How long an explicit session that ended keeps refusing requests
+    /// with `SessionExpired`; afterwards its ID is a plain session again
+    #[serde(default = "default_ended_retention_ms")]
+    pub ended_retention_ms: u64,
+}
+
+fn default_ended_retention_ms() -> u64 {
+    24 * 60 * 60 * 1000
+}
+
+impl Default for SessionPolicy {
+    fn default() -> Self {
+        Self {
+            idle_ttl_ms: 30 * 60 * 1000,
+            ended_retention_ms: default_ended_retention_ms(),
+        }
+    }
+}
+
+/// A session an agent began explicitly
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub session_id: String,
+    pub agent_id: String,
+    /// Idle time after which the session expires
+    pub ttl_ms: u64,
+    pub started_at: u64,
+    pub last_used: u64,
+}
+
+impl Session {
+    /// When the session expires unless it is used before
+    pub fn expires_at(&self) -> u64 {
+        self.last_used.saturating_add(self.ttl_ms)
+    }
+
+    pub fn is_live(&self, now: u64) -> bool {
+        self.expires_at() > now
+    }
+}
+
+/// What ending an explicit session released
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEnd {
+    pub session: Session,
+    /// Whether its TTL ran out, rather than the agent ending it
+    pub expired: bool,
+    pub leases_released: Vec<String>,
+    pub waiters_cancelled: usize,
+    pub intents_withdrawn: usize,
+}
+
+/// The session used for an agent's requests that name none
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DefaultSession {
+    pub agent_id: String,
+    pub session_id: String,
+    pub created_at: u64,
+    pub last_used: u64,
+}
+
+/// Default sessions, one per agent, and explicit sessions by ID
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    policy: SessionPolicy,
+    sessions: HashMap<String, DefaultSession>,
+    begun: HashMap<String, Session>,
+    /// Explicit sessions that ended, with their agent and when they ended
+    ended: HashMap<String, (String, u64)>,
+}
+
+impl SessionRegistry {
+    pub fn new(policy: SessionPolicy) -> Self {
+        Self {
+            policy,
+            ..Self::default()
+        }
+    }
+
+    pub fn policy(&self) -> &SessionPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: SessionPolicy) {
+        self.policy = policy;
+    }
+
+    fn is_live(&self, session: &DefaultSession, now: u64) -> bool {
+        session.last_used.saturating_add(self.policy.idle_ttl_ms) > now
+    }
+
+    /// The agent's default session ID, starting a new session if it has
+    /// none or the last one has gone idle. Counts as a use.
+    pub fn resolve(&mut self, agent_id: &str, now: u64) -> String {
+        let live = self
+            .sessions
+            .get(agent_id)
+            .is_some_and(|session| self.is_live(session, now));
+        if !live {
+            self.sessions.insert(
+                agent_id.to_string(),
+                DefaultSession {
+                    agent_id: agent_id.to_string(),
+                    session_id: format!("{}-default-{}", agent_id, now),
+                    created_at: now,
+                    last_used: now,
+                },
+            );
+        }
+        let session = self
+            .sessions
+            .get_mut(agent_id)
+            .expect("default session was just ensured");
+        session.last_used = now;
+        session.session_id.clone()
+    }
+
+    /// Keep the agent's default session, or its live explicit session,
+    /// alive if `session_id` is it
+    pub fn touch(&mut self, agent_id: &str, session_id: &str, now: u64) {
+        if let Some(session) = self.sessions.get_mut(agent_id)
+            && session.session_id == session_id
+        {
+            session.last_used = session.last_used.max(now);
+        }
+        if let Some(session) = self.begun.get_mut(session_id)
+            && session.agent_id == agent_id
+            && session.is_live(now)
+        {
+            session.last_used = session.last_used.max(now);
+        }
+    }
+
+    /// Begin an explicit session, or restart the TTL of one the agent
+    /// already began. Fails if another agent began it or it ended.
+    pub fn begin(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        ttl_ms: u64,
+        now: u64,
+    ) -> Result<Session, String> {
+        if ttl_ms == 0 {
+            return Err("ttl_ms must be positive".to_string());
+        }
+        if self.has_ended(agent_id, session_id) {
+            return Err(format!("Session '{}' has ended", session_id));
+        }
+        if let Some(session) = self.begun.get_mut(session_id) {
+            if session.agent_id != agent_id {
+                return Err(format!(
+                    "Session '{}' belongs to agent '{}'",
+                    session_id, session.agent_id
+                ));
+            }
+            session.ttl_ms = ttl_ms;
+            session.last_used = session.last_used.max(now);
+            return Ok(session.clone());
+        }
+        let session = Session {
+            session_id: session_id.to_string(),
+            agent_id: agent_id.to_string(),
+            ttl_ms,
+            started_at: now,
+            last_used: now,
+        };
+        self.begun.insert(session_id.to_string(), session.clone());
+        Ok(session)
+    }
+
+    /// The explicit session `session_id`, live or not yet found expired
+    pub fn session(&self, session_id: &str) -> Option<&Session> {
+        self.begun.get(session_id)
+    }
+
+    /// End an explicit session now, returning it
+    pub fn end_session(&mut self, session_id: &str, now: u64) -> Option<Session> {
+        let session = self.begun.remove(session_id)?;
+        self.ended
+            .insert(session.session_id.clone(), (session.agent_id.clone(), now));
+        Some(session)
+    }
+
+    /// End the explicit sessions whose TTL ran out, returning them
+    pub fn take_expired_sessions(&mut self, now: u64) -> Vec<Session> {
+        let mut expired = Vec::new();
+        self.begun.retain(|_, session| {
+            let live = session.is_live(now);
+            if !live {
+                expired.push(session.clone());
+            }
+            live
+        });
+        for session in &expired {
+            self.ended
+                .insert(session.session_id.clone(), (session.agent_id.clone(), now));
+        }
+        expired.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        expired
+    }
+
+    /// Whether the agent's explicit session `session_id` ended
+    pub fn has_ended(&self, agent_id: &str, session_id: &str) -> bool {
+        self.ended
+            .get(session_id)
+            .is_some_and(|(owner, _)| owner == agent_id)
+    }
+
+    /// Forget explicit sessions that ended longer ago than the policy's
+    /// retention. Returns the number forgotten.
+    pub fn purge_ended(&mut self, now: u64) -> usize {
+        let before = now.saturating_sub(self.policy.ended_retention_ms);
+        let initial = self.ended.len();
+        self.ended.retain(|_, (_, ended_at)| *ended_at >= before);
+        initial - self.ended.len()
+    }
+
+    /// Forget every session of the agent, default or explicit, without
+    /// remembering them as ended
+    pub fn forget(&mut self, agent_id: &str) {
+        self.sessions.remove(agent_id);
+        self.begun.retain(|_, session| session.agent_id != agent_id);
+        self.ended.retain(|_, (owner, _)| owner != agent_id);
+    }
+
+    /// The agent's live default session, if any
+    pub fn get(&self, agent_id: &str, now: u64) -> Option<&DefaultSession> {
+        self.sessions
+            .get(agent_id)
+            .filter(|session| self.is_live(session, now))
+    }
+
+    /// End the agent's default session now. Returns false if it had none.
+    pub fn end(&mut self, agent_id: &str) -> bool {
+        self.take(agent_id).is_some()
+    }
+
+    /// End the agent's default session now, returning it
+    pub fn take(&mut self, agent_id: &str) -> Option<DefaultSession> {
+        self.sessions.remove(agent_id)
+    }
+
+    /// Drop sessions idle for longer than the TTL. Returns the number
+    /// dropped.
+    pub fn purge_expired(&mut self, now: u64) -> usize {
+        self.take_expired(now).len()
+    }
+
+    /// Drop sessions idle for longer than the TTL, returning them
+    pub fn take_expired(&mut self, now: u64) -> Vec<DefaultSession> {
+        let ttl = self.policy.idle_ttl_ms;
+        let mut expired = Vec::new();
+        self.sessions.retain(|_, session| {
+            let live = session.last_used.saturating_add(ttl) > now;
+            if !live {
+                expired.push(session.clone());
+            }
+            live
+        });
+        expired
+    }
+
+    /// Default and explicit sessions, including those remembered as ended
+    pub fn len(&self) -> usize {
+        self.sessions.len() + self.begun.len() + self.ended.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty() && self.begun.is_empty() && self.ended.is_empty()
+    }
+
+    /// Approximate footprint of all sessions
+    pub fn estimated_bytes(&self) -> usize {
+        let defaults: usize = self
+            .sessions
+            .iter()
+            .map(|(agent_id, session)| {
+                agent_id.capacity()
+                    + session.agent_id.capacity()
+                    + session.session_id.capacity()
+                    + std::mem::size_of::<(String, DefaultSession)>()
+            })
+            .sum();
+        let begun: usize = self
+            .begun
+            .iter()
+            .map(|(session_id, session)| {
+                session_id.capacity()
+                    + session.agent_id.capacity()
+                    + session.session_id.capacity()
+                    + std::mem::size_of::<(String, Session)>()
+            })
+            .sum();
+        let ended: usize = self
+            .ended
+            .iter()
+            .map(|(session_id, (agent_id, _))| {
+                session_id.capacity()
+                    + agent_id.capacity()
+                    + std::mem::size_of::<(String, (String, u64))>()
+            })
+            .sum();
+        defaults + begun + ended
+    }
+}
@@ -0,0 +1,197 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::{Clock, ManualClock};
+    use crate::codes::ReasonCode;
+    use crate::session::{SessionPolicy, SessionRegistry};
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, Predicate, ResourceRef, ResourceType,
+        SPOTriple,
+    };
+    use std::sync::Arc;
+
+    fn manifest(session_id: &str, path: &str) -> IntentManifest {
+        IntentManifest {
+            session_id: session_id.to_string(),
+            agent_id: "agent".to_string(),
+            intents: vec![SPOTriple {
+                id: format!("t{}", path),
+                subject: "agent".to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, path),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: session_id.to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        }
+    }
+
+    fn acquire(client: &mut KlockClient, session_id: &str, path: &str) -> LeaseResult {
+        client.acquire_lease("agent", session_id, "FILE", path, "MUTATES", 60_000)
+    }
+
+    fn expired(result: LeaseResult) -> bool {
+        matches!(
+            result,
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::SessionExpired,
+                ..
+            }
+        )
+    }
+
+    #[test]
+    fn test_default_session_slides_and_expires() {
+        let mut sessions = SessionRegistry::new(SessionPolicy {
+            idle_ttl_ms: 1000,
+            ..SessionPolicy::default()
+        });
+        let first = sessions.resolve("agent", 1000);
+        assert_eq!(sessions.resolve("agent", 1900), first);
+        assert_ne!(sessions.resolve("other", 1900), first);
+
+        // Touching another session does not keep the default alive
+        sessions.touch("agent", "explicit", 2800);
+        assert!(sessions.get("agent", 2899).is_some());
+        sessions.touch("agent", &first, 2800);
+        assert_eq!(sessions.get("agent", 3700).unwrap().session_id, first);
+
+        // Idle past the TTL: the next request starts a new session
+        let second = sessions.resolve("agent", 3800);
+        assert_ne!(second, first);
+        assert_eq!(sessions.get("agent", 3800).unwrap().created_at, 3800);
+
+        assert_eq!(sessions.purge_expired(4700), 1);
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions.end("agent"));
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn test_client_fills_omitted_sessions() {
+        let mut client = KlockClient::new();
+        client.register_agent("agent", 100);
+        let LeaseResult::Success { lease: first } =
+            client.acquire_lease("agent", "", "FILE", "/src/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected lease");
+        };
+        let LeaseResult::Success { lease: second } =
+            client.acquire_lease("agent", "", "FILE", "/src/b.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected lease");
+        };
+        let session = client.default_session("agent");
+        assert_eq!(first.session_id, session);
+        assert_eq!(second.session_id, session);
+
+        // The default session is reentrant like any explicit one
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: String::new(),
+            agent_id: "agent".to_string(),
+            intents: vec![SPOTriple {
+                id: "t1".to_string(),
+                subject: "agent".to_string(),
+                predicate: Predicate::Mutates,
+                object: ResourceRef::new(ResourceType::File, "/src/a.ts"),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: String::new(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        assert_eq!(verdict.session_id, session);
+
+        // Explicit sessions are untouched
+        let LeaseResult::Success { lease: explicit } =
+            client.acquire_lease("agent", "s1", "FILE", "/src/c.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected lease");
+        };
+        assert_eq!(explicit.session_id, "s1");
+        assert_eq!(client.memory_report().sessions.count, 1);
+
+        assert!(client.end_default_session("agent"));
+        assert_eq!(client.memory_report().sessions.count, 0);
+    }
+
+    #[test]
+    fn test_ending_a_session_releases_its_work() {
+        let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        client.register_agent("agent", 100);
+        let session = client.begin_session("agent", "s1", 10_000).unwrap();
+        assert_eq!(session.expires_at(), 11_000);
+        assert!(matches!(
+            acquire(&mut client, "s1", "/a.ts"),
+            LeaseResult::Success { .. }
+        ));
+        let verdict = client.declare_intent(&manifest("s1", "/b.ts"));
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        // Sessions never begun are left alone
+        assert!(matches!(
+            acquire(&mut client, "s2", "/c.ts"),
+            LeaseResult::Success { .. }
+        ));
+
+        let end = client.end_session("s1").unwrap();
+        assert!(!end.expired);
+        assert_eq!(end.leases_released.len(), 1);
+        assert_eq!(end.intents_withdrawn, 1);
+        assert_eq!(client.get_active_leases().len(), 1);
+        assert!(client.end_session("s1").is_none());
+        assert!(client.end_session("s2").is_none());
+
+        assert!(expired(acquire(&mut client, "s1", "/a.ts")));
+        let verdict = client.declare_intent(&manifest("s1", "/b.ts"));
+        assert_eq!(verdict.status, KernelVerdictStatus::Rejected);
+        assert_eq!(verdict.code, Some(ReasonCode::SessionExpired));
+        assert!(client.begin_session("agent", "s1", 10_000).is_err());
+        assert!(client.begin_session("other", "s2", 0).is_err());
+    }
+
+    #[test]
+    fn test_sessions_expire_without_use() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("agent", 100);
+        let session_id = client
+            .begin_session("agent", "", 10_000)
+            .unwrap()
+            .session_id;
+        assert!(session_id.starts_with("agent-"));
+        assert!(client.begin_session("other", &session_id, 10_000).is_err());
+        let LeaseResult::Success { lease } = acquire(&mut client, &session_id, "/a.ts") else {
+            panic!("Expected lease");
+        };
+
+        // Heartbeats on its leases keep it alive
+        clock.advance(9_000);
+        assert!(client.heartbeat_lease(&lease.id, clock.now_ms()));
+        clock.advance(9_000);
+        assert!(client.session(&session_id).is_some());
+
+        clock.advance(1_001);
+        assert!(client.session(&session_id).is_none());
+        assert!(expired(acquire(&mut client, &session_id, "/b.ts")));
+        assert!(client.get_active_leases().is_empty());
+
+        // Once forgotten, the ID is a plain session again
+        client.set_session_policy(SessionPolicy {
+            ended_retention_ms: 1_000,
+            ..SessionPolicy::default()
+        });
+        clock.advance(1_001);
+        client.compact();
+        assert!(matches!(
+            acquire(&mut client, &session_id, "/b.ts"),
+            LeaseResult::Success { .. }
+        ));
+    }
+}
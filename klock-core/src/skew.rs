@@ -0,0 +1,133 @@
+//! Clock skew detection.
+//!
+//! Lease expiry is wall-clock time, and wall clocks jump: NTP steps them,
+//! and a paused VM resumes far behind. When the clock steps back, time the
+//! client already lived through repeats, and leases granted or renewed
+//! meanwhile are timed against a clock it no longer trusts. A `SkewPolicy`
+//! tolerates backward moves up to `tolerance_ms` (slews and small
+//! corrections). A larger one is a suspicious jump, and the client's
+//! eviction passes are held until the clock is back past the latest time
+//! seen before it. Jumps forward cannot be told from idle time without a
+//! second clock; run on a `MonotonicClock` to be immune to both.
+
+use serde::{Deserialize, Serialize};
+
+/// How far back the clock may move before eviction is held
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkewPolicy {
+    /// Backward moves up to this many ms are tolerated; 0 disables
+    /// detection
+    pub tolerance_ms: u64,
+}
+
+impl SkewPolicy {
+    pub fn new(tolerance_ms: u64) -> Self {
+        Self { tolerance_ms }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.tolerance_ms > 0
+    }
+}
+
+/// A backward jump of the clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockJump {
+    /// The latest time seen before the jump
+    pub from: u64,
+    /// The first time seen after it
+    pub to: u64,
+}
+
+impl ClockJump {
+    /// How far the clock went back, in ms
+    pub fn size_ms(&self) -> u64 {
+        self.from - self.to
+    }
+}
+
+/// Clock state, for health checks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockReport {
+    /// Expiry runs on a monotonic clock rather than the wall clock
+    pub monotonic: bool,
+    /// With a monotonic clock, how far the wall clock is ahead of it (ms),
+    /// to map lease times to wall time for display
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wall_offset_ms: Option<i64>,
+    pub tolerance_ms: u64,
+    /// A backward jump was seen and the clock is not yet past `high_water`;
+    /// eviction is held meanwhile
+    pub suspect: bool,
+    /// The latest time seen
+    pub high_water: u64,
+    pub backward_jumps: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_jump: Option<ClockJump>,
+    /// Eviction passes skipped while the clock was suspect
+    pub evictions_held: u64,
+}
+
+/// The policy and the clock readings it has judged
+#[derive(Debug, Clone, Default)]
+pub struct SkewDetector {
+    policy: SkewPolicy,
+    high_water: u64,
+    suspect: bool,
+    backward_jumps: u64,
+    last_jump: Option<ClockJump>,
+    evictions_held: u64,
+}
+
+impl SkewDetector {
+    pub fn policy(&self) -> &SkewPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: SkewPolicy) {
+        self.policy = policy;
+        self.suspect = false;
+    }
+
+    /// Judge the reading `now`. Returns whether eviction may run: false
+    /// from a jump back of more than the tolerance until the clock is past
+    /// the latest time seen before it.
+    pub fn observe(&mut self, now: u64) -> bool {
+        if self.policy.is_enabled() {
+            if now.saturating_add(self.policy.tolerance_ms) < self.high_water && !self.suspect {
+                self.suspect = true;
+                self.backward_jumps += 1;
+                self.last_jump = Some(ClockJump {
+                    from: self.high_water,
+                    to: now,
+                });
+            } else if self.suspect && now >= self.high_water {
+                self.suspect = false;
+            }
+        }
+        self.high_water = self.high_water.max(now);
+        !self.suspect
+    }
+
+    pub fn is_suspect(&self) -> bool {
+        self.suspect
+    }
+
+    pub fn record_held(&mut self) {
+        self.evictions_held += 1;
+    }
+
+    /// The state, with the clock's `wall_offset_ms` when it is monotonic
+    pub fn report(&self, wall_offset_ms: Option<i64>) -> ClockReport {
+        ClockReport {
+            monotonic: wall_offset_ms.is_some(),
+            wall_offset_ms,
+            tolerance_ms: self.policy.tolerance_ms,
+            suspect: self.suspect,
+            high_water: self.high_water,
+            backward_jumps: self.backward_jumps,
+            last_jump: self.last_jump,
+            evictions_held: self.evictions_held,
+        }
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::{Clock, ManualClock, MonotonicClock, SystemClock};
+    use crate::skew::{ClockJump, SkewDetector, SkewPolicy};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_small_backward_moves_are_tolerated() {
+        let mut detector = SkewDetector::default();
+        detector.set_policy(SkewPolicy::new(1000));
+        assert!(detector.observe(10_000));
+        assert!(detector.observe(9_000));
+        assert!(detector.observe(10_500));
+        assert!(!detector.is_suspect());
+        assert_eq!(detector.report(None).backward_jumps, 0);
+    }
+
+    #[test]
+    fn test_jump_back_holds_until_the_clock_is_past_the_high_water() {
+        let mut detector = SkewDetector::default();
+        detector.set_policy(SkewPolicy::new(1000));
+        assert!(detector.observe(10_000));
+        assert!(!detector.observe(5_000));
+        assert!(!detector.observe(9_999));
+        assert!(detector.observe(10_000));
+
+        let report = detector.report(None);
+        assert!(!report.suspect);
+        assert_eq!(report.backward_jumps, 1);
+        assert_eq!(
+            report.last_jump,
+            Some(ClockJump {
+                from: 10_000,
+                to: 5_000
+            })
+        );
+        assert_eq!(report.last_jump.unwrap().size_ms(), 5_000);
+    }
+
+    #[test]
+    fn test_detection_is_off_by_default() {
+        let mut detector = SkewDetector::default();
+        assert!(detector.observe(10_000));
+        assert!(detector.observe(1_000));
+
+        let client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        assert!(client.clock_report().is_none());
+    }
+
+    #[test]
+    fn test_client_holds_eviction_after_a_jump_back() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.set_skew_policy(SkewPolicy::new(1000));
+        client.register_agent("a", 100);
+        client.acquire_lease("a", "s1", "FILE", "/a.ts", "MUTATES", 10_000);
+        client.acquire_lease("a", "s1", "FILE", "/b.ts", "MUTATES", 30_000);
+
+        clock.set(20_000);
+        assert_eq!(client.evict_expired(), 1);
+
+        // An NTP step back by 8s: nothing is evicted until 20s again
+        clock.set(12_000);
+        assert_eq!(client.evict_expired(), 0);
+        assert_eq!(client.compact().leases_removed, 0);
+        let report = client.clock_report().unwrap();
+        assert!(report.suspect);
+        assert!(!report.monotonic);
+        assert_eq!(report.high_water, 20_000);
+        assert_eq!(report.evictions_held, 2);
+
+        clock.set(32_000);
+        assert_eq!(client.evict_expired(), 1);
+        assert!(!client.clock_report().unwrap().suspect);
+        assert!(client.get_active_leases().is_empty());
+    }
+
+    #[test]
+    fn test_monotonic_clock_starts_at_wall_time() {
+        let clock = MonotonicClock::new();
+        let wall = SystemClock.now_ms();
+        assert!(clock.now_ms().abs_diff(wall) < 1000);
+        assert!(clock.wall_offset_ms().unwrap().abs() < 1000);
+        assert!(clock.now_ms() <= clock.now_ms());
+
+        let mut client = KlockClient::new();
+        client.set_clock(Arc::new(clock));
+        let report = client.clock_report().unwrap();
+        assert!(report.monotonic);
+        assert!(report.wall_offset_ms.is_some());
+        assert_eq!(report.tolerance_ms, 0);
+    }
+}
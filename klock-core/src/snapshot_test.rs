@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::state::{IntentManifest, KernelVerdictStatus, StateSnapshot};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, LeaseState, Predicate, ResourceRef,
+        ResourceType, SPOTriple,
+    };
+    use std::sync::Arc;
+
+    fn manifest(agent_id: &str, predicate: Predicate, path: &str) -> IntentManifest {
+        IntentManifest {
+            session_id: format!("s_{}", agent_id),
+            agent_id: agent_id.to_string(),
+            intents: vec![SPOTriple {
+                id: format!("t_{}{}", agent_id, path),
+                subject: agent_id.to_string(),
+                predicate,
+                object: ResourceRef::new(ResourceType::File, path),
+                timestamp: 1000,
+                confidence: Confidence::High,
+                session_id: format!("s_{}", agent_id),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        }
+    }
+
+    fn client() -> KlockClient {
+        let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        client.register_agent("provider", 100);
+        client.register_agent("consumer", 200);
+        client.register_agent("writer", 300);
+        client
+    }
+
+    /// A client with a lease, a reservation and two dependent intents
+    fn populated() -> KlockClient {
+        let mut client = client();
+        client.acquire_lease("writer", "s1", "FILE", "/src/api.ts", "MUTATES", 60_000);
+        client
+            .pre_acquire(
+                "writer",
+                "s1",
+                &[(
+                    ResourceRef::new(ResourceType::File, "/src/db.ts"),
+                    Predicate::Mutates,
+                )],
+                60_000,
+                30_000,
+            )
+            .unwrap();
+        for (agent_id, predicate) in [
+            ("provider", Predicate::Provides),
+            ("consumer", Predicate::DependsOn),
+        ] {
+            let verdict = client.declare_intent(&manifest(agent_id, predicate, "/lib/auth.ts"));
+            assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        }
+        client
+    }
+
+    #[test]
+    fn test_snapshot_round_trips_through_json_into_another_client() {
+        let original = populated();
+        let json = serde_json::to_string(&original.snapshot()).unwrap();
+        let snapshot: StateSnapshot = serde_json::from_str(&json).unwrap();
+
+        let mut restored = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        restored.set_firehose_capacity(10);
+        assert_eq!(restored.restore(snapshot), Ok(2));
+        // Restored leases are published like any other grant
+        assert_eq!(restored.firehose(0, 10).unwrap().entries.len(), 2);
+
+        let before = original.snapshot();
+        let after = restored.snapshot();
+        assert_eq!(after.priorities, before.priorities);
+        assert_eq!(after.active_intents, before.active_intents);
+        assert_eq!(
+            serde_json::to_value(&after.active_leases).unwrap(),
+            serde_json::to_value(&before.active_leases).unwrap()
+        );
+        assert_eq!(after.pending_leases.len(), 1);
+        assert_eq!(after.pending_leases[0].state, LeaseState::Pending);
+        // Rebuilt from the intents, not copied
+        assert_eq!(restored.dependency_graph(), original.dependency_graph());
+        assert!(!restored.dependency_graph().is_empty());
+    }
+
+    #[test]
+    fn test_restored_state_blocks_like_the_original() {
+        let mut restored = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        restored.restore(populated().snapshot()).unwrap();
+
+        // The writer's restored lease is in the way of the consumer, still
+        // senior under the restored priorities...
+        let result =
+            restored.acquire_lease("consumer", "s2", "FILE", "/src/api.ts", "MUTATES", 60_000);
+        let LeaseResult::Failure { reason, .. } = result else {
+            panic!("Expected the restored lease to block, got {:?}", result);
+        };
+        assert_eq!(reason, LeaseFailureReason::Wait);
+        // ...and so is the provider's restored intent
+        let summary =
+            restored.summarize_intent(&manifest("writer", Predicate::Mutates, "/lib/auth.ts"));
+        assert_eq!(summary.conflicts, 2);
+    }
+
+    #[test]
+    fn test_restore_refuses_a_client_holding_state() {
+        let snapshot = populated().snapshot();
+        let mut busy = client();
+        busy.acquire_lease("writer", "s1", "FILE", "/src/other.ts", "MUTATES", 60_000);
+        assert!(busy.restore(snapshot.clone()).is_err());
+
+        let mut declared = client();
+        declared.declare_intent(&manifest("provider", Predicate::Provides, "/lib/x.ts"));
+        assert!(declared.restore(snapshot).is_err());
+        assert_eq!(declared.get_active_leases().len(), 0);
+    }
+}
@@ -1,8 +1,19 @@
-use crate::conflict::{ConflictEngine, ConflictResult};
-use crate::scheduler::{VerdictStatus, WaitDieScheduler};
-use crate::types::{Lease, SPOTriple};
+use crate::backoff::{BackoffPolicy, BackoffScheduler};
+use crate::clock::SystemClock;
+use crate::codes::ReasonCode;
+use crate::conflict::{ConflictDetail, ConflictEngine};
+use crate::deadlock::{WaitForEdge, WaitForGraph};
+use crate::dependency::{DependencyEdge, DependencyGraph};
+use crate::fallback::Fallback;
+use crate::infrastructure::partition_for_activation;
+use crate::registry::Priority;
+use crate::remediation::{self, Suggestion};
+use crate::scheduler::{Scheduler, SchedulerVerdict, VerdictStatus, WaitDieScheduler};
+use crate::summary::ConflictSummary;
+use crate::types::{Holder, Lease, LeaseState, Predicate, ResourceRef, SPOTriple};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IntentManifest {
@@ -11,11 +22,75 @@ pub struct IntentManifest {
     pub intents: Vec<SPOTriple>,
 }
 
-#[derive(Debug, Clone)]
+impl IntentManifest {
+    /// The resources the manifest declares for one agent's session
+    pub fn declared_by<'a>(
+        &'a self,
+        agent_id: &'a str,
+        session_id: &'a str,
+    ) -> impl Iterator<Item = &'a ResourceRef> + 'a {
+        self.intents
+            .iter()
+            .filter(move |i| i.subject == agent_id && i.session_id == session_id)
+            .map(|i| &i.object)
+    }
+}
+
+/// The state a manifest is judged against. Only leases and intents that may
+/// overlap the manifest matter; `KlockClient` builds snapshots holding just
+/// those, found through its resource indexes. `KlockClient::snapshot` holds
+/// everything, for checkpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateSnapshot {
     pub active_leases: Vec<Lease>,
+    /// Reservations waiting to be activated
+    pub pending_leases: Vec<Lease>,
     pub active_intents: Vec<SPOTriple>,
-    pub priorities: HashMap<String, u64>,
+    pub priorities: HashMap<String, Priority>,
+    /// `DEPENDS_ON` edges between the holders of all active intents, not
+    /// only those in `active_intents`
+    pub dependencies: DependencyGraph,
+    /// Who waits on whom, for deadlock detection (see `deadlock`)
+    pub waits_for: WaitForGraph,
+}
+
+impl StateSnapshot {
+    /// The resources one agent's session holds intents or leases on
+    pub fn declared_by<'a>(
+        &'a self,
+        agent_id: &'a str,
+        session_id: &'a str,
+    ) -> impl Iterator<Item = &'a ResourceRef> + 'a {
+        let intents = self
+            .active_intents
+            .iter()
+            .filter(move |i| i.subject == agent_id && i.session_id == session_id)
+            .map(|i| &i.object);
+        let leases = self
+            .active_leases
+            .iter()
+            .filter(move |l| l.agent_id == agent_id && l.session_id == session_id)
+            .map(|l| &l.resource);
+        intents.chain(leases)
+    }
+}
+
+/// Projected lease state at a future time, assuming no further heartbeats,
+/// releases, or acquisitions happen before then.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateProjection {
+    /// The projected timestamp
+    pub at: u64,
+    /// Leases whose TTL will have elapsed by `at`
+    pub expired: Vec<Lease>,
+    /// Leases still held at `at`
+    pub active: Vec<Lease>,
+    /// Reservations that would be granted if activated at `at`
+    pub grantable: Vec<Lease>,
+    /// Reservations that would still be refused at `at`
+    pub blocked: Vec<Lease>,
+    /// Reservations whose activation deadline will have passed by `at`
+    pub lapsed: Vec<Lease>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -23,6 +98,22 @@ pub enum KernelVerdictStatus {
     Granted,
     Wait,
     Die,
+    /// The manifest can never be granted as declared (it would close a
+    /// dependency cycle); retrying it unchanged does not help
+    Rejected,
+}
+
+impl KernelVerdictStatus {
+    /// How restrictive the status is: `Granted` < `Wait` < `Die` <
+    /// `Rejected`
+    pub fn severity(&self) -> u8 {
+        match self {
+            KernelVerdictStatus::Granted => 0,
+            KernelVerdictStatus::Wait => 1,
+            KernelVerdictStatus::Die => 2,
+            KernelVerdictStatus::Rejected => 3,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,93 +122,658 @@ pub struct KernelVerdict {
     pub session_id: String,
     pub status: KernelVerdictStatus,
     pub reason: Option<String>,
+    /// Catalog code for a refusal (see `codes`); absent when granted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ReasonCode>,
     pub held_by: Option<String>,
+    /// Holders of every lease the scheduler refused the manifest over
+    #[serde(default)]
+    pub holders: Vec<Holder>,
     pub conflicts: Vec<String>,
+    /// Every intent and lease standing in the manifest's way
+    #[serde(default)]
+    pub blockers: Vec<ConflictDetail>,
+    /// After a Die, how long to back off; after a Wait, how long until the
+    /// leases in the way are expected to run out
     pub retry_after_ms: Option<u64>,
+    /// Operations the engine's implication rules derived from the manifest
+    #[serde(default)]
+    pub implied: Vec<String>,
+    /// Conflicts involving tentative (low-confidence) intents, which the
+    /// engine reports without refusing the manifest
+    #[serde(default)]
+    pub warnings: Vec<ConflictDetail>,
+    /// Uncontended alternatives to the resources a Die verdict was
+    /// refused on, from the client's fallback groups
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<Fallback>,
+    /// What the agent can do about a Wait or Die (see `remediation`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Suggestion>,
+    /// Each intent's own verdict, in declaration order; empty when the
+    /// manifest was refused before its intents were judged
+    #[serde(default)]
+    pub per_intent: Vec<IntentVerdict>,
+}
+
+/// How one intent of a manifest fared
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntentVerdict {
+    pub intent_id: String,
+    /// `Granted` if nothing refuses the intent itself, even when the
+    /// manifest is refused over another one
+    pub status: KernelVerdictStatus,
+    /// The intent's share of `KernelVerdict::conflicts`
+    pub conflicts: Vec<String>,
+    /// The agent the intent's verdict was decided on
+    pub held_by: Option<String>,
+}
+
+impl IntentVerdict {
+    fn granted(intent: &SPOTriple) -> Self {
+        Self {
+            intent_id: intent.id.clone(),
+            status: KernelVerdictStatus::Granted,
+            conflicts: Vec::new(),
+            held_by: None,
+        }
+    }
+}
+
+/// The verdict on manifests declared together, all or nothing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AtomicVerdict {
+    /// `Granted` only if every manifest was; otherwise the most severe
+    /// status among them
+    pub status: KernelVerdictStatus,
+    /// One verdict per manifest, in declaration order. When any manifest
+    /// is refused, those that would have been granted are refused too,
+    /// with code `K1007 SIBLING_REFUSED`.
+    pub verdicts: Vec<KernelVerdict>,
+}
+
+impl AtomicVerdict {
+    /// Combine per-manifest verdicts: unless all were granted, refuse the
+    /// granted ones on behalf of the first manifest refused on its own.
+    /// Combining combined verdicts again refreshes those refusals.
+    pub fn new(mut verdicts: Vec<KernelVerdict>) -> Self {
+        let status = verdicts
+            .iter()
+            .map(|v| v.status.clone())
+            .max_by_key(KernelVerdictStatus::severity)
+            .unwrap_or(KernelVerdictStatus::Granted);
+        let refused = verdicts.iter().position(|v| {
+            v.status != KernelVerdictStatus::Granted && v.code != Some(ReasonCode::SiblingRefused)
+        });
+        if let Some(refused) = refused {
+            let culprit = verdicts[refused].clone();
+            for verdict in &mut verdicts {
+                if verdict.status != KernelVerdictStatus::Granted
+                    && verdict.code != Some(ReasonCode::SiblingRefused)
+                {
+                    continue;
+                }
+                verdict.status = status.clone();
+                verdict.code = Some(ReasonCode::SiblingRefused);
+                verdict.reason = Some(format!(
+                    "Not registered: the manifest for session '{}' was refused ({})",
+                    culprit.session_id,
+                    culprit.reason.as_deref().unwrap_or("no reason given")
+                ));
+                verdict.held_by = culprit.held_by.clone();
+                verdict.holders = culprit.holders.clone();
+                verdict.retry_after_ms = culprit.retry_after_ms;
+                verdict.implied.clear();
+            }
+        }
+        Self { status, verdicts }
+    }
+}
+
+/// The outcome of declaring a manifest and leasing its intents at once
+/// (`KlockClient::acquire_manifest`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestAcquisition {
+    pub verdict: KernelVerdict,
+    /// One lease per intent, in declaration order; empty unless every
+    /// intent got one
+    pub leases: Vec<Lease>,
+    /// The acquire that failed, rolling back the leases before it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<ManifestLeaseFailure>,
+}
+
+impl ManifestAcquisition {
+    /// Whether the manifest was granted and every intent leased
+    pub fn acquired(&self) -> bool {
+        self.verdict.status == KernelVerdictStatus::Granted && self.failure.is_none()
+    }
+}
+
+/// Why an intent of a granted manifest could not be leased
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestLeaseFailure {
+    pub intent_id: String,
+    /// `LeaseFailureReason::as_str`
+    pub reason: String,
+    pub code: ReasonCode,
+    pub wait_time: Option<u64>,
+    #[serde(default)]
+    pub holders: Vec<Holder>,
 }
 
 pub struct KlockKernel;
 
 impl KlockKernel {
+    /// Evaluate a manifest using the built-in matrix and the default Wait-Die
+    /// scheduler, with Die verdicts backing off as `BackoffPolicy::default()`.
     pub fn execute(state: &StateSnapshot, manifest: &IntentManifest) -> KernelVerdict {
+        Self::execute_with(
+            state,
+            manifest,
+            &ConflictEngine::default(),
+            &BackoffScheduler::new(
+                Arc::new(WaitDieScheduler),
+                BackoffPolicy::default(),
+                Arc::new(SystemClock),
+            ),
+        )
+    }
+
+    /// Evaluate a manifest, detecting conflicts with the given engine and
+    /// resolving them through the given scheduler.
+    pub fn execute_with(
+        state: &StateSnapshot,
+        manifest: &IntentManifest,
+        engine: &ConflictEngine,
+        scheduler: &dyn Scheduler,
+    ) -> KernelVerdict {
         let mut conflicts = Vec::new();
+        let mut blockers = Vec::new();
         let mut worst_status = KernelVerdictStatus::Granted;
         let mut return_reason = None;
         let mut return_held_by = None;
+        let mut holders: Vec<Holder> = Vec::new();
         let mut return_retry = None;
+        let mut implied = Vec::new();
+        let mut warnings = Vec::new();
+        let mut per_intent = Vec::with_capacity(manifest.intents.len());
+        let mut suggestions: Vec<Suggestion> = Vec::new();
+
+        let cycle = Self::dependency_cycle(state, manifest, engine);
 
-        for intent in &manifest.intents {
-            // 1. Check for Conflicts via Conflict Engine
-            let conflict_result = ConflictEngine::check(intent, &state.active_intents);
+        // Intents held at tentative confidence only warn
+        let split: (Vec<SPOTriple>, Vec<SPOTriple>);
+        let (firm_intents, tentative_intents): (&[SPOTriple], &[SPOTriple]) = if state
+            .active_intents
+            .iter()
+            .any(|i| engine.is_tentative(i.confidence))
+        {
+            split = state
+                .active_intents
+                .iter()
+                .cloned()
+                .partition(|i| !engine.is_tentative(i.confidence));
+            (&split.0, &split.1)
+        } else {
+            (&state.active_intents, &[])
+        };
 
-            if let ConflictResult::Conflict { reason } = conflict_result {
-                conflicts.push(reason.clone());
+        // Conflicts with existing intents, checked for the whole manifest at once
+        let firm_conflicts = engine.check_batch(&manifest.intents, firm_intents);
+        let tentative_conflicts = engine.check_batch(&manifest.intents, tentative_intents);
+        // A tentative intent is checked against every intent, firm or not
+        let all_conflicts = if manifest
+            .intents
+            .iter()
+            .any(|i| engine.is_tentative(i.confidence))
+        {
+            engine.check_batch(&manifest.intents, &state.active_intents)
+        } else {
+            Vec::new()
+        };
 
-                // 2. Resolve via Scheduler
-                let scheduler_verdict = WaitDieScheduler::decide(
+        // One scheduler decision and lease check per distinct operation,
+        // however often the manifest repeats it
+        let mut decisions: HashMap<(Predicate, &ResourceRef), SchedulerVerdict> = HashMap::new();
+        let mut held_leases: HashMap<(Predicate, &ResourceRef), Vec<ConflictDetail>> =
+            HashMap::new();
+
+        for (position, intent) in manifest.intents.iter().enumerate() {
+            implied.extend(
+                engine
+                    .implied(intent.predicate, &intent.object)
+                    .iter()
+                    .map(|i| i.describe(intent.predicate, &intent.object)),
+            );
+
+            let operation = (intent.predicate, &intent.object);
+            let refined_leases;
+            let leases: &[Lease] = if engine.symbol_refinement().is_some() {
+                let (relaxed, kept): (Vec<Lease>, Vec<Lease>) =
+                    state.active_leases.iter().cloned().partition(|lease| {
+                        engine.refines(
+                            lease.predicate,
+                            &lease.resource,
+                            state.declared_by(&lease.agent_id, &lease.session_id),
+                            intent.predicate,
+                            &intent.object,
+                            manifest.declared_by(&intent.subject, &intent.session_id),
+                        )
+                    });
+                warnings.extend(engine.check_leases_all(
                     &manifest.agent_id,
+                    &manifest.session_id,
                     intent.predicate,
                     &intent.object,
-                    &state.active_leases,
-                    &state.priorities,
-                );
+                    &relaxed,
+                ));
+                refined_leases = kept;
+                &refined_leases
+            } else {
+                &state.active_leases
+            };
+            let mut lease_blockers = || {
+                held_leases
+                    .entry(operation)
+                    .or_insert_with(|| {
+                        engine.check_leases_all(
+                            &manifest.agent_id,
+                            &manifest.session_id,
+                            intent.predicate,
+                            &intent.object,
+                            leases,
+                        )
+                    })
+                    .clone()
+            };
+
+            // A tentative intent is never refused, whatever it collides with
+            if engine.is_tentative(intent.confidence) {
+                warnings.extend(all_conflicts[position].iter().cloned());
+                warnings.extend(lease_blockers());
+                per_intent.push(IntentVerdict::granted(intent));
+                continue;
+            }
+            warnings.extend(tentative_conflicts[position].iter().cloned());
+
+            // 1. Check for Conflicts via Conflict Engine; file-level ones
+            // between disjoint symbols only warn
+            let refined_blockers;
+            let intent_blockers = if engine.symbol_refinement().is_some() {
+                let (relaxed, hard): (Vec<ConflictDetail>, Vec<ConflictDetail>) = firm_conflicts
+                    [position]
+                    .iter()
+                    .cloned()
+                    .partition(|conflict| {
+                        engine.refines(
+                            conflict.predicate,
+                            &conflict.resource,
+                            state.declared_by(&conflict.holder, &conflict.session_id),
+                            intent.predicate,
+                            &intent.object,
+                            manifest.declared_by(&intent.subject, &intent.session_id),
+                        )
+                    });
+                warnings.extend(relaxed);
+                refined_blockers = hard;
+                &refined_blockers
+            } else {
+                &firm_conflicts[position]
+            };
+
+            // 2. Resolve via Scheduler
+            let scheduler_verdict = decisions
+                .entry(operation)
+                .or_insert_with(|| {
+                    scheduler.decide(
+                        engine,
+                        &manifest.agent_id,
+                        &manifest.session_id,
+                        intent.predicate,
+                        &intent.object,
+                        leases,
+                        &state.priorities,
+                    )
+                })
+                .clone();
+
+            if scheduler_verdict.status != VerdictStatus::Granted {
+                for holder in &scheduler_verdict.holders {
+                    if !holders.contains(holder) {
+                        holders.push(holder.clone());
+                    }
+                }
+            }
+
+            let mut verdict = IntentVerdict::granted(intent);
+            if scheduler_verdict.status != VerdictStatus::Granted {
+                verdict.status = match scheduler_verdict.status {
+                    VerdictStatus::Wait => KernelVerdictStatus::Wait,
+                    _ => KernelVerdictStatus::Die,
+                };
+                verdict.held_by = scheduler_verdict.held_by().map(str::to_string);
+            }
 
+            if !intent_blockers.is_empty() {
+                conflicts.extend(intent_blockers.iter().map(|c| c.reason.clone()));
+                blockers.extend(intent_blockers.iter().cloned());
+                verdict
+                    .conflicts
+                    .extend(intent_blockers.iter().map(|c| c.reason.clone()));
+
+                if scheduler_verdict.status != VerdictStatus::Granted {
+                    blockers.extend(lease_blockers());
+                }
                 match scheduler_verdict.status {
                     VerdictStatus::Wait => {
                         if worst_status != KernelVerdictStatus::Die {
                             worst_status = KernelVerdictStatus::Wait;
+                            return_held_by = scheduler_verdict.held_by().map(str::to_string);
                             return_reason = scheduler_verdict.reason;
-                            return_held_by = scheduler_verdict.held_by;
                         }
                     }
                     VerdictStatus::Die => {
                         worst_status = KernelVerdictStatus::Die;
+                        return_held_by = scheduler_verdict.held_by().map(str::to_string);
                         return_reason = scheduler_verdict.reason;
-                        return_held_by = scheduler_verdict.held_by;
                         return_retry = scheduler_verdict.retry_after_ms;
                     }
                     VerdictStatus::Granted => {}
                 }
-            } else {
-                // No explicit intent conflicts, check against active leases directly
-                let lease_verdict = WaitDieScheduler::decide(
-                    &manifest.agent_id,
-                    intent.predicate,
-                    &intent.object,
-                    &state.active_leases,
-                    &state.priorities,
-                );
-
-                if lease_verdict.status != VerdictStatus::Granted {
-                    conflicts.push(format!("Conflict with active lease on {:?}", intent.object));
-                    match lease_verdict.status {
-                        VerdictStatus::Wait => {
-                            if worst_status != KernelVerdictStatus::Die {
-                                worst_status = KernelVerdictStatus::Wait;
-                                return_reason = lease_verdict.reason;
-                                return_held_by = lease_verdict.held_by;
-                            }
-                        }
-                        VerdictStatus::Die => {
-                            worst_status = KernelVerdictStatus::Die;
-                            return_reason = lease_verdict.reason;
-                            return_held_by = lease_verdict.held_by;
-                            return_retry = lease_verdict.retry_after_ms;
-                        }
-                        _ => {}
+            } else if scheduler_verdict.status != VerdictStatus::Granted {
+                // No explicit intent conflicts, but an active lease is in the way
+                let conflict = format!("Conflict with active lease on {:?}", intent.object);
+                verdict.conflicts.push(conflict.clone());
+                conflicts.push(conflict);
+                blockers.extend(lease_blockers());
+                match scheduler_verdict.status {
+                    VerdictStatus::Wait if worst_status != KernelVerdictStatus::Die => {
+                        worst_status = KernelVerdictStatus::Wait;
+                        return_held_by = scheduler_verdict.held_by().map(str::to_string);
+                        return_reason = scheduler_verdict.reason;
+                    }
+                    VerdictStatus::Die => {
+                        worst_status = KernelVerdictStatus::Die;
+                        return_held_by = scheduler_verdict.held_by().map(str::to_string);
+                        return_reason = scheduler_verdict.reason;
+                        return_retry = scheduler_verdict.retry_after_ms;
+                    }
+                    _ => {}
+                }
+            }
+            if verdict.status != KernelVerdictStatus::Granted {
+                let refused_by: Vec<ConflictDetail> = intent_blockers
+                    .iter()
+                    .cloned()
+                    .chain(lease_blockers())
+                    .collect();
+                for suggestion in remediation::suggest(intent, &refused_by, &state.active_leases) {
+                    if !suggestions.contains(&suggestion) {
+                        suggestions.push(suggestion);
+                    }
+                }
+            }
+            per_intent.push(verdict);
+        }
+
+        if let Some(cycle) = cycle {
+            let path = DependencyGraph::describe(&cycle);
+            let conflict = format!("Dependency cycle: {}", path);
+            // The intents adding an edge of the cycle are the ones refused
+            for (intent, verdict) in manifest.intents.iter().zip(&mut per_intent) {
+                let closes = DependencyGraph::edges_between(engine, intent, &state.active_intents)
+                    .iter()
+                    .any(|edge| cycle.contains(edge));
+                if closes {
+                    verdict.status = KernelVerdictStatus::Rejected;
+                    verdict.conflicts = vec![conflict.clone()];
+                    verdict.held_by = cycle.get(1).map(|edge| edge.dependent.clone());
+                }
+            }
+            return KernelVerdict {
+                agent_id: manifest.agent_id.clone(),
+                session_id: manifest.session_id.clone(),
+                status: KernelVerdictStatus::Rejected,
+                reason: Some(format!(
+                    "Manifest would create a dependency cycle: {}",
+                    path
+                )),
+                code: Some(ReasonCode::DependencyCycle),
+                held_by: cycle.get(1).map(|edge| edge.dependent.clone()),
+                holders: Vec::new(),
+                conflicts: vec![conflict],
+                blockers: Vec::new(),
+                retry_after_ms: None,
+                implied,
+                warnings: Vec::new(),
+                fallbacks: Vec::new(),
+                suggestions: Vec::new(),
+                per_intent,
+            };
+        }
+
+        if worst_status == KernelVerdictStatus::Wait {
+            let waits: Vec<WaitForEdge> = blockers
+                .iter()
+                .map(|b| WaitForEdge::new(&manifest.agent_id, &b.holder, b.resource.clone()))
+                .collect();
+            if let Some(cycle) = state.waits_for.find_cycle(&waits) {
+                let path = WaitForGraph::describe(&cycle);
+                conflicts.push(format!("Deadlock: {}", path));
+                // Every wait of the manifest goes into the cycle
+                for verdict in &mut per_intent {
+                    if verdict.status == KernelVerdictStatus::Wait {
+                        verdict.status = KernelVerdictStatus::Die;
+                        verdict.conflicts.push(format!("Deadlock: {}", path));
                     }
                 }
+                return KernelVerdict {
+                    agent_id: manifest.agent_id.clone(),
+                    session_id: manifest.session_id.clone(),
+                    status: KernelVerdictStatus::Die,
+                    reason: Some(format!("Waiting would close a wait-for cycle: {}", path)),
+                    code: Some(ReasonCode::Deadlock),
+                    held_by: cycle.first().map(|edge| edge.holder.clone()),
+                    holders,
+                    conflicts,
+                    blockers,
+                    retry_after_ms: None,
+                    implied,
+                    warnings,
+                    fallbacks: Vec::new(),
+                    // Waiting would deadlock
+                    suggestions: suggestions
+                        .into_iter()
+                        .filter(|s| matches!(s, Suggestion::Narrow { .. }))
+                        .collect(),
+                    per_intent,
+                };
             }
         }
 
         KernelVerdict {
             agent_id: manifest.agent_id.clone(),
             session_id: manifest.session_id.clone(),
+            code: match worst_status {
+                KernelVerdictStatus::Granted => None,
+                KernelVerdictStatus::Wait => Some(ReasonCode::WaitSenior),
+                KernelVerdictStatus::Die => Some(ReasonCode::DieJunior),
+                KernelVerdictStatus::Rejected => Some(ReasonCode::DependencyCycle),
+            },
             status: worst_status,
             reason: return_reason,
             held_by: return_held_by,
+            holders,
             conflicts,
+            blockers,
             retry_after_ms: return_retry,
+            implied,
+            warnings,
+            fallbacks: Vec::new(),
+            suggestions,
+            per_intent,
+        }
+    }
+
+    /// Evaluate manifests declared together, all or nothing. Each is judged
+    /// against `state` plus the intents of the manifests before it that
+    /// were granted, so manifests of one agent in different sessions can
+    /// conflict with each other; then, unless all were granted, none are
+    /// (see `AtomicVerdict::new`).
+    pub fn execute_atomic_with(
+        state: &StateSnapshot,
+        manifests: &[IntentManifest],
+        engine: &ConflictEngine,
+        scheduler: &dyn Scheduler,
+    ) -> AtomicVerdict {
+        let mut state = state.clone();
+        let mut verdicts = Vec::with_capacity(manifests.len());
+        for manifest in manifests {
+            let verdict = Self::execute_with(&state, manifest, engine, scheduler);
+            if verdict.status == KernelVerdictStatus::Granted {
+                for intent in &manifest.intents {
+                    for edge in
+                        DependencyGraph::edges_between(engine, intent, &state.active_intents)
+                    {
+                        state.dependencies.insert(edge);
+                    }
+                    state.active_intents.push(intent.clone());
+                }
+            }
+            verdicts.push(verdict);
+        }
+        AtomicVerdict::new(verdicts)
+    }
+
+    /// Judge the manifest as a whole and each of its intents on its own,
+    /// and group the conflicts by resource and by blocking agent (see
+    /// `ConflictSummary`)
+    pub fn summarize_with(
+        state: &StateSnapshot,
+        manifest: &IntentManifest,
+        engine: &ConflictEngine,
+        scheduler: &dyn Scheduler,
+    ) -> ConflictSummary {
+        let verdict = Self::execute_with(state, manifest, engine, scheduler);
+        // An intent can only be blocked by what blocks the whole manifest
+        let intent_verdicts: Vec<KernelVerdict> = if verdict.blockers.is_empty() {
+            Vec::new()
+        } else {
+            manifest
+                .intents
+                .iter()
+                .map(|intent| {
+                    let single = IntentManifest {
+                        session_id: manifest.session_id.clone(),
+                        agent_id: manifest.agent_id.clone(),
+                        intents: vec![intent.clone()],
+                    };
+                    Self::execute_with(state, &single, engine, scheduler)
+                })
+                .collect()
+        };
+        ConflictSummary::new(manifest, &verdict, &intent_verdicts)
+    }
+
+    /// A cycle of agents each waiting on the next in the state's wait-for
+    /// graph, if any. `execute_with` refuses the manifests that would close
+    /// one, but queued lease requests can still form one among themselves.
+    pub fn find_deadlock(state: &StateSnapshot) -> Option<Vec<WaitForEdge>> {
+        state.waits_for.any_cycle()
+    }
+
+    /// The dependency cycle the manifest's `DEPENDS_ON` and `PROVIDES`
+    /// intents would close, if any
+    fn dependency_cycle(
+        state: &StateSnapshot,
+        manifest: &IntentManifest,
+        engine: &ConflictEngine,
+    ) -> Option<Vec<DependencyEdge>> {
+        let added: Vec<_> = manifest
+            .intents
+            .iter()
+            .filter(|i| matches!(i.predicate, Predicate::DependsOn | Predicate::Provides))
+            .flat_map(|i| DependencyGraph::edges_between(engine, i, &state.active_intents))
+            .collect();
+        if added.is_empty() {
+            return None;
+        }
+        state.dependencies.find_cycle(&added)
+    }
+
+    /// Project the state at `at_time` using the built-in matrix and the
+    /// default Wait-Die scheduler.
+    pub fn project(state: &StateSnapshot, at_time: u64) -> StateProjection {
+        Self::project_with(
+            state,
+            at_time,
+            &ConflictEngine::default(),
+            &WaitDieScheduler,
+        )
+    }
+
+    /// Project the state at `at_time`: expire every lease whose TTL has
+    /// elapsed, then activate pending reservations in the order they were
+    /// made. Each reservation granted counts as held for the ones after it.
+    pub fn project_with(
+        state: &StateSnapshot,
+        at_time: u64,
+        engine: &ConflictEngine,
+        scheduler: &dyn Scheduler,
+    ) -> StateProjection {
+        // Stores expire a lease once `now` passes `expires_at`
+        let (expired, mut active): (Vec<Lease>, Vec<Lease>) = state
+            .active_leases
+            .iter()
+            .cloned()
+            .partition(|lease| lease.expires_at < at_time);
+
+        let (lapsed, mut pending): (Vec<Lease>, Vec<Lease>) = state
+            .pending_leases
+            .iter()
+            .cloned()
+            .partition(|lease| lease.expires_at < at_time);
+        pending.sort_by_key(|lease| lease.acquired_at);
+
+        let mut grantable = Vec::new();
+        let mut blocked = Vec::new();
+        for reservation in pending {
+            let (earlier, latecomers) = partition_for_activation(engine, &reservation, &active);
+            let verdict = scheduler.decide(
+                engine,
+                &reservation.agent_id,
+                &reservation.session_id,
+                reservation.predicate,
+                &reservation.resource,
+                &earlier,
+                &state.priorities,
+            );
+
+            if verdict.status != VerdictStatus::Granted {
+                blocked.push(reservation);
+                continue;
+            }
+
+            active.retain(|lease| {
+                !latecomers.contains(&lease.id) && !verdict.preempted.contains(&lease.id)
+            });
+            active.push(Lease {
+                state: LeaseState::Active,
+                last_heartbeat: at_time,
+                expires_at: at_time + reservation.ttl,
+                ..reservation.clone()
+            });
+            grantable.push(reservation);
+        }
+
+        StateProjection {
+            at: at_time,
+            expired,
+            active,
+            grantable,
+            blocked,
+            lapsed,
         }
     }
 }
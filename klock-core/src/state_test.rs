@@ -1,8 +1,21 @@
 #[cfg(test)]
 mod tests {
-    use crate::state::{IntentManifest, KernelVerdictStatus, KlockKernel, StateSnapshot};
-    use crate::types::{Confidence, Lease, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use crate::capacity::CapacityPolicy;
+    use crate::client::KlockClient;
+    use crate::codes::ReasonCode;
+    use crate::conflict::ConflictEngine;
+    use crate::deadlock::WaitForGraph;
+    use crate::dependency::DependencyGraph;
+    use crate::registry::Priority;
+    use crate::scheduler::{Scheduler, SchedulerVerdict, WaitDieScheduler};
+    use crate::state::{
+        AtomicVerdict, IntentManifest, KernelVerdictStatus, KlockKernel, StateSnapshot,
+    };
+    use crate::types::{
+        Confidence, Lease, LeaseResult, Predicate, ResourceRef, ResourceType, SPOTriple,
+    };
     use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     fn create_triple(agent_id: &str, predicate: Predicate, res_path: &str) -> SPOTriple {
         SPOTriple {
@@ -13,6 +26,9 @@ mod tests {
             timestamp: 1000,
             confidence: Confidence::High,
             session_id: "s1".to_string(),
+            renamed_to: None,
+            expires_at: None,
+            long_lived: false,
         }
     }
 
@@ -32,8 +48,11 @@ mod tests {
     fn test_kernel_execute_granted() {
         let state = StateSnapshot {
             active_leases: vec![],
+            pending_leases: vec![],
             active_intents: vec![],
             priorities: HashMap::new(),
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
         };
 
         let manifest = IntentManifest {
@@ -45,13 +64,14 @@ mod tests {
         let verdict = KlockKernel::execute(&state, &manifest);
         assert_eq!(verdict.status, KernelVerdictStatus::Granted);
         assert!(verdict.conflicts.is_empty());
+        assert_eq!(verdict.code, None);
     }
 
     #[test]
     fn test_kernel_execute_die() {
         let mut priorities = HashMap::new();
-        priorities.insert("agent_older".to_string(), 100);
-        priorities.insert("agent_younger".to_string(), 200);
+        priorities.insert("agent_older".to_string(), 100.into());
+        priorities.insert("agent_younger".to_string(), 200.into());
 
         let state = StateSnapshot {
             active_leases: vec![create_lease(
@@ -59,8 +79,11 @@ mod tests {
                 Predicate::Mutates,
                 "/src/app.ts",
             )],
+            pending_leases: vec![],
             active_intents: vec![],
             priorities,
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
         };
 
         let manifest = IntentManifest {
@@ -75,6 +98,7 @@ mod tests {
 
         let verdict = KlockKernel::execute(&state, &manifest);
         assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(verdict.code, Some(ReasonCode::DieJunior));
         assert!(!verdict.conflicts.is_empty());
         assert!(verdict.retry_after_ms.is_some());
     }
@@ -82,8 +106,8 @@ mod tests {
     #[test]
     fn test_kernel_execute_wait() {
         let mut priorities = HashMap::new();
-        priorities.insert("agent_older".to_string(), 100);
-        priorities.insert("agent_younger".to_string(), 200);
+        priorities.insert("agent_older".to_string(), 100.into());
+        priorities.insert("agent_younger".to_string(), 200.into());
 
         let state = StateSnapshot {
             active_leases: vec![create_lease(
@@ -91,8 +115,11 @@ mod tests {
                 Predicate::Mutates,
                 "/src/app.ts",
             )],
+            pending_leases: vec![],
             active_intents: vec![],
             priorities,
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
         };
 
         let manifest = IntentManifest {
@@ -107,6 +134,479 @@ mod tests {
 
         let verdict = KlockKernel::execute(&state, &manifest);
         assert_eq!(verdict.status, KernelVerdictStatus::Wait);
+        assert_eq!(verdict.code, Some(ReasonCode::WaitSenior));
         assert_eq!(verdict.held_by, Some("agent_younger".to_string()));
     }
+
+    fn create_reservation(agent_id: &str, res_path: &str, now: u64, activate_by: u64) -> Lease {
+        Lease::new(
+            format!("r_{}", agent_id),
+            agent_id.to_string(),
+            format!("s_{}", agent_id),
+            ResourceRef::new(ResourceType::File, res_path),
+            Predicate::Mutates,
+            5000,
+            now,
+        )
+        .into_pending(activate_by)
+    }
+
+    #[test]
+    fn test_kernel_project_expiry_frees_reservations() {
+        let mut priorities = HashMap::new();
+        priorities.insert("agent_holder".to_string(), 100.into());
+        priorities.insert("agent_first".to_string(), 200.into());
+        priorities.insert("agent_second".to_string(), 300.into());
+        priorities.insert("agent_late".to_string(), 400.into());
+
+        // The holder's lease runs until 6000
+        let state = StateSnapshot {
+            active_leases: vec![create_lease(
+                "agent_holder",
+                Predicate::Mutates,
+                "/src/app.ts",
+            )],
+            pending_leases: vec![
+                create_reservation("agent_second", "/src/app.ts", 1200, 20_000),
+                create_reservation("agent_first", "/src/app.ts", 1100, 20_000),
+                create_reservation("agent_late", "/src/lib.ts", 1300, 4000),
+            ],
+            active_intents: vec![],
+            priorities,
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
+        };
+
+        let ids = |leases: &[Lease]| leases.iter().map(|l| l.id.clone()).collect::<Vec<_>>();
+
+        // Before expiry the holder still blocks both reservations on its file
+        let projection = KlockKernel::project(&state, 3000);
+        assert!(projection.expired.is_empty());
+        assert_eq!(projection.blocked.len(), 2);
+        assert_eq!(ids(&projection.grantable), vec!["r_agent_late"]);
+        assert_eq!(
+            ids(&projection.active),
+            vec!["l_agent_holder", "r_agent_late"]
+        );
+
+        // After expiry the earliest reservation wins and blocks the next one
+        let projection = KlockKernel::project(&state, 7000);
+        assert_eq!(projection.at, 7000);
+        assert_eq!(ids(&projection.expired), vec!["l_agent_holder"]);
+        assert_eq!(ids(&projection.grantable), vec!["r_agent_first"]);
+        assert_eq!(ids(&projection.blocked), vec!["r_agent_second"]);
+        assert_eq!(ids(&projection.lapsed), vec!["r_agent_late"]);
+        assert_eq!(ids(&projection.active), vec!["r_agent_first"]);
+        assert_eq!(projection.active[0].expires_at, 12_000);
+    }
+
+    #[test]
+    fn test_kernel_verdict_lists_all_blockers() {
+        let mut priorities = HashMap::new();
+        priorities.insert("agent_a".to_string(), 100.into());
+        priorities.insert("agent_b".to_string(), 200.into());
+        priorities.insert("agent_c".to_string(), 300.into());
+
+        let state = StateSnapshot {
+            active_leases: vec![create_lease("agent_a", Predicate::Mutates, "/src/app.ts")],
+            pending_leases: vec![],
+            active_intents: vec![
+                create_triple("agent_a", Predicate::Mutates, "/src/app.ts"),
+                create_triple("agent_b", Predicate::Consumes, "/src/app.ts"),
+            ],
+            priorities,
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
+        };
+
+        let manifest = IntentManifest {
+            session_id: "s2".to_string(),
+            agent_id: "agent_c".to_string(),
+            intents: vec![create_triple("agent_c", Predicate::Mutates, "/src/app.ts")],
+        };
+
+        let verdict = KlockKernel::execute(&state, &manifest);
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(verdict.conflicts.len(), 2);
+        let blockers: Vec<(&str, Option<&str>)> = verdict
+            .blockers
+            .iter()
+            .map(|b| (b.holder.as_str(), b.lease_id.as_deref()))
+            .collect();
+        assert_eq!(
+            blockers,
+            vec![
+                ("agent_a", None),
+                ("agent_b", None),
+                ("agent_a", Some("l_agent_a")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_kernel_verdict_judges_each_intent() {
+        let mut priorities = HashMap::new();
+        priorities.insert("agent_a".to_string(), 100.into());
+        priorities.insert("agent_b".to_string(), 200.into());
+        priorities.insert("agent_c".to_string(), 300.into());
+
+        let state = StateSnapshot {
+            active_leases: vec![
+                create_lease("agent_a", Predicate::Mutates, "/src/a.ts"),
+                create_lease("agent_c", Predicate::Mutates, "/src/c.ts"),
+            ],
+            pending_leases: vec![],
+            active_intents: vec![create_triple("agent_a", Predicate::Consumes, "/src/d.ts")],
+            priorities,
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
+        };
+
+        let intent = |id: &str, predicate: Predicate, path: &str| SPOTriple {
+            id: id.to_string(),
+            ..create_triple("agent_b", predicate, path)
+        };
+        let manifest = IntentManifest {
+            session_id: "s2".to_string(),
+            agent_id: "agent_b".to_string(),
+            intents: vec![
+                intent("i1", Predicate::Mutates, "/src/a.ts"),
+                intent("i2", Predicate::Consumes, "/src/b.ts"),
+                intent("i3", Predicate::Mutates, "/src/c.ts"),
+                intent("i4", Predicate::Mutates, "/src/d.ts"),
+            ],
+        };
+
+        let verdict = KlockKernel::execute(&state, &manifest);
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        let per_intent: Vec<_> = verdict
+            .per_intent
+            .iter()
+            .map(|v| (v.intent_id.as_str(), v.status.clone(), v.held_by.as_deref()))
+            .collect();
+        assert_eq!(
+            per_intent,
+            [
+                ("i1", KernelVerdictStatus::Die, Some("agent_a")),
+                ("i2", KernelVerdictStatus::Granted, None),
+                ("i3", KernelVerdictStatus::Wait, Some("agent_c")),
+                // Conflicting with an intent alone is reported, not refused
+                ("i4", KernelVerdictStatus::Granted, None),
+            ]
+        );
+        assert!(verdict.per_intent[1].conflicts.is_empty());
+        assert_eq!(verdict.per_intent[3].conflicts.len(), 1);
+        let conflicts: Vec<String> = verdict
+            .per_intent
+            .iter()
+            .flat_map(|v| v.conflicts.clone())
+            .collect();
+        assert_eq!(conflicts, verdict.conflicts);
+    }
+
+    #[test]
+    fn test_tentative_intents_only_warn() {
+        let mut priorities = HashMap::new();
+        priorities.insert("agent_a".to_string(), 100.into());
+        priorities.insert("agent_b".to_string(), 200.into());
+        priorities.insert("agent_c".to_string(), 300.into());
+
+        let mut guess = create_triple("agent_b", Predicate::Consumes, "/src/lib.ts");
+        guess.confidence = Confidence::Low;
+        let state = StateSnapshot {
+            active_leases: vec![create_lease("agent_a", Predicate::Mutates, "/src/app.ts")],
+            pending_leases: vec![],
+            active_intents: vec![guess],
+            priorities,
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
+        };
+        let engine = ConflictEngine::default().with_tentative_confidence(Some(Confidence::Low));
+
+        // A low-confidence intent colliding with a senior lease is granted
+        let mut manifest = IntentManifest {
+            session_id: "s2".to_string(),
+            agent_id: "agent_c".to_string(),
+            intents: vec![create_triple("agent_c", Predicate::Mutates, "/src/app.ts")],
+        };
+        manifest.intents[0].confidence = Confidence::Low;
+        assert_eq!(
+            KlockKernel::execute(&state, &manifest).status,
+            KernelVerdictStatus::Die
+        );
+        let verdict = KlockKernel::execute_with(&state, &manifest, &engine, &WaitDieScheduler);
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        assert!(verdict.blockers.is_empty() && verdict.conflicts.is_empty());
+        assert_eq!(verdict.warnings.len(), 1);
+        assert_eq!(verdict.warnings[0].lease_id.as_deref(), Some("l_agent_a"));
+
+        // A firm intent colliding with a tentative one is warned, not blocked
+        manifest.intents = vec![create_triple("agent_c", Predicate::Mutates, "/src/lib.ts")];
+        let verdict = KlockKernel::execute_with(&state, &manifest, &engine, &WaitDieScheduler);
+        assert!(verdict.blockers.is_empty() && verdict.conflicts.is_empty());
+        assert_eq!(verdict.warnings[0].holder, "agent_b");
+
+        // Medium confidence is above the threshold
+        manifest.intents = vec![create_triple("agent_c", Predicate::Mutates, "/src/app.ts")];
+        manifest.intents[0].confidence = Confidence::Medium;
+        let verdict = KlockKernel::execute_with(&state, &manifest, &engine, &WaitDieScheduler);
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert!(verdict.warnings.is_empty());
+    }
+
+    /// Wait-Die, counting how often the kernel asks it to decide
+    #[derive(Default)]
+    struct CountingScheduler {
+        decisions: AtomicUsize,
+    }
+
+    impl Scheduler for CountingScheduler {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn resolve(
+            &self,
+            requesting_agent_id: &str,
+            conflicting_holders: &[&Lease],
+            priorities: &HashMap<String, Priority>,
+        ) -> SchedulerVerdict {
+            WaitDieScheduler.resolve(requesting_agent_id, conflicting_holders, priorities)
+        }
+
+        fn decide(
+            &self,
+            engine: &ConflictEngine,
+            requesting_agent_id: &str,
+            requesting_session_id: &str,
+            requesting_predicate: Predicate,
+            resource: &ResourceRef,
+            active_leases: &[Lease],
+            priorities: &HashMap<String, Priority>,
+        ) -> SchedulerVerdict {
+            self.decisions.fetch_add(1, Ordering::Relaxed);
+            Scheduler::decide(
+                &WaitDieScheduler,
+                engine,
+                requesting_agent_id,
+                requesting_session_id,
+                requesting_predicate,
+                resource,
+                active_leases,
+                priorities,
+            )
+        }
+    }
+
+    #[test]
+    fn test_kernel_decides_each_operation_once() {
+        let mut priorities = HashMap::new();
+        priorities.insert("agent_older".to_string(), 100.into());
+        priorities.insert("agent_younger".to_string(), 200.into());
+        let state = StateSnapshot {
+            active_leases: vec![create_lease(
+                "agent_older",
+                Predicate::Mutates,
+                "/src/app.ts",
+            )],
+            pending_leases: vec![],
+            active_intents: vec![],
+            priorities,
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
+        };
+
+        let manifest = IntentManifest {
+            session_id: "s2".to_string(),
+            agent_id: "agent_younger".to_string(),
+            intents: vec![
+                create_triple("agent_younger", Predicate::Mutates, "/src/app.ts"),
+                create_triple("agent_younger", Predicate::Mutates, "/src/lib.ts"),
+                create_triple("agent_younger", Predicate::Mutates, "/src/app.ts"),
+            ],
+        };
+
+        let scheduler = CountingScheduler::default();
+        let verdict =
+            KlockKernel::execute_with(&state, &manifest, &ConflictEngine::default(), &scheduler);
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(scheduler.decisions.load(Ordering::Relaxed), 2);
+        // Each occurrence is still reported
+        assert_eq!(verdict.blockers.len(), 2);
+    }
+
+    fn session_manifest(session_id: &str, path: &str) -> IntentManifest {
+        let mut triple = create_triple("agent_younger", Predicate::Mutates, path);
+        triple.session_id = session_id.to_string();
+        IntentManifest {
+            session_id: session_id.to_string(),
+            agent_id: "agent_younger".to_string(),
+            intents: vec![triple],
+        }
+    }
+
+    #[test]
+    fn test_atomic_refuses_siblings_of_a_refused_manifest() {
+        let state = StateSnapshot {
+            active_leases: vec![create_lease("agent_older", Predicate::Mutates, "/b.ts")],
+            pending_leases: vec![],
+            active_intents: vec![],
+            priorities: HashMap::from([
+                ("agent_older".to_string(), 100.into()),
+                ("agent_younger".to_string(), 200.into()),
+            ]),
+            dependencies: DependencyGraph::new(),
+            waits_for: WaitForGraph::new(),
+        };
+        let manifests = [
+            session_manifest("repo-a", "/a.ts"),
+            session_manifest("repo-b", "/b.ts"),
+            session_manifest("repo-c", "/c.ts"),
+        ];
+
+        let verdict = KlockKernel::execute_atomic_with(
+            &state,
+            &manifests,
+            &ConflictEngine::default(),
+            &WaitDieScheduler,
+        );
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        let codes: Vec<_> = verdict.verdicts.iter().map(|v| v.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                Some(ReasonCode::SiblingRefused),
+                Some(ReasonCode::DieJunior),
+                Some(ReasonCode::SiblingRefused),
+            ]
+        );
+        assert!(
+            verdict
+                .verdicts
+                .iter()
+                .all(|v| v.status == KernelVerdictStatus::Die)
+        );
+        assert!(
+            verdict.verdicts[0]
+                .reason
+                .as_ref()
+                .unwrap()
+                .contains("repo-b")
+        );
+        assert_eq!(
+            verdict.verdicts[0].retry_after_ms,
+            verdict.verdicts[1].retry_after_ms
+        );
+
+        // Nothing refused, nothing changed
+        let granted = AtomicVerdict::new(vec![
+            KlockKernel::execute(&state, &manifests[0]),
+            KlockKernel::execute(&state, &manifests[2]),
+        ]);
+        assert_eq!(granted.status, KernelVerdictStatus::Granted);
+        assert!(granted.verdicts.iter().all(|v| v.code.is_none()));
+    }
+
+    #[test]
+    fn test_declare_intents_atomic_registers_all_or_nothing() {
+        let mut client = KlockClient::new();
+        client.register_agent("agent_older", 100);
+        client.register_agent("agent_younger", 200);
+        let manifests = [
+            session_manifest("repo-a", "/a.ts"),
+            session_manifest("repo-b", "/b.ts"),
+        ];
+
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("agent_older", "s1", "FILE", "/b.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        let verdict = client.declare_intents_atomic(&manifests).unwrap();
+        assert_eq!(verdict.status, KernelVerdictStatus::Die);
+        assert_eq!(client.memory_report().intents.count, 0);
+
+        assert!(client.release_lease(&lease.id));
+        let verdict = client.declare_intents_atomic(&manifests).unwrap();
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        let sessions: Vec<&str> = verdict
+            .verdicts
+            .iter()
+            .map(|v| v.session_id.as_str())
+            .collect();
+        assert_eq!(sessions, vec!["repo-a", "repo-b"]);
+        assert_eq!(client.memory_report().intents.count, 2);
+
+        assert!(client.declare_intents_atomic(&[]).is_err());
+        let mut other = session_manifest("repo-c", "/c.ts");
+        other.agent_id = "agent_older".to_string();
+        assert!(
+            client
+                .declare_intents_atomic(&[manifests[0].clone(), other])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_acquire_manifest_leases_all_or_nothing() {
+        let mut client = KlockClient::new();
+        client.register_agent("agent_older", 100);
+        client.register_agent("agent_younger", 200);
+        let intents = [
+            (Predicate::Mutates, "/a.ts"),
+            (Predicate::Consumes, "/b.ts"),
+            (Predicate::Mutates, "/c.ts"),
+        ];
+        let manifest = IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: "agent_younger".to_string(),
+            intents: intents
+                .iter()
+                .map(|&(predicate, path)| SPOTriple {
+                    id: format!("t{}", path),
+                    ..create_triple("agent_younger", predicate, path)
+                })
+                .collect(),
+        };
+
+        // Refused by the kernel: nothing is leased
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("agent_older", "s9", "FILE", "/c.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        let refused = client.acquire_manifest(&manifest, 60_000);
+        assert_eq!(refused.verdict.status, KernelVerdictStatus::Die);
+        assert!(!refused.acquired() && refused.leases.is_empty());
+        assert!(refused.failure.is_none());
+        assert!(client.release_lease(&lease.id));
+
+        // Granted, but the last acquire is shed: the others are rolled back
+        client.set_capacity_policy(CapacityPolicy {
+            max_leases: Some(2),
+            shed_from_percent: 100,
+            ..CapacityPolicy::default()
+        });
+        let rolled_back = client.acquire_manifest(&manifest, 60_000);
+        assert_eq!(rolled_back.verdict.status, KernelVerdictStatus::Granted);
+        assert!(!rolled_back.acquired() && rolled_back.leases.is_empty());
+        let failure = rolled_back.failure.unwrap();
+        assert_eq!(failure.intent_id, "t/c.ts");
+        assert_eq!(failure.code, ReasonCode::CapacityExceeded);
+        assert!(client.get_active_leases().is_empty());
+        assert_eq!(client.memory_report().intents.count, 0);
+
+        client.set_capacity_policy(CapacityPolicy::default());
+        let acquired = client.acquire_manifest(&manifest, 60_000);
+        assert!(acquired.acquired());
+        let leased: Vec<(Predicate, &str)> = acquired
+            .leases
+            .iter()
+            .map(|lease| (lease.predicate, lease.resource.path.as_str()))
+            .collect();
+        assert_eq!(leased, intents);
+        assert!(acquired.leases.iter().all(|lease| lease.session_id == "s1"));
+        assert_eq!(client.get_active_leases().len(), 3);
+        assert_eq!(client.memory_report().intents.count, 3);
+    }
 }
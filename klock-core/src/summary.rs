@@ -0,0 +1,146 @@
+//! Aggregated conflict reports for large manifests.
+//!
+//! A verdict lists every blocker of every intent in one flat list, which is
+//! unreadable for a manifest of hundreds of intents. `ConflictSummary`
+//! groups the blockers by the requested resource and by the blocking
+//! agent, counting conflicts and intents per group and keeping the most
+//! restrictive verdict any intent in the group gets on its own, so a
+//! pre-flight check (`klock check --summary`) shows where the trouble is.
+
+use crate::codes::ReasonCode;
+use crate::state::{IntentManifest, KernelVerdict, KernelVerdictStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The conflicts on one resource, or with one blocking agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictGroup {
+    /// The requested resource's key, or the blocking agent's ID
+    pub key: String,
+    /// Blockers in the group, counted once per intent they block
+    pub conflicts: usize,
+    /// Intents of the manifest with a blocker in the group
+    pub intents: usize,
+    /// The most restrictive verdict an intent in the group gets on its own
+    pub status: KernelVerdictStatus,
+    /// That verdict's code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ReasonCode>,
+}
+
+/// A manifest's conflicts, grouped. Groups are listed worst first: most
+/// restrictive verdict, then most conflicts, then by key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictSummary {
+    pub agent_id: String,
+    pub session_id: String,
+    /// The verdict on the manifest as a whole
+    pub status: KernelVerdictStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ReasonCode>,
+    pub intents: usize,
+    /// Intents with at least one blocker
+    pub conflicting_intents: usize,
+    /// Blockers, counted once per intent they block
+    pub conflicts: usize,
+    /// Conflicts involving tentative intents, which refuse nothing
+    pub warnings: usize,
+    pub by_resource: Vec<ConflictGroup>,
+    pub by_holder: Vec<ConflictGroup>,
+}
+
+impl ConflictSummary {
+    /// Summarize `manifest` from its verdict and the verdicts its intents
+    /// get on their own, in manifest order. Intents past the end of
+    /// `intent_verdicts` are taken to have no blockers.
+    pub fn new(
+        manifest: &IntentManifest,
+        verdict: &KernelVerdict,
+        intent_verdicts: &[KernelVerdict],
+    ) -> Self {
+        let mut by_resource: BTreeMap<String, ConflictGroup> = BTreeMap::new();
+        let mut by_holder: BTreeMap<String, ConflictGroup> = BTreeMap::new();
+        let mut conflicting_intents = 0;
+        let mut conflicts = 0;
+
+        for (intent, intent_verdict) in manifest.intents.iter().zip(intent_verdicts) {
+            if intent_verdict.blockers.is_empty() {
+                continue;
+            }
+            conflicting_intents += 1;
+            conflicts += intent_verdict.blockers.len();
+
+            let entry = group(&mut by_resource, intent.object.key());
+            entry.conflicts += intent_verdict.blockers.len();
+            entry.intents += 1;
+            entry.worsen(intent_verdict);
+
+            let holders: BTreeSet<&str> = intent_verdict
+                .blockers
+                .iter()
+                .map(|b| b.holder.as_str())
+                .collect();
+            for holder in holders {
+                let entry = group(&mut by_holder, holder.to_string());
+                entry.conflicts += intent_verdict
+                    .blockers
+                    .iter()
+                    .filter(|b| b.holder == holder)
+                    .count();
+                entry.intents += 1;
+                entry.worsen(intent_verdict);
+            }
+        }
+
+        Self {
+            agent_id: manifest.agent_id.clone(),
+            session_id: manifest.session_id.clone(),
+            status: verdict.status.clone(),
+            code: verdict.code,
+            intents: manifest.intents.len(),
+            conflicting_intents,
+            conflicts,
+            warnings: verdict.warnings.len(),
+            by_resource: worst_first(by_resource),
+            by_holder: worst_first(by_holder),
+        }
+    }
+
+    /// Keep only the `n` worst groups of each kind
+    pub fn truncate(&mut self, n: usize) {
+        self.by_resource.truncate(n);
+        self.by_holder.truncate(n);
+    }
+}
+
+impl ConflictGroup {
+    /// Take `verdict`'s status if it is more restrictive
+    fn worsen(&mut self, verdict: &KernelVerdict) {
+        if verdict.status.severity() > self.status.severity() {
+            self.status = verdict.status.clone();
+            self.code = verdict.code;
+        }
+    }
+}
+
+fn group(groups: &mut BTreeMap<String, ConflictGroup>, key: String) -> &mut ConflictGroup {
+    groups.entry(key.clone()).or_insert_with(|| ConflictGroup {
+        key,
+        conflicts: 0,
+        intents: 0,
+        status: KernelVerdictStatus::Granted,
+        code: None,
+    })
+}
+
+fn worst_first(groups: BTreeMap<String, ConflictGroup>) -> Vec<ConflictGroup> {
+    // Already in key order, which the stable sort keeps for ties
+    let mut groups: Vec<ConflictGroup> = groups.into_values().collect();
+    groups.sort_by(|a, b| {
+        b.status
+            .severity()
+            .cmp(&a.status.severity())
+            .then(b.conflicts.cmp(&a.conflicts))
+    });
+    groups
+}
@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::sync::Arc;
+
+    fn manifest(agent: &str, paths: &[&str]) -> IntentManifest {
+        IntentManifest {
+            session_id: "s1".to_string(),
+            agent_id: agent.to_string(),
+            intents: paths
+                .iter()
+                .map(|&path| SPOTriple {
+                    id: format!("{}_{}", agent, path),
+                    subject: agent.to_string(),
+                    predicate: Predicate::Mutates,
+                    object: ResourceRef::new(ResourceType::File, path),
+                    timestamp: 1000,
+                    confidence: Confidence::High,
+                    session_id: "s1".to_string(),
+                    renamed_to: None,
+                    expires_at: None,
+                    long_lived: false,
+                })
+                .collect(),
+        }
+    }
+
+    fn keys(groups: &[crate::summary::ConflictGroup]) -> Vec<(&str, KernelVerdictStatus)> {
+        groups
+            .iter()
+            .map(|g| (g.key.as_str(), g.status.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_summary_groups_worst_first() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("old", 100);
+        client.register_agent("me", 150);
+        client.register_agent("young", 200);
+        for (agent, path) in [("old", "/a.ts"), ("old", "/b.ts"), ("young", "/c.ts")] {
+            client.acquire_lease(agent, "s0", "FILE", path, "MUTATES", 60_000);
+            clock.advance(1);
+        }
+
+        let mut summary =
+            client.summarize_intent(&manifest("me", &["/c.ts", "/b.ts", "/a.ts", "/d.ts"]));
+        assert_eq!(summary.status, KernelVerdictStatus::Die);
+        assert_eq!(summary.intents, 4);
+        assert_eq!(summary.conflicting_intents, 3);
+        assert_eq!(summary.conflicts, 3);
+        assert_eq!(
+            keys(&summary.by_resource),
+            vec![
+                ("FILE:/a.ts", KernelVerdictStatus::Die),
+                ("FILE:/b.ts", KernelVerdictStatus::Die),
+                ("FILE:/c.ts", KernelVerdictStatus::Wait),
+            ]
+        );
+        assert_eq!(
+            keys(&summary.by_holder),
+            vec![
+                ("old", KernelVerdictStatus::Die),
+                ("young", KernelVerdictStatus::Wait)
+            ]
+        );
+        assert_eq!(summary.by_holder[0].intents, 2);
+        assert_eq!(summary.by_holder[0].code, summary.code);
+
+        // Summarizing registers nothing, so the same manifest gets the same
+        // report again
+        let again = client.summarize_intent(&manifest("me", &["/c.ts", "/b.ts", "/a.ts", "/d.ts"]));
+        assert_eq!(again, summary);
+
+        summary.truncate(1);
+        assert_eq!(summary.by_resource.len(), 1);
+        assert_eq!(summary.by_holder.len(), 1);
+        assert_eq!(summary.conflicts, 3);
+    }
+
+    #[test]
+    fn test_summary_without_conflicts() {
+        let mut client = KlockClient::new();
+        client.register_agent("me", 150);
+        let summary = client.summarize_intent(&manifest("me", &["/a.ts", "/b.ts"]));
+        assert_eq!(summary.status, KernelVerdictStatus::Granted);
+        assert_eq!(summary.conflicting_intents, 0);
+        assert!(summary.by_resource.is_empty());
+        assert!(summary.by_holder.is_empty());
+    }
+}
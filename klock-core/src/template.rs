@@ -0,0 +1,246 @@
+//! Manifest templates.
+//!
+//! Teams run the same task shape across many modules: "refactor module X"
+//! always touches `X/src/index.ts` and `X/tests/`. A `ManifestTemplate`
+//! captures such a shape once, with `{{variable}}` placeholders in resource
+//! paths and rename targets, and is expanded into an `IntentManifest` for a
+//! given set of variables. Templates are either registered with the client
+//! (and looked up by name) or supplied with the request.
+
+use crate::state::IntentManifest;
+use crate::types::{Confidence, Predicate, ResourceRef, ResourceType, SPOTriple};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// One intent of a template, with placeholders in its paths
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateIntent {
+    pub predicate: Predicate,
+    pub resource_type: ResourceType,
+    /// Path with `{{variable}}` placeholders, e.g. `{{module}}/src/index.ts`
+    pub resource_path: String,
+    /// New path, for `Renames` intents; may hold placeholders too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_to: Option<String>,
+    /// High if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+}
+
+/// A named, reusable manifest shape
+///
+/// ```json
+/// {
+///   "name": "refactor",
+///   "intents": [
+///     { "predicate": "Mutates", "resource_type": "File", "resource_path": "{{module}}/src/index.ts" },
+///     { "predicate": "Mutates", "resource_type": "File", "resource_path": "{{module}}/tests/" }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestTemplate {
+    pub name: String,
+    pub intents: Vec<TemplateIntent>,
+}
+
+impl ManifestTemplate {
+    /// Check the template has a name, at least one intent, and well-formed
+    /// placeholders.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("Template name is required".to_string());
+        }
+        if self.intents.is_empty() {
+            return Err(format!("Template '{}' has no intents", self.name));
+        }
+        for text in self.texts() {
+            placeholders(text).map_err(|e| format!("Template '{}': {}", self.name, e))?;
+        }
+        Ok(())
+    }
+
+    /// The variables the template needs, sorted
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables: Vec<String> = self
+            .texts()
+            .flat_map(|text| placeholders(text).unwrap_or_default())
+            .map(str::to_string)
+            .collect();
+        variables.sort();
+        variables.dedup();
+        variables
+    }
+
+    /// The manifest `agent_id` declares by running this template with
+    /// `variables`, identifying its triples with `next_id`. Fails if a
+    /// variable the template uses is missing or empty.
+    pub fn expand(
+        &self,
+        agent_id: &str,
+        session_id: &str,
+        variables: &BTreeMap<String, String>,
+        now: u64,
+        mut next_id: impl FnMut() -> String,
+    ) -> Result<IntentManifest, String> {
+        self.validate()?;
+        let missing: Vec<String> = self
+            .variables()
+            .into_iter()
+            .filter(|name| variables.get(name).is_none_or(|value| value.is_empty()))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Template '{}' needs variables: {}",
+                self.name,
+                missing.join(", ")
+            ));
+        }
+
+        let intents = self
+            .intents
+            .iter()
+            .map(|intent| {
+                let object = ResourceRef::new(
+                    intent.resource_type.clone(),
+                    substitute(&intent.resource_path, variables)?,
+                );
+                Ok(SPOTriple {
+                    id: next_id(),
+                    subject: agent_id.to_string(),
+                    predicate: intent.predicate,
+                    object,
+                    timestamp: now,
+                    confidence: intent.confidence.unwrap_or(Confidence::High),
+                    session_id: session_id.to_string(),
+                    renamed_to: intent
+                        .renamed_to
+                        .as_deref()
+                        .map(|to| substitute(to, variables))
+                        .transpose()?,
+                    expires_at: None,
+                    long_lived: false,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(IntentManifest {
+            session_id: session_id.to_string(),
+            agent_id: agent_id.to_string(),
+            intents,
+        })
+    }
+
+    /// Every text that may hold placeholders
+    fn texts(&self) -> impl Iterator<Item = &str> {
+        self.intents.iter().flat_map(|intent| {
+            std::iter::once(intent.resource_path.as_str()).chain(intent.renamed_to.as_deref())
+        })
+    }
+
+    /// Approximate footprint of the template
+    pub fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.name.capacity()
+            + self
+                .intents
+                .iter()
+                .map(|intent| {
+                    std::mem::size_of::<TemplateIntent>()
+                        + intent.resource_path.capacity()
+                        + intent.renamed_to.as_ref().map_or(0, String::capacity)
+                })
+                .sum::<usize>()
+    }
+}
+
+/// The placeholder names in `text`, in order
+fn placeholders(text: &str) -> Result<Vec<&str>, String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| format!("unclosed placeholder in '{}'", text))?;
+        let name = after[..end].trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!(
+                "invalid placeholder '{{{{{}}}}}' in '{}': names are letters, digits and _",
+                &after[..end],
+                text
+            ));
+        }
+        names.push(name);
+        rest = &after[end + 2..];
+    }
+    Ok(names)
+}
+
+/// `text` with every placeholder replaced by its variable's value
+fn substitute(text: &str, variables: &BTreeMap<String, String>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| format!("unclosed placeholder in '{}'", text))?;
+        let name = after[..end].trim();
+        let value = variables
+            .get(name)
+            .ok_or_else(|| format!("missing variable '{}'", name))?;
+        expanded.push_str(value);
+        rest = &after[end + 2..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Templates registered by name
+#[derive(Debug, Clone, Default)]
+pub struct TemplateRegistry {
+    templates: BTreeMap<String, ManifestTemplate>,
+}
+
+impl TemplateRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a template, replacing any with the same name.
+    pub fn insert(&mut self, template: ManifestTemplate) -> Result<(), String> {
+        template.validate()?;
+        self.templates.insert(template.name.clone(), template);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ManifestTemplate> {
+        self.templates.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.templates.remove(name).is_some()
+    }
+
+    /// Every template, by name
+    pub fn list(&self) -> Vec<ManifestTemplate> {
+        self.templates.values().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// Approximate footprint of all templates
+    pub fn estimated_bytes(&self) -> usize {
+        self.templates
+            .iter()
+            .map(|(name, template)| name.capacity() + template.estimated_bytes())
+            .sum()
+    }
+}
@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::state::KernelVerdictStatus;
+    use crate::template::{ManifestTemplate, TemplateIntent};
+    use crate::types::{Predicate, ResourceRef, ResourceType};
+    use std::collections::BTreeMap;
+
+    fn refactor() -> ManifestTemplate {
+        serde_json::from_str(
+            r#"{
+                "name": "refactor",
+                "intents": [
+                    { "predicate": "Mutates", "resource_type": "File", "resource_path": "{{module}}/src/index.ts" },
+                    { "predicate": "Renames", "resource_type": "File", "resource_path": "{{module}}/old.ts",
+                      "renamed_to": "{{ module }}/{{name}}.ts" }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_substitutes_variables() {
+        let template = refactor();
+        assert_eq!(template.variables(), vec!["module", "name"]);
+
+        let mut ids = 0;
+        let manifest = template
+            .expand(
+                "agent",
+                "s1",
+                &vars(&[("module", "auth"), ("name", "new"), ("unused", "x")]),
+                1000,
+                || {
+                    ids += 1;
+                    format!("t{}", ids)
+                },
+            )
+            .unwrap();
+        assert_eq!(manifest.agent_id, "agent");
+        assert_eq!(manifest.intents.len(), 2);
+        assert_eq!(manifest.intents[0].id, "t1");
+        assert_eq!(
+            manifest.intents[0].object,
+            ResourceRef::new(ResourceType::File, "/auth/src/index.ts")
+        );
+        assert_eq!(manifest.intents[1].predicate, Predicate::Renames);
+        assert_eq!(
+            manifest.intents[1].renamed_to.as_deref(),
+            Some("auth/new.ts")
+        );
+
+        let err = template
+            .expand("agent", "s1", &vars(&[("module", "")]), 1000, String::new)
+            .unwrap_err();
+        assert_eq!(err, "Template 'refactor' needs variables: module, name");
+    }
+
+    #[test]
+    fn test_validate_rejects_malformed_templates() {
+        let intent = |path: &str| TemplateIntent {
+            predicate: Predicate::Mutates,
+            resource_type: ResourceType::File,
+            resource_path: path.to_string(),
+            renamed_to: None,
+            confidence: None,
+        };
+        let template = |intents| ManifestTemplate {
+            name: "t".to_string(),
+            intents,
+        };
+        assert!(
+            template(vec![intent("/{{module}}/a.ts")])
+                .validate()
+                .is_ok()
+        );
+        assert!(template(vec![]).validate().is_err());
+        assert!(template(vec![intent("/{{module/a.ts")]).validate().is_err());
+        assert!(template(vec![intent("/{{a-b}}/a.ts")]).validate().is_err());
+
+        let mut client = KlockClient::new();
+        assert!(client.register_template(template(vec![])).is_err());
+        assert!(client.templates().is_empty());
+    }
+
+    #[test]
+    fn test_client_declares_from_registered_template() {
+        let mut client = KlockClient::new();
+        client.register_agent("a", 100);
+        client.register_agent("b", 200);
+        client.register_template(refactor()).unwrap();
+        assert_eq!(client.memory_report().templates.count, 1);
+
+        let verdict = client
+            .declare_intent_from_template(
+                "a",
+                "s1",
+                "refactor",
+                &vars(&[("module", "auth"), ("name", "login")]),
+            )
+            .unwrap();
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+
+        // The same shape on the same module conflicts; another module does not
+        let verdict = client
+            .declare_intent_from_template(
+                "b",
+                "s2",
+                "refactor",
+                &vars(&[("module", "auth"), ("name", "login")]),
+            )
+            .unwrap();
+        assert!(!verdict.conflicts.is_empty());
+        let verdict = client
+            .declare_intent_with_template(
+                "b",
+                "s2",
+                &refactor(),
+                &vars(&[("module", "billing"), ("name", "invoice")]),
+            )
+            .unwrap();
+        assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        assert!(verdict.conflicts.is_empty());
+
+        assert!(
+            client
+                .declare_intent_from_template("a", "s1", "missing", &BTreeMap::new())
+                .is_err()
+        );
+        assert!(client.remove_template("refactor"));
+        assert!(client.template("refactor").is_none());
+    }
+}
@@ -0,0 +1,135 @@
+//! The versioned envelope every event Klock publishes travels in.
+//!
+//! Lease changes on the firehose, scheduler decisions in the audit log and
+//! lease changes replayed onto another backend share one schema, so
+//! consumers decode them all the same way: a `schema_version`, the event
+//! `type`, when it occurred and was recorded, the agent it concerns, and
+//! a `payload` whose shape the type decides.
+//!
+//! The schema evolves compatibly within a version:
+//! - fields are only ever added, optional or defaulted, and never renamed,
+//!   retyped or removed;
+//! - new event types may appear, and decode as `EventPayload::Unknown` for
+//!   consumers that predate them;
+//! - consumers ignore fields they do not know.
+//!
+//! A change that cannot follow these rules bumps `EVENT_SCHEMA_VERSION`,
+//! and consumers refuse envelopes newer than they understand (see
+//! `EventEnvelope::check_version`).
+
+use crate::audit::DecisionRecord;
+use crate::event::KlockEvent;
+use crate::types::Lease;
+use serde::{Deserialize, Serialize};
+
+/// The schema version of envelopes this build publishes
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// What happened, tagged by event type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum EventPayload {
+    /// A lease state change, with the lease as it left it; `None` for
+    /// compactions
+    LeaseChange {
+        change: KlockEvent,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lease: Option<Lease>,
+    },
+    /// A namespace was reaped, after the releases it caused
+    NamespaceReaped { namespace: String },
+    /// A scheduler decision (see `audit`)
+    SchedulerDecision(DecisionRecord),
+    /// An event type this build does not know, published by a newer one;
+    /// its payload is dropped
+    #[serde(untagged)]
+    Unknown {},
+}
+
+/// One published event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    pub schema_version: u32,
+    /// When the event happened, in ms since the epoch
+    pub occurred_at: u64,
+    /// When Klock recorded it, in ms since the epoch
+    pub recorded_at: u64,
+    /// The agent whose action or lease the event concerns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(flatten)]
+    pub event: EventPayload,
+}
+
+impl EventEnvelope {
+    /// Wrap `event`, recorded at `recorded_at`, in the current schema. The
+    /// actor and, where the event says, when it occurred are taken from it.
+    pub fn new(event: EventPayload, recorded_at: u64) -> Self {
+        let (actor, occurred_at) = match &event {
+            EventPayload::LeaseChange { change, lease } => {
+                let at = match change {
+                    KlockEvent::LeaseActivated { at, .. } | KlockEvent::LeaseRenewed { at, .. } => {
+                        Some(*at)
+                    }
+                    _ => None,
+                };
+                let holder = match change {
+                    KlockEvent::LeaseCreated { lease } => Some(lease),
+                    _ => lease.as_ref(),
+                };
+                (holder.map(|lease| lease.agent_id.clone()), at)
+            }
+            EventPayload::SchedulerDecision(record) => {
+                (Some(record.agent_id.clone()), Some(record.at))
+            }
+            EventPayload::NamespaceReaped { .. } | EventPayload::Unknown {} => (None, None),
+        };
+        Self {
+            schema_version: EVENT_SCHEMA_VERSION,
+            occurred_at: occurred_at.unwrap_or(recorded_at),
+            recorded_at,
+            actor,
+            event,
+        }
+    }
+
+    /// The lease change `change` made at `at`, leaving `lease` behind
+    pub fn lease_change(change: &KlockEvent, lease: Option<Lease>, at: u64) -> Self {
+        Self::new(
+            EventPayload::LeaseChange {
+                change: change.clone(),
+                lease,
+            },
+            at,
+        )
+    }
+
+    /// The event type, as serialized in `type`
+    pub fn event_type(&self) -> &'static str {
+        match self.event {
+            EventPayload::LeaseChange { .. } => "lease_change",
+            EventPayload::NamespaceReaped { .. } => "namespace_reaped",
+            EventPayload::SchedulerDecision(_) => "scheduler_decision",
+            EventPayload::Unknown {} => "unknown",
+        }
+    }
+
+    /// The lease a lease change left behind
+    pub fn lease(&self) -> Option<&Lease> {
+        match &self.event {
+            EventPayload::LeaseChange { lease, .. } => lease.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Refuse envelopes of a schema newer than this build understands
+    pub fn check_version(&self) -> Result<(), String> {
+        if self.schema_version > EVENT_SCHEMA_VERSION {
+            return Err(format!(
+                "Event schema version {} is newer than the supported {}",
+                self.schema_version, EVENT_SCHEMA_VERSION
+            ));
+        }
+        Ok(())
+    }
+}
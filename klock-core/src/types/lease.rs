@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
-use super::{Predicate, ResourceRef};
+use super::{Predicate, ResourceRef, SPOTriple};
+use crate::codes::ReasonCode;
 
 /// Lease states
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -13,6 +14,8 @@ pub enum LeaseState {
     Released,
     /// Lease was forcibly revoked (conflict resolution)
     Revoked,
+    /// Lease is reserved but not yet activated (warm spare); does not block others
+    Pending,
 }
 
 /// A time-bound lock on a resource
@@ -38,6 +41,24 @@ pub struct Lease {
     pub expires_at: u64,
     /// Last heartbeat timestamp
     pub last_heartbeat: u64,
+    /// Bumped by every change to the lease; revision-checked mutations
+    /// (see `LeaseStore::apply_at`) refuse a stale one
+    #[serde(default)]
+    pub revision: u64,
+    /// Heartbeats that renewed the lease
+    #[serde(default)]
+    pub renewals: u64,
+    /// Why the lease was revoked, once it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_reason: Option<String>,
+    /// When the holder needs its work done by (ms since epoch); breaks
+    /// priority ties against later requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
+    /// When the lease stopped being held (released, revoked or expired), in
+    /// stores that record it (see `LeaseStore::record_lease_end`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<u64>,
 }
 
 impl Lease {
@@ -61,10 +82,60 @@ impl Lease {
             ttl,
             expires_at: now + ttl,
             last_heartbeat: now,
+            revision: 0,
+            renewals: 0,
+            revoked_reason: None,
+            deadline: None,
+            ended_at: None,
         }
     }
+
+    /// Whether the lease has reached a final state and can never block again
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self.state,
+            LeaseState::Expired | LeaseState::Released | LeaseState::Revoked
+        )
+    }
+
+    /// Whether the lease was held at `at`: active from its acquisition
+    /// until it ended, or until it expires if no end was recorded
+    pub fn held_at(&self, at: u64) -> bool {
+        self.state != LeaseState::Pending
+            && self.acquired_at <= at
+            && at < self.ended_at.unwrap_or(self.expires_at)
+    }
+
+    /// Whether the lease was taken for `intent`: by the same agent and
+    /// session, for the same operation, on a resource containing its object
+    pub fn covers(&self, intent: &SPOTriple) -> bool {
+        self.agent_id == intent.subject
+            && self.session_id == intent.session_id
+            && self.predicate == intent.predicate
+            && self.resource.contains(&intent.object)
+    }
+
+    /// Approximate heap + inline footprint, used for memory reporting
+    pub fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.id.capacity()
+            + self.agent_id.capacity()
+            + self.session_id.capacity()
+            + self.resource.path.capacity()
+            + self.revoked_reason.as_ref().map_or(0, String::capacity)
+    }
+
+    /// Turn a freshly built lease into a warm-spare reservation that must be
+    /// activated before `activate_by`. While pending, `expires_at` holds the
+    /// activation deadline.
+    pub fn into_pending(mut self, activate_by: u64) -> Self {
+        self.state = LeaseState::Pending;
+        self.expires_at = activate_by;
+        self
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LeaseFailureReason {
     /// Another agent holds a conflicting lease
     Conflict,
@@ -76,9 +147,143 @@ pub enum LeaseFailureReason {
     ResourceLocked,
     /// The session has expired
     SessionExpired,
+    /// The pending reservation is unknown, already activated, or past its deadline
+    ReservationExpired,
+    /// The agent is quarantined for poor health
+    AgentQuarantined,
+    /// The agent retried the same resource before its minimum interval elapsed
+    TooManyRetries,
+    /// An operator froze the resource (see `quarantine`)
+    Quarantined,
+    /// A maintenance window reserves the resource for other agents (see
+    /// `maintenance`)
+    MaintenanceWindow,
+    /// The policy hook refused the request (see `policy_hook`)
+    PolicyDenied,
+    /// The agent has no registered priority and the client rejects such
+    /// agents (see `UnregisteredAgentPolicy`)
+    UnregisteredAgent,
+    /// The client is shedding load near its capacity (see `capacity`)
+    CapacityExceeded,
+    /// The operation needs another agent's approval; a pending lease
+    /// awaits it (see `cosign`)
+    CosignRequired,
+}
+
+impl LeaseFailureReason {
+    /// Stable wire representation used by the HTTP API and FFI layers
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeaseFailureReason::Conflict => "CONFLICT",
+            LeaseFailureReason::Wait => "WAIT",
+            LeaseFailureReason::Die => "DIE",
+            LeaseFailureReason::ResourceLocked => "RESOURCE_LOCKED",
+            LeaseFailureReason::SessionExpired => "SESSION_EXPIRED",
+            LeaseFailureReason::ReservationExpired => "RESERVATION_EXPIRED",
+            LeaseFailureReason::AgentQuarantined => "AGENT_QUARANTINED",
+            LeaseFailureReason::TooManyRetries => "TOO_MANY_RETRIES",
+            LeaseFailureReason::Quarantined => "QUARANTINED",
+            LeaseFailureReason::MaintenanceWindow => "MAINTENANCE_WINDOW",
+            LeaseFailureReason::PolicyDenied => "POLICY_DENIED",
+            LeaseFailureReason::UnregisteredAgent => "UNREGISTERED_AGENT",
+            LeaseFailureReason::CapacityExceeded => "CAPACITY_EXCEEDED",
+            LeaseFailureReason::CosignRequired => "COSIGN_REQUIRED",
+        }
+    }
+
+    /// The catalog code reported alongside `as_str`
+    pub fn code(&self) -> ReasonCode {
+        ReasonCode::from(*self)
+    }
+}
+
+/// Clock drift between a heartbeating client and the server from which
+/// `HeartbeatReport` warns: expiry times are server time, so a client this
+/// far off misjudges how long its lease has left
+pub const CLOCK_DRIFT_WARNING_MS: u64 = 1000;
+
+/// What a heartbeat found: the lease as the server sees it afterwards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatReport {
+    pub lease_id: String,
+    pub renewed: bool,
+    /// The lease's state, or `None` if the lease is unknown
+    pub state: Option<LeaseState>,
+    /// Time left until the lease expires unless renewed again; 0 unless
+    /// active
+    pub remaining_ttl: u64,
+    pub expires_at: Option<u64>,
+    /// Heartbeats that renewed the lease, this one included
+    pub renewals: u64,
+    pub revision: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_reason: Option<String>,
+    /// The client's clock minus the server's, when the client sent its time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_drift_ms: Option<i64>,
+    /// Set when the drift reaches `CLOCK_DRIFT_WARNING_MS`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drift_warning: Option<String>,
+}
+
+impl HeartbeatReport {
+    /// Report on `lease` (if it exists) at server time `now`, for a client
+    /// whose clock read `client_time`
+    pub fn new(
+        lease_id: &str,
+        lease: Option<&Lease>,
+        renewed: bool,
+        now: u64,
+        client_time: Option<u64>,
+    ) -> Self {
+        let clock_drift_ms = client_time.map(|t| t as i64 - now as i64);
+        let drift_warning = clock_drift_ms
+            .filter(|drift| drift.unsigned_abs() >= CLOCK_DRIFT_WARNING_MS)
+            .map(|drift| {
+                format!(
+                    "Client clock is {}ms {} the server's; lease expiry is server time",
+                    drift.unsigned_abs(),
+                    if drift > 0 { "ahead of" } else { "behind" }
+                )
+            });
+        Self {
+            lease_id: lease_id.to_string(),
+            renewed,
+            state: lease.map(|l| l.state),
+            remaining_ttl: lease
+                .filter(|l| l.state == LeaseState::Active)
+                .map_or(0, |l| l.expires_at.saturating_sub(now)),
+            expires_at: lease.map(|l| l.expires_at),
+            renewals: lease.map_or(0, |l| l.renewals),
+            revision: lease.map_or(0, |l| l.revision),
+            revoked_reason: lease.and_then(|l| l.revoked_reason.clone()),
+            clock_drift_ms,
+            drift_warning,
+        }
+    }
+}
+
+/// A conflicting lease's holder, as named in a refusal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Holder {
+    pub agent_id: String,
+    pub lease_id: String,
+    /// When the lease runs out unless renewed
+    pub expires_at: u64,
+}
+
+impl From<&Lease> for Holder {
+    fn from(lease: &Lease) -> Self {
+        Self {
+            agent_id: lease.agent_id.clone(),
+            lease_id: lease.id.clone(),
+            expires_at: lease.expires_at,
+        }
+    }
 }
 
 /// Result of attempting to acquire a lease
+#[derive(Debug, Clone)]
 pub enum LeaseResult {
     Success {
         lease: Lease,
@@ -86,6 +291,32 @@ pub enum LeaseResult {
     Failure {
         reason: LeaseFailureReason,
         existing_lease: Option<Lease>,
+        /// After a Die verdict, how long to back off; after a Wait, the
+        /// estimated time until the queued request is granted
+        /// (`wait_queue::estimated_wait_ms`)
         wait_time: Option<u64>,
+        /// Position in the resource's wait queue after a Wait verdict
+        /// (1 = next in line)
+        queue_position: Option<usize>,
+        /// ID of the queued request, to cancel it with
+        /// `KlockClient::cancel_pending`
+        request_id: Option<String>,
+        /// Holders of every conflicting lease after a Wait or Die verdict
+        holders: Vec<Holder>,
     },
 }
+
+impl LeaseResult {
+    /// A refusal decided before the scheduler ran: nothing is queued and no
+    /// holder is in the way. `wait_time` is when retrying may succeed.
+    pub fn rejected(reason: LeaseFailureReason, wait_time: Option<u64>) -> Self {
+        LeaseResult::Failure {
+            reason,
+            existing_lease: None,
+            wait_time,
+            queue_position: None,
+            request_id: None,
+            holders: Vec::new(),
+        }
+    }
+}
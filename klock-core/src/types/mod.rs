@@ -1,5 +1,9 @@
+pub mod envelope;
 pub mod lease;
+pub mod normalize;
+pub(crate) mod pattern;
 pub mod primitives;
 
+pub use envelope::*;
 pub use lease::*;
 pub use primitives::*;
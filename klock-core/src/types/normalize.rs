@@ -0,0 +1,152 @@
+//! Canonical resource paths.
+//!
+//! Agents spell the same file many ways: `src/app.ts`, `/src/app.ts`,
+//! `./src//app.ts`, `src\app.ts`. Every `ResourceRef` is normalized when it
+//! is constructed (or deserialized), so all of these produce the same key
+//! and collide in the conflict engine, the index, and every store.
+//!
+//! Case folding and workspace roots depend on where an agent runs, so they
+//! are not applied on construction: the `KlockClient` holds the rules, for
+//! every agent or per namespace, and applies the agent's rules to each
+//! resource it is handed.
+//!
+//! `FILE` paths (and the file part of file-qualified symbols such as
+//! `/src/user.ts#User`) are rewritten as follows:
+//!
+//! - backslashes become `/`
+//! - the path is made absolute (`src/app.ts` → `/src/app.ts`)
+//! - empty and `.` segments are dropped, and `..` removes the segment
+//!   before it
+//! - a trailing `/` (or `/.`) is kept as a single `/`, since it marks a
+//!   directory
+//! - with case folding enabled, the path is lowercased
+//! - with workspace roots configured, an absolute path under one of them
+//!   is made relative to it (`/repo/src/app.ts` → `/src/app.ts` under a
+//!   root of `/repo`), and a path whose `..` segments climb above the
+//!   root is an error rather than being clamped at `/`
+//!
+//! Absolute paths outside every root are taken to be workspace-relative
+//! already, as they are without roots: `/src/app.ts` stays `/src/app.ts`.
+//!
+//! Other resource types name symbols, tables, keys and routes whose
+//! spelling is significant, and are left unchanged.
+
+use super::ResourceType;
+use super::pattern::is_pattern;
+
+/// How resource paths are canonicalized
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathNormalization {
+    /// Treat `FILE` paths as case-insensitive (`/src/App.ts` is
+    /// `/src/app.ts`), as on the default macOS and Windows filesystems
+    pub case_fold: bool,
+    /// Absolute locations of the workspace (`/repo`, `/home/ci/checkout`)
+    /// that agents may prefix paths with; the longest matching root is
+    /// stripped
+    pub roots: Vec<String>,
+}
+
+impl PathNormalization {
+    /// The canonical form of `path` for a resource of `resource_type`. A
+    /// path escaping the workspace root is clamped at it; use `try_apply`
+    /// to reject it instead.
+    pub fn apply(&self, resource_type: &ResourceType, path: &str) -> String {
+        self.canonical(resource_type, path, false)
+            .unwrap_or_else(|_| unreachable!("clamped paths cannot escape"))
+    }
+
+    /// The canonical form of `path`, or an error if it escapes the
+    /// workspace root
+    pub fn try_apply(&self, resource_type: &ResourceType, path: &str) -> Result<String, String> {
+        self.canonical(resource_type, path, true)
+    }
+
+    fn canonical(
+        &self,
+        resource_type: &ResourceType,
+        path: &str,
+        strict: bool,
+    ) -> Result<String, String> {
+        match resource_type {
+            ResourceType::File => self.file_path(path, strict),
+            ResourceType::Symbol => match path.split_once('#') {
+                Some((file, member)) => Ok(format!("{}#{}", self.file_path(file, strict)?, member)),
+                None => Ok(path.to_string()),
+            },
+            _ => Ok(path.to_string()),
+        }
+    }
+
+    fn file_path(&self, path: &str, strict: bool) -> Result<String, String> {
+        // An empty path is invalid, not the root; keep it for validation
+        if path.is_empty() {
+            return Ok(String::new());
+        }
+        let original = path;
+        let mut path = path.replace('\\', "/");
+        if self.case_fold {
+            path = path.to_lowercase();
+        }
+        let directory = path.ends_with('/') || path.ends_with("/.") || path == ".";
+
+        let mut written: Vec<&str> = path
+            .split('/')
+            .filter(|segment| !matches!(*segment, "" | "."))
+            .collect();
+        let root = self.root_len(path.starts_with('/'), &written);
+        written.drain(..root);
+
+        let mut segments: Vec<&str> = Vec::new();
+        for segment in written {
+            match segment {
+                // `..` cannot step back over a glob, which may stand for
+                // any number of segments
+                ".." if segments
+                    .last()
+                    .is_some_and(|last| *last != ".." && !is_pattern(last)) =>
+                {
+                    segments.pop();
+                }
+                // Already at the root
+                ".." if segments.is_empty() => {
+                    if strict && !self.roots.is_empty() {
+                        return Err(format!("Path '{}' escapes the workspace root", original));
+                    }
+                }
+                segment => segments.push(segment),
+            }
+        }
+
+        let mut normalized = format!("/{}", segments.join("/"));
+        if directory && !segments.is_empty() {
+            normalized.push('/');
+        }
+        Ok(normalized)
+    }
+
+    /// How many leading segments of an absolute path the longest matching
+    /// root covers
+    fn root_len(&self, absolute: bool, segments: &[&str]) -> usize {
+        self.roots
+            .iter()
+            .filter(|root| absolute || !root.starts_with('/'))
+            .map(|root| {
+                let mut root = root.replace('\\', "/");
+                if self.case_fold {
+                    root = root.to_lowercase();
+                }
+                root.split('/')
+                    .filter(|segment| !matches!(*segment, "" | "."))
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|root| {
+                !root.is_empty()
+                    && root.len() <= segments.len()
+                    && root.iter().zip(segments).all(|(r, s)| r == s)
+            })
+            .map(|root| root.len())
+            .max()
+            .unwrap_or(0)
+    }
+}
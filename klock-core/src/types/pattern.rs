@@ -0,0 +1,133 @@
+//! Glob patterns over resource paths.
+//!
+//! `*` matches any run of characters within one path segment, `**` matches
+//! any run including separators (so `/src/**/*.ts` also matches
+//! `/src/a.ts`), and `?` matches a single non-separator character. The separator is `/` for files and API endpoints and `.` for
+//! dotted names (symbols, config keys, tables).
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Token {
+    Literal(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    Star,
+    /// `**`
+    Globstar,
+}
+
+impl Token {
+    fn is_star(self) -> bool {
+        matches!(self, Token::Star | Token::Globstar)
+    }
+}
+
+/// Whether `path` contains glob metacharacters
+pub(crate) fn is_pattern(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+pub(crate) fn tokenize(path: &str) -> Vec<Token> {
+    let mut tokens = Vec::with_capacity(path.len());
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                Token::Globstar
+            }
+            '*' => Token::Star,
+            '?' => Token::AnyChar,
+            c => Token::Literal(c),
+        });
+    }
+    tokens
+}
+
+/// Whether some concrete path is matched by both token sequences.
+pub(crate) fn intersects(a: &[Token], b: &[Token], separator: char) -> bool {
+    Intersection {
+        a,
+        b,
+        separator,
+        memo: HashMap::new(),
+    }
+    .at(0, 0)
+}
+
+/// Whether the literal `path` is matched by `pattern`.
+pub(crate) fn matches(pattern: &str, path: &str, separator: char) -> bool {
+    let literal: Vec<Token> = path.chars().map(Token::Literal).collect();
+    intersects(&tokenize(pattern), &literal, separator)
+}
+
+struct Intersection<'a> {
+    a: &'a [Token],
+    b: &'a [Token],
+    separator: char,
+    memo: HashMap<(usize, usize), bool>,
+}
+
+impl Intersection<'_> {
+    fn at(&mut self, i: usize, j: usize) -> bool {
+        if let Some(&known) = self.memo.get(&(i, j)) {
+            return known;
+        }
+        let result = self.compute(i, j);
+        self.memo.insert((i, j), result);
+        result
+    }
+
+    fn compute(&mut self, i: usize, j: usize) -> bool {
+        let (a, b) = (self.a.get(i).copied(), self.b.get(j).copied());
+        match (a, b) {
+            (None, None) => true,
+            (Some(x), Some(y)) if x.is_star() && y.is_star() => {
+                self.at(i + 1, j)
+                    || self.at(i, j + 1)
+                    || self.skips_directory(self.a, i) && self.at(i + 2, j)
+                    || self.skips_directory(self.b, j) && self.at(i, j + 2)
+            }
+            // A star may match nothing, or absorb one character the other side produces
+            (Some(x), _) if x.is_star() => {
+                self.at(i + 1, j)
+                    || self.skips_directory(self.a, i) && self.at(i + 2, j)
+                    || b.is_some_and(|y| self.star_absorbs(x, y)) && self.at(i, j + 1)
+            }
+            (_, Some(y)) if y.is_star() => {
+                self.at(i, j + 1)
+                    || self.skips_directory(self.b, j) && self.at(i, j + 2)
+                    || a.is_some_and(|x| self.star_absorbs(y, x)) && self.at(i + 1, j)
+            }
+            (Some(x), Some(y)) => self.chars_compatible(x, y) && self.at(i + 1, j + 1),
+            _ => false,
+        }
+    }
+
+    /// Whether `tokens[i]` is a `**` followed by a separator, which together
+    /// may match zero segments
+    fn skips_directory(&self, tokens: &[Token], i: usize) -> bool {
+        tokens[i] == Token::Globstar && tokens.get(i + 1) == Some(&Token::Literal(self.separator))
+    }
+
+    /// Whether `star` can consume a character produced by the single-char token `other`
+    fn star_absorbs(&self, star: Token, other: Token) -> bool {
+        match (star, other) {
+            (Token::Globstar, _) => true,
+            (_, Token::Literal(c)) => c != self.separator,
+            (_, _) => true, // `?` never produces a separator
+        }
+    }
+
+    fn chars_compatible(&self, x: Token, y: Token) -> bool {
+        match (x, y) {
+            (Token::Literal(c), Token::Literal(d)) => c == d,
+            (Token::Literal(c), Token::AnyChar) | (Token::AnyChar, Token::Literal(c)) => {
+                c != self.separator
+            }
+            _ => true,
+        }
+    }
+}
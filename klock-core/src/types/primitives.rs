@@ -1,8 +1,10 @@
+use super::normalize::PathNormalization;
+use super::pattern;
 use serde::{Deserialize, Serialize};
 
 /// Predicates represent the relationship between an agent and a resource.
 /// These are the verbs in the Subject-Predicate-Object (SPO) triples.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Predicate {
     /// Agent creates/exports something new
     Provides,
@@ -16,6 +18,9 @@ pub enum Predicate {
     DependsOn,
     /// Agent renames a resource
     Renames,
+    /// Agent needs the resource to itself: no other agent may touch it, not
+    /// even to read it
+    Excludes,
 }
 
 impl Predicate {
@@ -28,6 +33,7 @@ impl Predicate {
             Predicate::Deletes => 3,
             Predicate::DependsOn => 4,
             Predicate::Renames => 5,
+            Predicate::Excludes => 6,
         }
     }
 }
@@ -40,6 +46,20 @@ pub enum Confidence {
     Low,
 }
 
+impl Confidence {
+    /// Whether this is no more certain than `other`
+    pub fn at_most(self, other: Confidence) -> bool {
+        fn level(confidence: Confidence) -> u8 {
+            match confidence {
+                Confidence::Low => 0,
+                Confidence::Medium => 1,
+                Confidence::High => 2,
+            }
+        }
+        level(self) <= level(other)
+    }
+}
+
 /// Types of resources that can be leased and conflict-checked
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ResourceType {
@@ -55,6 +75,16 @@ pub enum ResourceType {
     ConfigKey,
 }
 
+impl ResourceType {
+    /// Path segment separator used by glob patterns
+    pub fn separator(&self) -> char {
+        match self {
+            ResourceType::File | ResourceType::ApiEndpoint => '/',
+            ResourceType::Symbol | ResourceType::DatabaseTable | ResourceType::ConfigKey => '.',
+        }
+    }
+}
+
 impl std::fmt::Display for ResourceType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -67,19 +97,47 @@ impl std::fmt::Display for ResourceType {
     }
 }
 
+/// HTTP methods recognised as a prefix of `API_ENDPOINT` paths (`GET /users`)
+pub const HTTP_METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
 /// A reference to a resource in the system
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "ResourceRefFields")]
 pub struct ResourceRef {
     pub resource_type: ResourceType,
     /// Normalized path (e.g., "/src/auth.ts" or "User.authenticate")
     pub path: String,
 }
 
+/// Deserialized form of `ResourceRef`, normalized on conversion
+#[derive(Deserialize)]
+struct ResourceRefFields {
+    resource_type: ResourceType,
+    path: String,
+}
+
+impl From<ResourceRefFields> for ResourceRef {
+    fn from(fields: ResourceRefFields) -> Self {
+        Self::new(fields.resource_type, fields.path)
+    }
+}
+
 impl ResourceRef {
+    /// A resource with its path normalized under the default
+    /// `PathNormalization` rules: no case folding and no workspace roots.
     pub fn new(resource_type: ResourceType, path: impl Into<String>) -> Self {
+        let path = path.into();
         Self {
+            path: PathNormalization::default().apply(&resource_type, &path),
             resource_type,
-            path: path.into(),
+        }
+    }
+
+    /// The resource with its path normalized again under `rules`
+    pub fn normalized(&self, rules: &PathNormalization) -> Self {
+        Self {
+            resource_type: self.resource_type.clone(),
+            path: rules.apply(&self.resource_type, &self.path),
         }
     }
 
@@ -87,6 +145,217 @@ impl ResourceRef {
     pub fn key(&self) -> String {
         format!("{}:{}", self.resource_type, self.path)
     }
+
+    /// Parses a key produced by `key()` back into a resource
+    pub fn from_key(key: &str) -> Option<Self> {
+        let (resource_type, path) = key.split_once(':')?;
+        let resource_type = match resource_type {
+            "FILE" => ResourceType::File,
+            "SYMBOL" => ResourceType::Symbol,
+            "API_ENDPOINT" => ResourceType::ApiEndpoint,
+            "DATABASE_TABLE" => ResourceType::DatabaseTable,
+            "CONFIG_KEY" => ResourceType::ConfigKey,
+            _ => return None,
+        };
+        Some(Self::new(resource_type, path))
+    }
+
+    /// An `API_ENDPOINT` on `route`, optionally restricted to one HTTP
+    /// method (`GET /users`). Without a method it covers every method.
+    pub fn api_endpoint(method: Option<&str>, route: &str) -> Self {
+        match method {
+            Some(method) => Self::new(
+                ResourceType::ApiEndpoint,
+                format!("{} {}", method.to_ascii_uppercase(), route),
+            ),
+            None => Self::new(ResourceType::ApiEndpoint, route),
+        }
+    }
+
+    /// A `DATABASE_TABLE`, optionally narrowed to one column
+    /// (`users#email`). The table may be schema-qualified
+    /// (`public.users`); a table covers all of its columns.
+    pub fn database_table(table: &str, column: Option<&str>) -> Self {
+        match column {
+            Some(column) => Self::new(ResourceType::DatabaseTable, format!("{}#{}", table, column)),
+            None => Self::new(ResourceType::DatabaseTable, table),
+        }
+    }
+
+    /// The HTTP method (if any) and route of an `API_ENDPOINT`
+    /// (`GET /users` → `(Some("GET"), "/users")`)
+    pub fn endpoint(&self) -> Option<(Option<&str>, &str)> {
+        if self.resource_type != ResourceType::ApiEndpoint {
+            return None;
+        }
+        match self.path.split_once(' ') {
+            Some((method, route)) if HTTP_METHODS.contains(&method) => Some((Some(method), route)),
+            _ => Some((None, &self.path)),
+        }
+    }
+
+    /// A `FILE` path ending in `/` denotes a directory
+    pub fn is_directory(&self) -> bool {
+        self.resource_type == ResourceType::File && self.path.ends_with('/')
+    }
+
+    /// The file defining a file-qualified symbol (`/src/user.ts#User.login`)
+    pub fn containing_file(&self) -> Option<ResourceRef> {
+        if self.resource_type != ResourceType::Symbol {
+            return None;
+        }
+        let (file, _) = self.path.split_once('#')?;
+        Some(Self::new(ResourceType::File, file))
+    }
+
+    /// Whether this resource covers `other`: a directory covers everything
+    /// beneath it, a file covers the symbols qualified with it, a symbol
+    /// covers its members (`User` covers `User.authenticate`), a table
+    /// covers its columns (`public.users` covers `public.users#email`, but
+    /// not the table `public.users.archive`), and an endpoint
+    /// without a method covers every method on its route.
+    pub fn contains(&self, other: &ResourceRef) -> bool {
+        if self == other {
+            return true;
+        }
+
+        match (&self.resource_type, &other.resource_type) {
+            (ResourceType::File, ResourceType::File) => {
+                self.is_directory() && other.path.starts_with(&self.path)
+            }
+            (ResourceType::File, ResourceType::Symbol) => other
+                .containing_file()
+                .is_some_and(|file| self.contains(&file)),
+            (ResourceType::Symbol, ResourceType::Symbol) => other
+                .path
+                .strip_prefix(&self.path)
+                .is_some_and(|member| member.starts_with('.')),
+            (ResourceType::DatabaseTable, ResourceType::DatabaseTable) => {
+                !self.path.contains('#')
+                    && other
+                        .path
+                        .strip_prefix(&self.path)
+                        .is_some_and(|column| column.starts_with('#'))
+            }
+            (ResourceType::ApiEndpoint, ResourceType::ApiEndpoint) => {
+                matches!(
+                    (self.endpoint(), other.endpoint()),
+                    (Some((None, route)), Some((Some(_), other_route))) if route == other_route
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the path is a glob pattern (`FILE:/src/**/*.ts`) rather than
+    /// a single resource
+    pub fn is_pattern(&self) -> bool {
+        pattern::is_pattern(&self.path)
+    }
+
+    /// Whether a glob pattern matches the literal resource `other`
+    pub fn matches(&self, other: &ResourceRef) -> bool {
+        if let (Some((method, route)), Some((other_method, other_route))) =
+            (self.endpoint(), other.endpoint())
+        {
+            return (method.is_none() || method == other_method)
+                && pattern::matches(route, other_route, '/');
+        }
+        self.resource_type == other.resource_type
+            && pattern::matches(&self.path, &other.path, self.resource_type.separator())
+    }
+
+    /// Whether the two resources share any concrete resource: either one
+    /// contains the other, or (for patterns) some resource is covered by both.
+    pub fn overlaps(&self, other: &ResourceRef) -> bool {
+        if !self.is_pattern() && !other.is_pattern() {
+            return self.contains(other) || other.contains(self);
+        }
+
+        match (&self.resource_type, &other.resource_type) {
+            (ResourceType::File, ResourceType::File) => {
+                pattern::intersects(&self.coverage(), &other.coverage(), '/')
+            }
+            (ResourceType::File, ResourceType::Symbol) => other
+                .containing_file()
+                .is_some_and(|file| self.overlaps(&file)),
+            (ResourceType::Symbol, ResourceType::File) => self
+                .containing_file()
+                .is_some_and(|file| file.overlaps(other)),
+            (ResourceType::Symbol, ResourceType::Symbol) => {
+                match (self.path.split_once('#'), other.path.split_once('#')) {
+                    (Some((file_a, member_a)), Some((file_b, member_b))) => {
+                        pattern::intersects(
+                            &pattern::tokenize(file_a),
+                            &pattern::tokenize(file_b),
+                            '/',
+                        ) && members_overlap(member_a, member_b)
+                    }
+                    _ => members_overlap(&self.path, &other.path),
+                }
+            }
+            (ResourceType::DatabaseTable, ResourceType::DatabaseTable) => {
+                columns_overlap(&self.path, &other.path)
+            }
+            (ResourceType::ApiEndpoint, ResourceType::ApiEndpoint) => {
+                match (self.endpoint(), other.endpoint()) {
+                    (Some((method_a, route_a)), Some((method_b, route_b))) => {
+                        (method_a.is_none() || method_b.is_none() || method_a == method_b)
+                            && pattern::intersects(
+                                &pattern::tokenize(route_a),
+                                &pattern::tokenize(route_b),
+                                '/',
+                            )
+                    }
+                    _ => false,
+                }
+            }
+            (a, b) if a == b => pattern::intersects(
+                &pattern::tokenize(&self.path),
+                &pattern::tokenize(&other.path),
+                a.separator(),
+            ),
+            _ => false,
+        }
+    }
+
+    /// Tokens matching every path this `FILE` resource covers
+    fn coverage(&self) -> Vec<pattern::Token> {
+        let mut tokens = pattern::tokenize(&self.path);
+        if self.is_directory() {
+            tokens.push(pattern::Token::Globstar);
+        }
+        tokens
+    }
+}
+
+/// Whether two tables or columns (`public.users#email`, possibly patterns)
+/// share a resource: their tables match, and their columns do unless one
+/// of them is the whole table
+fn columns_overlap(a: &str, b: &str) -> bool {
+    let (table_a, column_a) = a.split_once('#').map_or((a, None), |(t, c)| (t, Some(c)));
+    let (table_b, column_b) = b.split_once('#').map_or((b, None), |(t, c)| (t, Some(c)));
+    let intersects =
+        |a: &str, b: &str| pattern::intersects(&pattern::tokenize(a), &pattern::tokenize(b), '.');
+    intersects(table_a, table_b)
+        && match (column_a, column_b) {
+            (Some(column_a), Some(column_b)) => intersects(column_a, column_b),
+            _ => true,
+        }
+}
+
+/// Whether two dotted symbol names (possibly patterns) share a resource,
+/// counting members as covered by their parents
+fn members_overlap(a: &str, b: &str) -> bool {
+    let member_of = |tokens: &[pattern::Token]| {
+        let mut tokens = tokens.to_vec();
+        tokens.extend([pattern::Token::Literal('.'), pattern::Token::Globstar]);
+        tokens
+    };
+    let (a, b) = (pattern::tokenize(a), pattern::tokenize(b));
+    pattern::intersects(&a, &b, '.')
+        || pattern::intersects(&member_of(&a), &b, '.')
+        || pattern::intersects(&a, &member_of(&b), '.')
 }
 
 /// A Subject-Predicate-Object triple representing an agent's intent
@@ -106,4 +375,33 @@ pub struct SPOTriple {
     pub confidence: Confidence,
     /// The session this triple belongs to
     pub session_id: String,
+    /// New path of the object, for `Renames` intents. Once granted, the old
+    /// and new names alias each other for the rest of the session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_to: Option<String>,
+    /// When the intent lapses unless its session renews it. Expired intents
+    /// conflict with nothing and are evicted (see
+    /// `KlockClient::evict_expired_intents`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Keep the intent after its session releases every lease covering it
+    /// (see `Lease::covers`), for declarations that outlive their leases
+    #[serde(default)]
+    pub long_lived: bool,
+}
+
+impl SPOTriple {
+    /// Whether the intent has lapsed by `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at < now)
+    }
+
+    /// Approximate heap + inline footprint, used for memory reporting
+    pub fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.id.capacity()
+            + self.subject.capacity()
+            + self.object.path.capacity()
+            + self.session_id.capacity()
+    }
 }
@@ -0,0 +1,422 @@
+//! Lease usage metering, for billing and chargeback.
+//!
+//! Platform teams want to know which teams' agents keep the coordinator
+//! busy. Agents carry labels (e.g. `team=payments`) set when they register.
+//! With metering on, the client counts each agent's acquires and lease time
+//! from the changes its store applies: a lease counts from its grant or
+//! activation until it is released, revoked or runs out, and reservations
+//! count only once activated. `UsageMeter::roll_up` closes the current
+//! period and hands one `UsageRollup` per agent to a `UsageStore` (in
+//! memory, or a SQLite table that outlives the process); reports add up
+//! the rollups of a window, plus the period still open, grouped by a label.
+
+use crate::event::KlockEvent;
+use crate::types::{Lease, LeaseState};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+/// Labels of an agent, e.g. `{"team": "payments"}`
+pub type AgentLabels = BTreeMap<String, String>;
+
+/// Groups reports by agent instead of by a label
+pub const GROUP_BY_AGENT: &str = "agent";
+
+/// One agent's usage over one period
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageRollup {
+    /// When the period started, in ms since the epoch
+    pub start: u64,
+    /// When the period was closed (or, for the open period, the report's
+    /// time)
+    pub end: u64,
+    pub agent_id: String,
+    /// The agent's labels when the period was closed
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: AgentLabels,
+    /// Leases granted or activated
+    pub acquires: u64,
+    /// Time leases were held, summed over the agent's leases
+    pub lease_ms: u64,
+}
+
+/// Where closed periods are kept.
+pub trait UsageStore: Send + Sync {
+    /// Keep `rollups`, the closed period's usage
+    fn append(&self, rollups: &[UsageRollup]) -> Result<(), String>;
+
+    /// Rollups whose period overlaps `from..to`, oldest first
+    fn rollups(&self, from: u64, to: u64) -> Result<Vec<UsageRollup>, String>;
+}
+
+/// Rollups kept in process memory.
+#[derive(Debug, Default)]
+pub struct InMemoryUsageStore {
+    rollups: RwLock<Vec<UsageRollup>>,
+}
+
+impl InMemoryUsageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl UsageStore for InMemoryUsageStore {
+    fn append(&self, rollups: &[UsageRollup]) -> Result<(), String> {
+        self.rollups.write().unwrap().extend_from_slice(rollups);
+        Ok(())
+    }
+
+    fn rollups(&self, from: u64, to: u64) -> Result<Vec<UsageRollup>, String> {
+        Ok(self
+            .rollups
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|rollup| rollup.end > from && rollup.start < to)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Rollups persisted in the `usage_rollups` table of a SQLite database.
+#[cfg(feature = "sqlite")]
+pub struct SqliteUsageStore {
+    conn: std::sync::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteUsageStore {
+    /// Open (or create) the rollup table in the SQLite database at `path`.
+    pub fn open(path: &str) -> Result<Self, rusqlite::Error> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS usage_rollups (
+                period_start INTEGER NOT NULL,
+                period_end INTEGER NOT NULL,
+                agent_id TEXT NOT NULL,
+                labels TEXT NOT NULL,
+                acquires INTEGER NOT NULL,
+                lease_ms INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS usage_rollups_by_end ON usage_rollups (period_end);",
+        )?;
+        Ok(Self {
+            conn: std::sync::Mutex::new(conn),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl UsageStore for SqliteUsageStore {
+    fn append(&self, rollups: &[UsageRollup]) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for rollup in rollups {
+            let labels = serde_json::to_string(&rollup.labels).map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO usage_rollups
+                 (period_start, period_end, agent_id, labels, acquires, lease_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                rusqlite::params![
+                    rollup.start,
+                    rollup.end,
+                    rollup.agent_id,
+                    labels,
+                    rollup.acquires,
+                    rollup.lease_ms
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn rollups(&self, from: u64, to: u64) -> Result<Vec<UsageRollup>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT period_start, period_end, agent_id, labels, acquires, lease_ms
+                 FROM usage_rollups WHERE period_end > ?1 AND period_start < ?2
+                 ORDER BY period_start, rowid",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            // SQLite integers are signed
+            .query_map(
+                rusqlite::params![from.min(i64::MAX as u64), to.min(i64::MAX as u64)],
+                |row| {
+                    Ok((
+                        row.get::<_, u64>(0)?,
+                        row.get::<_, u64>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, u64>(4)?,
+                        row.get::<_, u64>(5)?,
+                    ))
+                },
+            )
+            .map_err(|e| e.to_string())?;
+        let mut rollups = Vec::new();
+        for row in rows {
+            let (start, end, agent_id, labels, acquires, lease_ms) =
+                row.map_err(|e| e.to_string())?;
+            rollups.push(UsageRollup {
+                start,
+                end,
+                agent_id,
+                labels: serde_json::from_str(&labels).unwrap_or_default(),
+                acquires,
+                lease_ms,
+            });
+        }
+        Ok(rollups)
+    }
+}
+
+/// The usage of one group in a report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageGroup {
+    /// The label's value, or the agent ID when grouping by agent; `None`
+    /// for agents without the label
+    pub group: Option<String>,
+    /// Agents counted in the group
+    pub agents: Vec<String>,
+    pub acquires: u64,
+    pub lease_ms: u64,
+}
+
+/// Usage over a window, grouped
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub from: u64,
+    pub to: u64,
+    /// The label grouped by, or `agent`
+    pub group_by: String,
+    /// Heaviest users first
+    pub groups: Vec<UsageGroup>,
+}
+
+impl UsageReport {
+    /// Add up `rollups` by the `group_by` label (or by agent). Rollups are
+    /// counted whole, so the window is as precise as the rollup interval.
+    pub fn new(rollups: &[UsageRollup], group_by: &str, from: u64, to: u64) -> Self {
+        let mut groups: BTreeMap<Option<String>, UsageGroup> = BTreeMap::new();
+        for rollup in rollups {
+            let key = if group_by == GROUP_BY_AGENT {
+                Some(rollup.agent_id.clone())
+            } else {
+                rollup.labels.get(group_by).cloned()
+            };
+            let group = groups.entry(key.clone()).or_insert_with(|| UsageGroup {
+                group: key,
+                agents: Vec::new(),
+                acquires: 0,
+                lease_ms: 0,
+            });
+            if !group.agents.contains(&rollup.agent_id) {
+                group.agents.push(rollup.agent_id.clone());
+            }
+            group.acquires += rollup.acquires;
+            group.lease_ms += rollup.lease_ms;
+        }
+        let mut groups: Vec<UsageGroup> = groups.into_values().collect();
+        for group in &mut groups {
+            group.agents.sort();
+        }
+        groups.sort_by(|a, b| {
+            b.lease_ms
+                .cmp(&a.lease_ms)
+                .then(b.acquires.cmp(&a.acquires))
+        });
+        Self {
+            from,
+            to,
+            group_by: group_by.to_string(),
+            groups,
+        }
+    }
+}
+
+/// Parse a report window such as `90m`, `24h` or `7d` into milliseconds
+pub fn parse_period(spec: &str) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "Invalid period '{}': expected a number of minutes, hours or days, e.g. 24h or 7d",
+            spec
+        )
+    };
+    let (count, unit_ms) = [('m', 60_000u64), ('h', 3_600_000), ('d', 86_400_000)]
+        .into_iter()
+        .find_map(|(unit, unit_ms)| Some((spec.strip_suffix(unit)?, unit_ms)))
+        .ok_or_else(invalid)?;
+    match count.parse::<u64>() {
+        Ok(count) if count > 0 => count.checked_mul(unit_ms).ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Usage {
+    acquires: u64,
+    lease_ms: u64,
+}
+
+/// Agent labels, and the usage of the open period
+#[derive(Default)]
+pub struct UsageMeter {
+    store: Option<Arc<dyn UsageStore>>,
+    labels: HashMap<String, AgentLabels>,
+    period_start: u64,
+    current: BTreeMap<String, Usage>,
+    /// Leases being held, by ID
+    open: HashMap<String, OpenLease>,
+}
+
+/// A lease being held
+#[derive(Debug, Clone)]
+struct OpenLease {
+    agent_id: String,
+    /// When its time in the open period started
+    from: u64,
+    /// When it runs out unless renewed; it may not be evicted right away
+    expires_at: u64,
+}
+
+impl OpenLease {
+    fn held_until(&self, at: u64) -> u64 {
+        at.min(self.expires_at).saturating_sub(self.from)
+    }
+}
+
+impl UsageMeter {
+    /// Meter usage from `now` on, closing periods into `store`; `None`
+    /// stops metering
+    pub fn set_store(&mut self, store: Option<Arc<dyn UsageStore>>, now: u64) {
+        self.store = store;
+        self.period_start = now;
+        self.current.clear();
+        self.open.clear();
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Replace the agent's labels
+    pub fn set_labels(&mut self, agent_id: &str, labels: AgentLabels) {
+        if labels.is_empty() {
+            self.labels.remove(agent_id);
+        } else {
+            self.labels.insert(agent_id.to_string(), labels);
+        }
+    }
+
+    pub fn labels(&self, agent_id: &str) -> Option<&AgentLabels> {
+        self.labels.get(agent_id)
+    }
+
+    /// Count a change the store applied at `now`; `lease` is the lease as
+    /// it was left
+    pub fn observe(&mut self, now: u64, event: &KlockEvent, lease: Option<&Lease>) {
+        if !self.is_enabled() {
+            return;
+        }
+        match event {
+            KlockEvent::LeaseCreated { lease } if lease.state == LeaseState::Active => {
+                self.opened(lease, lease.acquired_at.min(now));
+            }
+            KlockEvent::LeaseActivated { at, .. } => {
+                if let Some(lease) = lease {
+                    self.opened(lease, *at);
+                }
+            }
+            KlockEvent::LeaseRenewed { lease_id, .. } => {
+                if let (Some(open), Some(lease)) = (self.open.get_mut(lease_id), lease) {
+                    open.expires_at = lease.expires_at;
+                }
+            }
+            KlockEvent::LeaseReleased { lease_id }
+            | KlockEvent::LeaseRevoked { lease_id, .. }
+            | KlockEvent::LeaseExpired { lease_id } => {
+                if let Some(open) = self.open.remove(lease_id) {
+                    self.current
+                        .entry(open.agent_id.clone())
+                        .or_default()
+                        .lease_ms += open.held_until(now);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn opened(&mut self, lease: &Lease, at: u64) {
+        self.current
+            .entry(lease.agent_id.clone())
+            .or_default()
+            .acquires += 1;
+        self.open.insert(
+            lease.id.clone(),
+            OpenLease {
+                agent_id: lease.agent_id.clone(),
+                from: at.max(self.period_start),
+                expires_at: lease.expires_at,
+            },
+        );
+    }
+
+    /// The open period's usage up to `now`, without closing it
+    pub fn pending(&self, now: u64) -> Vec<UsageRollup> {
+        let mut usage = self.current.clone();
+        for open in self.open.values() {
+            usage.entry(open.agent_id.clone()).or_default().lease_ms += open.held_until(now);
+        }
+        usage
+            .into_iter()
+            .filter(|(_, usage)| usage.acquires > 0 || usage.lease_ms > 0)
+            .map(|(agent_id, usage)| UsageRollup {
+                start: self.period_start,
+                end: now,
+                labels: self.labels.get(&agent_id).cloned().unwrap_or_default(),
+                agent_id,
+                acquires: usage.acquires,
+                lease_ms: usage.lease_ms,
+            })
+            .collect()
+    }
+
+    /// Close the open period at `now`, handing its usage to the store, and
+    /// start the next. Returns the rollups stored. If the store refuses
+    /// them, the period stays open and the next roll-up retries.
+    pub fn roll_up(&mut self, now: u64) -> Result<usize, String> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        let rollups = self.pending(now);
+        store.append(&rollups)?;
+        self.current.clear();
+        for open in self.open.values_mut() {
+            open.from = now;
+        }
+        self.period_start = now;
+        Ok(rollups.len())
+    }
+
+    /// Usage in `from..to` grouped by `group_by`, including the open
+    /// period's so far at `now`. `None` when metering is off.
+    pub fn report(
+        &self,
+        group_by: &str,
+        from: u64,
+        to: u64,
+        now: u64,
+    ) -> Option<Result<UsageReport, String>> {
+        let store = self.store.as_ref()?;
+        Some(store.rollups(from, to).map(|mut rollups| {
+            if now > from && self.period_start < to {
+                rollups.extend(self.pending(now));
+            }
+            UsageReport::new(&rollups, group_by, from, to)
+        }))
+    }
+}
@@ -0,0 +1,167 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::types::{LeaseResult, Predicate, ResourceRef, ResourceType};
+    use crate::usage::{
+        AgentLabels, GROUP_BY_AGENT, InMemoryUsageStore, UsageGroup, UsageStore, parse_period,
+    };
+    use std::sync::Arc;
+
+    fn team(name: &str) -> AgentLabels {
+        AgentLabels::from([("team".to_string(), name.to_string())])
+    }
+
+    fn lease_id(result: LeaseResult) -> String {
+        match result {
+            LeaseResult::Success { lease } => lease.id,
+            other => panic!("Expected success, got {:?}", other),
+        }
+    }
+
+    /// (group, agents, acquires, lease_ms) of each group, heaviest first
+    fn summary(groups: Vec<UsageGroup>) -> Vec<(Option<String>, Vec<String>, u64, u64)> {
+        groups
+            .into_iter()
+            .map(|g| (g.group, g.agents, g.acquires, g.lease_ms))
+            .collect()
+    }
+
+    #[test]
+    fn test_lease_time_and_acquires_roll_up_by_label() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        let store = Arc::new(InMemoryUsageStore::new());
+        client.set_usage_store(Some(store.clone()));
+        for (agent, labels) in [
+            ("a", team("payments")),
+            ("b", team("payments")),
+            ("c", team("search")),
+            ("d", AgentLabels::new()),
+        ] {
+            client.register_agent(agent, 100);
+            client.set_agent_labels(agent, labels);
+        }
+
+        let a = lease_id(client.acquire_lease("a", "s", "FILE", "/a.ts", "MUTATES", 10_000));
+        clock.advance(4_000);
+        assert!(client.release_lease(&a));
+        let b = lease_id(client.acquire_lease("b", "s", "FILE", "/b.ts", "MUTATES", 10_000));
+        // A reservation counts once activated
+        let reserved = client
+            .pre_acquire(
+                "c",
+                "s",
+                &[(
+                    ResourceRef::new(ResourceType::File, "/c.ts"),
+                    Predicate::Mutates,
+                )],
+                10_000,
+                20_000,
+            )
+            .unwrap();
+        clock.advance(1_000);
+        lease_id(client.activate_lease(&reserved[0].id));
+        // Runs out at 8_000, though nothing evicts it before the roll-up
+        client.acquire_lease("d", "s", "FILE", "/d.ts", "MUTATES", 2_000);
+
+        clock.advance(4_000);
+        assert_eq!(client.roll_up_usage(), Ok(4));
+        let report = client.usage_report("team", 0, 10_000).unwrap().unwrap();
+        assert_eq!(
+            summary(report.groups),
+            vec![
+                (
+                    Some("payments".to_string()),
+                    vec!["a".to_string(), "b".to_string()],
+                    2,
+                    9_000
+                ),
+                (Some("search".to_string()), vec!["c".to_string()], 1, 4_000),
+                (None, vec!["d".to_string()], 1, 2_000),
+            ]
+        );
+
+        // Rollups keep the labels the agent had when they were closed
+        client.set_agent_labels("b", team("search"));
+        clock.advance(2_000);
+        assert!(client.release_lease(&b));
+        assert_eq!(client.evict_expired(), 1);
+        let report = client.usage_report("team", 0, 12_000).unwrap().unwrap();
+        assert_eq!(
+            summary(report.groups),
+            vec![
+                (
+                    Some("payments".to_string()),
+                    vec!["a".to_string(), "b".to_string()],
+                    2,
+                    9_000
+                ),
+                (
+                    Some("search".to_string()),
+                    vec!["b".to_string(), "c".to_string()],
+                    1,
+                    8_000
+                ),
+                (None, vec!["d".to_string()], 1, 2_000),
+            ]
+        );
+
+        // A window after the last roll-up only sees the open period
+        let report = client
+            .usage_report(GROUP_BY_AGENT, 10_000, 12_000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            summary(report.groups),
+            vec![
+                (Some("b".to_string()), vec!["b".to_string()], 0, 2_000),
+                (Some("c".to_string()), vec!["c".to_string()], 0, 2_000),
+            ]
+        );
+        assert_eq!(store.rollups(0, u64::MAX).unwrap().len(), 4);
+
+        client.set_usage_store(None);
+        assert!(client.usage_report("team", 0, 12_000).is_none());
+    }
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(parse_period("90m"), Ok(5_400_000));
+        assert_eq!(parse_period("24h"), Ok(86_400_000));
+        assert_eq!(parse_period("7d"), Ok(604_800_000));
+        for invalid in ["", "d", "0h", "7", "1w", "-1d", "7д"] {
+            assert!(parse_period(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_rollups_outlive_the_store() {
+        use crate::usage::{SqliteUsageStore, UsageRollup};
+
+        let path = std::env::temp_dir().join(format!("klock_usage_test_{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let rollup = |start: u64, agent_id: &str| UsageRollup {
+            start,
+            end: start + 1000,
+            agent_id: agent_id.to_string(),
+            labels: team("payments"),
+            acquires: 3,
+            lease_ms: 750,
+        };
+        SqliteUsageStore::open(path)
+            .unwrap()
+            .append(&[rollup(0, "a"), rollup(1000, "b")])
+            .unwrap();
+
+        let store = SqliteUsageStore::open(path).unwrap();
+        assert_eq!(
+            store.rollups(0, u64::MAX).unwrap(),
+            vec![rollup(0, "a"), rollup(1000, "b")]
+        );
+        assert_eq!(store.rollups(1000, 1500).unwrap(), vec![rollup(1000, "b")]);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,327 @@
+//! Per-resource FIFO wait queues.
+//!
+//! An acquire refused with a Wait verdict joins the queue for its resource.
+//! When a lease ends, the store grants the head of each queue whose request
+//! no longer conflicts, then the one behind it, in the order they joined; a
+//! head that is still blocked holds up the waiters behind it. The granted
+//! lease is recorded like any other, and a waiter that retries after being
+//! granted gets that lease back rather than a second one. Waiters leave the
+//! queue when granted, when a retry is granted or dies, when cancelled, or
+//! once their TTL passes without a grant.
+//!
+//! A Wait verdict reports the request's place in its queue and an estimated
+//! wait (see `estimated_wait_ms`), so the caller can choose between
+//! blocking and doing other work first.
+
+use crate::conflict::ConflictEngine;
+use crate::types::{Holder, Lease, Predicate, ResourceRef};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// An acquire waiting for its resource
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Waiter {
+    /// Assigned when the request joins its queue; cancels it
+    pub id: String,
+    pub agent_id: String,
+    pub session_id: String,
+    pub resource: ResourceRef,
+    pub predicate: Predicate,
+    /// TTL of the lease to grant
+    pub ttl: u64,
+    /// When the waiter joined the queue
+    pub enqueued_at: u64,
+    /// Leaves the queue if not granted by then
+    pub expires_at: u64,
+    /// Deadline of the request, kept on the lease it is granted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
+}
+
+impl Waiter {
+    pub fn new(
+        agent_id: &str,
+        session_id: &str,
+        resource: ResourceRef,
+        predicate: Predicate,
+        ttl: u64,
+        now: u64,
+    ) -> Self {
+        Self {
+            id: String::new(),
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            resource,
+            predicate,
+            ttl,
+            enqueued_at: now,
+            expires_at: now.saturating_add(ttl),
+            deadline: None,
+        }
+    }
+
+    fn is_request(
+        &self,
+        agent_id: &str,
+        session_id: &str,
+        resource: &ResourceRef,
+        predicate: Predicate,
+    ) -> bool {
+        self.agent_id == agent_id
+            && self.session_id == session_id
+            && self.predicate == predicate
+            && self.resource == *resource
+    }
+
+    fn matches(&self, filter: &WaiterFilter) -> bool {
+        match filter {
+            WaiterFilter::Id(id) => self.id == *id,
+            WaiterFilter::Resource { agent_id, resource } => {
+                self.agent_id == *agent_id && self.resource == *resource
+            }
+            WaiterFilter::Session {
+                agent_id,
+                session_id,
+            } => self.agent_id == *agent_id && self.session_id == *session_id,
+        }
+    }
+}
+
+/// Which queued requests to cancel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaiterFilter {
+    /// The request with this ID
+    Id(String),
+    /// The agent's requests for this resource, in any session
+    Resource {
+        agent_id: String,
+        resource: ResourceRef,
+    },
+    /// Every request queued in the agent's session
+    Session {
+        agent_id: String,
+        session_id: String,
+    },
+}
+
+/// A request, as the queue identifies it
+type RequestKey = (String, String, String, Predicate);
+
+fn request_key(
+    agent_id: &str,
+    session_id: &str,
+    resource: &ResourceRef,
+    predicate: Predicate,
+) -> RequestKey {
+    (
+        agent_id.to_string(),
+        session_id.to_string(),
+        resource.key(),
+        predicate,
+    )
+}
+
+/// The waiters for each resource, and the leases granted to waiters that
+/// have not retried since (with when those leases expire)
+#[derive(Debug, Default)]
+pub struct WaitQueue {
+    queues: HashMap<String, VecDeque<Waiter>>,
+    granted: HashMap<RequestKey, (String, u64)>,
+    /// Source of waiter IDs
+    enqueued: u64,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the waiter to the back of its resource's queue, or refresh its
+    /// TTL if the same request is already queued. Returns its ID and its
+    /// position (1 = next in line).
+    pub fn enqueue(&mut self, mut waiter: Waiter) -> (String, usize) {
+        let queue = self.queues.entry(waiter.resource.key()).or_default();
+        if let Some(index) = queue.iter().position(|w| {
+            w.is_request(
+                &waiter.agent_id,
+                &waiter.session_id,
+                &waiter.resource,
+                waiter.predicate,
+            )
+        }) {
+            queue[index].ttl = waiter.ttl;
+            queue[index].expires_at = waiter.expires_at;
+            return (queue[index].id.clone(), index + 1);
+        }
+        self.enqueued += 1;
+        waiter.id = format!("wait_{}", self.enqueued);
+        let id = waiter.id.clone();
+        queue.push_back(waiter);
+        (id, queue.len())
+    }
+
+    /// Take the requests `filter` selects out of their queues, returning
+    /// them. Leases already granted to them are not affected.
+    pub fn cancel(&mut self, filter: &WaiterFilter) -> Vec<Waiter> {
+        let mut cancelled = Vec::new();
+        self.queues.retain(|_, queue| {
+            queue.retain(|w| {
+                if w.matches(filter) {
+                    cancelled.push(w.clone());
+                    false
+                } else {
+                    true
+                }
+            });
+            !queue.is_empty()
+        });
+        cancelled.sort_by(|a, b| a.enqueued_at.cmp(&b.enqueued_at).then(a.id.cmp(&b.id)));
+        cancelled
+    }
+
+    /// Take the request out of the queue. Returns whether it was queued.
+    pub fn remove(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: &ResourceRef,
+        predicate: Predicate,
+    ) -> bool {
+        let key = resource.key();
+        let Some(queue) = self.queues.get_mut(&key) else {
+            return false;
+        };
+        let before = queue.len();
+        queue.retain(|w| !w.is_request(agent_id, session_id, resource, predicate));
+        let removed = queue.len() != before;
+        if queue.is_empty() {
+            self.queues.remove(&key);
+        }
+        removed
+    }
+
+    /// The request's position in its resource's queue (1 = next in line)
+    pub fn position(
+        &self,
+        agent_id: &str,
+        session_id: &str,
+        resource: &ResourceRef,
+        predicate: Predicate,
+    ) -> Option<usize> {
+        self.queues
+            .get(&resource.key())?
+            .iter()
+            .position(|w| w.is_request(agent_id, session_id, resource, predicate))
+            .map(|index| index + 1)
+    }
+
+    /// The waiters queued ahead of the request, next in line first
+    pub fn ahead(
+        &self,
+        agent_id: &str,
+        session_id: &str,
+        resource: &ResourceRef,
+        predicate: Predicate,
+    ) -> Vec<&Waiter> {
+        self.queues
+            .get(&resource.key())
+            .map(|queue| {
+                queue
+                    .iter()
+                    .take_while(|w| !w.is_request(agent_id, session_id, resource, predicate))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Drop waiters whose TTL passed before they were granted, and grants
+    /// whose lease has expired unclaimed
+    pub fn expire(&mut self, now: u64) {
+        self.queues.retain(|_, queue| {
+            queue.retain(|w| w.expires_at >= now);
+            !queue.is_empty()
+        });
+        self.granted.retain(|_, (_, expires_at)| *expires_at >= now);
+    }
+
+    /// The waiter at the front of each queue, longest-waiting first
+    pub fn heads(&self) -> Vec<Waiter> {
+        let mut heads: Vec<Waiter> = self
+            .queues
+            .values()
+            .filter_map(|queue| queue.front().cloned())
+            .collect();
+        heads.sort_by(|a, b| {
+            a.enqueued_at
+                .cmp(&b.enqueued_at)
+                .then_with(|| a.resource.key().cmp(&b.resource.key()))
+        });
+        heads
+    }
+
+    /// Take the head of `waiter`'s queue off it, and remember the lease it
+    /// was granted for when it retries
+    pub fn granted(&mut self, waiter: &Waiter, lease: &Lease) {
+        self.remove(
+            &waiter.agent_id,
+            &waiter.session_id,
+            &waiter.resource,
+            waiter.predicate,
+        );
+        self.granted.insert(
+            request_key(
+                &waiter.agent_id,
+                &waiter.session_id,
+                &waiter.resource,
+                waiter.predicate,
+            ),
+            (lease.id.clone(), lease.expires_at),
+        );
+    }
+
+    /// The lease granted to this request while it waited, if it has not
+    /// been claimed yet
+    pub fn claim(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+        resource: &ResourceRef,
+        predicate: Predicate,
+    ) -> Option<String> {
+        self.granted
+            .remove(&request_key(agent_id, session_id, resource, predicate))
+            .map(|(lease_id, _)| lease_id)
+    }
+
+    /// Every waiter, by resource and then in queue order
+    pub fn waiters(&self) -> Vec<Waiter> {
+        let mut keys: Vec<&String> = self.queues.keys().collect();
+        keys.sort();
+        keys.into_iter()
+            .flat_map(|key| self.queues[key].iter().cloned())
+            .collect()
+    }
+}
+
+/// How long a request refused with Wait can expect to wait: until the last
+/// of `holders` runs out, then the full TTL of each waiter `ahead` of it
+/// whose `predicate` conflicts with its own (waiters that do not conflict
+/// are granted alongside it). Assumes no lease is renewed or released
+/// early.
+pub fn estimated_wait_ms(
+    engine: &ConflictEngine,
+    predicate: Predicate,
+    holders: &[Holder],
+    ahead: &[&Waiter],
+    now: u64,
+) -> u64 {
+    let held = holders
+        .iter()
+        .map(|holder| holder.expires_at.saturating_sub(now))
+        .max()
+        .unwrap_or(0);
+    ahead
+        .iter()
+        .filter(|w| engine.is_conflict_on(w.predicate, predicate, &w.resource.resource_type))
+        .fold(held, |eta, w| eta.saturating_add(w.ttl))
+}
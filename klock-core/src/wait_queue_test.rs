@@ -0,0 +1,251 @@
+#[cfg(test)]
+mod tests {
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::feed::AgentEvent;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{
+        Confidence, LeaseFailureReason, LeaseResult, LeaseState, Predicate, ResourceRef,
+        ResourceType, SPOTriple,
+    };
+    use crate::wait_queue::{WaitQueue, Waiter, WaiterFilter};
+    use std::sync::Arc;
+
+    fn file(path: &str) -> ResourceRef {
+        ResourceRef::new(ResourceType::File, path)
+    }
+
+    fn queue_position(result: &LeaseResult) -> Option<usize> {
+        match result {
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Wait,
+                queue_position,
+                ..
+            } => *queue_position,
+            _ => panic!("Expected Wait, got {:?}", result),
+        }
+    }
+
+    /// Queue position and estimated wait of a queued acquire
+    fn place(result: &LeaseResult) -> (Option<usize>, Option<u64>) {
+        match result {
+            LeaseResult::Failure {
+                reason: LeaseFailureReason::Wait,
+                queue_position,
+                wait_time,
+                ..
+            } => (*queue_position, *wait_time),
+            _ => panic!("Expected Wait, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_queue_order_and_expiry() {
+        let mut queue = WaitQueue::new();
+        let waiter = |agent: &str, path: &str, now: u64| {
+            Waiter::new(agent, "s1", file(path), Predicate::Mutates, 1000, now)
+        };
+        let (first, position) = queue.enqueue(waiter("a", "/x.ts", 10));
+        assert_eq!(position, 1);
+        assert_eq!(queue.enqueue(waiter("b", "/x.ts", 20)).1, 2);
+        assert_eq!(queue.enqueue(waiter("c", "/y.ts", 5)).1, 1);
+        // Retrying keeps the waiter's place and ID, and extends its wait
+        assert_eq!(queue.enqueue(waiter("a", "/x.ts", 500)), (first, 1));
+
+        let heads: Vec<String> = queue.heads().into_iter().map(|w| w.agent_id).collect();
+        assert_eq!(heads, vec!["c", "a"]);
+
+        assert!(queue.remove("a", "s1", &file("/x.ts"), Predicate::Mutates));
+        assert_eq!(
+            queue.position("b", "s1", &file("/x.ts"), Predicate::Mutates),
+            Some(1)
+        );
+
+        // c joined at 5 with a 1000ms TTL; b at 20
+        queue.expire(1010);
+        let waiting: Vec<String> = queue.waiters().into_iter().map(|w| w.agent_id).collect();
+        assert_eq!(waiting, vec!["b"]);
+    }
+
+    #[test]
+    fn test_release_grants_head_waiter() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("holder", 300);
+        client.register_agent("first", 200);
+        client.register_agent("second", 100);
+
+        let LeaseResult::Success { lease: held } =
+            client.acquire_lease("holder", "s1", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        clock.advance(1);
+        let result = client.acquire_lease("first", "s2", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(queue_position(&result), Some(1));
+        clock.advance(1);
+        let result = client.acquire_lease("second", "s3", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(queue_position(&result), Some(2));
+
+        // First come, first served, even though "second" is older
+        clock.advance(1);
+        assert!(client.release_lease(&held.id));
+        let waiting: Vec<String> = client
+            .lease_waiters()
+            .into_iter()
+            .map(|w| w.agent_id)
+            .collect();
+        assert_eq!(waiting, vec!["second"]);
+
+        let batch = client.poll_events("first", "s2");
+        let [AgentEvent::LeaseGranted { lease_id, .. }] = batch.events.as_slice() else {
+            panic!("Expected lease_granted, got {:?}", batch.events);
+        };
+        assert_eq!(
+            client.get_lease(lease_id).unwrap().state,
+            LeaseState::Active
+        );
+
+        // Retrying hands back the granted lease instead of a second one
+        clock.advance(1);
+        let LeaseResult::Success { lease } =
+            client.acquire_lease("first", "s2", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected success");
+        };
+        assert_eq!(&lease.id, lease_id);
+        assert_eq!(client.get_active_leases().len(), 1);
+    }
+
+    #[test]
+    fn test_eviction_grants_waiter() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("holder", 200);
+        client.register_agent("waiter", 100);
+
+        client.acquire_lease("holder", "s1", "FILE", "/a.ts", "MUTATES", 1000);
+        clock.advance(1);
+        let result = client.acquire_lease("waiter", "s2", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(queue_position(&result), Some(1));
+
+        clock.advance(2000);
+        assert_eq!(client.evict_expired(), 1);
+        let active = client.get_active_leases();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].agent_id, "waiter");
+        assert!(client.lease_waiters().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_pending_tells_holders() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("holder", 300);
+        client.register_agent("first", 200);
+        client.register_agent("second", 100);
+
+        client.acquire_lease("holder", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        let LeaseResult::Failure {
+            request_id: Some(request_id),
+            ..
+        } = client.acquire_lease("first", "s2", "FILE", "/a.ts", "MUTATES", 60_000)
+        else {
+            panic!("Expected a queued request");
+        };
+        client.acquire_lease("second", "s3", "FILE", "/a.ts", "MUTATES", 60_000);
+
+        clock.advance(5);
+        let cancelled = client.cancel_pending(&WaiterFilter::Id(request_id.clone()));
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].agent_id, "first");
+        assert!(
+            client
+                .cancel_pending(&WaiterFilter::Id(request_id.clone()))
+                .is_empty()
+        );
+
+        let batch = client.poll_events("holder", "s1");
+        assert_eq!(
+            batch.events,
+            vec![AgentEvent::WaiterCancelled {
+                request_id,
+                agent_id: "first".to_string(),
+                resource: file("/a.ts"),
+                predicate: Predicate::Mutates,
+                waiting: 1,
+                at: 1005,
+            }]
+        );
+
+        let cancelled = client.cancel_pending(&WaiterFilter::Resource {
+            agent_id: "second".to_string(),
+            resource: file("/a.ts"),
+        });
+        assert_eq!(cancelled.len(), 1);
+        assert!(client.lease_waiters().is_empty());
+    }
+
+    #[test]
+    fn test_ending_default_session_cancels_its_requests() {
+        let mut client = KlockClient::new();
+        client.register_agent("holder", 300);
+        client.register_agent("waiter", 100);
+        client.acquire_lease("holder", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+        let result = client.acquire_lease("waiter", "", "FILE", "/a.ts", "MUTATES", 60_000);
+        assert_eq!(queue_position(&result), Some(1));
+
+        assert!(client.end_default_session("waiter"));
+        assert!(client.lease_waiters().is_empty());
+        assert!(matches!(
+            client.poll_events("holder", "s1").events.as_slice(),
+            [AgentEvent::WaiterCancelled { waiting: 0, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_wait_estimates_count_holders_and_conflicting_waiters_ahead() {
+        let clock = Arc::new(ManualClock::new(1000));
+        let mut client = KlockClient::deterministic(1, clock.clone());
+        client.register_agent("holder", 300);
+        for (agent, priority) in [("writer", 200), ("reader1", 150), ("reader2", 100)] {
+            client.register_agent(agent, priority);
+        }
+        // Held until 61_000
+        client.acquire_lease("holder", "s1", "FILE", "/a.ts", "MUTATES", 60_000);
+
+        clock.advance(1);
+        let writer = client.acquire_lease("writer", "s2", "FILE", "/a.ts", "MUTATES", 30_000);
+        assert_eq!(place(&writer), (Some(1), Some(59_999)));
+
+        // Behind the writer, who may hold the file for its whole TTL
+        clock.advance(1);
+        let reader = client.acquire_lease("reader1", "s3", "FILE", "/a.ts", "CONSUMES", 5_000);
+        assert_eq!(place(&reader), (Some(2), Some(89_998)));
+
+        // Readers ahead are granted alongside
+        clock.advance(1);
+        let reader = client.acquire_lease("reader2", "s4", "FILE", "/a.ts", "CONSUMES", 5_000);
+        assert_eq!(place(&reader), (Some(3), Some(89_997)));
+
+        // Manifests are not queued, but learn when the leases in the way run out
+        let verdict = client.declare_intent(&IntentManifest {
+            session_id: "s5".to_string(),
+            agent_id: "writer".to_string(),
+            intents: vec![SPOTriple {
+                id: "i1".to_string(),
+                subject: "writer".to_string(),
+                predicate: Predicate::Mutates,
+                object: file("/a.ts"),
+                timestamp: 1003,
+                confidence: Confidence::High,
+                session_id: "s5".to_string(),
+                renamed_to: None,
+                expires_at: None,
+                long_lived: false,
+            }],
+        });
+        assert_eq!(verdict.status, KernelVerdictStatus::Wait);
+        assert_eq!(verdict.retry_after_ms, Some(59_997));
+    }
+}
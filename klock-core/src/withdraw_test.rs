@@ -0,0 +1,159 @@
+#[cfg(test)]
+mod tests {
+    use crate::activity::{ActivityKind, TimelineQuery};
+    use crate::client::KlockClient;
+    use crate::clock::ManualClock;
+    use crate::state::{IntentManifest, KernelVerdictStatus};
+    use crate::types::{Confidence, LeaseResult, Predicate, ResourceRef, ResourceType, SPOTriple};
+    use std::sync::Arc;
+
+    fn manifest(agent_id: &str, session_id: &str, paths: &[&str]) -> IntentManifest {
+        IntentManifest {
+            session_id: session_id.to_string(),
+            agent_id: agent_id.to_string(),
+            intents: paths
+                .iter()
+                .map(|&path| SPOTriple {
+                    id: format!("t_{}_{}{}", agent_id, session_id, path),
+                    subject: agent_id.to_string(),
+                    predicate: Predicate::Mutates,
+                    object: ResourceRef::new(ResourceType::File, path),
+                    timestamp: 1000,
+                    confidence: Confidence::High,
+                    session_id: session_id.to_string(),
+                    renamed_to: None,
+                    expires_at: None,
+                    long_lived: false,
+                })
+                .collect(),
+        }
+    }
+
+    fn client() -> KlockClient {
+        let mut client = KlockClient::deterministic(1, Arc::new(ManualClock::new(1000)));
+        client.register_agent("a", 100);
+        client.register_agent("b", 200);
+        for session_id in ["s1", "s2"] {
+            let verdict = client.declare_intent(&manifest("a", session_id, &["/a.ts", "/b.ts"]));
+            assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        }
+        client
+    }
+
+    fn conflicts(client: &KlockClient, path: &str) -> usize {
+        client
+            .summarize_intent(&manifest("b", "s9", &[path]))
+            .conflicts
+    }
+
+    #[test]
+    fn test_withdrawn_intents_block_nobody() {
+        let mut client = client();
+        assert_eq!(conflicts(&client, "/a.ts"), 2);
+
+        let intent = client.withdraw_intent("t_a_s1/a.ts").unwrap();
+        assert_eq!(intent.object.path, "/a.ts");
+        assert!(client.withdraw_intent("t_a_s1/a.ts").is_none());
+        assert_eq!(conflicts(&client, "/a.ts"), 1);
+        assert_eq!(conflicts(&client, "/b.ts"), 2);
+
+        let withdrawn = client.agent_timeline(
+            "a",
+            &TimelineQuery {
+                kind: Some(ActivityKind::IntentWithdrawn),
+                ..TimelineQuery::default()
+            },
+        );
+        assert_eq!(withdrawn.len(), 1);
+        assert_eq!(withdrawn[0].session_id, "s1");
+        assert_eq!(withdrawn[0].predicate, Some(Predicate::Mutates));
+    }
+
+    #[test]
+    fn test_withdrawing_a_session_leaves_other_sessions_and_leases() {
+        let mut client = client();
+        let acquisition = client.acquire_manifest(&manifest("b", "s3", &["/c.ts"]), 60_000);
+        assert!(acquisition.acquired());
+
+        let withdrawn: Vec<String> = client
+            .withdraw_session_intents("a", "s1")
+            .into_iter()
+            .map(|intent| intent.id)
+            .collect();
+        assert_eq!(withdrawn, ["t_a_s1/a.ts", "t_a_s1/b.ts"]);
+        assert!(client.withdraw_session_intents("a", "s1").is_empty());
+        assert_eq!(conflicts(&client, "/a.ts"), 1);
+
+        // The lease acquired for an intent outlives its withdrawal
+        assert_eq!(client.withdraw_session_intents("b", "s3").len(), 1);
+        assert_eq!(conflicts(&client, "/c.ts"), 1);
+        assert_eq!(client.get_active_leases().len(), 1);
+    }
+
+    #[test]
+    fn test_session_ids_are_only_withdrawn_for_their_agent() {
+        let mut client = client();
+        client.begin_session("a", "shared", 60_000).unwrap();
+        for (agent_id, path) in [("a", "/x.ts"), ("b", "/y.ts")] {
+            let verdict = client.declare_intent(&manifest(agent_id, "shared", &[path]));
+            assert_eq!(verdict.status, KernelVerdictStatus::Granted);
+        }
+
+        // Ending a's session leaves b's intents in a session of the same ID
+        let end = client.end_session("shared").unwrap();
+        assert_eq!(end.intents_withdrawn, 1);
+        assert_eq!(conflicts(&client, "/x.ts"), 0);
+        assert_eq!(conflicts(&client, "/y.ts"), 1);
+
+        assert!(client.withdraw_session_intents("a", "shared").is_empty());
+        assert_eq!(client.withdraw_session_intents("b", "shared").len(), 1);
+        assert_eq!(conflicts(&client, "/y.ts"), 0);
+    }
+
+    #[test]
+    fn test_releasing_every_covering_lease_releases_the_intent() {
+        let mut client = client();
+        let acquisition =
+            client.acquire_manifest(&manifest("a", "s3", &["/c.ts", "/lib/d.ts"]), 60_000);
+        assert!(acquisition.acquired());
+        let mut long_lived = manifest("a", "s4", &["/e.ts"]);
+        long_lived.intents[0].long_lived = true;
+        assert!(client.acquire_manifest(&long_lived, 60_000).acquired());
+        // A directory lease of the same session also covers /lib/d.ts
+        let directory = match client.acquire_lease("a", "s3", "FILE", "/lib/", "MUTATES", 60_000) {
+            LeaseResult::Success { lease } => lease,
+            other => panic!("Expected success, got {:?}", other),
+        };
+        assert_eq!(conflicts(&client, "/c.ts"), 2);
+
+        assert!(client.release_lease(&acquisition.leases[0].id));
+        assert!(client.release_lease(&acquisition.leases[1].id));
+        // The directory lease still covers one intent
+        assert_eq!(conflicts(&client, "/c.ts"), 0);
+        assert_eq!(conflicts(&client, "/lib/d.ts"), 2);
+        assert!(client.release_lease(&directory.id));
+        assert_eq!(conflicts(&client, "/lib/d.ts"), 0);
+
+        // Intents never leased, and long-lived ones, stay
+        assert_eq!(conflicts(&client, "/a.ts"), 2);
+        let long_lived_lease = client.get_active_leases()[0].id.clone();
+        assert!(client.release_lease(&long_lived_lease));
+        assert_eq!(conflicts(&client, "/e.ts"), 1);
+
+        let released: Vec<String> = client
+            .agent_timeline(
+                "a",
+                &TimelineQuery {
+                    kind: Some(ActivityKind::IntentWithdrawn),
+                    ..TimelineQuery::default()
+                },
+            )
+            .into_iter()
+            .map(|entry| {
+                assert_eq!(entry.outcome.as_deref(), Some("Released"));
+                entry.resources[0].path.clone()
+            })
+            .collect();
+        assert_eq!(released, ["/c.ts", "/lib/d.ts"]);
+    }
+}
@@ -0,0 +1,75 @@
+import test from 'node:test';
+import assert from 'node:assert';
+import { readdirSync, readFileSync } from 'node:fs';
+import { dirname, join } from 'node:path';
+import { fileURLToPath } from 'node:url';
+import klockModule from '../index.js';
+
+const { KlockClient } = klockModule;
+
+// Vectors generated from the Rust kernel (`klock conformance generate`)
+const vectorsDir = join(dirname(fileURLToPath(import.meta.url)), '..', '..', 'conformance', 'vectors');
+const vectors = readdirSync(vectorsDir)
+    .filter((file) => file.endsWith('.json'))
+    .sort()
+    .map((file) => JSON.parse(readFileSync(join(vectorsDir, file), 'utf8')));
+
+// Lease IDs are derived from the millisecond, so steps are spaced out the
+// way the kernel's own replay advances its clock between steps
+const pause = () => new Promise((resolve) => setTimeout(resolve, 2));
+
+async function replay(vector) {
+    const client = new KlockClient();
+    for (const [agentId, priority] of Object.entries(vector.agents ?? {})) {
+        // Agents outside the normal class are listed with their class
+        if (typeof priority === 'object') {
+            client.registerAgent(agentId, priority.timestamp, priority.class);
+        } else {
+            client.registerAgent(agentId, priority);
+        }
+    }
+
+    const leases = new Map();
+    const outcomes = [];
+    for (const [index, step] of vector.steps.entries()) {
+        if (step.op === 'acquire') {
+            const result = JSON.parse(
+                client.acquireLease(
+                    step.agent_id,
+                    step.session_id,
+                    step.resource_type,
+                    step.resource_path,
+                    step.predicate,
+                    step.ttl
+                )
+            );
+            if (result.success) {
+                leases.set(index, result.leaseId);
+                outcomes.push({ success: true });
+            } else {
+                outcomes.push({ success: false, reason: result.reason });
+            }
+        } else if (step.op === 'release') {
+            outcomes.push(
+                client.releaseLease(leases.get(step.step))
+                    ? { success: true }
+                    : { success: false, reason: 'LEASE_NOT_FOUND' }
+            );
+        }
+        await pause();
+    }
+    return outcomes;
+}
+
+test('klock-js conformance vectors', async (t) => {
+    for (const vector of vectors) {
+        // The bindings acquire and release; manifests are kernel-only for now
+        const supported = vector.steps.every((step) => step.op === 'acquire' || step.op === 'release');
+        await t.test(vector.name, { skip: !supported && 'needs intent declaration' }, async () => {
+            const outcomes = await replay(vector);
+            vector.steps.forEach((step, index) => {
+                assert.deepStrictEqual(outcomes[index], step.expect, `${vector.name}: step ${index}`);
+            });
+        });
+    }
+});
@@ -30,6 +30,27 @@ test('klock-js smoke test', async (t) => {
         assert.strictEqual(releaseResult, true, 'Lease release should succeed');
     });
 
+    await t.test('should release a batch only if its conditions hold', () => {
+        // One agent per lease: lease IDs are only unique per agent and millisecond
+        const ids = ['batch-a', 'batch-b'].map((agentId, i) => {
+            client.registerAgent(agentId, 300 + i);
+            const path = `/batch/${agentId}.ts`;
+            return JSON.parse(client.acquireLease(agentId, 'batch', 'FILE', path, 'MUTATES', 60000)).leaseId;
+        });
+        client.heartbeatLease(ids[0]);
+
+        const stale = JSON.stringify([{ condition: 'revision', leaseId: ids[0], revision: 0 }]);
+        const refused = JSON.parse(client.releaseManyChecked(ids, stale));
+        assert.strictEqual(refused.released, false);
+        assert.deepStrictEqual(refused.results.map((r) => r.status), ['condition_failed', 'aborted']);
+        assert.strictEqual(refused.results[0].code, 'K3005');
+
+        const fresh = JSON.stringify([{ condition: 'revision', leaseId: ids[0], revision: 1 }]);
+        const released = JSON.parse(client.releaseManyChecked(ids, fresh));
+        assert.strictEqual(released.released, true);
+        assert.throws(() => client.releaseManyChecked([ids[0], ids[0]]), /twice/);
+    });
+
     await t.test('should detect conflicts', () => {
         // Acquire a lease for agent-1
         client.acquireLease('agent-1', 's1', 'FILE', '/shared.ts', 'MUTATES', 60000);
@@ -133,4 +154,18 @@ test('klock-js smoke test', async (t) => {
             }
         }
     });
+
+    await t.test('should let a senior wound a junior under wound-wait', () => {
+        const woundWait = new KlockClient('wound-wait');
+        woundWait.registerAgent('senior', 100);
+        woundWait.registerAgent('junior', 200);
+
+        const junior = JSON.parse(woundWait.acquireLease('junior', 's2', 'FILE', '/ww.ts', 'MUTATES', 60000));
+        assert.strictEqual(junior.success, true);
+        const senior = JSON.parse(woundWait.acquireLease('senior', 's1', 'FILE', '/ww.ts', 'MUTATES', 60000));
+        assert.strictEqual(senior.success, true);
+        assert.strictEqual(woundWait.releaseLease(junior.leaseId), false);
+
+        assert.throws(() => new KlockClient('lottery'), /Unknown scheduler/);
+    });
 });
@@ -4,18 +4,65 @@
 /* auto-generated by NAPI-RS */
 
 export declare class KlockClient {
-  constructor()
-  /** Register an agent with a priority (lower = older = higher priority). */
-  registerAgent(agentId: string, priority: number): void
+  /**
+   * Create a client resolving conflicts with the named scheduler:
+   * "wait-die" (the default), "wound-wait" or "fifo".
+   */
+  constructor(scheduler?: string | undefined | null)
+  /**
+   * Register an agent with a priority (lower = older = higher priority)
+   * in a priority class: "critical", "normal" (the default) or
+   * "background". The class always dominates the priority.
+   */
+  registerAgent(agentId: string, priority: number, class?: string | undefined | null): void
   /**
    * Acquire a lease on a resource.
-   * Returns a JSON string with the result.
+   * Returns a JSON string with the result. A null `sessionId` uses the
+   * agent's default session.
    */
-  acquireLease(agentId: string, sessionId: string, resourceType: string, resourcePath: string, predicate: string, ttl: number): string
+  acquireLease(agentId: string, sessionId: string | undefined | null, resourceType: string, resourcePath: string, predicate: string, ttl: number): string
   /** Release a lease by ID. */
   releaseLease(leaseId: string): boolean
+  /**
+   * Release leases together, only if each is held and every condition
+   * holds. `conditions` is a JSON list of `{ condition: "revision",
+   * leaseId, revision }` and `{ condition: "no_conflicting_lease",
+   * leaseId }` objects. Returns a JSON string: `released`, and `results`
+   * with each lease's `status`.
+   */
+  releaseManyChecked(leaseIds: Array<string>, conditions?: string | undefined | null): string
+  /**
+   * Renew a lease. Returns a JSON string with the lease as it stands
+   * afterwards; `clientTime` (ms since the epoch) reports clock drift.
+   */
+  heartbeatLease(leaseId: string, clientTime?: number | undefined | null): string
   /** Get count of active leases. */
   activeLeaseCount(): number
   /** Evict expired leases. Returns number evicted. */
   evictExpired(): number
+  /**
+   * Create a barrier that trips once `parties` distinct agents arrive.
+   * Returns a JSON string with the barrier status.
+   */
+  createBarrier(name: string, parties: number): string
+  /**
+   * Record that an agent reached the barrier.
+   * Returns a JSON string with the barrier status; `tripped` is true once
+   * every party has arrived.
+   */
+  arrive(name: string, agentId: string): string
+  /**
+   * Take one permit of a semaphore, declaring it with `permits` total
+   * permits on first use. Returns a JSON string with the result.
+   */
+  acquireSemaphore(agentId: string, name: string, permits: number, ttl: number): string
+  /** Return a semaphore permit before its TTL elapses. */
+  releaseSemaphore(permitId: string): boolean
+  /** Get a barrier's status as a JSON string, or null if it does not exist. */
+  barrierStatus(name: string): string | null
 }
+/**
+ * The reason-code catalog as a JSON string: a list of objects with
+ * `code`, `name`, `summary` and `remedy`, in code order.
+ */
+export declare function codes(): string
@@ -311,7 +311,7 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { KlockClient } = nativeBinding
+const { KlockClient, codes } = nativeBinding
 
 class KlockHttpClient {
   constructor(options = {}) {
@@ -326,10 +326,11 @@ class KlockHttpClient {
     this.autoStartedPid = null
   }
 
-  async registerAgent(agentId, priority) {
+  async registerAgent(agentId, priority, priorityClass = 'normal') {
     const response = await this.#request('POST', '/agents', {
       agent_id: agentId,
       priority,
+      class: priorityClass,
     })
 
     if (!response.success) {
@@ -362,6 +363,8 @@ class KlockHttpClient {
       success: false,
       reason: response.reason || 'CONFLICT',
       waitTime: response.wait_time ?? 1000,
+      queuePosition: response.queue_position ?? null,
+      requestId: response.request_id ?? null,
     }
   }
 
@@ -370,6 +373,28 @@ class KlockHttpClient {
     return Boolean(response.success)
   }
 
+  async releaseManyChecked(leaseIds, conditions = []) {
+    const response = await this.#request('POST', '/leases/release', {
+      lease_ids: leaseIds,
+      conditions: conditions.map(({ condition, leaseId, revision }) => ({
+        condition,
+        lease_id: leaseId,
+        revision,
+      })),
+    })
+    if (!response.data) {
+      throw new Error(response.error || 'Failed to release Klock leases')
+    }
+
+    return {
+      released: response.data.released,
+      results: response.data.results.map(({ lease_id, ...result }) => ({
+        leaseId: lease_id,
+        ...result,
+      })),
+    }
+  }
+
   async heartbeatLease(leaseId) {
     const response = await this.#request('POST', `/leases/${leaseId}/heartbeat`)
     return Boolean(response.success)
@@ -599,3 +624,4 @@ function isLocalBaseUrl(baseUrl) {
 
 module.exports.KlockClient = KlockClient
 module.exports.KlockHttpClient = KlockHttpClient
+module.exports.codes = codes
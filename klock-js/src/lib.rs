@@ -1,9 +1,15 @@
 #![deny(clippy::all)]
 
 use napi_derive::napi;
+use serde::Deserialize;
 
+use klock_core::barrier::BarrierStatus;
 use klock_core::client::KlockClient as RustClient;
-use klock_core::types::{LeaseFailureReason, LeaseResult as RustLeaseResult};
+use klock_core::registry::PriorityClass;
+use klock_core::release::ReleaseCondition;
+use klock_core::scheduler::scheduler_by_name;
+use klock_core::semaphore::SemaphoreResult;
+use klock_core::types::LeaseResult as RustLeaseResult;
 
 // ─── JS-facing KlockClient ─────────────────────────────────────────────────
 
@@ -12,28 +18,55 @@ pub struct KlockClient {
     inner: RustClient,
 }
 
-#[napi]
-impl KlockClient {
-    #[napi(constructor)]
-    pub fn new() -> Self {
+impl Default for KlockClient {
+    fn default() -> Self {
         Self {
             inner: RustClient::new(),
         }
     }
+}
 
-    /// Register an agent with a priority (lower = older = higher priority).
+#[napi]
+impl KlockClient {
+    /// Create a client resolving conflicts with the named scheduler:
+    /// "wait-die" (the default), "wound-wait" or "fifo".
+    #[napi(constructor)]
+    pub fn new(scheduler: Option<String>) -> napi::Result<Self> {
+        let scheduler = scheduler_by_name(scheduler.as_deref().unwrap_or("wait-die"))
+            .map_err(napi::Error::from_reason)?;
+        Ok(Self {
+            inner: RustClient::new().with_scheduler(scheduler),
+        })
+    }
+
+    /// Register an agent with a priority (lower = older = higher priority)
+    /// in a priority class: "critical", "normal" (the default) or
+    /// "background". The class always dominates the priority.
     #[napi]
-    pub fn register_agent(&mut self, agent_id: String, priority: f64) {
-        self.inner.register_agent(&agent_id, priority as u64);
+    pub fn register_agent(
+        &mut self,
+        agent_id: String,
+        priority: f64,
+        class: Option<String>,
+    ) -> napi::Result<()> {
+        let class: PriorityClass = class
+            .as_deref()
+            .unwrap_or("normal")
+            .parse()
+            .map_err(napi::Error::from_reason)?;
+        self.inner
+            .register_agent_in_class(&agent_id, class, priority as u64);
+        Ok(())
     }
 
     /// Acquire a lease on a resource.
-    /// Returns a JSON string with the result.
+    /// Returns a JSON string with the result. A null `sessionId` uses the
+    /// agent's default session.
     #[napi]
     pub fn acquire_lease(
         &mut self,
         agent_id: String,
-        session_id: String,
+        session_id: Option<String>,
         resource_type: String,
         resource_path: String,
         predicate: String,
@@ -41,7 +74,7 @@ impl KlockClient {
     ) -> String {
         let result = self.inner.acquire_lease(
             &agent_id,
-            &session_id,
+            session_id.as_deref().unwrap_or_default(),
             &resource_type,
             &resource_path,
             &predicate,
@@ -53,24 +86,26 @@ impl KlockClient {
                 "success": true,
                 "leaseId": lease.id,
                 "agentId": lease.agent_id,
+                "sessionId": lease.session_id,
                 "resource": format!("{}:{}", resource_type, resource_path),
                 "expiresAt": lease.expires_at,
             })
             .to_string(),
             RustLeaseResult::Failure {
-                reason, wait_time, ..
+                reason,
+                wait_time,
+                queue_position,
+                request_id,
+                ..
             } => {
-                let reason_str = match reason {
-                    LeaseFailureReason::Wait => "WAIT",
-                    LeaseFailureReason::Die => "DIE",
-                    LeaseFailureReason::Conflict => "CONFLICT",
-                    LeaseFailureReason::ResourceLocked => "RESOURCE_LOCKED",
-                    LeaseFailureReason::SessionExpired => "SESSION_EXPIRED",
-                };
+                let reason_str = reason.as_str();
                 serde_json::json!({
                     "success": false,
                     "reason": reason_str,
+                    "code": reason.code(),
                     "waitTime": wait_time,
+                    "queuePosition": queue_position,
+                    "requestId": request_id,
                 })
                 .to_string()
             }
@@ -83,6 +118,75 @@ impl KlockClient {
         self.inner.release_lease(&lease_id)
     }
 
+    /// Release leases together, only if each is held and every condition
+    /// holds. `conditions` is a JSON list of `{ condition: "revision",
+    /// leaseId, revision }` and `{ condition: "no_conflicting_lease",
+    /// leaseId }` objects. Returns a JSON string: `released`, and `results`
+    /// with each lease's `status`.
+    #[napi]
+    pub fn release_many_checked(
+        &mut self,
+        lease_ids: Vec<String>,
+        conditions: Option<String>,
+    ) -> napi::Result<String> {
+        let conditions: Vec<JsReleaseCondition> = match conditions {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| napi::Error::from_reason(format!("Invalid conditions: {}", e)))?,
+            None => Vec::new(),
+        };
+        let conditions: Vec<ReleaseCondition> = conditions
+            .into_iter()
+            .map(JsReleaseCondition::into_condition)
+            .collect::<Result<_, _>>()
+            .map_err(napi::Error::from_reason)?;
+        let batch = self
+            .inner
+            .release_many_checked(&lease_ids, &conditions)
+            .map_err(napi::Error::from_reason)?;
+        let results: Vec<_> = batch
+            .results
+            .iter()
+            .map(|result| {
+                let mut json = serde_json::to_value(&result.status).unwrap_or_default();
+                json["leaseId"] = result.lease_id.clone().into();
+                json["code"] = serde_json::json!(result.status.code());
+                json
+            })
+            .collect();
+        Ok(serde_json::json!({
+            "released": batch.released,
+            "results": results,
+        })
+        .to_string())
+    }
+
+    /// Renew a lease. Returns a JSON string with the lease as it stands
+    /// afterwards; `clientTime` (ms since the epoch) reports clock drift.
+    #[napi]
+    pub fn heartbeat_lease(
+        &mut self,
+        lease_id: String,
+        client_time: Option<f64>,
+    ) -> napi::Result<String> {
+        let report = self
+            .inner
+            .heartbeat_lease_report(&lease_id, None, client_time.map(|t| t as u64))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(serde_json::json!({
+            "leaseId": report.lease_id,
+            "renewed": report.renewed,
+            "state": report.state,
+            "remainingTtl": report.remaining_ttl,
+            "expiresAt": report.expires_at,
+            "renewals": report.renewals,
+            "revision": report.revision,
+            "revokedReason": report.revoked_reason,
+            "clockDriftMs": report.clock_drift_ms,
+            "driftWarning": report.drift_warning,
+        })
+        .to_string())
+    }
+
     /// Get count of active leases.
     #[napi]
     pub fn active_lease_count(&self) -> u32 {
@@ -94,4 +198,118 @@ impl KlockClient {
     pub fn evict_expired(&mut self) -> u32 {
         self.inner.evict_expired() as u32
     }
+
+    /// Create a barrier that trips once `parties` distinct agents arrive.
+    /// Returns a JSON string with the barrier status.
+    #[napi]
+    pub fn create_barrier(&mut self, name: String, parties: u32) -> napi::Result<String> {
+        self.inner
+            .create_barrier(&name, parties as usize)
+            .map(|status| barrier_json(&status))
+            .map_err(napi::Error::from_reason)
+    }
+
+    /// Record that an agent reached the barrier.
+    /// Returns a JSON string with the barrier status; `tripped` is true once
+    /// every party has arrived.
+    #[napi]
+    pub fn arrive(&mut self, name: String, agent_id: String) -> napi::Result<String> {
+        self.inner
+            .arrive(&name, &agent_id)
+            .map(|status| barrier_json(&status))
+            .map_err(napi::Error::from_reason)
+    }
+
+    /// Take one permit of a semaphore, declaring it with `permits` total
+    /// permits on first use. Returns a JSON string with the result.
+    #[napi]
+    pub fn acquire_semaphore(
+        &mut self,
+        agent_id: String,
+        name: String,
+        permits: u32,
+        ttl: f64,
+    ) -> napi::Result<String> {
+        let result = self
+            .inner
+            .acquire_semaphore(&agent_id, &name, permits as usize, ttl as u64)
+            .map_err(napi::Error::from_reason)?;
+        Ok(match result {
+            SemaphoreResult::Success { permit } => serde_json::json!({
+                "success": true,
+                "permitId": permit.id,
+                "agentId": permit.agent_id,
+                "expiresAt": permit.expires_at,
+            }),
+            SemaphoreResult::Failure { reason, wait_time } => serde_json::json!({
+                "success": false,
+                "reason": reason.as_str(),
+                "code": reason.code(),
+                "waitTime": wait_time,
+            }),
+        }
+        .to_string())
+    }
+
+    /// Return a semaphore permit before its TTL elapses.
+    #[napi]
+    pub fn release_semaphore(&mut self, permit_id: String) -> bool {
+        self.inner.release_semaphore(&permit_id)
+    }
+
+    /// Get a barrier's status as a JSON string, or null if it does not exist.
+    #[napi]
+    pub fn barrier_status(&self, name: String) -> Option<String> {
+        self.inner
+            .barrier_status(&name)
+            .map(|status| barrier_json(&status))
+    }
+}
+
+/// A `ReleaseCondition` as JS passes it
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsReleaseCondition {
+    condition: String,
+    lease_id: String,
+    revision: Option<u64>,
+}
+
+impl JsReleaseCondition {
+    fn into_condition(self) -> Result<ReleaseCondition, String> {
+        match (self.condition.as_str(), self.revision) {
+            ("revision", Some(revision)) => Ok(ReleaseCondition::Revision {
+                lease_id: self.lease_id,
+                revision,
+            }),
+            ("revision", None) => Err("A revision condition needs a revision".to_string()),
+            ("no_conflicting_lease", _) => Ok(ReleaseCondition::NoConflictingLease {
+                lease_id: self.lease_id,
+            }),
+            (other, _) => Err(format!(
+                "Unknown condition '{}': use revision or no_conflicting_lease",
+                other
+            )),
+        }
+    }
+}
+
+fn barrier_json(status: &BarrierStatus) -> String {
+    serde_json::json!({
+        "name": status.name,
+        "parties": status.parties,
+        "arrived": status.arrived,
+        "waitingFor": status.waiting_for,
+        "tripped": status.tripped,
+        "createdAt": status.created_at,
+        "trippedAt": status.tripped_at,
+    })
+    .to_string()
+}
+
+/// The reason-code catalog as a JSON string: a list of objects with
+/// `code`, `name`, `summary` and `remedy`, in code order.
+#[napi]
+pub fn codes() -> String {
+    serde_json::to_string(&klock_core::codes::codes()).unwrap_or_else(|_| "[]".to_string())
 }
@@ -2,18 +2,64 @@
 
 from typing import Optional
 
+def codes() -> list[dict[str, str]]:
+    """The reason-code catalog, in code order.
+
+    Each entry has 'code' (e.g. "K1001"), 'name' (e.g. "WAIT_SENIOR"),
+    'summary' and 'remedy'. Refusals carry the code in their 'code' field,
+    so callers can branch on it instead of parsing messages.
+    """
+    ...
+
 class KlockClient:
     """The Klock coordination client.
     
     Manages agent registration, lease acquisition, and conflict resolution
     through a Rust-powered coordination kernel.
+
+    Store operations release the GIL, so one client can be shared between
+    threads. Embedded state is private to the process; use `connect` to
+    coordinate several processes through a Klock server.
     """
 
-    def __init__(self) -> None:
-        """Create a new KlockClient with an empty in-memory store."""
+    def __init__(self, scheduler: str = "wait-die") -> None:
+        """Create a new KlockClient with an empty in-memory store.
+
+        Args:
+            scheduler: Conflict resolution policy, "wait-die" (seniors wait,
+                juniors die), "wound-wait" (seniors revoke junior holders,
+                juniors wait) or "fifo" (everyone waits for the holder).
+
+        Raises:
+            RuntimeError: If the scheduler name is unknown.
+        """
         ...
 
-    def register_agent(self, agent_id: str, priority: int) -> None:
+    @staticmethod
+    def connect(
+        url: str,
+        api_key: Optional[str] = None,
+        timeout_ms: int = 5000,
+    ) -> "KlockClient":
+        """Connect to a running `klock serve` instance instead of an embedded kernel.
+
+        Remote clients are picklable, so they can be passed to
+        `multiprocessing` workers. A server is never auto-started.
+
+        Args:
+            url: Base URL of the server (e.g., "http://localhost:3100").
+            api_key: Bearer token if the server requires one.
+            timeout_ms: Per-request timeout in milliseconds.
+        """
+        ...
+
+    def is_remote(self) -> bool:
+        """True when created via `connect`."""
+        ...
+
+    def register_agent(
+        self, agent_id: str, priority: int, priority_class: str = "normal"
+    ) -> None:
         """Register an agent with a priority.
         
         Lower priority values = older = higher precedence in Wait-Die scheduling.
@@ -21,13 +67,18 @@ class KlockClient:
         Args:
             agent_id: Unique identifier for the agent.
             priority: Timestamp-based priority (lower = older = higher priority).
+            priority_class: 'critical', 'normal' or 'background'. The class
+                always dominates: priority only orders agents within a class.
+        
+        Raises:
+            ValueError: If the class is unknown.
         """
         ...
 
     def acquire_lease(
         self,
         agent_id: str,
-        session_id: str,
+        session_id: str | None,
         resource_type: str,
         resource_path: str,
         predicate: str,
@@ -37,17 +88,27 @@ class KlockClient:
         
         Args:
             agent_id: ID of the requesting agent.
-            session_id: Session identifier (same agent+session = reentrant).
+            session_id: Session identifier (same agent+session = reentrant), or
+                None for the agent's default session.
             resource_type: One of: FILE, SYMBOL, API_ENDPOINT, DATABASE_TABLE, CONFIG_KEY.
             resource_path: Path to the resource (e.g., "/src/auth.ts").
-            predicate: One of: PROVIDES, CONSUMES, MUTATES, DELETES, DEPENDS_ON, RENAMES.
+            predicate: One of: PROVIDES, CONSUMES, MUTATES, DELETES, DEPENDS_ON, RENAMES, EXCLUDES.
             ttl: Time-to-live in milliseconds.
         
         Returns:
-            On success: {"success": True, "lease_id": str, "agent_id": str, "resource": str, "expires_at": int}
-            On failure: {"success": False, "reason": str, "wait_time": Optional[int]}
+            On success: {"success": True, "lease_id": str, "agent_id": str, "session_id": str, "resource": str, "expires_at": int}
+            On failure: {"success": False, "reason": str, "code": str, "wait_time": Optional[int],
+                         "queue_position": Optional[int], "request_id": Optional[str]}
             
-            Reason values: "DIE", "WAIT", "CONFLICT", "RESOURCE_LOCKED", "SESSION_EXPIRED"
+            Reason values: "DIE", "WAIT", "CONFLICT", "RESOURCE_LOCKED", "SESSION_EXPIRED",
+            "RESERVATION_EXPIRED", "AGENT_QUARANTINED", "TOO_MANY_RETRIES", "QUARANTINED",
+            "MAINTENANCE_WINDOW".
+            'code' is the matching catalog code (e.g. "K1002"); see `codes()`.
+            After a WAIT the request is queued for the resource, and
+            'queue_position' is its place in line (1 = next); it is granted
+            automatically when the resource frees up, and retrying returns
+            that lease. 'request_id' identifies the queued request;
+            `DELETE /queue/{request_id}` on the server withdraws it.
         """
         ...
 
@@ -62,6 +123,51 @@ class KlockClient:
         """
         ...
 
+    def heartbeat_lease(
+        self, lease_id: str, client_time: Optional[int] = None
+    ) -> dict[str, object]:
+        """Renew a lease.
+        
+        Args:
+            lease_id: The ID of the lease to renew.
+            client_time: This process's clock in ms since the epoch; if given,
+                the result reports how far it is from the server's.
+        
+        Returns:
+            The lease as it stands afterwards: 'renewed', 'state' (None if the
+            lease is unknown), 'remaining_ttl', 'expires_at', 'renewals',
+            'revision', 'revoked_reason', 'clock_drift_ms' and
+            'drift_warning', set once the drift reaches a second.
+        """
+        ...
+
+    def release_many_checked(
+        self,
+        lease_ids: list[str],
+        conditions: Optional[list[dict[str, object]]] = None,
+    ) -> dict[str, object]:
+        """Release a batch of leases all at once, or none of them.
+        
+        Args:
+            lease_ids: The leases to release.
+            conditions: Post-conditions that must all hold, each a dict with
+                'condition' and 'lease_id': {'condition': 'revision',
+                'revision': n} holds while the lease is at revision n, and
+                {'condition': 'no_conflicting_lease'} while no other session
+                has acquired a conflicting lease since it was acquired.
+        
+        Returns:
+            A dict with 'released' and 'results': per lease, 'lease_id',
+            'status' ('released', 'not_found', 'not_held', 'condition_failed'
+            or 'aborted'), 'state' or 'reason' where they apply, and 'code'
+            for a lease that held the batch back.
+        
+        Raises:
+            ValueError: If a lease is listed twice, or a condition is on a
+                lease outside the batch.
+        """
+        ...
+
     def active_lease_count(self) -> int:
         """Get the count of currently active leases."""
         ...
@@ -74,6 +180,72 @@ class KlockClient:
         """
         ...
 
+    def acquire_semaphore(
+        self, agent_id: str, name: str, permits: int, ttl: int
+    ) -> dict[str, object]:
+        """Take one permit of a named counting semaphore.
+        
+        The first acquire declares the semaphore with `permits` total permits;
+        later acquires must pass the same count while it has holders. Permits
+        not released are reclaimed after `ttl` milliseconds.
+        
+        Returns:
+            A dict with 'success' and either 'permit_id'/'expires_at' or
+            'reason' ("WAIT", "DIE", "AGENT_QUARANTINED"), 'code' and
+            'wait_time'.
+        
+        Raises:
+            RuntimeError: If the arguments are invalid or the permit count
+                does not match the declared one.
+        """
+        ...
+
+    def release_semaphore(self, permit_id: str) -> bool:
+        """Return a semaphore permit before its TTL elapses."""
+        ...
+
+    def semaphore_status(self, name: str) -> Optional[dict[str, object]]:
+        """Get 'permits', 'available' and 'holders' of a semaphore, or None if
+        no permits are held."""
+        ...
+
+    def create_barrier(self, name: str, parties: int) -> dict[str, object]:
+        """Create a barrier that trips once `parties` distinct agents arrive.
+        
+        Returns:
+            The barrier status: 'name', 'parties', 'arrived', 'waiting_for',
+            'tripped', 'created_at', 'tripped_at'.
+        
+        Raises:
+            RuntimeError: If the name is taken or `parties` is 0.
+        """
+        ...
+
+    def arrive(self, name: str, agent_id: str) -> dict[str, object]:
+        """Record that an agent reached the barrier.
+        
+        Arriving twice, or after the barrier tripped, changes nothing.
+        
+        Returns:
+            The barrier status; 'tripped' is True once every party has arrived.
+        
+        Raises:
+            RuntimeError: If the barrier does not exist.
+        """
+        ...
+
+    def barrier_status(self, name: str) -> Optional[dict[str, object]]:
+        """Get a barrier's status, or None if it does not exist."""
+        ...
+
+    def wait_barrier(self, name: str, timeout_ms: int = 30000) -> dict[str, object]:
+        """Block until the barrier trips or `timeout_ms` elapses.
+        
+        Returns:
+            The barrier status; check 'tripped' to tell a trip from a timeout.
+        """
+        ...
+
 
 class KlockHttpClient:
     """HTTP client for a local or remote Klock coordination server."""
@@ -89,7 +261,9 @@ class KlockHttpClient:
     ) -> None:
         ...
 
-    def register_agent(self, agent_id: str, priority: int) -> None:
+    def register_agent(
+        self, agent_id: str, priority: int, priority_class: str = "normal"
+    ) -> None:
         ...
 
     def auto_start_enabled(self) -> bool:
@@ -104,7 +278,7 @@ class KlockHttpClient:
     def acquire_lease(
         self,
         agent_id: str,
-        session_id: str,
+        session_id: str | None,
         resource_type: str,
         resource_path: str,
         predicate: str,
@@ -120,3 +294,61 @@ class KlockHttpClient:
 
     def list_leases(self) -> list[dict[str, object]]:
         ...
+
+class KlockHybridClient:
+    """Client that grants low-risk leases locally and reconciles with a server.
+
+    Leases are decided against a replica of the server's leases, streamed
+    from its firehose. Optimistic predicates (CONSUMES by default) that
+    conflict with nothing known locally are granted at once as provisional
+    leases; a background thread confirms them with the server. Grants the
+    server refuses come back from `poll_events` as `lease_revoked` events.
+    """
+
+    def __init__(
+        self,
+        url: str,
+        api_key: Optional[str] = None,
+        timeout_ms: int = 5000,
+        sync_interval_ms: int = 100,
+        optimistic: Optional[list[str]] = None,
+    ) -> None:
+        """Seed the replica from the server at `url` and start reconciling.
+
+        Raises:
+            RuntimeError: If the server is unreachable or its firehose is
+                disabled.
+        """
+        ...
+
+    def acquire_lease(
+        self,
+        agent_id: str,
+        session_id: str | None,
+        resource_type: str,
+        resource_path: str,
+        predicate: str,
+        ttl: int,
+    ) -> dict[str, object]:
+        """Acquire a lease; local grants carry 'provisional': True."""
+        ...
+
+    def release_lease(self, lease_id: str) -> bool:
+        ...
+
+    def sync(self) -> None:
+        """Stream the firehose and reconcile pending grants now."""
+        ...
+
+    def is_provisional(self, lease_id: str) -> bool:
+        ...
+
+    def server_lease_id(self, lease_id: str) -> Optional[str]:
+        ...
+
+    def poll_events(self) -> list[dict[str, object]]:
+        ...
+
+    def close(self) -> None:
+        """Stop background reconciliation."""
+        ...
@@ -1,25 +1,74 @@
+#![allow(clippy::too_many_arguments)]
+
 use std::process::{Command, Stdio};
-use std::sync::Mutex;
-use std::thread::sleep;
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use pyo3::types::{PyDict, PyList, PyTuple};
 use serde_json::{json, Value};
 
-use ::klock_core::client::KlockClient as RustClient;
-use ::klock_core::types::{LeaseFailureReason, LeaseResult as RustLeaseResult};
+use ::klock_core::barrier::BarrierStatus;
+use ::klock_core::client::{parse_predicate, parse_resource_type, KlockClient as RustClient};
+use ::klock_core::codes::ReasonCode;
+use ::klock_core::firehose::FirehosePage;
+use ::klock_core::hybrid::{HybridDecision, HybridPolicy, LocalReplica};
+use ::klock_core::registry::PriorityClass;
+use ::klock_core::release::{BatchRelease, ReleaseCondition, ReleaseStatus};
+use ::klock_core::scheduler::scheduler_by_name;
+use ::klock_core::semaphore::{SemaphoreResult, SemaphoreStatus};
+use ::klock_core::state::StateProjection;
+use ::klock_core::types::{
+    HeartbeatReport, LeaseResult as RustLeaseResult, Predicate, ResourceRef,
+};
 
 /// The Klock coordination client for Python.
 /// Manages agent registration, lease acquisition, and conflict resolution.
-#[pyclass(unsendable)]
+///
+/// Store operations run with the GIL released, so one client can be shared
+/// by many Python threads. For multiple processes, connect every process to
+/// the same server with `KlockClient.connect`.
+#[pyclass(module = "klock")]
 pub struct KlockClient {
-    inner: RustClient,
+    backend: Backend,
+}
+
+enum Backend {
+    /// In-process kernel; state is private to this process
+    Embedded(Box<Mutex<RustClient>>),
+    /// A `klock serve` instance shared by every connected process
+    Remote(KlockHttpClient),
+}
+
+impl Default for KlockClient {
+    fn default() -> Self {
+        Self {
+            backend: Backend::Embedded(Box::new(Mutex::new(RustClient::new()))),
+        }
+    }
+}
+
+/// Client that grants low-risk leases locally, against a replica of a
+/// server's leases streamed from its firehose, and confirms them with the
+/// server in the background. Refused grants surface as `lease_revoked`
+/// events from `poll_events`.
+#[pyclass(module = "klock")]
+pub struct KlockHybridClient {
+    shared: Arc<HybridShared>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct HybridShared {
+    http: KlockHttpClient,
+    replica: Mutex<LocalReplica>,
+    stop: AtomicBool,
 }
 
 /// HTTP client for talking to a local or remote Klock server.
-#[pyclass]
+#[pyclass(module = "klock")]
 pub struct KlockHttpClient {
     base_url: String,
     api_key: Option<String>,
@@ -34,56 +83,388 @@ pub struct KlockHttpClient {
 
 #[pymethods]
 impl KlockClient {
-    /// Create a new embedded KlockClient.
+    /// Create a new embedded KlockClient, resolving conflicts with the
+    /// named scheduler ("wait-die" by default, "wound-wait" or "fifo").
     #[new]
-    pub fn new() -> Self {
+    #[pyo3(signature = (scheduler = "wait-die"))]
+    pub fn new(scheduler: &str) -> PyResult<Self> {
+        let scheduler = scheduler_by_name(scheduler).map_err(PyRuntimeError::new_err)?;
+        Ok(Self {
+            backend: Backend::Embedded(Box::new(Mutex::new(
+                RustClient::new().with_scheduler(scheduler),
+            ))),
+        })
+    }
+
+    /// Connect to a running Klock server instead of an embedded kernel.
+    /// Safe to use from multiple processes, and picklable so it can be
+    /// handed to `multiprocessing` workers. Never auto-starts a server.
+    #[staticmethod]
+    #[pyo3(signature = (url, api_key = None, timeout_ms = 5000))]
+    pub fn connect(url: String, api_key: Option<String>, timeout_ms: u64) -> Self {
         Self {
-            inner: RustClient::new(),
+            backend: Backend::Remote(KlockHttpClient::new(
+                url, api_key, timeout_ms, false, 0, None,
+            )),
         }
     }
 
-    /// Register an agent with a priority (lower = older = higher priority).
-    pub fn register_agent(&mut self, agent_id: &str, priority: u64) {
-        self.inner.register_agent(agent_id, priority);
+    /// Returns true when connected to a server via `connect`.
+    pub fn is_remote(&self) -> bool {
+        matches!(self.backend, Backend::Remote(_))
+    }
+
+    /// Register an agent with a priority (lower = older = higher priority)
+    /// in a priority class: 'critical', 'normal' (the default) or
+    /// 'background'. The class always dominates the priority.
+    #[pyo3(signature = (agent_id, priority, priority_class = "normal"))]
+    pub fn register_agent(
+        &self,
+        py: Python<'_>,
+        agent_id: &str,
+        priority: u64,
+        priority_class: &str,
+    ) -> PyResult<()> {
+        let class: PriorityClass = priority_class.parse().map_err(PyValueError::new_err)?;
+        match &self.backend {
+            Backend::Embedded(inner) => {
+                py.allow_threads(|| lock(inner).register_agent_in_class(agent_id, class, priority));
+                Ok(())
+            }
+            Backend::Remote(http) => http.register_agent(py, agent_id, priority, class.as_str()),
+        }
     }
 
     /// Acquire a lease on a resource.
-    /// Returns a dict with 'success', 'lease_id', 'reason', and 'wait_time'.
+    /// Returns a dict with 'success', 'lease_id', 'reason', 'wait_time' and,
+    /// after a WAIT, 'queue_position' and 'request_id'. A `session_id` of None uses the agent's default session.
     pub fn acquire_lease<'py>(
-        &mut self,
+        &self,
         py: Python<'py>,
         agent_id: &str,
-        session_id: &str,
+        session_id: Option<&str>,
         resource_type: &str,
         resource_path: &str,
         predicate: &str,
         ttl: u64,
     ) -> PyResult<Bound<'py, PyDict>> {
-        let result = self.inner.acquire_lease(
-            agent_id,
-            session_id,
-            resource_type,
-            resource_path,
-            predicate,
-            ttl,
-        );
-
-        lease_result_to_dict(py, result, resource_type, resource_path)
+        match &self.backend {
+            Backend::Embedded(inner) => {
+                let result = py.allow_threads(|| {
+                    lock(inner).acquire_lease(
+                        agent_id,
+                        session_id.unwrap_or_default(),
+                        resource_type,
+                        resource_path,
+                        predicate,
+                        ttl,
+                    )
+                });
+                lease_result_to_dict(py, result, resource_type, resource_path)
+            }
+            Backend::Remote(http) => http.acquire_lease(
+                py,
+                agent_id,
+                session_id,
+                resource_type,
+                resource_path,
+                predicate,
+                ttl,
+            ),
+        }
     }
 
     /// Release a lease by its ID.
-    pub fn release_lease(&mut self, lease_id: &str) -> bool {
-        self.inner.release_lease(lease_id)
+    pub fn release_lease(&self, py: Python<'_>, lease_id: &str) -> PyResult<bool> {
+        match &self.backend {
+            Backend::Embedded(inner) => {
+                Ok(py.allow_threads(|| lock(inner).release_lease(lease_id)))
+            }
+            Backend::Remote(http) => http.release_lease(py, lease_id),
+        }
+    }
+
+    /// Renew a lease. Returns a dict with the lease as it stands afterwards:
+    /// 'renewed', 'state', 'remaining_ttl', 'renewals' and more. Passing
+    /// `client_time` (ms since the epoch) reports clock drift.
+    #[pyo3(signature = (lease_id, client_time=None))]
+    pub fn heartbeat_lease<'py>(
+        &self,
+        py: Python<'py>,
+        lease_id: &str,
+        client_time: Option<u64>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let report = match &self.backend {
+            Backend::Embedded(inner) => py
+                .allow_threads(|| lock(inner).heartbeat_lease_report(lease_id, None, client_time))
+                .map_err(|e| PyRuntimeError::new_err(e.to_string()))?,
+            Backend::Remote(http) => http.heartbeat_report(py, lease_id, client_time)?,
+        };
+        heartbeat_report_to_dict(py, &report)
+    }
+
+    /// Release a batch of leases only if all of them can be released and
+    /// every condition holds; otherwise release none. `conditions` is a
+    /// list of dicts: {'condition': 'revision', 'lease_id', 'revision'} or
+    /// {'condition': 'no_conflicting_lease', 'lease_id'}. Returns a dict
+    /// with 'released' and 'results', one dict per lease with 'lease_id',
+    /// 'status' and, for a lease that held the batch back, 'code'.
+    #[pyo3(signature = (lease_ids, conditions=None))]
+    pub fn release_many_checked<'py>(
+        &self,
+        py: Python<'py>,
+        lease_ids: Vec<String>,
+        conditions: Option<Vec<Bound<'py, PyDict>>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let conditions = conditions
+            .unwrap_or_default()
+            .iter()
+            .map(release_condition_from_dict)
+            .collect::<PyResult<Vec<_>>>()?;
+        let batch = match &self.backend {
+            Backend::Embedded(inner) => py
+                .allow_threads(|| lock(inner).release_many_checked(&lease_ids, &conditions))
+                .map_err(PyValueError::new_err)?,
+            Backend::Remote(http) => http.release_many_checked(py, &lease_ids, &conditions)?,
+        };
+        batch_release_to_dict(py, &batch)
     }
 
     /// Get the number of currently active leases.
-    pub fn active_lease_count(&self) -> usize {
-        self.inner.get_active_leases().len()
+    pub fn active_lease_count(&self, py: Python<'_>) -> PyResult<usize> {
+        match &self.backend {
+            Backend::Embedded(inner) => {
+                Ok(py.allow_threads(|| lock(inner).get_active_leases().len()))
+            }
+            Backend::Remote(http) => Ok(http.list_leases(py)?.len()),
+        }
     }
 
     /// Evict expired leases. Returns number evicted.
-    pub fn evict_expired(&mut self) -> usize {
-        self.inner.evict_expired()
+    pub fn evict_expired(&self, py: Python<'_>) -> PyResult<usize> {
+        match &self.backend {
+            Backend::Embedded(inner) => Ok(py.allow_threads(|| lock(inner).evict_expired())),
+            Backend::Remote(http) => {
+                let response = http.call(py, "POST", "/evict", None)?;
+                response
+                    .pointer("/data/evicted")
+                    .and_then(Value::as_u64)
+                    .map(|evicted| evicted as usize)
+                    .ok_or_else(|| PyRuntimeError::new_err(extract_error(&response)))
+            }
+        }
+    }
+
+    /// Create a barrier that trips once `parties` distinct agents arrive.
+    /// Returns the barrier status dict.
+    pub fn create_barrier<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+        parties: usize,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let status = match &self.backend {
+            Backend::Embedded(inner) => py
+                .allow_threads(|| lock(inner).create_barrier(name, parties))
+                .map_err(PyRuntimeError::new_err)?,
+            Backend::Remote(http) => http.barrier(
+                py,
+                "POST",
+                "/barriers".to_string(),
+                Some(json!({ "name": name, "parties": parties })),
+            )?,
+        };
+        barrier_to_dict(py, &status)
+    }
+
+    /// Record that `agent_id` reached the barrier. The returned dict has
+    /// `tripped` set once every party has arrived.
+    pub fn arrive<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+        agent_id: &str,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let status = match &self.backend {
+            Backend::Embedded(inner) => py
+                .allow_threads(|| lock(inner).arrive(name, agent_id))
+                .map_err(PyRuntimeError::new_err)?,
+            Backend::Remote(http) => http.barrier(
+                py,
+                "POST",
+                format!("/barriers/{}/arrive", name),
+                Some(json!({ "agent_id": agent_id })),
+            )?,
+        };
+        barrier_to_dict(py, &status)
+    }
+
+    /// Current status of a barrier, or None if it does not exist.
+    pub fn barrier_status<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+    ) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let status = match &self.backend {
+            Backend::Embedded(inner) => py.allow_threads(|| lock(inner).barrier_status(name)),
+            Backend::Remote(http) => http.barrier_status(py, name)?,
+        };
+        status
+            .map(|status| barrier_to_dict(py, &status))
+            .transpose()
+    }
+
+    /// Block (with the GIL released) until the barrier trips or `timeout_ms`
+    /// elapses, then return its status. Check `tripped` to tell them apart.
+    #[pyo3(signature = (name, timeout_ms=30000))]
+    pub fn wait_barrier<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+        timeout_ms: u64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let status = match &self.backend {
+            Backend::Embedded(inner) => py
+                .allow_threads(|| {
+                    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+                    loop {
+                        let status = lock(inner)
+                            .barrier_status(name)
+                            .ok_or_else(|| format!("Barrier '{}' not found", name))?;
+                        if status.tripped || Instant::now() >= deadline {
+                            return Ok::<_, String>(status);
+                        }
+                        sleep(Duration::from_millis(20));
+                    }
+                })
+                .map_err(PyRuntimeError::new_err)?,
+            Backend::Remote(http) => {
+                // Each long-poll must finish inside the HTTP timeout
+                let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+                let chunk = Duration::from_millis((http.timeout_ms / 2).max(1));
+                loop {
+                    let wait = deadline
+                        .saturating_duration_since(Instant::now())
+                        .min(chunk);
+                    let status = http.barrier(
+                        py,
+                        "GET",
+                        format!("/barriers/{}/wait?timeout_ms={}", name, wait.as_millis()),
+                        None,
+                    )?;
+                    if status.tripped || Instant::now() >= deadline {
+                        break status;
+                    }
+                }
+            }
+        };
+        barrier_to_dict(py, &status)
+    }
+
+    /// Take one permit of the semaphore `name`, declaring it with `permits`
+    /// total permits on first use. Returns a dict with 'success' and either
+    /// 'permit_id'/'expires_at' or 'reason'/'wait_time'.
+    pub fn acquire_semaphore<'py>(
+        &self,
+        py: Python<'py>,
+        agent_id: &str,
+        name: &str,
+        permits: usize,
+        ttl: u64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        match &self.backend {
+            Backend::Embedded(inner) => {
+                let result = py
+                    .allow_threads(|| lock(inner).acquire_semaphore(agent_id, name, permits, ttl))
+                    .map_err(PyRuntimeError::new_err)?;
+                semaphore_result_to_dict(py, result)
+            }
+            Backend::Remote(http) => {
+                let response = http.call(
+                    py,
+                    "POST",
+                    &format!("/semaphores/{}/acquire", name),
+                    Some(json!({ "agent_id": agent_id, "permits": permits, "ttl": ttl })),
+                )?;
+                let dict = PyDict::new(py);
+                match response.get("success").and_then(Value::as_bool) {
+                    Some(true) => {
+                        dict.set_item("success", true)?;
+                        dict.set_item("permit_id", value_as_str(response.pointer("/data/id"))?)?;
+                        dict.set_item(
+                            "expires_at",
+                            response.pointer("/data/expires_at").and_then(Value::as_u64),
+                        )?;
+                    }
+                    _ if response.get("reason").is_some() => {
+                        dict.set_item("success", false)?;
+                        dict.set_item("reason", value_as_str(response.get("reason"))?)?;
+                        dict.set_item("code", response.get("code").and_then(Value::as_str))?;
+                        dict.set_item(
+                            "wait_time",
+                            response.get("wait_time").and_then(Value::as_u64),
+                        )?;
+                    }
+                    _ => return Err(PyRuntimeError::new_err(extract_error(&response))),
+                }
+                Ok(dict)
+            }
+        }
+    }
+
+    /// Return a semaphore permit before its TTL elapses.
+    pub fn release_semaphore(&self, py: Python<'_>, permit_id: &str) -> PyResult<bool> {
+        match &self.backend {
+            Backend::Embedded(inner) => {
+                Ok(py.allow_threads(|| lock(inner).release_semaphore(permit_id)))
+            }
+            Backend::Remote(http) => {
+                let response = http.call(py, "DELETE", &format!("/permits/{}", permit_id), None)?;
+                Ok(response
+                    .get("success")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false))
+            }
+        }
+    }
+
+    /// Current holders of a semaphore, or None if it has none.
+    pub fn semaphore_status<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+    ) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let status = match &self.backend {
+            Backend::Embedded(inner) => py.allow_threads(|| lock(inner).semaphore_status(name)),
+            Backend::Remote(http) => {
+                let response = http.call(py, "GET", &format!("/semaphores/{}", name), None)?;
+                match response.get("data") {
+                    Some(data) => Some(serde_json::from_value(data.clone()).map_err(|err| {
+                        PyRuntimeError::new_err(format!(
+                            "Klock server returned a malformed semaphore: {}",
+                            err
+                        ))
+                    })?),
+                    None => None,
+                }
+            }
+        };
+        status
+            .map(|status| semaphore_status_to_dict(py, &status))
+            .transpose()
+    }
+
+    /// Pickle remote clients as a fresh connection to the same server.
+    /// Embedded clients hold process-local state and cannot be pickled.
+    pub fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyTuple>> {
+        let Backend::Remote(http) = &self.backend else {
+            return Err(PyTypeError::new_err(
+                "Embedded KlockClient state is local to this process; use KlockClient.connect(url) to share a server across processes",
+            ));
+        };
+        let connect = py.get_type::<Self>().getattr("connect")?;
+        let args = (http.base_url.clone(), http.api_key.clone(), http.timeout_ms);
+        PyTuple::new(py, [connect.into_any(), args.into_pyobject(py)?.into_any()])
     }
 }
 
@@ -136,13 +517,22 @@ impl KlockHttpClient {
     }
 
     /// Register an agent against the Klock server.
-    pub fn register_agent(&self, agent_id: &str, priority: u64) -> PyResult<()> {
-        let response = self.request_json(
+    #[pyo3(signature = (agent_id, priority, priority_class = "normal"))]
+    pub fn register_agent(
+        &self,
+        py: Python<'_>,
+        agent_id: &str,
+        priority: u64,
+        priority_class: &str,
+    ) -> PyResult<()> {
+        let response = self.call(
+            py,
             "POST",
             "/agents",
             Some(json!({
                 "agent_id": agent_id,
                 "priority": priority,
+                "class": priority_class,
             })),
         )?;
 
@@ -157,23 +547,25 @@ impl KlockHttpClient {
         }
     }
 
-    /// Acquire a lease from the Klock server.
+    /// Acquire a lease from the Klock server. A `session_id` of None uses
+    /// the agent's default session.
     pub fn acquire_lease<'py>(
         &self,
         py: Python<'py>,
         agent_id: &str,
-        session_id: &str,
+        session_id: Option<&str>,
         resource_type: &str,
         resource_path: &str,
         predicate: &str,
         ttl: u64,
     ) -> PyResult<Bound<'py, PyDict>> {
-        let response = self.request_json(
+        let response = self.call(
+            py,
             "POST",
             "/leases",
             Some(json!({
                 "agent_id": agent_id,
-                "session_id": session_id,
+                "session_id": session_id.unwrap_or_default(),
                 "resource_type": resource_type,
                 "resource_path": resource_path,
                 "predicate": predicate,
@@ -197,6 +589,9 @@ impl KlockHttpClient {
             dict.set_item("success", true)?;
             dict.set_item("lease_id", value_as_str(data.get("lease_id"))?)?;
             dict.set_item("agent_id", value_as_str(data.get("agent_id"))?)?;
+            if let Some(session_id) = data.get("session_id").and_then(Value::as_str) {
+                dict.set_item("session_id", session_id)?;
+            }
             dict.set_item("resource", value_as_str(data.get("resource"))?)?;
 
             if let Some(predicate_value) = data.get("predicate").and_then(Value::as_str) {
@@ -218,6 +613,7 @@ impl KlockHttpClient {
                     .and_then(Value::as_str)
                     .unwrap_or("CONFLICT"),
             )?;
+            dict.set_item("code", response.get("code").and_then(Value::as_str))?;
             dict.set_item(
                 "wait_time",
                 response
@@ -225,13 +621,21 @@ impl KlockHttpClient {
                     .and_then(Value::as_u64)
                     .unwrap_or(1000),
             )?;
+            dict.set_item(
+                "queue_position",
+                response.get("queue_position").and_then(Value::as_u64),
+            )?;
+            dict.set_item(
+                "request_id",
+                response.get("request_id").and_then(Value::as_str),
+            )?;
             Ok(dict)
         }
     }
 
     /// Release a lease by its ID.
-    pub fn release_lease(&self, lease_id: &str) -> PyResult<bool> {
-        let response = self.request_json("DELETE", &format!("/leases/{}", lease_id), None)?;
+    pub fn release_lease(&self, py: Python<'_>, lease_id: &str) -> PyResult<bool> {
+        let response = self.call(py, "DELETE", &format!("/leases/{}", lease_id), None)?;
         Ok(response
             .get("success")
             .and_then(Value::as_bool)
@@ -239,9 +643,8 @@ impl KlockHttpClient {
     }
 
     /// Renew a lease heartbeat.
-    pub fn heartbeat_lease(&self, lease_id: &str) -> PyResult<bool> {
-        let response =
-            self.request_json("POST", &format!("/leases/{}/heartbeat", lease_id), None)?;
+    pub fn heartbeat_lease(&self, py: Python<'_>, lease_id: &str) -> PyResult<bool> {
+        let response = self.call(py, "POST", &format!("/leases/{}/heartbeat", lease_id), None)?;
         Ok(response
             .get("success")
             .and_then(Value::as_bool)
@@ -250,7 +653,7 @@ impl KlockHttpClient {
 
     /// List currently active leases.
     pub fn list_leases<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
-        let response = self.request_json("GET", "/leases", None)?;
+        let response = self.call(py, "GET", "/leases", None)?;
         if !response
             .get("success")
             .and_then(Value::as_bool)
@@ -298,7 +701,369 @@ impl KlockHttpClient {
     }
 }
 
+#[pymethods]
+impl KlockHybridClient {
+    /// Replicate the leases of the server at `url` and start reconciling
+    /// every `sync_interval_ms`. `optimistic` lists the predicates granted
+    /// locally (CONSUMES by default). The server must keep a firehose.
+    #[new]
+    #[pyo3(signature = (url, api_key = None, timeout_ms = 5000, sync_interval_ms = 100, optimistic = None))]
+    pub fn new(
+        py: Python<'_>,
+        url: String,
+        api_key: Option<String>,
+        timeout_ms: u64,
+        sync_interval_ms: u64,
+        optimistic: Option<Vec<String>>,
+    ) -> PyResult<Self> {
+        let policy = match optimistic {
+            Some(predicates) => HybridPolicy {
+                optimistic: predicates.iter().map(|p| parse_predicate(p)).collect(),
+            },
+            None => HybridPolicy::default(),
+        };
+        let shared = Arc::new(HybridShared {
+            http: KlockHttpClient::new(url, api_key, timeout_ms, false, 0, None),
+            replica: Mutex::new(LocalReplica::new(policy)),
+            stop: AtomicBool::new(false),
+        });
+        py.allow_threads(|| shared.resync())?;
+
+        let worker = {
+            let shared = shared.clone();
+            let interval = Duration::from_millis(sync_interval_ms.max(1));
+            std::thread::spawn(move || {
+                while !shared.stop.load(Ordering::Relaxed) {
+                    // Unreachable servers are retried on the next pass
+                    let _ = shared.sync_once();
+                    sleep(interval);
+                }
+            })
+        };
+        Ok(Self {
+            shared,
+            worker: Mutex::new(Some(worker)),
+        })
+    }
+
+    /// Acquire a lease. Optimistic predicates that conflict with nothing
+    /// the replica knows of are granted at once with a provisional
+    /// `lease_id` and `provisional` set; the rest go to the server.
+    pub fn acquire_lease<'py>(
+        &self,
+        py: Python<'py>,
+        agent_id: &str,
+        session_id: Option<&str>,
+        resource_type: &str,
+        resource_path: &str,
+        predicate: &str,
+        ttl: u64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let resource = ResourceRef::new(parse_resource_type(resource_type), resource_path);
+        let decision = self.shared.replica().decide(
+            agent_id,
+            session_id.unwrap_or_default(),
+            resource,
+            parse_predicate(predicate),
+            ttl,
+            now_ms(),
+        );
+        let HybridDecision::Optimistic(lease) = decision else {
+            return self.shared.http.acquire_lease(
+                py,
+                agent_id,
+                session_id,
+                resource_type,
+                resource_path,
+                predicate,
+                ttl,
+            );
+        };
+        let dict = PyDict::new(py);
+        dict.set_item("success", true)?;
+        dict.set_item("lease_id", &lease.id)?;
+        dict.set_item("agent_id", &lease.agent_id)?;
+        dict.set_item("session_id", &lease.session_id)?;
+        dict.set_item("resource", lease.resource.key())?;
+        dict.set_item("predicate", predicate.to_uppercase())?;
+        dict.set_item("expires_at", lease.granted_at + lease.ttl)?;
+        dict.set_item("provisional", true)?;
+        Ok(dict)
+    }
+
+    /// Release a lease, provisional or not. A provisional lease still in
+    /// flight is released on the server once it is confirmed.
+    pub fn release_lease(&self, py: Python<'_>, lease_id: &str) -> PyResult<bool> {
+        match self.shared.replica().release(lease_id) {
+            Some(server_lease_id) => self.shared.http.release_lease(py, &server_lease_id),
+            None => Ok(true),
+        }
+    }
+
+    /// Stream the firehose and reconcile pending grants now, instead of
+    /// waiting for the background pass.
+    pub fn sync(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| self.shared.sync_once())
+    }
+
+    /// Whether a provisional lease still awaits the server's answer
+    pub fn is_provisional(&self, lease_id: &str) -> bool {
+        self.shared.replica().is_provisional(lease_id)
+    }
+
+    /// The server's ID for a confirmed provisional lease
+    pub fn server_lease_id(&self, lease_id: &str) -> Option<String> {
+        self.shared
+            .replica()
+            .server_lease_id(lease_id)
+            .map(str::to_string)
+    }
+
+    /// Revocations of provisional leases the server refused since the last
+    /// call, as `lease_revoked` event dicts.
+    pub fn poll_events<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let events = self.shared.replica().take_events();
+        let json = py.import("json")?;
+        let list = PyList::empty(py);
+        for event in events {
+            let text = serde_json::to_string(&event)
+                .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+            list.append(json.call_method1("loads", (text,))?)?;
+        }
+        Ok(list)
+    }
+
+    /// Stop background reconciliation. Grants not yet confirmed stay
+    /// provisional until `sync` is called.
+    pub fn close(&self, py: Python<'_>) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        let worker = self.worker.lock().unwrap().take();
+        if let Some(worker) = worker {
+            py.allow_threads(|| {
+                let _ = worker.join();
+            });
+        }
+    }
+}
+
+impl Drop for KlockHybridClient {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl HybridShared {
+    fn replica(&self) -> std::sync::MutexGuard<'_, LocalReplica> {
+        self.replica.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The `data` of a successful GET, decoded
+    fn get_data<T: serde::de::DeserializeOwned>(&self, path: &str) -> PyResult<T> {
+        let response = self.http.request_json("GET", path, None)?;
+        match response.get("data") {
+            Some(data) if response.get("success").and_then(Value::as_bool) == Some(true) => {
+                serde_json::from_value(data.clone()).map_err(|err| {
+                    PyRuntimeError::new_err(format!(
+                        "Klock server returned a malformed response to {}: {}",
+                        path, err
+                    ))
+                })
+            }
+            _ => Err(PyRuntimeError::new_err(extract_error(&response))),
+        }
+    }
+
+    /// Seed the replica from the server's active leases. The firehose is
+    /// read to its end first, so the snapshot covers everything before the
+    /// cursor.
+    fn resync(&self) -> PyResult<()> {
+        let mut cursor = 0;
+        let epoch = loop {
+            let page: FirehosePage =
+                self.get_data(&format!("/firehose?cursor={}&limit=5000", cursor))?;
+            cursor = page.next_cursor;
+            if page.entries.is_empty() {
+                break page.epoch;
+            }
+        };
+        let projection: StateProjection =
+            self.get_data(&format!("/state/projection?at={}", now_ms()))?;
+        self.replica().seed(epoch, cursor, projection.active);
+        Ok(())
+    }
+
+    /// Catch up with the firehose, then send provisional leases to the
+    /// server and record its answers.
+    fn sync_once(&self) -> PyResult<()> {
+        loop {
+            let cursor = self.replica().cursor();
+            let page: FirehosePage =
+                self.get_data(&format!("/firehose?cursor={}&limit=500", cursor))?;
+            if self.replica().apply_page(&page).is_err() {
+                self.resync()?;
+                break;
+            }
+            if page.entries.is_empty() {
+                break;
+            }
+        }
+
+        let unsent = self.replica().take_unsent();
+        for lease in unsent {
+            let payload = json!({
+                "agent_id": lease.agent_id,
+                "session_id": lease.session_id,
+                "resource_type": lease.resource.resource_type.to_string(),
+                "resource_path": lease.resource.path,
+                "predicate": predicate_name(lease.predicate),
+                "ttl": lease.ttl,
+            });
+            let response = match self.http.request_json("POST", "/leases", Some(payload)) {
+                Ok(response) => response,
+                Err(_) => {
+                    // Unconfirmed grants protect nothing
+                    self.replica().refuse(
+                        &lease.id,
+                        "the server could not be reached",
+                        None,
+                        now_ms(),
+                    );
+                    continue;
+                }
+            };
+            let granted = response.get("success").and_then(Value::as_bool) == Some(true);
+            match response.pointer("/data/lease_id").and_then(Value::as_str) {
+                Some(lease_id) if granted => {
+                    let release = self.replica().confirm(&lease.id, lease_id);
+                    if let Some(lease_id) = release {
+                        self.http
+                            .request_json("DELETE", &format!("/leases/{}", lease_id), None)?;
+                    }
+                }
+                _ => {
+                    // Don't leave a waiter queued for a grant nobody waits on
+                    if let Some(request_id) = response.get("request_id").and_then(Value::as_str) {
+                        self.http.request_json(
+                            "DELETE",
+                            &format!("/queue/{}", request_id),
+                            None,
+                        )?;
+                    }
+                    let holder = response
+                        .pointer("/holders/0/agent_id")
+                        .and_then(Value::as_str)
+                        .map(str::to_string);
+                    let reason = extract_error(&response);
+                    self.replica().refuse(&lease.id, &reason, holder, now_ms());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl KlockHttpClient {
+    /// `request_json` with the GIL released, so other Python threads keep
+    /// running while the request is in flight.
+    fn call(
+        &self,
+        py: Python<'_>,
+        method: &str,
+        path: &str,
+        payload: Option<Value>,
+    ) -> PyResult<Value> {
+        py.allow_threads(|| self.request_json(method, path, payload))
+    }
+
+    /// Call a barrier endpoint and decode the status it returns.
+    fn barrier(
+        &self,
+        py: Python<'_>,
+        method: &str,
+        path: String,
+        payload: Option<Value>,
+    ) -> PyResult<BarrierStatus> {
+        let response = self.call(py, method, &path, payload)?;
+        match response.get("data") {
+            Some(data) if response.get("success").and_then(Value::as_bool) == Some(true) => {
+                serde_json::from_value(data.clone()).map_err(|err| {
+                    PyRuntimeError::new_err(format!(
+                        "Klock server returned a malformed barrier: {}",
+                        err
+                    ))
+                })
+            }
+            _ => Err(PyRuntimeError::new_err(extract_error(&response))),
+        }
+    }
+
+    /// Heartbeat a lease and decode the report the server returns.
+    fn heartbeat_report(
+        &self,
+        py: Python<'_>,
+        lease_id: &str,
+        client_time: Option<u64>,
+    ) -> PyResult<HeartbeatReport> {
+        let path = match client_time {
+            Some(t) => format!("/leases/{}/heartbeat?client_time={}", lease_id, t),
+            None => format!("/leases/{}/heartbeat", lease_id),
+        };
+        let response = self.call(py, "POST", &path, None)?;
+        match response.get("data") {
+            Some(data) => serde_json::from_value(data.clone()).map_err(|err| {
+                PyRuntimeError::new_err(format!(
+                    "Klock server returned a malformed heartbeat report: {}",
+                    err
+                ))
+            }),
+            // The server has never heard of the lease
+            None if response.get("code").and_then(Value::as_str)
+                == Some(ReasonCode::LeaseNotFound.code()) =>
+            {
+                Ok(HeartbeatReport::new(lease_id, None, false, 0, None))
+            }
+            None => Err(PyRuntimeError::new_err(extract_error(&response))),
+        }
+    }
+
+    /// Release a batch of leases with a checked release. A batch held back
+    /// by one of its leases still comes back, with a 409.
+    fn release_many_checked(
+        &self,
+        py: Python<'_>,
+        lease_ids: &[String],
+        conditions: &[ReleaseCondition],
+    ) -> PyResult<BatchRelease> {
+        let payload = json!({ "lease_ids": lease_ids, "conditions": conditions });
+        let response = self.call(py, "POST", "/leases/release", Some(payload))?;
+        match response.get("data") {
+            Some(data) => serde_json::from_value(data.clone()).map_err(|err| {
+                PyRuntimeError::new_err(format!(
+                    "Klock server returned a malformed batch release: {}",
+                    err
+                ))
+            }),
+            None => Err(PyValueError::new_err(extract_error(&response))),
+        }
+    }
+
+    fn barrier_status(&self, py: Python<'_>, name: &str) -> PyResult<Option<BarrierStatus>> {
+        // Status is a plain GET: any failure other than transport means the
+        // barrier is unknown
+        let response = self.call(py, "GET", &format!("/barriers/{}", name), None)?;
+        match response.get("data") {
+            Some(data) => serde_json::from_value(data.clone())
+                .map(Some)
+                .map_err(|err| {
+                    PyRuntimeError::new_err(format!(
+                        "Klock server returned a malformed barrier: {}",
+                        err
+                    ))
+                }),
+            None => Ok(None),
+        }
+    }
+
     fn request_json(&self, method: &str, path: &str, payload: Option<Value>) -> PyResult<Value> {
         if path != "/health" {
             self.ensure_server()?;
@@ -448,7 +1213,7 @@ impl KlockHttpClient {
         )))
     }
 
-    fn health_check(&self) -> Result<(), ureq::Error> {
+    fn health_check(&self) -> Result<(), Box<ureq::Error>> {
         let agent = ureq::AgentBuilder::new()
             .timeout(Duration::from_millis(self.timeout_ms))
             .build();
@@ -463,11 +1228,38 @@ impl KlockHttpClient {
 
         match request.call() {
             Ok(_) => Ok(()),
-            Err(err) => Err(err),
+            Err(err) => Err(Box::new(err)),
         }
     }
 }
 
+/// Lock the embedded client, recovering it if another thread panicked
+/// mid-operation.
+/// The name `parse_predicate` reads back
+fn predicate_name(predicate: Predicate) -> &'static str {
+    match predicate {
+        Predicate::Provides => "PROVIDES",
+        Predicate::Consumes => "CONSUMES",
+        Predicate::Mutates => "MUTATES",
+        Predicate::Deletes => "DELETES",
+        Predicate::DependsOn => "DEPENDS_ON",
+        Predicate::Renames => "RENAMES",
+        Predicate::Excludes => "EXCLUDES",
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+fn lock(inner: &Mutex<RustClient>) -> std::sync::MutexGuard<'_, RustClient> {
+    inner
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
 fn default_server_command() -> Vec<String> {
     if let Ok(command) = std::env::var("KLOCK_SERVER_COMMAND") {
         let parts: Vec<String> = command
@@ -571,28 +1363,153 @@ fn lease_result_to_dict<'py>(
             dict.set_item("success", true)?;
             dict.set_item("lease_id", &lease.id)?;
             dict.set_item("agent_id", &lease.agent_id)?;
+            dict.set_item("session_id", &lease.session_id)?;
             dict.set_item("resource", format!("{}:{}", resource_type, resource_path))?;
             dict.set_item("expires_at", lease.expires_at)?;
         }
         RustLeaseResult::Failure {
-            reason, wait_time, ..
+            reason,
+            wait_time,
+            queue_position,
+            request_id,
+            ..
         } => {
-            let reason_str = match reason {
-                LeaseFailureReason::Wait => "WAIT",
-                LeaseFailureReason::Die => "DIE",
-                LeaseFailureReason::Conflict => "CONFLICT",
-                LeaseFailureReason::ResourceLocked => "RESOURCE_LOCKED",
-                LeaseFailureReason::SessionExpired => "SESSION_EXPIRED",
-            };
+            let reason_str = reason.as_str();
             dict.set_item("success", false)?;
             dict.set_item("reason", reason_str)?;
+            dict.set_item("code", reason.code().code())?;
             dict.set_item("wait_time", wait_time)?;
+            dict.set_item("queue_position", queue_position)?;
+            dict.set_item("request_id", request_id)?;
         }
     }
 
     Ok(dict)
 }
 
+fn semaphore_result_to_dict<'py>(
+    py: Python<'py>,
+    result: SemaphoreResult,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    match result {
+        SemaphoreResult::Success { permit } => {
+            dict.set_item("success", true)?;
+            dict.set_item("permit_id", &permit.id)?;
+            dict.set_item("expires_at", permit.expires_at)?;
+        }
+        SemaphoreResult::Failure { reason, wait_time } => {
+            dict.set_item("success", false)?;
+            dict.set_item("reason", reason.as_str())?;
+            dict.set_item("code", reason.code().code())?;
+            dict.set_item("wait_time", wait_time)?;
+        }
+    }
+    Ok(dict)
+}
+
+fn semaphore_status_to_dict<'py>(
+    py: Python<'py>,
+    status: &SemaphoreStatus,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("name", &status.name)?;
+    dict.set_item("permits", status.permits)?;
+    dict.set_item("available", status.available)?;
+    let holders = PyList::empty(py);
+    for permit in &status.holders {
+        let holder = PyDict::new(py);
+        holder.set_item("permit_id", &permit.id)?;
+        holder.set_item("agent_id", &permit.agent_id)?;
+        holder.set_item("acquired_at", permit.acquired_at)?;
+        holder.set_item("expires_at", permit.expires_at)?;
+        holders.append(holder)?;
+    }
+    dict.set_item("holders", holders)?;
+    Ok(dict)
+}
+
+fn barrier_to_dict<'py>(py: Python<'py>, status: &BarrierStatus) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("name", &status.name)?;
+    dict.set_item("parties", status.parties)?;
+    dict.set_item("arrived", &status.arrived)?;
+    dict.set_item("waiting_for", status.waiting_for)?;
+    dict.set_item("tripped", status.tripped)?;
+    dict.set_item("created_at", status.created_at)?;
+    dict.set_item("tripped_at", status.tripped_at)?;
+    Ok(dict)
+}
+
+fn heartbeat_report_to_dict<'py>(
+    py: Python<'py>,
+    report: &HeartbeatReport,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("lease_id", &report.lease_id)?;
+    dict.set_item("renewed", report.renewed)?;
+    dict.set_item("state", report.state.map(|state| format!("{:?}", state)))?;
+    dict.set_item("remaining_ttl", report.remaining_ttl)?;
+    dict.set_item("expires_at", report.expires_at)?;
+    dict.set_item("renewals", report.renewals)?;
+    dict.set_item("revision", report.revision)?;
+    dict.set_item("revoked_reason", &report.revoked_reason)?;
+    dict.set_item("clock_drift_ms", report.clock_drift_ms)?;
+    dict.set_item("drift_warning", &report.drift_warning)?;
+    Ok(dict)
+}
+
+fn release_condition_from_dict(dict: &Bound<'_, PyDict>) -> PyResult<ReleaseCondition> {
+    let field = |name: &str| -> PyResult<Bound<'_, PyAny>> {
+        dict.get_item(name)?
+            .ok_or_else(|| PyValueError::new_err(format!("Release condition needs '{}'", name)))
+    };
+    let lease_id: String = field("lease_id")?.extract()?;
+    let condition: String = field("condition")?.extract()?;
+    match condition.as_str() {
+        "revision" => Ok(ReleaseCondition::Revision {
+            lease_id,
+            revision: field("revision")?.extract()?,
+        }),
+        "no_conflicting_lease" => Ok(ReleaseCondition::NoConflictingLease { lease_id }),
+        other => Err(PyValueError::new_err(format!(
+            "Unknown release condition '{}': use 'revision' or 'no_conflicting_lease'",
+            other
+        ))),
+    }
+}
+
+fn batch_release_to_dict<'py>(
+    py: Python<'py>,
+    batch: &BatchRelease,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("released", batch.released)?;
+    let results = PyList::empty(py);
+    for result in &batch.results {
+        let entry = PyDict::new(py);
+        entry.set_item("lease_id", &result.lease_id)?;
+        let status = match &result.status {
+            ReleaseStatus::Released => "released",
+            ReleaseStatus::NotFound => "not_found",
+            ReleaseStatus::NotHeld { state } => {
+                entry.set_item("state", format!("{:?}", state))?;
+                "not_held"
+            }
+            ReleaseStatus::ConditionFailed { reason } => {
+                entry.set_item("reason", reason)?;
+                "condition_failed"
+            }
+            ReleaseStatus::Aborted => "aborted",
+        };
+        entry.set_item("status", status)?;
+        entry.set_item("code", result.status.code().map(|code| code.code()))?;
+        results.append(entry)?;
+    }
+    dict.set_item("results", results)?;
+    Ok(dict)
+}
+
 fn extract_error(response: &Value) -> String {
     response
         .get("error")
@@ -609,9 +1526,27 @@ fn value_as_str(value: Option<&Value>) -> PyResult<&str> {
 }
 
 /// The Klock Python module.
+/// The reason-code catalog: a list of dicts with 'code', 'name',
+/// 'summary' and 'remedy', in code order.
+#[pyfunction]
+fn codes(py: Python<'_>) -> PyResult<Bound<'_, PyList>> {
+    let list = PyList::empty(py);
+    for info in ::klock_core::codes::codes() {
+        let dict = PyDict::new(py);
+        dict.set_item("code", info.code)?;
+        dict.set_item("name", info.name)?;
+        dict.set_item("summary", info.summary)?;
+        dict.set_item("remedy", info.remedy)?;
+        list.append(dict)?;
+    }
+    Ok(list)
+}
+
 #[pymodule]
 fn klock(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<KlockClient>()?;
     m.add_class::<KlockHttpClient>()?;
+    m.add_class::<KlockHybridClient>()?;
+    m.add_function(wrap_pyfunction!(codes, m)?)?;
     Ok(())
 }